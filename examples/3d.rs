@@ -1,14 +1,95 @@
-use cgmath::{Deg, Quaternion, Rotation, Rotation3, Vector3};
+use cgmath::{InnerSpace, Vector2, Vector3};
 use eureka::core::App;
-use eureka::render::Texture;
-use eureka::scene::{AsNode3d, Camera3d, DirectionalLight, Model, PointLight, Sky, Sprite2d};
+use eureka::math::color::ColorU;
+use eureka::math::csg::Csg;
+use eureka::math::types::{Quat, Vec3};
+use eureka::render::BillboardMode;
+use eureka::render::{CustomVertex, Mesh, VertexLayout, VertexLayoutBuilder};
+use eureka::render::{RenderSettings, Texture};
+use eureka::scene::{
+    AsNode3d, Camera3d, DirectionalLight, Label3d, Model, PointLight, Sky, Sprite2d, Sprite3d,
+    Trail3d, TransformGizmo, WaterPlane,
+};
+use std::f32::consts::PI;
+
+/// A vertex type of our own, in a different order than the engine's and with extra data.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaveVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    uv: [f32; 2],
+    /// Not read by the standard shader.
+    phase: f32,
+    tangent: [f32; 3],
+    bi_tangent: [f32; 3],
+}
+
+impl CustomVertex for WaveVertex {
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new::<WaveVertex>()
+            .attribute(0, wgpu::VertexFormat::Float32x3)
+            .attribute(2, wgpu::VertexFormat::Float32x3)
+            .attribute(1, wgpu::VertexFormat::Float32x2)
+            .skip(4)
+            .attribute(3, wgpu::VertexFormat::Float32x3)
+            .attribute(4, wgpu::VertexFormat::Float32x3)
+            .build()
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
+/// A wavy sheet of `n` by `n` quads.
+fn wave_mesh(device: &wgpu::Device, n: u32) -> Mesh {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    for z in 0..=n {
+        for x in 0..=n {
+            let (u, v) = (x as f32 / n as f32, z as f32 / n as f32);
+            let phase = u * std::f32::consts::TAU * 2.0;
+            let slope = 0.2 * std::f32::consts::TAU * 2.0 * phase.cos() / 2.0;
+
+            let tangent = Vector3::new(1.0, slope, 0.0).normalize();
+            let bi_tangent = Vector3::new(0.0, 0.0, 1.0);
+
+            vertices.push(WaveVertex {
+                position: [u * 2.0 - 1.0, 0.2 * phase.sin(), v * 2.0 - 1.0],
+                normal: bi_tangent.cross(tangent).into(),
+                uv: [u, v],
+                phase,
+                tangent: tangent.into(),
+                bi_tangent: bi_tangent.into(),
+            });
+        }
+    }
+
+    for z in 0..n {
+        for x in 0..n {
+            let i = z * (n + 1) + x;
+            indices.extend_from_slice(&[i, i + n + 1, i + 1, i + 1, i + n + 1, i + n + 2]);
+        }
+    }
+
+    Mesh::from_vertices(device, "wave", &vertices, indices)
+}
 
 // fn custom_update(dt: f32, light: &mut PointLight) {
-//     light.set_position(Vector3::new(, 1.0, 0.0));
+//     light.set_position(Vec3::new(, 1.0, 0.0));
 // }
 
 fn main() {
-    let mut app = App::new();
+    // Reverse-Z keeps the ground from z-fighting at a distance.
+    // HDR lets emissive materials go brighter than white, so they bloom.
+    let mut app = App::with_render_settings(RenderSettings {
+        reverse_z: true,
+        hdr: true,
+        ..Default::default()
+    });
+    app.render_world.post_process_settings.bloom_intensity = 1.0;
 
     let camera3d = Camera3d::new(
         (-10.0, 0.0, 0.0),
@@ -32,9 +113,31 @@ fn main() {
 
     // Light 1.
     let mut light = PointLight::new();
-    light.set_position(Vector3::new(4.0, 5.0, 0.0));
+    light.set_position(Vec3::new(4.0, 5.0, 0.0));
     light.strength = 5.0;
     app.add_node(light, None);
+
+    // Light icon.
+    let light_icon_tex = Texture::load(
+        &app.singletons.render_server.device,
+        &app.singletons.render_server.queue,
+        &mut app.render_world.texture_cache,
+        &app.singletons
+            .asset_server
+            .asset_dir
+            .join("images/light.png"),
+    )
+    .unwrap();
+    let mut light_icon = Sprite3d::new(light_icon_tex);
+    light_icon.set_position(Vec3::new(4.0, 5.0, 0.0));
+    light_icon.billboard_mode = BillboardMode::Enabled;
+    light_icon.alpha_cut = Some(0.5);
+    app.add_node(light_icon, None);
+
+    let mut light_label = Label3d::new("Point light".to_string());
+    light_label.set_position(Vec3::new(4.0, 5.6, 0.0));
+    light_label.billboard_mode = BillboardMode::Enabled;
+    app.add_node(light_label, None);
     //
     // // Light 2.
     // let mut light = PointLight::new();
@@ -42,17 +145,18 @@ fn main() {
     // light.strength = 2.0;
     // app.add_node(light, None);
 
-    // Light 3.
-    // let mut light = DirectionalLight::new();
-    // // light.transform.rotation = Quaternion::from_angle_x(Deg(180.0));
-    // light.strength = 2.0;
-    // app.add_node(light, None);
+    // Light 3, the sun. Casts cascaded shadows.
+    let mut light = DirectionalLight::new();
+    light.transform.position = Vector3::new(1.0, 2.0, 0.5);
+    light.strength = 0.6;
+    app.add_node(light, None);
 
     // Model1.
     let mut obj_model = Model::load(
         &mut app.render_world.texture_cache,
         &mut app.render_world.mesh_render_resources.material_cache,
         &mut app.render_world.mesh_cache,
+        &mut app.singletons.asset_server.registry,
         &app.singletons.render_server,
         &app.singletons
             .asset_server
@@ -60,14 +164,33 @@ fn main() {
             .join("models/ferris/ferris3d_v1.0.obj"),
     )
     .unwrap();
-    obj_model.set_position(Vector3::new(0.0, 2.0, 0.0));
-    app.add_node(obj_model, None);
+    obj_model.set_position(Vec3::new(0.0, 2.0, 0.0));
+
+    // Make Ferris glow.
+    let material_cache = &mut app.render_world.mesh_render_resources.material_cache;
+    for material_id in obj_model.materials.iter().flatten() {
+        let material = material_cache.storage.get_mut(material_id).unwrap();
+        material.emissive_texture = material.color_texture;
+        material.emissive_color = ColorU::white();
+        material.emissive_strength = 3.0;
+    }
+
+    let obj_model_id = app.add_node(obj_model, None);
+
+    // Drag the handles with the left mouse button to move the model.
+    app.add_node(TransformGizmo::new(obj_model_id), None);
+
+    // Leaves a trail while being dragged.
+    let mut trail = Trail3d::new(obj_model_id);
+    trail.color = ColorU::new(255, 160, 0, 255);
+    app.add_node(trail, None);
 
     // Model 2.
     let mut obj_model2 = Model::load(
         &mut app.render_world.texture_cache,
         &mut app.render_world.mesh_render_resources.material_cache,
         &mut app.render_world.mesh_cache,
+        &mut app.singletons.asset_server.registry,
         &app.singletons.render_server,
         &app.singletons
             .asset_server
@@ -75,8 +198,10 @@ fn main() {
             .join("models/viking_room/viking_room.obj"),
     )
     .unwrap();
-    obj_model2.set_position(Vector3::new(5.0, 1.0, 0.0));
-    obj_model2.set_rotation(Quaternion::from_angle_z(Deg(180.0)));
+    obj_model2.set_position(Vec3::new(5.0, 1.0, 0.0));
+    obj_model2.set_rotation(Quat::from_axis_angle(Vec3::Z, PI));
+    obj_model2.debug_aabb = true;
+    obj_model2.debug_normals = true;
     app.add_node(obj_model2, None);
 
     // Model 3.
@@ -84,6 +209,7 @@ fn main() {
         &mut app.render_world.texture_cache,
         &mut app.render_world.mesh_render_resources.material_cache,
         &mut app.render_world.mesh_cache,
+        &mut app.singletons.asset_server.registry,
         &app.singletons.render_server,
         &app.singletons
             .asset_server
@@ -91,8 +217,39 @@ fn main() {
             .join("models/granite_ground/granite_ground.obj"),
     )
     .unwrap();
-    obj_model3.set_scale(Vector3::new(5.0, 1.0, 5.0));
+    obj_model3.set_scale(Vec3::new(5.0, 1.0, 5.0));
     app.add_node(obj_model3, None);
 
+    // Boolean mesh: a box with a sphere carved out of it.
+    let block = Csg::cuboid(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0))
+        .subtract(&Csg::sphere(Vector3::new(0.0, 0.0, 0.0), 1.3, 24, 12));
+    let block_mesh = app
+        .render_world
+        .mesh_cache
+        .add(block.to_mesh(&app.singletons.render_server.device, "csg block"));
+    let mut block_model = Model::from_mesh(&app.render_world.mesh_cache, block_mesh, None);
+    block_model.set_position(Vec3::new(0.0, 1.0, 5.0));
+    app.add_node(block_model, None);
+
+    // A shallow pond around the block, which gets foam where it sticks out.
+    let mut pond = WaterPlane::new(Vector2::new(6.0, 6.0));
+    pond.set_position(Vec3::new(0.0, 0.4, 5.0));
+    app.add_node(pond, None);
+
+    // Procedural mesh with a custom vertex layout.
+    let wave_mesh = app
+        .render_world
+        .mesh_cache
+        .add(wave_mesh(&app.singletons.render_server.device, 32));
+    let mut wave_model = Model::from_mesh(&app.render_world.mesh_cache, wave_mesh, None);
+    wave_model.set_position(Vec3::new(-4.0, 1.0, 4.0));
+    app.add_node(wave_model, None);
+
+    // Keeps the models grounded where shadow maps lose their contact with the floor.
+    app.render_world.shadow_settings.contact_shadows = true;
+
+    // Post effects.
+    app.render_world.post_process_settings.vignette_intensity = 0.5;
+
     app.run();
 }
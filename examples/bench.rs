@@ -0,0 +1,186 @@
+use cgmath::{Deg, Vector2, Vector3, Vector4};
+use eureka::core::{Benchmark, HeadlessApp};
+use eureka::math::csg::Csg;
+use eureka::math::types::Vec3;
+use eureka::render::{Texture, VectorTexture};
+use eureka::scene::{
+    AsNode3d, AsNodeUi, Camera2d, Camera3d, Label, Model, NodeId, PointLight, Sprite2d,
+    VectorSprite, World,
+};
+use std::fmt::Write;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+
+/// Stress test scenes, measured without a window. Prints where the results went.
+/// Usage: cargo run --release --example bench -- [--frames count] [--out bench.csv|bench.json]
+fn main() -> anyhow::Result<()> {
+    let mut frames = 300;
+    let mut out = "bench.csv".to_string();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => frames = args.next().unwrap_or_default().parse()?,
+            "--out" => out = args.next().unwrap_or(out),
+            _ => anyhow::bail!("Unknown argument {}", arg),
+        }
+    }
+
+    let mut benchmark = Benchmark::new(frames);
+
+    // One app for all scenes, since the GL backend doesn't support several.
+    let mut app = HeadlessApp::new(WIDTH, HEIGHT)?;
+
+    sprites(&mut app, 10_000)?;
+    benchmark.run("10k sprites", &mut app, |_, _| {});
+
+    let labels = labels(&mut app, 1_000);
+    benchmark.run("1k animated labels", &mut app, |app, frame| {
+        for (i, id) in labels.iter().enumerate() {
+            let label = app.get_world_mut().get_node_mut::<Label>(*id).unwrap();
+            label.set_text(format!("Label {} at frame {}", i, frame));
+        }
+    });
+
+    models(&mut app, 500);
+    benchmark.run("500 models", &mut app, |_, _| {});
+
+    giant_svg(&mut app, 20_000)?;
+    benchmark.run("giant SVG", &mut app, |_, _| {});
+
+    benchmark.save(&out)?;
+    println!("{}", out);
+
+    Ok(())
+}
+
+/// Small rotating sprites sharing a texture, in a grid over the view.
+fn sprites(app: &mut HeadlessApp, count: u32) -> anyhow::Result<()> {
+    app.change_scene(World::new(Vector2::new(0, 0)));
+    app.add_node(Camera2d::default(), None);
+
+    let texture = Texture::load(
+        &app.singletons.render_server.device,
+        &app.singletons.render_server.queue,
+        &mut app.render_world.texture_cache,
+        app.singletons
+            .asset_server
+            .asset_dir
+            .join("images/happy-tree.png"),
+    )?;
+
+    let columns = (count as f32).sqrt().ceil() as u32;
+
+    for i in 0..count {
+        let mut sprite = Sprite2d::new(&app.render_world.texture_cache, texture);
+        sprite.set_position(Vector2::new(
+            (i % columns) as f32 / columns as f32 * WIDTH as f32,
+            (i / columns) as f32 / columns as f32 * HEIGHT as f32,
+        ));
+        // A 16 pixel tile of the texture, like sprites from a sheet.
+        sprite.region = Vector4::new(0.46875, 0.46875, 0.0625, 0.0625);
+        sprite.custom_update = Some(|dt, sprite| sprite.set_rotation(sprite.get_rotation() + dt));
+        app.add_node(sprite, None);
+    }
+
+    Ok(())
+}
+
+/// Labels whose text is changed every frame by the benchmark.
+fn labels(app: &mut HeadlessApp, count: u32) -> Vec<NodeId> {
+    app.change_scene(World::new(Vector2::new(0, 0)));
+    app.add_node(Camera2d::default(), None);
+
+    let columns = 10;
+
+    (0..count)
+        .map(|i| {
+            let mut label = Label::default();
+            label.auto_translate = false;
+            label.set_position(Vector2::new(
+                (i % columns) as f32 * WIDTH as f32 / columns as f32,
+                (i / columns) as f32 * HEIGHT as f32 / (count / columns) as f32,
+            ));
+            app.add_node(label, None)
+        })
+        .collect()
+}
+
+/// Spheres with a mesh each, in a grid in front of the camera.
+fn models(app: &mut HeadlessApp, count: u32) {
+    app.change_scene(World::new(Vector2::new(0, 0)));
+
+    app.add_node(
+        Camera3d::new(
+            (0.0, 10.0, 30.0),
+            Deg(-90.0),
+            Deg(-20.0),
+            &app.singletons.render_server,
+        ),
+        None,
+    );
+
+    let mut light = PointLight::new();
+    light.set_position(Vec3::new(0.0, 20.0, 10.0));
+    light.strength = 5.0;
+    app.add_node(light, None);
+
+    let columns = (count as f32).sqrt().ceil() as u32;
+
+    for i in 0..count {
+        let sphere = Csg::sphere(Vector3::new(0.0, 0.0, 0.0), 0.4, 16, 8);
+        let mesh = app
+            .render_world
+            .mesh_cache
+            .add(sphere.to_mesh(&app.singletons.render_server.device, "bench sphere"));
+
+        let mut model = Model::from_mesh(&app.render_world.mesh_cache, mesh, None);
+        model.set_position(Vec3::new(
+            (i % columns) as f32 - columns as f32 * 0.5,
+            0.0,
+            -((i / columns) as f32),
+        ));
+        app.add_node(model, None);
+    }
+}
+
+/// One SVG with many overlapping shapes, scaled to the view.
+fn giant_svg(app: &mut HeadlessApp, shapes: u32) -> anyhow::Result<()> {
+    app.change_scene(World::new(Vector2::new(0, 0)));
+    app.add_node(Camera2d::default(), None);
+
+    let size = 4096;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}">"#,
+        size
+    );
+    for i in 0..shapes {
+        // Spread over the canvas without a random number generator.
+        let x = (i * 7919) % size;
+        let y = (i * 104729) % size;
+        let color = i.wrapping_mul(2654435761) >> 8;
+
+        let _ = if i % 2 == 0 {
+            write!(
+                svg,
+                r##"<circle cx="{}" cy="{}" r="24" fill="#{:06x}" stroke="black" stroke-width="2"/>"##,
+                x, y, color
+            )
+        } else {
+            write!(
+                svg,
+                r##"<path d="M{} {} q30 -40 60 0 t60 0 l-60 40 z" fill="#{:06x}"/>"##,
+                x, y, color
+            )
+        };
+    }
+    svg.push_str("</svg>");
+
+    let mut sprite = VectorSprite::new(VectorTexture::from_data(svg.as_bytes())?);
+    sprite.transform.scale = Vector2::new(1.0, 1.0) * HEIGHT as f32 / size as f32;
+    app.add_node(sprite, None);
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use cgmath::{Vector2, Vector4};
+use eureka::core::{App, Singletons};
+use eureka::math::transform::Transform2d;
+use eureka::render::{DrawCommands, DrawSource, Texture, TextureId};
+use eureka::scene::{AsNode, Camera2d};
+
+/// Stands in for an external ECS world, with components stored per entity.
+struct Entities {
+    positions: Vec<Vector2<f32>>,
+    velocities: Vec<Vector2<f32>>,
+    texture: TextureId,
+    /// Nodes can be owned outside the scene tree too.
+    camera: Camera2d,
+}
+
+impl DrawSource for Entities {
+    fn update(&mut self, dt: f32, singletons: &mut Singletons) {
+        self.camera.update(dt, singletons);
+
+        let config = &singletons.render_server.surface_config;
+        let bounds = Vector2::new(config.width as f32, config.height as f32);
+
+        for (position, velocity) in self.positions.iter_mut().zip(&mut self.velocities) {
+            *position += *velocity * dt;
+
+            if position.x < 0.0 || position.x > bounds.x {
+                velocity.x = -velocity.x;
+            }
+            if position.y < 0.0 || position.y > bounds.y {
+                velocity.y = -velocity.y;
+            }
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        self.camera.draw(draw_cmds);
+
+        for position in &self.positions {
+            let mut transform = Transform2d::default();
+            transform.position = *position;
+
+            draw_cmds.draw_sprite(
+                self.texture,
+                Vector4::new(0.0, 0.0, 1.0, 1.0),
+                transform,
+                Some(Vector2::new(64.0, 64.0)),
+                true,
+            );
+        }
+    }
+}
+
+/// Draws without any nodes in the scene tree.
+fn main() {
+    let mut app = App::new();
+
+    let texture = Texture::load(
+        &app.singletons.render_server.device,
+        &app.singletons.render_server.queue,
+        &mut app.render_world.texture_cache,
+        app.singletons
+            .asset_server
+            .asset_dir
+            .join("images/happy-tree.png"),
+    )
+    .unwrap();
+
+    let mut entities = Entities {
+        positions: vec![],
+        velocities: vec![],
+        texture,
+        camera: Camera2d::default(),
+    };
+
+    for i in 0..100 {
+        let angle = i as f32 * 0.7;
+        entities.positions.push(Vector2::new(
+            640.0 + angle.cos() * 200.0,
+            360.0 + angle.sin() * 200.0,
+        ));
+        entities
+            .velocities
+            .push(Vector2::new(angle.cos(), angle.sin()) * (50.0 + i as f32 * 2.0));
+    }
+
+    app.add_draw_source(entities);
+
+    app.run();
+}
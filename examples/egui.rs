@@ -0,0 +1,47 @@
+use eureka::core::App;
+use eureka::egui;
+use eureka::render::Texture;
+use eureka::scene::{AsNodeUi, Camera2d, Sprite2d};
+
+/// Run with `cargo run --example egui --features egui`.
+fn main() {
+    let mut app = App::new();
+
+    app.add_node(Camera2d::default(), None);
+
+    let img_tex = Texture::load(
+        &app.singletons.render_server.device,
+        &app.singletons.render_server.queue,
+        &mut app.render_world.texture_cache,
+        app.singletons
+            .asset_server
+            .asset_dir
+            .join("images/happy-tree.png"),
+    )
+    .unwrap();
+
+    let sprite_id = app.add_node(
+        Sprite2d::new(&app.render_world.texture_cache, img_tex),
+        None,
+    );
+
+    app.add_egui_ui(move |ctx, world, singletons| {
+        egui::Window::new("Debug").show(ctx, |ui| {
+            ui.label(format!("FPS: {:.0}", singletons.engine.get_fps()));
+
+            let sprite = world.get_node_mut::<Sprite2d>(sprite_id).unwrap();
+
+            let mut position = sprite.get_position();
+            ui.add(egui::Slider::new(&mut position.x, 0.0..=1280.0).text("x"));
+            ui.add(egui::Slider::new(&mut position.y, 0.0..=720.0).text("y"));
+            sprite.set_position(position);
+
+            let mut visible = world.is_visible(sprite_id);
+            if ui.checkbox(&mut visible, "Visible").changed() {
+                world.set_visible(sprite_id, visible);
+            }
+        });
+    });
+
+    app.run();
+}
@@ -1,6 +1,7 @@
 use eureka::core::App;
 use eureka::scene::Camera2d;
 use eureka::scene::Label;
+use eureka::text::TextRenderMode;
 
 fn main() {
     let mut app = App::new();
@@ -15,12 +16,20 @@ fn main() {
         .into_os_string()
         .into_string()
         .unwrap();
-    app.singletons.text_server.load_font(
+    let font = app.singletons.asset_server.load_font(
         &font_path,
-        &mut app.singletons.render_server,
+        &mut app.singletons.text_server,
+        &app.singletons.render_server,
         &mut app.render_world.texture_cache,
     );
 
+    // Sharper small text on LCD panels.
+    app.singletons.text_server.set_render_mode(
+        TextRenderMode::Subpixel,
+        &app.singletons.render_server,
+        &mut app.render_world,
+    );
+
     let mut text = "".to_string();
     text += "🌤你好世界！\n"; // Chinese
     text += "こんにちは世界！\n"; // Japanese
@@ -35,7 +44,7 @@ fn main() {
 
     let mut label = Label::default();
     label.set_text(text);
-    label.set_font(font_path);
+    label.set_font(font);
 
     app.add_node(label, None);
 
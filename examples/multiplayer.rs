@@ -0,0 +1,49 @@
+use cgmath::Vector2;
+use eureka::core::App;
+use eureka::render::Texture;
+use eureka::scene::{AsNodeUi, Camera2d, Sprite2d};
+
+const ADDRESS: &str = "127.0.0.1:7777";
+
+fn custom_update(dt: f32, sprite: &mut Sprite2d) {
+    sprite.set_rotation(sprite.get_rotation() + dt);
+}
+
+/// Run `cargo run --example multiplayer server` first, then any number of
+/// `cargo run --example multiplayer`. The sprite spins on the server and is replicated to clients.
+fn main() {
+    let is_server = std::env::args().nth(1).as_deref() == Some("server");
+
+    let mut app = App::new();
+
+    app.add_node(Camera2d::default(), None);
+
+    let img_tex = Texture::load(
+        &app.singletons.render_server.device,
+        &app.singletons.render_server.queue,
+        &mut app.render_world.texture_cache,
+        app.singletons
+            .asset_server
+            .asset_dir
+            .join("images/happy-tree.png"),
+    )
+    .unwrap();
+
+    let mut sprite = Sprite2d::new(&app.render_world.texture_cache, img_tex);
+    sprite.set_position(Vector2::new(300.0, 300.0));
+    if is_server {
+        sprite.custom_update = Some(custom_update);
+    }
+    let sprite_id = app.add_node(sprite, None);
+
+    // Both sides mark the same nodes.
+    app.singletons.network.replicate(sprite_id);
+
+    if is_server {
+        app.singletons.network.host(ADDRESS).unwrap();
+    } else {
+        app.singletons.network.connect(ADDRESS).unwrap();
+    }
+
+    app.run();
+}
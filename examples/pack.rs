@@ -0,0 +1,23 @@
+use eureka::asset::{PackBackend, PackBuilder, ASSET_PACK_NAME};
+
+/// Bundle an asset directory into a pack.
+/// Usage: cargo run --example pack -- [assets dir] [output file]
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let source_dir = args.next().unwrap_or("assets".to_string());
+    let output = args.next().unwrap_or(ASSET_PACK_NAME.to_string());
+
+    let mut builder = PackBuilder::new();
+    builder.add_dir(&source_dir)?;
+    builder.write(&output)?;
+
+    let pack = PackBackend::open(&output)?;
+    println!(
+        "Packed {} files from {} into {}",
+        pack.get_file_count(),
+        source_dir,
+        output
+    );
+
+    Ok(())
+}
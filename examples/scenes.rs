@@ -0,0 +1,73 @@
+use cgmath::Vector2;
+use eureka::core::App;
+use eureka::math::types::Vec3;
+use eureka::render::{Texture, TransitionKind};
+use eureka::scene::{
+    AsNode3d, AsNodeUi, Camera2d, Camera3d, Model, PointLight, SceneLoader, Sprite2d,
+};
+
+fn main() {
+    let mut app = App::new();
+
+    // The first scene.
+    app.add_node(Camera2d::default(), None);
+
+    let img_tex = Texture::load(
+        &app.singletons.render_server.device,
+        &app.singletons.render_server.queue,
+        &mut app.render_world.texture_cache,
+        app.singletons
+            .asset_server
+            .asset_dir
+            .join("images/happy-tree.png"),
+    )
+    .unwrap();
+
+    let mut sprite = Sprite2d::new(&app.render_world.texture_cache, img_tex);
+    sprite.set_position(Vector2::new(200.0, 200.0));
+    app.add_node(sprite, None);
+
+    // The second scene, loaded in the background one step per node.
+    let mut loader = SceneLoader::new();
+
+    loader.add_step(|ctx| {
+        let camera = Camera3d::new(
+            (-6.0, 2.0, 0.0),
+            cgmath::Deg(0.0),
+            cgmath::Deg(-10.0),
+            ctx.render_server,
+        );
+        ctx.add_node(camera, None);
+        ctx.add_node(PointLight::new(), None);
+    });
+
+    loader.add_step(|ctx| {
+        let mut model = Model::load(
+            &mut ctx.render_world.texture_cache,
+            &mut ctx.render_world.mesh_render_resources.material_cache,
+            &mut ctx.render_world.mesh_cache,
+            &mut ctx.asset_server.registry,
+            ctx.render_server,
+            ctx.asset_server
+                .asset_dir
+                .join("models/viking_room/viking_room.obj"),
+        )
+        .unwrap();
+        model.set_position(Vec3::new(0.0, 0.0, 0.0));
+        ctx.add_node(model, None);
+    });
+
+    app.singletons.scene_manager.preload("room", loader);
+
+    // Fade out, switch scenes, then fade back in.
+    app.render_world.screen_transition.cover(
+        TransitionKind::Fade,
+        2.0,
+        Some(|_, singletons, transition| {
+            singletons.scene_manager.change_scene("room");
+            transition.reveal(TransitionKind::Fade, 1.0, None);
+        }),
+    );
+
+    app.run();
+}
@@ -1,9 +1,13 @@
 use cgmath::Vector2;
 use eureka::core::App;
-use eureka::render::Texture;
+use eureka::math::color::ColorU;
+use eureka::render::{Texture, TransitionKind};
 use eureka::scene::NodeType::VectorSprite;
 use eureka::scene::Sprite2d;
-use eureka::scene::{AsNodeUi, Camera2d};
+use eureka::scene::{
+    AsNodeUi, Camera2d, Light2d, LightOccluder2d, Line2d, LineCap, LineJoint, ParallaxBackground,
+    ParallaxLayer,
+};
 
 fn custom_update(dt: f32, sprite: &mut Sprite2d) {
     sprite.set_rotation(sprite.get_rotation() + dt);
@@ -52,6 +56,24 @@ fn main() {
     )
     .unwrap();
 
+    // An endlessly scrolling background, moving at half the speed of the scene.
+    let mut background = ParallaxBackground::new();
+    background.custom_update = Some(|dt, background| background.scroll_offset.x -= 100.0 * dt);
+    let background_id = app.add_node(background, None);
+
+    // texture.jpg is 512 pixels wide.
+    let tile_width = 512.0;
+
+    let mut layer = ParallaxLayer::new(Vector2::new(0.5, 0.5));
+    layer.mirroring.x = tile_width;
+    let layer_id = app.add_node(layer, Some(background_id));
+
+    for i in 0..4 {
+        let mut tile = Sprite2d::new(&app.render_world.texture_cache, img_tex2);
+        tile.set_position(Vector2::new(i as f32 * tile_width, 0.0));
+        app.add_node(tile, Some(layer_id));
+    }
+
     let mut sprite1 = Sprite2d::new(&app.render_world.texture_cache, img_tex);
     sprite1.custom_update = Some(custom_update);
     app.add_node(sprite1, None);
@@ -64,9 +86,48 @@ fn main() {
     sprite3.set_position(Vector2::new(400f32, 400f32));
     app.add_node(sprite3, None);
 
+    // A light with a box casting a shadow over the sprites.
+    let mut light = Light2d::new_point(600.0);
+    light.transform.position = Vector2::new(100.0, 100.0);
+    app.add_node(light, None);
+
+    let mut occluder = LightOccluder2d::from_rect(Vector2::new(40.0, 40.0));
+    occluder.transform.position = Vector2::new(250.0, 150.0);
+    app.add_node(occluder, None);
+
+    // A zigzag line fading from red to blue.
+    let mut line = Line2d::new(vec![
+        Vector2::new(50.0, 500.0),
+        Vector2::new(150.0, 420.0),
+        Vector2::new(250.0, 500.0),
+        Vector2::new(350.0, 420.0),
+    ]);
+    line.width = 12.0;
+    line.joint = LineJoint::Round;
+    line.begin_cap = LineCap::Round;
+    line.end_cap = LineCap::Round;
+    line.colors = vec![
+        ColorU::new(255, 0, 0, 255),
+        ColorU::new(170, 0, 85, 255),
+        ColorU::new(85, 0, 170, 255),
+        ColorU::new(0, 0, 255, 255),
+    ];
+    app.add_node(line, None);
+
     // let mut button = Button::new(&app.singletons.render_server);
     // button.transform.position = Vector2::new(200.0, 200.0);
     // app.add_node(button, None);
 
+    // Open the scene with an iris.
+    let transition = &mut app.render_world.screen_transition;
+    transition.set_coverage(1.0);
+    transition.reveal(
+        TransitionKind::Iris {
+            center: Vector2::new(0.5, 0.5),
+        },
+        1.0,
+        None,
+    );
+
     app.run();
 }
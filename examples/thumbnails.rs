@@ -0,0 +1,41 @@
+use eureka::asset::{ThumbnailView, Thumbnailer};
+use std::path::Path;
+
+/// Render preview images of assets without opening a window.
+/// Usage: cargo run --example thumbnails -- [asset files...] [--out dir] [--size pixels] [--views count]
+fn main() -> anyhow::Result<()> {
+    let mut files = vec![];
+    let mut out_dir = "thumbnails".to_string();
+    let mut size = 256;
+    let mut views = 4;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out_dir = args.next().unwrap_or(out_dir),
+            "--size" => size = args.next().unwrap_or_default().parse()?,
+            "--views" => views = args.next().unwrap_or_default().parse()?,
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        files.push("assets/models/ferris/ferris3d_v1.0.obj".to_string());
+    }
+
+    let mut thumbnailer = Thumbnailer::new(size)?;
+    thumbnailer.views = ThumbnailView::orbit(views);
+
+    for file in &files {
+        match thumbnailer.render(Path::new(file), Path::new(&out_dir)) {
+            Ok(paths) => {
+                for path in paths {
+                    println!("{}", path.display());
+                }
+            }
+            Err(e) => eprintln!("Skipped {}: {}", file, e),
+        }
+    }
+
+    Ok(())
+}
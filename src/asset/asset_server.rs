@@ -1,10 +1,25 @@
-use assets_manager::{loader, Asset, AssetCache, Compound, Handle};
-use std::collections::HashMap;
-use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::asset::http::HttpBackend;
+use crate::asset::http::HttpClient;
+use crate::asset::pack::PackBackend;
+use crate::asset::registry::{AssetHandle, AssetKey, AssetRegistry};
+use crate::asset::vfs::{get_user_data_dir, DirectoryBackend, MemoryBackend, Vfs};
+use crate::render::render_world::RenderWorld;
+use crate::render::{RenderServer, Texture, TextureCache};
+use crate::text::{TextServer, Translation};
+use anyhow::Result;
+use assets_manager::AssetCache;
+use std::path::{Path, PathBuf};
 
 pub struct AssetServer {
     pub asset_dir: PathBuf,
     pub asset_cache: AssetCache,
+    /// Serves "asset://" (read-only), "user://", "memory://" and "http(s)://" URIs.
+    pub vfs: Vfs,
+    /// Reference counts and dependencies of loaded assets.
+    pub registry: AssetRegistry,
+    /// Downloads remote assets in the background.
+    pub http: HttpClient,
 }
 
 impl AssetServer {
@@ -16,9 +31,41 @@ impl AssetServer {
         // Create a new cache to load assets under the "./assets" folder.
         let cache = AssetCache::new("assets").unwrap();
 
+        let user_dir = get_user_data_dir(env!("CARGO_PKG_NAME"));
+        log::info!("User dir: {}", user_dir.display());
+
+        let mut vfs = Vfs::new();
+        // Prefer a pack next to the executable over loose files.
+        match find_asset_pack() {
+            Some(pack_path) => match PackBackend::open(&pack_path) {
+                Ok(pack) => {
+                    log::info!("Asset pack: {}", pack_path.display());
+                    vfs.mount("asset", pack);
+                }
+                Err(e) => {
+                    log::warn!("Failed to open asset pack: {}", e);
+                    vfs.mount("asset", DirectoryBackend::new(&asset_dir, true));
+                }
+            },
+            None => vfs.mount("asset", DirectoryBackend::new(&asset_dir, true)),
+        }
+        vfs.mount("user", DirectoryBackend::new(&user_dir, false));
+        vfs.mount("memory", MemoryBackend::new());
+
+        // Remote files are cached in the user directory.
+        let http = HttpClient::new(user_dir.join("http_cache"));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            vfs.mount("http", HttpBackend::new("http", http.clone()));
+            vfs.mount("https", HttpBackend::new("https", http.clone()));
+        }
+
         Self {
             asset_dir,
             asset_cache: cache,
+            vfs,
+            registry: AssetRegistry::new(),
+            http,
         }
     }
 
@@ -26,4 +73,178 @@ impl AssetServer {
     pub fn update(&mut self) {
         self.asset_cache.hot_reload();
     }
+
+    /// Load a texture, or get the already loaded one for the same path.
+    pub fn load_texture<P: AsRef<Path>>(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        path: P,
+    ) -> Result<AssetHandle> {
+        load_texture(&mut self.registry, render_server, texture_cache, path)
+    }
+
+    /// Load a font into the text server. The handle can be given to labels.
+    pub fn load_font(
+        &mut self,
+        path: &str,
+        text_server: &mut TextServer,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> AssetHandle {
+        if let Some(handle) = self.registry.get_by_label(path) {
+            return handle;
+        }
+
+        text_server.load_font(&path.to_string(), render_server, texture_cache);
+
+        self.add_font(path, text_server, texture_cache)
+    }
+
+    /// Load a pre-baked BMFont ".fnt" file into the text server, for pixel-perfect text.
+    /// The handle can be given to labels like any other font.
+    pub fn load_bitmap_font(
+        &mut self,
+        path: &str,
+        text_server: &mut TextServer,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> Result<AssetHandle> {
+        if let Some(handle) = self.registry.get_by_label(path) {
+            return Ok(handle);
+        }
+
+        text_server.load_bitmap_font(path, render_server, texture_cache)?;
+
+        Ok(self.add_font(path, text_server, texture_cache))
+    }
+
+    /// Register a font loaded by the text server, and its atlas.
+    fn add_font(
+        &mut self,
+        path: &str,
+        text_server: &TextServer,
+        texture_cache: &TextureCache,
+    ) -> AssetHandle {
+        let atlas_texture = text_server.get_font_atlas(path).unwrap();
+        let atlas_size = texture_cache.get(atlas_texture).unwrap().get_memory_size();
+        let atlas_handle = self.registry.add(
+            AssetKey::Texture(atlas_texture),
+            &format!("{} (atlas)", path),
+            atlas_size,
+            vec![],
+        );
+
+        self.registry.add(
+            AssetKey::Font(path.to_string()),
+            path,
+            0,
+            vec![atlas_handle],
+        )
+    }
+
+    /// Add the translations in a ".csv" table or a Fluent ".ftl" file to the text server.
+    /// Fluent files hold a single locale, named by the file, e.g. "asset://locales/de.ftl".
+    pub fn load_translation(&self, uri: &str, text_server: &mut TextServer) -> Result<()> {
+        let source = self.vfs.read_to_string(uri)?;
+        let path = Path::new(uri);
+
+        let translations = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Translation::from_csv(&source)?,
+            Some("ftl") => {
+                let locale = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or_default();
+                vec![Translation::from_fluent(locale, &source)?]
+            }
+            _ => anyhow::bail!("Unknown translation format: {}", uri),
+        };
+
+        for translation in translations {
+            text_server.add_translation(translation);
+        }
+
+        Ok(())
+    }
+
+    /// Free GPU resources of registered assets that are no longer referenced,
+    /// e.g. after the nodes using them have been removed.
+    pub fn free_unused(&mut self, render_world: &mut RenderWorld, text_server: &mut TextServer) {
+        for key in self.registry.collect_unused() {
+            log::info!("Freeing unused asset: {:?}", key);
+
+            match key {
+                AssetKey::Texture(id) => render_world.texture_cache.remove(id),
+                AssetKey::Mesh(id) => {
+                    render_world.mesh_cache.remove(id);
+                    render_world
+                        .mesh_render_resources
+                        .instance_cache
+                        .remove(&id);
+                    render_world.morph_render_resources.remove(id);
+                }
+                AssetKey::Material(id) => {
+                    let resources = &mut render_world.mesh_render_resources;
+                    resources.material_cache.remove(&id);
+                    resources.texture_bind_group_cache.remove(&id);
+                    resources.material_uniform_buffer_cache.remove(&id);
+                }
+                AssetKey::Font(id) => text_server.unload_font(&id),
+            }
+        }
+    }
+
+    /// A listing of live assets and their sizes, largest first.
+    pub fn report(&self) -> String {
+        let assets = self.registry.get_live_assets();
+        let total: u64 = assets.iter().map(|a| a.size).sum();
+
+        let mut report = format!(
+            "{} live assets, {:.1} MiB\n",
+            assets.len(),
+            total as f64 / (1024.0 * 1024.0)
+        );
+        for asset in assets {
+            report += &format!("{}\n", asset);
+        }
+
+        report
+    }
+}
+
+/// Load a texture through the registry, reusing it if the path was loaded before.
+pub(crate) fn load_texture<P: AsRef<Path>>(
+    registry: &mut AssetRegistry,
+    render_server: &RenderServer,
+    texture_cache: &mut TextureCache,
+    path: P,
+) -> Result<AssetHandle> {
+    let label = path.as_ref().to_string_lossy().into_owned();
+
+    if let Some(handle) = registry.get_by_label(&label) {
+        return Ok(handle);
+    }
+
+    let texture = Texture::load(
+        &render_server.device,
+        &render_server.queue,
+        texture_cache,
+        path,
+    )?;
+    let size = texture_cache.get(texture).unwrap().get_memory_size();
+
+    Ok(registry.add(AssetKey::Texture(texture), &label, size, vec![]))
+}
+
+/// Name of the asset pack looked up next to the executable.
+pub const ASSET_PACK_NAME: &str = "assets.pak";
+
+fn find_asset_pack() -> Option<PathBuf> {
+    let path = std::env::current_exe()
+        .ok()?
+        .parent()?
+        .join(ASSET_PACK_NAME);
+
+    path.exists().then_some(path)
 }
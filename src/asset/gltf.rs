@@ -0,0 +1,572 @@
+use crate::asset::{
+    AssetHandle, AssetKey, AssetRegistry, TextureFilter, TextureImportSettings, TextureWrap,
+};
+use crate::math::transform::Transform3d;
+use crate::physics::Aabb;
+use crate::render::material::{
+    MaterialCache, MaterialId, MaterialStandard, Transparency, VertexAnimation,
+};
+use crate::render::vertex::{Vertex3d, Vertex3dColored};
+use crate::render::{
+    CustomVertex, DepthStencilConfig, Mesh, MeshCache, MeshId, MorphTarget, RenderServer, Texture,
+    TextureCache,
+};
+use crate::scene::d3::model::{compute_tangents, emissive_from_linear};
+use crate::scene::{AsNode3d, Model, NodeId, World};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use cgmath::{InnerSpace, Quaternion, Vector2, Vector3, Zero};
+use std::path::Path;
+
+/// A node of a glTF scene.
+pub struct GltfNode {
+    /// Named after the glTF node. Nodes without a mesh are models without meshes, which
+    /// only place their children.
+    pub model: Model,
+    /// Index into `GltfScene::nodes`, always before this node.
+    pub parent: Option<usize>,
+}
+
+/// The default scene of a glTF 2.0 file (.gltf or .glb), as a tree of models.
+///
+/// Meshes come with their base color, normal and emissive textures, and the base color
+/// factor and vertex colors as vertex colors. Morph targets become blend shapes, named
+/// by the `targetNames` extra of the mesh. Metallic-roughness, skins, animations,
+/// cameras and lights are not imported. Node transforms are global, like
+/// everywhere in the world, so shear from non-uniform scale in the hierarchy is lost.
+pub struct GltfScene {
+    /// Parents before their children.
+    pub nodes: Vec<GltfNode>,
+}
+
+impl GltfScene {
+    pub fn load<P: AsRef<Path>>(
+        texture_cache: &mut TextureCache,
+        material_cache: &mut MaterialCache,
+        mesh_cache: &mut MeshCache,
+        asset_registry: &mut AssetRegistry,
+        render_server: &RenderServer,
+        path: P,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(&bytes)?;
+
+        // Relative URIs are next to the file.
+        let containing_folder = path.parent().context("Directory has no parent")?;
+
+        let mut blob = blob;
+        let buffers = document
+            .buffers()
+            .map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Bin => blob.take().context("GLB has no binary chunk"),
+                gltf::buffer::Source::Uri(uri) => read_uri(containing_folder, uri),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut loader = Loader {
+            path,
+            containing_folder,
+            buffers: &buffers,
+            texture_cache,
+            mesh_cache,
+            asset_registry,
+            device: &render_server.device,
+            queue: &render_server.queue,
+        };
+
+        let materials = document
+            .materials()
+            .map(|material| loader.load_material(material_cache, &material))
+            .collect::<Vec<_>>();
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .context("glTF file has no scene")?;
+
+        let mut nodes = vec![];
+        let mut transforms: Vec<Transform3d> = vec![];
+
+        // Nodes with their parent, in depth-first order.
+        let mut stack: Vec<(gltf::Node, Option<usize>)> =
+            scene.nodes().map(|node| (node, None)).collect();
+        stack.reverse();
+
+        while let Some((node, parent)) = stack.pop() {
+            let parent_transform = parent.map_or(Transform3d::default(), |i| transforms[i]);
+
+            let mut model = match node.mesh() {
+                Some(mesh) => loader.load_mesh(&mesh, node.weights(), &materials)?,
+                None => Model::from_parts(vec![], vec![], vec![], Aabb::from_points(&[])),
+            };
+
+            let (translation, rotation, scale) = node.transform().decomposed();
+            let transform = parent_transform.mul_transform(&Transform3d {
+                position: translation.into(),
+                rotation: Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]),
+                scale: scale.into(),
+            });
+
+            model.set_position(transform.position);
+            model.set_rotation(transform.rotation);
+            model.set_scale(transform.scale);
+            model.name = node
+                .name()
+                .map_or_else(|| format!("node{}", node.index()), str::to_string);
+
+            let index = nodes.len();
+            nodes.push(GltfNode { model, parent });
+            transforms.push(transform);
+
+            // Pushed in reverse, so that the first child comes out first.
+            let first = stack.len();
+            stack.extend(node.children().map(|child| (child, Some(index))));
+            stack[first..].reverse();
+        }
+
+        Ok(Self { nodes })
+    }
+
+    /// Add the nodes to the world under `parent`, or under the root if None. Returns
+    /// their IDs, in the same order as `nodes`.
+    pub fn add_to_world(self, world: &mut World, parent: Option<NodeId>) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = vec![];
+
+        for node in self.nodes {
+            let node_parent = node.parent.map(|i| ids[i]).or(parent);
+            ids.push(world.add_node(Box::new(node.model), node_parent));
+        }
+
+        ids
+    }
+}
+
+struct Loader<'a> {
+    path: &'a Path,
+    containing_folder: &'a Path,
+    buffers: &'a [Vec<u8>],
+
+    texture_cache: &'a mut TextureCache,
+    mesh_cache: &'a mut MeshCache,
+    asset_registry: &'a mut AssetRegistry,
+    device: &'a wgpu::Device,
+    queue: &'a wgpu::Queue,
+}
+
+impl Loader<'_> {
+    fn load_texture(&mut self, texture: gltf::Texture, srgb: bool) -> Result<AssetHandle> {
+        let image = texture.source();
+
+        let name = image
+            .name()
+            .map_or_else(|| format!("image{}", image.index()), str::to_string);
+        let label = format!(
+            "{}#{}{}",
+            self.path.display(),
+            name,
+            if srgb { "" } else { " (linear)" }
+        );
+
+        // Shared by the materials using it.
+        if let Some(handle) = self.asset_registry.get_by_label(&label) {
+            return Ok(handle);
+        }
+
+        let bytes = match image.source() {
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                buffer[view.offset()..view.offset() + view.length()].to_vec()
+            }
+            gltf::image::Source::Uri { uri, .. } => read_uri(self.containing_folder, uri)?,
+        };
+        let img = image::load_from_memory(&bytes)?;
+
+        let sampler = texture.sampler();
+        let settings = TextureImportSettings {
+            filter: match sampler.mag_filter() {
+                Some(gltf::texture::MagFilter::Nearest) => TextureFilter::Nearest,
+                _ => TextureFilter::Linear,
+            },
+            wrap: match sampler.wrap_s() {
+                gltf::texture::WrappingMode::ClampToEdge => TextureWrap::ClampToEdge,
+                gltf::texture::WrappingMode::MirroredRepeat => TextureWrap::MirrorRepeat,
+                gltf::texture::WrappingMode::Repeat => TextureWrap::Repeat,
+            },
+            srgb,
+            mipmaps: !matches!(
+                sampler.min_filter(),
+                Some(gltf::texture::MinFilter::Nearest | gltf::texture::MinFilter::Linear)
+            ),
+            // Normal maps aren't colors.
+            premultiply_alpha: srgb,
+        };
+
+        let id = Texture::from_image_with_settings(
+            self.device,
+            self.queue,
+            self.texture_cache,
+            &img,
+            Some(&label),
+            &settings,
+        )?;
+        let size = self.texture_cache.get(id).unwrap().get_memory_size();
+
+        Ok(self
+            .asset_registry
+            .add(AssetKey::Texture(id), &label, size, vec![]))
+    }
+
+    /// Load a texture, or log why not.
+    fn try_load_texture(
+        &mut self,
+        texture: Option<gltf::Texture>,
+        srgb: bool,
+    ) -> Option<AssetHandle> {
+        let texture = texture?;
+
+        match self.load_texture(texture.clone(), srgb) {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load texture {} of {:?}: {}",
+                    texture.index(),
+                    self.path,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// The material with the base color factor, which goes into vertex colors.
+    fn load_material(
+        &mut self,
+        material_cache: &mut MaterialCache,
+        material: &gltf::Material,
+    ) -> (MaterialId, AssetHandle, [f32; 4]) {
+        let pbr = material.pbr_metallic_roughness();
+
+        let color_texture =
+            self.try_load_texture(pbr.base_color_texture().map(|t| t.texture()), true);
+        let normal_texture =
+            self.try_load_texture(material.normal_texture().map(|t| t.texture()), false);
+        let emissive_texture =
+            self.try_load_texture(material.emissive_texture().map(|t| t.texture()), true);
+
+        let (emissive_color, emissive_strength) = emissive_from_linear(material.emissive_factor());
+
+        let name = material.name().map_or_else(
+            || format!("material{}", material.index().unwrap_or(0)),
+            str::to_string,
+        );
+
+        let material = MaterialStandard {
+            name: name.clone(),
+            color_texture: color_texture.as_ref().and_then(|h| h.get_texture()),
+            normal_texture: normal_texture.as_ref().and_then(|h| h.get_texture()),
+            emissive_texture: emissive_texture.as_ref().and_then(|h| h.get_texture()),
+            emissive_color,
+            emissive_strength: emissive_strength * material.emissive_strength().unwrap_or(1.0),
+            texture_bind_group: None,
+            uv_offset: Vector2::zero(),
+            uv_scale: Vector2::new(1.0, 1.0),
+            uv_rotation: 0.0,
+            transparency: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => Transparency::Opaque,
+                gltf::material::AlphaMode::Mask => Transparency::AlphaCut {
+                    cutoff: material.alpha_cutoff().unwrap_or(0.5),
+                },
+                gltf::material::AlphaMode::Blend => Transparency::AlphaBlend,
+            },
+            double_sided: material.double_sided(),
+            depth_stencil: DepthStencilConfig::opaque(),
+            vertex_animation: VertexAnimation::None,
+        };
+
+        let material_id = material_cache.add(material);
+        let material_handle = self.asset_registry.add(
+            AssetKey::Material(material_id),
+            &format!("{}#{}", self.path.display(), name),
+            0,
+            color_texture
+                .into_iter()
+                .chain(normal_texture)
+                .chain(emissive_texture)
+                .collect(),
+        );
+
+        (material_id, material_handle, pbr.base_color_factor())
+    }
+
+    /// A model with a mesh per triangle primitive. Nodes using the same glTF mesh get
+    /// their own copies, since a mesh is drawn with a single transform.
+    ///
+    /// Morph target weights start at the node's `weights`, or else the mesh's.
+    fn load_mesh(
+        &mut self,
+        mesh: &gltf::Mesh,
+        node_weights: Option<&[f32]>,
+        materials: &[(MaterialId, AssetHandle, [f32; 4])],
+    ) -> Result<Model> {
+        let mut meshes = vec![];
+        let mut mesh_materials = vec![];
+        let mut mesh_blend_shapes = vec![];
+        let mut assets = vec![];
+        let mut positions = vec![];
+
+        let mesh_name = mesh
+            .name()
+            .map_or_else(|| format!("mesh{}", mesh.index()), str::to_string);
+
+        let target_names = morph_target_names(mesh);
+        let target_name = |i: usize| {
+            target_names
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("target{}", i))
+        };
+        // Every primitive of a mesh has the same targets.
+        let mut target_count = 0;
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "Skipped primitive {} of {} in {:?}, only triangles are supported",
+                    primitive.index(),
+                    mesh_name,
+                    self.path
+                );
+                continue;
+            }
+
+            let material = primitive.material().index().map(|i| &materials[i]);
+
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+            let primitive_positions: Vec<[f32; 3]> = reader
+                .read_positions()
+                .ok_or_else(|| anyhow!("Primitive of {} has no positions", mesh_name))?
+                .collect();
+
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..primitive_positions.len() as u32).collect(),
+            };
+            if let Some(index) = indices
+                .iter()
+                .find(|i| **i as usize >= primitive_positions.len())
+            {
+                bail!(
+                    "Primitive of {} has an out of range index {}",
+                    mesh_name,
+                    index
+                );
+            }
+
+            let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => compute_normals(&primitive_positions, &indices),
+            };
+
+            let uvs: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                Some(uvs) => uvs.into_f32().collect(),
+                None => vec![[0.0; 2]; primitive_positions.len()],
+            };
+
+            let mut vertices: Vec<Vertex3d> = primitive_positions
+                .iter()
+                .zip(&normals)
+                .zip(&uvs)
+                .map(|((position, normal), uv)| Vertex3d {
+                    position: *position,
+                    // Already top to bottom, like wgpu.
+                    uv: *uv,
+                    normal: *normal,
+                    tangent: [0.0; 3],
+                    bi_tangent: [0.0; 3],
+                })
+                .collect();
+
+            match reader.read_tangents() {
+                Some(tangents) => {
+                    for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                        let normal = Vector3::from(vertex.normal);
+                        let t = Vector3::new(tangent[0], tangent[1], tangent[2]);
+
+                        // The bi-tangent points up in the texture, like for OBJ models.
+                        vertex.tangent = t.into();
+                        vertex.bi_tangent = (normal.cross(t) * tangent[3]).into();
+                    }
+                }
+                None => compute_tangents(&mut vertices, &indices),
+            }
+
+            let targets: Vec<MorphTarget> = reader
+                .read_morph_targets()
+                .enumerate()
+                .map(|(i, (position_deltas, normal_deltas, _))| MorphTarget {
+                    name: target_name(i),
+                    position_deltas: match position_deltas {
+                        Some(deltas) => deltas.collect(),
+                        None => vec![[0.0; 3]; vertices.len()],
+                    },
+                    normal_deltas: normal_deltas.map_or_else(Vec::new, |deltas| deltas.collect()),
+                })
+                .collect();
+            target_count = target_count.max(targets.len());
+            mesh_blend_shapes.push((0..targets.len()).collect());
+
+            let base_color = material.map_or([1.0; 4], |(_, _, factor)| *factor);
+            let label = format!(
+                "{}#{}/{}",
+                self.path.display(),
+                mesh_name,
+                primitive.index()
+            );
+
+            // Only meshes with vertex colors or a tinted material pay for them.
+            let colors = reader.read_colors(0).map(|colors| colors.into_rgba_f32());
+            let mesh = if colors.is_some() || base_color != [1.0; 4] {
+                let colors: Vec<[f32; 4]> = match colors {
+                    Some(colors) => colors.collect(),
+                    None => vec![[1.0; 4]; vertices.len()],
+                };
+
+                let colored: Vec<Vertex3dColored> = vertices
+                    .iter()
+                    .zip(colors)
+                    .map(|(vertex, color)| Vertex3dColored {
+                        vertex: *vertex,
+                        color: std::array::from_fn(|i| color[i] * base_color[i]),
+                    })
+                    .collect();
+
+                build_mesh(self.device, &label, &colored, indices, targets)?
+            } else {
+                build_mesh(self.device, &label, &vertices, indices, targets)?
+            };
+
+            positions.extend(primitive_positions.iter().map(|p| Vector3::from(*p)));
+
+            let mesh_size = mesh.vertex_buffer.size() + mesh.index_buffer.size();
+            let mesh_id: MeshId = self.mesh_cache.add(mesh);
+            meshes.push(mesh_id);
+            assets.push(self.asset_registry.add(
+                AssetKey::Mesh(mesh_id),
+                &label,
+                mesh_size,
+                vec![],
+            ));
+
+            match material {
+                Some((material_id, material_handle, _)) => {
+                    mesh_materials.push(Some(*material_id));
+                    assets.push(material_handle.clone());
+                }
+                None => mesh_materials.push(None),
+            }
+        }
+
+        let weights = node_weights.or(mesh.weights()).unwrap_or_default();
+        let blend_shapes = (0..target_count)
+            .map(|i| (target_name(i), weights.get(i).copied().unwrap_or(0.0)))
+            .collect();
+
+        let mut model = Model::from_parts(
+            meshes,
+            mesh_materials,
+            assets,
+            Aabb::from_points(&positions),
+        );
+        model.set_blend_shapes(blend_shapes, mesh_blend_shapes);
+
+        Ok(model)
+    }
+}
+
+fn build_mesh<V: CustomVertex>(
+    device: &wgpu::Device,
+    label: &str,
+    vertices: &[V],
+    indices: Vec<u32>,
+    targets: Vec<MorphTarget>,
+) -> Result<Mesh> {
+    if targets.is_empty() {
+        Ok(Mesh::from_vertices(device, label, vertices, indices))
+    } else {
+        Mesh::from_vertices_with_morph_targets(device, label, vertices, indices, targets)
+    }
+}
+
+/// Morph target names aren't part of glTF, but most exporters write them to the
+/// `targetNames` extra of the mesh.
+fn morph_target_names(mesh: &gltf::Mesh) -> Vec<String> {
+    let Some(extras) = mesh.extras() else {
+        return vec![];
+    };
+
+    serde_json::from_str::<serde_json::Value>(extras.get())
+        .ok()
+        .and_then(|extras| serde_json::from_value(extras.get("targetNames")?.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// Smooth normals, weighted by triangle area, for meshes without any.
+fn compute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vector3::zero(); positions.len()];
+
+    for c in indices.chunks_exact(3) {
+        let [p0, p1, p2] = [0, 1, 2].map(|i| Vector3::from(positions[c[i] as usize]));
+        let normal = (p1 - p0).cross(p2 - p0);
+
+        for i in c {
+            normals[*i as usize] += normal;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|n: Vector3<f32>| {
+            if n.magnitude2() > 0.0 {
+                n.normalize().into()
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
+/// Data of a buffer or image, from a base64 data URI or a file next to the glTF file.
+fn read_uri(containing_folder: &Path, uri: &str) -> Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, data) = data
+            .split_once(";base64,")
+            .context("Only base64 data URIs are supported")?;
+
+        return Ok(base64::engine::general_purpose::STANDARD.decode(data)?);
+    }
+
+    let path = containing_folder.join(percent_decode(uri)?);
+    std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// URIs in glTF files escape spaces and other characters, e.g. "my%20texture.png".
+fn percent_decode(uri: &str) -> Result<String> {
+    let mut bytes = vec![];
+    let mut rest = uri.as_bytes();
+
+    while let Some((byte, tail)) = rest.split_first() {
+        if *byte == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2])?;
+            bytes
+                .push(u8::from_str_radix(hex, 16).with_context(|| format!("Invalid URI {}", uri))?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(*byte);
+            rest = tail;
+        }
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
@@ -0,0 +1,242 @@
+use crate::asset::VfsBackend;
+use anyhow::{anyhow, bail, Result};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::task::{Context, Poll};
+
+/// Downloads files by URL, keeping a copy in a local cache directory.
+///
+/// Requests run in the background. Poll the returned request each frame,
+/// or await it in an engine task.
+/// ```ignore
+/// let request = singletons.asset_server.http.fetch("https://example.com/tree.png");
+/// singletons.engine.spawn_task(async move {
+///     let bytes = request.await.unwrap();
+///     ...
+/// });
+/// ```
+#[derive(Clone)]
+pub struct HttpClient {
+    cache_dir: PathBuf,
+}
+
+impl HttpClient {
+    pub fn new<P: AsRef<Path>>(cache_dir: P) -> Self {
+        Self {
+            cache_dir: cache_dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Start downloading a file. Cached files are returned without a download.
+    pub fn fetch(&self, url: &str) -> HttpRequest {
+        let (sender, receiver) = channel();
+
+        start_request(url.to_string(), self.get_cache_path(url), sender);
+
+        HttpRequest {
+            url: url.to_string(),
+            receiver,
+        }
+    }
+
+    /// Download a file, blocking until it's done.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fetch_blocking(&self, url: &str) -> Result<Vec<u8>> {
+        fetch_cached(url, &self.get_cache_path(url))
+    }
+
+    /// Where a URL is cached. The file may not exist yet.
+    pub fn get_cache_path(&self, url: &str) -> PathBuf {
+        // Keep the extension so that cached files can be opened by type.
+        let file_name = url.rsplit('/').next().unwrap_or_default();
+        let extension = Path::new(file_name)
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+
+        self.cache_dir
+            .join(format!("{:016x}{}", hash_url(url), extension))
+    }
+
+    pub fn is_cached(&self, url: &str) -> bool {
+        self.get_cache_path(url).exists()
+    }
+
+    /// Delete all cached downloads.
+    pub fn clear_cache(&self) -> Result<()> {
+        if self.cache_dir.exists() {
+            std::fs::remove_dir_all(&self.cache_dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// A download in progress.
+pub struct HttpRequest {
+    url: String,
+    receiver: Receiver<Result<Vec<u8>>>,
+}
+
+impl HttpRequest {
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the result if the download has finished. Returns it only once.
+    pub fn poll(&mut self) -> Option<Result<Vec<u8>>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!(
+                "Request for {} was dropped before finishing",
+                self.url
+            ))),
+        }
+    }
+}
+
+impl Future for HttpRequest {
+    type Output = Result<Vec<u8>>;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Engine tasks are polled every frame, so there's no need to register the waker.
+        match HttpRequest::poll(&mut self) {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Serves "http://" or "https://" URIs through the VFS. Reads block until the
+/// download finishes, so prefer `HttpClient::fetch` for large files.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct HttpBackend {
+    scheme: String,
+    client: HttpClient,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpBackend {
+    pub fn new(scheme: &str, client: HttpClient) -> Self {
+        Self {
+            scheme: scheme.to_string(),
+            client,
+        }
+    }
+
+    fn get_url(&self, path: &str) -> String {
+        format!("{}://{}", self.scheme, path)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl VfsBackend for HttpBackend {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.client.fetch_blocking(&self.get_url(path))
+    }
+
+    fn write(&mut self, path: &str, _data: &[u8]) -> Result<()> {
+        bail!("Cannot write {}: remote files are read-only", path)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        let url = self.get_url(path);
+
+        self.client.is_cached(&url)
+            || reqwest::blocking::Client::new()
+                .head(&url)
+                .send()
+                .is_ok_and(|r| r.status().is_success())
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        bail!("Cannot list {}: remote directories are not supported", dir)
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    /// Only cached files have a path on disk.
+    fn resolve_path(&self, path: &str) -> Option<PathBuf> {
+        let cache_path = self.client.get_cache_path(&self.get_url(path));
+        cache_path.exists().then_some(cache_path)
+    }
+}
+
+/// FNV-1a, which unlike the std hasher is stable across builds.
+fn hash_url(url: &str) -> u64 {
+    url.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fetch_cached(url: &str, cache_path: &Path) -> Result<Vec<u8>> {
+    if let Ok(data) = std::fs::read(cache_path) {
+        return Ok(data);
+    }
+
+    log::info!("Downloading {}", url);
+
+    let response = reqwest::blocking::get(url)?;
+    if !response.status().is_success() {
+        bail!("HTTP {} for {}", response.status(), url);
+    }
+    let data = response.bytes()?.to_vec();
+
+    // Write to a temporary file first, so an interrupted write doesn't leave a broken cache.
+    if let Some(parent) = cache_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp_path = cache_path.with_extension("part");
+    std::fs::write(&temp_path, &data)?;
+    std::fs::rename(&temp_path, cache_path)?;
+
+    Ok(data)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn start_request(url: String, cache_path: PathBuf, sender: Sender<Result<Vec<u8>>>) {
+    std::thread::spawn(move || {
+        // The request may have been dropped, nothing to do then.
+        let _ = sender.send(fetch_cached(&url, &cache_path));
+    });
+}
+
+/// The browser keeps its own HTTP cache, so there is no local copy on the web.
+#[cfg(target_arch = "wasm32")]
+fn start_request(url: String, _cache_path: PathBuf, sender: Sender<Result<Vec<u8>>>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = sender.send(fetch_web(&url).await);
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch_web(url: &str) -> Result<Vec<u8>> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().ok_or_else(|| anyhow!("No window to fetch {}", url))?;
+
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(|e| anyhow!("Failed to fetch {}: {:?}", url, e))?
+        .dyn_into()
+        .map_err(|_| anyhow!("Invalid response for {}", url))?;
+
+    if !response.ok() {
+        bail!("HTTP {} for {}", response.status(), url);
+    }
+
+    let buffer = response
+        .array_buffer()
+        .map_err(|e| anyhow!("Failed to read {}: {:?}", url, e))?;
+    let buffer = JsFuture::from(buffer)
+        .await
+        .map_err(|e| anyhow!("Failed to read {}: {:?}", url, e))?;
+
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
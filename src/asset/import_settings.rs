@@ -0,0 +1,78 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Deserialize)]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Deserialize)]
+pub enum TextureWrap {
+    #[default]
+    Repeat,
+    ClampToEdge,
+    MirrorRepeat,
+}
+
+/// How an image is imported as a texture.
+///
+/// Read from an optional RON sidecar next to the image, e.g. `player.png.meta`:
+/// ```ron
+/// (filter: Nearest, wrap: ClampToEdge, srgb: true, mipmaps: false, premultiply_alpha: false)
+/// ```
+/// Missing fields use their defaults.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TextureImportSettings {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    /// Treat color data as sRGB. Disable for normal maps and other data textures.
+    /// 16-bit and float images are always linear.
+    pub srgb: bool,
+    pub mipmaps: bool,
+    /// Multiply color by alpha at load time. All engine pipelines blend with
+    /// premultiplied alpha, so only disable this for non-color data.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for TextureImportSettings {
+    fn default() -> Self {
+        Self {
+            filter: TextureFilter::Linear,
+            wrap: TextureWrap::Repeat,
+            srgb: true,
+            mipmaps: false,
+            premultiply_alpha: true,
+        }
+    }
+}
+
+impl TextureImportSettings {
+    pub const META_EXTENSION: &'static str = "meta";
+
+    pub fn from_ron(source: &str) -> Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    /// Settings from the sidecar of an image file, or the defaults if there is none.
+    pub fn load_for<P: AsRef<Path>>(image_path: P) -> Self {
+        let mut meta_path = image_path.as_ref().as_os_str().to_owned();
+        meta_path.push(".");
+        meta_path.push(Self::META_EXTENSION);
+
+        match std::fs::read_to_string(&meta_path) {
+            Ok(source) => Self::from_ron_or_default(&source, &meta_path.to_string_lossy()),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub(crate) fn from_ron_or_default(source: &str, label: &str) -> Self {
+        Self::from_ron(source).unwrap_or_else(|e| {
+            log::warn!("Invalid texture import settings {}: {}", label, e);
+            Self::default()
+        })
+    }
+}
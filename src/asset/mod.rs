@@ -1,5 +1,21 @@
 pub(crate) mod asset_server;
+pub(crate) mod gltf;
+pub(crate) mod http;
 pub(crate) mod image;
+pub(crate) mod import_settings;
+pub(crate) mod pack;
+pub(crate) mod registry;
+pub(crate) mod thumbnail;
+pub(crate) mod tiled;
+pub(crate) mod vfs;
 
+pub use self::gltf::*;
 pub use asset_server::*;
+pub use http::*;
 pub use image::*;
+pub use import_settings::*;
+pub use pack::*;
+pub use registry::*;
+pub use thumbnail::*;
+pub use tiled::*;
+pub use vfs::*;
@@ -0,0 +1,206 @@
+use crate::asset::VfsBackend;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Pack file layout (little endian):
+/// - magic "EPAK", format version (u32), entry count (u32)
+/// - index: for each entry, path length (u16), UTF-8 path, data offset (u64),
+///   compressed size (u64), uncompressed size (u64)
+/// - zlib compressed file data
+const PACK_MAGIC: &[u8; 4] = b"EPAK";
+const PACK_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    compressed_size: u64,
+    size: u64,
+}
+
+/// Bundles files into a single compressed pack.
+#[derive(Default)]
+pub struct PackBuilder {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, path: &str, data: Vec<u8>) {
+        self.files.insert(path.replace('\\', "/"), data);
+    }
+
+    /// Add all files under a directory, keyed by their path relative to it.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        let mut pending = vec![dir.to_path_buf()];
+
+        while let Some(current) = pending.pop() {
+            for entry in std::fs::read_dir(&current)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    let relative = path.strip_prefix(dir)?.to_string_lossy().into_owned();
+                    self.add_file(&relative, std::fs::read(&path)?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        // Compress everything first so that offsets are known.
+        let mut blobs = Vec::with_capacity(self.files.len());
+        for data in self.files.values() {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            blobs.push(encoder.finish()?);
+        }
+
+        let index_size: usize = self.files.keys().map(|p| 2 + p.len() + 8 * 3).sum();
+        let mut offset = (4 + 4 + 4 + index_size) as u64;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(PACK_MAGIC);
+        out.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+
+        for ((path, data), blob) in self.files.iter().zip(&blobs) {
+            out.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            out.extend_from_slice(path.as_bytes());
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&(blob.len() as u64).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            offset += blob.len() as u64;
+        }
+
+        for blob in &blobs {
+            out.extend_from_slice(blob);
+        }
+
+        std::fs::write(path.as_ref(), out)
+            .with_context(|| format!("Failed to write pack {:?}", path.as_ref()))
+    }
+}
+
+/// Read-only VFS backend serving files from a pack.
+pub struct PackBackend {
+    path: PathBuf,
+    entries: BTreeMap<String, PackEntry>,
+}
+
+impl PackBackend {
+    /// Open a pack and read its index. File data is read on demand.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = BufReader::new(
+            File::open(&path).with_context(|| format!("Failed to open pack {:?}", path))?,
+        );
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != PACK_MAGIC {
+            bail!("Not a pack file: {:?}", path);
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != PACK_VERSION {
+            bail!("Unsupported pack version {} in {:?}", version, path);
+        }
+
+        let count = read_u32(&mut reader)?;
+        let mut entries = BTreeMap::new();
+
+        for _ in 0..count {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+
+            let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+            reader.read_exact(&mut name)?;
+
+            let entry = PackEntry {
+                offset: read_u64(&mut reader)?,
+                compressed_size: read_u64(&mut reader)?,
+                size: read_u64(&mut reader)?,
+            };
+
+            entries.insert(String::from_utf8(name)?, entry);
+        }
+
+        Ok(Self { path, entries })
+    }
+
+    pub fn get_file_count(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl VfsBackend for PackBackend {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let entry = self
+            .entries
+            .get(path)
+            .ok_or_else(|| anyhow!("File not found in pack: {}", path))?;
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+
+        let mut data = Vec::with_capacity(entry.size as usize);
+        ZlibDecoder::new(file.take(entry.compressed_size)).read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    fn write(&mut self, path: &str, _data: &[u8]) -> Result<()> {
+        bail!("Cannot write {}: pack files are read-only", path)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir.trim_end_matches('/'))
+        };
+
+        let mut entries: Vec<String> = self
+            .entries
+            .keys()
+            .filter_map(|k| k.strip_prefix(&prefix))
+            .map(|rest| rest.split('/').next().unwrap().to_string())
+            .collect();
+        entries.dedup();
+
+        Ok(entries)
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
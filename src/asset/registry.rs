@@ -0,0 +1,218 @@
+use crate::render::material::MaterialId;
+use crate::render::{MeshId, TextureId};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// Identifies a loaded asset in its cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AssetKey {
+    Texture(TextureId),
+    Mesh(MeshId),
+    Material(MaterialId),
+    /// Font ID in the text server.
+    Font(String),
+}
+
+/// A counted reference to a registered asset.
+///
+/// An asset stays loaded while any handle to it, or to an asset depending on it, is alive.
+#[derive(Clone, Debug)]
+pub struct AssetHandle {
+    key: Rc<AssetKey>,
+}
+
+impl AssetHandle {
+    pub fn get_key(&self) -> &AssetKey {
+        &self.key
+    }
+
+    pub fn get_texture(&self) -> Option<TextureId> {
+        match *self.key {
+            AssetKey::Texture(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn get_mesh(&self) -> Option<MeshId> {
+        match *self.key {
+            AssetKey::Mesh(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn get_material(&self) -> Option<MaterialId> {
+        match *self.key {
+            AssetKey::Material(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn get_font(&self) -> Option<&str> {
+        match &*self.key {
+            AssetKey::Font(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// An untracked handle, for a texture made directly through the cache.
+/// It doesn't keep a registered texture loaded, use the handle from
+/// `AssetServer::load_texture` for that.
+impl From<TextureId> for AssetHandle {
+    fn from(id: TextureId) -> Self {
+        Self {
+            key: Rc::new(AssetKey::Texture(id)),
+        }
+    }
+}
+
+struct AssetEntry {
+    /// Kept by the registry itself, so it doesn't count as a user.
+    handle: AssetHandle,
+    /// Usually the path the asset was loaded from.
+    label: String,
+    size: u64,
+    /// Assets this one uses, e.g. the textures of a material.
+    dependencies: Vec<AssetHandle>,
+}
+
+/// A live asset, as listed by `AssetServer::report`.
+#[derive(Clone, Debug)]
+pub struct AssetInfo {
+    pub key: AssetKey,
+    pub label: String,
+    /// Approximate GPU memory in bytes, not including dependencies.
+    pub size: u64,
+    /// Handles held outside the registry, including by dependent assets.
+    pub ref_count: usize,
+    pub dependencies: Vec<AssetKey>,
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.key {
+            AssetKey::Texture(_) => "Texture",
+            AssetKey::Mesh(_) => "Mesh",
+            AssetKey::Material(_) => "Material",
+            AssetKey::Font(_) => "Font",
+        };
+
+        write!(
+            f,
+            "{:<8} {:>10.1} KiB  refs: {:<3} {}",
+            kind,
+            self.size as f64 / 1024.0,
+            self.ref_count,
+            self.label
+        )
+    }
+}
+
+/// Tracks reference counts and dependencies of loaded assets.
+///
+/// Only registered assets are freed automatically. Resources created directly
+/// through the caches are left alone.
+#[derive(Default)]
+pub struct AssetRegistry {
+    entries: HashMap<AssetKey, AssetEntry>,
+    /// Lookup by label, so the same file isn't loaded twice.
+    labels: HashMap<String, AssetKey>,
+}
+
+impl AssetRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking an asset. Returns the first handle to it.
+    pub fn add(
+        &mut self,
+        key: AssetKey,
+        label: &str,
+        size: u64,
+        dependencies: Vec<AssetHandle>,
+    ) -> AssetHandle {
+        let handle = AssetHandle {
+            key: Rc::new(key.clone()),
+        };
+
+        self.labels.insert(label.to_string(), key.clone());
+        self.entries.insert(
+            key,
+            AssetEntry {
+                handle: handle.clone(),
+                label: label.to_string(),
+                size,
+                dependencies,
+            },
+        );
+
+        handle
+    }
+
+    pub fn get(&self, key: &AssetKey) -> Option<AssetHandle> {
+        self.entries.get(key).map(|e| e.handle.clone())
+    }
+
+    pub fn get_by_label(&self, label: &str) -> Option<AssetHandle> {
+        self.labels.get(label).and_then(|key| self.get(key))
+    }
+
+    pub fn get_ref_count(&self, key: &AssetKey) -> usize {
+        self.entries
+            .get(key)
+            .map_or(0, |e| Rc::strong_count(&e.handle.key) - 1)
+    }
+
+    pub fn get_asset_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Forget all assets that have no handles left. Dropping an asset releases
+    /// its dependencies, so this repeats until nothing else becomes unused.
+    pub(crate) fn collect_unused(&mut self) -> Vec<AssetKey> {
+        let mut unused = vec![];
+
+        loop {
+            let keys: Vec<AssetKey> = self
+                .entries
+                .iter()
+                .filter(|(_, e)| Rc::strong_count(&e.handle.key) == 1)
+                .map(|(k, _)| k.clone())
+                .collect();
+
+            if keys.is_empty() {
+                break;
+            }
+
+            for key in keys {
+                if let Some(entry) = self.entries.remove(&key) {
+                    if self.labels.get(&entry.label) == Some(&key) {
+                        self.labels.remove(&entry.label);
+                    }
+                }
+                unused.push(key);
+            }
+        }
+
+        unused
+    }
+
+    pub fn get_live_assets(&self) -> Vec<AssetInfo> {
+        let mut assets: Vec<AssetInfo> = self
+            .entries
+            .iter()
+            .map(|(key, e)| AssetInfo {
+                key: key.clone(),
+                label: e.label.clone(),
+                size: e.size,
+                ref_count: Rc::strong_count(&e.handle.key) - 1,
+                dependencies: e.dependencies.iter().map(|d| d.get_key().clone()).collect(),
+            })
+            .collect();
+        assets.sort_by_key(|a| std::cmp::Reverse(a.size));
+
+        assets
+    }
+}
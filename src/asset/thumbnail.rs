@@ -0,0 +1,159 @@
+use anyhow::{bail, Context, Result};
+use cgmath::{Deg, InnerSpace, Rad, Vector2, Vector3};
+use std::path::{Path, PathBuf};
+
+use crate::core::HeadlessApp;
+use crate::scene::{AsNode3d, Camera2d, Camera3d, Label, Model, PointLight, World};
+
+/// A camera angle around the asset, in degrees.
+#[derive(Debug, Copy, Clone)]
+pub struct ThumbnailView {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl ThumbnailView {
+    /// `count` views evenly spaced around the asset, looking slightly down.
+    pub fn orbit(count: u32) -> Vec<Self> {
+        (0..count)
+            .map(|i| Self {
+                yaw: 360.0 * i as f32 / count as f32,
+                pitch: -20.0,
+            })
+            .collect()
+    }
+}
+
+/// Renders preview images of models and fonts, without a window.
+pub struct Thumbnailer {
+    app: HeadlessApp,
+    /// Models are rendered once per view. Fonts have a single image.
+    pub views: Vec<ThumbnailView>,
+}
+
+impl Thumbnailer {
+    /// Thumbnails are `size` pixels square.
+    pub fn new(size: u32) -> Result<Self> {
+        let mut app = HeadlessApp::new(size, size)?;
+
+        // Just the asset.
+        app.render_world.grid_settings.enabled_3d = false;
+
+        Ok(Self {
+            app,
+            views: ThumbnailView::orbit(4),
+        })
+    }
+
+    /// Render an asset and save the images to `out_dir` as `<name>_<view>.png`.
+    pub fn render(&mut self, path: &Path, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let images = match extension.as_str() {
+            "obj" => self.render_model(path)?,
+            "ttf" | "otf" => self.render_font(path)?,
+            "svg" => bail!("SVG rendering isn't available yet"),
+            _ => bail!("Unsupported asset type: {}", path.display()),
+        };
+
+        std::fs::create_dir_all(out_dir)?;
+
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        let mut paths = vec![];
+        for (i, image) in images.into_iter().enumerate() {
+            let out_path = out_dir.join(format!("{}_{}.png", stem, i));
+            image.save(&out_path)?;
+            paths.push(out_path);
+        }
+
+        Ok(paths)
+    }
+
+    fn render_model(&mut self, path: &Path) -> Result<Vec<image::RgbaImage>> {
+        let app = &mut self.app;
+        app.change_scene(World::new(Vector2::new(0, 0)));
+
+        let model = Model::load(
+            &mut app.render_world.texture_cache,
+            &mut app.render_world.mesh_render_resources.material_cache,
+            &mut app.render_world.mesh_cache,
+            &mut app.singletons.asset_server.registry,
+            &app.singletons.render_server,
+            path,
+        )
+        .with_context(|| format!("Failed to load {}", path.display()))?;
+
+        // Fit the model's bounding sphere in the view.
+        let aabb = model.get_local_aabb();
+        let center = (aabb.min + aabb.max) * 0.5;
+        let radius = ((aabb.max - aabb.min).magnitude() * 0.5).max(0.001);
+        let distance = radius / Rad::from(Deg(45.0f32 / 2.0)).0.sin();
+
+        app.add_node(model, None);
+
+        let mut light = PointLight::new();
+        light.set_position(center + Vector3::new(radius, radius * 2.0, radius) * 2.0);
+        light.strength = 5.0;
+        app.add_node(light, None);
+
+        let mut images = vec![];
+
+        for view in &self.views {
+            let (yaw, pitch) = (Rad::from(Deg(view.yaw)), Rad::from(Deg(view.pitch)));
+            let forward = Vector3::new(
+                pitch.0.cos() * yaw.0.cos(),
+                pitch.0.sin(),
+                pitch.0.cos() * yaw.0.sin(),
+            );
+            let position = center - forward * distance;
+
+            let camera = Camera3d::new(
+                (position.x, position.y, position.z),
+                yaw,
+                pitch,
+                &app.singletons.render_server,
+            );
+            let camera_id = app.add_node(camera, None);
+
+            app.update(0.0);
+            images.push(app.render()?);
+
+            app.get_world_mut().remove_node(camera_id);
+        }
+
+        Ok(images)
+    }
+
+    fn render_font(&mut self, path: &Path) -> Result<Vec<image::RgbaImage>> {
+        let app = &mut self.app;
+        app.change_scene(World::new(Vector2::new(0, 0)));
+
+        let path = path
+            .to_str()
+            .context("Font paths have to be valid UTF-8")?
+            .to_string();
+
+        let font = app.singletons.asset_server.load_font(
+            &path,
+            &mut app.singletons.text_server,
+            &app.singletons.render_server,
+            &mut app.render_world.texture_cache,
+        );
+
+        app.add_node(Camera2d::default(), None);
+
+        let mut label = Label::default();
+        label.set_text("Aa Bb\n123".to_string());
+        label.set_font(font);
+        app.add_node(label, None);
+
+        app.update(0.0);
+
+        Ok(vec![app.render()?])
+    }
+}
@@ -0,0 +1,454 @@
+use crate::math::rect::Rect2;
+use crate::render::{Texture, TextureCache};
+use crate::scene::{TileCell, TileLayer, TileMap, TileSet};
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use cgmath::Vector2;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x80000000;
+const FLIPPED_VERTICALLY: u32 = 0x40000000;
+/// Flag bits of a global tile ID, the rest is the ID.
+const FLIP_MASK: u32 = 0xf0000000;
+
+/// Name or class of the object layers and objects that are returned as
+/// collision rectangles.
+const COLLISION_CLASS: &str = "collision";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapObjectShape {
+    Point,
+    Rect(Vector2<f32>),
+    Ellipse(Vector2<f32>),
+    /// Points relative to the object position.
+    Polygon(Vec<Vector2<f32>>),
+    Polyline(Vec<Vector2<f32>>),
+}
+
+/// An object placed in an object layer of a Tiled map.
+#[derive(Debug, Clone)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    /// Called type before Tiled 1.9.
+    pub class: String,
+    /// Top left corner, in map pixels before the tile map's transform.
+    pub position: Vector2<f32>,
+    pub shape: MapObjectShape,
+    /// Custom properties, as written in the file.
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub class: String,
+    pub objects: Vec<MapObject>,
+}
+
+/// A map made with the Tiled editor, loaded from a .tmx file.
+///
+/// Only orthogonal, finite maps are supported. Tile sets may be embedded or in
+/// .tsx files, but need a single image.
+pub struct TiledMap {
+    pub tile_map: TileMap,
+    pub object_layers: Vec<ObjectLayer>,
+}
+
+impl TiledMap {
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        path: P,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Invalid Tiled map path {:?}", path))?;
+        let document = Document::parse(&text)?;
+        let map = document.root_element();
+
+        if map.attribute("orientation") != Some("orthogonal") {
+            bail!("Only orthogonal Tiled maps are supported: {:?}", path);
+        }
+        if map.attribute("infinite") == Some("1") {
+            bail!("Infinite Tiled maps aren't supported: {:?}", path);
+        }
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let mut tile_map = TileMap::new(Vector2::new(
+            parse_attribute(map, "tilewidth")?,
+            parse_attribute(map, "tileheight")?,
+        ));
+
+        // First global tile ID of each tile set.
+        let mut first_gids = vec![];
+
+        for node in map.children().filter(|n| n.has_tag_name("tileset")) {
+            first_gids.push(parse_attribute::<u32>(node, "firstgid")?);
+
+            let tile_set = match node.attribute("source") {
+                Some(source) => {
+                    let tsx_path = dir.join(source);
+                    let text = std::fs::read_to_string(&tsx_path)
+                        .with_context(|| format!("Invalid Tiled tile set path {:?}", tsx_path))?;
+                    let document = Document::parse(&text)?;
+
+                    let tsx_dir = tsx_path.parent().unwrap_or(Path::new(""));
+                    load_tile_set(device, queue, cache, document.root_element(), tsx_dir)?
+                }
+                None => load_tile_set(device, queue, cache, node, dir)?,
+            };
+
+            tile_map.tile_sets.push(tile_set);
+        }
+
+        let mut object_layers = vec![];
+
+        load_layers(
+            map,
+            Vector2::new(0.0, 0.0),
+            true,
+            &first_gids,
+            &mut tile_map.layers,
+            &mut object_layers,
+        )?;
+
+        Ok(Self {
+            tile_map,
+            object_layers,
+        })
+    }
+
+    /// Position of a point object, e.g. "player" for where the player starts.
+    pub fn get_spawn_point(&self, name: &str) -> Option<Vector2<f32>> {
+        self.get_spawn_points()
+            .find(|(n, _)| *n == name)
+            .map(|(_, position)| position)
+    }
+
+    /// Names and positions of all point objects.
+    pub fn get_spawn_points(&self) -> impl Iterator<Item = (&str, Vector2<f32>)> {
+        self.object_layers
+            .iter()
+            .flat_map(|l| &l.objects)
+            .filter(|o| o.shape == MapObjectShape::Point)
+            .map(|o| (o.name.as_str(), o.position))
+    }
+
+    /// Rectangles in object layers named or classed "collision", and rectangle objects
+    /// of that class anywhere. For ray tests and overlap checks.
+    pub fn get_collision_rects(&self) -> Vec<Rect2> {
+        let is_collision = |name: &str, class: &str| {
+            name.eq_ignore_ascii_case(COLLISION_CLASS)
+                || class.eq_ignore_ascii_case(COLLISION_CLASS)
+        };
+
+        self.object_layers
+            .iter()
+            .flat_map(|layer| {
+                let whole_layer = is_collision(&layer.name, &layer.class);
+
+                layer.objects.iter().filter_map(move |o| match o.shape {
+                    MapObjectShape::Rect(size)
+                        if whole_layer || o.class.eq_ignore_ascii_case(COLLISION_CLASS) =>
+                    {
+                        Some(Rect2::from_position_size(o.position, size))
+                    }
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse_attribute<T: std::str::FromStr>(node: Node, name: &str) -> Result<T> {
+    let value = node
+        .attribute(name)
+        .with_context(|| format!("Tiled {} is missing {}", node.tag_name().name(), name))?;
+
+    value.parse().ok().with_context(|| {
+        format!(
+            "Invalid {} in Tiled {}: {}",
+            name,
+            node.tag_name().name(),
+            value
+        )
+    })
+}
+
+fn parse_attribute_or<T: std::str::FromStr>(node: Node, name: &str, default: T) -> T {
+    node.attribute(name)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn load_tile_set(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    cache: &mut TextureCache,
+    node: Node,
+    dir: &Path,
+) -> Result<TileSet> {
+    let name = node.attribute("name").unwrap_or_default();
+
+    let image = node
+        .children()
+        .find(|n| n.has_tag_name("image"))
+        .with_context(|| {
+            format!(
+                "Tiled tile sets of separate images aren't supported: {}",
+                name
+            )
+        })?;
+
+    let texture = Texture::load(
+        device,
+        queue,
+        cache,
+        dir.join(parse_attribute::<String>(image, "source")?),
+    )?;
+    let texture_size = cache.get(texture).unwrap().size;
+
+    let mut animations = HashMap::new();
+
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        let Some(animation) = tile.children().find(|n| n.has_tag_name("animation")) else {
+            continue;
+        };
+
+        let frames = animation
+            .children()
+            .filter(|n| n.has_tag_name("frame"))
+            .map(|frame| {
+                // Durations are in milliseconds.
+                Ok((
+                    parse_attribute(frame, "tileid")?,
+                    parse_attribute::<f32>(frame, "duration")? / 1000.0,
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        animations.insert(parse_attribute(tile, "id")?, frames);
+    }
+
+    Ok(TileSet {
+        texture,
+        texture_size,
+        tile_size: (
+            parse_attribute(node, "tilewidth")?,
+            parse_attribute(node, "tileheight")?,
+        ),
+        columns: parse_attribute(node, "columns")?,
+        margin: parse_attribute_or(node, "margin", 0),
+        spacing: parse_attribute_or(node, "spacing", 0),
+        animations,
+    })
+}
+
+/// Load the layers under `parent`, flattening groups into their offset and visibility.
+fn load_layers(
+    parent: Node,
+    offset: Vector2<f32>,
+    visible: bool,
+    first_gids: &[u32],
+    tile_layers: &mut Vec<TileLayer>,
+    object_layers: &mut Vec<ObjectLayer>,
+) -> Result<()> {
+    for node in parent.children().filter(|n| n.is_element()) {
+        let name = node.attribute("name").unwrap_or_default();
+        let offset = offset
+            + Vector2::new(
+                parse_attribute_or(node, "offsetx", 0.0),
+                parse_attribute_or(node, "offsety", 0.0),
+            );
+        let visible = visible && node.attribute("visible") != Some("0");
+
+        match node.tag_name().name() {
+            "layer" => {
+                let mut layer = TileLayer::new(
+                    name,
+                    parse_attribute(node, "width")?,
+                    parse_attribute(node, "height")?,
+                );
+                layer.offset = offset;
+                layer.visible = visible;
+
+                let data = node
+                    .children()
+                    .find(|n| n.has_tag_name("data"))
+                    .with_context(|| format!("Tiled layer has no data: {}", name))?;
+
+                let gids = parse_layer_data(data)?;
+                if gids.len() != layer.cells.len() {
+                    bail!("Tiled layer has the wrong number of tiles: {}", name);
+                }
+
+                for (cell, gid) in layer.cells.iter_mut().zip(gids) {
+                    *cell = to_cell(gid, first_gids);
+                }
+
+                tile_layers.push(layer);
+            }
+            "objectgroup" => {
+                let objects = node
+                    .children()
+                    .filter(|n| n.has_tag_name("object"))
+                    .map(|object| load_object(object, offset))
+                    .collect::<Result<_>>()?;
+
+                object_layers.push(ObjectLayer {
+                    name: name.to_string(),
+                    class: node.attribute("class").unwrap_or_default().to_string(),
+                    objects,
+                });
+            }
+            "group" => {
+                load_layers(
+                    node,
+                    offset,
+                    visible,
+                    first_gids,
+                    tile_layers,
+                    object_layers,
+                )?;
+            }
+            // Image layers and properties.
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Global tile IDs, 0 for empty cells.
+fn parse_layer_data(data: Node) -> Result<Vec<u32>> {
+    if data.children().any(|n| n.has_tag_name("chunk")) {
+        bail!("Infinite Tiled maps aren't supported");
+    }
+
+    let text = data.text().unwrap_or_default().trim();
+
+    match data.attribute("encoding") {
+        Some("csv") => text
+            .split(',')
+            .map(|gid| gid.trim().parse().context("Invalid tile in Tiled CSV data"))
+            .collect(),
+        Some("base64") => {
+            let bytes = base64::engine::general_purpose::STANDARD.decode(text)?;
+
+            let mut decoded = vec![];
+            match data.attribute("compression") {
+                None => decoded = bytes,
+                Some("zlib") => {
+                    ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+                }
+                Some("gzip") => {
+                    GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+                }
+                Some(compression) => bail!("Unsupported Tiled compression: {}", compression),
+            }
+
+            Ok(decoded
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        // One element per tile, deprecated.
+        None => Ok(data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| parse_attribute_or(tile, "gid", 0))
+            .collect()),
+        Some(encoding) => bail!("Unsupported Tiled encoding: {}", encoding),
+    }
+}
+
+fn to_cell(gid: u32, first_gids: &[u32]) -> Option<TileCell> {
+    let id = gid & !FLIP_MASK;
+    if id == 0 {
+        return None;
+    }
+
+    // Tile sets are in order of their first IDs.
+    let tile_set = first_gids.iter().rposition(|first| *first <= id)?;
+
+    Some(TileCell {
+        tile_set,
+        tile: id - first_gids[tile_set],
+        flip_x: gid & FLIPPED_HORIZONTALLY != 0,
+        flip_y: gid & FLIPPED_VERTICALLY != 0,
+    })
+}
+
+fn load_object(node: Node, offset: Vector2<f32>) -> Result<MapObject> {
+    let mut position = offset
+        + Vector2::new(
+            parse_attribute_or(node, "x", 0.0),
+            parse_attribute_or(node, "y", 0.0),
+        );
+    let size = Vector2::new(
+        parse_attribute_or(node, "width", 0.0),
+        parse_attribute_or(node, "height", 0.0),
+    );
+
+    let child = |name: &str| node.children().find(|n| n.has_tag_name(name));
+
+    let parse_points = |points: Node| -> Result<Vec<Vector2<f32>>> {
+        parse_attribute::<String>(points, "points")?
+            .split_whitespace()
+            .map(|point| {
+                let (x, y) = point.split_once(',').context("Invalid Tiled point")?;
+                Ok(Vector2::new(x.parse()?, y.parse()?))
+            })
+            .collect()
+    };
+
+    let shape = if child("point").is_some() {
+        MapObjectShape::Point
+    } else if child("ellipse").is_some() {
+        MapObjectShape::Ellipse(size)
+    } else if let Some(polygon) = child("polygon") {
+        MapObjectShape::Polygon(parse_points(polygon)?)
+    } else if let Some(polyline) = child("polyline") {
+        MapObjectShape::Polyline(parse_points(polyline)?)
+    } else {
+        // Tile objects are placed by their bottom left corner.
+        if node.attribute("gid").is_some() {
+            position.y -= size.y;
+        }
+        MapObjectShape::Rect(size)
+    };
+
+    let mut properties = HashMap::new();
+    if let Some(list) = child("properties") {
+        for property in list.children().filter(|n| n.has_tag_name("property")) {
+            let value = property
+                .attribute("value")
+                .map(str::to_string)
+                .or_else(|| property.text().map(str::to_string))
+                .unwrap_or_default();
+
+            properties.insert(parse_attribute(property, "name")?, value);
+        }
+    }
+
+    Ok(MapObject {
+        id: parse_attribute_or(node, "id", 0),
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        class: node
+            .attribute("class")
+            .or(node.attribute("type"))
+            .unwrap_or_default()
+            .to_string(),
+        position,
+        shape,
+        properties,
+    })
+}
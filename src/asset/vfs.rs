@@ -0,0 +1,237 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Storage behind a VFS scheme. Paths are relative and use forward slashes.
+pub trait VfsBackend {
+    fn read(&self, path: &str) -> Result<Vec<u8>>;
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()>;
+
+    fn exists(&self, path: &str) -> bool;
+
+    /// Names of the entries directly under a directory.
+    fn list(&self, dir: &str) -> Result<Vec<String>>;
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Real path on disk, for APIs that can only take file paths.
+    fn resolve_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Files in a directory on disk.
+pub struct DirectoryBackend {
+    root: PathBuf,
+    read_only: bool,
+}
+
+impl DirectoryBackend {
+    pub fn new<P: AsRef<Path>>(root: P, read_only: bool) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            read_only,
+        }
+    }
+
+    fn full_path(&self, path: &str) -> Result<PathBuf> {
+        // Don't allow escaping the root.
+        if Path::new(path)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            bail!("Invalid VFS path: {}", path);
+        }
+
+        Ok(self.root.join(path))
+    }
+}
+
+impl VfsBackend for DirectoryBackend {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let full_path = self.full_path(path)?;
+        std::fs::read(&full_path).with_context(|| format!("Failed to read {:?}", full_path))
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        if self.read_only {
+            bail!("Cannot write {}: backend is read-only", path);
+        }
+
+        let full_path = self.full_path(path)?;
+
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&full_path, data).with_context(|| format!("Failed to write {:?}", full_path))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.full_path(path).is_ok_and(|p| p.exists())
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let full_path = if dir.is_empty() {
+            self.root.clone()
+        } else {
+            self.full_path(dir)?
+        };
+
+        let mut entries = vec![];
+        for entry in std::fs::read_dir(full_path)? {
+            entries.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        entries.sort();
+
+        Ok(entries)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn resolve_path(&self, path: &str) -> Option<PathBuf> {
+        self.full_path(path).ok()
+    }
+}
+
+/// Files kept in memory. Useful for generated content and tests.
+#[derive(Default)]
+pub struct MemoryBackend {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl VfsBackend for MemoryBackend {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| anyhow!("File not found in memory: {}", path))
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        self.files.insert(path.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>> {
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir.trim_end_matches('/'))
+        };
+
+        let mut entries: Vec<String> = self
+            .files
+            .keys()
+            .filter_map(|k| k.strip_prefix(&prefix))
+            .map(|rest| rest.split('/').next().unwrap().to_string())
+            .collect();
+        entries.sort();
+        entries.dedup();
+
+        Ok(entries)
+    }
+}
+
+/// Maps URI schemes (e.g. "asset://", "user://") to storage backends.
+#[derive(Default)]
+pub struct Vfs {
+    mounts: HashMap<String, Box<dyn VfsBackend>>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mount a backend under a scheme, replacing any previous one.
+    pub fn mount<B: VfsBackend + 'static>(&mut self, scheme: &str, backend: B) {
+        self.mounts.insert(scheme.to_string(), Box::new(backend));
+    }
+
+    pub fn unmount(&mut self, scheme: &str) {
+        self.mounts.remove(scheme);
+    }
+
+    /// Split "scheme://path" into its parts.
+    fn split_uri(uri: &str) -> Result<(&str, &str)> {
+        uri.split_once("://")
+            .ok_or_else(|| anyhow!("Invalid VFS URI (missing scheme): {}", uri))
+    }
+
+    fn get_backend(&self, uri: &str) -> Result<(&dyn VfsBackend, String)> {
+        let (scheme, path) = Self::split_uri(uri)?;
+        let backend = self
+            .mounts
+            .get(scheme)
+            .ok_or_else(|| anyhow!("No backend mounted for scheme: {}", scheme))?;
+
+        Ok((backend.as_ref(), path.trim_start_matches('/').to_string()))
+    }
+
+    pub fn read(&self, uri: &str) -> Result<Vec<u8>> {
+        let (backend, path) = self.get_backend(uri)?;
+        backend.read(&path)
+    }
+
+    pub fn read_to_string(&self, uri: &str) -> Result<String> {
+        Ok(String::from_utf8(self.read(uri)?)?)
+    }
+
+    pub fn write(&mut self, uri: &str, data: &[u8]) -> Result<()> {
+        let (scheme, path) = Self::split_uri(uri)?;
+        let backend = self
+            .mounts
+            .get_mut(scheme)
+            .ok_or_else(|| anyhow!("No backend mounted for scheme: {}", scheme))?;
+
+        backend.write(path.trim_start_matches('/'), data)
+    }
+
+    pub fn exists(&self, uri: &str) -> bool {
+        self.get_backend(uri)
+            .is_ok_and(|(backend, path)| backend.exists(&path))
+    }
+
+    pub fn list(&self, uri: &str) -> Result<Vec<String>> {
+        let (backend, path) = self.get_backend(uri)?;
+        backend.list(path.trim_end_matches('/'))
+    }
+
+    pub fn resolve_path(&self, uri: &str) -> Option<PathBuf> {
+        let (backend, path) = self.get_backend(uri).ok()?;
+        backend.resolve_path(&path)
+    }
+}
+
+/// Per-user writable data directory for the given app.
+pub fn get_user_data_dir(app_name: &str) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Library/Application Support"));
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    base.unwrap_or_else(std::env::temp_dir).join(app_name)
+}
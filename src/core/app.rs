@@ -3,26 +3,37 @@ use std::sync::Arc;
 
 use winit::{
     event::*,
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
     window::{Window, WindowBuilder},
 };
 
-use cgmath::{prelude::*, Vector2};
-use indextree::NodeId;
+use crate::scene::NodeId;
+use cgmath::Vector2;
 
-use crate::core::engine::Engine;
+use crate::core::engine::{Engine, RunMode};
 use wgpu::{util::DeviceExt, SamplerBindingType};
 use winit::dpi::PhysicalSize;
+use winit::keyboard::PhysicalKey;
 use winit::platform::run_on_demand::EventLoopExtRunOnDemand;
 
 // Import local crates.
 use crate::asset::AssetServer;
 use crate::core::singleton::Singletons;
+use crate::net::Network;
+use crate::render::render_server::device_descriptor;
 use crate::render::render_world::RenderWorld;
-use crate::render::{RenderServer, Texture};
-use crate::scene::{AsNode, Camera2d, World};
+use crate::render::{DisplayOutput, DrawSource, FrameRecorder, RenderServer, RenderSettings};
+use crate::scene::{AsNode, SceneManager, World};
 use crate::text::TextServer;
-use crate::window::InputServer;
+use crate::window::{InputServer, WindowServer};
+
+/// Called once before the app exits, for cleaning up and saving.
+pub type ExitCallback = Box<dyn FnMut(&mut World, &mut Singletons)>;
+
+/// Called when the user tries to close the window. Returns whether to exit. To ask first,
+/// e.g. with a "save changes?" dialog, return false and call [`Engine::request_exit`]
+/// once the user confirms.
+pub type CloseRequestHandler = Box<dyn FnMut(&mut World, &mut Singletons) -> bool>;
 
 const INITIAL_WINDOW_WIDTH: u32 = 1280;
 const INITIAL_WINDOW_HEIGHT: u32 = 720;
@@ -37,14 +48,31 @@ pub struct App<'a> {
     /// In order to call EventLoop::run_return from App::run,
     /// we have to put it in an option to avoid borrow errors.
     event_loop: Option<EventLoop<()>>,
+    #[cfg(feature = "debug-server")]
+    debug_server: Option<crate::core::DebugServer>,
+    draw_sources: Vec<Box<dyn DrawSource>>,
+    #[cfg(feature = "egui")]
+    egui_layer: crate::core::egui_layer::EguiLayer,
+    exit_callbacks: Vec<ExitCallback>,
+    close_request_handler: Option<CloseRequestHandler>,
+    /// When the last frame started, for the frame limit.
+    last_redraw: std::time::Instant,
 }
 
 impl<'a> App<'a> {
     pub fn new() -> Self {
+        Self::with_render_settings(RenderSettings::default())
+    }
+
+    /// Render settings can't be changed once the app is created.
+    pub fn with_render_settings(render_settings: RenderSettings) -> Self {
         let env = env_logger::Env::default()
             .filter_or("EUREKA_LOG_LEVEL", "info")
             .write_style_or("EUREKA_LOG_STYLE", "always");
+        #[cfg(not(feature = "debug-server"))]
         env_logger::init_from_env(env);
+        #[cfg(feature = "debug-server")]
+        crate::core::debug_server::DebugLogger::init(env);
 
         let event_loop = EventLoop::new().unwrap();
 
@@ -62,7 +90,14 @@ impl<'a> App<'a> {
         );
 
         // App::init_render uses async code, so we're going to wait for it to finish.
-        let mut render_server = pollster::block_on(App::init_render(window.clone()));
+        let mut render_server =
+            pollster::block_on(App::init_render(window.clone(), render_settings));
+
+        // Uploads queued from loader threads need a frame, also when the app is idle.
+        let upload_window = window.clone();
+        render_server
+            .uploads
+            .set_waker(move || upload_window.request_redraw());
 
         let mut engine = Engine::new();
 
@@ -74,14 +109,44 @@ impl<'a> App<'a> {
 
         let text_server = TextServer::new(&render_server, &mut render_world.texture_cache);
 
-        let singletons = Singletons {
+        let mut singletons = Singletons {
             engine,
             render_server,
             input_server: InputServer::new(),
+            window_server: WindowServer::new(),
             text_server,
             asset_server,
+            scene_manager: SceneManager::new(),
+            network: Network::new(),
+            frame_recorder: FrameRecorder::new(),
+        };
+        // Recordings are 8-bit, so not of HDR output.
+        singletons.frame_recorder.supported = singletons
+            .render_server
+            .surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC)
+            && !singletons
+                .render_server
+                .get_settings()
+                .display_output
+                .is_hdr();
+
+        // Set EUREKA_DEBUG_ADDR to change the address.
+        #[cfg(feature = "debug-server")]
+        let debug_server = {
+            let addr =
+                std::env::var("EUREKA_DEBUG_ADDR").unwrap_or_else(|_| "127.0.0.1:9001".to_string());
+
+            crate::core::DebugServer::new(addr)
+                .map_err(|e| log::warn!("Failed to start debug server: {}", e))
+                .ok()
         };
 
+        #[cfg(feature = "egui")]
+        let egui_layer =
+            crate::core::egui_layer::EguiLayer::new(&window, &singletons.render_server);
+
         Self {
             window,
             window_size,
@@ -90,11 +155,19 @@ impl<'a> App<'a> {
             singletons,
             initialized: false,
             event_loop: Some(event_loop),
+            #[cfg(feature = "debug-server")]
+            debug_server,
+            draw_sources: vec![],
+            #[cfg(feature = "egui")]
+            egui_layer,
+            exit_callbacks: vec![],
+            close_request_handler: None,
+            last_redraw: std::time::Instant::now(),
         }
     }
 
     // Creating some of the wgpu types requires async code.
-    async fn init_render(window: Arc<Window>) -> RenderServer<'a> {
+    async fn init_render(window: Arc<Window>, mut settings: RenderSettings) -> RenderServer<'a> {
         // Context for all other wgpu objects.
         let instance = wgpu::Instance::default();
 
@@ -113,27 +186,41 @@ impl<'a> App<'a> {
 
         // Use the adapter to create a device and a queue.
         let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: Default::default(),
-                },
-                None,
-            )
+            .request_device(&device_descriptor(&adapter), None)
             .await
             .expect("Failed to create device!");
 
         // Get the window's inner size.
         let size = window.inner_size();
 
-        let surface_config = surface
+        let mut surface_config = surface
             .get_default_config(&adapter, size.width, size.height)
             .expect("Surface unsupported by adapter!");
+
+        if let Some(format) = settings.display_output.surface_format() {
+            if surface.get_capabilities(&adapter).formats.contains(&format) {
+                surface_config.format = format;
+            } else {
+                log::warn!(
+                    "Surface doesn't support {:?} output, falling back to SDR",
+                    settings.display_output
+                );
+                settings.display_output = DisplayOutput::Sdr;
+            }
+        }
+
+        // For frame recording.
+        if surface
+            .get_capabilities(&adapter)
+            .usages
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            surface_config.usage |= wgpu::TextureUsages::COPY_SRC;
+        }
         surface.configure(&device, &surface_config);
 
         // Create a render server.
-        RenderServer::new(surface, surface_config, device, queue)
+        RenderServer::new(Some(surface), surface_config, device, queue, settings)
     }
 
     pub fn run(&mut self) {
@@ -155,9 +242,44 @@ impl<'a> App<'a> {
                         ref mut event,
                         window_id,
                     } if window_id == self.window.id() => {
+                        // Events used by egui don't reach the scene.
+                        #[cfg(feature = "egui")]
+                        let egui_consumed = self.egui_layer.on_window_event(&self.window, event);
+                        #[cfg(not(feature = "egui"))]
+                        let egui_consumed = false;
+
+                        // Reactive apps redraw after anything that isn't the redraw itself.
+                        if !matches!(event, WindowEvent::RedrawRequested) {
+                            self.singletons.engine.request_redraw();
+                        }
+
                         match event {
-                            WindowEvent::CloseRequested => elwt.exit(),
+                            WindowEvent::CloseRequested => {
+                                let exit = match &mut self.close_request_handler {
+                                    Some(handler) => handler(&mut self.world, &mut self.singletons),
+                                    None => true,
+                                };
+
+                                if exit {
+                                    self.singletons.engine.request_exit();
+                                }
+                            }
+                            // Nodes always hear about focus, even if egui has it.
+                            WindowEvent::Focused(_) => {
+                                self.input(event);
+                                self.update_background_state();
+                            }
+                            WindowEvent::Occluded(occluded) => {
+                                self.singletons.input_server.set_minimized(*occluded);
+                                self.update_background_state();
+                            }
                             WindowEvent::Resized(physical_size) => {
+                                // Minimized windows get a zero size on some platforms.
+                                self.singletons.input_server.set_minimized(
+                                    physical_size.width == 0 || physical_size.height == 0,
+                                );
+                                self.update_background_state();
+
                                 // See https://github.com/rust-windowing/winit/issues/2094.
                                 if self.initialized {
                                     return;
@@ -167,11 +289,17 @@ impl<'a> App<'a> {
 
                                 log::info!("Window resized to {:?}", physical_size);
                             }
+                            // The window may be on another monitor now.
+                            WindowEvent::Moved(_) => {
+                                self.singletons.window_server.refresh_monitors();
+                            }
                             // Scale factor changed.
                             WindowEvent::ScaleFactorChanged {
                                 scale_factor,
                                 ref mut inner_size_writer,
                             } => {
+                                self.singletons.window_server.refresh_monitors();
+
                                 let new_width =
                                     (self.window_size.width as f64 * *scale_factor) as u32;
                                 let new_height =
@@ -189,38 +317,56 @@ impl<'a> App<'a> {
                             }
                             // Redraw request.
                             WindowEvent::RedrawRequested => {
+                                self.last_redraw = std::time::Instant::now();
+
                                 self.singletons.input_server.update(&self.window);
+                                self.singletons.window_server.update(&self.window);
 
                                 self.update();
 
+                                // Save the GPU while there's nothing to see.
+                                if self.singletons.input_server.is_minimized() {
+                                    return;
+                                }
+
                                 match self.render() {
-                                    Ok(_) => {
-                                        self.window.request_redraw();
-                                    }
+                                    Ok(_) => {}
                                     // Reconfigure the surface if lost.
                                     Err(wgpu::SurfaceError::Lost) => self.resize(self.window_size),
                                     // The system is out of memory, we should probably quit.
-                                    Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
+                                    Err(wgpu::SurfaceError::OutOfMemory) => self.exit(elwt),
                                     // All other errors (Outdated, Timeout) should be resolved by the next frame.
                                     Err(e) => eprintln!("App resource error: {:?}", e),
                                 }
                             }
+                            _ if egui_consumed => {}
                             _ => {
                                 // Other input events should be handled by the input server.
                                 self.input(event);
                             }
                         }
                     }
-                    // Event::MainEventsCleared => {
-                    //     // RedrawRequested will only trigger once, unless we manually request it.
-                    //     self.window.request_redraw();
-                    // }
+                    // RedrawRequested will only trigger once, unless we manually request it.
+                    Event::AboutToWait => {
+                        if self.singletons.engine.is_exit_requested() {
+                            self.exit(elwt);
+                        } else {
+                            self.schedule_redraw(elwt);
+                        }
+                    }
                     Event::NewEvents(cause) => {
                         if cause == StartCause::Init {
                             self.initialized = true;
                         } else {
                             self.initialized = false;
                         }
+
+                        // A budgeted frame is due. Clear the deadline so the next one is set
+                        // from now.
+                        if let StartCause::ResumeTimeReached { .. } = cause {
+                            elwt.set_control_flow(ControlFlow::Wait);
+                            self.window.request_redraw();
+                        }
                     }
                     _ => {}
                 }
@@ -228,8 +374,131 @@ impl<'a> App<'a> {
             .expect("TODO: panic message");
     }
 
-    pub fn add_node(&mut self, new_node: impl AsNode + 'static, parent: Option<NodeId>) {
-        self.world.add_node(Box::new(new_node), parent);
+    /// Run the exit callbacks and leave the event loop.
+    fn exit(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        for mut callback in std::mem::take(&mut self.exit_callbacks) {
+            callback(&mut self.world, &mut self.singletons);
+        }
+
+        elwt.exit();
+    }
+
+    /// Quit at the end of the frame, see [`Engine::request_exit`].
+    pub fn request_exit(&mut self) {
+        self.singletons.engine.request_exit();
+    }
+
+    /// Run `callback` once before the app exits, however it exits.
+    pub fn add_exit_callback(
+        &mut self,
+        callback: impl FnMut(&mut World, &mut Singletons) + 'static,
+    ) {
+        self.exit_callbacks.push(Box::new(callback));
+    }
+
+    /// Decide what happens when the user closes the window, see [`CloseRequestHandler`].
+    pub fn set_close_request_handler(
+        &mut self,
+        handler: impl FnMut(&mut World, &mut Singletons) -> bool + 'static,
+    ) {
+        self.close_request_handler = Some(Box::new(handler));
+    }
+
+    /// Pause or mute the engine according to its background policy.
+    fn update_background_state(&mut self) {
+        let input_server = &self.singletons.input_server;
+        let (focused, minimized) = (input_server.is_focused(), input_server.is_minimized());
+
+        self.singletons
+            .engine
+            .update_background_state(focused, minimized);
+    }
+
+    /// Request the next frame, or wait for events, depending on the run mode.
+    fn schedule_redraw(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        let redraw_requested = self.singletons.engine.take_redraw_request();
+        let run_mode = self.singletons.engine.get_run_mode();
+
+        // Hidden windows only tick now and then, without drawing.
+        if self.singletons.input_server.is_minimized() && run_mode != RunMode::Reactive {
+            let rate = self
+                .singletons
+                .engine
+                .get_background_policy()
+                .hidden_update_rate;
+            Self::wait_for_next_frame(elwt, rate);
+            return;
+        }
+
+        match run_mode {
+            RunMode::Continuous => self.redraw_within_frame_limit(elwt),
+            RunMode::Reactive => {
+                elwt.set_control_flow(ControlFlow::Wait);
+                if redraw_requested {
+                    self.window.request_redraw();
+                }
+            }
+            RunMode::Budgeted { .. }
+                if self.singletons.input_server.is_focused()
+                    && !self.singletons.input_server.is_minimized() =>
+            {
+                self.redraw_within_frame_limit(elwt)
+            }
+            RunMode::Budgeted { background_fps } => Self::wait_for_next_frame(elwt, background_fps),
+        }
+    }
+
+    /// Redraw once `1 / fps` seconds have passed.
+    fn wait_for_next_frame(elwt: &EventLoopWindowTarget<()>, fps: f32) {
+        // Keep waiting for the same deadline while other events come in.
+        if !matches!(elwt.control_flow(), ControlFlow::WaitUntil(_)) {
+            let interval = std::time::Duration::from_secs_f32(1.0 / fps.max(0.01));
+            elwt.set_control_flow(ControlFlow::WaitUntil(std::time::Instant::now() + interval));
+        }
+    }
+
+    /// Redraw now, or wait until the frame limit allows it.
+    fn redraw_within_frame_limit(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        if let Some(fps) = self.singletons.engine.get_frame_limit() {
+            let next_redraw =
+                self.last_redraw + std::time::Duration::from_secs_f32(1.0 / fps.max(1.0));
+
+            if std::time::Instant::now() < next_redraw {
+                elwt.set_control_flow(ControlFlow::WaitUntil(next_redraw));
+                return;
+            }
+        }
+
+        elwt.set_control_flow(ControlFlow::Poll);
+        self.window.request_redraw();
+    }
+
+    pub fn add_node(&mut self, new_node: impl AsNode + 'static, parent: Option<NodeId>) -> NodeId {
+        self.world.add_node(Box::new(new_node), parent)
+    }
+
+    /// Draw from outside the scene tree, e.g. from an external ECS world.
+    pub fn add_draw_source(&mut self, source: impl DrawSource + 'static) {
+        self.draw_sources.push(Box::new(source));
+    }
+
+    /// Show egui windows over the scene. `ui` is called every frame.
+    #[cfg(feature = "egui")]
+    pub fn add_egui_ui(
+        &mut self,
+        ui: impl FnMut(&egui::Context, &mut World, &mut Singletons) + 'static,
+    ) {
+        self.egui_layer.add_ui(Box::new(ui));
+    }
+
+    /// Replace the running scene. Assets only the old scene used are freed at the end of the frame.
+    pub fn change_scene(&mut self, mut world: World) {
+        world.when_view_size_changes(Vector2::new(
+            self.window_size.width,
+            self.window_size.height,
+        ));
+
+        self.world = world;
     }
 
     /// Resize window.
@@ -245,11 +514,36 @@ impl<'a> App<'a> {
             self.singletons
                 .render_server
                 .surface
+                .as_ref()
+                .unwrap()
                 .configure(&self.singletons.render_server.device, config);
 
             self.render_world
                 .recreate_depth_texture(&self.singletons.render_server);
 
+            self.render_world
+                .post_process_render_resources
+                .recreate_textures(
+                    &self.singletons.render_server,
+                    &mut self.render_world.texture_cache,
+                );
+
+            self.render_world
+                .light2d_render_resources
+                .recreate_textures(
+                    &self.singletons.render_server,
+                    &mut self.render_world.texture_cache,
+                );
+
+            self.render_world
+                .backdrop_render_resources
+                .recreate_textures(
+                    &self.singletons.render_server,
+                    &mut self.render_world.texture_cache,
+                );
+
+            // Water and OIT targets follow the 3D render size, resized in RenderWorld::prepare.
+
             self.world
                 .when_view_size_changes(Vector2::new(new_size.width, new_size.height))
         }
@@ -257,6 +551,16 @@ impl<'a> App<'a> {
 
     /// Handle input events.
     fn input(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput { event, .. } = event {
+            if let (PhysicalKey::Code(key), ElementState::Pressed, false) =
+                (event.physical_key, event.state, event.repeat)
+            {
+                self.singletons
+                    .frame_recorder
+                    .on_key_pressed(key, &self.singletons.render_server);
+            }
+        }
+
         // Convert to our own input events.
         self.singletons
             .input_server
@@ -278,23 +582,77 @@ impl<'a> App<'a> {
         //         self.singletons.core_server.get_fps() as i32
         //     ));
 
-        self.world.update(
-            self.singletons.engine.get_delta() as f32,
-            &mut self.singletons,
+        let dt = self.singletons.engine.get_delta() as f32;
+
+        self.singletons.frame_recorder.update(dt);
+
+        self.world.update(dt, &mut self.singletons);
+
+        for source in &mut self.draw_sources {
+            source.update(dt, &mut self.singletons);
+        }
+
+        #[cfg(feature = "egui")]
+        self.egui_layer
+            .update(&self.window, &mut self.world, &mut self.singletons);
+
+        // Replicate after the server has moved its nodes.
+        self.singletons.network.update(dt, &mut self.world);
+
+        if let Some(on_complete) = self.render_world.screen_transition.update(dt) {
+            on_complete(
+                &mut self.world,
+                &mut self.singletons,
+                &mut self.render_world.screen_transition,
+            );
+        }
+
+        let singletons = &mut self.singletons;
+        let next_scene = singletons.scene_manager.update(
+            &singletons.render_server,
+            &mut self.render_world,
+            &mut singletons.asset_server,
+            &mut singletons.text_server,
         );
+        if let Some(world) = next_scene {
+            self.change_scene(world);
+        }
+
+        self.singletons.engine.update_tasks();
+
+        #[cfg(feature = "debug-server")]
+        if let Some(debug_server) = &mut self.debug_server {
+            debug_server.update(&mut self.world, &self.singletons.engine, &self.render_world);
+        }
+
+        self.singletons
+            .asset_server
+            .free_unused(&mut self.render_world, &mut self.singletons.text_server);
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         // Collects draw commands from the scene world.
-        let draw_commands = self.world.queue_draw();
+        let mut draw_commands = self.world.queue_draw();
+
+        for source in &self.draw_sources {
+            source.draw(&mut draw_commands);
+        }
 
         // Extract render entities from the draw commands.
         self.render_world.extract(&draw_commands);
 
+        let engine = &self.singletons.engine;
+        self.render_world
+            .set_time(engine.get_time() as f32, engine.get_delta() as f32);
+
         let render_server = &self.singletons.render_server;
 
         self.render_world.prepare(render_server);
 
+        if self.render_world.has_pending_uploads() {
+            self.singletons.engine.request_redraw();
+        }
+
         // Update server GPU resources.
         self.singletons.text_server.prepare(
             &self.singletons.render_server,
@@ -304,67 +662,45 @@ impl<'a> App<'a> {
         let render_world = &self.render_world;
 
         // First we need to get a frame to draw to.
-        let surface_texture = render_server.surface.get_current_texture()?;
+        let surface_texture = render_server
+            .surface
+            .as_ref()
+            .unwrap()
+            .get_current_texture()?;
 
         // Creates a TextureView with default settings.
         let view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        let depth_texture = render_world
-            .texture_cache
-            .get(render_world.surface_depth_texture)
-            .unwrap();
+        let mut encoder = render_world.encode_frame(render_server, &view);
 
-        // Builds a command buffer that we can then send to the GPU.
-        let mut encoder =
-            render_server
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("main render encoder"),
-                });
+        // Debug UI goes over transitions.
+        #[cfg(feature = "egui")]
+        let egui_command_buffers =
+            self.egui_layer
+                .render(&self.singletons.render_server, &mut encoder, &view);
+        #[cfg(not(feature = "egui"))]
+        let egui_command_buffers = vec![];
 
-        // The RenderPass has all the methods to do the actual drawing.
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("main render pass"),
-                color_attachments: &[
-                    // This is what @location(0) in the fragment shader targets.
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &view, // Change this to change where to draw.
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    }),
-                ],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            self.render_world.render(&mut render_pass);
-        }
+        self.singletons
+            .frame_recorder
+            .capture(&mut encoder, &surface_texture.texture);
 
         // Finish the command encoder to generate a command buffer,
         // then submit it for execution.
+        self.singletons.render_server.queue.submit(
+            egui_command_buffers
+                .into_iter()
+                .chain(std::iter::once(encoder.finish())),
+        );
+
+        self.render_world
+            .track_gpu_frame_time(&self.singletons.render_server);
+
         self.singletons
-            .render_server
-            .queue
-            .submit(std::iter::once(encoder.finish()));
+            .frame_recorder
+            .finish_frame(&self.singletons.render_server);
 
         // Present the swapchain surface.
         surface_texture.present();
@@ -0,0 +1,202 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::core::HeadlessApp;
+use crate::render::RenderStats;
+
+/// Frame times of a benchmark, in milliseconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct FrameTimes {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl FrameTimes {
+    fn from_samples(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        samples.sort_by(f64::total_cmp);
+
+        // Nearest rank, so that every percentile is a frame that happened.
+        let percentile = |p: f64| {
+            let rank = (p * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+
+        Self {
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkResult {
+    pub name: String,
+    /// Measured frames, warmup left out.
+    pub frames: u32,
+    /// Updating the scene, drawing it and waiting for the GPU to finish.
+    pub frame_time: FrameTimes,
+    /// Of the last frame.
+    pub render_stats: RenderStats,
+}
+
+/// Runs scenes without a window and measures their frames, to validate performance
+/// work such as batching and culling. The results can be saved as CSV or JSON.
+/// ```ignore
+/// let mut benchmark = Benchmark::new(300);
+/// benchmark.run("sprites", &mut app, |_, _| {});
+/// benchmark.save("bench.csv")?;
+/// ```
+pub struct Benchmark {
+    /// Frames run before measuring, so that uploads and caches settle.
+    pub warmup_frames: u32,
+    pub frames: u32,
+    /// Scene time per frame, in seconds.
+    pub delta: f32,
+    results: Vec<BenchmarkResult>,
+}
+
+impl Benchmark {
+    /// Measure `frames` frames per benchmark.
+    pub fn new(frames: u32) -> Self {
+        Self {
+            warmup_frames: 10,
+            frames,
+            delta: 1.0 / 60.0,
+            results: vec![],
+        }
+    }
+
+    /// Run the scene of `app`. `each_frame` is called with the frame number before the
+    /// scene is updated, e.g. to animate it, and is part of the frame time.
+    ///
+    /// Frames are drawn offscreen at the app's size, without reading them back.
+    pub fn run(
+        &mut self,
+        name: &str,
+        app: &mut HeadlessApp,
+        mut each_frame: impl FnMut(&mut HeadlessApp, u32),
+    ) -> &BenchmarkResult {
+        let render_server = &app.singletons.render_server;
+        let target = render_server
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("benchmark target"),
+                size: wgpu::Extent3d {
+                    width: render_server.surface_config.width,
+                    height: render_server.surface_config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: render_server.surface_config.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut samples = Vec::with_capacity(self.frames as usize);
+
+        for frame in 0..self.warmup_frames + self.frames {
+            let start = Instant::now();
+
+            each_frame(app, frame);
+            app.update(self.delta);
+            app.render_to_view(&view);
+
+            // Include the GPU work of the frame.
+            app.singletons
+                .render_server
+                .device
+                .poll(wgpu::Maintain::Wait);
+
+            if frame >= self.warmup_frames {
+                samples.push(start.elapsed().as_secs_f64() * 1000.0);
+            }
+        }
+
+        let result = BenchmarkResult {
+            name: name.to_string(),
+            frames: self.frames,
+            frame_time: FrameTimes::from_samples(samples),
+            render_stats: app.render_world.get_render_stats(),
+        };
+
+        log::info!(
+            "Benchmark {}: {:.2} ms median, {:.2} ms p99, {} draw calls",
+            result.name,
+            result.frame_time.p50,
+            result.frame_time.p99,
+            result.render_stats.draw_calls
+        );
+
+        self.results.push(result);
+        self.results.last().unwrap()
+    }
+
+    pub fn get_results(&self) -> &[BenchmarkResult] {
+        &self.results
+    }
+
+    /// One row per benchmark, with a header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "name,frames,mean_ms,p50_ms,p90_ms,p99_ms,max_ms,draw_calls,sprites,\
+            sprite_batches,atlases,meshes,drawn_meshes\n"
+            .to_string();
+
+        for result in &self.results {
+            let times = &result.frame_time;
+            let stats = &result.render_stats;
+
+            let _ = writeln!(
+                csv,
+                "\"{}\",{},{:.3},{:.3},{:.3},{:.3},{:.3},{},{},{},{},{},{}",
+                result.name.replace('"', "\"\""),
+                result.frames,
+                times.mean,
+                times.p50,
+                times.p90,
+                times.p99,
+                times.max,
+                stats.draw_calls,
+                stats.sprites,
+                stats.sprite_batches,
+                stats.atlases,
+                stats.meshes,
+                stats.drawn_meshes
+            );
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.results)?)
+    }
+
+    /// Save the results as CSV or JSON, depending on the extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        let data = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => self.to_csv(),
+            Some("json") => self.to_json()?,
+            _ => bail!("Benchmark results are saved as .csv or .json: {:?}", path),
+        };
+
+        std::fs::write(path, data).with_context(|| format!("Failed to save {:?}", path))
+    }
+}
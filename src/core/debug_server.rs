@@ -0,0 +1,360 @@
+use crate::core::engine::Engine;
+use crate::render::render_world::RenderWorld;
+use crate::scene::NodeId;
+use crate::scene::World;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// Frame stats are sent at most this often, in seconds.
+const STATS_INTERVAL: f64 = 0.1;
+
+/// Log messages kept while no tool is connected.
+const MAX_BUFFERED_LOGS: usize = 1000;
+
+static LOG_BUFFER: Mutex<VecDeque<serde_json::Value>> = Mutex::new(VecDeque::new());
+
+/// Forwards to env_logger and keeps records for the debug server.
+pub(crate) struct DebugLogger {
+    inner: env_logger::Logger,
+}
+
+impl DebugLogger {
+    pub(crate) fn init(env: env_logger::Env) {
+        let inner = env_logger::Builder::from_env(env).build();
+        log::set_max_level(inner.filter());
+
+        if log::set_boxed_logger(Box::new(Self { inner })).is_err() {
+            log::warn!("A logger is already set, logs won't be sent to the debug server.");
+        }
+    }
+}
+
+impl log::Log for DebugLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+
+        self.inner.log(record);
+
+        let mut buffer = LOG_BUFFER.lock().unwrap();
+        if buffer.len() >= MAX_BUFFERED_LOGS {
+            buffer.pop_front();
+        }
+        buffer.push_back(json!({
+            "type": "log",
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        }));
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Commands from debug tools.
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Command {
+    GetTree,
+    /// The selected node's details are streamed along with frame stats.
+    SelectNode {
+        id: usize,
+    },
+    /// Change a transform field (position, rotation, scale) or a field of the node's saved state.
+    SetProperty {
+        id: usize,
+        property: String,
+        value: serde_json::Value,
+    },
+    SetVisible {
+        id: usize,
+        visible: bool,
+    },
+}
+
+struct Client {
+    socket: WebSocket<TcpStream>,
+    selected: Option<usize>,
+}
+
+/// Streams frame stats, the scene tree and logs to connected tools as JSON, and runs their commands.
+///
+/// Try it with any WebSocket client, e.g. send `{"cmd": "select_node", "id": 1}`.
+pub struct DebugServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    last_stats_time: f64,
+    /// To notice when the tree has changed.
+    last_tree: Vec<NodeId>,
+}
+
+impl DebugServer {
+    pub fn new(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        log::info!("Debug server listening on ws://{}", listener.local_addr()?);
+
+        Ok(Self {
+            listener,
+            clients: vec![],
+            last_stats_time: 0.0,
+            last_tree: vec![],
+        })
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        world: &mut World,
+        engine: &Engine,
+        render_world: &RenderWorld,
+    ) {
+        let tree = world.traverse();
+        let tree_changed = tree != self.last_tree;
+        self.last_tree = tree;
+
+        while let Ok((stream, addr)) = self.listener.accept() {
+            match Self::handshake(stream) {
+                Ok(socket) => {
+                    log::info!("Debug client connected from {}", addr);
+
+                    let mut client = Client {
+                        socket,
+                        selected: None,
+                    };
+                    let _ = client
+                        .socket
+                        .send(Message::Text(tree_message(world).to_string()));
+                    self.clients.push(client);
+                }
+                Err(e) => log::warn!("Debug client handshake failed: {}", e),
+            }
+        }
+
+        // Run commands.
+        for client in &mut self.clients {
+            loop {
+                let text = match client.socket.read() {
+                    Ok(Message::Text(text)) => text,
+                    Ok(_) => continue,
+                    Err(_) => break,
+                };
+
+                let reply = match serde_json::from_str::<Command>(&text) {
+                    Ok(command) => run_command(command, client, world),
+                    Err(e) => Err(anyhow!(e)),
+                };
+
+                let reply = reply.unwrap_or_else(|e| {
+                    json!({
+                        "type": "error",
+                        "message": e.to_string(),
+                    })
+                });
+                let _ = client.socket.send(Message::Text(reply.to_string()));
+            }
+        }
+
+        let mut messages = vec![];
+
+        if tree_changed {
+            messages.push(tree_message(world));
+        }
+
+        messages.extend(LOG_BUFFER.lock().unwrap().drain(..));
+
+        let send_stats = engine.get_elapsed() - self.last_stats_time >= STATS_INTERVAL;
+        if send_stats {
+            self.last_stats_time = engine.get_elapsed();
+
+            messages.push(json!({
+                "type": "stats",
+                "fps": engine.get_fps(),
+                "delta": engine.get_delta(),
+                "elapsed": engine.get_elapsed(),
+                "node_count": self.last_tree.len(),
+                "gpu_memory": gpu_memory_stats(render_world),
+            }));
+        }
+
+        for client in &mut self.clients {
+            for message in &messages {
+                let _ = client.socket.send(Message::Text(message.to_string()));
+            }
+
+            if send_stats {
+                if let Some(id) = client.selected {
+                    let message = node_message(world, id).unwrap_or_else(|e| {
+                        client.selected = None;
+                        json!({
+                            "type": "error",
+                            "message": e.to_string(),
+                        })
+                    });
+                    let _ = client.socket.send(Message::Text(message.to_string()));
+                }
+            }
+        }
+
+        // Drop disconnected clients.
+        self.clients
+            .retain_mut(|client| match client.socket.flush() {
+                Ok(_) => true,
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => true,
+                Err(_) => {
+                    log::info!("Debug client disconnected");
+                    false
+                }
+            });
+    }
+
+    fn handshake(stream: TcpStream) -> Result<WebSocket<TcpStream>> {
+        // Block for the handshake, so it completes in one go.
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+        let socket = tungstenite::accept(stream).map_err(|e| anyhow!("{}", e))?;
+        socket.get_ref().set_nonblocking(true)?;
+
+        Ok(socket)
+    }
+}
+
+fn run_command(
+    command: Command,
+    client: &mut Client,
+    world: &mut World,
+) -> Result<serde_json::Value> {
+    match command {
+        Command::GetTree => Ok(tree_message(world)),
+        Command::SelectNode { id } => {
+            let message = node_message(world, id)?;
+            client.selected = Some(id);
+            Ok(message)
+        }
+        Command::SetProperty {
+            id,
+            property,
+            value,
+        } => {
+            let node_id = find_node(world, id)?;
+            set_property(world, node_id, &property, value)?;
+            node_message(world, id)
+        }
+        Command::SetVisible { id, visible } => {
+            let node_id = find_node(world, id)?;
+            world.set_visible(node_id, visible);
+            node_message(world, id)
+        }
+    }
+}
+
+/// Node IDs are sent as their arena index.
+fn find_node(world: &World, id: usize) -> Result<NodeId> {
+    world
+        .traverse()
+        .into_iter()
+        .find(|node_id| node_id.index() == id)
+        .ok_or_else(|| anyhow!("No node with ID {}", id))
+}
+
+fn tree_message(world: &World) -> serde_json::Value {
+    fn node_json(world: &World, id: NodeId) -> serde_json::Value {
+        let children: Vec<serde_json::Value> = world
+            .arena
+            .children(id)
+            .map(|child| node_json(world, child))
+            .collect();
+
+        json!({
+            "id": id.index(),
+            "node_type": world.arena[id].node_type().to_string(),
+            "visible": world.is_visible(id),
+            "children": children,
+        })
+    }
+
+    let root = world.traverse().first().map(|root| node_json(world, *root));
+
+    json!({
+        "type": "tree",
+        "root": root,
+    })
+}
+
+fn node_message(world: &mut World, id: usize) -> Result<serde_json::Value> {
+    let node_id = find_node(world, id)?;
+
+    Ok(json!({
+        "type": "node",
+        "id": id,
+        "node_type": world.arena[node_id].node_type().to_string(),
+        "visible": world.is_visible(node_id),
+        "transform": world.get_transform_state(node_id),
+        "state": world.arena[node_id].save_state(),
+    }))
+}
+
+fn set_property(
+    world: &mut World,
+    id: NodeId,
+    property: &str,
+    value: serde_json::Value,
+) -> Result<()> {
+    // Transform fields, e.g. {"D2": {"position": [0, 0], ...}}.
+    if let Some(transform) = world.get_transform_state(id) {
+        let mut transform_json = serde_json::to_value(&transform)?;
+
+        if let Some(fields) = transform_json
+            .as_object_mut()
+            .and_then(|variant| variant.values_mut().next())
+            .and_then(|fields| fields.as_object_mut())
+        {
+            if fields.contains_key(property) {
+                fields.insert(property.to_string(), value);
+                world.set_transform_state(id, &serde_json::from_value(transform_json)?);
+                return Ok(());
+            }
+        }
+    }
+
+    let node = &mut world.arena[id];
+
+    if let Some(mut state) = node.save_state() {
+        if let Some(fields) = state.as_object_mut() {
+            if fields.contains_key(property) {
+                fields.insert(property.to_string(), value);
+                return node.load_state(&state);
+            }
+        }
+    }
+
+    bail!("{} has no property {}", node.node_type(), property)
+}
+
+/// Bytes in use per category, and the budget if there is one.
+fn gpu_memory_stats(render_world: &RenderWorld) -> serde_json::Value {
+    let usage = render_world.get_gpu_memory();
+
+    json!({
+        "textures": usage.textures,
+        "meshes": usage.meshes,
+        "uniforms": usage.uniforms,
+        "total": usage.total(),
+        "budget": render_world.gpu_memory_budget,
+    })
+}
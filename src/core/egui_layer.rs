@@ -0,0 +1,149 @@
+use crate::core::singleton::Singletons;
+use crate::render::RenderServer;
+use crate::scene::World;
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+/// Builds egui windows every frame. It can inspect and change the scene.
+pub type EguiUi = Box<dyn FnMut(&egui::Context, &mut World, &mut Singletons)>;
+
+/// Draws egui over everything else, for debug tools.
+pub(crate) struct EguiLayer {
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    uis: Vec<EguiUi>,
+
+    /// Output of the last update, drawn in the next render.
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures_delta: egui::TexturesDelta,
+    pixels_per_point: f32,
+}
+
+impl EguiLayer {
+    pub(crate) fn new(window: &Window, render_server: &RenderServer) -> Self {
+        let context = egui::Context::default();
+
+        let state = egui_winit::State::new(
+            context.clone(),
+            context.viewport_id(),
+            window,
+            Some(window.scale_factor() as f32),
+            Some(render_server.device.limits().max_texture_dimension_2d as usize),
+        );
+
+        let renderer = egui_wgpu::Renderer::new(
+            &render_server.device,
+            render_server.surface_config.format,
+            None,
+            1,
+        );
+
+        Self {
+            state,
+            renderer,
+            uis: vec![],
+            paint_jobs: vec![],
+            textures_delta: Default::default(),
+            pixels_per_point: 1.0,
+        }
+    }
+
+    pub(crate) fn add_ui(&mut self, ui: EguiUi) {
+        self.uis.push(ui);
+    }
+
+    /// Returns true if egui used the event, so the scene shouldn't get it.
+    pub(crate) fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        window: &Window,
+        world: &mut World,
+        singletons: &mut Singletons,
+    ) {
+        if self.uis.is_empty() {
+            return;
+        }
+
+        let raw_input = self.state.take_egui_input(window);
+        let context = self.state.egui_ctx().clone();
+
+        let output = context.run(raw_input, |ctx| {
+            for ui in &mut self.uis {
+                ui(ctx, world, singletons);
+            }
+        });
+
+        self.state
+            .handle_platform_output(window, output.platform_output);
+
+        self.pixels_per_point = output.pixels_per_point;
+        self.paint_jobs = context.tessellate(output.shapes, output.pixels_per_point);
+        self.textures_delta.append(output.textures_delta);
+    }
+
+    /// Returns command buffers to submit before the encoder's.
+    pub(crate) fn render(
+        &mut self,
+        render_server: &RenderServer,
+        encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+    ) -> Vec<wgpu::CommandBuffer> {
+        if self.uis.is_empty() {
+            return vec![];
+        }
+
+        let device = &render_server.device;
+        let queue = &render_server.queue;
+
+        for (id, image_delta) in &self.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [
+                render_server.surface_config.width,
+                render_server.surface_config.height,
+            ],
+            pixels_per_point: self.pixels_per_point,
+        };
+
+        let command_buffers = self.renderer.update_buffers(
+            device,
+            queue,
+            encoder,
+            &self.paint_jobs,
+            &screen_descriptor,
+        );
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("egui render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.renderer
+                .render(&mut render_pass, &self.paint_jobs, &screen_descriptor);
+        }
+
+        for id in &self.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+        self.textures_delta.clear();
+
+        command_buffers
+    }
+}
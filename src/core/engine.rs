@@ -1,15 +1,85 @@
+use crate::core::task::TaskExecutor;
+use crate::math::noise::Rng;
+use std::future::Future;
 use std::time::SystemTime;
 
+/// When the app updates and redraws.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum RunMode {
+    /// Every frame, as fast as presentation allows. For games.
+    #[default]
+    Continuous,
+    /// Only after input, resizing or [`Engine::request_redraw`]. For tools that sit idle
+    /// most of the time. Time keeps passing while idle, so the next delta can be large.
+    Reactive,
+    /// Like continuous while the window is focused and visible, otherwise capped to
+    /// `background_fps` updates per second to save battery.
+    Budgeted { background_fps: f32 },
+}
+
+/// What to do while the window is in the background.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BackgroundPolicy {
+    /// Stop game time while the window doesn't have focus.
+    pub pause_when_unfocused: bool,
+    /// Stop game time while the window is minimized or hidden.
+    pub pause_when_minimized: bool,
+    /// Silence audio while the window doesn't have focus, see [`Engine::is_muted`].
+    pub mute_when_unfocused: bool,
+    /// Updates per second while the window is minimized or hidden. Nothing is drawn
+    /// then, the scene only keeps ticking.
+    pub hidden_update_rate: f32,
+}
+
+impl Default for BackgroundPolicy {
+    fn default() -> Self {
+        Self {
+            pause_when_unfocused: false,
+            pause_when_minimized: true,
+            mute_when_unfocused: false,
+            hidden_update_rate: 10.0,
+        }
+    }
+}
+
 pub struct Engine {
     startup_time: SystemTime,
 
     last_frame_time: SystemTime,
     /// Frame time.
     delta: f64,
+    /// Sum of all deltas.
+    time: f64,
     /// Approximate FPS over the last second.
     fps: f32,
 
     last_time_updated_fps: SystemTime,
+
+    /// Async tasks spanning multiple frames.
+    tasks: TaskExecutor,
+
+    run_mode: RunMode,
+    /// Max frames per second while running continuously.
+    frame_limit: Option<f32>,
+    /// Another frame was asked for, see [`RunMode::Reactive`].
+    redraw_requested: bool,
+    exit_requested: bool,
+
+    background_policy: BackgroundPolicy,
+    /// Paused by the user.
+    paused: bool,
+    /// Window state the background policy was last applied to.
+    window_focused: bool,
+    window_minimized: bool,
+    /// Paused or muted because of the background policy.
+    background_paused: bool,
+    background_muted: bool,
+
+    /// Game-wide randomness, see [`Engine::set_seed`].
+    rng: Rng,
+    seed: u64,
+    /// Delta of every frame in determinism mode, see [`Engine::set_fixed_timestep`].
+    fixed_timestep: Option<f64>,
 }
 
 impl Engine {
@@ -18,35 +88,178 @@ impl Engine {
             startup_time: SystemTime::now(),
             last_frame_time: SystemTime::now(),
             delta: 0.0,
+            time: 0.0,
             fps: 0.0,
             last_time_updated_fps: SystemTime::now(),
+            tasks: TaskExecutor::new(),
+            run_mode: RunMode::default(),
+            frame_limit: None,
+            // For the first frame.
+            redraw_requested: true,
+            exit_requested: false,
+            background_policy: BackgroundPolicy::default(),
+            paused: false,
+            window_focused: true,
+            window_minimized: false,
+            background_paused: false,
+            background_muted: false,
+            rng: Rng::new(0),
+            seed: 0,
+            fixed_timestep: None,
         }
     }
 
+    pub fn get_background_policy(&self) -> BackgroundPolicy {
+        self.background_policy
+    }
+
+    pub fn set_background_policy(&mut self, policy: BackgroundPolicy) {
+        self.background_policy = policy;
+        self.update_background_state(self.window_focused, self.window_minimized);
+    }
+
+    /// While paused the frame delta is zero, so the scene stops moving but still draws
+    /// and gets input. [`Engine::get_elapsed`] keeps counting wall time.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Paused by the user or by the background policy.
+    pub fn is_paused(&self) -> bool {
+        self.paused || self.background_paused
+    }
+
+    /// Whether audio should be silent, because of the background policy.
+    pub fn is_muted(&self) -> bool {
+        self.background_muted
+    }
+
+    /// Apply the background policy to the window's state. Called by the app when it
+    /// gains or loses focus or gets minimized.
+    pub(crate) fn update_background_state(&mut self, focused: bool, minimized: bool) {
+        self.window_focused = focused;
+        self.window_minimized = minimized;
+
+        let policy = self.background_policy;
+
+        self.background_paused =
+            (policy.pause_when_unfocused && !focused) || (policy.pause_when_minimized && minimized);
+        self.background_muted = policy.mute_when_unfocused && !focused;
+    }
+
+    pub fn get_run_mode(&self) -> RunMode {
+        self.run_mode
+    }
+
+    pub fn set_run_mode(&mut self, run_mode: RunMode) {
+        self.run_mode = run_mode;
+        self.redraw_requested = true;
+    }
+
+    pub fn get_frame_limit(&self) -> Option<f32> {
+        self.frame_limit
+    }
+
+    /// Cap the frame rate when drawing every frame, e.g. to the monitor's refresh rate
+    /// from [`crate::window::WindowServer::get_refresh_rate`]. None is uncapped.
+    pub fn set_frame_limit(&mut self, fps: Option<f32>) {
+        self.frame_limit = fps;
+    }
+
+    /// Ask for another frame. Only needed in [`RunMode::Reactive`], e.g. while something
+    /// is animating or after changing the scene from outside an input event.
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+
+    pub(crate) fn take_redraw_request(&mut self) -> bool {
+        std::mem::take(&mut self.redraw_requested)
+    }
+
+    /// Quit at the end of the frame, after running the app's exit callbacks. Unlike
+    /// closing the window, this doesn't go through the close request handler.
+    pub fn request_exit(&mut self) {
+        self.exit_requested = true;
+        self.redraw_requested = true;
+    }
+
+    pub fn is_exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    /// The RNG for game logic. Draw from it only in the same order on every run, e.g.
+    /// from node updates, for replays and lockstep netcode to stay in sync.
+    pub fn get_rng_mut(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restart the RNG from a seed, e.g. the one a replay was recorded with.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn get_fixed_timestep(&self) -> Option<f64> {
+        self.fixed_timestep
+    }
+
+    /// Determinism mode: every frame advances game time by exactly `timestep` seconds,
+    /// however long it really took, so that the simulation only depends on the number of
+    /// frames. Together with [`Engine::set_seed`] and [`crate::scene::World::checksum`]
+    /// this is what replays and lockstep netcode need. None goes back to the wall clock.
+    pub fn set_fixed_timestep(&mut self, timestep: Option<f64>) {
+        self.fixed_timestep = timestep;
+    }
+
     pub fn tick(&mut self) {
         let now = SystemTime::now();
 
+        let mut elapsed = 0.0;
         match self.last_frame_time.elapsed() {
-            Ok(elapsed) => {
-                self.delta = elapsed.as_secs_f64();
+            Ok(wall) => {
+                elapsed = wall.as_secs_f64();
+
+                self.delta = if self.is_paused() {
+                    0.0
+                } else {
+                    self.fixed_timestep.unwrap_or(elapsed)
+                };
             }
             Err(e) => {
                 println!("Error: {:?}", e);
             }
         }
 
-        if self.last_time_updated_fps.elapsed().unwrap().as_secs_f64() > 1.0 {
+        // Measured on the wall clock, also with a fixed timestep.
+        if elapsed > 0.0 && self.last_time_updated_fps.elapsed().unwrap().as_secs_f64() > 1.0 {
             self.last_time_updated_fps = now;
-            self.fps = 1.0 / self.delta as f32;
+            self.fps = 1.0 / elapsed as f32;
         }
 
+        self.time += self.delta;
         self.last_frame_time = now;
     }
 
+    /// Advance by a fixed delta instead of the wall clock, e.g. for headless rendering.
+    pub(crate) fn step(&mut self, delta: f64) {
+        self.delta = delta;
+        self.time += delta;
+    }
+
     pub fn get_delta(&self) -> f64 {
         return self.delta;
     }
 
+    /// Game time in seconds. Stops while paused, unlike [`Engine::get_elapsed`].
+    /// Shaders get it too, e.g. for material wind.
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
     pub fn get_elapsed(&self) -> f64 {
         match self.startup_time.elapsed() {
             Ok(elapsed) => elapsed.as_secs_f64(),
@@ -60,4 +273,19 @@ impl Engine {
     pub fn get_fps(&self) -> f32 {
         self.fps
     }
+
+    /// Run a task over multiple frames. It's polled once per frame after the scene update.
+    pub fn spawn_task<F: Future<Output = ()> + 'static>(&mut self, task: F) {
+        self.tasks.spawn(task);
+    }
+
+    /// Tasks wait on game time, so they stop while paused and follow a fixed timestep.
+    pub(crate) fn update_tasks(&mut self) {
+        self.tasks.update(self.time);
+
+        // Unfinished tasks need more frames.
+        if self.tasks.get_task_count() > 0 {
+            self.request_redraw();
+        }
+    }
 }
@@ -0,0 +1,353 @@
+use crate::scene::NodeId;
+use anyhow::{Context, Result};
+use cgmath::Vector2;
+use image::RgbaImage;
+use std::io::Cursor;
+use std::mem;
+
+use crate::asset::AssetServer;
+use crate::core::engine::Engine;
+use crate::core::singleton::Singletons;
+use crate::net::Network;
+use crate::render::camera::{CameraType, ExtractedCameras};
+use crate::render::draw_command::DrawCommands;
+use crate::render::probe::{
+    convolve_irradiance, cube_face_camera, faces_to_strip, irradiance_file_name, mirror_face,
+    radiance_file_name, PROBE_BAKE_DIR,
+};
+use crate::render::render_world::RenderWorld;
+use crate::render::{FrameRecorder, RenderServer, RenderSettings};
+use crate::scene::{AsNode, AsNode3d, ReflectionProbe, SceneManager, World};
+use crate::text::TextServer;
+use crate::window::{InputEvent, InputServer, WindowServer};
+
+/// Runs the engine without a window, rendering frames to images.
+///
+/// For tools and batch jobs, e.g. generating asset previews.
+pub struct HeadlessApp {
+    world: World,
+    pub render_world: RenderWorld,
+    pub singletons: Singletons<'static>,
+}
+
+impl HeadlessApp {
+    /// Frames are rendered at this size.
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        Self::with_render_settings(width, height, RenderSettings::default())
+    }
+
+    pub fn with_render_settings(
+        width: u32,
+        height: u32,
+        render_settings: RenderSettings,
+    ) -> Result<Self> {
+        let env = env_logger::Env::default()
+            .filter_or("EUREKA_LOG_LEVEL", "info")
+            .write_style_or("EUREKA_LOG_STYLE", "always");
+        let _ = env_logger::try_init_from_env(env);
+
+        let render_server = RenderServer::new_headless(width, height, render_settings)?;
+
+        let mut render_world = RenderWorld::new(&render_server);
+
+        let text_server = TextServer::new(&render_server, &mut render_world.texture_cache);
+
+        let singletons = Singletons {
+            engine: Engine::new(),
+            render_server,
+            input_server: InputServer::new(),
+            window_server: WindowServer::new(),
+            text_server,
+            asset_server: AssetServer::new(),
+            scene_manager: SceneManager::new(),
+            network: Network::new(),
+            frame_recorder: FrameRecorder::new(),
+        };
+
+        Ok(Self {
+            world: World::new(Vector2::new(width, height)),
+            render_world,
+            singletons,
+        })
+    }
+
+    pub fn add_node(&mut self, new_node: impl AsNode + 'static, parent: Option<NodeId>) -> NodeId {
+        self.world.add_node(Box::new(new_node), parent)
+    }
+
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn get_world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn change_scene(&mut self, mut world: World) {
+        let config = &self.singletons.render_server.surface_config;
+        world.when_view_size_changes(Vector2::new(config.width, config.height));
+
+        self.world = world;
+    }
+
+    /// Deliver a synthetic input event to the scene.
+    pub fn input(&mut self, event: InputEvent) {
+        let input_server = &mut self.singletons.input_server;
+        input_server.input_events.clear();
+        input_server.inject_event(event);
+
+        self.world.input(input_server);
+    }
+
+    /// Advance the scene by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.singletons.engine.step(dt as f64);
+
+        self.world.update(dt, &mut self.singletons);
+
+        self.singletons.engine.update_tasks();
+
+        self.singletons
+            .asset_server
+            .free_unused(&mut self.render_world, &mut self.singletons.text_server);
+    }
+
+    /// Change the frame size.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let render_server = &mut self.singletons.render_server;
+        render_server.surface_config.width = width.max(1);
+        render_server.surface_config.height = height.max(1);
+
+        let render_server = &self.singletons.render_server;
+        let render_world = &mut self.render_world;
+
+        render_world.recreate_depth_texture(render_server);
+        render_world
+            .post_process_render_resources
+            .recreate_textures(render_server, &mut render_world.texture_cache);
+        render_world
+            .light2d_render_resources
+            .recreate_textures(render_server, &mut render_world.texture_cache);
+        render_world
+            .backdrop_render_resources
+            .recreate_textures(render_server, &mut render_world.texture_cache);
+        // Water and OIT targets follow the 3D render size, resized in RenderWorld::prepare.
+
+        let config = &render_server.surface_config;
+        self.world
+            .when_view_size_changes(Vector2::new(config.width, config.height));
+    }
+
+    /// Render the scene and read the frame back.
+    pub fn render(&mut self) -> Result<RgbaImage> {
+        let draw_commands = self.world.queue_draw();
+
+        self.render_draw_commands(&draw_commands)
+    }
+
+    /// Render the scene into `view` without reading it back. The view has to be the size of
+    /// the app.
+    pub(crate) fn render_to_view(&mut self, view: &wgpu::TextureView) {
+        let draw_commands = self.world.queue_draw();
+        let encoder = self.encode_draw_commands(&draw_commands, view);
+
+        self.singletons
+            .render_server
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Render every `ReflectionProbe` in the scene, save the cubemaps to "user://probes"
+    /// and load them into the probes. Faces are `face_size` pixels square.
+    ///
+    /// Probes see each other's previous bake, so baking twice adds a light bounce.
+    pub fn bake_probes(&mut self, face_size: u32) -> Result<()> {
+        let probe_ids: Vec<NodeId> = self
+            .world
+            .traverse()
+            .into_iter()
+            .filter(|id| self.world.get_node::<ReflectionProbe>(*id).is_some())
+            .collect();
+
+        if probe_ids.is_empty() {
+            return Ok(());
+        }
+
+        let config = &self.singletons.render_server.surface_config;
+        let size = (config.width, config.height);
+        self.resize(face_size, face_size);
+
+        // Probes capture the scene itself, not the screen effects or editor helpers.
+        let grid_settings = self.render_world.grid_settings.clone();
+        self.render_world.grid_settings.enabled_3d = false;
+        let post_process_settings = mem::take(&mut self.render_world.post_process_settings);
+
+        let result = self.bake_probe_faces(&probe_ids);
+
+        self.render_world.grid_settings = grid_settings;
+        self.render_world.post_process_settings = post_process_settings;
+        self.resize(size.0, size.1);
+
+        result
+    }
+
+    fn bake_probe_faces(&mut self, probe_ids: &[NodeId]) -> Result<()> {
+        let reverse_z = self.singletons.render_server.get_settings().reverse_z;
+
+        for id in probe_ids {
+            let probe = self.world.get_node::<ReflectionProbe>(*id).unwrap();
+            let (name, position) = (probe.name.clone(), probe.get_transform().position);
+
+            let mut faces = vec![];
+
+            for face in 0..6 {
+                let mut draw_commands = self.world.queue_draw();
+
+                // Just the 3D scene, seen from the probe.
+                let extracted = &mut draw_commands.extracted;
+                extracted.cameras = ExtractedCameras::default();
+                extracted.cameras.add(
+                    CameraType::D3,
+                    cube_face_camera(face, position, 0.1, 100.0, reverse_z),
+                );
+                extracted.sprites.clear();
+                extracted.lights_2d.clear();
+                extracted.occluders_2d.clear();
+                extracted.atlases.clear();
+                extracted.clips.clear();
+                extracted.clip_commands.clear();
+                extracted.ui_shapes.clear();
+                extracted.lines_2d.clear();
+                extracted.curves_2d.clear();
+                extracted.gizmo_vertices.clear();
+                extracted.debug_lines.clear();
+                extracted.mesh_normals.clear();
+                self.render_world.sprite_batches.clear();
+
+                let image = self.render_draw_commands(&draw_commands)?;
+                faces.push(mirror_face(&image));
+            }
+
+            let irradiance = convolve_irradiance(&faces);
+
+            let vfs = &mut self.singletons.asset_server.vfs;
+            for (file_name, faces) in [
+                (radiance_file_name(&name), &faces),
+                (irradiance_file_name(&name), &irradiance),
+            ] {
+                let mut png = vec![];
+                faces_to_strip(faces)
+                    .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+
+                let uri = format!("{}/{}", PROBE_BAKE_DIR, file_name);
+                vfs.write(&uri, &png)
+                    .with_context(|| format!("Failed to save {}", uri))?;
+
+                log::info!("Baked {}", uri);
+            }
+
+            let probe = self.world.get_node_mut::<ReflectionProbe>(*id).unwrap();
+            probe.load_baked(
+                &self.singletons.asset_server,
+                &self.singletons.render_server,
+                &mut self.render_world.texture_cache,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_draw_commands(
+        &mut self,
+        draw_commands: &DrawCommands,
+        view: &wgpu::TextureView,
+    ) -> wgpu::CommandEncoder {
+        self.render_world.extract(draw_commands);
+
+        let engine = &self.singletons.engine;
+        self.render_world
+            .set_time(engine.get_time() as f32, engine.get_delta() as f32);
+
+        let render_server = &self.singletons.render_server;
+
+        self.render_world.prepare(render_server);
+
+        self.singletons
+            .text_server
+            .prepare(render_server, &mut self.render_world.texture_cache);
+
+        self.render_world.encode_frame(render_server, view)
+    }
+
+    fn render_draw_commands(&mut self, draw_commands: &DrawCommands) -> Result<RgbaImage> {
+        let render_server = &self.singletons.render_server;
+
+        let config = &render_server.surface_config;
+        let size = wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        };
+
+        let target = render_server
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("headless target texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: config.format,
+                usage: config.usage,
+                view_formats: &[],
+            });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self.encode_draw_commands(draw_commands, &view);
+        let render_server = &self.singletons.render_server;
+
+        let padded_bytes_per_row =
+            (size.width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let readback_buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("headless readback buffer"),
+            size: (padded_bytes_per_row * size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+
+        render_server
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        self.render_world.track_gpu_frame_time(render_server);
+
+        let slice = readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        render_server.device.poll(wgpu::Maintain::Wait);
+
+        let mut pixels = Vec::with_capacity((size.width * size.height * 4) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..(size.width * 4) as usize]);
+            }
+        }
+        readback_buffer.unmap();
+
+        RgbaImage::from_raw(size.width, size.height, pixels).context("Invalid frame size")
+    }
+}
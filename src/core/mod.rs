@@ -1,7 +1,25 @@
 pub mod app;
+pub(crate) mod bench;
+#[cfg(feature = "debug-server")]
+pub(crate) mod debug_server;
+#[cfg(feature = "egui")]
+pub(crate) mod egui_layer;
 pub(crate) mod engine;
+pub(crate) mod headless;
 pub(crate) mod singleton;
+pub(crate) mod state_machine;
+pub(crate) mod task;
+pub(crate) mod ui_test_driver;
 
 pub use app::*;
+pub use bench::*;
+#[cfg(feature = "debug-server")]
+pub use debug_server::*;
+#[cfg(feature = "egui")]
+pub use egui_layer::EguiUi;
 pub use engine::*;
+pub use headless::*;
 pub use singleton::*;
+pub use state_machine::*;
+pub use task::*;
+pub use ui_test_driver::*;
@@ -1,13 +1,19 @@
 use crate::asset::AssetServer;
 use crate::core::engine::Engine;
-use crate::render::RenderServer;
+use crate::net::Network;
+use crate::render::{FrameRecorder, RenderServer};
+use crate::scene::SceneManager;
 use crate::text::TextServer;
-use crate::window::InputServer;
+use crate::window::{InputServer, WindowServer};
 
 pub struct Singletons<'a> {
     pub engine: Engine,
     pub render_server: RenderServer<'a>,
     pub input_server: InputServer,
+    pub window_server: WindowServer,
     pub text_server: TextServer,
     pub asset_server: AssetServer,
+    pub scene_manager: SceneManager,
+    pub network: Network,
+    pub frame_recorder: FrameRecorder,
 }
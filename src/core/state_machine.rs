@@ -0,0 +1,115 @@
+/// Callbacks for a single state. `C` is the data the state operates on.
+pub struct StateHandlers<T, C> {
+    pub on_enter: Option<fn(&mut C)>,
+    /// Returns the next state to switch to, if any.
+    pub on_update: Option<fn(&mut C, f32) -> Option<T>>,
+    pub on_exit: Option<fn(&mut C)>,
+}
+
+impl<T, C> Default for StateHandlers<T, C> {
+    fn default() -> Self {
+        Self {
+            on_enter: None,
+            on_update: None,
+            on_exit: None,
+        }
+    }
+}
+
+/// A finite state machine over a state type `T`, usually a fieldless enum.
+///
+/// Logic for each state lives in its own handlers instead of one big match.
+/// The machine doesn't own the data it drives, so a node can keep the machine
+/// and its data in separate fields and pass the latter to `update`.
+pub struct StateMachine<T, C = ()> {
+    state: T,
+    previous_state: Option<T>,
+    /// Requested by `transition_to`, applied on the next update.
+    pending_state: Option<T>,
+    /// Whether `on_enter` still has to be called for the current state.
+    entered: bool,
+    time_in_state: f32,
+    handlers: Vec<(T, StateHandlers<T, C>)>,
+}
+
+impl<T: Copy + PartialEq, C> StateMachine<T, C> {
+    pub fn new(initial_state: T) -> Self {
+        Self {
+            state: initial_state,
+            previous_state: None,
+            pending_state: None,
+            entered: false,
+            time_in_state: 0.0,
+            handlers: vec![],
+        }
+    }
+
+    /// Register handlers for a state. Replaces existing ones.
+    pub fn add_state(&mut self, state: T, handlers: StateHandlers<T, C>) {
+        self.handlers.retain(|(s, _)| *s != state);
+        self.handlers.push((state, handlers));
+    }
+
+    pub fn get_state(&self) -> T {
+        self.state
+    }
+
+    pub fn get_previous_state(&self) -> Option<T> {
+        self.previous_state
+    }
+
+    /// Seconds spent in the current state.
+    pub fn get_time_in_state(&self) -> f32 {
+        self.time_in_state
+    }
+
+    pub fn is_in(&self, state: T) -> bool {
+        self.state == state
+    }
+
+    /// Switch to another state on the next update.
+    pub fn transition_to(&mut self, state: T) {
+        self.pending_state = Some(state);
+    }
+
+    fn get_handlers(&self, state: T) -> Option<&StateHandlers<T, C>> {
+        self.handlers
+            .iter()
+            .find(|(s, _)| *s == state)
+            .map(|(_, h)| h)
+    }
+
+    fn switch(&mut self, state: T, context: &mut C) {
+        if let Some(on_exit) = self.get_handlers(self.state).and_then(|h| h.on_exit) {
+            on_exit(context);
+        }
+
+        self.previous_state = Some(self.state);
+        self.state = state;
+        self.time_in_state = 0.0;
+        self.entered = false;
+    }
+
+    /// Apply pending transitions and run the current state's handlers.
+    pub fn update(&mut self, context: &mut C, dt: f32) {
+        if let Some(state) = self.pending_state.take() {
+            self.switch(state, context);
+        }
+
+        if !self.entered {
+            self.entered = true;
+
+            if let Some(on_enter) = self.get_handlers(self.state).and_then(|h| h.on_enter) {
+                on_enter(context);
+            }
+        }
+
+        self.time_in_state += dt;
+
+        if let Some(on_update) = self.get_handlers(self.state).and_then(|h| h.on_update) {
+            if let Some(next) = on_update(context, dt) {
+                self.transition_to(next);
+            }
+        }
+    }
+}
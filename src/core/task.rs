@@ -0,0 +1,101 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+thread_local! {
+    /// Game time in seconds, set before tasks are polled.
+    static TASK_TIME: Cell<f64> = const { Cell::new(0.0) };
+}
+
+/// Runs async blocks over multiple frames, polling each task once per frame.
+///
+/// Tasks are `'static`, so share state with nodes through `Rc<RefCell<_>>`.
+/// ```ignore
+/// singletons.engine.spawn_task(async move {
+///     door.borrow_mut().open = true;
+///     wait_seconds(2.0).await;
+///     door.borrow_mut().open = false;
+/// });
+/// ```
+#[derive(Default)]
+pub struct TaskExecutor {
+    tasks: Vec<Pin<Box<dyn Future<Output = ()>>>>,
+}
+
+impl TaskExecutor {
+    pub fn new() -> Self {
+        Self { tasks: vec![] }
+    }
+
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, task: F) {
+        self.tasks.push(Box::pin(task));
+    }
+
+    pub fn get_task_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Poll all tasks once, dropping finished ones.
+    pub(crate) fn update(&mut self, time: f64) {
+        TASK_TIME.with(|t| t.set(time));
+
+        let mut context = Context::from_waker(Waker::noop());
+
+        // Tasks spawned while polling are picked up on the next frame.
+        self.tasks
+            .retain_mut(|task| task.as_mut().poll(&mut context).is_pending());
+    }
+}
+
+/// Suspend the task until the next frame.
+pub fn next_frame() -> NextFrame {
+    NextFrame { yielded: false }
+}
+
+pub struct NextFrame {
+    yielded: bool,
+}
+
+impl Future for NextFrame {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            Poll::Pending
+        }
+    }
+}
+
+/// Suspend the task for some seconds of game time, see [`crate::core::Engine::get_time`].
+pub fn wait_seconds(seconds: f64) -> WaitSeconds {
+    WaitSeconds {
+        seconds,
+        deadline: None,
+    }
+}
+
+pub struct WaitSeconds {
+    seconds: f64,
+    /// Set on the first poll.
+    deadline: Option<f64>,
+}
+
+impl Future for WaitSeconds {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let now = TASK_TIME.with(|t| t.get());
+        let seconds = self.seconds;
+        let deadline = *self.deadline.get_or_insert(now + seconds);
+
+        if now >= deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::scene::NodeId;
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use std::sync::mpsc;
+use winit::keyboard::KeyCode;
+
+use crate::core::headless::HeadlessApp;
+use crate::math::rect::Rect2;
+use crate::scene::{AsNode, UiEvent, World};
+use crate::window::{InputEvent, Key, MouseButton, MouseMotion, MouseScroll};
+
+/// Drives a headless app like a user would, for integration tests of UI behavior.
+///
+/// Input is delivered immediately, and frames only advance on `step`,
+/// always by `frame_time`, so runs are reproducible.
+pub struct UiTestDriver {
+    pub app: HeadlessApp,
+    /// Seconds each `step` advances the scene by.
+    pub frame_time: f32,
+    ui_events: mpsc::Receiver<UiEvent>,
+    /// Frames stepped so far.
+    frame: u64,
+}
+
+impl UiTestDriver {
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        let mut app = HeadlessApp::new(width, height)?;
+        let ui_events = app.get_world_mut().subscribe_ui_events();
+
+        Ok(Self {
+            app,
+            frame_time: 1.0 / 60.0,
+            ui_events,
+            frame: 0,
+        })
+    }
+
+    pub fn add_node(&mut self, node: impl AsNode + 'static, parent: Option<NodeId>) -> NodeId {
+        self.app.add_node(node, parent)
+    }
+
+    /// Swap in another scene, keeping the UI event subscription.
+    pub fn change_scene(&mut self, mut world: World) {
+        self.ui_events = world.subscribe_ui_events();
+        self.app.change_scene(world);
+    }
+
+    pub fn get_node<T: 'static>(&self, id: NodeId) -> Option<&T> {
+        self.app.get_world().get_node::<T>(id)
+    }
+
+    /// First UI node with this name, e.g. a button's text.
+    pub fn find_node(&mut self, name: &str) -> Option<NodeId> {
+        let world = self.app.get_world_mut();
+
+        world.traverse().into_iter().find(|id| {
+            world
+                .get_node_ui_mut(*id)
+                .is_some_and(|node| node.get_ui_name() == name)
+        })
+    }
+
+    pub fn get_frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Advance the scene by one frame.
+    pub fn step(&mut self) {
+        self.app.update(self.frame_time);
+        self.frame += 1;
+    }
+
+    pub fn step_frames(&mut self, count: u32) {
+        for _ in 0..count {
+            self.step();
+        }
+    }
+
+    pub fn mouse_move(&mut self, x: f32, y: f32) {
+        let last = self.app.singletons.input_server.mouse_position;
+
+        self.app.input(InputEvent::MouseMotion(MouseMotion::new(
+            (x - last.0, y - last.1),
+            (x, y),
+        )));
+    }
+
+    pub fn mouse_button(&mut self, button: winit::event::MouseButton, pressed: bool) {
+        let position = self.app.singletons.input_server.mouse_position;
+
+        self.app.input(InputEvent::MouseButton(MouseButton::new(
+            button, pressed, position,
+        )));
+    }
+
+    /// Move to a point and press and release the left button there.
+    pub fn click(&mut self, x: f32, y: f32) {
+        self.mouse_move(x, y);
+        self.mouse_button(winit::event::MouseButton::Left, true);
+        self.mouse_button(winit::event::MouseButton::Left, false);
+    }
+
+    /// Click the center of a UI node.
+    pub fn click_node(&mut self, id: NodeId) -> Result<()> {
+        let node = self
+            .app
+            .get_world_mut()
+            .get_node_ui_mut(id)
+            .context("Not a UI node")?;
+        let center = node.get_position() + node.get_size() * 0.5;
+
+        self.click(center.x, center.y);
+
+        Ok(())
+    }
+
+    pub fn scroll(&mut self, delta: f32) {
+        self.app
+            .input(InputEvent::MouseScroll(MouseScroll::new(delta)));
+    }
+
+    pub fn key(&mut self, key_code: KeyCode, pressed: bool) {
+        self.app.input(InputEvent::Key(Key::new(key_code, pressed)));
+    }
+
+    /// Press and release a key.
+    pub fn tap_key(&mut self, key_code: KeyCode) {
+        self.key(key_code, true);
+        self.key(key_code, false);
+    }
+
+    /// UI events sent since the last call, see [`crate::scene::World::subscribe_ui_events`].
+    pub fn take_ui_events(&mut self) -> Vec<UiEvent> {
+        self.ui_events.try_iter().collect()
+    }
+
+    /// Render the current frame, e.g. to compare against a reference image.
+    pub fn screenshot(&mut self) -> Result<RgbaImage> {
+        self.app.render()
+    }
+
+    /// Where a UI node is on screen.
+    pub fn get_node_rect(&mut self, id: NodeId) -> Option<Rect2> {
+        let node = self.app.get_world_mut().get_node_ui_mut(id)?;
+
+        Some(Rect2::from_position_size(
+            node.get_position(),
+            node.get_size(),
+        ))
+    }
+}
@@ -2,7 +2,14 @@
 pub mod asset;
 pub mod core;
 pub mod math;
+pub mod navigation;
+pub mod net;
+pub mod physics;
 pub mod render;
 pub mod scene;
 pub mod text;
 pub mod window;
+
+// So users get the same egui version.
+#[cfg(feature = "egui")]
+pub use egui;
@@ -0,0 +1,260 @@
+//! Paths made of cubic Bezier segments, in 2D or 3D, that can be walked at an even speed.
+//!
+//! Each point has handles relative to its position, like in vector drawing tools. Segment
+//! lengths are approximated with a polyline, which is rebuilt whenever points change.
+
+use cgmath::{InnerSpace, VectorSpace};
+
+/// Polyline steps per segment for measuring lengths.
+const LENGTH_STEPS: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BezierPoint<V> {
+    pub position: V,
+    /// Handle towards the previous point, relative to the position.
+    pub in_handle: V,
+    /// Handle towards the next point, relative to the position.
+    pub out_handle: V,
+}
+
+impl<V: VectorSpace<Scalar = f32>> BezierPoint<V> {
+    /// A corner point without handles.
+    pub fn new(position: V) -> Self {
+        Self {
+            position,
+            in_handle: V::zero(),
+            out_handle: V::zero(),
+        }
+    }
+
+    /// A smooth point with mirrored handles.
+    pub fn smooth(position: V, handle: V) -> Self {
+        Self {
+            position,
+            in_handle: handle * -1.0,
+            out_handle: handle,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BezierPath<V> {
+    points: Vec<BezierPoint<V>>,
+    /// Connect the last point back to the first.
+    closed: bool,
+    /// Distance from the start at each polyline step, LENGTH_STEPS per segment plus the end.
+    lengths: Vec<f32>,
+}
+
+impl<V> BezierPath<V>
+where
+    V: InnerSpace<Scalar = f32>,
+{
+    pub fn new(points: Vec<BezierPoint<V>>, closed: bool) -> Self {
+        let mut path = Self {
+            points,
+            closed,
+            lengths: vec![],
+        };
+        path.measure();
+
+        path
+    }
+
+    /// Straight lines between the points.
+    pub fn from_positions(positions: &[V], closed: bool) -> Self {
+        Self::new(
+            positions.iter().map(|p| BezierPoint::new(*p)).collect(),
+            closed,
+        )
+    }
+
+    pub fn get_points(&self) -> &[BezierPoint<V>] {
+        &self.points
+    }
+
+    pub fn add_point(&mut self, point: BezierPoint<V>) {
+        self.points.push(point);
+        self.measure();
+    }
+
+    pub fn insert_point(&mut self, index: usize, point: BezierPoint<V>) {
+        self.points.insert(index.min(self.points.len()), point);
+        self.measure();
+    }
+
+    pub fn set_point(&mut self, index: usize, point: BezierPoint<V>) {
+        if let Some(p) = self.points.get_mut(index) {
+            *p = point;
+            self.measure();
+        }
+    }
+
+    pub fn remove_point(&mut self, index: usize) -> Option<BezierPoint<V>> {
+        let point = (index < self.points.len()).then(|| self.points.remove(index));
+        self.measure();
+
+        point
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn set_closed(&mut self, closed: bool) {
+        self.closed = closed;
+        self.measure();
+    }
+
+    pub fn segment_count(&self) -> usize {
+        match self.points.len() {
+            0 | 1 => 0,
+            n if self.closed => n,
+            n => n - 1,
+        }
+    }
+
+    /// Point on a segment, `t` going from 0.0 to 1.0.
+    pub fn sample_segment(&self, segment: usize, t: f32) -> V {
+        let [p0, p1, p2, p3] = self.control_points(segment);
+        let inv = 1.0 - t;
+
+        p0 * (inv * inv * inv)
+            + p1 * (3.0 * inv * inv * t)
+            + p2 * (3.0 * inv * t * t)
+            + p3 * (t * t * t)
+    }
+
+    /// Derivative on a segment, not normalized.
+    fn derivative_segment(&self, segment: usize, t: f32) -> V {
+        let [p0, p1, p2, p3] = self.control_points(segment);
+        let inv = 1.0 - t;
+
+        (p1 - p0) * (3.0 * inv * inv) + (p2 - p1) * (6.0 * inv * t) + (p3 - p2) * (3.0 * t * t)
+    }
+
+    /// Points along the path, `steps` per segment, e.g. for drawing it with a line.
+    pub fn tessellate(&self, steps: usize) -> Vec<V> {
+        let steps = steps.max(1);
+        let mut points: Vec<V> = self
+            .points
+            .first()
+            .map(|p| p.position)
+            .into_iter()
+            .collect();
+
+        for segment in 0..self.segment_count() {
+            points.extend(
+                (1..=steps).map(|step| self.sample_segment(segment, step as f32 / steps as f32)),
+            );
+        }
+
+        points
+    }
+
+    fn control_points(&self, segment: usize) -> [V; 4] {
+        let a = &self.points[segment];
+        let b = &self.points[(segment + 1) % self.points.len()];
+
+        [
+            a.position,
+            a.position + a.out_handle,
+            b.position + b.in_handle,
+            b.position,
+        ]
+    }
+
+    fn measure(&mut self) {
+        self.lengths.clear();
+
+        if self.segment_count() == 0 {
+            return;
+        }
+
+        let mut length = 0.0;
+        self.lengths.push(length);
+
+        for segment in 0..self.segment_count() {
+            let mut prev = self.sample_segment(segment, 0.0);
+
+            for step in 1..=LENGTH_STEPS {
+                let point = self.sample_segment(segment, step as f32 / LENGTH_STEPS as f32);
+                length += (point - prev).magnitude();
+                prev = point;
+                self.lengths.push(length);
+            }
+        }
+    }
+
+    pub fn get_length(&self) -> f32 {
+        self.lengths.last().copied().unwrap_or(0.0)
+    }
+
+    /// Segment, polyline step within it and how far between the step and the next one,
+    /// at a distance from the start, clamped to the path.
+    fn locate(&self, distance: f32) -> Option<(usize, usize, f32)> {
+        if self.lengths.is_empty() {
+            return None;
+        }
+
+        let distance = distance.clamp(0.0, self.get_length());
+        let i = self
+            .lengths
+            .partition_point(|l| *l < distance)
+            .clamp(1, self.lengths.len() - 1);
+
+        let (a, b) = (self.lengths[i - 1], self.lengths[i]);
+        let f = if b > a { (distance - a) / (b - a) } else { 0.0 };
+
+        let step = i - 1;
+        Some((step / LENGTH_STEPS, step % LENGTH_STEPS, f))
+    }
+
+    /// Point at a distance along the path. Walking the distance at a constant rate moves
+    /// at a constant speed, which the segment parameter doesn't.
+    pub fn sample_at_distance(&self, distance: f32) -> Option<V> {
+        if self.points.len() == 1 {
+            return Some(self.points[0].position);
+        }
+
+        // Along the polyline rather than at the interpolated parameter, which would speed
+        // up and slow down within each step.
+        let (segment, step, f) = self.locate(distance)?;
+        let a = self.sample_segment(segment, step as f32 / LENGTH_STEPS as f32);
+        let b = self.sample_segment(segment, (step + 1) as f32 / LENGTH_STEPS as f32);
+
+        Some(a.lerp(b, f))
+    }
+
+    /// Normalized direction of travel at a distance along the path.
+    pub fn tangent_at_distance(&self, distance: f32) -> Option<V> {
+        let (segment, step, f) = self.locate(distance)?;
+        let t = (step as f32 + f) / LENGTH_STEPS as f32;
+        let derivative = self.derivative_segment(segment, t);
+
+        // Handles on top of their points have no derivative at the ends.
+        if derivative.magnitude2() > f32::EPSILON {
+            Some(derivative.normalize())
+        } else {
+            let [p0, _, _, p3] = self.control_points(segment);
+            Some((p3 - p0).normalize())
+        }
+    }
+
+    /// Distance along the path of the closest polyline step to `point`.
+    pub fn closest_distance(&self, point: V) -> f32 {
+        let mut best = (f32::MAX, 0.0);
+
+        for segment in 0..self.segment_count() {
+            for step in 0..=LENGTH_STEPS {
+                let p = self.sample_segment(segment, step as f32 / LENGTH_STEPS as f32);
+                let d = (p - point).magnitude2();
+                if d < best.0 {
+                    best = (d, self.lengths[segment * LENGTH_STEPS + step]);
+                }
+            }
+        }
+
+        best.1
+    }
+}
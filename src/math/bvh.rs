@@ -0,0 +1,330 @@
+//! Bounding volume hierarchy over 3D boxes, for finding what overlaps a box or a ray
+//! without testing everything.
+//!
+//! Items can be added, moved and removed at any time. Each insertion descends towards the
+//! branch whose bounds grow the least, so the tree stays reasonable without rebuilding.
+//! [`Bvh::build`] splits a known set of items top-down, which gives a better tree.
+//!
+//! Ray picking in `World::intersect_ray` and rays against triangle meshes use it.
+
+use crate::physics::{Aabb, Ray3d};
+
+/// Handle to an item in a [`Bvh`]. Stays valid until the item is removed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BvhId(usize);
+
+#[derive(Debug, Clone)]
+enum BvhNodeKind<T> {
+    Leaf(T),
+    Branch([usize; 2]),
+}
+
+#[derive(Debug, Clone)]
+struct BvhNode<T> {
+    aabb: Aabb,
+    parent: Option<usize>,
+    kind: BvhNodeKind<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bvh<T> {
+    /// Removed nodes leave holes, reused by later insertions.
+    nodes: Vec<Option<BvhNode<T>>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    len: usize,
+}
+
+impl<T> Bvh<T> {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            free: vec![],
+            root: None,
+            len: 0,
+        }
+    }
+
+    /// Build a tree from a known set of items, splitting them at the median along the
+    /// longest axis. Returns the IDs of the items in order.
+    pub fn build(items: Vec<(Aabb, T)>) -> (Self, Vec<BvhId>) {
+        let mut bvh = Self::new();
+        let count = items.len();
+
+        let mut leaves: Vec<(usize, Aabb)> = items
+            .into_iter()
+            .map(|(aabb, item)| (bvh.alloc(aabb, None, BvhNodeKind::Leaf(item)), aabb))
+            .collect();
+        let ids = leaves.iter().map(|(i, _)| BvhId(*i)).collect();
+
+        bvh.root = bvh.build_branch(&mut leaves);
+        bvh.len = count;
+
+        (bvh, ids)
+    }
+
+    fn build_branch(&mut self, leaves: &mut [(usize, Aabb)]) -> Option<usize> {
+        match leaves {
+            [] => None,
+            [(leaf, _)] => Some(*leaf),
+            _ => {
+                let bounds = leaves
+                    .iter()
+                    .skip(1)
+                    .fold(leaves[0].1, |bounds, (_, aabb)| bounds.merged(aabb));
+
+                let size = bounds.max - bounds.min;
+                let axis = if size.x >= size.y && size.x >= size.z {
+                    0
+                } else if size.y >= size.z {
+                    1
+                } else {
+                    2
+                };
+
+                let mid = leaves.len() / 2;
+                leaves.select_nth_unstable_by(mid, |(_, a), (_, b)| {
+                    a.center()[axis].total_cmp(&b.center()[axis])
+                });
+
+                let (left, right) = leaves.split_at_mut(mid);
+                let children = [self.build_branch(left)?, self.build_branch(right)?];
+
+                let branch = self.alloc(bounds, None, BvhNodeKind::Branch(children));
+                for child in children {
+                    self.node_mut(child).parent = Some(branch);
+                }
+
+                Some(branch)
+            }
+        }
+    }
+
+    fn alloc(&mut self, aabb: Aabb, parent: Option<usize>, kind: BvhNodeKind<T>) -> usize {
+        let node = Some(BvhNode { aabb, parent, kind });
+
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn dealloc(&mut self, index: usize) -> BvhNode<T> {
+        self.free.push(index);
+        self.nodes[index].take().unwrap()
+    }
+
+    fn node(&self, index: usize) -> &BvhNode<T> {
+        self.nodes[index].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut BvhNode<T> {
+        self.nodes[index].as_mut().unwrap()
+    }
+
+    fn get_leaf(&self, id: BvhId) -> Option<&BvhNode<T>> {
+        self.nodes
+            .get(id.0)?
+            .as_ref()
+            .filter(|node| matches!(node.kind, BvhNodeKind::Leaf(_)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn get(&self, id: BvhId) -> Option<&T> {
+        match &self.get_leaf(id)?.kind {
+            BvhNodeKind::Leaf(item) => Some(item),
+            BvhNodeKind::Branch(_) => None,
+        }
+    }
+
+    pub fn get_aabb(&self, id: BvhId) -> Option<Aabb> {
+        self.get_leaf(id).map(|node| node.aabb)
+    }
+
+    pub fn insert(&mut self, aabb: Aabb, item: T) -> BvhId {
+        let leaf = self.alloc(aabb, None, BvhNodeKind::Leaf(item));
+        self.insert_leaf(leaf);
+        self.len += 1;
+
+        BvhId(leaf)
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(mut sibling) = self.root else {
+            self.root = Some(leaf);
+            return;
+        };
+        let aabb = self.node(leaf).aabb;
+
+        // Go down the branch that grows the least.
+        while let BvhNodeKind::Branch(children) = self.node(sibling).kind {
+            let cost = |child: usize| {
+                let child = &self.node(child).aabb;
+                child.merged(&aabb).surface_area() - child.surface_area()
+            };
+
+            sibling = if cost(children[0]) <= cost(children[1]) {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+
+        // Pair the leaf with the sibling under a new branch.
+        let parent = self.node(sibling).parent;
+        let branch_aabb = self.node(sibling).aabb.merged(&aabb);
+        let branch = self.alloc(branch_aabb, parent, BvhNodeKind::Branch([sibling, leaf]));
+
+        match parent {
+            Some(parent) => self.replace_child(parent, sibling, branch),
+            None => self.root = Some(branch),
+        }
+        self.node_mut(sibling).parent = Some(branch);
+        self.node_mut(leaf).parent = Some(branch);
+
+        self.refit(parent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let BvhNodeKind::Branch(children) = &mut self.node_mut(parent).kind {
+            for child in children.iter_mut().filter(|c| **c == old) {
+                *child = new;
+            }
+        }
+    }
+
+    /// Recompute the bounds of a branch and its ancestors.
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(i) = index {
+            if let BvhNodeKind::Branch([a, b]) = self.node(i).kind {
+                self.node_mut(i).aabb = self.node(a).aabb.merged(&self.node(b).aabb);
+            }
+            index = self.node(i).parent;
+        }
+    }
+
+    /// Take a leaf out of the tree, without freeing it.
+    fn detach_leaf(&mut self, leaf: usize) {
+        let Some(parent) = self.node(leaf).parent else {
+            self.root = None;
+            return;
+        };
+
+        let BvhNodeKind::Branch(children) = self.node(parent).kind else {
+            unreachable!();
+        };
+        let sibling = if children[0] == leaf {
+            children[1]
+        } else {
+            children[0]
+        };
+
+        // The sibling takes the place of the parent.
+        let grandparent = self.dealloc(parent).parent;
+        self.node_mut(sibling).parent = grandparent;
+        match grandparent {
+            Some(grandparent) => self.replace_child(grandparent, parent, sibling),
+            None => self.root = Some(sibling),
+        }
+        self.node_mut(leaf).parent = None;
+
+        self.refit(grandparent);
+    }
+
+    pub fn remove(&mut self, id: BvhId) -> Option<T> {
+        self.get_leaf(id)?;
+
+        self.detach_leaf(id.0);
+        self.len -= 1;
+
+        match self.dealloc(id.0).kind {
+            BvhNodeKind::Leaf(item) => Some(item),
+            BvhNodeKind::Branch(_) => None,
+        }
+    }
+
+    /// Move an item. Returns false if it's not in the tree.
+    pub fn update(&mut self, id: BvhId, aabb: Aabb) -> bool {
+        if self.get_leaf(id).is_none() {
+            return false;
+        }
+
+        self.detach_leaf(id.0);
+        self.node_mut(id.0).aabb = aabb;
+        self.insert_leaf(id.0);
+
+        true
+    }
+
+    /// Visit the leaves of branches accepted by `visit_branch`.
+    fn traverse(&self, visit_branch: impl Fn(&Aabb) -> bool, mut visit_leaf: impl FnMut(usize)) {
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(index) = stack.pop() {
+            let node = self.node(index);
+
+            if !visit_branch(&node.aabb) {
+                continue;
+            }
+
+            match node.kind {
+                BvhNodeKind::Leaf(_) => visit_leaf(index),
+                BvhNodeKind::Branch(children) => stack.extend(children),
+            }
+        }
+    }
+
+    /// Items whose boxes overlap `aabb`.
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<(BvhId, &T)> {
+        let mut found = vec![];
+
+        self.traverse(
+            |node| node.overlaps(aabb),
+            |leaf| found.push((BvhId(leaf), self.get(BvhId(leaf)).unwrap())),
+        );
+
+        found
+    }
+
+    /// Items whose boxes the ray enters within `max_distance`, closest first, with the
+    /// distance where the ray enters them.
+    pub fn query_ray(&self, ray: &Ray3d, max_distance: f32) -> Vec<(BvhId, &T, f32)> {
+        let mut found = vec![];
+
+        self.traverse(
+            |node| node.intersect_ray(ray, max_distance).is_some(),
+            |leaf| {
+                let node = self.node(leaf);
+                if let Some(distance) = node.aabb.intersect_ray(ray, max_distance) {
+                    found.push((BvhId(leaf), self.get(BvhId(leaf)).unwrap(), distance));
+                }
+            },
+        );
+
+        found.sort_by(|a, b| a.2.total_cmp(&b.2));
+        found
+    }
+}
+
+impl<T> Default for Bvh<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
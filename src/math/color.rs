@@ -1,5 +1,7 @@
-use cgmath::Vector3;
+use anyhow::{anyhow, Result};
+use cgmath::{Vector3, Vector4};
 
+/// 8-bit sRGB color, the form colors are usually written and stored in.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ColorU {
     pub r: u8,
@@ -15,11 +17,67 @@ impl ColorU {
     }
 
     pub fn to_vec3(&self) -> Vector3<f32> {
-        Vector3::new(
-            self.r as f32 / 255.0,
-            self.g as f32 / 255.5,
-            self.b as f32 / 255.5,
-        )
+        self.to_f32().to_vec3()
+    }
+
+    #[inline]
+    pub fn to_f32(&self) -> ColorF {
+        ColorF::from(*self)
+    }
+
+    /// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`. The `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<ColorU> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("Invalid hex color: {hex}"));
+        }
+
+        let channel = |i: usize, width: usize| {
+            let value = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).unwrap();
+            // Short forms repeat each digit, so "f" means "ff".
+            if width == 1 {
+                value * 17
+            } else {
+                value
+            }
+        };
+
+        match digits.len() {
+            3 => Ok(ColorU::new(
+                channel(0, 1),
+                channel(1, 1),
+                channel(2, 1),
+                255,
+            )),
+            4 => Ok(ColorU::new(
+                channel(0, 1),
+                channel(1, 1),
+                channel(2, 1),
+                channel(3, 1),
+            )),
+            6 => Ok(ColorU::new(
+                channel(0, 2),
+                channel(1, 2),
+                channel(2, 2),
+                255,
+            )),
+            8 => Ok(ColorU::new(
+                channel(0, 2),
+                channel(1, 2),
+                channel(2, 2),
+                channel(3, 2),
+            )),
+            _ => Err(anyhow!("Invalid hex color: {hex}")),
+        }
+    }
+
+    /// `#rrggbb`, or `#rrggbbaa` if not opaque.
+    pub fn to_hex(&self) -> String {
+        if self.a == 255 {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
     }
 
     #[inline]
@@ -57,3 +115,224 @@ impl ColorU {
         }
     }
 }
+
+impl From<ColorF> for ColorU {
+    fn from(color: ColorF) -> Self {
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        ColorU::new(
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            channel(color.a),
+        )
+    }
+}
+
+/// Float color, with channels in 0.0..=1.0 (or above for HDR).
+///
+/// The channels are in sRGB like [`ColorU`], unless it came from [`ColorF::to_linear`].
+/// Alpha is never gamma-encoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorF {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl ColorF {
+    pub const TRANSPARENT: ColorF = ColorF::new(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: ColorF = ColorF::new(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: ColorF = ColorF::new(1.0, 1.0, 1.0, 1.0);
+
+    #[inline]
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> ColorF {
+        ColorF { r, g, b, a }
+    }
+
+    /// Opaque.
+    #[inline]
+    pub const fn rgb(r: f32, g: f32, b: f32) -> ColorF {
+        ColorF::new(r, g, b, 1.0)
+    }
+
+    /// See [`ColorU::from_hex`].
+    pub fn from_hex(hex: &str) -> Result<ColorF> {
+        ColorU::from_hex(hex).map(ColorF::from)
+    }
+
+    pub fn to_hex(&self) -> String {
+        ColorU::from(*self).to_hex()
+    }
+
+    /// Hue in degrees (0.0..360.0), saturation and value in 0.0..=1.0.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> ColorF {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        let m = value - chroma;
+        ColorF::new(r + m, g + m, b + m, alpha)
+    }
+
+    /// Returns (hue, saturation, value), see [`ColorF::from_hsv`].
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let chroma = max - min;
+
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / chroma + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+
+        (hue, saturation, max)
+    }
+
+    /// From sRGB to linear, which is what lighting math and blending expect.
+    pub fn to_linear(&self) -> ColorF {
+        ColorF::new(
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        )
+    }
+
+    /// From linear back to sRGB.
+    pub fn from_linear(linear: ColorF) -> ColorF {
+        ColorF::new(
+            linear_to_srgb(linear.r),
+            linear_to_srgb(linear.g),
+            linear_to_srgb(linear.b),
+            linear.a,
+        )
+    }
+
+    /// Straight per-channel interpolation.
+    pub fn lerp(&self, other: ColorF, t: f32) -> ColorF {
+        ColorF::new(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+
+    /// Interpolation in OKLab, which keeps brightness even and avoids the muddy middle
+    /// of sRGB blends, e.g. between blue and yellow.
+    pub fn lerp_oklab(&self, other: ColorF, t: f32) -> ColorF {
+        let a = self.to_oklab();
+        let b = other.to_oklab();
+
+        let mut color = ColorF::from_oklab(a + (b - a) * t);
+        color.a = self.a + (other.a - self.a) * t;
+        color
+    }
+
+    fn to_oklab(self) -> Vector3<f32> {
+        let linear = self.to_linear();
+        let (r, g, b) = (linear.r, linear.g, linear.b);
+
+        let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+        let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+        let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+
+        Vector3::new(
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+        )
+    }
+
+    fn from_oklab(lab: Vector3<f32>) -> ColorF {
+        let l = (lab.x + 0.39633778 * lab.y + 0.21580376 * lab.z).powi(3);
+        let m = (lab.x - 0.105561346 * lab.y - 0.06385417 * lab.z).powi(3);
+        let s = (lab.x - 0.08948418 * lab.y - 1.2914855 * lab.z).powi(3);
+
+        ColorF::from_linear(ColorF::rgb(
+            4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+            -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+            -0.0041960864 * l - 0.7034186 * m + 1.7076147 * s,
+        ))
+    }
+
+    /// Multiply RGB by alpha.
+    pub fn premultiplied(&self) -> ColorF {
+        ColorF::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn to_vec3(&self) -> Vector3<f32> {
+        Vector3::new(self.r, self.g, self.b)
+    }
+
+    pub fn to_vec4(&self) -> Vector4<f32> {
+        Vector4::new(self.r, self.g, self.b, self.a)
+    }
+}
+
+impl Default for ColorF {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl From<ColorU> for ColorF {
+    fn from(color: ColorU) -> Self {
+        ColorF::new(
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0,
+            color.a as f32 / 255.0,
+        )
+    }
+}
+
+/// Channels are passed as they are. Convert with [`ColorF::to_linear`] first if the
+/// target expects linear values.
+impl From<ColorF> for wgpu::Color {
+    fn from(color: ColorF) -> Self {
+        wgpu::Color {
+            r: color.r as f64,
+            g: color.g as f64,
+            b: color.b as f64,
+            a: color.a as f64,
+        }
+    }
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
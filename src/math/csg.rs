@@ -0,0 +1,501 @@
+//! Constructive solid geometry on closed triangle meshes.
+//!
+//! Boolean operations are done with BSP trees, following the approach of csg.js.
+//! Results are flat shaded, with normals recomputed from the resulting faces.
+
+use crate::math::transform::Transform3d;
+use crate::render::vertex::Vertex3d;
+use crate::render::Mesh;
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+use std::f32::consts::PI;
+use std::mem;
+
+/// Tolerance used to decide if a point is on a plane.
+const EPSILON: f32 = 1e-5;
+
+const COPLANAR: u8 = 0;
+const FRONT: u8 = 1;
+const BACK: u8 = 2;
+const SPANNING: u8 = 3;
+
+#[derive(Debug, Copy, Clone)]
+struct Plane {
+    normal: Vector3<f32>,
+    w: f32,
+}
+
+impl Plane {
+    /// Returns `None` if the points are (nearly) collinear.
+    fn from_points(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Option<Plane> {
+        let normal = (b - a).cross(c - a);
+        if normal.magnitude2() < EPSILON * EPSILON {
+            return None;
+        }
+
+        let normal = normal.normalize();
+
+        Some(Plane {
+            normal,
+            w: normal.dot(a),
+        })
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.w = -self.w;
+    }
+
+    /// Put the polygon, or its pieces if it spans the plane, into the matching lists.
+    fn split_polygon(
+        &self,
+        polygon: &Polygon,
+        coplanar_front: &mut Vec<Polygon>,
+        coplanar_back: &mut Vec<Polygon>,
+        front: &mut Vec<Polygon>,
+        back: &mut Vec<Polygon>,
+    ) {
+        let mut polygon_type = COPLANAR;
+
+        let types: Vec<u8> = polygon
+            .vertices
+            .iter()
+            .map(|v| {
+                let t = self.normal.dot(*v) - self.w;
+                let vertex_type = if t < -EPSILON {
+                    BACK
+                } else if t > EPSILON {
+                    FRONT
+                } else {
+                    COPLANAR
+                };
+                polygon_type |= vertex_type;
+                vertex_type
+            })
+            .collect();
+
+        match polygon_type {
+            COPLANAR => {
+                if self.normal.dot(polygon.plane.normal) > 0.0 {
+                    coplanar_front.push(polygon.clone());
+                } else {
+                    coplanar_back.push(polygon.clone());
+                }
+            }
+            FRONT => front.push(polygon.clone()),
+            BACK => back.push(polygon.clone()),
+            _ => {
+                let mut f = Vec::new();
+                let mut b = Vec::new();
+
+                let count = polygon.vertices.len();
+                for i in 0..count {
+                    let j = (i + 1) % count;
+                    let (ti, tj) = (types[i], types[j]);
+                    let (vi, vj) = (polygon.vertices[i], polygon.vertices[j]);
+
+                    if ti != BACK {
+                        f.push(vi);
+                    }
+                    if ti != FRONT {
+                        b.push(vi);
+                    }
+
+                    // The edge crosses the plane, so split it.
+                    if ti | tj == SPANNING {
+                        let t = (self.w - self.normal.dot(vi)) / self.normal.dot(vj - vi);
+                        let v = vi + (vj - vi) * t;
+                        f.push(v);
+                        b.push(v);
+                    }
+                }
+
+                // The pieces keep the original plane, which is more stable than recomputing it.
+                if f.len() >= 3 {
+                    front.push(Polygon {
+                        vertices: f,
+                        plane: polygon.plane,
+                    });
+                }
+                if b.len() >= 3 {
+                    back.push(Polygon {
+                        vertices: b,
+                        plane: polygon.plane,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A convex polygon, with vertices in counter-clockwise order when seen from the front.
+#[derive(Debug, Clone)]
+struct Polygon {
+    vertices: Vec<Vector3<f32>>,
+    plane: Plane,
+}
+
+impl Polygon {
+    /// Returns `None` for degenerate polygons.
+    fn new(mut vertices: Vec<Vector3<f32>>) -> Option<Polygon> {
+        vertices.dedup_by(|a, b| (*a - *b).magnitude2() < EPSILON * EPSILON);
+
+        if vertices.len() < 3 {
+            return None;
+        }
+
+        let plane = Plane::from_points(vertices[0], vertices[1], vertices[2])?;
+
+        Some(Polygon { vertices, plane })
+    }
+
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.plane.flip();
+    }
+}
+
+/// A BSP tree node. Polygons coplanar with the node's plane are stored in the node.
+#[derive(Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Node {
+        let mut node = Node::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Swap solid space and empty space.
+    fn invert(&mut self) {
+        for polygon in &mut self.polygons {
+            polygon.flip();
+        }
+
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+
+        mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Remove the parts of `polygons` that are inside this tree.
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let Some(plane) = self.plane else {
+            return polygons;
+        };
+
+        let mut coplanar_front = Vec::new();
+        let mut coplanar_back = Vec::new();
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+
+        for polygon in &polygons {
+            plane.split_polygon(
+                polygon,
+                &mut coplanar_front,
+                &mut coplanar_back,
+                &mut front,
+                &mut back,
+            );
+        }
+
+        front.append(&mut coplanar_front);
+        back.append(&mut coplanar_back);
+
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+
+        // Nothing behind a leaf plane survives.
+        if let Some(node) = &self.back {
+            front.append(&mut node.clip_polygons(back));
+        }
+
+        front
+    }
+
+    /// Remove the parts of the polygons in this tree that are inside `bsp`.
+    fn clip_to(&mut self, bsp: &Node) {
+        self.polygons = bsp.clip_polygons(mem::take(&mut self.polygons));
+
+        if let Some(front) = &mut self.front {
+            front.clip_to(bsp);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(bsp);
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut polygons = self.polygons.clone();
+
+        if let Some(front) = &self.front {
+            polygons.append(&mut front.all_polygons());
+        }
+        if let Some(back) = &self.back {
+            polygons.append(&mut back.all_polygons());
+        }
+
+        polygons
+    }
+
+    /// Add polygons to the tree, splitting them where needed.
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+
+        let mut coplanar_front = Vec::new();
+        let mut coplanar_back = Vec::new();
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+
+        for polygon in &polygons {
+            plane.split_polygon(
+                polygon,
+                &mut coplanar_front,
+                &mut coplanar_back,
+                &mut front,
+                &mut back,
+            );
+        }
+
+        self.polygons.append(&mut coplanar_front);
+        self.polygons.append(&mut coplanar_back);
+
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Default::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Default::default).build(back);
+        }
+    }
+}
+
+/// A solid for boolean operations. Inputs are expected to be closed (watertight)
+/// and to have counter-clockwise front faces.
+#[derive(Debug, Clone, Default)]
+pub struct Csg {
+    polygons: Vec<Polygon>,
+}
+
+impl Csg {
+    pub fn from_triangles(positions: &[[f32; 3]], indices: &[u32]) -> Csg {
+        let polygons = indices
+            .chunks_exact(3)
+            .filter_map(|c| {
+                Polygon::new(
+                    c.iter()
+                        .map(|&i| Vector3::from(positions[i as usize]))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        Csg { polygons }
+    }
+
+    /// Uses the CPU copy of the mesh's positions and indices.
+    pub fn from_mesh(mesh: &Mesh) -> Csg {
+        Csg::from_triangles(&mesh.positions, &mesh.indices)
+    }
+
+    pub fn cuboid(center: Vector3<f32>, half_extents: Vector3<f32>) -> Csg {
+        // Bit 0, 1 and 2 of a corner index pick the max side on x, y and z.
+        let faces = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            center
+                + Vector3::new(
+                    half_extents.x * sign(1),
+                    half_extents.y * sign(2),
+                    half_extents.z * sign(4),
+                )
+        };
+
+        let polygons = faces
+            .iter()
+            .filter_map(|face| Polygon::new(face.iter().map(|&i| corner(i)).collect()))
+            .collect();
+
+        Csg { polygons }
+    }
+
+    /// A UV sphere. `slices` go around the Y axis and `stacks` from pole to pole.
+    pub fn sphere(center: Vector3<f32>, radius: f32, slices: u32, stacks: u32) -> Csg {
+        let slices = slices.max(3);
+        let stacks = stacks.max(2);
+
+        let point = |i: u32, j: u32| {
+            let theta = i as f32 / slices as f32 * PI * 2.0;
+            let phi = j as f32 / stacks as f32 * PI;
+            center
+                + Vector3::new(theta.cos() * phi.sin(), phi.cos(), theta.sin() * phi.sin()) * radius
+        };
+
+        let mut polygons = Vec::new();
+
+        for i in 0..slices {
+            for j in 0..stacks {
+                // Quads at the poles collapse into triangles.
+                let vertices = vec![
+                    point(i, j),
+                    point(i + 1, j),
+                    point(i + 1, j + 1),
+                    point(i, j + 1),
+                ];
+                polygons.extend(Polygon::new(vertices));
+            }
+        }
+
+        Csg { polygons }
+    }
+
+    /// Copy of this solid with `transform` applied.
+    pub fn transformed(&self, transform: &Transform3d) -> Csg {
+        let matrix = Matrix4::from(transform.to_matrix());
+        // A mirroring transform turns the faces inside out.
+        let mirrored = matrix.determinant() < 0.0;
+
+        let polygons = self
+            .polygons
+            .iter()
+            .filter_map(|polygon| {
+                let mut vertices: Vec<_> = polygon
+                    .vertices
+                    .iter()
+                    .map(|v| (matrix * v.extend(1.0)).truncate())
+                    .collect();
+                if mirrored {
+                    vertices.reverse();
+                }
+                Polygon::new(vertices)
+            })
+            .collect();
+
+        Csg { polygons }
+    }
+
+    /// Space inside either solid.
+    pub fn union(&self, other: &Csg) -> Csg {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+
+        Csg {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    /// Space inside this solid but not inside `other`.
+    pub fn subtract(&self, other: &Csg) -> Csg {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+
+        a.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        a.invert();
+
+        Csg {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    /// Space inside both solids.
+    pub fn intersect(&self, other: &Csg) -> Csg {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+
+        a.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        a.build(b.all_polygons());
+        a.invert();
+
+        Csg {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    /// Flat shaded triangles. UVs are planar projections along the dominant axis
+    /// of each face, one unit per texture repeat.
+    pub(crate) fn to_vertices(&self) -> (Vec<Vertex3d>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for polygon in &self.polygons {
+            let normal = polygon.plane.normal;
+
+            // Tangent and bi-tangent follow the UV axes.
+            let (tangent, bi_tangent) = if normal.x.abs() >= normal.y.abs().max(normal.z.abs()) {
+                (Vector3::unit_z(), Vector3::unit_y())
+            } else if normal.y.abs() >= normal.z.abs() {
+                (Vector3::unit_x(), Vector3::unit_z())
+            } else {
+                (Vector3::unit_x(), Vector3::unit_y())
+            };
+
+            let start = vertices.len() as u32;
+
+            for v in &polygon.vertices {
+                vertices.push(Vertex3d {
+                    position: (*v).into(),
+                    // Texture V goes down.
+                    uv: [v.dot(tangent), -v.dot(bi_tangent)],
+                    normal: normal.into(),
+                    tangent: tangent.into(),
+                    bi_tangent: bi_tangent.into(),
+                });
+            }
+
+            // Convex polygons can be triangulated as a fan.
+            for i in 1..polygon.vertices.len() as u32 - 1 {
+                indices.extend_from_slice(&[start, start + i, start + i + 1]);
+            }
+        }
+
+        (vertices, indices)
+    }
+
+    pub fn to_mesh(&self, device: &wgpu::Device, name: &str) -> Mesh {
+        let (vertices, indices) = self.to_vertices();
+
+        Mesh::from_vertices(device, name, &vertices, indices)
+    }
+}
@@ -0,0 +1,208 @@
+use crate::math::color::ColorU;
+use crate::math::easing::Easing;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// A keyframe of a [`Curve`].
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurveKey {
+    pub time: f32,
+    pub value: f32,
+    /// Slope arriving at the key, in value per time. Only used by cubic curves.
+    pub in_tangent: f32,
+    /// Slope leaving the key.
+    pub out_tangent: f32,
+}
+
+impl CurveKey {
+    /// A key with flat tangents.
+    pub fn new(time: f32, value: f32) -> Self {
+        Self {
+            time,
+            value,
+            in_tangent: 0.0,
+            out_tangent: 0.0,
+        }
+    }
+}
+
+/// How a [`Curve`] gets from one key to the next.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum CurveInterpolation {
+    #[default]
+    Linear,
+    /// Hermite spline through the keys, shaped by their tangents.
+    Cubic,
+    /// Every segment follows the same easing function. Tangents are ignored.
+    Eased(Easing),
+}
+
+/// A value over time, e.g. particle size over its lifetime or a camera's height along a
+/// shot. Flat before the first and after the last key.
+///
+/// Curves are plain data and can be saved to and loaded from JSON, so they can be edited
+/// outside the code.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Curve {
+    /// Sorted by time.
+    keys: Vec<CurveKey>,
+    pub interpolation: CurveInterpolation,
+}
+
+impl Curve {
+    /// Linear curve through (time, value) points.
+    pub fn new(points: Vec<(f32, f32)>) -> Self {
+        let mut curve = Self::default();
+        for (time, value) in points {
+            curve.add_key(CurveKey::new(time, value));
+        }
+
+        curve
+    }
+
+    pub fn constant(value: f32) -> Self {
+        Self::new(vec![(0.0, value)])
+    }
+
+    /// From `from` at 0.0 to `to` at 1.0.
+    pub fn linear(from: f32, to: f32) -> Self {
+        Self::new(vec![(0.0, from), (1.0, to)])
+    }
+
+    /// From `from` at 0.0 to `to` at 1.0, following an easing function.
+    pub fn eased(from: f32, to: f32, easing: Easing) -> Self {
+        let mut curve = Self::linear(from, to);
+        curve.interpolation = CurveInterpolation::Eased(easing);
+        curve
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut curve: Curve = serde_json::from_str(json)?;
+        curve.sort();
+        Ok(curve)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn get_keys(&self) -> &[CurveKey] {
+        &self.keys
+    }
+
+    /// Returns the index the key ended up at.
+    pub fn add_key(&mut self, key: CurveKey) -> usize {
+        let index = self.keys.partition_point(|k| k.time <= key.time);
+        self.keys.insert(index, key);
+        index
+    }
+
+    pub fn remove_key(&mut self, index: usize) -> Option<CurveKey> {
+        (index < self.keys.len()).then(|| self.keys.remove(index))
+    }
+
+    /// Replace a key, which may move it if its time changed. Returns its new index.
+    pub fn set_key(&mut self, index: usize, key: CurveKey) -> Option<usize> {
+        self.remove_key(index)?;
+        Some(self.add_key(key))
+    }
+
+    fn sort(&mut self) {
+        self.keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    /// Set every tangent to the slope between the neighbouring keys (Catmull-Rom), which
+    /// gives a smooth curve through the keys.
+    pub fn set_auto_tangents(&mut self) {
+        let slopes: Vec<f32> = (0..self.keys.len())
+            .map(|i| {
+                let prev = &self.keys[i.saturating_sub(1)];
+                let next = &self.keys[(i + 1).min(self.keys.len() - 1)];
+                let dt = next.time - prev.time;
+
+                if dt > 0.0 {
+                    (next.value - prev.value) / dt
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        for (key, slope) in self.keys.iter_mut().zip(slopes) {
+            key.in_tangent = slope;
+            key.out_tangent = slope;
+        }
+    }
+
+    /// 0.0 without keys.
+    pub fn sample(&self, t: f32) -> f32 {
+        let next = self.keys.partition_point(|k| k.time <= t);
+
+        match next {
+            _ if self.keys.is_empty() => 0.0,
+            0 => self.keys[0].value,
+            _ if next == self.keys.len() => self.keys[next - 1].value,
+            _ => {
+                let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+                let dt = b.time - a.time;
+                let f = (t - a.time) / dt;
+
+                match self.interpolation {
+                    CurveInterpolation::Linear => a.value + (b.value - a.value) * f,
+                    CurveInterpolation::Eased(easing) => easing.interpolate(a.value, b.value, f),
+                    CurveInterpolation::Cubic => {
+                        let (f2, f3) = (f * f, f * f * f);
+
+                        (2.0 * f3 - 3.0 * f2 + 1.0) * a.value
+                            + (f3 - 2.0 * f2 + f) * dt * a.out_tangent
+                            + (-2.0 * f3 + 3.0 * f2) * b.value
+                            + (f3 - f2) * dt * b.in_tangent
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Colors over time, e.g. a particle fading from yellow to red to transparent.
+/// Colors are interpolated in OKLab, see [`ColorF::lerp_oklab`].
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    /// (time, color), sorted by time.
+    stops: Vec<(f32, ColorU)>,
+}
+
+impl Gradient {
+    pub fn new(mut stops: Vec<(f32, ColorU)>) -> Self {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Self { stops }
+    }
+
+    /// From `from` at 0.0 to `to` at 1.0.
+    pub fn linear(from: ColorU, to: ColorU) -> Self {
+        Self::new(vec![(0.0, from), (1.0, to)])
+    }
+
+    pub fn get_stops(&self) -> &[(f32, ColorU)] {
+        &self.stops
+    }
+
+    /// Transparent black without stops.
+    pub fn sample(&self, t: f32) -> ColorU {
+        let next = self.stops.partition_point(|(time, _)| *time <= t);
+
+        match next {
+            _ if self.stops.is_empty() => ColorU::transparent_black(),
+            0 => self.stops[0].1,
+            _ if next == self.stops.len() => self.stops[next - 1].1,
+            _ => {
+                let (t0, a) = self.stops[next - 1];
+                let (t1, b) = self.stops[next];
+                let f = (t - t0) / (t1 - t0);
+
+                a.to_f32().lerp_oklab(b.to_f32(), f).into()
+            }
+        }
+    }
+}
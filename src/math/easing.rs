@@ -0,0 +1,184 @@
+//! Easing functions, mapping progress in 0.0..=1.0 to eased progress. `In` starts slow,
+//! `Out` ends slow and `InOut` does both. Back and elastic overshoot past 0.0 and 1.0.
+//!
+//! See <https://easings.net> for what they look like.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    QuartIn,
+    QuartOut,
+    QuartInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    CircIn,
+    CircOut,
+    CircInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    /// Holds the start value until the end.
+    Step,
+    /// CSS-style `cubic-bezier(x1, y1, x2, y2)`, from (0, 0) to (1, 1).
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+    /// `t` is clamped to 0.0..=1.0.
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match *self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => out(t, |t| t * t),
+            Easing::QuadInOut => in_out(t, |t| t * t),
+            Easing::CubicIn => t.powi(3),
+            Easing::CubicOut => out(t, |t| t.powi(3)),
+            Easing::CubicInOut => in_out(t, |t| t.powi(3)),
+            Easing::QuartIn => t.powi(4),
+            Easing::QuartOut => out(t, |t| t.powi(4)),
+            Easing::QuartInOut => in_out(t, |t| t.powi(4)),
+            Easing::SineIn => sine_in(t),
+            Easing::SineOut => out(t, sine_in),
+            Easing::SineInOut => in_out(t, sine_in),
+            Easing::ExpoIn => expo_in(t),
+            Easing::ExpoOut => out(t, expo_in),
+            Easing::ExpoInOut => in_out(t, expo_in),
+            Easing::CircIn => circ_in(t),
+            Easing::CircOut => out(t, circ_in),
+            Easing::CircInOut => in_out(t, circ_in),
+            Easing::BackIn => back_in(t),
+            Easing::BackOut => out(t, back_in),
+            Easing::BackInOut => in_out(t, back_in),
+            Easing::ElasticIn => elastic_in(t),
+            Easing::ElasticOut => out(t, elastic_in),
+            Easing::ElasticInOut => in_out(t, elastic_in),
+            Easing::BounceIn => out(t, bounce_out),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => in_out(t, |t| out(t, bounce_out)),
+            Easing::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(t, x1, y1, x2, y2),
+        }
+    }
+
+    /// Ease between two values.
+    pub fn interpolate(&self, from: f32, to: f32, t: f32) -> f32 {
+        from + (to - from) * self.ease(t)
+    }
+}
+
+/// Mirror an ease-in into an ease-out.
+fn out(t: f32, ease_in: impl Fn(f32) -> f32) -> f32 {
+    1.0 - ease_in(1.0 - t)
+}
+
+/// Ease in for the first half and out for the second.
+fn in_out(t: f32, ease_in: impl Fn(f32) -> f32) -> f32 {
+    if t < 0.5 {
+        ease_in(t * 2.0) * 0.5
+    } else {
+        1.0 - ease_in((1.0 - t) * 2.0) * 0.5
+    }
+}
+
+fn sine_in(t: f32) -> f32 {
+    1.0 - (t * PI * 0.5).cos()
+}
+
+fn expo_in(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else {
+        2.0f32.powf(10.0 * t - 10.0)
+    }
+}
+
+fn circ_in(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).sqrt()
+}
+
+fn back_in(t: f32) -> f32 {
+    const OVERSHOOT: f32 = 1.70158;
+    t * t * ((OVERSHOOT + 1.0) * t - OVERSHOOT)
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t;
+    }
+
+    -(2.0f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Find the curve parameter where x is `t` with Newton's method, then return y there.
+fn cubic_bezier(t: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    let bezier = |s: f32, p1: f32, p2: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+    };
+    let derivative = |s: f32, p1: f32, p2: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * p1 + 6.0 * inv * s * (p2 - p1) + 3.0 * s * s * (1.0 - p2)
+    };
+
+    let mut s = t;
+    for _ in 0..8 {
+        let error = bezier(s, x1, x2) - t;
+        if error.abs() < 1e-6 {
+            break;
+        }
+
+        let slope = derivative(s, x1, x2);
+        if slope.abs() < 1e-6 {
+            break;
+        }
+        s = (s - error / slope).clamp(0.0, 1.0);
+    }
+
+    bezier(s, y1, y2)
+}
@@ -0,0 +1,68 @@
+use crate::math::plane::Plane;
+use crate::physics::Aabb;
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3};
+
+/// Volume a camera sees, bounded by planes facing inwards. For culling things that are
+/// off screen.
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far. The far plane is missing for projections
+    /// that reach to infinity.
+    pub planes: Vec<Plane>,
+}
+
+impl Frustum {
+    /// Extract the planes of a view-projection matrix with wgpu's 0..1 clip depth.
+    /// Works with reversed depth too, near and far just swap places.
+    pub fn from_view_projection(view_projection: Matrix4<f32>) -> Self {
+        // Rows of the matrix, cgmath stores columns.
+        let m = view_projection.transpose();
+        let (x, y, z, w) = (m.x, m.y, m.z, m.w);
+
+        let planes = [w + x, w - x, w + y, w - y, z, w - z]
+            .into_iter()
+            // An infinite far plane comes out with no normal.
+            .filter(|p| p.truncate().magnitude2() > f32::EPSILON)
+            .map(Plane::from_vec4)
+            .collect();
+
+        Self { planes }
+    }
+
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        self.planes.iter().all(|p| p.signed_distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.signed_distance(center) >= -radius)
+    }
+
+    /// May report boxes near the frustum corners as intersecting when they aren't, which
+    /// is fine for culling.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|p| {
+            // The corner furthest along the normal.
+            let corner = Vector3::new(
+                if p.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if p.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if p.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+
+            p.signed_distance(corner) >= 0.0
+        })
+    }
+}
@@ -0,0 +1,315 @@
+//! Inverse kinematics on skeleton joint chains, e.g. to plant feet on uneven ground or
+//! turn a head towards something. Solvers work in model space, after animations are applied.
+
+use crate::scene::NodeId;
+use crate::scene::Skeleton;
+use cgmath::{InnerSpace, One, Quaternion, Rad, Rotation, Rotation3, Vector3};
+
+#[derive(Debug, Copy, Clone)]
+pub enum IkSolver {
+    /// Exact solution for three joints, like hip, knee and ankle.
+    TwoBone,
+    /// Iterative solution for chains of any length, like a spine or a tail.
+    Fabrik {
+        iterations: u32,
+        /// Stop once the end joint is this close to the target.
+        tolerance: f32,
+    },
+}
+
+/// Makes the end of a joint chain of a [`Model`](crate::scene::Model) reach for a target.
+/// Solved by the world after nodes are updated.
+#[derive(Debug, Clone)]
+pub struct IkConstraint {
+    pub solver: IkSolver,
+
+    /// The joint reaching for the target, e.g. the ankle.
+    pub end_joint: usize,
+
+    /// Joints above the end joint that are rotated. Two-bone IK always rotates two.
+    pub chain_length: usize,
+
+    /// The node to reach for. It has to implement AsNode3d.
+    pub target: Option<NodeId>,
+
+    /// Where to reach for, in world space. Follows the target node if there is one.
+    pub target_position: Vector3<f32>,
+
+    /// The node middle joints bend towards, like where a knee points. It has to implement
+    /// AsNode3d.
+    pub pole: Option<NodeId>,
+
+    /// In world space. Follows the pole node if there is one. Without a pole, joints keep
+    /// bending the way the animation has them.
+    pub pole_position: Option<Vector3<f32>>,
+}
+
+impl IkConstraint {
+    pub fn two_bone(end_joint: usize) -> Self {
+        Self {
+            solver: IkSolver::TwoBone,
+            end_joint,
+            chain_length: 2,
+            target: None,
+            target_position: Vector3::new(0.0, 0.0, 0.0),
+            pole: None,
+            pole_position: None,
+        }
+    }
+
+    pub fn fabrik(end_joint: usize, chain_length: usize) -> Self {
+        Self {
+            solver: IkSolver::Fabrik {
+                iterations: 10,
+                tolerance: 0.001,
+            },
+            chain_length,
+            ..Self::two_bone(end_joint)
+        }
+    }
+
+    /// Solve with target and pole already in model space.
+    pub fn solve(
+        &self,
+        skeleton: &mut Skeleton,
+        target: Vector3<f32>,
+        pole: Option<Vector3<f32>>,
+    ) -> bool {
+        match self.solver {
+            IkSolver::TwoBone => solve_two_bone(skeleton, self.end_joint, target, pole),
+            IkSolver::Fabrik {
+                iterations,
+                tolerance,
+            } => solve_fabrik(
+                skeleton,
+                self.end_joint,
+                self.chain_length,
+                target,
+                pole,
+                iterations,
+                tolerance,
+            ),
+        }
+    }
+}
+
+/// The end joint and `length` joints above it, root first.
+fn get_chain(skeleton: &Skeleton, end_joint: usize, length: usize) -> Option<Vec<usize>> {
+    let mut chain = vec![end_joint];
+
+    for _ in 0..length {
+        let parent = skeleton.get_joints().get(*chain.last()?)?.parent?;
+        chain.push(parent);
+    }
+
+    chain.reverse();
+    Some(chain)
+}
+
+fn global_position(skeleton: &Skeleton, joint: usize) -> Vector3<f32> {
+    skeleton.get_global_transform(joint).w.truncate()
+}
+
+/// Model space rotation of a joint, ignoring scale.
+fn global_rotation(skeleton: &Skeleton, joint: usize) -> Quaternion<f32> {
+    let pose = &skeleton.get_pose().transforms;
+
+    let mut rotation = pose[joint].rotation;
+    let mut parent = skeleton.get_joints()[joint].parent;
+
+    while let Some(p) = parent {
+        rotation = pose[p].rotation * rotation;
+        parent = skeleton.get_joints()[p].parent;
+    }
+
+    rotation
+}
+
+/// Rotate each joint of a chain so it points at the solved position of the next one.
+fn apply_positions(skeleton: &mut Skeleton, chain: &[usize], positions: &[Vector3<f32>]) {
+    for i in 0..chain.len() - 1 {
+        skeleton.update();
+
+        let joint = chain[i];
+        let position = global_position(skeleton, joint);
+        let from = global_position(skeleton, chain[i + 1]) - position;
+        let to = positions[i + 1] - position;
+
+        if from.magnitude2() < 1e-12 || to.magnitude2() < 1e-12 {
+            continue;
+        }
+
+        // Rotation in model space, brought into the parent's space.
+        let delta = Quaternion::between_vectors(from.normalize(), to.normalize());
+        let parent_rotation = skeleton.get_joints()[joint]
+            .parent
+            .map_or(Quaternion::one(), |p| global_rotation(skeleton, p));
+
+        let mut transform = skeleton.get_pose().transforms[joint];
+        transform.rotation =
+            (parent_rotation.invert() * delta * parent_rotation * transform.rotation).normalize();
+        skeleton.set_joint_transform(joint, transform);
+    }
+
+    skeleton.update();
+}
+
+/// Rotate `point` around the line through `start` and `end`, so it's on the same side as `pole`.
+fn bend_towards_pole(
+    point: Vector3<f32>,
+    start: Vector3<f32>,
+    end: Vector3<f32>,
+    pole: Vector3<f32>,
+) -> Vector3<f32> {
+    let axis = end - start;
+    if axis.magnitude2() < 1e-12 {
+        return point;
+    }
+    let axis = axis.normalize();
+
+    let project = |v: Vector3<f32>| v - axis * v.dot(axis);
+    let point_dir = project(point - start);
+    let pole_dir = project(pole - start);
+
+    if point_dir.magnitude2() < 1e-12 || pole_dir.magnitude2() < 1e-12 {
+        return point;
+    }
+
+    let angle = point_dir
+        .cross(pole_dir)
+        .dot(axis)
+        .atan2(point_dir.dot(pole_dir));
+
+    start + Quaternion::from_axis_angle(axis, Rad(angle)) * (point - start)
+}
+
+/// Rotate the two parents of `end_joint` so it reaches `target`, bending the middle joint
+/// towards `pole`. Out of reach targets straighten the chain towards them.
+///
+/// Returns false if the end joint doesn't have two parents.
+pub fn solve_two_bone(
+    skeleton: &mut Skeleton,
+    end_joint: usize,
+    target: Vector3<f32>,
+    pole: Option<Vector3<f32>>,
+) -> bool {
+    let Some(chain) = get_chain(skeleton, end_joint, 2) else {
+        return false;
+    };
+
+    skeleton.update();
+
+    let root = global_position(skeleton, chain[0]);
+    let middle = global_position(skeleton, chain[1]);
+    let end = global_position(skeleton, chain[2]);
+
+    let upper = (middle - root).magnitude();
+    let lower = (end - middle).magnitude();
+
+    let to_target = target - root;
+    if to_target.magnitude2() < 1e-12 || upper < 1e-6 || lower < 1e-6 {
+        return true;
+    }
+    let direction = to_target.normalize();
+
+    // Keep a tiny bend at full reach, so the pole still decides the bend direction.
+    let distance = to_target
+        .magnitude()
+        .clamp((upper - lower).abs() + 1e-4, upper + lower - 1e-4);
+
+    // Bend towards the pole, or the way the middle joint already bends.
+    let pole = pole.unwrap_or(middle) - root;
+    let mut bend = pole - direction * pole.dot(direction);
+    if bend.magnitude2() < 1e-12 {
+        // Straight chain and no pole, pick any perpendicular.
+        bend = direction.cross(Vector3::unit_y());
+        if bend.magnitude2() < 1e-12 {
+            bend = direction.cross(Vector3::unit_x());
+        }
+    }
+    let bend = bend.normalize();
+
+    // Law of cosines.
+    let along = (upper * upper - lower * lower + distance * distance) / (2.0 * distance);
+    let height = (upper * upper - along * along).max(0.0).sqrt();
+
+    let positions = [
+        root,
+        root + direction * along + bend * height,
+        root + direction * distance,
+    ];
+    apply_positions(skeleton, &chain, &positions);
+
+    true
+}
+
+/// Rotate `chain_length` parents of `end_joint` so it reaches `target`, using FABRIK
+/// (Forward And Backward Reaching Inverse Kinematics). Middle joints bend towards `pole`.
+///
+/// Returns false if the end joint doesn't have that many parents.
+pub fn solve_fabrik(
+    skeleton: &mut Skeleton,
+    end_joint: usize,
+    chain_length: usize,
+    target: Vector3<f32>,
+    pole: Option<Vector3<f32>>,
+    iterations: u32,
+    tolerance: f32,
+) -> bool {
+    let Some(chain) = get_chain(skeleton, end_joint, chain_length) else {
+        return false;
+    };
+
+    skeleton.update();
+
+    let mut positions: Vec<Vector3<f32>> = chain
+        .iter()
+        .map(|j| global_position(skeleton, *j))
+        .collect();
+    let lengths: Vec<f32> = positions
+        .windows(2)
+        .map(|w| (w[1] - w[0]).magnitude())
+        .collect();
+
+    let root = positions[0];
+    let last = positions.len() - 1;
+
+    // Out of reach, just point at the target.
+    if (target - root).magnitude() >= lengths.iter().sum() {
+        let direction = (target - root).normalize();
+        for i in 0..last {
+            positions[i + 1] = positions[i] + direction * lengths[i];
+        }
+    } else {
+        for _ in 0..iterations {
+            if (positions[last] - target).magnitude() <= tolerance {
+                break;
+            }
+
+            // Backward: from the target to the root.
+            positions[last] = target;
+            for i in (0..last).rev() {
+                let direction = (positions[i] - positions[i + 1]).normalize();
+                positions[i] = positions[i + 1] + direction * lengths[i];
+            }
+
+            // Forward: from the root back to the target.
+            positions[0] = root;
+            for i in 0..last {
+                let direction = (positions[i + 1] - positions[i]).normalize();
+                positions[i + 1] = positions[i] + direction * lengths[i];
+            }
+
+            if let Some(pole) = pole {
+                for i in 1..last {
+                    positions[i] =
+                        bend_towards_pole(positions[i], positions[i - 1], positions[i + 1], pole);
+                }
+            }
+        }
+    }
+
+    apply_positions(skeleton, &chain, &positions);
+
+    true
+}
@@ -1,5 +1,21 @@
+pub mod bezier;
+pub mod bvh;
 pub mod color;
+pub mod csg;
+pub mod curve;
+pub mod easing;
+pub mod frustum;
+pub mod ik;
+pub mod noise;
+pub mod plane;
+pub mod quadtree;
+pub mod rect;
+pub mod shape2d;
 pub mod transform;
+pub mod types;
+
+/// 3D boxes live with the physics shapes, this is the name next to [`rect::Rect2`].
+pub use crate::physics::Aabb as Aabb3;
 
 use allsorts::pathfinder_geometry::rect::RectF;
 use cgmath::Vector4;
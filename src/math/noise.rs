@@ -0,0 +1,408 @@
+//! Seeded randomness: a small RNG with independent streams, and Perlin, Simplex and
+//! fractal noise. Everything is deterministic for a given seed, so results can be
+//! reproduced across runs and machines.
+
+use cgmath::{Vector2, Vector3};
+
+/// PCG32 random number generator. Not for cryptography.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+    /// Selects the stream, always odd.
+    increment: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0)
+    }
+
+    /// Generators with the same seed but different streams give unrelated sequences,
+    /// e.g. one per system, so drawing more numbers in one doesn't change the others.
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.increment);
+
+        let xor_shifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xor_shifted.rotate_right(rotation)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// In [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// In [min, max).
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// In [min, max). Returns `min` if the range is empty.
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+
+        let span = (max as i64 - min as i64) as u64;
+        min + ((self.next_u32() as u64 * span) >> 32) as i32
+    }
+
+    /// True with a probability of `probability`, from 0.0 to 1.0.
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+
+        items.get(self.range_i32(0, items.len() as i32) as usize)
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.range_i32(0, i as i32 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// A random direction, evenly distributed on the unit circle.
+    pub fn unit_vector2(&mut self) -> Vector2<f32> {
+        let angle = self.next_f32() * std::f32::consts::TAU;
+        Vector2::new(angle.cos(), angle.sin())
+    }
+
+    /// A random direction, evenly distributed on the unit sphere.
+    pub fn unit_vector3(&mut self) -> Vector3<f32> {
+        let z = self.range_f32(-1.0, 1.0);
+        let angle = self.next_f32() * std::f32::consts::TAU;
+        let r = (1.0 - z * z).sqrt();
+
+        Vector3::new(r * angle.cos(), r * angle.sin(), z)
+    }
+}
+
+/// Integer hash with good avalanche, for deriving per-cell values from coordinates.
+pub fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+fn hash_cell(seed: u32, x: i32, y: i32, z: i32) -> u32 {
+    let h = hash(x as u32 ^ hash(y as u32 ^ hash(z as u32)));
+    hash(seed.wrapping_mul(0x9e37_79b9) ^ h)
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Smooth 1D gradient noise in [-1, 1].
+pub fn perlin1(seed: u32, x: f32) -> f32 {
+    let i = x.floor();
+    let f = x - i;
+
+    let gradient = |i: i32| {
+        let h = hash(seed.wrapping_mul(0x9e37_79b9) ^ i as u32);
+        h as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    let v0 = gradient(i as i32) * f;
+    let v1 = gradient(i as i32 + 1) * (f - 1.0);
+
+    let t = f * f * (3.0 - 2.0 * f);
+
+    // 1D gradient noise stays within [-0.5, 0.5].
+    (v0 + (v1 - v0) * t) * 2.0
+}
+
+/// One of eight directions.
+fn gradient2(hash: u32, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// One of the twelve edge directions of a cube.
+fn gradient3(hash: u32, x: f32, y: f32, z: f32) -> f32 {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// 2D Perlin noise in [-1, 1].
+pub fn perlin2(seed: u32, p: Vector2<f32>) -> f32 {
+    let (x0, y0) = (p.x.floor(), p.y.floor());
+    let (fx, fy) = (p.x - x0, p.y - y0);
+    let (x0, y0) = (x0 as i32, y0 as i32);
+
+    let corner = |dx: i32, dy: i32| {
+        gradient2(
+            hash_cell(seed, x0 + dx, y0 + dy, 0),
+            fx - dx as f32,
+            fy - dy as f32,
+        )
+    };
+
+    let (u, v) = (fade(fx), fade(fy));
+    let value = lerp(
+        lerp(corner(0, 0), corner(1, 0), u),
+        lerp(corner(0, 1), corner(1, 1), u),
+        v,
+    );
+
+    value.clamp(-1.0, 1.0)
+}
+
+/// 3D Perlin noise in [-1, 1].
+pub fn perlin3(seed: u32, p: Vector3<f32>) -> f32 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (fx, fy, fz) = (p.x - x0, p.y - y0, p.z - z0);
+    let (x0, y0, z0) = (x0 as i32, y0 as i32, z0 as i32);
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        gradient3(
+            hash_cell(seed, x0 + dx, y0 + dy, z0 + dz),
+            fx - dx as f32,
+            fy - dy as f32,
+            fz - dz as f32,
+        )
+    };
+
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let value = lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    );
+
+    value.clamp(-1.0, 1.0)
+}
+
+/// 2D Simplex noise in [-1, 1]. Cheaper than Perlin and without its grid artifacts.
+pub fn simplex2(seed: u32, p: Vector2<f32>) -> f32 {
+    const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+    const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+    // Skew into the simplex grid.
+    let s = (p.x + p.y) * F2;
+    let (i, j) = ((p.x + s).floor(), (p.y + s).floor());
+    let t = (i + j) * G2;
+    let (x0, y0) = (p.x - (i - t), p.y - (j - t));
+
+    // Which of the two triangles of the cell.
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+    let corners = [
+        (0, 0, x0, y0),
+        (i1, j1, x0 - i1 as f32 + G2, y0 - j1 as f32 + G2),
+        (1, 1, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+    ];
+
+    let (i, j) = (i as i32, j as i32);
+    let value: f32 = corners
+        .iter()
+        .map(|(di, dj, x, y)| {
+            let falloff = 0.5 - x * x - y * y;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+
+            let h = hash_cell(seed, i + di, j + dj, 0);
+            falloff.powi(4) * gradient2(h, *x, *y)
+        })
+        .sum();
+
+    (value * 70.0).clamp(-1.0, 1.0)
+}
+
+/// 3D Simplex noise in [-1, 1].
+pub fn simplex3(seed: u32, p: Vector3<f32>) -> f32 {
+    const F3: f32 = 1.0 / 3.0;
+    const G3: f32 = 1.0 / 6.0;
+
+    let s = (p.x + p.y + p.z) * F3;
+    let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+    let t = (i + j + k) * G3;
+    let (x0, y0, z0) = (p.x - (i - t), p.y - (j - t), p.z - (k - t));
+
+    // Which of the six tetrahedra of the cell.
+    let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+        if y0 >= z0 {
+            ((1, 0, 0), (1, 1, 0))
+        } else if x0 >= z0 {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if y0 < z0 {
+        ((0, 0, 1), (0, 1, 1))
+    } else if x0 < z0 {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+
+    let offset = |di: i32, dj: i32, dk: i32, n: f32| {
+        (
+            di,
+            dj,
+            dk,
+            x0 - di as f32 + n * G3,
+            y0 - dj as f32 + n * G3,
+            z0 - dk as f32 + n * G3,
+        )
+    };
+    let corners = [
+        offset(0, 0, 0, 0.0),
+        offset(i1, j1, k1, 1.0),
+        offset(i2, j2, k2, 2.0),
+        offset(1, 1, 1, 3.0),
+    ];
+
+    let (i, j, k) = (i as i32, j as i32, k as i32);
+    let value: f32 = corners
+        .iter()
+        .map(|(di, dj, dk, x, y, z)| {
+            let falloff = 0.6 - x * x - y * y - z * z;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+
+            let h = hash_cell(seed, i + di, j + dj, k + dk);
+            falloff.powi(4) * gradient3(h, *x, *y, *z)
+        })
+        .sum();
+
+    (value * 32.0).clamp(-1.0, 1.0)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoiseKind {
+    Perlin,
+    Simplex,
+}
+
+/// Fractal Brownian motion: octaves of noise at rising frequencies and falling amplitudes,
+/// for natural looking detail like terrain heights or clouds.
+#[derive(Debug, Copy, Clone)]
+pub struct Fbm {
+    pub kind: NoiseKind,
+    pub octaves: u32,
+    /// Of the first octave.
+    pub frequency: f32,
+    /// Frequency multiplier per octave.
+    pub lacunarity: f32,
+    /// Amplitude multiplier per octave.
+    pub gain: f32,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Self {
+            kind: NoiseKind::Perlin,
+            octaves: 4,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Fbm {
+    /// Sum the octaves, each with its own seed. Normalized to [-1, 1].
+    fn sum(&self, seed: u32, octave: impl Fn(u32, f32) -> f32) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut value = 0.0;
+        let mut total = 0.0;
+
+        for i in 0..self.octaves {
+            value += octave(seed.wrapping_add(i), frequency) * amplitude;
+            total += amplitude;
+
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if total > 0.0 {
+            value / total
+        } else {
+            0.0
+        }
+    }
+
+    /// 1D noise is always Perlin.
+    pub fn sample1(&self, seed: u32, x: f32) -> f32 {
+        self.sum(seed, |seed, frequency| perlin1(seed, x * frequency))
+    }
+
+    pub fn sample2(&self, seed: u32, p: Vector2<f32>) -> f32 {
+        self.sum(seed, |seed, frequency| match self.kind {
+            NoiseKind::Perlin => perlin2(seed, p * frequency),
+            NoiseKind::Simplex => simplex2(seed, p * frequency),
+        })
+    }
+
+    pub fn sample3(&self, seed: u32, p: Vector3<f32>) -> f32 {
+        self.sum(seed, |seed, frequency| match self.kind {
+            NoiseKind::Perlin => perlin3(seed, p * frequency),
+            NoiseKind::Simplex => simplex3(seed, p * frequency),
+        })
+    }
+}
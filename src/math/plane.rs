@@ -0,0 +1,70 @@
+use crate::physics::Ray3d;
+use cgmath::{InnerSpace, Vector3, Vector4};
+
+/// Infinite plane of points `p` where `normal.dot(p) + distance == 0`. The normal points
+/// to the front side.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    /// Always normalized.
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<f32>, distance: f32) -> Self {
+        let length = normal.magnitude();
+
+        Self {
+            normal: normal / length,
+            distance: distance / length,
+        }
+    }
+
+    pub fn from_point_normal(point: Vector3<f32>, normal: Vector3<f32>) -> Self {
+        let normal = normal.normalize();
+
+        Self {
+            normal,
+            distance: -normal.dot(point),
+        }
+    }
+
+    /// Counter-clockwise points face the front.
+    pub fn from_points(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Self {
+        Self::from_point_normal(a, (b - a).cross(c - a))
+    }
+
+    /// From `(a, b, c, d)` coefficients, which needn't be normalized.
+    pub fn from_vec4(coefficients: Vector4<f32>) -> Self {
+        Self::new(coefficients.truncate(), coefficients.w)
+    }
+
+    /// Positive in front of the plane.
+    pub fn signed_distance(&self, point: Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+
+    /// Closest point on the plane.
+    pub fn project_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        point - self.normal * self.signed_distance(point)
+    }
+
+    /// Returns the distance along the ray where it crosses the plane, from either side.
+    pub fn intersect_ray(&self, ray: &Ray3d, max_distance: f32) -> Option<f32> {
+        let denom = self.normal.dot(ray.direction);
+        if denom.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let t = -self.signed_distance(ray.origin) / denom;
+        (0.0..=max_distance).contains(&t).then_some(t)
+    }
+
+    /// The same plane facing the other way.
+    pub fn flipped(&self) -> Self {
+        Self {
+            normal: -self.normal,
+            distance: -self.distance,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Quadtree over 2D rectangles, for finding what overlaps a rectangle, a point or a ray
+//! without testing everything.
+//!
+//! Items live in the smallest node that fully contains them. Nodes split into quadrants
+//! once they hold too many items. Items outside the root bounds are kept in the root.
+//!
+//! The engine doesn't use it itself yet. It's there for gameplay queries over 2D bounds.
+
+use crate::math::rect::Rect2;
+use crate::physics::Ray2d;
+use cgmath::Vector2;
+
+/// Items a node holds before it splits.
+const NODE_CAPACITY: usize = 8;
+
+const MAX_DEPTH: u32 = 8;
+
+/// Handle to an item in a [`Quadtree`]. Stays valid until the item is removed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct QuadtreeId(usize);
+
+#[derive(Debug, Clone)]
+struct QuadNode {
+    bounds: Rect2,
+    depth: u32,
+    children: Option<[usize; 4]>,
+    /// Indices into the items.
+    items: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct QuadItem<T> {
+    rect: Rect2,
+    item: T,
+    node: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Quadtree<T> {
+    nodes: Vec<QuadNode>,
+    /// Removed items leave holes, reused by later insertions.
+    items: Vec<Option<QuadItem<T>>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> Quadtree<T> {
+    pub fn new(bounds: Rect2) -> Self {
+        Self {
+            nodes: vec![QuadNode {
+                bounds,
+                depth: 0,
+                children: None,
+                items: vec![],
+            }],
+            items: vec![],
+            free: vec![],
+            len: 0,
+        }
+    }
+
+    pub fn get_bounds(&self) -> Rect2 {
+        self.nodes[0].bounds
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.get_bounds());
+    }
+
+    pub fn get(&self, id: QuadtreeId) -> Option<&T> {
+        self.items.get(id.0)?.as_ref().map(|i| &i.item)
+    }
+
+    pub fn get_rect(&self, id: QuadtreeId) -> Option<Rect2> {
+        self.items.get(id.0)?.as_ref().map(|i| i.rect)
+    }
+
+    pub fn insert(&mut self, rect: Rect2, item: T) -> QuadtreeId {
+        let quad_item = Some(QuadItem {
+            rect,
+            item,
+            node: 0,
+        });
+
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.items[index] = quad_item;
+                index
+            }
+            None => {
+                self.items.push(quad_item);
+                self.items.len() - 1
+            }
+        };
+
+        self.place(index);
+        self.len += 1;
+
+        QuadtreeId(index)
+    }
+
+    pub fn remove(&mut self, id: QuadtreeId) -> Option<T> {
+        let item = self.items.get_mut(id.0)?.take()?;
+
+        self.nodes[item.node].items.retain(|i| *i != id.0);
+        self.free.push(id.0);
+        self.len -= 1;
+
+        Some(item.item)
+    }
+
+    /// Move an item. Returns false if it's not in the tree.
+    pub fn update(&mut self, id: QuadtreeId, rect: Rect2) -> bool {
+        let Some(Some(item)) = self.items.get_mut(id.0) else {
+            return false;
+        };
+        item.rect = rect;
+
+        let node = item.node;
+        self.nodes[node].items.retain(|i| *i != id.0);
+        self.place(id.0);
+
+        true
+    }
+
+    /// Put an item into the smallest node that contains it, splitting nodes as they fill up.
+    fn place(&mut self, index: usize) {
+        let rect = self.items[index].as_ref().unwrap().rect;
+
+        let mut node = 0;
+        while let Some(child) = self.child_containing(node, &rect) {
+            node = child;
+        }
+
+        self.nodes[node].items.push(index);
+        self.items[index].as_mut().unwrap().node = node;
+
+        let full = self.nodes[node].items.len() > NODE_CAPACITY;
+        if full && self.nodes[node].children.is_none() && self.nodes[node].depth < MAX_DEPTH {
+            self.split(node);
+        }
+    }
+
+    fn child_containing(&self, node: usize, rect: &Rect2) -> Option<usize> {
+        self.nodes[node]
+            .children?
+            .into_iter()
+            .find(|child| self.nodes[*child].bounds.contains(rect))
+    }
+
+    fn split(&mut self, node: usize) {
+        let QuadNode { bounds, depth, .. } = self.nodes[node];
+        let center = bounds.center();
+
+        let quadrants = [
+            Rect2::new(bounds.min, center),
+            Rect2::new(
+                Vector2::new(center.x, bounds.min.y),
+                Vector2::new(bounds.max.x, center.y),
+            ),
+            Rect2::new(
+                Vector2::new(bounds.min.x, center.y),
+                Vector2::new(center.x, bounds.max.y),
+            ),
+            Rect2::new(center, bounds.max),
+        ];
+
+        let first = self.nodes.len();
+        self.nodes.extend(quadrants.map(|bounds| QuadNode {
+            bounds,
+            depth: depth + 1,
+            children: None,
+            items: vec![],
+        }));
+        self.nodes[node].children = Some([first, first + 1, first + 2, first + 3]);
+
+        // Move down the items that fit into a quadrant.
+        let items = std::mem::take(&mut self.nodes[node].items);
+        for index in items {
+            let rect = self.items[index].as_ref().unwrap().rect;
+
+            let target = self.child_containing(node, &rect).unwrap_or(node);
+            self.nodes[target].items.push(index);
+            self.items[index].as_mut().unwrap().node = target;
+        }
+    }
+
+    /// Visit the items of nodes accepted by `visit_node`.
+    fn traverse(&self, visit_node: impl Fn(&Rect2) -> bool, mut visit_item: impl FnMut(usize)) {
+        // The root is always visited, it also holds items outside its bounds.
+        let mut stack = vec![0];
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            for index in &node.items {
+                visit_item(*index);
+            }
+
+            if let Some(children) = node.children {
+                stack.extend(
+                    children
+                        .into_iter()
+                        .filter(|child| visit_node(&self.nodes[*child].bounds)),
+                );
+            }
+        }
+    }
+
+    /// Items whose rectangles overlap `rect`.
+    pub fn query_rect(&self, rect: &Rect2) -> Vec<(QuadtreeId, &T)> {
+        let mut found = vec![];
+
+        self.traverse(
+            |bounds| bounds.overlaps(rect),
+            |index| {
+                let item = self.items[index].as_ref().unwrap();
+                if item.rect.overlaps(rect) {
+                    found.push((QuadtreeId(index), &item.item));
+                }
+            },
+        );
+
+        found
+    }
+
+    /// Items whose rectangles contain `point`.
+    pub fn query_point(&self, point: Vector2<f32>) -> Vec<(QuadtreeId, &T)> {
+        self.query_rect(&Rect2::new(point, point))
+    }
+
+    /// Items whose rectangles the ray enters within `max_distance`, closest first, with the
+    /// distance where the ray enters them.
+    pub fn query_ray(&self, ray: &Ray2d, max_distance: f32) -> Vec<(QuadtreeId, &T, f32)> {
+        let mut found = vec![];
+
+        self.traverse(
+            |bounds| bounds.intersect_ray(ray, max_distance).is_some(),
+            |index| {
+                let item = self.items[index].as_ref().unwrap();
+                if let Some(distance) = item.rect.intersect_ray(ray, max_distance) {
+                    found.push((QuadtreeId(index), &item.item, distance));
+                }
+            },
+        );
+
+        found.sort_by(|a, b| a.2.total_cmp(&b.2));
+        found
+    }
+}
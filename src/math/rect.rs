@@ -0,0 +1,134 @@
+use crate::math::transform::Transform2d;
+use crate::physics::Ray2d;
+use cgmath::Vector2;
+
+/// Axis-aligned 2D rectangle.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rect2 {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl Rect2 {
+    pub fn new(min: Vector2<f32>, max: Vector2<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_position_size(position: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Self {
+            min: position,
+            max: position + size,
+        }
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vector2<f32> {
+        self.max - self.min
+    }
+
+    /// Whether it has no area, e.g. after an intersection that touched only an edge.
+    pub fn is_empty(&self) -> bool {
+        self.max.x <= self.min.x || self.max.y <= self.min.y
+    }
+
+    /// Smallest rectangle containing both.
+    pub fn union(&self, other: &Rect2) -> Rect2 {
+        Rect2 {
+            min: Vector2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vector2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /// The overlapping part of two rectangles, if they overlap.
+    pub fn intersection(&self, other: &Rect2) -> Option<Rect2> {
+        self.overlaps(other).then(|| Rect2 {
+            min: Vector2::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y)),
+            max: Vector2::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y)),
+        })
+    }
+
+    /// Grown by `amount` on every side, or shrunk if negative.
+    pub fn grow(&self, amount: f32) -> Rect2 {
+        let amount = Vector2::new(amount, amount);
+
+        Rect2 {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+
+    /// Bounds of the rectangle's corners after a transform.
+    pub fn transformed(&self, transform: &Transform2d) -> Rect2 {
+        let corners = [
+            self.min,
+            Vector2::new(self.max.x, self.min.y),
+            self.max,
+            Vector2::new(self.min.x, self.max.y),
+        ]
+        .map(|corner| Vector2::from(transform.transform_point(corner)));
+
+        corners[1..]
+            .iter()
+            .fold(Rect2::new(corners[0], corners[0]), |rect, p| {
+                rect.union(&Rect2::new(*p, *p))
+            })
+    }
+
+    pub fn overlaps(&self, other: &Rect2) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    /// Whether `other` is fully inside.
+    pub fn contains(&self, other: &Rect2) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+    }
+
+    pub fn contains_point(&self, point: Vector2<f32>) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    /// Returns the distance along the ray where it enters the rectangle, if it does.
+    pub fn intersect_ray(&self, ray: &Ray2d, max_distance: f32) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = max_distance;
+
+        for i in 0..2 {
+            let origin = ray.origin[i];
+            let direction = ray.direction[i];
+
+            if direction.abs() < f32::EPSILON {
+                if origin < self.min[i] || origin > self.max[i] {
+                    return None;
+                }
+            } else {
+                let inv = 1.0 / direction;
+                let mut t1 = (self.min[i] - origin) * inv;
+                let mut t2 = (self.max[i] - origin) * inv;
+                if t1 > t2 {
+                    std::mem::swap(&mut t1, &mut t2);
+                }
+
+                t_min = t_min.max(t1);
+                t_max = t_max.min(t2);
+
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+
+        Some(t_min)
+    }
+}
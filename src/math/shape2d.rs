@@ -0,0 +1,698 @@
+//! Boolean operations and offsetting on filled 2D shapes.
+//!
+//! Booleans work on the arrangement of the edges of both shapes: edges are split where
+//! they meet, and each piece is kept if the result is filled on one side of it only.
+//! Offsetting moves every edge outwards, adds joins at the corners, and keeps the areas
+//! that end up wound positively, like Clipper does.
+//!
+//! Edges are tested pairwise, so this is meant for shapes of up to a few thousand edges,
+//! e.g. fog of war or silhouettes built at runtime.
+
+use crate::math::transform::Transform2d;
+use cgmath::{InnerSpace, Vector2};
+use lyon::math::point;
+use lyon::path::iterator::PathIterator;
+use lyon::path::{FillRule, Path, PathEvent};
+use std::collections::HashMap;
+
+/// Points closer than this are merged, in shape units.
+const SNAP: f64 = 1e-4;
+
+/// Longest miter, relative to the offset, before falling back to a bevel. The SVG default.
+const MITER_LIMIT: f64 = 4.0;
+
+type Point = Vector2<f64>;
+
+/// How corners are filled when offsetting outwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OffsetJoin {
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BooleanOp {
+    Union,
+    Intersect,
+    Subtract,
+    Xor,
+}
+
+impl BooleanOp {
+    fn apply(self, a: bool, b: bool) -> bool {
+        match self {
+            BooleanOp::Union => a || b,
+            BooleanOp::Intersect => a && b,
+            BooleanOp::Subtract => a && !b,
+            BooleanOp::Xor => a != b,
+        }
+    }
+}
+
+/// Which winding numbers are filled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Winding {
+    NonZero,
+    EvenOdd,
+    Positive,
+}
+
+impl Winding {
+    fn is_filled(self, winding: i32) -> bool {
+        match self {
+            Winding::NonZero => winding != 0,
+            Winding::EvenOdd => winding % 2 != 0,
+            Winding::Positive => winding > 0,
+        }
+    }
+}
+
+impl From<FillRule> for Winding {
+    fn from(fill_rule: FillRule) -> Self {
+        match fill_rule {
+            FillRule::NonZero => Winding::NonZero,
+            FillRule::EvenOdd => Winding::EvenOdd,
+        }
+    }
+}
+
+/// A filled area made of closed polygons.
+///
+/// Contours never cross. Outer ones have a positive signed area (counter-clockwise with
+/// Y up, clockwise on screen) and holes a negative one, so any fill rule draws them right.
+#[derive(Debug, Clone, Default)]
+pub struct Shape2d {
+    contours: Vec<Vec<Vector2<f32>>>,
+}
+
+impl Shape2d {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A polygon in any winding. Self-intersections are filled by the non-zero rule.
+    pub fn from_polygon(points: &[Vector2<f32>]) -> Self {
+        Self::from_contours(&[to_f64(points)], Winding::NonZero)
+    }
+
+    pub fn rect(position: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Self::from_polygon(&[
+            position,
+            position + Vector2::new(size.x, 0.0),
+            position + size,
+            position + Vector2::new(0.0, size.y),
+        ])
+    }
+
+    pub fn circle(center: Vector2<f32>, radius: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+
+        let points: Vec<Vector2<f32>> = (0..segments)
+            .map(|i| {
+                let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+                center + Vector2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+
+        Self::from_polygon(&points)
+    }
+
+    /// Flatten the closed subpaths of a path. Open ones are closed.
+    pub fn from_path(path: &Path, fill_rule: FillRule, tolerance: f32) -> Self {
+        let mut contours = vec![];
+        let mut contour = vec![];
+
+        for event in path.iter().flattened(tolerance) {
+            match event {
+                PathEvent::Begin { at } => contour = vec![Point::new(at.x as f64, at.y as f64)],
+                PathEvent::Line { to, .. } => contour.push(Point::new(to.x as f64, to.y as f64)),
+                PathEvent::End { .. } => contours.push(std::mem::take(&mut contour)),
+                _ => {}
+            }
+        }
+
+        Self::from_contours(&contours, fill_rule.into())
+    }
+
+    fn from_contours(contours: &[Vec<Point>], winding: Winding) -> Self {
+        Self::from_f64(boolean(
+            contours,
+            winding,
+            &[],
+            Winding::NonZero,
+            BooleanOp::Union,
+        ))
+    }
+
+    fn from_f64(contours: Vec<Vec<Point>>) -> Self {
+        Self {
+            contours: contours
+                .iter()
+                .map(|c| c.iter().map(|p| p.cast().unwrap()).collect())
+                .collect(),
+        }
+    }
+
+    fn to_f64(&self) -> Vec<Vec<Point>> {
+        self.contours.iter().map(|c| to_f64(c)).collect()
+    }
+
+    pub fn get_contours(&self) -> &[Vec<Vector2<f32>>] {
+        &self.contours
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contours.is_empty()
+    }
+
+    /// Filled area, without the holes.
+    pub fn area(&self) -> f32 {
+        self.to_f64().iter().map(|c| signed_area(c)).sum::<f64>() as f32
+    }
+
+    pub fn contains_point(&self, position: Vector2<f32>) -> bool {
+        let position = position.cast().unwrap();
+
+        let winding: i32 = self
+            .to_f64()
+            .iter()
+            .flat_map(|c| (0..c.len()).map(move |i| (c[i], c[(i + 1) % c.len()])))
+            .map(|(a, b)| crossing_x(a, b, position))
+            .sum();
+
+        winding != 0
+    }
+
+    pub fn union(&self, other: &Shape2d) -> Shape2d {
+        self.boolean(other, BooleanOp::Union)
+    }
+
+    pub fn subtract(&self, other: &Shape2d) -> Shape2d {
+        self.boolean(other, BooleanOp::Subtract)
+    }
+
+    pub fn intersect(&self, other: &Shape2d) -> Shape2d {
+        self.boolean(other, BooleanOp::Intersect)
+    }
+
+    /// Areas covered by exactly one of the shapes.
+    pub fn xor(&self, other: &Shape2d) -> Shape2d {
+        self.boolean(other, BooleanOp::Xor)
+    }
+
+    fn boolean(&self, other: &Shape2d, op: BooleanOp) -> Shape2d {
+        Self::from_f64(boolean(
+            &self.to_f64(),
+            Winding::NonZero,
+            &other.to_f64(),
+            Winding::NonZero,
+            op,
+        ))
+    }
+
+    /// Grow the shape by `delta`, or shrink it if negative. Holes shrink as the shape grows.
+    /// Round joins are flattened to within `tolerance`.
+    pub fn offset(&self, delta: f32, join: OffsetJoin, tolerance: f32) -> Shape2d {
+        if delta == 0.0 {
+            return self.clone();
+        }
+
+        let raw: Vec<Vec<Point>> = self
+            .to_f64()
+            .iter()
+            .map(|c| offset_contour(c, delta as f64, join, tolerance as f64))
+            .collect();
+
+        // Corners cut off by shrinking, and other inverted parts, wind negatively.
+        Self::from_f64(boolean(
+            &raw,
+            Winding::Positive,
+            &[],
+            Winding::NonZero,
+            BooleanOp::Union,
+        ))
+    }
+
+    pub fn transformed(&self, transform: &Transform2d) -> Shape2d {
+        let contours: Vec<Vec<Point>> = self
+            .contours
+            .iter()
+            .map(|c| {
+                to_f64(
+                    &c.iter()
+                        .map(|p| transform.transform_point(p).into())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        // Mirroring flips the winding, the non-zero rule doesn't mind.
+        Self::from_contours(&contours, Winding::NonZero)
+    }
+
+    /// For drawing, e.g. with `VectorTexture::add_shape`.
+    pub fn to_path(&self) -> Path {
+        let mut builder = Path::builder();
+
+        for contour in &self.contours {
+            builder.begin(point(contour[0].x, contour[0].y));
+            for p in &contour[1..] {
+                builder.line_to(point(p.x, p.y));
+            }
+            builder.end(true);
+        }
+
+        builder.build()
+    }
+}
+
+fn to_f64(points: &[Vector2<f32>]) -> Vec<Point> {
+    points.iter().map(|p| p.cast().unwrap()).collect()
+}
+
+fn cross(a: Point, b: Point) -> f64 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Positive for counter-clockwise points, with Y up.
+fn signed_area(points: &[Point]) -> f64 {
+    let count = points.len();
+
+    (0..count)
+        .map(|i| cross(points[i], points[(i + 1) % count]))
+        .sum::<f64>()
+        * 0.5
+}
+
+/// Winding change of a ray from `p` towards +X crossing the edge from `a` to `b`.
+fn crossing_x(a: Point, b: Point, p: Point) -> i32 {
+    if (a.y <= p.y) == (b.y <= p.y) {
+        return 0;
+    }
+
+    let x = a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y);
+    if x <= p.x {
+        return 0;
+    }
+
+    if b.y > a.y {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Winding change of a ray from `p` towards +Y crossing the edge from `a` to `b`.
+fn crossing_y(a: Point, b: Point, p: Point) -> i32 {
+    if (a.x <= p.x) == (b.x <= p.x) {
+        return 0;
+    }
+
+    let y = a.y + (p.x - a.x) * (b.y - a.y) / (b.x - a.x);
+    if y <= p.y {
+        return 0;
+    }
+
+    if b.x < a.x {
+        1
+    } else {
+        -1
+    }
+}
+
+/// Points merged within `SNAP` of each other.
+#[derive(Default)]
+struct Vertices {
+    points: Vec<Point>,
+    grid: HashMap<(i64, i64), usize>,
+}
+
+impl Vertices {
+    fn insert(&mut self, p: Point) -> usize {
+        let cell = ((p.x / SNAP).round() as i64, (p.y / SNAP).round() as i64);
+
+        // Look around too, for points rounded into a neighbouring cell.
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if let Some(&id) = self.grid.get(&(cell.0 + dx, cell.1 + dy)) {
+                    if (self.points[id] - p).magnitude2() <= SNAP * SNAP * 4.0 {
+                        return id;
+                    }
+                }
+            }
+        }
+
+        let id = self.points.len();
+        self.points.push(p);
+        self.grid.insert(cell, id);
+        id
+    }
+}
+
+struct Edge {
+    a: Point,
+    b: Point,
+    /// 0 for the subject, 1 for the clip.
+    shape: usize,
+}
+
+/// Where two edges cross or overlap, as parameters along each of them.
+/// Only points inside the edges are returned, ends don't need splitting.
+fn split_params(e1: &Edge, e2: &Edge) -> (Vec<f64>, Vec<f64>) {
+    let (mut t1, mut t2) = (vec![], vec![]);
+
+    let d1 = e1.b - e1.a;
+    let d2 = e2.b - e2.a;
+    let len1 = d1.magnitude();
+    let len2 = d2.magnitude();
+    let eps1 = SNAP / len1;
+    let eps2 = SNAP / len2;
+    let inside1 = |t: f64| t > eps1 && t < 1.0 - eps1;
+    let inside2 = |t: f64| t > eps2 && t < 1.0 - eps2;
+
+    let r = e2.a - e1.a;
+    let denom = cross(d1, d2);
+
+    if denom.abs() > 1e-12 * len1 * len2 {
+        let t = cross(r, d2) / denom;
+        let u = cross(r, d1) / denom;
+
+        if t >= -eps1 && t <= 1.0 + eps1 && u >= -eps2 && u <= 1.0 + eps2 {
+            if inside1(t) {
+                t1.push(t);
+            }
+            if inside2(u) {
+                t2.push(u);
+            }
+        }
+    } else if cross(r, d1).abs() / len1 < SNAP {
+        // Collinear. Split each edge at the ends of the other.
+        for p in [e2.a, e2.b] {
+            let t = (p - e1.a).dot(d1) / (len1 * len1);
+            if inside1(t) {
+                t1.push(t);
+            }
+        }
+        for p in [e1.a, e1.b] {
+            let t = (p - e2.a).dot(d2) / (len2 * len2);
+            if inside2(t) {
+                t2.push(t);
+            }
+        }
+    }
+
+    (t1, t2)
+}
+
+/// The boundary of `op` applied to the areas of both sets of contours.
+fn boolean(
+    subject: &[Vec<Point>],
+    subject_winding: Winding,
+    clip: &[Vec<Point>],
+    clip_winding: Winding,
+    op: BooleanOp,
+) -> Vec<Vec<Point>> {
+    let mut edges = vec![];
+    for (shape, contours) in [subject, clip].into_iter().enumerate() {
+        for contour in contours {
+            let count = contour.len();
+            for i in 0..count {
+                let (a, b) = (contour[i], contour[(i + 1) % count]);
+                if (b - a).magnitude2() > SNAP * SNAP {
+                    edges.push(Edge { a, b, shape });
+                }
+            }
+        }
+    }
+
+    // Split the edges where they meet.
+    let mut params = vec![vec![]; edges.len()];
+    let bounds: Vec<(Point, Point)> = edges
+        .iter()
+        .map(|e| {
+            (
+                Point::new(e.a.x.min(e.b.x), e.a.y.min(e.b.y)),
+                Point::new(e.a.x.max(e.b.x), e.a.y.max(e.b.y)),
+            )
+        })
+        .collect();
+
+    for i in 0..edges.len() {
+        for j in i + 1..edges.len() {
+            let (min1, max1) = bounds[i];
+            let (min2, max2) = bounds[j];
+            if min1.x > max2.x + SNAP
+                || min2.x > max1.x + SNAP
+                || min1.y > max2.y + SNAP
+                || min2.y > max1.y + SNAP
+            {
+                continue;
+            }
+
+            let (t1, t2) = split_params(&edges[i], &edges[j]);
+            params[i].extend(t1);
+            params[j].extend(t2);
+        }
+    }
+
+    let mut vertices = Vertices::default();
+    // Pieces as (from, to, shape).
+    let mut pieces = vec![];
+
+    for (edge, params) in edges.iter().zip(&mut params) {
+        params.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut ids = vec![vertices.insert(edge.a)];
+        for t in params.iter() {
+            ids.push(vertices.insert(edge.a + (edge.b - edge.a) * *t));
+        }
+        ids.push(vertices.insert(edge.b));
+
+        for pair in ids.windows(2) {
+            if pair[0] != pair[1] {
+                pieces.push((pair[0], pair[1], edge.shape));
+            }
+        }
+    }
+
+    // Overlapping pieces of both shapes become one.
+    let mut groups: Vec<(usize, usize)> = vec![];
+    let mut group_of: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut piece_groups = Vec::with_capacity(pieces.len());
+    for &(from, to, _) in &pieces {
+        let key = (from.min(to), from.max(to));
+        let group = *group_of.entry(key).or_insert_with(|| {
+            groups.push((from, to));
+            groups.len() - 1
+        });
+        piece_groups.push(group);
+    }
+
+    let points = &vertices.points;
+    let windings = [subject_winding, clip_winding];
+
+    // Boundary edges of the result, with the filled side on their left.
+    let mut boundary = vec![];
+
+    for (group, &(from, to)) in groups.iter().enumerate() {
+        let (a, b) = (points[from], points[to]);
+        let d = b - a;
+        let middle = (a + b) * 0.5;
+
+        // Cast the ray along the axis that crosses the piece the most.
+        let along_x = d.y.abs() >= d.x.abs();
+
+        // Winding numbers on the side the ray goes to, where the overlapping pieces
+        // don't count, and on the other side, where they all do.
+        let mut far = [0, 0];
+        let mut near = [0, 0];
+
+        for (i, &(p, q, shape)) in pieces.iter().enumerate() {
+            let (p, q) = (points[p], points[q]);
+
+            if piece_groups[i] == group {
+                near[shape] += if along_x {
+                    if q.y > p.y {
+                        1
+                    } else {
+                        -1
+                    }
+                } else if q.x < p.x {
+                    1
+                } else {
+                    -1
+                };
+            } else {
+                let crossing = if along_x {
+                    crossing_x(p, q, middle)
+                } else {
+                    crossing_y(p, q, middle)
+                };
+                far[shape] += crossing;
+                near[shape] += crossing;
+            }
+        }
+
+        let filled =
+            |w: [i32; 2]| op.apply(windings[0].is_filled(w[0]), windings[1].is_filled(w[1]));
+        let filled_far = filled(far);
+        if filled_far == filled(near) {
+            continue;
+        }
+
+        let ray = if along_x {
+            Point::new(1.0, 0.0)
+        } else {
+            Point::new(0.0, 1.0)
+        };
+        let far_is_left = Point::new(-d.y, d.x).dot(ray) > 0.0;
+
+        if filled_far == far_is_left {
+            boundary.push((from, to));
+        } else {
+            boundary.push((to, from));
+        }
+    }
+
+    link_contours(&boundary, points)
+}
+
+/// Chain directed edges into closed contours. Every vertex has as many edges in as out.
+fn link_contours(edges: &[(usize, usize)], points: &[Point]) -> Vec<Vec<Point>> {
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(from, _)) in edges.iter().enumerate() {
+        outgoing.entry(from).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut contours = vec![];
+
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+
+        let start = edges[first].0;
+        let mut contour = vec![];
+        let mut current = first;
+
+        loop {
+            used[current] = true;
+            let (from, to) = edges[current];
+            contour.push(points[from]);
+
+            if to == start {
+                break;
+            }
+
+            match outgoing
+                .get(&to)
+                .and_then(|next| next.iter().copied().find(|e| !used[*e]))
+            {
+                Some(next) => current = next,
+                None => {
+                    // Only with numerical trouble. Drop the open chain.
+                    contour.clear();
+                    break;
+                }
+            }
+        }
+
+        let contour = remove_collinear(contour);
+        if contour.len() >= 3 && signed_area(&contour).abs() > SNAP * SNAP {
+            contours.push(contour);
+        }
+    }
+
+    contours
+}
+
+/// Remove points in the middle of straight runs.
+fn remove_collinear(mut contour: Vec<Point>) -> Vec<Point> {
+    let mut i = 0;
+    while contour.len() >= 3 && i < contour.len() {
+        let count = contour.len();
+        let prev = contour[(i + count - 1) % count];
+        let p = contour[i];
+        let next = contour[(i + 1) % count];
+
+        let (d1, d2) = (p - prev, next - p);
+        if cross(d1, d2).abs() <= SNAP * (d1.magnitude() + d2.magnitude()) && d1.dot(d2) >= 0.0 {
+            contour.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+
+    contour
+}
+
+/// Move each edge by `delta` to its right, away from the filled side, and join them.
+/// The result can cross itself, and is cleaned up by the positive fill rule.
+fn offset_contour(contour: &[Point], delta: f64, join: OffsetJoin, tolerance: f64) -> Vec<Point> {
+    let count = contour.len();
+    let mut out = vec![];
+
+    let normal = |a: Point, b: Point| {
+        let d = (b - a).normalize();
+        Point::new(d.y, -d.x)
+    };
+
+    for i in 0..count {
+        let prev = contour[(i + count - 1) % count];
+        let p = contour[i];
+        let next = contour[(i + 1) % count];
+
+        let n1 = normal(prev, p);
+        let n2 = normal(p, next);
+        let turn = cross(p - prev, next - p);
+
+        // Nearly straight.
+        if n1.dot(n2) > 1.0 - 1e-9 {
+            out.push(p + n1 * delta);
+            continue;
+        }
+
+        // The edges move apart here, and a join fills the gap.
+        if turn * delta > 0.0 {
+            match join {
+                OffsetJoin::Miter => {
+                    let q = 1.0 + n1.dot(n2);
+                    if (2.0 / q).sqrt() <= MITER_LIMIT {
+                        out.push(p + (n1 + n2) * (delta / q));
+                    } else {
+                        out.push(p + n1 * delta);
+                        out.push(p + n2 * delta);
+                    }
+                }
+                OffsetJoin::Bevel => {
+                    out.push(p + n1 * delta);
+                    out.push(p + n2 * delta);
+                }
+                OffsetJoin::Round => {
+                    let angle = cross(n1, n2).atan2(n1.dot(n2));
+                    let step = 2.0 * (1.0 - tolerance.min(delta.abs()) / delta.abs()).acos();
+                    let steps = (angle.abs() / step.max(1e-3)).ceil().max(1.0) as usize;
+
+                    for k in 0..=steps {
+                        let a = angle * k as f64 / steps as f64;
+                        let n = Point::new(
+                            n1.x * a.cos() - n1.y * a.sin(),
+                            n1.x * a.sin() + n1.y * a.cos(),
+                        );
+                        out.push(p + n * delta);
+                    }
+                }
+            }
+        } else {
+            // The edges overlap here. Going through the corner keeps the winding right.
+            out.push(p + n1 * delta);
+            out.push(p);
+            out.push(p + n2 * delta);
+        }
+    }
+
+    out
+}
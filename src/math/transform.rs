@@ -1,4 +1,8 @@
-use cgmath::{Deg, InnerSpace, Point3, Quaternion, Rotation3, Vector2, Vector3, Zero};
+use crate::math::types::{Mat4, Quat, Vec2, Vec3};
+use cgmath::{
+    Deg, ElementWise, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rotation3, Vector2,
+    Vector3, Zero,
+};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Transform2d {
@@ -16,7 +20,8 @@ impl Transform2d {
         }
     }
 
-    pub fn transform_point(&self, point: &Vector2<f32>) -> Vector2<f32> {
+    pub fn transform_point(&self, point: impl Into<Vec2>) -> Vec2 {
+        let point: Vector2<f32> = point.into().into();
         let m00 = self.rotation.cos() * self.scale.x;
         let m01 = -self.rotation.sin();
         let m10 = self.rotation.sin();
@@ -26,7 +31,24 @@ impl Transform2d {
         new_point.x = m00 * point.x + m01 * point.y;
         new_point.y = m10 * point.x + m11 * point.y;
 
-        new_point + self.position
+        (new_point + self.position).into()
+    }
+
+    /// Undo [`Transform2d::transform_point`]. Returns the point unchanged if a scale is zero.
+    pub fn inverse_transform_point(&self, point: impl Into<Vec2>) -> Vec2 {
+        let point: Vector2<f32> = point.into().into();
+        let m00 = self.rotation.cos() * self.scale.x;
+        let m01 = -self.rotation.sin();
+        let m10 = self.rotation.sin();
+        let m11 = self.rotation.cos() * self.scale.y;
+
+        let det = m00 * m11 - m01 * m10;
+        if det.abs() < f32::EPSILON {
+            return point.into();
+        }
+
+        let p = point - self.position;
+        Vec2::new(m11 * p.x - m01 * p.y, -m10 * p.x + m00 * p.y) / det
     }
 }
 
@@ -64,4 +86,152 @@ impl Transform3d {
             scale,
         }
     }
+
+    /// Split an affine matrix into translation, rotation and scale. Shear is lost.
+    pub fn from_matrix(matrix: impl Into<Mat4>) -> Self {
+        let matrix: Matrix4<f32> = matrix.into().into();
+        let scale = Vector3::new(
+            matrix.x.truncate().magnitude(),
+            matrix.y.truncate().magnitude(),
+            matrix.z.truncate().magnitude(),
+        );
+
+        let rotation = Matrix3::from_cols(
+            matrix.x.truncate() / scale.x,
+            matrix.y.truncate() / scale.y,
+            matrix.z.truncate() / scale.z,
+        );
+
+        Self {
+            position: matrix.w.truncate(),
+            rotation: Quaternion::from(rotation).normalize(),
+            scale,
+        }
+    }
+
+    pub fn from_position(position: impl Into<Vec3>) -> Self {
+        Self {
+            position: position.into().into(),
+            ..Self::default()
+        }
+    }
+
+    /// Local -Z, the way cameras and lights face.
+    pub fn forward(&self) -> Vec3 {
+        (self.rotation * -Vector3::unit_z()).into()
+    }
+
+    /// Local +X.
+    pub fn right(&self) -> Vec3 {
+        (self.rotation * Vector3::unit_x()).into()
+    }
+
+    /// Local +Y.
+    pub fn up(&self) -> Vec3 {
+        (self.rotation * Vector3::unit_y()).into()
+    }
+
+    /// Turn so that [`Transform3d::forward`] points at `target`. Does nothing if `target`
+    /// is at the position.
+    pub fn look_at(&mut self, target: impl Into<Vec3>, up: impl Into<Vec3>) {
+        let up: Vector3<f32> = up.into().into();
+        let direction = Vector3::from(target.into()) - self.position;
+        if direction.magnitude2() < f32::EPSILON {
+            return;
+        }
+
+        // Fall back to another up axis when looking straight along it.
+        let up = if direction.normalize().cross(up).magnitude2() < 1e-6 {
+            Vector3::unit_z()
+        } else {
+            up
+        };
+
+        // look_to_rh is a view matrix, the rotation of the transform is its inverse.
+        let view = Matrix3::look_to_rh(direction, up);
+        self.rotation = Quaternion::from(view).conjugate().normalize();
+    }
+
+    pub fn looking_at(mut self, target: impl Into<Vec3>, up: impl Into<Vec3>) -> Self {
+        self.look_at(target, up);
+        self
+    }
+
+    /// Orbit around `point`, turning along with the orbit.
+    pub fn rotate_around(&mut self, point: impl Into<Vec3>, rotation: impl Into<Quat>) {
+        let point: Vector3<f32> = point.into().into();
+        let rotation: Quaternion<f32> = rotation.into().into();
+        self.position = point + rotation * (self.position - point);
+        self.rotation = (rotation * self.rotation).normalize();
+    }
+
+    /// Rotate in place around a local axis.
+    pub fn rotate_local(&mut self, rotation: impl Into<Quat>) {
+        self.rotation = (self.rotation * Quaternion::from(rotation.into())).normalize();
+    }
+
+    /// Global transform of a child with this as its parent transform. Like multiplying
+    /// the matrices, except that shear from non-uniform scale is lost.
+    pub fn mul_transform(&self, child: &Transform3d) -> Transform3d {
+        Transform3d {
+            position: self.transform_point(child.position).into(),
+            rotation: (self.rotation * child.rotation).normalize(),
+            scale: self.scale.mul_element_wise(child.scale),
+        }
+    }
+
+    /// The transform that undoes this one. Exact for uniform scale.
+    pub fn inverse(&self) -> Transform3d {
+        let rotation = self.rotation.conjugate();
+        let scale = Vector3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        let position = -(rotation * self.position).mul_element_wise(scale);
+
+        Transform3d {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Express a global transform relative to this one, the opposite of
+    /// [`Transform3d::mul_transform`].
+    pub fn to_local(&self, global: &Transform3d) -> Transform3d {
+        self.inverse().mul_transform(global)
+    }
+
+    /// Apply scale, rotation and translation to a point.
+    pub fn transform_point(&self, point: impl Into<Vec3>) -> Vec3 {
+        Vec3::from(self.position) + self.transform_vector(point)
+    }
+
+    /// Apply scale and rotation to a direction or offset.
+    pub fn transform_vector(&self, vector: impl Into<Vec3>) -> Vec3 {
+        let vector = vector.into();
+        (self.rotation
+            * Vector3::new(
+                vector.x * self.scale.x,
+                vector.y * self.scale.y,
+                vector.z * self.scale.z,
+            ))
+        .into()
+    }
+
+    /// Undo [`Transform3d::transform_point`].
+    pub fn inverse_transform_point(&self, point: impl Into<Vec3>) -> Vec3 {
+        let local = self.rotation.conjugate() * (Vector3::from(point.into()) - self.position);
+
+        Vec3::new(
+            local.x / self.scale.x,
+            local.y / self.scale.y,
+            local.z / self.scale.z,
+        )
+    }
+
+    /// Scale, then rotate, then translate.
+    pub fn to_matrix(&self) -> Mat4 {
+        (Matrix4::from_translation(self.position)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z))
+        .into()
+    }
 }
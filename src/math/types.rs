@@ -0,0 +1,407 @@
+//! Engine math types, so the math backend can change without breaking users.
+//!
+//! These are plain `#[repr(C)]` structs that convert to and from the cgmath types the
+//! engine uses internally, as well as arrays. Transforms, 3D nodes, cameras and 2D lights
+//! take `impl Into<Vec3>` and friends and return these types, so both keep working while
+//! code moves over.
+
+use cgmath::{InnerSpace, Matrix, Rotation3, SquareMatrix};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+macro_rules! impl_vector {
+    ($name:ident, $cg:ident, $n:literal, $($field:ident),+) => {
+        #[repr(C)]
+        #[derive(Debug, Copy, Clone, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+        pub struct $name {
+            $(pub $field: f32),+
+        }
+
+        impl $name {
+            pub const ZERO: $name = $name { $($field: 0.0),+ };
+            pub const ONE: $name = $name { $($field: 1.0),+ };
+
+            pub const fn new($($field: f32),+) -> Self {
+                Self { $($field),+ }
+            }
+
+            pub const fn splat(value: f32) -> Self {
+                Self { $($field: value),+ }
+            }
+
+            pub fn dot(self, other: Self) -> f32 {
+                0.0 $(+ self.$field * other.$field)+
+            }
+
+            pub fn length(self) -> f32 {
+                self.dot(self).sqrt()
+            }
+
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
+
+            /// Zero stays zero.
+            pub fn normalize_or_zero(self) -> Self {
+                let length = self.length();
+                if length > f32::EPSILON {
+                    self / length
+                } else {
+                    Self::ZERO
+                }
+            }
+
+            pub fn lerp(self, other: Self, t: f32) -> Self {
+                self + (other - self) * t
+            }
+
+            pub fn min(self, other: Self) -> Self {
+                Self { $($field: self.$field.min(other.$field)),+ }
+            }
+
+            pub fn max(self, other: Self) -> Self {
+                Self { $($field: self.$field.max(other.$field)),+ }
+            }
+
+            pub fn to_array(self) -> [f32; $n] {
+                [$(self.$field),+]
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Self { $($field: self.$field + other.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self { $($field: self.$field - other.$field),+ }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = Self;
+
+            fn mul(self, scalar: f32) -> Self {
+                Self { $($field: self.$field * scalar),+ }
+            }
+        }
+
+        /// Component-wise.
+        impl Mul for $name {
+            type Output = Self;
+
+            fn mul(self, other: Self) -> Self {
+                Self { $($field: self.$field * other.$field),+ }
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = Self;
+
+            fn div(self, scalar: f32) -> Self {
+                Self { $($field: self.$field / scalar),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                *self = *self - other;
+            }
+        }
+
+        impl MulAssign<f32> for $name {
+            fn mul_assign(&mut self, scalar: f32) {
+                *self = *self * scalar;
+            }
+        }
+
+        impl From<cgmath::$cg<f32>> for $name {
+            fn from(v: cgmath::$cg<f32>) -> Self {
+                Self { $($field: v.$field),+ }
+            }
+        }
+
+        impl From<&cgmath::$cg<f32>> for $name {
+            fn from(v: &cgmath::$cg<f32>) -> Self {
+                Self::from(*v)
+            }
+        }
+
+        impl From<$name> for cgmath::$cg<f32> {
+            fn from(v: $name) -> Self {
+                cgmath::$cg::new($(v.$field),+)
+            }
+        }
+
+        impl From<[f32; $n]> for $name {
+            fn from(a: [f32; $n]) -> Self {
+                let [$($field),+] = a;
+                Self { $($field),+ }
+            }
+        }
+
+        impl From<$name> for [f32; $n] {
+            fn from(v: $name) -> Self {
+                v.to_array()
+            }
+        }
+    };
+}
+
+impl_vector!(Vec2, Vector2, 2, x, y);
+impl_vector!(Vec3, Vector3, 3, x, y, z);
+impl_vector!(Vec4, Vector4, 4, x, y, z, w);
+
+impl Vec3 {
+    pub const X: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    pub const Y: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+    pub const Z: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        cgmath::Vector3::from(self).cross(other.into()).into()
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+}
+
+impl Vec4 {
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+impl From<cgmath::Point3<f32>> for Vec3 {
+    fn from(p: cgmath::Point3<f32>) -> Self {
+        Vec3::new(p.x, p.y, p.z)
+    }
+}
+
+impl From<Vec3> for cgmath::Point3<f32> {
+    fn from(v: Vec3) -> Self {
+        cgmath::Point3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Vec2::new(x, y)
+    }
+}
+
+impl From<(f32, f32, f32)> for Vec3 {
+    fn from((x, y, z): (f32, f32, f32)) -> Self {
+        Vec3::new(x, y, z)
+    }
+}
+
+/// Rotation as a unit quaternion.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    /// Angle in radians.
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quat {
+        cgmath::Quaternion::from_axis_angle(
+            cgmath::Vector3::from(axis).normalize(),
+            cgmath::Rad(angle),
+        )
+        .into()
+    }
+
+    /// Rotate `a` onto `b`.
+    pub fn from_arc(a: Vec3, b: Vec3) -> Quat {
+        cgmath::Quaternion::from_arc(a.into(), b.into(), None).into()
+    }
+
+    pub fn inverse(self) -> Quat {
+        cgmath::Quaternion::from(self).conjugate().into()
+    }
+
+    pub fn slerp(self, other: Quat, t: f32) -> Quat {
+        cgmath::Quaternion::from(self).slerp(other.into(), t).into()
+    }
+
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        (cgmath::Quaternion::from(self) * cgmath::Vector3::from(v)).into()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, other: Quat) -> Quat {
+        (cgmath::Quaternion::from(self) * cgmath::Quaternion::from(other)).into()
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.rotate(v)
+    }
+}
+
+impl From<cgmath::Quaternion<f32>> for Quat {
+    fn from(q: cgmath::Quaternion<f32>) -> Self {
+        Quat {
+            x: q.v.x,
+            y: q.v.y,
+            z: q.v.z,
+            w: q.s,
+        }
+    }
+}
+
+impl From<Quat> for cgmath::Quaternion<f32> {
+    fn from(q: Quat) -> Self {
+        cgmath::Quaternion::new(q.w, q.x, q.y, q.z)
+    }
+}
+
+/// Column-major 4x4 matrix, the layout shaders expect.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Mat4 {
+    pub x_axis: Vec4,
+    pub y_axis: Vec4,
+    pub z_axis: Vec4,
+    pub w_axis: Vec4,
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        x_axis: Vec4::new(1.0, 0.0, 0.0, 0.0),
+        y_axis: Vec4::new(0.0, 1.0, 0.0, 0.0),
+        z_axis: Vec4::new(0.0, 0.0, 1.0, 0.0),
+        w_axis: Vec4::new(0.0, 0.0, 0.0, 1.0),
+    };
+
+    pub fn from_translation(translation: Vec3) -> Mat4 {
+        cgmath::Matrix4::from_translation(translation.into()).into()
+    }
+
+    pub fn from_quat(rotation: Quat) -> Mat4 {
+        cgmath::Matrix4::from(cgmath::Quaternion::from(rotation)).into()
+    }
+
+    pub fn from_scale(scale: Vec3) -> Mat4 {
+        cgmath::Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z).into()
+    }
+
+    /// Scale, then rotate, then translate.
+    pub fn from_scale_rotation_translation(scale: Vec3, rotation: Quat, translation: Vec3) -> Mat4 {
+        Mat4::from_translation(translation) * Mat4::from_quat(rotation) * Mat4::from_scale(scale)
+    }
+
+    pub fn transpose(self) -> Mat4 {
+        cgmath::Matrix4::from(self).transpose().into()
+    }
+
+    pub fn inverse(self) -> Option<Mat4> {
+        cgmath::Matrix4::from(self).invert().map(Mat4::from)
+    }
+
+    pub fn transform_point(self, point: Vec3) -> Vec3 {
+        let p = self * point.extend(1.0);
+        p.truncate() / p.w
+    }
+
+    pub fn transform_vector(self, vector: Vec3) -> Vec3 {
+        (self * vector.extend(0.0)).truncate()
+    }
+
+    pub fn to_cols_array_2d(self) -> [[f32; 4]; 4] {
+        cgmath::Matrix4::from(self).into()
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        (cgmath::Matrix4::from(self) * cgmath::Matrix4::from(other)).into()
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        (cgmath::Matrix4::from(self) * cgmath::Vector4::from(v)).into()
+    }
+}
+
+impl From<cgmath::Matrix4<f32>> for Mat4 {
+    fn from(m: cgmath::Matrix4<f32>) -> Self {
+        Mat4 {
+            x_axis: m.x.into(),
+            y_axis: m.y.into(),
+            z_axis: m.z.into(),
+            w_axis: m.w.into(),
+        }
+    }
+}
+
+impl From<Mat4> for cgmath::Matrix4<f32> {
+    fn from(m: Mat4) -> Self {
+        cgmath::Matrix4::from_cols(
+            m.x_axis.into(),
+            m.y_axis.into(),
+            m.z_axis.into(),
+            m.w_axis.into(),
+        )
+    }
+}
+
+impl From<[[f32; 4]; 4]> for Mat4 {
+    fn from(cols: [[f32; 4]; 4]) -> Self {
+        cgmath::Matrix4::from(cols).into()
+    }
+}
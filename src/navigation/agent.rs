@@ -0,0 +1,73 @@
+use crate::navigation::NavMesh;
+use cgmath::{InnerSpace, Vector2};
+
+/// Follows a path on a navigation mesh at a constant speed.
+pub struct NavAgent {
+    pub position: Vector2<f32>,
+
+    /// Units per second.
+    pub speed: f32,
+
+    path: Vec<Vector2<f32>>,
+    next_point: usize,
+}
+
+impl NavAgent {
+    pub fn new(position: Vector2<f32>, speed: f32) -> Self {
+        Self {
+            position,
+            speed,
+            path: vec![],
+            next_point: 0,
+        }
+    }
+
+    /// Query a path to the target. Returns false if the target is unreachable.
+    pub fn set_target(&mut self, navmesh: &NavMesh, target: Vector2<f32>) -> bool {
+        match navmesh.find_path(self.position, target) {
+            Some(path) => {
+                self.set_path(path);
+                true
+            }
+            None => {
+                self.set_path(vec![]);
+                false
+            }
+        }
+    }
+
+    pub fn set_path(&mut self, path: Vec<Vector2<f32>>) {
+        self.path = path;
+        self.next_point = 0;
+    }
+
+    pub fn get_path(&self) -> &[Vector2<f32>] {
+        &self.path
+    }
+
+    pub fn is_navigation_finished(&self) -> bool {
+        self.next_point >= self.path.len()
+    }
+
+    /// Move along the path. Returns the new position.
+    pub fn advance(&mut self, dt: f32) -> Vector2<f32> {
+        let mut distance = self.speed * dt;
+
+        while distance > 0.0 && !self.is_navigation_finished() {
+            let target = self.path[self.next_point];
+            let to_target = target - self.position;
+            let length = to_target.magnitude();
+
+            if length <= distance {
+                self.position = target;
+                self.next_point += 1;
+                distance -= length;
+            } else {
+                self.position += to_target / length * distance;
+                distance = 0.0;
+            }
+        }
+
+        self.position
+    }
+}
@@ -0,0 +1,154 @@
+use crate::navigation::OpenNode;
+use cgmath::Vector2;
+use std::collections::BinaryHeap;
+
+/// Walkability grid for tile-based pathfinding.
+pub struct NavGrid {
+    width: u32,
+    height: u32,
+    walkable: Vec<bool>,
+
+    /// Allow diagonal moves. Corners of blocked cells are never cut.
+    pub allow_diagonal: bool,
+}
+
+impl NavGrid {
+    /// Create a grid with all cells walkable.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            walkable: vec![true; (width * height) as usize],
+            allow_diagonal: true,
+        }
+    }
+
+    pub fn get_size(&self) -> Vector2<u32> {
+        Vector2::new(self.width, self.height)
+    }
+
+    pub fn is_in_bounds(&self, cell: Vector2<i32>) -> bool {
+        cell.x >= 0 && cell.y >= 0 && (cell.x as u32) < self.width && (cell.y as u32) < self.height
+    }
+
+    pub fn is_walkable(&self, cell: Vector2<i32>) -> bool {
+        self.is_in_bounds(cell) && self.walkable[self.to_index(cell)]
+    }
+
+    pub fn set_walkable(&mut self, cell: Vector2<i32>, walkable: bool) {
+        if self.is_in_bounds(cell) {
+            let index = self.to_index(cell);
+            self.walkable[index] = walkable;
+        }
+    }
+
+    fn to_index(&self, cell: Vector2<i32>) -> usize {
+        (cell.y as u32 * self.width + cell.x as u32) as usize
+    }
+
+    fn to_cell(&self, index: usize) -> Vector2<i32> {
+        Vector2::new(
+            (index as u32 % self.width) as i32,
+            (index as u32 / self.width) as i32,
+        )
+    }
+
+    /// Octile distance, admissible for both 4 and 8 connectivity.
+    fn heuristic(&self, a: Vector2<i32>, b: Vector2<i32>) -> f32 {
+        let dx = (a.x - b.x).abs() as f32;
+        let dy = (a.y - b.y).abs() as f32;
+
+        if self.allow_diagonal {
+            dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+        } else {
+            dx + dy
+        }
+    }
+
+    /// Find the shortest path between two cells using A*.
+    /// The returned path includes both the start and the goal.
+    pub fn find_path(&self, start: Vector2<i32>, goal: Vector2<i32>) -> Option<Vec<Vector2<i32>>> {
+        if !self.is_walkable(start) || !self.is_walkable(goal) {
+            return None;
+        }
+
+        let cell_count = self.walkable.len();
+        let mut g_costs = vec![f32::INFINITY; cell_count];
+        let mut came_from = vec![usize::MAX; cell_count];
+        let mut closed = vec![false; cell_count];
+        let mut open = BinaryHeap::new();
+
+        let start_index = self.to_index(start);
+        let goal_index = self.to_index(goal);
+
+        g_costs[start_index] = 0.0;
+        open.push(OpenNode {
+            cost: self.heuristic(start, goal),
+            index: start_index,
+        });
+
+        const STRAIGHT: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == goal_index {
+                let mut path = vec![goal];
+                let mut current = index;
+
+                while current != start_index {
+                    current = came_from[current];
+                    path.push(self.to_cell(current));
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+
+            let cell = self.to_cell(index);
+
+            let diagonal: &[(i32, i32)] = if self.allow_diagonal { &DIAGONAL } else { &[] };
+
+            for &(dx, dy) in STRAIGHT.iter().chain(diagonal) {
+                let next = Vector2::new(cell.x + dx, cell.y + dy);
+
+                if !self.is_walkable(next) {
+                    continue;
+                }
+
+                let is_diagonal = dx != 0 && dy != 0;
+
+                // Don't cut corners.
+                if is_diagonal
+                    && (!self.is_walkable(Vector2::new(cell.x + dx, cell.y))
+                        || !self.is_walkable(Vector2::new(cell.x, cell.y + dy)))
+                {
+                    continue;
+                }
+
+                let next_index = self.to_index(next);
+                let step_cost = if is_diagonal {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let g_cost = g_costs[index] + step_cost;
+
+                if g_cost < g_costs[next_index] {
+                    g_costs[next_index] = g_cost;
+                    came_from[next_index] = index;
+                    open.push(OpenNode {
+                        cost: g_cost + self.heuristic(next, goal),
+                        index: next_index,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,30 @@
+pub(crate) mod agent;
+pub(crate) mod grid;
+pub(crate) mod navmesh;
+
+pub use agent::*;
+pub use grid::*;
+pub use navmesh::*;
+
+use std::cmp::Ordering;
+
+/// Entry in an A* open list. Ordered so that `BinaryHeap` pops the lowest cost first.
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) struct OpenNode {
+    pub(crate) cost: f32,
+    pub(crate) index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,278 @@
+use crate::navigation::OpenNode;
+use cgmath::{InnerSpace, Vector2};
+use std::collections::{BinaryHeap, HashMap};
+
+/// A shared edge between two polygons.
+#[derive(Debug, Copy, Clone)]
+struct Link {
+    polygon: usize,
+    /// Portal end points, seen from the polygon the link belongs to.
+    left: Vector2<f32>,
+    right: Vector2<f32>,
+}
+
+/// Navigation mesh made of convex polygons.
+pub struct NavMesh {
+    vertices: Vec<Vector2<f32>>,
+    /// Vertex indices of each polygon, counter-clockwise.
+    polygons: Vec<Vec<usize>>,
+    centroids: Vec<Vector2<f32>>,
+    links: Vec<Vec<Link>>,
+}
+
+impl NavMesh {
+    /// Build a navigation mesh from convex polygons.
+    /// Polygons sharing an edge (identical end points) are connected.
+    pub fn from_polygons(polygons: &[Vec<Vector2<f32>>]) -> Self {
+        let mut vertices: Vec<Vector2<f32>> = vec![];
+        let mut indexed = vec![];
+
+        for polygon in polygons {
+            let indices = polygon
+                .iter()
+                .map(|p| match vertices.iter().position(|v| v == p) {
+                    Some(i) => i,
+                    None => {
+                        vertices.push(*p);
+                        vertices.len() - 1
+                    }
+                })
+                .collect();
+
+            indexed.push(indices);
+        }
+
+        Self::new(vertices, indexed)
+    }
+
+    pub fn new(vertices: Vec<Vector2<f32>>, mut polygons: Vec<Vec<usize>>) -> Self {
+        // Make sure all polygons wind counter-clockwise.
+        for polygon in &mut polygons {
+            if signed_area(&vertices, polygon) < 0.0 {
+                polygon.reverse();
+            }
+        }
+
+        let centroids = polygons
+            .iter()
+            .map(|polygon| {
+                polygon
+                    .iter()
+                    .fold(Vector2::new(0.0, 0.0), |sum, &i| sum + vertices[i])
+                    / polygon.len() as f32
+            })
+            .collect();
+
+        // Find shared edges.
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (p, polygon) in polygons.iter().enumerate() {
+            for i in 0..polygon.len() {
+                let a = polygon[i];
+                let b = polygon[(i + 1) % polygon.len()];
+                edges.entry((a.min(b), a.max(b))).or_default().push(p);
+            }
+        }
+
+        let mut links = vec![vec![]; polygons.len()];
+        for (p, polygon) in polygons.iter().enumerate() {
+            for i in 0..polygon.len() {
+                let a = polygon[i];
+                let b = polygon[(i + 1) % polygon.len()];
+
+                for &other in &edges[&(a.min(b), a.max(b))] {
+                    if other != p {
+                        // Leaving a CCW polygon through edge a->b, b is on the left.
+                        links[p].push(Link {
+                            polygon: other,
+                            left: vertices[b],
+                            right: vertices[a],
+                        });
+                    }
+                }
+            }
+        }
+
+        Self {
+            vertices,
+            polygons,
+            centroids,
+            links,
+        }
+    }
+
+    /// Index of the polygon containing the point.
+    pub fn find_polygon(&self, point: Vector2<f32>) -> Option<usize> {
+        self.polygons.iter().position(|polygon| {
+            (0..polygon.len()).all(|i| {
+                let a = self.vertices[polygon[i]];
+                let b = self.vertices[polygon[(i + 1) % polygon.len()]];
+                cross(a, b, point) >= 0.0
+            })
+        })
+    }
+
+    /// Find the shortest path between two points on the mesh.
+    /// The returned path includes both end points.
+    pub fn find_path(&self, start: Vector2<f32>, end: Vector2<f32>) -> Option<Vec<Vector2<f32>>> {
+        let start_polygon = self.find_polygon(start)?;
+        let end_polygon = self.find_polygon(end)?;
+
+        let corridor = self.find_corridor(start_polygon, end_polygon, end)?;
+
+        // Portals between consecutive polygons, framed by the end points.
+        let mut portals = vec![(start, start)];
+        for pair in corridor.windows(2) {
+            let link = self.links[pair[0]]
+                .iter()
+                .find(|l| l.polygon == pair[1])
+                .unwrap();
+            portals.push((link.left, link.right));
+        }
+        portals.push((end, end));
+
+        Some(string_pull(&portals))
+    }
+
+    /// A* over polygons, using centroids as graph nodes.
+    fn find_corridor(&self, start: usize, end: usize, goal: Vector2<f32>) -> Option<Vec<usize>> {
+        let count = self.polygons.len();
+        let mut g_costs = vec![f32::INFINITY; count];
+        let mut came_from = vec![usize::MAX; count];
+        let mut closed = vec![false; count];
+        let mut open = BinaryHeap::new();
+
+        g_costs[start] = 0.0;
+        open.push(OpenNode {
+            cost: 0.0,
+            index: start,
+        });
+
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == end {
+                let mut corridor = vec![end];
+                let mut current = end;
+
+                while current != start {
+                    current = came_from[current];
+                    corridor.push(current);
+                }
+
+                corridor.reverse();
+                return Some(corridor);
+            }
+
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+
+            for link in &self.links[index] {
+                let next = link.polygon;
+                let g_cost =
+                    g_costs[index] + (self.centroids[next] - self.centroids[index]).magnitude();
+
+                if g_cost < g_costs[next] {
+                    g_costs[next] = g_cost;
+                    came_from[next] = index;
+                    open.push(OpenNode {
+                        cost: g_cost + (goal - self.centroids[next]).magnitude(),
+                        index: next,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Polygon outlines as line segments, for debug drawing.
+    pub fn get_debug_lines(&self) -> Vec<(Vector2<f32>, Vector2<f32>)> {
+        let mut lines = vec![];
+
+        for polygon in &self.polygons {
+            for i in 0..polygon.len() {
+                lines.push((
+                    self.vertices[polygon[i]],
+                    self.vertices[polygon[(i + 1) % polygon.len()]],
+                ));
+            }
+        }
+
+        lines
+    }
+}
+
+/// Positive if c is on the left of a->b.
+fn cross(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+fn signed_area(vertices: &[Vector2<f32>], polygon: &[usize]) -> f32 {
+    let mut area = 0.0;
+
+    for i in 0..polygon.len() {
+        let a = vertices[polygon[i]];
+        let b = vertices[polygon[(i + 1) % polygon.len()]];
+        area += a.x * b.y - b.x * a.y;
+    }
+
+    area * 0.5
+}
+
+/// Simple stupid funnel algorithm.
+/// See http://digestingduck.blogspot.com/2010/03/simple-stupid-funnel-algorithm.html.
+fn string_pull(portals: &[(Vector2<f32>, Vector2<f32>)]) -> Vec<Vector2<f32>> {
+    let mut path = vec![portals[0].0];
+
+    let mut apex = portals[0].0;
+    let mut left = portals[0].0;
+    let mut right = portals[0].1;
+    let mut left_index = 0;
+    let mut right_index = 0;
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+
+        // Try to narrow the right side.
+        if cross(apex, right, next_right) >= 0.0 {
+            if apex == right || cross(apex, left, next_right) < 0.0 {
+                right = next_right;
+                right_index = i;
+            } else {
+                // Right crossed over left, so left becomes the new apex.
+                path.push(left);
+                apex = left;
+                right = apex;
+                right_index = left_index;
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        // Try to narrow the left side.
+        if cross(apex, left, next_left) <= 0.0 {
+            if apex == left || cross(apex, right, next_left) > 0.0 {
+                left = next_left;
+                left_index = i;
+            } else {
+                // Left crossed over right, so right becomes the new apex.
+                path.push(right);
+                apex = right;
+                left = apex;
+                left_index = right_index;
+                i = right_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    let end = portals[portals.len() - 1].0;
+    if path.last() != Some(&end) {
+        path.push(end);
+    }
+
+    path
+}
@@ -0,0 +1,5 @@
+pub(crate) mod network;
+pub(crate) mod transport;
+
+pub use network::*;
+pub use transport::*;
@@ -0,0 +1,240 @@
+use crate::net::transport::{Channel, Endpoint, NetEvent, PeerId};
+use crate::scene::NodeId;
+use crate::scene::{TransformState, World};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::ToSocketAddrs;
+
+/// Identifies a replicated node across peers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NetId(pub u32);
+
+/// Transforms per replication message, to keep it under the payload limit.
+const TRANSFORMS_PER_MESSAGE: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+enum NetMessage {
+    User(Vec<u8>),
+    Replication {
+        tick: u32,
+        transforms: Vec<(NetId, TransformState)>,
+    },
+}
+
+/// Connects to other instances of the game, and keeps marked nodes in sync.
+///
+/// The server sends the transforms of replicated nodes to all clients, which apply them
+/// to their nodes with the same net IDs.
+pub struct Network {
+    endpoint: Option<Endpoint>,
+
+    /// Index is the net ID.
+    replicated: Vec<NodeId>,
+
+    /// Replication messages per second.
+    pub send_rate: f32,
+    send_timer: f32,
+
+    tick: u32,
+    last_received_tick: Option<u32>,
+
+    events: VecDeque<NetEvent>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            replicated: vec![],
+            send_rate: 20.0,
+            send_timer: 0.0,
+            tick: 0,
+            last_received_tick: None,
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a server, e.g. on "0.0.0.0:7777".
+    pub fn host(&mut self, addr: impl ToSocketAddrs) -> Result<()> {
+        self.disconnect();
+        self.endpoint = Some(Endpoint::host(addr)?);
+        Ok(())
+    }
+
+    /// Connect to a server. [`NetEvent::Connected`] is sent once it accepts.
+    pub fn connect(&mut self, addr: impl ToSocketAddrs) -> Result<()> {
+        self.disconnect();
+        self.endpoint = Some(Endpoint::connect(addr)?);
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        if let Some(mut endpoint) = self.endpoint.take() {
+            endpoint.disconnect();
+        }
+
+        self.last_received_tick = None;
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.endpoint.as_ref().is_some_and(|e| e.is_server())
+    }
+
+    pub fn is_client(&self) -> bool {
+        self.endpoint.as_ref().is_some_and(|e| !e.is_server())
+    }
+
+    /// Connected clients on the server, or the server on clients.
+    pub fn get_peers(&self) -> Vec<PeerId> {
+        self.endpoint
+            .as_ref()
+            .map_or(vec![], |e| e.peers().collect())
+    }
+
+    /// Keep a node's transform in sync from the server to clients.
+    ///
+    /// Net IDs are given out in order, so the server and clients have to mark
+    /// the same nodes in the same order.
+    pub fn replicate(&mut self, node: NodeId) -> NetId {
+        self.replicated.push(node);
+        NetId(self.replicated.len() as u32 - 1)
+    }
+
+    pub fn get_replicated_node(&self, net_id: NetId) -> Option<NodeId> {
+        self.replicated.get(net_id.0 as usize).copied()
+    }
+
+    /// Stop syncing all nodes, e.g. when changing scenes.
+    pub fn clear_replicated(&mut self) {
+        self.replicated.clear();
+    }
+
+    /// Send a message to a peer.
+    pub fn send(&mut self, peer: PeerId, channel: Channel, payload: &[u8]) -> Result<()> {
+        let endpoint = self
+            .endpoint
+            .as_mut()
+            .ok_or_else(|| anyhow!("Not connected"))?;
+
+        let message = bincode::serialize(&NetMessage::User(payload.to_vec()))?;
+        endpoint.send(peer, channel, &message)
+    }
+
+    /// Send a message to all peers. On clients, this is just the server.
+    pub fn broadcast(&mut self, channel: Channel, payload: &[u8]) -> Result<()> {
+        for peer in self.get_peers() {
+            self.send(peer, channel, payload)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn poll_event(&mut self) -> Option<NetEvent> {
+        self.events.pop_front()
+    }
+
+    pub(crate) fn update(&mut self, dt: f32, world: &mut World) {
+        let Some(endpoint) = &mut self.endpoint else {
+            return;
+        };
+
+        endpoint.update();
+
+        while let Some(event) = endpoint.poll_event() {
+            let NetEvent::Message {
+                peer,
+                channel,
+                payload,
+            } = event
+            else {
+                self.events.push_back(event);
+                continue;
+            };
+
+            match bincode::deserialize::<NetMessage>(&payload) {
+                Ok(NetMessage::User(payload)) => {
+                    self.events.push_back(NetEvent::Message {
+                        peer,
+                        channel,
+                        payload,
+                    });
+                }
+                Ok(NetMessage::Replication { tick, transforms }) => {
+                    // Messages of the same tick are applied together, older ones are dropped.
+                    if endpoint.is_server() || self.last_received_tick.is_some_and(|t| tick < t) {
+                        continue;
+                    }
+                    self.last_received_tick = Some(tick);
+
+                    for (net_id, transform) in transforms {
+                        if let Some(node) = self.replicated.get(net_id.0 as usize) {
+                            world.set_transform_state(*node, &transform);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Invalid message from {:?}: {}", peer, e);
+                }
+            }
+        }
+
+        if endpoint.is_server() {
+            self.send_timer += dt;
+
+            if self.send_timer >= 1.0 / self.send_rate {
+                self.send_timer = 0.0;
+                self.send_replication(world);
+            }
+        }
+    }
+
+    fn send_replication(&mut self, world: &mut World) {
+        let Some(endpoint) = &mut self.endpoint else {
+            return;
+        };
+
+        let peers: Vec<PeerId> = endpoint.peers().collect();
+        if peers.is_empty() {
+            return;
+        }
+
+        self.tick = self.tick.wrapping_add(1);
+
+        // Removed nodes are skipped.
+        let transforms: Vec<(NetId, TransformState)> = self
+            .replicated
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| {
+                if !world.arena.contains(*node) {
+                    return None;
+                }
+
+                world
+                    .get_transform_state(*node)
+                    .map(|t| (NetId(i as u32), t))
+            })
+            .collect();
+
+        for chunk in transforms.chunks(TRANSFORMS_PER_MESSAGE) {
+            let message = NetMessage::Replication {
+                tick: self.tick,
+                transforms: chunk.to_vec(),
+            };
+            let payload = bincode::serialize(&message).unwrap();
+
+            for peer in &peers {
+                if let Err(e) = endpoint.send(*peer, Channel::Unreliable, &payload) {
+                    log::warn!("Failed to replicate to {:?}: {}", peer, e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Packets from other programs or versions are ignored.
+const PROTOCOL_ID: u32 = 0x4575_0001;
+
+/// Payloads above this may be dropped by routers, as we don't fragment packets.
+pub const MAX_PAYLOAD_SIZE: usize = 1024;
+
+const CONNECT_INTERVAL: Duration = Duration::from_millis(250);
+const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PeerId(pub u32);
+
+impl PeerId {
+    /// How clients see the server.
+    pub const SERVER: PeerId = PeerId(0);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    /// Resent until acknowledged, and received in the order it was sent.
+    Reliable,
+    /// May be lost. Messages older than the last received one are dropped.
+    Unreliable,
+}
+
+#[derive(Debug, Clone)]
+pub enum NetEvent {
+    Connected(PeerId),
+    Disconnected(PeerId),
+    Message {
+        /// On clients, this is always the server.
+        peer: PeerId,
+        channel: Channel,
+        payload: Vec<u8>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    Connect,
+    Accept { peer: PeerId },
+    Disconnect,
+    Heartbeat,
+    Reliable { sequence: u32, payload: Vec<u8> },
+    Ack { sequence: u32 },
+    Unreliable { sequence: u32, payload: Vec<u8> },
+}
+
+struct Connection {
+    peer: PeerId,
+    last_received: Instant,
+    last_sent: Instant,
+
+    next_reliable: u32,
+    /// Sent reliable payloads and when they were last sent.
+    unacked: BTreeMap<u32, (Vec<u8>, Instant)>,
+    next_expected: u32,
+    /// Reliable payloads that arrived ahead of a missing one.
+    out_of_order: BTreeMap<u32, Vec<u8>>,
+
+    next_unreliable: u32,
+    last_unreliable: Option<u32>,
+}
+
+impl Connection {
+    fn new(peer: PeerId) -> Self {
+        let now = Instant::now();
+
+        Self {
+            peer,
+            last_received: now,
+            last_sent: now,
+            next_reliable: 0,
+            unacked: BTreeMap::new(),
+            next_expected: 0,
+            out_of_order: BTreeMap::new(),
+            next_unreliable: 0,
+            last_unreliable: None,
+        }
+    }
+}
+
+enum Role {
+    Server {
+        next_peer: u32,
+    },
+    Client {
+        server: SocketAddr,
+        last_connect: Option<Instant>,
+    },
+}
+
+/// A UDP socket with connections, acknowledgements and ordering on top.
+pub(crate) struct Endpoint {
+    socket: UdpSocket,
+    role: Role,
+    connections: HashMap<SocketAddr, Connection>,
+    events: VecDeque<NetEvent>,
+}
+
+impl Endpoint {
+    pub(crate) fn host(addr: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+
+        log::info!("Hosting on {}", socket.local_addr()?);
+
+        Ok(Self {
+            socket,
+            role: Role::Server { next_peer: 1 },
+            connections: HashMap::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    pub(crate) fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let server = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("No server address"))?;
+
+        let local_addr: SocketAddr = if server.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            role: Role::Client {
+                server,
+                last_connect: None,
+            },
+            connections: HashMap::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    pub(crate) fn is_server(&self) -> bool {
+        matches!(self.role, Role::Server { .. })
+    }
+
+    pub(crate) fn peers(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.connections.values().map(|c| c.peer)
+    }
+
+    pub(crate) fn poll_event(&mut self) -> Option<NetEvent> {
+        self.events.pop_front()
+    }
+
+    /// Receive packets, resend lost ones and drop timed out connections.
+    pub(crate) fn update(&mut self) {
+        let mut buffer = [0u8; 2048];
+
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, addr)) => {
+                    let Ok((protocol_id, packet)) =
+                        bincode::deserialize::<(u32, Packet)>(&buffer[..size])
+                    else {
+                        continue;
+                    };
+
+                    if protocol_id == PROTOCOL_ID {
+                        self.handle_packet(addr, packet);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("Failed to receive packet: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let now = Instant::now();
+
+        // Keep asking the server until it accepts.
+        if let Role::Client {
+            server,
+            last_connect,
+        } = &mut self.role
+        {
+            if self.connections.is_empty()
+                && last_connect.is_none_or(|t| now - t >= CONNECT_INTERVAL)
+            {
+                *last_connect = Some(now);
+                let server = *server;
+                self.send_packet(server, &Packet::Connect);
+            }
+        }
+
+        let mut outgoing = vec![];
+        let mut timed_out = vec![];
+
+        for (addr, connection) in &mut self.connections {
+            if now - connection.last_received >= TIMEOUT {
+                timed_out.push(*addr);
+                continue;
+            }
+
+            for (sequence, (payload, sent)) in &mut connection.unacked {
+                if now - *sent >= RESEND_INTERVAL {
+                    *sent = now;
+                    outgoing.push((
+                        *addr,
+                        Packet::Reliable {
+                            sequence: *sequence,
+                            payload: payload.clone(),
+                        },
+                    ));
+                }
+            }
+
+            if now - connection.last_sent >= HEARTBEAT_INTERVAL {
+                outgoing.push((*addr, Packet::Heartbeat));
+            }
+        }
+
+        for (addr, packet) in outgoing {
+            self.send_packet(addr, &packet);
+        }
+
+        for addr in timed_out {
+            if let Some(connection) = self.connections.remove(&addr) {
+                log::info!("Peer {:?} timed out", connection.peer);
+                self.events
+                    .push_back(NetEvent::Disconnected(connection.peer));
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, addr: SocketAddr, packet: Packet) {
+        match (&mut self.role, packet) {
+            (Role::Server { next_peer }, Packet::Connect) => {
+                let peer = match self.connections.get(&addr) {
+                    // Our accept was lost.
+                    Some(connection) => connection.peer,
+                    None => {
+                        let peer = PeerId(*next_peer);
+                        *next_peer += 1;
+
+                        log::info!("Peer {:?} connected from {}", peer, addr);
+                        self.connections.insert(addr, Connection::new(peer));
+                        self.events.push_back(NetEvent::Connected(peer));
+                        peer
+                    }
+                };
+
+                self.send_packet(addr, &Packet::Accept { peer });
+            }
+            (Role::Client { server, .. }, Packet::Accept { .. }) if *server == addr => {
+                if self.connections.is_empty() {
+                    log::info!("Connected to {}", addr);
+                    self.connections
+                        .insert(addr, Connection::new(PeerId::SERVER));
+                    self.events.push_back(NetEvent::Connected(PeerId::SERVER));
+                }
+            }
+            (_, packet) => {
+                let Some(connection) = self.connections.get_mut(&addr) else {
+                    return;
+                };
+                connection.last_received = Instant::now();
+
+                match packet {
+                    Packet::Disconnect => {
+                        let peer = connection.peer;
+                        self.connections.remove(&addr);
+                        self.events.push_back(NetEvent::Disconnected(peer));
+                    }
+                    Packet::Reliable { sequence, payload } => {
+                        let peer = connection.peer;
+
+                        // Already delivered ones are acknowledged again, as the ack may have been lost.
+                        if sequence >= connection.next_expected {
+                            connection.out_of_order.insert(sequence, payload);
+                        }
+
+                        while let Some(payload) =
+                            connection.out_of_order.remove(&connection.next_expected)
+                        {
+                            connection.next_expected += 1;
+                            self.events.push_back(NetEvent::Message {
+                                peer,
+                                channel: Channel::Reliable,
+                                payload,
+                            });
+                        }
+
+                        self.send_packet(addr, &Packet::Ack { sequence });
+                    }
+                    Packet::Ack { sequence } => {
+                        connection.unacked.remove(&sequence);
+                    }
+                    // Dropped if older than the last one.
+                    Packet::Unreliable { sequence, payload }
+                        if connection
+                            .last_unreliable
+                            .is_none_or(|last| sequence > last) =>
+                    {
+                        connection.last_unreliable = Some(sequence);
+                        self.events.push_back(NetEvent::Message {
+                            peer: connection.peer,
+                            channel: Channel::Unreliable,
+                            payload,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    pub(crate) fn send(&mut self, peer: PeerId, channel: Channel, payload: &[u8]) -> Result<()> {
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            bail!(
+                "Payload of {} bytes is over the {} byte limit",
+                payload.len(),
+                MAX_PAYLOAD_SIZE
+            );
+        }
+
+        let (addr, connection) = self
+            .connections
+            .iter_mut()
+            .find(|(_, c)| c.peer == peer)
+            .ok_or_else(|| anyhow!("Peer {:?} is not connected", peer))?;
+        let addr = *addr;
+
+        let packet = match channel {
+            Channel::Reliable => {
+                let sequence = connection.next_reliable;
+                connection.next_reliable += 1;
+                connection
+                    .unacked
+                    .insert(sequence, (payload.to_vec(), Instant::now()));
+
+                Packet::Reliable {
+                    sequence,
+                    payload: payload.to_vec(),
+                }
+            }
+            Channel::Unreliable => {
+                let sequence = connection.next_unreliable;
+                connection.next_unreliable += 1;
+
+                Packet::Unreliable {
+                    sequence,
+                    payload: payload.to_vec(),
+                }
+            }
+        };
+
+        self.send_packet(addr, &packet);
+
+        Ok(())
+    }
+
+    /// Tell all peers we're leaving. Lost notices are covered by the timeout.
+    pub(crate) fn disconnect(&mut self) {
+        let addrs: Vec<SocketAddr> = self.connections.keys().copied().collect();
+
+        for addr in addrs {
+            self.send_packet(addr, &Packet::Disconnect);
+        }
+
+        self.connections.clear();
+    }
+
+    fn send_packet(&mut self, addr: SocketAddr, packet: &Packet) {
+        let data = bincode::serialize(&(PROTOCOL_ID, packet)).unwrap();
+
+        if let Err(e) = self.socket.send_to(&data, addr) {
+            log::warn!("Failed to send packet to {}: {}", addr, e);
+        }
+
+        if let Some(connection) = self.connections.get_mut(&addr) {
+            connection.last_sent = Instant::now();
+        }
+    }
+}
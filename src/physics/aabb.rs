@@ -0,0 +1,188 @@
+use crate::physics::Ray3d;
+use cgmath::{Matrix3, Matrix4, Quaternion, Vector3};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center_half_extents(center: Vector3<f32>, half_extents: Vector3<f32>) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector3<f32>>>(points: I) -> Self {
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+
+        for p in points {
+            min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Smallest box containing both.
+    pub fn merged(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    /// Whether `other` is fully inside.
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.min.z <= other.min.z
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+            && self.max.z >= other.max.z
+    }
+
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+    }
+
+    /// The overlapping part of two boxes, if they overlap.
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        self.overlaps(other).then(|| Aabb {
+            min: Vector3::new(
+                self.min.x.max(other.min.x),
+                self.min.y.max(other.min.y),
+                self.min.z.max(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.min(other.max.x),
+                self.max.y.min(other.max.y),
+                self.max.z.min(other.max.z),
+            ),
+        })
+    }
+
+    /// Bounds of this box after an affine transform.
+    pub fn transformed_by(&self, matrix: &Matrix4<f32>) -> Aabb {
+        let center = (matrix * self.center().extend(1.0)).truncate();
+        let half = self.half_extents();
+
+        // Columns of the matrix are the transformed axes.
+        let half = Vector3::new(
+            matrix.x.x.abs() * half.x + matrix.y.x.abs() * half.y + matrix.z.x.abs() * half.z,
+            matrix.x.y.abs() * half.x + matrix.y.y.abs() * half.y + matrix.z.y.abs() * half.z,
+            matrix.x.z.abs() * half.x + matrix.y.z.abs() * half.y + matrix.z.z.abs() * half.z,
+        );
+
+        Aabb::from_center_half_extents(center, half)
+    }
+
+    /// Bounds of this box after being scaled, rotated and then translated.
+    pub fn transformed(
+        &self,
+        position: Vector3<f32>,
+        rotation: Quaternion<f32>,
+        scale: Vector3<f32>,
+    ) -> Aabb {
+        let center = self.center();
+        let half = self.half_extents();
+
+        let center = position
+            + rotation * Vector3::new(center.x * scale.x, center.y * scale.y, center.z * scale.z);
+        let half = Vector3::new(
+            (half.x * scale.x).abs(),
+            (half.y * scale.y).abs(),
+            (half.z * scale.z).abs(),
+        );
+
+        Aabb::from_center_half_extents(center, rotated_half_extents(rotation, half))
+    }
+
+    /// Returns the distance along the ray where it enters the box, if it does.
+    pub fn intersect_ray(&self, ray: &Ray3d, max_distance: f32) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = max_distance;
+
+        for i in 0..3 {
+            let origin = ray.origin[i];
+            let direction = ray.direction[i];
+
+            if direction.abs() < f32::EPSILON {
+                if origin < self.min[i] || origin > self.max[i] {
+                    return None;
+                }
+            } else {
+                let inv = 1.0 / direction;
+                let mut t1 = (self.min[i] - origin) * inv;
+                let mut t2 = (self.max[i] - origin) * inv;
+                if t1 > t2 {
+                    std::mem::swap(&mut t1, &mut t2);
+                }
+
+                t_min = t_min.max(t1);
+                t_max = t_max.min(t2);
+
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+/// Half extents of the AABB enclosing a rotated box.
+pub(crate) fn rotated_half_extents(rotation: Quaternion<f32>, half: Vector3<f32>) -> Vector3<f32> {
+    let m = Matrix3::from(rotation);
+
+    // Columns of the matrix are the rotated axes.
+    Vector3::new(
+        m.x.x.abs() * half.x + m.y.x.abs() * half.y + m.z.x.abs() * half.z,
+        m.x.y.abs() * half.x + m.y.y.abs() * half.y + m.z.y.abs() * half.z,
+        m.x.z.abs() * half.x + m.y.z.abs() * half.y + m.z.z.abs() * half.z,
+    )
+}
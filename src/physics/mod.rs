@@ -0,0 +1,7 @@
+pub(crate) mod aabb;
+pub(crate) mod ray;
+pub(crate) mod shape;
+
+pub use aabb::*;
+pub use ray::*;
+pub use shape::*;
@@ -0,0 +1,52 @@
+use crate::scene::NodeId;
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ray3d {
+    pub origin: Vector3<f32>,
+    /// Always normalized.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray3d {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ray2d {
+    pub origin: Vector2<f32>,
+    /// Always normalized.
+    pub direction: Vector2<f32>,
+}
+
+impl Ray2d {
+    pub fn new(origin: Vector2<f32>, direction: Vector2<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vector2<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
+/// Result of a ray query against the world.
+#[derive(Debug, Copy, Clone)]
+pub struct RayHit {
+    /// The collision shape that was hit.
+    pub node: NodeId,
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
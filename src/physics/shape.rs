@@ -0,0 +1,605 @@
+use crate::math::bvh::Bvh;
+use crate::math::transform::Transform3d;
+use crate::physics::aabb::rotated_half_extents;
+use crate::physics::{Aabb, Ray3d};
+use crate::render::Mesh;
+use cgmath::{ElementWise, InnerSpace, Matrix3, Rotation, Vector3, Zero};
+
+const EPSILON: f32 = 1e-6;
+
+/// Triangles in local space, with cached bounds.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    pub(crate) triangles: Vec<[Vector3<f32>; 3]>,
+    pub(crate) bounds: Aabb,
+    /// Triangle indices, so rays only test the triangles they pass near.
+    bvh: Bvh<usize>,
+}
+
+impl TriangleMesh {
+    pub fn new(triangles: Vec<[Vector3<f32>; 3]>) -> Self {
+        let bounds = Aabb::from_points(triangles.iter().flatten());
+
+        let (bvh, _) = Bvh::build(
+            triangles
+                .iter()
+                .enumerate()
+                .map(|(i, tri)| (Aabb::from_points(tri), i))
+                .collect(),
+        );
+
+        Self {
+            triangles,
+            bounds,
+            bvh,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Shape3d {
+    Box {
+        half_extents: Vector3<f32>,
+    },
+    Sphere {
+        radius: f32,
+    },
+    /// Capsule along the local Y axis. The height doesn't include the caps.
+    Capsule {
+        radius: f32,
+        height: f32,
+    },
+    /// Concave triangle mesh. Overlap tests against it only use its bounding box,
+    /// while ray tests are exact.
+    Trimesh(TriangleMesh),
+}
+
+impl Shape3d {
+    /// Build a triangle mesh shape from the CPU data of a mesh.
+    pub fn trimesh_from_mesh(mesh: &Mesh) -> Self {
+        let triangles = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|c| {
+                [
+                    mesh.positions[c[0] as usize].into(),
+                    mesh.positions[c[1] as usize].into(),
+                    mesh.positions[c[2] as usize].into(),
+                ]
+            })
+            .collect();
+
+        Shape3d::Trimesh(TriangleMesh::new(triangles))
+    }
+}
+
+/// Simplified description of a convex shape in world space.
+enum Convex {
+    /// Segment swept by a sphere. Spheres use a degenerate segment.
+    Capsule {
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        radius: f32,
+    },
+    /// Oriented box.
+    Box {
+        center: Vector3<f32>,
+        axes: [Vector3<f32>; 3],
+        half_extents: Vector3<f32>,
+    },
+}
+
+/// A shape placed in the world.
+pub(crate) struct PosedShape<'a> {
+    pub(crate) shape: &'a Shape3d,
+    pub(crate) transform: Transform3d,
+}
+
+impl<'a> PosedShape<'a> {
+    pub(crate) fn new(shape: &'a Shape3d, transform: Transform3d) -> Self {
+        Self { shape, transform }
+    }
+
+    fn to_local(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.transform.rotation.invert() * (point - self.transform.position)
+    }
+
+    fn to_local_direction(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        self.transform.rotation.invert() * direction
+    }
+
+    fn max_scale(&self) -> f32 {
+        let s = self.transform.scale;
+        s.x.abs().max(s.y.abs()).max(s.z.abs())
+    }
+
+    fn to_convex(&self) -> Option<Convex> {
+        let t = &self.transform;
+        let s = t.scale;
+
+        match self.shape {
+            Shape3d::Sphere { radius } => Some(Convex::Capsule {
+                a: t.position,
+                b: t.position,
+                radius: radius * self.max_scale(),
+            }),
+            Shape3d::Capsule { radius, height } => {
+                let axis = t.rotation * Vector3::unit_y() * (height * 0.5 * s.y.abs());
+                Some(Convex::Capsule {
+                    a: t.position - axis,
+                    b: t.position + axis,
+                    radius: radius * s.x.abs().max(s.z.abs()),
+                })
+            }
+            Shape3d::Box { half_extents } => {
+                let m = Matrix3::from(t.rotation);
+                Some(Convex::Box {
+                    center: t.position,
+                    axes: [m.x, m.y, m.z],
+                    half_extents: Vector3::new(
+                        (half_extents.x * s.x).abs(),
+                        (half_extents.y * s.y).abs(),
+                        (half_extents.z * s.z).abs(),
+                    ),
+                })
+            }
+            Shape3d::Trimesh(_) => None,
+        }
+    }
+
+    pub(crate) fn world_aabb(&self) -> Aabb {
+        let t = &self.transform;
+
+        match self.shape {
+            Shape3d::Trimesh(mesh) => mesh.bounds.transformed(t.position, t.rotation, t.scale),
+            _ => match self.to_convex().unwrap() {
+                Convex::Capsule { a, b, radius } => {
+                    let r = Vector3::new(radius, radius, radius);
+                    let aabb = Aabb::from_points([a, b].iter());
+                    Aabb::new(aabb.min - r, aabb.max + r)
+                }
+                Convex::Box {
+                    center,
+                    half_extents,
+                    ..
+                } => Aabb::from_center_half_extents(
+                    center,
+                    rotated_half_extents(t.rotation, half_extents),
+                ),
+            },
+        }
+    }
+
+    pub(crate) fn overlaps(&self, other: &PosedShape) -> bool {
+        let (a, b) = match (self.to_convex(), other.to_convex()) {
+            (Some(a), Some(b)) => (a, b),
+            // Fall back to bounding boxes for triangle meshes.
+            _ => return self.world_aabb().overlaps(&other.world_aabb()),
+        };
+
+        match (a, b) {
+            (
+                Convex::Capsule {
+                    a: a0,
+                    b: b0,
+                    radius: r0,
+                },
+                Convex::Capsule {
+                    a: a1,
+                    b: b1,
+                    radius: r1,
+                },
+            ) => {
+                let (p, q) = closest_points_segments(a0, b0, a1, b1);
+                (p - q).magnitude2() <= (r0 + r1) * (r0 + r1)
+            }
+            (
+                Convex::Capsule { a, b, radius },
+                Convex::Box {
+                    center,
+                    axes,
+                    half_extents,
+                },
+            )
+            | (
+                Convex::Box {
+                    center,
+                    axes,
+                    half_extents,
+                },
+                Convex::Capsule { a, b, radius },
+            ) => {
+                let distance = segment_box_distance(a, b, center, &axes, half_extents);
+                distance <= radius
+            }
+            (
+                Convex::Box {
+                    center: c0,
+                    axes: axes0,
+                    half_extents: h0,
+                },
+                Convex::Box {
+                    center: c1,
+                    axes: axes1,
+                    half_extents: h1,
+                },
+            ) => boxes_overlap(c0, &axes0, h0, c1, &axes1, h1),
+        }
+    }
+
+    /// Returns the hit distance and the surface normal.
+    pub(crate) fn intersect_ray(
+        &self,
+        ray: &Ray3d,
+        max_distance: f32,
+    ) -> Option<(f32, Vector3<f32>)> {
+        let t = &self.transform;
+
+        let hit = match self.shape {
+            Shape3d::Trimesh(mesh) => {
+                let mut closest: Option<(f32, Vector3<f32>)> = None;
+
+                // Unnormalized, so the BVH is queried along the same line in local space.
+                let local_ray = Ray3d {
+                    origin: self.to_local(ray.origin).div_element_wise(t.scale),
+                    direction: self
+                        .to_local_direction(ray.direction)
+                        .div_element_wise(t.scale),
+                };
+                let candidates = mesh.bvh.query_ray(&local_ray, f32::MAX);
+
+                for (_, i, _) in candidates {
+                    let tri = &mesh.triangles[*i];
+                    let v = tri.map(|p| {
+                        t.position
+                            + t.rotation
+                                * Vector3::new(p.x * t.scale.x, p.y * t.scale.y, p.z * t.scale.z)
+                    });
+
+                    if let Some(hit) = ray_triangle(ray, v[0], v[1], v[2]) {
+                        if hit.0 <= max_distance && closest.is_none_or(|c| hit.0 < c.0) {
+                            closest = Some(hit);
+                        }
+                    }
+                }
+
+                closest
+            }
+            _ => match self.to_convex().unwrap() {
+                Convex::Capsule { a, b, radius } => ray_capsule(ray, a, b, radius),
+                Convex::Box { half_extents, .. } => {
+                    let local_ray = Ray3d {
+                        origin: self.to_local(ray.origin),
+                        direction: self.to_local_direction(ray.direction),
+                    };
+
+                    ray_box(&local_ray, half_extents, max_distance)
+                        .map(|(distance, normal)| (distance, t.rotation * normal))
+                }
+            },
+        };
+
+        hit.filter(|(distance, _)| *distance <= max_distance)
+    }
+}
+
+/// Closest points between segments p1-q1 and p2-q2.
+/// See Real-Time Collision Detection, 5.1.9.
+fn closest_points_segments(
+    p1: Vector3<f32>,
+    q1: Vector3<f32>,
+    p2: Vector3<f32>,
+    q2: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.dot(d1);
+    let e = d2.dot(d2);
+    let f = d2.dot(r);
+
+    if a <= EPSILON && e <= EPSILON {
+        return (p1, p2);
+    }
+
+    let (s, t);
+
+    if a <= EPSILON {
+        s = 0.0;
+        t = (f / e).clamp(0.0, 1.0);
+    } else {
+        let c = d1.dot(r);
+
+        if e <= EPSILON {
+            t = 0.0;
+            s = (-c / a).clamp(0.0, 1.0);
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+
+            let s0 = if denom != 0.0 {
+                ((b * f - c * e) / denom).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t0 = (b * s0 + f) / e;
+
+            if t0 < 0.0 {
+                t = 0.0;
+                s = (-c / a).clamp(0.0, 1.0);
+            } else if t0 > 1.0 {
+                t = 1.0;
+                s = ((b - c) / a).clamp(0.0, 1.0);
+            } else {
+                t = t0;
+                s = s0;
+            }
+        }
+    }
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+fn closest_point_on_segment(a: Vector3<f32>, b: Vector3<f32>, p: Vector3<f32>) -> Vector3<f32> {
+    let ab = b - a;
+    let len2 = ab.magnitude2();
+
+    if len2 <= EPSILON {
+        return a;
+    }
+
+    a + ab * ((p - a).dot(ab) / len2).clamp(0.0, 1.0)
+}
+
+fn closest_point_on_box(
+    p: Vector3<f32>,
+    center: Vector3<f32>,
+    axes: &[Vector3<f32>; 3],
+    half_extents: Vector3<f32>,
+) -> Vector3<f32> {
+    let d = p - center;
+    let mut q = center;
+
+    for i in 0..3 {
+        let dist = d.dot(axes[i]).clamp(-half_extents[i], half_extents[i]);
+        q += axes[i] * dist;
+    }
+
+    q
+}
+
+/// Distance between a segment and an oriented box, found by alternating projections.
+fn segment_box_distance(
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    center: Vector3<f32>,
+    axes: &[Vector3<f32>; 3],
+    half_extents: Vector3<f32>,
+) -> f32 {
+    let mut p = closest_point_on_segment(a, b, center);
+    let mut q = closest_point_on_box(p, center, axes, half_extents);
+
+    // Both sets are convex, so this converges quickly.
+    for _ in 0..8 {
+        p = closest_point_on_segment(a, b, q);
+        q = closest_point_on_box(p, center, axes, half_extents);
+    }
+
+    (p - q).magnitude()
+}
+
+/// Separating axis test between two oriented boxes.
+fn boxes_overlap(
+    c0: Vector3<f32>,
+    axes0: &[Vector3<f32>; 3],
+    h0: Vector3<f32>,
+    c1: Vector3<f32>,
+    axes1: &[Vector3<f32>; 3],
+    h1: Vector3<f32>,
+) -> bool {
+    let d = c1 - c0;
+
+    let separated_on = |axis: Vector3<f32>| {
+        if axis.magnitude2() < EPSILON {
+            // Parallel edges give no new axis.
+            return false;
+        }
+
+        let r0 = (0..3)
+            .map(|i| h0[i] * axes0[i].dot(axis).abs())
+            .sum::<f32>();
+        let r1 = (0..3)
+            .map(|i| h1[i] * axes1[i].dot(axis).abs())
+            .sum::<f32>();
+
+        d.dot(axis).abs() > r0 + r1
+    };
+
+    for i in 0..3 {
+        if separated_on(axes0[i]) || separated_on(axes1[i]) {
+            return false;
+        }
+    }
+
+    for a in axes0 {
+        for b in axes1 {
+            if separated_on(a.cross(*b)) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Slab test in the box's local space.
+fn ray_box(
+    ray: &Ray3d,
+    half_extents: Vector3<f32>,
+    max_distance: f32,
+) -> Option<(f32, Vector3<f32>)> {
+    let mut t_min = 0.0f32;
+    let mut t_max = max_distance;
+    let mut normal = Vector3::zero();
+
+    for i in 0..3 {
+        let origin = ray.origin[i];
+        let direction = ray.direction[i];
+
+        if direction.abs() < EPSILON {
+            if origin < -half_extents[i] || origin > half_extents[i] {
+                return None;
+            }
+        } else {
+            let inv = 1.0 / direction;
+            let t1 = (-half_extents[i] - origin) * inv;
+            let t2 = (half_extents[i] - origin) * inv;
+            let (t_near, t_far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+
+            if t_near > t_min {
+                t_min = t_near;
+                normal = Vector3::zero();
+                normal[i] = -direction.signum();
+            }
+            t_max = t_max.min(t_far);
+
+            if t_min > t_max {
+                return None;
+            }
+        }
+    }
+
+    // The ray starts inside the box.
+    if normal == Vector3::zero() {
+        normal = -ray.direction;
+    }
+
+    Some((t_min, normal))
+}
+
+fn ray_sphere(ray: &Ray3d, center: Vector3<f32>, radius: f32) -> Option<(f32, Vector3<f32>)> {
+    let m = ray.origin - center;
+    let b = m.dot(ray.direction);
+    let c = m.magnitude2() - radius * radius;
+
+    // The ray starts inside the sphere.
+    if c <= 0.0 {
+        return Some((0.0, -ray.direction));
+    }
+
+    if b > 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let distance = -b - discriminant.sqrt();
+    let normal = (ray.at(distance) - center).normalize();
+
+    Some((distance, normal))
+}
+
+fn ray_capsule(
+    ray: &Ray3d,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    radius: f32,
+) -> Option<(f32, Vector3<f32>)> {
+    let ab = b - a;
+    let length = ab.magnitude();
+
+    if length <= EPSILON {
+        return ray_sphere(ray, a, radius);
+    }
+
+    let axis = ab / length;
+
+    // The ray starts inside the capsule.
+    let closest = closest_point_on_segment(a, b, ray.origin);
+    if (ray.origin - closest).magnitude2() <= radius * radius {
+        return Some((0.0, -ray.direction));
+    }
+
+    let mut best: Option<(f32, Vector3<f32>)> = None;
+    let mut consider = |hit: Option<(f32, Vector3<f32>)>| {
+        if let Some(hit) = hit {
+            if best.is_none_or(|b| hit.0 < b.0) {
+                best = Some(hit);
+            }
+        }
+    };
+
+    // Infinite cylinder, clipped by the segment ends.
+    let m = ray.origin - a;
+    let d_perp = ray.direction - axis * ray.direction.dot(axis);
+    let m_perp = m - axis * m.dot(axis);
+    let qa = d_perp.magnitude2();
+    let qb = 2.0 * d_perp.dot(m_perp);
+    let qc = m_perp.magnitude2() - radius * radius;
+    let discriminant = qb * qb - 4.0 * qa * qc;
+
+    if qa > EPSILON && discriminant >= 0.0 {
+        let distance = (-qb - discriminant.sqrt()) / (2.0 * qa);
+
+        if distance >= 0.0 {
+            let p = ray.at(distance);
+            let h = (p - a).dot(axis);
+
+            if (0.0..=length).contains(&h) {
+                let normal = (p - (a + axis * h)).normalize();
+                consider(Some((distance, normal)));
+            }
+        }
+    }
+
+    // Caps.
+    consider(ray_sphere(ray, a, radius));
+    consider(ray_sphere(ray, b, radius));
+
+    best
+}
+
+/// Möller–Trumbore intersection. Both faces are hit.
+fn ray_triangle(
+    ray: &Ray3d,
+    v0: Vector3<f32>,
+    v1: Vector3<f32>,
+    v2: Vector3<f32>,
+) -> Option<(f32, Vector3<f32>)> {
+    let e1 = v1 - v0;
+    let e2 = v2 - v0;
+    let p = ray.direction.cross(e2);
+    let det = e1.dot(p);
+
+    if det.abs() < EPSILON {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = ray.origin - v0;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(e1);
+    let v = ray.direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = e2.dot(q) * inv_det;
+    if distance < 0.0 {
+        return None;
+    }
+
+    let mut normal = e1.cross(e2).normalize();
+    if normal.dot(ray.direction) > 0.0 {
+        normal = -normal;
+    }
+
+    Some((distance, normal))
+}
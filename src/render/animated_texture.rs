@@ -0,0 +1,207 @@
+use crate::render::{Texture, TextureCache, TextureId};
+use anyhow::{bail, Context, Result};
+use cgmath::Vector4;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, DynamicImage, Frame, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Transparent gap between frames in the atlas, so that filtering doesn't bleed.
+const FRAME_PADDING: u32 = 1;
+
+#[derive(Debug, Copy, Clone)]
+pub struct AnimatedFrame {
+    /// Normalized region of the atlas (x, y, width, height).
+    pub region: Vector4<f32>,
+    /// How long the frame is shown, in seconds.
+    pub delay: f32,
+}
+
+/// A named run of frames, e.g. "walk" or "jump", played with `Sprite2d::play_tag`.
+#[derive(Debug, Clone)]
+pub struct AnimationTag {
+    pub name: String,
+    /// Frame indices in playing order. Ping-pong tags list the way back too.
+    pub frames: Vec<usize>,
+}
+
+/// Frames of an animated image (GIF or APNG) packed into a single atlas texture.
+#[derive(Debug, Clone)]
+pub struct AnimatedTexture {
+    pub texture: TextureId,
+    pub frame_size: (u32, u32),
+    pub frames: Vec<AnimatedFrame>,
+    /// Only sprite sheets have tags, see `SpriteAtlas`.
+    pub tags: Vec<AnimationTag>,
+}
+
+impl AnimatedTexture {
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        path: P,
+    ) -> Result<Self> {
+        let bytes = std::fs::read(path.as_ref())
+            .with_context(|| format!("Invalid image path {:?}", path.as_ref()))?;
+
+        Self::from_bytes(
+            device,
+            queue,
+            cache,
+            &bytes,
+            &path.as_ref().to_string_lossy(),
+        )
+    }
+
+    /// Decode an animated GIF or PNG. A still PNG becomes a single frame.
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        let frames = match image::guess_format(bytes)? {
+            ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes))?
+                .into_frames()
+                .collect_frames()?,
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(Cursor::new(bytes))?;
+                if decoder.is_apng() {
+                    decoder.apng().into_frames().collect_frames()?
+                } else {
+                    let image = DynamicImage::from_decoder(decoder)?.to_rgba8();
+                    vec![Frame::new(image)]
+                }
+            }
+            format => bail!("Unsupported animated image format: {:?}", format),
+        };
+
+        Self::from_frames(device, queue, cache, &frames, label)
+    }
+
+    pub fn from_frames(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        frames: &[Frame],
+        label: &str,
+    ) -> Result<Self> {
+        if frames.is_empty() {
+            bail!("Animated image has no frames: {}", label);
+        }
+
+        let frame_size = frames[0].buffer().dimensions();
+
+        // Lay the frames out in a roughly square grid.
+        let columns = (frames.len() as f32).sqrt().ceil() as u32;
+        let rows = (frames.len() as u32).div_ceil(columns);
+        let cell_size = (frame_size.0 + FRAME_PADDING, frame_size.1 + FRAME_PADDING);
+        let atlas_size = (columns * cell_size.0, rows * cell_size.1);
+
+        let max_size = device.limits().max_texture_dimension_2d;
+        if atlas_size.0 > max_size || atlas_size.1 > max_size {
+            bail!(
+                "Animated image is too large for a {}x{} atlas: {}",
+                max_size,
+                max_size,
+                label
+            );
+        }
+
+        let mut atlas = RgbaImage::new(atlas_size.0, atlas_size.1);
+        let mut atlas_frames = Vec::with_capacity(frames.len());
+
+        for (i, frame) in frames.iter().enumerate() {
+            let x = (i as u32 % columns) * cell_size.0;
+            let y = (i as u32 / columns) * cell_size.1;
+
+            image::imageops::replace(&mut atlas, frame.buffer(), x as i64, y as i64);
+
+            // Like browsers, treat a zero delay as 100 ms.
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = if numer == 0 {
+                0.1
+            } else {
+                numer as f32 / denom.max(1) as f32 / 1000.0
+            };
+
+            atlas_frames.push(AnimatedFrame {
+                region: Vector4::new(
+                    x as f32 / atlas_size.0 as f32,
+                    y as f32 / atlas_size.1 as f32,
+                    frame_size.0 as f32 / atlas_size.0 as f32,
+                    frame_size.1 as f32 / atlas_size.1 as f32,
+                ),
+                delay,
+            });
+        }
+
+        let texture = Texture::from_image(
+            device,
+            queue,
+            cache,
+            &DynamicImage::ImageRgba8(atlas),
+            Some(label),
+        )?;
+
+        Ok(Self {
+            texture,
+            frame_size,
+            frames: atlas_frames,
+            tags: vec![],
+        })
+    }
+
+    /// Total length of one loop, in seconds.
+    pub fn get_duration(&self) -> f32 {
+        self.frames.iter().map(|f| f.delay).sum()
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<usize> {
+        self.tags.iter().position(|t| t.name == name)
+    }
+
+    /// Index of the frame shown at some time into the animation.
+    pub fn get_frame_at(&self, time: f32, looping: bool) -> usize {
+        self.get_sequence_frame_at(0..self.frames.len(), time, looping)
+    }
+
+    /// Like `get_frame_at`, for the frames of a tag only.
+    pub fn get_tag_frame_at(&self, tag: usize, time: f32, looping: bool) -> usize {
+        self.get_sequence_frame_at(self.tags[tag].frames.iter().copied(), time, looping)
+    }
+
+    fn get_sequence_frame_at(
+        &self,
+        sequence: impl Iterator<Item = usize> + Clone,
+        time: f32,
+        looping: bool,
+    ) -> usize {
+        let duration: f32 = sequence.clone().map(|i| self.frames[i].delay).sum();
+        let Some(last) = sequence.clone().last() else {
+            return 0;
+        };
+
+        if duration <= 0.0 {
+            return sequence.clone().next().unwrap();
+        }
+
+        let mut time = if looping {
+            time.rem_euclid(duration)
+        } else {
+            time.min(duration)
+        };
+
+        for i in sequence {
+            if time < self.frames[i].delay {
+                return i;
+            }
+            time -= self.frames[i].delay;
+        }
+
+        last
+    }
+}
@@ -1,17 +1,19 @@
 use crate::math::alignup_u32;
+use crate::render::clip::clipped_stencil_state;
 use crate::render::shader_maker::ShaderMaker;
 use crate::render::vertex::VertexBuffer;
-use crate::render::{InstanceRaw, RenderServer, Texture, TextureCache, TextureId};
+use crate::render::{InstanceRaw, RenderServer, Texture, TextureCache, TextureId, UniformBuffer};
 use cgmath::{Vector2, Vector4};
 use std::collections::HashMap;
 use std::mem;
+use std::ops::Range;
 use wgpu::{BufferAddress, DynamicOffset, RenderPass, SamplerBindingType};
 
 pub struct AtlasRenderResources {
     // Use dynamic offset.
     params_bind_group_layout: wgpu::BindGroupLayout,
     params_bind_group: Option<wgpu::BindGroup>,
-    params_buffer: Option<wgpu::Buffer>,
+    params_buffer: Option<UniformBuffer>,
     params_buffer_capacity: usize,
 
     // Use range for different atlas.
@@ -32,7 +34,7 @@ impl AtlasRenderResources {
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     entries: &[wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: true,
@@ -107,10 +109,36 @@ impl AtlasRenderResources {
                 push_constant_ranges: &[],
             });
 
-            let defs = if mode == AtlasMode::Text {
-                vec!["TEXT"]
+            let dual_source = device
+                .features()
+                .contains(wgpu::Features::DUAL_SOURCE_BLENDING);
+
+            let mut defs = match mode {
+                AtlasMode::Sprite => vec![],
+                AtlasMode::Text => vec!["TEXT"],
+                AtlasMode::SubpixelText if dual_source => vec!["TEXT", "SUBPIXEL", "DUAL_SOURCE"],
+                AtlasMode::SubpixelText => vec!["TEXT", "SUBPIXEL"],
+            };
+            if render_server.get_settings().reverse_z {
+                defs.push("REVERSE_Z");
+            }
+
+            // Subpixel text blends each color channel with its own coverage.
+            let blend = if mode == AtlasMode::SubpixelText && dual_source {
+                wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrc1,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }
             } else {
-                vec![]
+                wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING
             };
 
             // Shader descriptor, not a shader module yet.
@@ -134,8 +162,8 @@ impl AtlasRenderResources {
                     module: &shader_module,
                     entry_point: "fs_main",
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: render_server.surface_config.format,
-                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        format: render_server.scene_format(),
+                        blend: Some(blend),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
                 }),
@@ -146,10 +174,10 @@ impl AtlasRenderResources {
                     ..Default::default()
                 },
                 depth_stencil: Some(wgpu::DepthStencilState {
-                    format: Texture::DEPTH_FORMAT,
+                    format: render_server.depth_format(),
                     depth_write_enabled: false,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
+                    depth_compare: render_server.depth_compare(wgpu::CompareFunction::Less),
+                    stencil: clipped_stencil_state(),
                     bias: wgpu::DepthBiasState::default(),
                 }),
                 multisample: wgpu::MultisampleState::default(),
@@ -193,6 +221,8 @@ pub(crate) struct AtlasInstanceRaw {
 pub(crate) struct AtlasParamsUniform {
     camera_view_size: [f32; 2],
     atlas_size: [f32; 2],
+    text_gamma: f32,
+    _pad: f32,
 }
 
 #[derive(Default, Copy, Clone, Eq, Hash, PartialEq)]
@@ -200,14 +230,22 @@ pub(crate) enum AtlasMode {
     #[default]
     Sprite = 0x1,
     Text = 0x2,
+    /// Text from an RGB subpixel atlas.
+    SubpixelText = 0x4,
 }
 
 /// Parameters for atlas drawing control.
 impl AtlasParamsUniform {
-    pub(crate) fn new(atlas_size: Vector2<u32>, camera_view_size: Vector2<u32>) -> Self {
+    pub(crate) fn new(
+        atlas_size: Vector2<u32>,
+        camera_view_size: Vector2<u32>,
+        text_gamma: f32,
+    ) -> Self {
         Self {
             camera_view_size: [camera_view_size.x as f32, camera_view_size.y as f32],
             atlas_size: [atlas_size.x as f32, atlas_size.y as f32],
+            text_gamma,
+            _pad: 0.0,
         }
     }
 
@@ -215,13 +253,15 @@ impl AtlasParamsUniform {
         Self {
             camera_view_size: [0.0, 0.0],
             atlas_size: [0.0, 0.0],
+            text_gamma: 1.0,
+            _pad: 0.0,
         }
     }
 }
 
 impl AtlasInstance {
     // CPU data format -> GPU data format.
-    fn to_raw(&self) -> AtlasInstanceRaw {
+    pub(crate) fn to_raw(&self) -> AtlasInstanceRaw {
         AtlasInstanceRaw {
             position: self.position.into(),
             size: self.size.into(),
@@ -266,7 +306,7 @@ impl VertexBuffer for AtlasInstanceRaw {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub(crate) struct Atlas {
     pub(crate) texture: Option<TextureId>,
     pub(crate) instances: Vec<AtlasInstance>,
@@ -274,6 +314,9 @@ pub(crate) struct Atlas {
     pub(crate) texture_size: (u32, u32),
 
     pub(crate) mode: AtlasMode,
+
+    /// Coverage exponent for text modes.
+    pub(crate) gamma: f32,
 }
 
 impl Atlas {
@@ -285,6 +328,7 @@ impl Atlas {
             instances: vec![],
             texture_size,
             mode: AtlasMode::Sprite,
+            gamma: 1.0,
         }
     }
 
@@ -306,6 +350,7 @@ impl Atlas {
             instances: vec![],
             texture_size: size,
             mode: AtlasMode::Sprite,
+            gamma: 1.0,
         }
     }
 }
@@ -362,19 +407,15 @@ pub fn prepare_atlas(
 
     // Prepare the params uniform buffer.
     {
-        let offset_limit = wgpu::Limits::downlevel_defaults().min_uniform_buffer_offset_alignment;
-        let offset =
-            alignup_u32(mem::size_of::<AtlasParamsUniform>() as u32, offset_limit) * offset_limit;
+        let offset = params_offset_unit();
 
         if render_resources.params_buffer_capacity < atlas_count {
             render_resources.params_buffer_capacity = atlas_count;
 
-            let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("atlas params uniform buffer (unique)"),
-                size: (offset * instance_count as u32) as BufferAddress,
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
+            let buffer = render_server.create_uniform_buffer(
+                "atlas params uniform buffer (unique)",
+                (offset * atlas_count as u32) as BufferAddress,
+            );
 
             let bind_group = render_server
                 .device
@@ -403,14 +444,15 @@ pub fn prepare_atlas(
         let mut uniforms = Vec::new();
 
         for e in extracted {
-            let atlas_params = AtlasParamsUniform::new(e.atlas.texture_size.into(), e.view_size);
+            let atlas_params =
+                AtlasParamsUniform::new(e.atlas.texture_size.into(), e.view_size, e.atlas.gamma);
 
             uniforms.push(atlas_params);
         }
 
         if (render_resources.params_buffer.is_some()) {
             // Consider align-up.
-            let mut aligned_up_data = vec![0u8; offset as usize * instance_count];
+            let mut aligned_up_data = vec![0u8; offset as usize * atlas_count];
 
             for i in 0..uniforms.len() {
                 let slice = bytemuck::cast_slice(&uniforms[i..i + 1]);
@@ -465,13 +507,19 @@ pub fn prepare_atlas(
 
 pub fn render_atlas<'a, 'b: 'a>(
     atlases: &'b Vec<ExtractedAtlas>,
+    range: Range<usize>,
     render_resources: &'b AtlasRenderResources,
     render_pass: &mut RenderPass<'a>,
 ) {
-    let mut instance_offset = 0u32;
+    // Instances of all atlases share one buffer.
+    let mut instance_offset = atlases[..range.start]
+        .iter()
+        .map(|e| e.atlas.instances.len() as u32)
+        .sum::<u32>();
 
-    for i in 0..atlases.len() {
+    for i in range {
         let a = &atlases[i].atlas;
+        let instance_count = a.instances.len() as u32;
 
         let pipeline = render_resources.pipeline_cache.get(&a.mode);
         let texture_bind_group = render_resources
@@ -490,16 +538,22 @@ pub fn render_atlas<'a, 'b: 'a>(
         render_pass.set_bind_group(
             0,
             &render_resources.params_bind_group.as_ref().unwrap(),
-            &[(i * mem::size_of::<AtlasParamsUniform>()) as DynamicOffset],
+            &[i as DynamicOffset * params_offset_unit()],
         );
         render_pass.set_bind_group(1, &texture_bind_group.unwrap(), &[]);
 
-        render_pass.draw(0..4, instance_offset..a.instances.len() as u32);
+        render_pass.draw(0..4, instance_offset..instance_offset + instance_count);
 
-        instance_offset += a.instances.len() as u32;
+        instance_offset += instance_count;
     }
 }
 
+/// Stride between atlas params in the uniform buffer.
+fn params_offset_unit() -> u32 {
+    let offset_limit = wgpu::Limits::downlevel_defaults().min_uniform_buffer_offset_alignment;
+    alignup_u32(mem::size_of::<AtlasParamsUniform>() as u32, offset_limit) * offset_limit
+}
+
 pub trait DrawAtlas<'a> {
     fn draw_atlas(
         &mut self,
@@ -0,0 +1,376 @@
+use crate::render::post_process::{begin_fullscreen_pass, create_fullscreen_pipeline};
+use crate::render::{RenderServer, Texture, TextureCache, TextureId, UniformBuffer};
+use std::mem;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+/// The backdrop is blurred at a fraction of the surface resolution, which is cheaper
+/// and widens the blur for free.
+const BACKDROP_DOWNSAMPLE: u32 = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurParamsUniform {
+    step: [f32; 2],
+    _pad: [f32; 2],
+}
+
+/// One fullscreen blur pass with its own source and parameters.
+struct BlurPass {
+    params_buffer: UniformBuffer,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl BlurPass {
+    fn new(render_server: &RenderServer, label: &str) -> Self {
+        let params_buffer = render_server
+            .create_uniform_buffer(label, mem::size_of::<BlurParamsUniform>() as BufferAddress);
+
+        Self {
+            params_buffer,
+            bind_group: None,
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        source: &Texture,
+        step: [f32; 2],
+        layout: &wgpu::BindGroupLayout,
+        render_server: &RenderServer,
+    ) {
+        render_server.queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[BlurParamsUniform {
+                step,
+                _pad: [0.0; 2],
+            }]),
+        );
+
+        // The scene color texture may be recreated at any time, so rebuild the bind group every frame.
+        let bind_group = render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&source.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.params_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("backdrop blur bind group"),
+            });
+
+        self.bind_group = Some(bind_group);
+    }
+}
+
+/// Blurred copy of the 3D scene, sampled by UI shapes that request a backdrop blur.
+pub(crate) struct BackdropRenderResources {
+    /// Output of the horizontal pass.
+    intermediate_texture: TextureId,
+    /// Output of the vertical pass, which is what UI shapes sample.
+    blurred_texture: TextureId,
+
+    blur_bind_group_layout: wgpu::BindGroupLayout,
+    blur_pipeline: wgpu::RenderPipeline,
+
+    horizontal_pass: BlurPass,
+    vertical_pass: BlurPass,
+    /// Copies the scene to the surface when there is no post processing to do it.
+    copy_pass: BlurPass,
+
+    pub(crate) sample_bind_group_layout: wgpu::BindGroupLayout,
+    pub(crate) sample_bind_group: wgpu::BindGroup,
+
+    /// If any UI shape wants a blurred backdrop this frame.
+    pub(crate) enabled: bool,
+}
+
+impl BackdropRenderResources {
+    pub(crate) fn new(render_server: &RenderServer, texture_cache: &mut TextureCache) -> Self {
+        let device = &render_server.device;
+
+        let (intermediate_texture, blurred_texture) =
+            Self::create_textures(render_server, texture_cache);
+
+        let blur_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("backdrop blur bind group layout"),
+            });
+
+        let blur_pipeline = {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("backdrop blur pipeline layout"),
+                bind_group_layouts: &[&blur_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("backdrop blur shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/backdrop_blur.wgsl").into(),
+                ),
+            };
+
+            create_fullscreen_pipeline(
+                render_server,
+                &pipeline_layout,
+                shader,
+                "backdrop blur pipeline",
+            )
+        };
+
+        let sample_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("backdrop sample bind group layout"),
+            });
+
+        let sample_bind_group = Self::create_sample_bind_group(
+            render_server,
+            texture_cache,
+            &sample_bind_group_layout,
+            blurred_texture,
+        );
+
+        Self {
+            intermediate_texture,
+            blurred_texture,
+            blur_bind_group_layout,
+            blur_pipeline,
+            horizontal_pass: BlurPass::new(render_server, "backdrop horizontal blur params buffer"),
+            vertical_pass: BlurPass::new(render_server, "backdrop vertical blur params buffer"),
+            copy_pass: BlurPass::new(render_server, "backdrop copy params buffer"),
+            sample_bind_group_layout,
+            sample_bind_group,
+            enabled: false,
+        }
+    }
+
+    fn create_textures(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> (TextureId, TextureId) {
+        let mut config = render_server.surface_config.clone();
+        config.width = (config.width / BACKDROP_DOWNSAMPLE).max(1);
+        config.height = (config.height / BACKDROP_DOWNSAMPLE).max(1);
+
+        let intermediate_texture = Texture::create_render_texture(
+            &render_server.device,
+            texture_cache,
+            &config,
+            Some("backdrop intermediate texture"),
+        );
+
+        let blurred_texture = Texture::create_render_texture(
+            &render_server.device,
+            texture_cache,
+            &config,
+            Some("backdrop blurred texture"),
+        );
+
+        (intermediate_texture, blurred_texture)
+    }
+
+    fn create_sample_bind_group(
+        render_server: &RenderServer,
+        texture_cache: &TextureCache,
+        layout: &wgpu::BindGroupLayout,
+        blurred_texture: TextureId,
+    ) -> wgpu::BindGroup {
+        let texture = texture_cache.get(blurred_texture).unwrap();
+
+        render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+                label: Some("backdrop sample bind group"),
+            })
+    }
+
+    /// Offscreen textures have to follow the surface size.
+    pub(crate) fn recreate_textures(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) {
+        texture_cache.remove(self.intermediate_texture);
+        texture_cache.remove(self.blurred_texture);
+
+        (self.intermediate_texture, self.blurred_texture) =
+            Self::create_textures(render_server, texture_cache);
+
+        self.sample_bind_group = Self::create_sample_bind_group(
+            render_server,
+            texture_cache,
+            &self.sample_bind_group_layout,
+            self.blurred_texture,
+        );
+    }
+}
+
+/// `radius` is roughly the blur extent in surface pixels. `copy_to_surface` prepares
+/// `render_backdrop_copy`, which is also used without a blur.
+pub(crate) fn prepare_backdrop(
+    radius: f32,
+    copy_to_surface: bool,
+    scene_color_texture: TextureId,
+    render_resources: &mut BackdropRenderResources,
+    render_server: &RenderServer,
+    texture_cache: &TextureCache,
+) {
+    let scene_color = texture_cache.get(scene_color_texture).unwrap();
+
+    if copy_to_surface {
+        render_resources.copy_pass.prepare(
+            scene_color,
+            [0.0, 0.0],
+            &render_resources.blur_bind_group_layout,
+            render_server,
+        );
+    }
+
+    if !render_resources.enabled {
+        return;
+    }
+
+    let intermediate = texture_cache
+        .get(render_resources.intermediate_texture)
+        .unwrap();
+
+    // Four taps on each side of the center.
+    let tap_distance = radius.max(0.0) / 4.0;
+
+    render_resources.horizontal_pass.prepare(
+        scene_color,
+        [tap_distance / scene_color.size.0 as f32, 0.0],
+        &render_resources.blur_bind_group_layout,
+        render_server,
+    );
+
+    render_resources.vertical_pass.prepare(
+        intermediate,
+        [0.0, tap_distance / scene_color.size.1 as f32],
+        &render_resources.blur_bind_group_layout,
+        render_server,
+    );
+}
+
+/// Blur the scene color into the backdrop texture.
+pub(crate) fn render_backdrop(
+    render_resources: &BackdropRenderResources,
+    texture_cache: &TextureCache,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    let passes = [
+        (
+            &render_resources.horizontal_pass,
+            render_resources.intermediate_texture,
+            "backdrop horizontal blur pass",
+        ),
+        (
+            &render_resources.vertical_pass,
+            render_resources.blurred_texture,
+            "backdrop vertical blur pass",
+        ),
+    ];
+
+    for (pass, target, label) in passes {
+        let target_view = &texture_cache.get(target).unwrap().view;
+
+        render_fullscreen(render_resources, pass, encoder, target_view, label);
+    }
+}
+
+/// Copy the scene color to `target_view` unchanged.
+pub(crate) fn render_backdrop_copy(
+    render_resources: &BackdropRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+) {
+    render_fullscreen(
+        render_resources,
+        &render_resources.copy_pass,
+        encoder,
+        target_view,
+        "backdrop copy pass",
+    );
+}
+
+fn render_fullscreen(
+    render_resources: &BackdropRenderResources,
+    pass: &BlurPass,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+    label: &str,
+) {
+    let mut render_pass = begin_fullscreen_pass(encoder, target_view, label);
+
+    render_pass.set_pipeline(&render_resources.blur_pipeline);
+    render_pass.set_bind_group(0, pass.bind_group.as_ref().unwrap(), &[]);
+    render_pass.draw(0..3, 0..1);
+}
@@ -1,5 +1,9 @@
-use crate::render::{Texture, TextureCache, TextureId};
+use crate::math::alignup_u32;
+use crate::render::{RenderServer, TextureCache, TextureId, UniformBuffer};
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::mem;
+use wgpu::{BufferAddress, DynamicOffset, SamplerBindingType};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct BindGroupId(uuid::Uuid);
@@ -25,3 +29,198 @@ impl BindGroupCache {
         self.storage.get(&bind_group_id)
     }
 }
+
+/// One uniform per draw in a single buffer, each bound through a dynamic offset.
+/// The buffer grows to fit the most draws seen so far.
+pub(crate) struct DynamicUniformBuffer<T> {
+    label: &'static str,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: Option<wgpu::BindGroup>,
+    buffer: Option<UniformBuffer>,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> DynamicUniformBuffer<T> {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        visibility: wgpu::ShaderStages,
+        label: &'static str,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some(&format!("{} bind group layout", label)),
+        });
+
+        Self {
+            label,
+            bind_group_layout,
+            bind_group: None,
+            buffer: None,
+            capacity: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Distance between two uniforms in the buffer.
+    pub(crate) fn offset_unit() -> u32 {
+        let offset_limit = wgpu::Limits::downlevel_defaults().min_uniform_buffer_offset_alignment;
+        let multiplier = alignup_u32(mem::size_of::<T>() as u32, offset_limit);
+
+        multiplier * offset_limit
+    }
+
+    /// Offset of the `index`th uniform, for `set_bind_group`.
+    pub(crate) fn offset(index: usize) -> DynamicOffset {
+        index as DynamicOffset * Self::offset_unit()
+    }
+
+    /// Upload the uniforms, reallocating the buffer if they don't fit.
+    pub(crate) fn write(&mut self, render_server: &RenderServer, uniforms: &[T]) {
+        if uniforms.is_empty() {
+            return;
+        }
+
+        let offset_unit = Self::offset_unit() as usize;
+
+        if self.capacity < uniforms.len() {
+            let buffer = render_server.create_uniform_buffer(
+                &format!("{} buffer (unique)", self.label),
+                (offset_unit * uniforms.len()) as BufferAddress,
+            );
+
+            let bind_group = render_server
+                .device
+                .create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &self.bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &buffer,
+                            offset: 0,
+                            // See DynamicUniformBufferOffset.
+                            size: Some(wgpu::BufferSize::new(mem::size_of::<T>() as u64).unwrap()),
+                        }),
+                    }],
+                    label: Some(&format!("{} bind group (unique)", self.label)),
+                });
+
+            self.buffer = Some(buffer);
+            self.bind_group = Some(bind_group);
+            self.capacity = uniforms.len();
+        }
+
+        // Consider align-up.
+        let mut aligned_up_data = vec![0u8; offset_unit * uniforms.len()];
+
+        for (i, uniform) in uniforms.iter().enumerate() {
+            let slice: &[u8] = bytemuck::bytes_of(uniform);
+            aligned_up_data[i * offset_unit..i * offset_unit + slice.len()].copy_from_slice(slice);
+        }
+
+        render_server
+            .queue
+            .write_buffer(self.buffer.as_ref().unwrap(), 0, &aligned_up_data);
+    }
+
+    /// Only valid after a `write`.
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
+        self.bind_group.as_ref().unwrap()
+    }
+}
+
+/// Bind groups of a texture and its sampler, one per texture.
+/// Textures removed from or replaced in the `TextureCache` have to be evicted.
+pub(crate) struct TextureBindGroupCache {
+    label: &'static str,
+    bind_group_layout: wgpu::BindGroupLayout,
+    storage: HashMap<TextureId, wgpu::BindGroup>,
+}
+
+impl TextureBindGroupCache {
+    pub(crate) fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some(&format!("{} bind group layout", label)),
+        });
+
+        Self {
+            label,
+            bind_group_layout,
+            storage: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub(crate) fn add(
+        &mut self,
+        device: &wgpu::Device,
+        texture_cache: &TextureCache,
+        texture_id: TextureId,
+    ) {
+        if self.storage.contains_key(&texture_id) {
+            return;
+        }
+
+        let texture = texture_cache.get(texture_id).unwrap();
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some(&format!("{} bind group", self.label)),
+        });
+
+        self.storage.insert(texture_id, bind_group);
+    }
+
+    /// Only valid after an `add` of the texture.
+    pub(crate) fn get(&self, texture_id: TextureId) -> &wgpu::BindGroup {
+        self.storage.get(&texture_id).unwrap()
+    }
+
+    pub(crate) fn evict(&mut self, texture_id: TextureId) {
+        self.storage.remove(&texture_id);
+    }
+}
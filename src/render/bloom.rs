@@ -0,0 +1,442 @@
+use crate::render::post_process::{
+    begin_fullscreen_pass, create_fullscreen_pipeline_with_target, PostProcessSettings,
+};
+use crate::render::render_server::HDR_FORMAT;
+use crate::render::{RenderServer, Texture, UniformBuffer};
+use std::mem;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+/// Mips below this size add little but blur cost.
+const MAX_BLOOM_MIPS: u32 = 6;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomParamsUniform {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    mip_count: f32,
+    /// Colors are clamped to this when resolving.
+    max_color: f32,
+    _pad: [f32; 3],
+}
+
+/// Blurs the bright parts of the scene and adds them back when resolving it to the surface.
+///
+/// The resolve pass also runs without bloom, to bring an HDR scene to the surface.
+pub(crate) struct BloomRenderResources {
+    /// Half the screen size. The bright pass goes into mip 0, smaller mips are blurrier.
+    texture: wgpu::Texture,
+    mip_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+
+    params_buffer: UniformBuffer,
+    params_bind_group: wgpu::BindGroup,
+
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    resolve_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Mip `i` as the source of a pass.
+    mip_bind_groups: Vec<wgpu::BindGroup>,
+    /// Reads the scene color, rebuilt every frame.
+    prefilter_bind_group: Option<wgpu::BindGroup>,
+    resolve_bind_group: Option<wgpu::BindGroup>,
+
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    resolve_pipeline: wgpu::RenderPipeline,
+
+    pub(crate) enabled: bool,
+}
+
+impl BloomRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let device = &render_server.device;
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("bloom params bind group layout"),
+            });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[texture_entry(0), sampler_entry(1)],
+                label: Some("bloom texture bind group layout"),
+            });
+
+        let resolve_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    texture_entry(0),
+                    sampler_entry(1),
+                    texture_entry(2),
+                    sampler_entry(3),
+                ],
+                label: Some("bloom resolve bind group layout"),
+            });
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "bloom params buffer",
+            mem::size_of::<BloomParamsUniform>() as BufferAddress,
+        );
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("bloom params bind group"),
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("bloom shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/bloom.wgsl").into()),
+        });
+
+        let bloom_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("bloom pipeline layout"),
+                bind_group_layouts: &[&params_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let bloom_pipeline = |entry_point, blend, label| {
+            create_fullscreen_pipeline_with_target(
+                render_server,
+                &bloom_pipeline_layout,
+                &shader_module,
+                entry_point,
+                HDR_FORMAT,
+                blend,
+                label,
+            )
+        };
+
+        let prefilter_pipeline = bloom_pipeline(
+            "fs_prefilter",
+            wgpu::BlendState::REPLACE,
+            "bloom prefilter pipeline",
+        );
+        let downsample_pipeline = bloom_pipeline(
+            "fs_downsample",
+            wgpu::BlendState::REPLACE,
+            "bloom downsample pipeline",
+        );
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let upsample_pipeline = bloom_pipeline(
+            "fs_upsample",
+            wgpu::BlendState {
+                color: additive,
+                alpha: additive,
+            },
+            "bloom upsample pipeline",
+        );
+
+        let resolve_pipeline = {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("bloom resolve pipeline layout"),
+                bind_group_layouts: &[&params_bind_group_layout, &resolve_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            create_fullscreen_pipeline_with_target(
+                render_server,
+                &pipeline_layout,
+                &shader_module,
+                "fs_resolve",
+                render_server.output_format(),
+                wgpu::BlendState::REPLACE,
+                "bloom resolve pipeline",
+            )
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let (texture, mip_views) = create_bloom_texture(render_server);
+
+        let mut render_resources = Self {
+            texture,
+            mip_views,
+            sampler,
+            params_buffer,
+            params_bind_group,
+            texture_bind_group_layout,
+            resolve_bind_group_layout,
+            mip_bind_groups: vec![],
+            prefilter_bind_group: None,
+            resolve_bind_group: None,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            resolve_pipeline,
+            enabled: false,
+        };
+        render_resources.create_mip_bind_groups(render_server);
+
+        render_resources
+    }
+
+    /// The mip chain has to follow the surface size.
+    pub(crate) fn recreate_textures(&mut self, render_server: &RenderServer) {
+        (self.texture, self.mip_views) = create_bloom_texture(render_server);
+        self.create_mip_bind_groups(render_server);
+
+        self.prefilter_bind_group = None;
+        self.resolve_bind_group = None;
+    }
+
+    fn create_mip_bind_groups(&mut self, render_server: &RenderServer) {
+        self.mip_bind_groups = self
+            .mip_views
+            .iter()
+            .map(|view| {
+                render_server
+                    .device
+                    .create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout: &self.texture_bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(&self.sampler),
+                            },
+                        ],
+                        label: Some("bloom mip bind group"),
+                    })
+            })
+            .collect();
+    }
+}
+
+/// Half float texture at half the surface size, with as many mips as fit.
+fn create_bloom_texture(render_server: &RenderServer) -> (wgpu::Texture, Vec<wgpu::TextureView>) {
+    let config = &render_server.surface_config;
+    let size = wgpu::Extent3d {
+        width: (config.width / 2).max(1),
+        height: (config.height / 2).max(1),
+        depth_or_array_layers: 1,
+    };
+
+    let mip_level_count = size
+        .max_mips(wgpu::TextureDimension::D2)
+        .min(MAX_BLOOM_MIPS);
+
+    let texture = render_server
+        .device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("bloom texture"),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+    let mip_views = (0..mip_level_count)
+        .map(|mip| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("bloom mip view"),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    (texture, mip_views)
+}
+
+pub(crate) fn prepare_bloom(
+    settings: &PostProcessSettings,
+    scene_color: &Texture,
+    render_resources: &mut BloomRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.enabled = settings.bloom_enabled();
+
+    let params = BloomParamsUniform {
+        threshold: settings.bloom_threshold.max(0.0),
+        knee: settings.bloom_knee.clamp(0.0, 1.0),
+        intensity: if render_resources.enabled {
+            settings.bloom_intensity
+        } else {
+            0.0
+        },
+        mip_count: render_resources.texture.mip_level_count() as f32,
+        // HDR display output is tone mapped later.
+        max_color: if render_server.get_settings().display_output.is_hdr() {
+            f32::MAX
+        } else {
+            1.0
+        },
+        _pad: [0.0; 3],
+    };
+
+    render_server.queue.write_buffer(
+        &render_resources.params_buffer,
+        0,
+        bytemuck::cast_slice(&[params]),
+    );
+
+    let device = &render_server.device;
+
+    if render_resources.enabled {
+        render_resources.prefilter_bind_group =
+            Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &render_resources.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&scene_color.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&scene_color.sampler),
+                    },
+                ],
+                label: Some("bloom prefilter bind group"),
+            }));
+    }
+
+    render_resources.resolve_bind_group =
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_resources.resolve_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&scene_color.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&render_resources.mip_views[0]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&render_resources.sampler),
+                },
+            ],
+            label: Some("bloom resolve bind group"),
+        }));
+}
+
+/// Run the bloom chain if enabled, then resolve the scene into `target_view`.
+pub(crate) fn render_bloom(
+    render_resources: &BloomRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+) {
+    let params_bind_group = &render_resources.params_bind_group;
+    let mip_views = &render_resources.mip_views;
+
+    if render_resources.enabled {
+        {
+            let mut render_pass =
+                begin_fullscreen_pass(encoder, &mip_views[0], "bloom prefilter pass");
+
+            render_pass.set_pipeline(&render_resources.prefilter_pipeline);
+            render_pass.set_bind_group(0, params_bind_group, &[]);
+            render_pass.set_bind_group(
+                1,
+                render_resources.prefilter_bind_group.as_ref().unwrap(),
+                &[],
+            );
+            render_pass.draw(0..3, 0..1);
+        }
+
+        // Each mip reads the previous one.
+        for (view, source) in mip_views[1..].iter().zip(&render_resources.mip_bind_groups) {
+            let mut render_pass = begin_fullscreen_pass(encoder, view, "bloom downsample pass");
+
+            render_pass.set_pipeline(&render_resources.downsample_pipeline);
+            render_pass.set_bind_group(0, params_bind_group, &[]);
+            render_pass.set_bind_group(1, source, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        // Each mip keeps its own blur and gets the smaller ones added on top.
+        for mip in (1..mip_views.len()).rev() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("bloom upsample pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &mip_views[mip - 1],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&render_resources.upsample_pipeline);
+            render_pass.set_bind_group(0, params_bind_group, &[]);
+            render_pass.set_bind_group(1, &render_resources.mip_bind_groups[mip], &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    let mut render_pass = begin_fullscreen_pass(encoder, target_view, "bloom resolve pass");
+
+    render_pass.set_pipeline(&render_resources.resolve_pipeline);
+    render_pass.set_bind_group(0, params_bind_group, &[]);
+    render_pass.set_bind_group(
+        1,
+        render_resources.resolve_bind_group.as_ref().unwrap(),
+        &[],
+    );
+    render_pass.draw(0..3, 0..1);
+}
@@ -1,7 +1,8 @@
 use crate::math::alignup_u32;
-use crate::render::RenderServer;
+use crate::render::globals::GlobalsRenderResources;
+use crate::render::{RenderServer, UniformBuffer};
 use crate::scene::OPENGL_TO_WGPU_MATRIX;
-use cgmath::{ortho, perspective, Matrix4, Rad, Vector2};
+use cgmath::{frustum, ortho, perspective, Matrix4, Rad, Vector2};
 use std::mem;
 use wgpu::BufferAddress;
 
@@ -15,12 +16,71 @@ pub(crate) enum CameraType {
 pub(crate) struct ExtractedCameras {
     pub(crate) types: Vec<CameraType>,
     pub(crate) uniforms: Vec<CameraUniform>,
+    /// Area each camera draws to. None for the whole view.
+    pub(crate) viewports: Vec<Option<ViewportRect>>,
+    /// Round 2D sprites to whole pixels and sample 2D textures without filtering.
+    pub(crate) pixel_snap: bool,
 }
 
 impl ExtractedCameras {
     pub(crate) fn add(&mut self, camera_type: CameraType, uniform: CameraUniform) {
+        self.add_with_viewport(camera_type, uniform, None);
+    }
+
+    pub(crate) fn add_with_viewport(
+        &mut self,
+        camera_type: CameraType,
+        uniform: CameraUniform,
+        viewport: Option<ViewportRect>,
+    ) {
         self.types.push(camera_type);
         self.uniforms.push(uniform);
+        self.viewports.push(viewport);
+    }
+}
+
+/// Part of the view a camera draws to, in pixels. The rest is left as it is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct ViewportRect {
+    pub(crate) position: Vector2<u32>,
+    pub(crate) size: Vector2<u32>,
+    /// Size of the whole view, to draw everywhere again after the camera.
+    pub(crate) view_size: Vector2<u32>,
+    /// Map the camera's view onto the rect. Otherwise it's only cut to it.
+    pub(crate) fit: bool,
+}
+
+impl ViewportRect {
+    /// Only draw into the rect.
+    pub(crate) fn set(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.fit {
+            render_pass.set_viewport(
+                self.position.x as f32,
+                self.position.y as f32,
+                self.size.x as f32,
+                self.size.y as f32,
+                0.0,
+                1.0,
+            );
+        }
+
+        render_pass.set_scissor_rect(self.position.x, self.position.y, self.size.x, self.size.y);
+    }
+
+    /// Draw over the whole view again.
+    pub(crate) fn reset(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        if self.fit {
+            render_pass.set_viewport(
+                0.0,
+                0.0,
+                self.view_size.x as f32,
+                self.view_size.y as f32,
+                0.0,
+                1.0,
+            );
+        }
+
+        render_pass.set_scissor_rect(0, 0, self.view_size.x, self.view_size.y);
     }
 }
 
@@ -62,7 +122,7 @@ impl CameraUniform {
 
 pub(crate) struct CameraRenderResources {
     /// A big buffer for all 3d camera uniforms. Allows using uniform buffer offset.
-    pub(crate) uniform_buffer: Option<wgpu::Buffer>,
+    pub(crate) uniform_buffer: Option<UniformBuffer>,
     uniform_buffer_capacity: usize,
 
     pub(crate) bind_group_layout: wgpu::BindGroupLayout,
@@ -75,16 +135,20 @@ impl CameraRenderResources {
             render_server
                 .device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: true,
-                            min_binding_size: None,
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: true,
+                                min_binding_size: None,
+                            },
+                            count: None,
                         },
-                        count: None,
-                    }],
+                        // Time, viewport size and so on, see `GlobalsUniform`.
+                        GlobalsRenderResources::layout_entry(1),
+                    ],
                     label: Some("mesh camera bind group layout"),
                 });
 
@@ -96,7 +160,12 @@ impl CameraRenderResources {
         }
     }
 
-    pub fn prepare_cameras(&mut self, render_server: &RenderServer, cameras: &ExtractedCameras) {
+    pub fn prepare_cameras(
+        &mut self,
+        render_server: &RenderServer,
+        cameras: &ExtractedCameras,
+        globals_render_resources: &GlobalsRenderResources,
+    ) {
         let camera_count = cameras.uniforms.len();
 
         if self.uniform_buffer_capacity < camera_count {
@@ -104,29 +173,33 @@ impl CameraRenderResources {
             let buffer_size = offset_unit * camera_count as u32;
 
             // Create a buffer for the camera uniform.
-            let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("camera uniform buffer (unique)"),
-                size: buffer_size as BufferAddress,
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
+            let buffer = render_server.create_uniform_buffer(
+                "camera uniform buffer (unique)",
+                buffer_size as BufferAddress,
+            );
 
             let bind_group = render_server
                 .device
                 .create_bind_group(&wgpu::BindGroupDescriptor {
                     layout: &self.bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                            buffer: &buffer,
-                            offset: 0,
-                            // See DynamicUniformBufferOffset.
-                            size: Some(
-                                wgpu::BufferSize::new(mem::size_of::<CameraUniform>() as u64)
-                                    .unwrap(),
-                            ),
-                        }),
-                    }],
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &buffer,
+                                offset: 0,
+                                // See DynamicUniformBufferOffset.
+                                size: Some(
+                                    wgpu::BufferSize::new(mem::size_of::<CameraUniform>() as u64)
+                                        .unwrap(),
+                                ),
+                            }),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: globals_render_resources.uniform_buffer.as_entire_binding(),
+                        },
+                    ],
                     label: Some("camera bind group (unique)"),
                 });
 
@@ -163,6 +236,7 @@ impl CameraRenderResources {
 pub enum Projection {
     Perspective(PerspectiveProjection),
     Orthographic(OrthographicProjection),
+    OffAxis(OffAxisProjection),
 }
 
 impl From<PerspectiveProjection> for Projection {
@@ -177,11 +251,19 @@ impl From<OrthographicProjection> for Projection {
     }
 }
 
+impl From<OffAxisProjection> for Projection {
+    fn from(p: OffAxisProjection) -> Self {
+        Self::OffAxis(p)
+    }
+}
+
 impl Projection {
     pub(crate) fn update(&mut self, width: f32, height: f32) {
         match self {
             Projection::Perspective(projection) => projection.update(width, height),
             Projection::Orthographic(projection) => projection.update(width, height),
+            // The angles don't depend on the view size.
+            Projection::OffAxis(_) => {}
         }
     }
 
@@ -189,8 +271,42 @@ impl Projection {
         match self {
             Projection::Perspective(projection) => projection.calc_matrix(),
             Projection::Orthographic(projection) => projection.calc_matrix(),
+            Projection::OffAxis(projection) => projection.calc_matrix(),
+        }
+    }
+
+    pub(crate) fn set_reverse_z(&mut self, reverse_z: bool) {
+        match self {
+            Projection::Perspective(projection) => projection.reverse_z = reverse_z,
+            Projection::Orthographic(projection) => projection.reverse_z = reverse_z,
+            Projection::OffAxis(projection) => projection.reverse_z = reverse_z,
         }
     }
+
+    pub(crate) fn is_reverse_z(&self) -> bool {
+        match self {
+            Projection::Perspective(projection) => projection.reverse_z,
+            Projection::Orthographic(projection) => projection.reverse_z,
+            Projection::OffAxis(projection) => projection.reverse_z,
+        }
+    }
+}
+
+/// Maps depth d to 1 - d, for a reverse-Z depth buffer.
+#[rustfmt::skip]
+pub const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
+fn apply_reverse_z(matrix: Matrix4<f32>, reverse_z: bool) -> Matrix4<f32> {
+    if reverse_z {
+        REVERSE_Z_MATRIX * matrix
+    } else {
+        matrix
+    }
 }
 
 #[derive(Clone)]
@@ -201,6 +317,8 @@ pub struct PerspectiveProjection {
     // Note : near and far are always positive.
     near: f32,
     far: f32,
+    /// Set from the render settings.
+    reverse_z: bool,
 }
 
 impl PerspectiveProjection {
@@ -210,6 +328,7 @@ impl PerspectiveProjection {
             fovy: fovy.into(),
             near,
             far,
+            reverse_z: false,
         }
     }
 
@@ -218,7 +337,10 @@ impl PerspectiveProjection {
     }
 
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.near, self.far)
+        apply_reverse_z(
+            OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.near, self.far),
+            self.reverse_z,
+        )
     }
 }
 
@@ -230,6 +352,8 @@ pub struct OrthographicProjection {
     top: f32,
     near: f32,
     far: f32,
+    /// Set from the render settings.
+    reverse_z: bool,
 }
 
 impl OrthographicProjection {
@@ -241,6 +365,7 @@ impl OrthographicProjection {
             top: 1f32,
             near,
             far,
+            reverse_z: false,
         }
     }
 
@@ -252,6 +377,7 @@ impl OrthographicProjection {
             top: 0.1,
             near: 100.0,
             far: 0.0,
+            reverse_z: false,
         }
     }
 
@@ -267,14 +393,63 @@ impl OrthographicProjection {
 
     /// Get projection matrix.
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX
-            * ortho(
-                self.left,
-                self.right,
-                self.bottom,
-                self.top,
-                self.near,
-                self.far,
-            )
+        apply_reverse_z(
+            OPENGL_TO_WGPU_MATRIX
+                * ortho(
+                    self.left,
+                    self.right,
+                    self.bottom,
+                    self.top,
+                    self.near,
+                    self.far,
+                ),
+            self.reverse_z,
+        )
+    }
+}
+
+/// Field of view as angles in radians from the forward direction. Left and down are
+/// usually negative, like in OpenXR's `XrFovf`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FovAngles {
+    pub left: f32,
+    pub right: f32,
+    pub up: f32,
+    pub down: f32,
+}
+
+/// Perspective projection that isn't centered on the view, e.g. for an eye of a headset.
+#[derive(Clone)]
+pub struct OffAxisProjection {
+    fov: FovAngles,
+    near: f32,
+    far: f32,
+    /// Set from the render settings.
+    reverse_z: bool,
+}
+
+impl OffAxisProjection {
+    pub fn new(fov: FovAngles, near: f32, far: f32) -> Self {
+        Self {
+            fov,
+            near,
+            far,
+            reverse_z: false,
+        }
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        apply_reverse_z(
+            OPENGL_TO_WGPU_MATRIX
+                * frustum(
+                    self.near * self.fov.left.tan(),
+                    self.near * self.fov.right.tan(),
+                    self.near * self.fov.down.tan(),
+                    self.near * self.fov.up.tan(),
+                    self.near,
+                    self.far,
+                ),
+            self.reverse_z,
+        )
     }
 }
@@ -0,0 +1,322 @@
+use crate::math::transform::Transform2d;
+use crate::render::vertex::VertexBuffer;
+use crate::render::RenderServer;
+use cgmath::Vector2;
+use std::mem;
+use std::ops::Range;
+use wgpu::BufferAddress;
+
+/// A clip shape in view (pixel) space, like UI nodes.
+/// The rectangle spans from the transform origin to `size`, rotated around the origin.
+#[derive(Debug, Clone)]
+pub struct ExtractedClip {
+    pub(crate) transform: Transform2d,
+    pub(crate) size: Vector2<f32>,
+    pub(crate) corner_radius: f32,
+    pub(crate) view_size: Vector2<u32>,
+    /// Arbitrary shapes as triangles, three points each, e.g. SVG clip paths.
+    /// Used instead of the rectangle if not empty.
+    pub(crate) triangles: Vec<Vector2<f32>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ClipOp {
+    Push,
+    Pop,
+}
+
+/// Where the clip stack changes relative to other 2D draws.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ClipCommand {
+    pub(crate) op: ClipOp,
+    /// Index into the extracted clips.
+    pub(crate) clip: usize,
+    /// Number of sprites drawn before this command.
+    pub(crate) sprite_index: usize,
+    /// Number of atlases drawn before this command.
+    pub(crate) atlas_index: usize,
+    /// Number of UI shapes drawn before this command.
+    pub(crate) ui_shape_index: usize,
+    /// Number of 2D lines drawn before this command.
+    pub(crate) line_index: usize,
+    /// Number of 2D curve draws before this command.
+    pub(crate) curve_index: usize,
+}
+
+/// Stencil state for 2D content, which is drawn where the stencil value equals the clip depth.
+pub(crate) fn clipped_stencil_state() -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Equal,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op: wgpu::StencilOperation::Keep,
+    };
+
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask: 0,
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ClipVertex {
+    position: [f32; 2],
+    local: [f32; 2],
+    half_size: [f32; 2],
+    corner_radius: f32,
+    _pad: f32,
+}
+
+impl VertexBuffer for ClipVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ClipVertex>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// Six vertices (two triangles) per clip rectangle.
+const CLIP_VERTEX_COUNT: usize = 6;
+
+pub struct ClipRenderResources {
+    /// Increments the stencil inside a shape whose parent clip depth matches the reference.
+    push_pipeline: wgpu::RenderPipeline,
+    /// Decrements it again once the shape's children are drawn.
+    pop_pipeline: wgpu::RenderPipeline,
+
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_buffer_capacity: usize,
+
+    /// Vertices of each clip shape.
+    ranges: Vec<Range<u32>>,
+}
+
+impl ClipRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        Self {
+            push_pipeline: Self::create_pipeline(
+                render_server,
+                wgpu::StencilOperation::IncrementClamp,
+                "clip push pipeline",
+            ),
+            pop_pipeline: Self::create_pipeline(
+                render_server,
+                wgpu::StencilOperation::DecrementClamp,
+                "clip pop pipeline",
+            ),
+            vertex_buffer: None,
+            vertex_buffer_capacity: 0,
+            ranges: vec![],
+        }
+    }
+
+    fn create_pipeline(
+        render_server: &RenderServer,
+        pass_op: wgpu::StencilOperation,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        let device = &render_server.device;
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("clip pipeline layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("clip shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/clip.wgsl").into()),
+        });
+
+        let face = wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[ClipVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState {
+                    front: face,
+                    back: face,
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+}
+
+pub(crate) fn prepare_clip(
+    clips: &[ExtractedClip],
+    render_resources: &mut ClipRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.ranges.clear();
+
+    if clips.is_empty() {
+        return;
+    }
+
+    let vertex_count = clips
+        .iter()
+        .map(|clip| {
+            if clip.triangles.is_empty() {
+                CLIP_VERTEX_COUNT
+            } else {
+                clip.triangles.len()
+            }
+        })
+        .sum();
+
+    // Reallocate the vertex buffer.
+    if render_resources.vertex_buffer_capacity < vertex_count {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("clip vertex buffer (unique)"),
+            size: (mem::size_of::<ClipVertex>() * vertex_count) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.vertex_buffer_capacity = vertex_count;
+        render_resources.vertex_buffer = Some(buffer);
+    }
+
+    let mut vertices = Vec::with_capacity(vertex_count);
+
+    for clip in clips {
+        let start = vertices.len() as u32;
+        let view_size = Vector2::new(clip.view_size.x as f32, clip.view_size.y as f32);
+        let to_clip_space = |position: Vector2<f32>| {
+            [
+                position.x / view_size.x * 2.0 - 1.0,
+                1.0 - position.y / view_size.y * 2.0,
+            ]
+        };
+
+        if !clip.triangles.is_empty() {
+            // Inside of a unit box with sharp corners, so that nothing is discarded.
+            vertices.extend(clip.triangles.iter().map(|position| ClipVertex {
+                position: to_clip_space(*position),
+                local: [0.0, 0.0],
+                half_size: [1.0, 1.0],
+                corner_radius: 0.0,
+                _pad: 0.0,
+            }));
+
+            render_resources.ranges.push(start..vertices.len() as u32);
+            continue;
+        }
+
+        let half_size = clip.size / 2.0;
+
+        // Corner radius can't exceed half of the shorter side.
+        let corner_radius = clip.corner_radius.clamp(0.0, half_size.x.min(half_size.y));
+
+        let corners = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(clip.size.x, 0.0),
+            Vector2::new(clip.size.x, clip.size.y),
+            Vector2::new(0.0, clip.size.y),
+        ]
+        .map(|local| {
+            let position = Vector2::from(clip.transform.transform_point(local));
+
+            ClipVertex {
+                position: to_clip_space(position),
+                local: (local - half_size).into(),
+                half_size: half_size.into(),
+                corner_radius,
+                _pad: 0.0,
+            }
+        });
+
+        for i in [0, 1, 2, 0, 2, 3] {
+            vertices.push(corners[i]);
+        }
+
+        render_resources.ranges.push(start..vertices.len() as u32);
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.vertex_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&vertices),
+    );
+}
+
+/// Push or pop a clip shape. `depth` is the clip depth before the command.
+pub(crate) fn render_clip<'a, 'b: 'a>(
+    command: &ClipCommand,
+    depth: u32,
+    render_resources: &'b ClipRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+) {
+    let pipeline = match command.op {
+        ClipOp::Push => &render_resources.push_pipeline,
+        ClipOp::Pop => &render_resources.pop_pipeline,
+    };
+
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_stencil_reference(depth);
+    render_pass.set_vertex_buffer(
+        0,
+        render_resources.vertex_buffer.as_ref().unwrap().slice(..),
+    );
+
+    render_pass.draw(render_resources.ranges[command.clip].clone(), 0..1);
+}
@@ -0,0 +1,526 @@
+use crate::render::camera::CameraUniform;
+use crate::render::light::PointLightUniform;
+use crate::render::{RenderServer, UniformBuffer};
+use cgmath::{Matrix4, SquareMatrix, Vector3, Vector4};
+use std::mem;
+use wgpu::BufferAddress;
+
+/// Clusters along the screen width, height and view depth.
+/// Same as in mesh.wgsl and cluster.wgsl.
+const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+
+/// Lights beyond this in a cluster are ignored. Same as in mesh.wgsl and cluster.wgsl.
+const MAX_CLUSTER_LIGHTS: usize = 64;
+
+/// Point lights beyond this are ignored.
+const MAX_CLUSTERED_POINT_LIGHTS: usize = 1024;
+
+/// A light count, then the light indices.
+const CLUSTER_STRIDE: usize = MAX_CLUSTER_LIGHTS + 1;
+
+/// Light below this fraction of its color is cut off, which gives the lights a range.
+const LIGHT_CUTOFF: f32 = 1.0 / 256.0;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+fn cluster_count() -> usize {
+    CLUSTER_GRID.iter().product::<u32>() as usize
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusteredPointLight {
+    position: [f32; 3],
+    /// Where the light falls below `LIGHT_CUTOFF`.
+    radius: f32,
+    color: [f32; 3],
+    constant: f32,
+    linear: f32,
+    quadratic: f32,
+    _pad: [f32; 2],
+}
+
+impl ClusteredPointLight {
+    fn new(light: &PointLightUniform, far: f32) -> Self {
+        // Solve 1 / (constant + linear * d + quadratic * d^2) = cutoff / brightness.
+        let brightness = light.color.iter().copied().fold(0.0, f32::max);
+        let k = brightness / LIGHT_CUTOFF;
+
+        let radius = if light.quadratic > 0.0 {
+            let b = light.linear;
+            let c = light.constant - k;
+            (-b + (b * b - 4.0 * light.quadratic * c).max(0.0).sqrt()) / (2.0 * light.quadratic)
+        } else if light.linear > 0.0 {
+            (k - light.constant) / light.linear
+        } else {
+            far
+        };
+
+        Self {
+            position: light.position,
+            radius: radius.clamp(0.0, far),
+            color: light.color,
+            constant: light.constant,
+            linear: light.linear,
+            quadratic: light.quadratic,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+/// View space bounds of a cluster.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterBounds {
+    min: [f32; 4],
+    max: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterParamsUniform {
+    view: [[f32; 4]; 4],
+    light_count: u32,
+    /// View depth of the first and last slice.
+    near: f32,
+    far: f32,
+    _pad: f32,
+}
+
+/// Projection and surface size the cluster bounds were computed for.
+type BoundsKey = ([[f32; 4]; 4], (u32, u32));
+
+/// Clustered forward+ lighting, used if `RenderSettings::render_path` is
+/// `RenderPath::ForwardPlus`.
+///
+/// The view frustum of the main 3D camera is split into a grid of clusters, and each
+/// cluster gets a list of the point lights reaching it. Meshes then only shade the
+/// lights of the cluster a fragment is in, instead of all lights.
+pub(crate) struct ClusterRenderResources {
+    params_buffer: UniformBuffer,
+    lights_buffer: wgpu::Buffer,
+    bounds_buffer: wgpu::Buffer,
+    /// `CLUSTER_STRIDE` indices per cluster.
+    cluster_lights_buffer: wgpu::Buffer,
+
+    /// None if compute shaders aren't supported, then lights are assigned on the CPU.
+    pipeline: Option<(wgpu::BindGroup, wgpu::ComputePipeline)>,
+
+    bounds_key: Option<BoundsKey>,
+    bounds: Vec<ClusterBounds>,
+
+    /// If lights are assigned on the GPU this frame.
+    dispatch: bool,
+}
+
+impl ClusterRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let device = &render_server.device;
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "cluster params buffer",
+            mem::size_of::<ClusterParamsUniform>() as BufferAddress,
+        );
+
+        let storage_buffer = |label, size: usize| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: size as BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        let lights_buffer = storage_buffer(
+            "cluster lights buffer",
+            mem::size_of::<ClusteredPointLight>() * MAX_CLUSTERED_POINT_LIGHTS,
+        );
+        let bounds_buffer = storage_buffer(
+            "cluster bounds buffer",
+            mem::size_of::<ClusterBounds>() * cluster_count(),
+        );
+        let cluster_lights_buffer = storage_buffer(
+            "cluster light indices buffer",
+            mem::size_of::<u32>() * CLUSTER_STRIDE * cluster_count(),
+        );
+
+        let pipeline = render_server.supports_compute().then(|| {
+            create_cluster_pipeline(
+                device,
+                &params_buffer,
+                &lights_buffer,
+                &bounds_buffer,
+                &cluster_lights_buffer,
+            )
+        });
+
+        if pipeline.is_none() {
+            log::info!(
+                "Compute shaders aren't supported, lights are assigned to clusters on the CPU"
+            );
+        }
+
+        Self {
+            params_buffer,
+            lights_buffer,
+            bounds_buffer,
+            cluster_lights_buffer,
+            pipeline,
+            bounds_key: None,
+            bounds: vec![],
+            dispatch: false,
+        }
+    }
+
+    /// Layout entries for the mesh light bind group, starting at `first_binding`.
+    pub(crate) fn layout_entries(first_binding: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let storage = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let uniform = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+
+        [uniform, storage, storage]
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| wgpu::BindGroupLayoutEntry {
+                binding: first_binding + i as u32,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty,
+                count: None,
+            })
+            .collect()
+    }
+
+    /// Bind group entries matching `layout_entries`.
+    pub(crate) fn bind_group_entries(&self, first_binding: u32) -> Vec<wgpu::BindGroupEntry<'_>> {
+        [
+            &self.params_buffer,
+            &self.lights_buffer,
+            &self.cluster_lights_buffer,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, buffer)| wgpu::BindGroupEntry {
+            binding: first_binding + i as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect()
+    }
+}
+
+fn create_cluster_pipeline(
+    device: &wgpu::Device,
+    params_buffer: &wgpu::Buffer,
+    lights_buffer: &wgpu::Buffer,
+    bounds_buffer: &wgpu::Buffer,
+    cluster_lights_buffer: &wgpu::Buffer,
+) -> (wgpu::BindGroup, wgpu::ComputePipeline) {
+    let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            storage_entry(1, true),
+            storage_entry(2, true),
+            storage_entry(3, false),
+        ],
+        label: Some("cluster bind group layout"),
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: lights_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: bounds_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: cluster_lights_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("cluster bind group"),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("cluster pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("cluster shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/cluster.wgsl").into()),
+    });
+
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("cluster pipeline"),
+        layout: Some(&pipeline_layout),
+        module: &shader_module,
+        entry_point: "cs_main",
+    });
+
+    (bind_group, pipeline)
+}
+
+/// View depth of the near and far planes of a projection.
+fn near_far(inverse_proj: &Matrix4<f32>, render_server: &RenderServer) -> (f32, f32) {
+    let view_depth = |ndc_z: f32| {
+        let view = inverse_proj * Vector4::new(0.0, 0.0, ndc_z, 1.0);
+        -view.z / view.w
+    };
+
+    let far_ndc = render_server.depth_clear_value();
+
+    (view_depth(1.0 - far_ndc), view_depth(far_ndc))
+}
+
+/// Slices grow with the distance, like the precision we need.
+fn slice_depth(slice: u32, near: f32, far: f32) -> f32 {
+    near * (far / near).powf(slice as f32 / CLUSTER_GRID[2] as f32)
+}
+
+fn compute_bounds(proj: &Matrix4<f32>, near: f32, far: f32) -> Vec<ClusterBounds> {
+    let inverse_proj = proj.invert().unwrap_or(Matrix4::identity());
+
+    // NDC depth of a view depth, so that orthographic projections work too.
+    let ndc_z = |depth: f32| {
+        let clip = proj * Vector4::new(0.0, 0.0, -depth, 1.0);
+        clip.z / clip.w
+    };
+
+    let unproject = |x: f32, y: f32, z: f32| {
+        let view = inverse_proj * Vector4::new(x, y, z, 1.0);
+        view.truncate() / view.w
+    };
+
+    let [grid_x, grid_y, grid_z] = CLUSTER_GRID;
+    let mut bounds = Vec::with_capacity(cluster_count());
+
+    for z in 0..grid_z {
+        let depths = [
+            ndc_z(slice_depth(z, near, far)),
+            ndc_z(slice_depth(z + 1, near, far)),
+        ];
+
+        for y in 0..grid_y {
+            // Rows go down the screen, like pixels.
+            let ndc_y = [
+                1.0 - 2.0 * y as f32 / grid_y as f32,
+                1.0 - 2.0 * (y + 1) as f32 / grid_y as f32,
+            ];
+
+            for x in 0..grid_x {
+                let ndc_x = [
+                    2.0 * x as f32 / grid_x as f32 - 1.0,
+                    2.0 * (x + 1) as f32 / grid_x as f32 - 1.0,
+                ];
+
+                let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+                let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+
+                for corner in 0..8 {
+                    let p = unproject(
+                        ndc_x[corner & 1],
+                        ndc_y[(corner >> 1) & 1],
+                        depths[corner >> 2],
+                    );
+                    min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+                    max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+                }
+
+                bounds.push(ClusterBounds {
+                    min: min.extend(0.0).into(),
+                    max: max.extend(0.0).into(),
+                });
+            }
+        }
+    }
+
+    bounds
+}
+
+/// What the compute shader does, for devices without compute shaders.
+fn assign_lights(
+    bounds: &[ClusterBounds],
+    lights: &[ClusteredPointLight],
+    view: &Matrix4<f32>,
+) -> Vec<u32> {
+    let view_lights: Vec<(Vector3<f32>, f32)> = lights
+        .iter()
+        .map(|light| {
+            let position = view * Vector3::from(light.position).extend(1.0);
+            (position.truncate(), light.radius)
+        })
+        .collect();
+
+    let mut cluster_lights = vec![0u32; CLUSTER_STRIDE * bounds.len()];
+
+    for (i, cluster) in bounds.iter().enumerate() {
+        let base = i * CLUSTER_STRIDE;
+        let mut count = 0;
+
+        for (light_index, (center, radius)) in view_lights.iter().enumerate() {
+            if count == MAX_CLUSTER_LIGHTS {
+                break;
+            }
+
+            // Closest point of the box to the sphere.
+            let closest = Vector3::new(
+                center.x.clamp(cluster.min[0], cluster.max[0]),
+                center.y.clamp(cluster.min[1], cluster.max[1]),
+                center.z.clamp(cluster.min[2], cluster.max[2]),
+            );
+            let offset = closest - center;
+
+            if offset.x * offset.x + offset.y * offset.y + offset.z * offset.z <= radius * radius {
+                cluster_lights[base + 1 + count] = light_index as u32;
+                count += 1;
+            }
+        }
+
+        cluster_lights[base] = count as u32;
+    }
+
+    cluster_lights
+}
+
+pub(crate) fn prepare_clusters(
+    point_lights: &[PointLightUniform],
+    camera: Option<&CameraUniform>,
+    render_resources: &mut ClusterRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.dispatch = false;
+
+    let Some(camera) = camera else {
+        return;
+    };
+
+    let proj = Matrix4::from(camera.proj);
+    let view = Matrix4::from(camera.view);
+    let inverse_proj = proj.invert().unwrap_or(Matrix4::identity());
+    let (near, far) = near_far(&inverse_proj, render_server);
+
+    if !(near > 0.0 && far > near) {
+        return;
+    }
+
+    // The frustum only changes with the projection.
+    let config = &render_server.surface_config;
+    let bounds_key = Some((camera.proj, (config.width, config.height)));
+
+    if render_resources.bounds_key != bounds_key {
+        render_resources.bounds = compute_bounds(&proj, near, far);
+        render_resources.bounds_key = bounds_key;
+
+        render_server.queue.write_buffer(
+            &render_resources.bounds_buffer,
+            0,
+            bytemuck::cast_slice(&render_resources.bounds),
+        );
+    }
+
+    if point_lights.len() > MAX_CLUSTERED_POINT_LIGHTS {
+        log::warn!(
+            "Only {} of {} point lights are drawn",
+            MAX_CLUSTERED_POINT_LIGHTS,
+            point_lights.len()
+        );
+    }
+
+    let lights: Vec<ClusteredPointLight> = point_lights
+        .iter()
+        .take(MAX_CLUSTERED_POINT_LIGHTS)
+        .map(|light| ClusteredPointLight::new(light, far))
+        .collect();
+
+    let params = ClusterParamsUniform {
+        view: camera.view,
+        light_count: lights.len() as u32,
+        near,
+        far,
+        _pad: 0.0,
+    };
+
+    render_server.queue.write_buffer(
+        &render_resources.params_buffer,
+        0,
+        bytemuck::bytes_of(&params),
+    );
+
+    if !lights.is_empty() {
+        render_server.queue.write_buffer(
+            &render_resources.lights_buffer,
+            0,
+            bytemuck::cast_slice(&lights),
+        );
+    }
+
+    if render_resources.pipeline.is_some() {
+        render_resources.dispatch = true;
+    } else {
+        let cluster_lights = assign_lights(&render_resources.bounds, &lights, &view);
+
+        render_server.queue.write_buffer(
+            &render_resources.cluster_lights_buffer,
+            0,
+            bytemuck::cast_slice(&cluster_lights),
+        );
+    }
+}
+
+/// Assign the lights to clusters, before anything is drawn.
+pub(crate) fn render_clusters(
+    render_resources: &ClusterRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    let Some((bind_group, pipeline)) = render_resources
+        .pipeline
+        .as_ref()
+        .filter(|_| render_resources.dispatch)
+    else {
+        return;
+    };
+
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("cluster pass"),
+        timestamp_writes: None,
+    });
+
+    compute_pass.set_pipeline(pipeline);
+    compute_pass.set_bind_group(0, bind_group, &[]);
+    compute_pass.dispatch_workgroups((cluster_count() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+}
@@ -0,0 +1,218 @@
+use crate::render::camera::CameraUniform;
+use crate::render::light::ExtractedLights;
+use crate::render::post_process::create_fullscreen_pipeline_with_target;
+use crate::render::{RenderServer, ShadowSettings, TextureCache, TextureId, UniformBuffer};
+use cgmath::{InnerSpace, Vector3};
+use std::mem;
+use wgpu::BufferAddress;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ContactShadowParamsUniform {
+    /// Towards the light, normalized.
+    light_direction: [f32; 3],
+    length: f32,
+    strength: f32,
+    /// What the depth buffer is cleared to, i.e. where nothing was drawn.
+    depth_clear: f32,
+    _pad: [f32; 2],
+}
+
+/// Short screen space rays towards the sun, darkening the spots where an object meets
+/// what it stands on. Shadow maps lose those to their bias.
+pub(crate) struct ContactShadowRenderResources {
+    params_buffer: UniformBuffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Rebuilt every frame, the depth texture may be recreated at any time.
+    bind_group: Option<wgpu::BindGroup>,
+    pipeline: wgpu::RenderPipeline,
+
+    /// If contact shadows are drawn this frame.
+    pub(crate) enabled: bool,
+}
+
+impl ContactShadowRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "contact shadow params buffer",
+            mem::size_of::<ContactShadowParamsUniform>() as BufferAddress,
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        // Read as floats, since GL can't load from depth textures.
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("contact shadow bind group layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("contact shadow pipeline layout"),
+            bind_group_layouts: &[globals_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("contact shadow shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/contact_shadow.wgsl").into()),
+        });
+
+        // Multiply the scene color with the shader output.
+        let multiply = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::Zero,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        let pipeline = create_fullscreen_pipeline_with_target(
+            render_server,
+            &pipeline_layout,
+            &shader_module,
+            "fs_main",
+            render_server.scene_format(),
+            multiply,
+            "contact shadow pipeline",
+        );
+
+        Self {
+            params_buffer,
+            bind_group_layout,
+            bind_group: None,
+            pipeline,
+            enabled: false,
+        }
+    }
+}
+
+pub(crate) fn prepare_contact_shadows(
+    settings: &ShadowSettings,
+    extracted_lights: &ExtractedLights,
+    camera: Option<&CameraUniform>,
+    render_resources: &mut ContactShadowRenderResources,
+    texture_cache: &TextureCache,
+    depth_texture: TextureId,
+    render_server: &RenderServer,
+) {
+    // Follows the shadow maps of the directional light.
+    let direction = extracted_lights
+        .directional_light
+        .filter(|_| settings.enabled && settings.contact_shadows)
+        .filter(|_| extracted_lights.directional_light_shadows)
+        .map(|light| Vector3::from(light.direction))
+        .filter(|direction| direction.magnitude2() > 0.0)
+        .filter(|_| camera.is_some());
+
+    render_resources.enabled = direction.is_some();
+
+    let Some(direction) = direction else {
+        return;
+    };
+
+    let uniform = ContactShadowParamsUniform {
+        light_direction: direction.normalize().into(),
+        length: settings.contact_shadow_length.max(0.0),
+        strength: settings.contact_shadow_strength.clamp(0.0, 1.0),
+        depth_clear: render_server.depth_clear_value(),
+        _pad: [0.0; 2],
+    };
+
+    render_server.queue.write_buffer(
+        &render_resources.params_buffer,
+        0,
+        bytemuck::bytes_of(&uniform),
+    );
+
+    // The stencil aspect can't be sampled along with depth.
+    let depth_view = texture_cache
+        .get(depth_texture)
+        .unwrap()
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+
+    render_resources.bind_group = Some(render_server.device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            layout: &render_resources.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: render_resources.params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+            ],
+            label: Some("contact shadow bind group"),
+        },
+    ));
+}
+
+/// Darken `scene_view` where the rays are blocked. Runs without a depth attachment,
+/// so that the depth buffer can be sampled.
+pub(crate) fn render_contact_shadows(
+    render_resources: &ContactShadowRenderResources,
+    globals_bind_group: &wgpu::BindGroup,
+    encoder: &mut wgpu::CommandEncoder,
+    scene_view: &wgpu::TextureView,
+) {
+    if !render_resources.enabled {
+        return;
+    }
+
+    // Keeps what has been drawn, unlike begin_fullscreen_pass.
+    // GL keeps pass labels next to push constants, a length that isn't a multiple
+    // of 4 would misalign them.
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("contact shadows pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: scene_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+    render_pass.set_bind_group(0, globals_bind_group, &[]);
+    render_pass.set_bind_group(1, render_resources.bind_group.as_ref().unwrap(), &[]);
+    render_pass.draw(0..3, 0..1);
+}
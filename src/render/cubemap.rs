@@ -0,0 +1,369 @@
+use crate::render::texture::{encode_pixels, is_high_precision};
+use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use anyhow::{bail, Context, Result};
+use image::{DynamicImage, GenericImageView, Rgba, Rgba32FImage};
+use std::f32::consts::PI;
+use std::path::Path;
+
+/// How the six faces of a cubemap are arranged in a single image.
+///
+/// Faces are in the order +X, -X, +Y, -Y, +Z, -Z. Cross layouts follow the usual convention:
+/// ```text
+///  Horizontal      Vertical
+///  .  +Y .  .      .  +Y .
+///  -X +Z +X -Z     -X +Z +X
+///  .  -Y .  .      .  -Y .
+///                  .  -Z .   (-Z upside down)
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CubemapLayout {
+    /// Detect from the image aspect ratio.
+    Auto,
+    /// Faces stacked from top to bottom.
+    VerticalStrip,
+    /// Faces side by side from left to right.
+    HorizontalStrip,
+    HorizontalCross,
+    VerticalCross,
+    /// A 2:1 latitude-longitude panorama, with -Z at the center.
+    Equirectangular,
+}
+
+impl CubemapLayout {
+    fn detect(width: u32, height: u32) -> Result<Self> {
+        Ok(if width * 6 == height {
+            Self::VerticalStrip
+        } else if height * 6 == width {
+            Self::HorizontalStrip
+        } else if width * 3 == height * 4 {
+            Self::HorizontalCross
+        } else if width * 4 == height * 3 {
+            Self::VerticalCross
+        } else if width == height * 2 {
+            Self::Equirectangular
+        } else {
+            bail!("Unknown cubemap layout for a {}x{} image", width, height);
+        })
+    }
+}
+
+/// File names tried for each face by `load_cube_dir`, in face order.
+const FACE_NAMES: [[&str; 3]; 6] = [
+    ["posx", "px", "right"],
+    ["negx", "nx", "left"],
+    ["posy", "py", "top"],
+    ["negy", "ny", "bottom"],
+    ["posz", "pz", "front"],
+    ["negz", "nz", "back"],
+];
+
+impl Texture {
+    /// Load a cubemap from a single image, detecting the layout.
+    pub fn load_cube<P: AsRef<Path>>(
+        render_server: &RenderServer,
+        cache: &mut TextureCache,
+        path: P,
+    ) -> Result<TextureId> {
+        Self::load_cube_with_layout(render_server, cache, path, CubemapLayout::Auto)
+    }
+
+    pub fn load_cube_with_layout<P: AsRef<Path>>(
+        render_server: &RenderServer,
+        cache: &mut TextureCache,
+        path: P,
+        layout: CubemapLayout,
+    ) -> Result<TextureId> {
+        // Needed to appease the borrow checker.
+        let path_copy = path.as_ref().to_path_buf();
+        let label = path_copy.to_str();
+
+        let img = image::open(path).context("Invalid image path")?;
+
+        Self::from_cube_image(
+            &render_server.device,
+            &render_server.queue,
+            cache,
+            &img,
+            layout,
+            label,
+        )
+    }
+
+    /// Load a cubemap from six images, in the order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn load_cube_faces<P: AsRef<Path>>(
+        render_server: &RenderServer,
+        cache: &mut TextureCache,
+        paths: [P; 6],
+    ) -> Result<TextureId> {
+        let label = paths[0].as_ref().parent().and_then(|p| p.to_str());
+
+        let mut faces = vec![];
+        for path in &paths {
+            faces.push(
+                image::open(path)
+                    .with_context(|| format!("Invalid image path {:?}", path.as_ref()))?,
+            );
+        }
+
+        Self::from_cube_faces(
+            &render_server.device,
+            &render_server.queue,
+            cache,
+            &faces,
+            label,
+        )
+    }
+
+    /// Load a cubemap from a directory of six images named by face,
+    /// e.g. "posx.png", "negx.png", ... (also "px"/"nx" or "right"/"left"/"top"/"bottom"/"front"/"back").
+    pub fn load_cube_dir<P: AsRef<Path>>(
+        render_server: &RenderServer,
+        cache: &mut TextureCache,
+        dir: P,
+    ) -> Result<TextureId> {
+        let files: Vec<_> = std::fs::read_dir(dir.as_ref())?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect();
+
+        let mut paths = vec![];
+        for names in FACE_NAMES {
+            let path = files
+                .iter()
+                .find(|f| {
+                    f.file_stem()
+                        .and_then(|s| s.to_str())
+                        .is_some_and(|s| names.contains(&s.to_lowercase().as_str()))
+                })
+                .with_context(|| format!("No {} face in {:?}", names[0], dir.as_ref()))?;
+
+            paths.push(path.clone());
+        }
+
+        Self::load_cube_faces(render_server, cache, paths.try_into().unwrap())
+    }
+
+    /// Create a cubemap from a single image in the given layout.
+    pub fn from_cube_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        img: &DynamicImage,
+        layout: CubemapLayout,
+        label: Option<&str>,
+    ) -> Result<TextureId> {
+        let (width, height) = img.dimensions();
+
+        let layout = match layout {
+            CubemapLayout::Auto => CubemapLayout::detect(width, height)?,
+            layout => layout,
+        };
+
+        // Cell positions of each face, in face size units.
+        let cells: [(u32, u32); 6] = match layout {
+            CubemapLayout::VerticalStrip => [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5)],
+            CubemapLayout::HorizontalStrip => [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0)],
+            CubemapLayout::HorizontalCross => [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+            CubemapLayout::VerticalCross => [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+            CubemapLayout::Equirectangular => {
+                let faces = equirectangular_to_faces(img, height / 2);
+                return Self::from_cube_faces(device, queue, cache, &faces, label);
+            }
+            CubemapLayout::Auto => unreachable!(),
+        };
+
+        let face_size = match layout {
+            CubemapLayout::VerticalStrip => width,
+            CubemapLayout::HorizontalStrip => height,
+            CubemapLayout::HorizontalCross => width / 4,
+            _ => width / 3,
+        };
+
+        let mut faces: Vec<DynamicImage> = cells
+            .iter()
+            .map(|(x, y)| img.crop_imm(x * face_size, y * face_size, face_size, face_size))
+            .collect();
+
+        if layout == CubemapLayout::VerticalCross {
+            faces[5] = faces[5].rotate180();
+        }
+
+        Self::from_cube_faces(device, queue, cache, &faces, label)
+    }
+
+    /// Create a cubemap from six square images, in the order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn from_cube_faces(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        faces: &[DynamicImage],
+        label: Option<&str>,
+    ) -> Result<TextureId> {
+        if faces.len() != 6 {
+            bail!("A cubemap needs 6 faces, got {}", faces.len());
+        }
+
+        let face_size = faces[0].dimensions();
+        if face_size.0 != face_size.1 || faces.iter().any(|f| f.dimensions() != face_size) {
+            bail!("Cubemap faces must be square and of the same size");
+        }
+
+        // HDR environment maps are kept as linear half floats.
+        let hdr = faces.iter().any(is_high_precision);
+        let format = if hdr {
+            wgpu::TextureFormat::Rgba16Float
+        } else {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        };
+        let bytes_per_pixel = format.block_copy_size(None).unwrap();
+
+        let mut data = vec![];
+        for face in faces {
+            if hdr {
+                let rgba = DynamicImage::ImageRgba32F(face.to_rgba32f());
+                data.extend_from_slice(&encode_pixels(&rgba, format));
+            } else {
+                data.extend_from_slice(&face.to_rgba8());
+            }
+        }
+
+        let size = wgpu::Extent3d {
+            width: face_size.0,
+            height: face_size.1,
+            depth_or_array_layers: 6,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Write image data to texture.
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_pixel * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("cubemap texture view"),
+            format: Some(format),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            aspect: wgpu::TextureAspect::default(),
+            base_mip_level: 0,
+            mip_level_count: Some(1),
+            base_array_layer: 0,
+            array_layer_count: Some(6),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let texture = Self {
+            size: face_size,
+            texture,
+            view,
+            sampler,
+            format,
+        };
+
+        Ok(cache.add(texture))
+    }
+}
+
+/// Unnormalized direction through a point on a cube face, with `u` and `v` in [-1, 1]
+/// going right and down the face image. Same face orientation as the GPU uses for cube sampling.
+pub(crate) fn cube_face_direction(face: usize, u: f32, v: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    }
+}
+
+/// Resample a latitude-longitude panorama into six cube faces.
+fn equirectangular_to_faces(img: &DynamicImage, face_size: u32) -> Vec<DynamicImage> {
+    let source = img.to_rgba32f();
+    let hdr = is_high_precision(img);
+
+    (0..6)
+        .map(|face| {
+            let mut output = Rgba32FImage::new(face_size, face_size);
+
+            for (x, y, pixel) in output.enumerate_pixels_mut() {
+                let u = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+
+                let [dx, dy, dz] = cube_face_direction(face, u, v);
+                let length = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                let longitude = f32::atan2(dx, -dz);
+                let latitude = (dy / length).asin();
+
+                let sx = (0.5 + longitude / (2.0 * PI)) * source.width() as f32;
+                let sy = (0.5 - latitude / PI) * source.height() as f32;
+
+                *pixel = sample_bilinear(&source, sx, sy);
+            }
+
+            let face = DynamicImage::ImageRgba32F(output);
+            if hdr {
+                face
+            } else {
+                DynamicImage::ImageRgba8(face.to_rgba8())
+            }
+        })
+        .collect()
+}
+
+/// Bilinear sample with horizontal wrapping, at pixel coordinates.
+fn sample_bilinear(img: &Rgba32FImage, x: f32, y: f32) -> Rgba<f32> {
+    let (width, height) = img.dimensions();
+
+    let x = x - 0.5;
+    let y = (y - 0.5).clamp(0.0, (height - 1) as f32);
+
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let tx = x - x0;
+    let ty = y - y0;
+
+    let x0 = (x0 as i64).rem_euclid(width as i64) as u32;
+    let x1 = (x0 + 1) % width;
+    let y0 = y0 as u32;
+    let y1 = (y0 + 1).min(height - 1);
+
+    let mut result = [0.0; 4];
+    for (i, channel) in result.iter_mut().enumerate() {
+        let top = img.get_pixel(x0, y0)[i] * (1.0 - tx) + img.get_pixel(x1, y0)[i] * tx;
+        let bottom = img.get_pixel(x0, y1)[i] * (1.0 - tx) + img.get_pixel(x1, y1)[i] * tx;
+        *channel = top * (1.0 - ty) + bottom * ty;
+    }
+
+    Rgba(result)
+}
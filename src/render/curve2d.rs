@@ -0,0 +1,234 @@
+//! Resolution independent 2D fills, drawn on the GPU with the Loop-Blinn method.
+//!
+//! A fill is split into solid triangles for its straight-edged interior, and one triangle
+//! per quadratic curve, whose control points get the curve coordinates (0, 0), (0.5, 0) and
+//! (1, 1). The fragment shader keeps one side of u² = v, with an anti-aliased edge at any
+//! zoom level, instead of the edge being flattened into lines up front.
+
+use crate::render::clip::clipped_stencil_state;
+use crate::render::sprite::SpriteRenderResources;
+use crate::render::vertex::{Vertex2dCurve, VertexBuffer};
+use crate::render::{
+    create_render_pipeline, DepthStencilConfig, RenderServer, Texture, TextureCache, TextureId,
+};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::mem;
+use std::ops::Range;
+use wgpu::BufferAddress;
+
+/// Curve and solid triangles, in world space.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtractedCurve2d {
+    pub(crate) vertices: Vec<Vertex2dCurve>,
+    pub(crate) indices: Vec<u32>,
+    pub(crate) texture_id: Option<TextureId>,
+}
+
+/// Curves use the sprite texture and light map bind groups, so they are lit like sprites.
+pub(crate) struct Curve2dRenderResources {
+    pipeline: wgpu::RenderPipeline,
+
+    /// Used by curves without a texture.
+    white_texture: TextureId,
+
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_buffer_capacity: usize,
+    index_buffer: Option<wgpu::Buffer>,
+    index_buffer_capacity: usize,
+
+    /// Index range and texture of each draw.
+    draws: Vec<(Range<u32>, TextureId)>,
+}
+
+impl Curve2dRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        sprite_render_resources: &SpriteRenderResources,
+        light_map_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let pipeline_layout =
+            render_server
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("curve2d pipeline layout"),
+                    bind_group_layouts: &[
+                        camera_bind_group_layout,
+                        &sprite_render_resources.texture_bind_group_layout,
+                        light_map_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("curve2d shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/curve2d.wgsl").into()),
+        };
+
+        // Curve triangles keep the winding of their control points, so don't cull.
+        let pipeline = create_render_pipeline(
+            render_server,
+            &pipeline_layout,
+            render_server.scene_format(),
+            &[Vertex2dCurve::desc()],
+            shader,
+            "curve2d pipeline",
+            true,
+            None,
+            DepthStencilConfig::transparent().with_stencil(clipped_stencil_state()),
+        );
+
+        let white = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, Rgba([255; 4])));
+
+        let white_texture = Texture::from_image(
+            &render_server.device,
+            &render_server.queue,
+            texture_cache,
+            &white,
+            Some("curve2d white texture"),
+        )
+        .unwrap();
+
+        Self {
+            pipeline,
+            white_texture,
+            vertex_buffer: None,
+            vertex_buffer_capacity: 0,
+            index_buffer: None,
+            index_buffer_capacity: 0,
+            draws: vec![],
+        }
+    }
+
+    pub(crate) fn draw_count(&self) -> usize {
+        self.draws
+            .iter()
+            .filter(|(indices, _)| !indices.is_empty())
+            .count()
+    }
+}
+
+pub(crate) fn prepare_curves(
+    curves: &[ExtractedCurve2d],
+    render_resources: &mut Curve2dRenderResources,
+    sprite_render_resources: &mut SpriteRenderResources,
+    texture_cache: &TextureCache,
+    render_server: &RenderServer,
+) {
+    render_resources.draws.clear();
+
+    if curves.is_empty() {
+        return;
+    }
+
+    let mut all_vertices = vec![];
+    let mut all_indices = vec![];
+
+    for curve in curves {
+        let texture_id = curve.texture_id.unwrap_or(render_resources.white_texture);
+
+        sprite_render_resources.add_texture_bind_group(
+            &render_server.device,
+            texture_cache,
+            texture_id,
+        );
+
+        let base_vertex = all_vertices.len() as u32;
+        let start = all_indices.len() as u32;
+
+        all_vertices.extend_from_slice(&curve.vertices);
+        all_indices.extend(curve.indices.iter().map(|i| i + base_vertex));
+
+        render_resources
+            .draws
+            .push((start..all_indices.len() as u32, texture_id));
+    }
+
+    if all_indices.is_empty() {
+        return;
+    }
+
+    // Reallocate the vertex buffer.
+    if render_resources.vertex_buffer_capacity < all_vertices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("curve2d vertex buffer"),
+            size: mem::size_of_val(all_vertices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.vertex_buffer_capacity = all_vertices.len();
+        render_resources.vertex_buffer = Some(buffer);
+    }
+
+    // Reallocate the index buffer.
+    if render_resources.index_buffer_capacity < all_indices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("curve2d index buffer"),
+            size: mem::size_of_val(all_indices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.index_buffer_capacity = all_indices.len();
+        render_resources.index_buffer = Some(buffer);
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.vertex_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&all_vertices),
+    );
+
+    render_server.queue.write_buffer(
+        render_resources.index_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&all_indices),
+    );
+}
+
+/// Draw the curves in `range`.
+pub(crate) fn render_curves<'a, 'b: 'a>(
+    range: Range<usize>,
+    render_resources: &'b Curve2dRenderResources,
+    sprite_render_resources: &'b SpriteRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+    light_map_bind_group: &'b wgpu::BindGroup,
+) {
+    let draws = &render_resources.draws[range];
+
+    if draws.iter().all(|(indices, _)| indices.is_empty()) {
+        return;
+    }
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+
+    render_pass.set_vertex_buffer(
+        0,
+        render_resources.vertex_buffer.as_ref().unwrap().slice(..),
+    );
+
+    render_pass.set_index_buffer(
+        render_resources.index_buffer.as_ref().unwrap().slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+    render_pass.set_bind_group(2, light_map_bind_group, &[]);
+
+    for (indices, texture_id) in draws {
+        if indices.is_empty() {
+            continue;
+        }
+
+        render_pass.set_bind_group(
+            1,
+            sprite_render_resources.get_texture_bind_group(*texture_id),
+            &[],
+        );
+
+        render_pass.draw_indexed(indices.clone(), 0, 0..1);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::math::alignup_u32;
+use crate::math::transform::Transform3d;
+use crate::render::gizmo::GizmoVertex;
+use crate::render::vertex::{Vertex3d, VertexBuffer};
+use crate::render::{MeshCache, MeshId, RenderServer, UniformBuffer};
+use cgmath::{Matrix, Matrix4, SquareMatrix};
+use std::mem;
+use wgpu::{BufferAddress, DynamicOffset};
+
+/// Normal (and optionally tangent) lines of a mesh.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ExtractedMeshNormals {
+    pub(crate) transform: Transform3d,
+    pub(crate) mesh_id: MeshId,
+    pub(crate) line_length: f32,
+    pub(crate) show_tangents: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DebugMeshUniform {
+    model: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    line_length: f32,
+    show_tangents: u32,
+    _pad: [f32; 2],
+}
+
+/// Stride between mesh params in the uniform buffer.
+fn mesh_offset_unit() -> u32 {
+    let offset_limit = wgpu::Limits::downlevel_defaults().min_uniform_buffer_offset_alignment;
+    alignup_u32(mem::size_of::<DebugMeshUniform>() as u32, offset_limit) * offset_limit
+}
+
+pub(crate) struct DebugDrawRenderResources {
+    line_pipeline: wgpu::RenderPipeline,
+    line_vertex_buffer: Option<wgpu::Buffer>,
+    line_vertex_buffer_capacity: usize,
+    line_vertex_count: u32,
+
+    normals_pipeline: wgpu::RenderPipeline,
+    mesh_bind_group_layout: wgpu::BindGroupLayout,
+    mesh_buffer: Option<UniformBuffer>,
+    mesh_buffer_capacity: usize,
+    mesh_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl DebugDrawRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let mesh_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("debug mesh bind group layout"),
+            });
+
+        let line_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("debug line pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let normals_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("debug normals pipeline layout"),
+                bind_group_layouts: &[camera_bind_group_layout, &mesh_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let line_pipeline = Self::create_pipeline(
+            render_server,
+            &line_pipeline_layout,
+            "vs_main_line",
+            GizmoVertex::desc(),
+            "debug line pipeline",
+        );
+
+        // Mesh vertices are read per instance, then each one is expanded into lines.
+        let normals_pipeline = Self::create_pipeline(
+            render_server,
+            &normals_pipeline_layout,
+            "vs_main_normals",
+            wgpu::VertexBufferLayout {
+                step_mode: wgpu::VertexStepMode::Instance,
+                ..Vertex3d::desc()
+            },
+            "debug normals pipeline",
+        );
+
+        Self {
+            line_pipeline,
+            line_vertex_buffer: None,
+            line_vertex_buffer_capacity: 0,
+            line_vertex_count: 0,
+            normals_pipeline,
+            mesh_bind_group_layout,
+            mesh_buffer: None,
+            mesh_buffer_capacity: 0,
+            mesh_bind_group: None,
+        }
+    }
+
+    fn create_pipeline(
+        render_server: &RenderServer,
+        layout: &wgpu::PipelineLayout,
+        vs_entry: &str,
+        vertex_buffer: wgpu::VertexBufferLayout,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        let device = &render_server.device;
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("debug draw shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/debug_draw.wgsl").into()),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: vs_entry,
+                buffers: &[vertex_buffer],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                depth_write_enabled: false,
+                depth_compare: render_server.depth_compare(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+}
+
+pub(crate) fn prepare_debug_draw(
+    lines: &[GizmoVertex],
+    normals: &[ExtractedMeshNormals],
+    render_resources: &mut DebugDrawRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.line_vertex_count = lines.len() as u32;
+
+    if !lines.is_empty() {
+        // Reallocate the vertex buffer.
+        if render_resources.line_vertex_buffer_capacity < lines.len() {
+            let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("debug line vertex buffer"),
+                size: mem::size_of_val(lines) as BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            render_resources.line_vertex_buffer_capacity = lines.len();
+            render_resources.line_vertex_buffer = Some(buffer);
+        }
+
+        render_server.queue.write_buffer(
+            render_resources.line_vertex_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(lines),
+        );
+    }
+
+    if normals.is_empty() {
+        return;
+    }
+
+    let offset = mesh_offset_unit() as usize;
+
+    // Reallocate the uniform buffer.
+    if render_resources.mesh_buffer_capacity < normals.len() {
+        let buffer = render_server.create_uniform_buffer(
+            "debug mesh uniform buffer",
+            (offset * normals.len()) as BufferAddress,
+        );
+
+        let bind_group = render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &render_resources.mesh_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(mem::size_of::<DebugMeshUniform>() as u64),
+                    }),
+                }],
+                label: Some("debug mesh bind group"),
+            });
+
+        render_resources.mesh_buffer_capacity = normals.len();
+        render_resources.mesh_buffer = Some(buffer);
+        render_resources.mesh_bind_group = Some(bind_group);
+    }
+
+    for (i, extracted) in normals.iter().enumerate() {
+        let model = Matrix4::from(extracted.transform.to_matrix());
+        let normal_matrix = model.invert().unwrap_or(Matrix4::identity()).transpose();
+
+        let uniform = DebugMeshUniform {
+            model: model.into(),
+            normal_matrix: normal_matrix.into(),
+            line_length: extracted.line_length,
+            show_tangents: extracted.show_tangents as u32,
+            _pad: [0.0; 2],
+        };
+
+        render_server.queue.write_buffer(
+            render_resources.mesh_buffer.as_ref().unwrap(),
+            (i * offset) as BufferAddress,
+            bytemuck::cast_slice(&[uniform]),
+        );
+    }
+}
+
+pub(crate) fn render_debug_draw<'a, 'b: 'a>(
+    normals: &'b [ExtractedMeshNormals],
+    mesh_cache: &'b MeshCache,
+    render_resources: &'b DebugDrawRenderResources,
+    camera_bind_group: &'b wgpu::BindGroup,
+    render_pass: &mut wgpu::RenderPass<'a>,
+) {
+    if render_resources.line_vertex_count == 0 && normals.is_empty() {
+        return;
+    }
+
+    // Set camera group. Offset 0 is the first camera, which all 3D passes draw with for now.
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+
+    if !normals.is_empty() {
+        render_pass.set_pipeline(&render_resources.normals_pipeline);
+
+        for (i, extracted) in normals.iter().enumerate() {
+            let Some(mesh) = mesh_cache.get(extracted.mesh_id) else {
+                continue;
+            };
+
+            render_pass.set_bind_group(
+                1,
+                render_resources.mesh_bind_group.as_ref().unwrap(),
+                &[i as DynamicOffset * mesh_offset_unit()],
+            );
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+
+            // Two vertices per line.
+            let vertex_count = if extracted.show_tangents { 6 } else { 2 };
+            render_pass.draw(0..vertex_count, 0..mesh.positions.len() as u32);
+        }
+    }
+
+    if render_resources.line_vertex_count > 0 {
+        render_pass.set_pipeline(&render_resources.line_pipeline);
+        render_pass.set_vertex_buffer(
+            0,
+            render_resources
+                .line_vertex_buffer
+                .as_ref()
+                .unwrap()
+                .slice(..),
+        );
+        render_pass.draw(0..render_resources.line_vertex_count, 0..1);
+    }
+}
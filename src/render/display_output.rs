@@ -0,0 +1,219 @@
+use crate::render::frame_graph::TransientTargets;
+use crate::render::post_process::{
+    begin_fullscreen_pass, create_fullscreen_pipeline_with_target, PostProcessSettings,
+};
+use crate::render::{DisplayOutput, RenderServer, TextureCache, TextureId, UniformBuffer};
+use std::mem;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+const ENCODING_HDR10: u32 = 0;
+const ENCODING_SCRGB: u32 = 1;
+
+/// Name of the output texture in the frame graph.
+pub(crate) const DISPLAY_OUTPUT_TARGET: &str = "display output";
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DisplayOutputParamsUniform {
+    paper_white: f32,
+    peak_brightness: f32,
+    encoding: u32,
+    _pad: f32,
+}
+
+/// With HDR display output, the final image is drawn linear into `output_texture`
+/// and encoded for the display by a last pass. See `RenderSettings::display_output`.
+pub(crate) struct DisplayOutputRenderResources {
+    /// Post processing and transitions draw here instead of to the surface. Transient.
+    pub(crate) output_texture: TextureId,
+
+    params_buffer: UniformBuffer,
+    params_bind_group: wgpu::BindGroup,
+
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group: Option<wgpu::BindGroup>,
+
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl DisplayOutputRenderResources {
+    /// None unless the display output is HDR.
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        transient_targets: &mut TransientTargets,
+    ) -> Option<Self> {
+        if !render_server.get_settings().display_output.is_hdr() {
+            return None;
+        }
+
+        let device = &render_server.device;
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("display output params bind group layout"),
+            });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("display output texture bind group layout"),
+            });
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "display output params buffer",
+            mem::size_of::<DisplayOutputParamsUniform>() as BufferAddress,
+        );
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("display output params bind group"),
+        });
+
+        let pipeline = {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("display output pipeline layout"),
+                bind_group_layouts: &[&params_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("display output shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/display_output.wgsl").into(),
+                ),
+            });
+
+            create_fullscreen_pipeline_with_target(
+                render_server,
+                &pipeline_layout,
+                &shader_module,
+                "fs_main",
+                render_server.surface_config.format,
+                wgpu::BlendState::REPLACE,
+                "display output pipeline",
+            )
+        };
+
+        let output_texture =
+            transient_targets.create(DISPLAY_OUTPUT_TARGET, render_server.output_format());
+
+        Some(Self {
+            output_texture,
+            params_buffer,
+            params_bind_group,
+            texture_bind_group_layout,
+            texture_bind_group: None,
+            pipeline,
+        })
+    }
+}
+
+fn create_texture_bind_group(
+    render_server: &RenderServer,
+    texture_cache: &TextureCache,
+    layout: &wgpu::BindGroupLayout,
+    texture_id: TextureId,
+) -> wgpu::BindGroup {
+    let texture = texture_cache.get(texture_id).unwrap();
+
+    render_server
+        .device
+        .create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("display output texture bind group"),
+        })
+}
+
+pub(crate) fn prepare_display_output(
+    settings: &PostProcessSettings,
+    render_resources: &mut DisplayOutputRenderResources,
+    render_server: &RenderServer,
+    texture_cache: &TextureCache,
+) {
+    let encoding = match render_server.get_settings().display_output {
+        DisplayOutput::ScRgb => ENCODING_SCRGB,
+        _ => ENCODING_HDR10,
+    };
+
+    let peak_brightness = settings.peak_brightness.max(1.0);
+
+    let params = DisplayOutputParamsUniform {
+        paper_white: settings.paper_white.clamp(1.0, peak_brightness),
+        peak_brightness,
+        encoding,
+        _pad: 0.0,
+    };
+
+    render_server.queue.write_buffer(
+        &render_resources.params_buffer,
+        0,
+        bytemuck::cast_slice(&[params]),
+    );
+
+    // The texture backing the output changes with the surface size and the frame graph.
+    render_resources.texture_bind_group = Some(create_texture_bind_group(
+        render_server,
+        texture_cache,
+        &render_resources.texture_bind_group_layout,
+        render_resources.output_texture,
+    ));
+}
+
+/// Encode the output texture into `target_view`, the surface.
+pub(crate) fn render_display_output(
+    render_resources: &DisplayOutputRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+) {
+    let mut render_pass = begin_fullscreen_pass(encoder, target_view, "display output pass");
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+    render_pass.set_bind_group(0, &render_resources.params_bind_group, &[]);
+    render_pass.set_bind_group(
+        1,
+        render_resources.texture_bind_group.as_ref().unwrap(),
+        &[],
+    );
+    render_pass.draw(0..3, 0..1);
+}
@@ -1,12 +1,242 @@
+use crate::core::singleton::Singletons;
+use crate::math::color::ColorU;
+use crate::math::transform::{Transform2d, Transform3d};
+use crate::physics::Aabb;
 use crate::render::atlas::ExtractedAtlas;
 use crate::render::camera::CameraUniform;
+use crate::render::clip::{ClipCommand, ClipOp, ExtractedClip};
+use crate::render::debug_draw::ExtractedMeshNormals;
+use crate::render::gizmo::GizmoVertex;
+use crate::render::material::MaterialId;
 use crate::render::render_world::Extracted;
 use crate::render::sprite::ExtractedSprite2d;
+use crate::render::ui_shape::{ExtractedUiShape, UiShape, UiShapeKind};
 use crate::render::view::ViewInfo;
-use crate::render::ExtractedMesh;
+use crate::render::{ExtractedMesh, MeshId, TextureId};
+use cgmath::{InnerSpace, Matrix4, Vector2, Vector3, Vector4};
+
+/// Draws things kept outside the scene tree, e.g. entities in an external ECS world.
+///
+/// Nodes like cameras and labels can also be owned by a source and drawn through their
+/// `AsNode` functions, without being added to the world.
+pub trait DrawSource {
+    /// Called every frame after the scene tree has been updated.
+    fn update(&mut self, _dt: f32, _singletons: &mut Singletons) {}
+
+    /// Called every frame after the scene tree has been drawn.
+    fn draw(&self, draw_cmds: &mut DrawCommands);
+}
 
 #[derive(Default)]
 pub struct DrawCommands {
     pub(crate) view_info: ViewInfo,
     pub(crate) extracted: Extracted,
+
+    /// Indices of the clips currently pushed.
+    clip_stack: Vec<usize>,
+}
+
+impl DrawCommands {
+    /// Clip everything drawn after this until the matching pop.
+    pub(crate) fn push_clip(&mut self, clip: ExtractedClip) {
+        let index = self.extracted.clips.len();
+        self.extracted.clips.push(clip);
+        self.clip_stack.push(index);
+        self.add_clip_command(ClipOp::Push, index);
+    }
+
+    pub(crate) fn pop_clip(&mut self) {
+        if let Some(index) = self.clip_stack.pop() {
+            self.add_clip_command(ClipOp::Pop, index);
+        }
+    }
+
+    /// Number of clips currently pushed.
+    pub(crate) fn get_clip_depth(&self) -> usize {
+        self.clip_stack.len()
+    }
+
+    /// Add transform gizmo handle triangles, in world space.
+    pub(crate) fn draw_gizmo_triangles(&mut self, vertices: &[GizmoVertex]) {
+        self.extracted.gizmo_vertices.extend_from_slice(vertices);
+    }
+
+    /// Debug line helper, in world space. Depth tested against the 3D scene.
+    pub fn draw_line_3d(&mut self, from: Vector3<f32>, to: Vector3<f32>, color: ColorU) {
+        let color = color.to_f32().to_array();
+
+        self.extracted.debug_lines.push(GizmoVertex {
+            position: from.into(),
+            color,
+        });
+        self.extracted.debug_lines.push(GizmoVertex {
+            position: to.into(),
+            color,
+        });
+    }
+
+    /// Debug line helper. Draws the edges of a local space box placed by `transform`.
+    pub fn draw_aabb(&mut self, aabb: &Aabb, transform: &Transform3d, color: ColorU) {
+        let matrix = Matrix4::from(transform.to_matrix());
+
+        // Bit 0, 1 and 2 of the index pick the max corner on x, y and z.
+        let corners: Vec<Vector3<f32>> = (0..8)
+            .map(|i| {
+                let corner = Vector3::new(
+                    if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                    if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                    if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+                );
+                (matrix * corner.extend(1.0)).truncate()
+            })
+            .collect();
+
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                // Connect each corner to the neighbours that differ in one bit, once.
+                if i & bit == 0 {
+                    self.draw_line_3d(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    /// Draw a sprite, in world space. `region` is the normalized region of the texture
+    /// (x, y, width, height). The texture size is used if `size` is `None`.
+    pub fn draw_sprite(
+        &mut self,
+        texture_id: TextureId,
+        region: Vector4<f32>,
+        transform: Transform2d,
+        size: Option<Vector2<f32>>,
+        centered: bool,
+    ) {
+        self.extracted.sprites.push(ExtractedSprite2d {
+            transform,
+            size: size.map(|s| s.into()),
+            texture_id,
+            region,
+            centered,
+            flip_x: false,
+            flip_y: false,
+        });
+    }
+
+    /// Draw a cached mesh, e.g. one from a loaded [`Model`].
+    ///
+    /// [`Model`]: crate::scene::Model
+    pub fn draw_mesh(
+        &mut self,
+        mesh_id: MeshId,
+        material_id: Option<MaterialId>,
+        transform: Transform3d,
+    ) {
+        self.extracted.meshes.push(ExtractedMesh {
+            transform,
+            mesh_id,
+            material_id,
+        });
+    }
+
+    /// Draw per-vertex normal (and tangent) lines of a mesh.
+    pub(crate) fn draw_mesh_normals(&mut self, normals: ExtractedMeshNormals) {
+        self.extracted.mesh_normals.push(normals);
+    }
+
+    /// Draw an anti-aliased shape, in view (pixel) space.
+    pub fn draw_ui_shape(&mut self, shape: &UiShape, transform: Transform2d, size: Vector2<f32>) {
+        self.extracted.ui_shapes.push(ExtractedUiShape {
+            transform,
+            size,
+            kind: shape.kind,
+            corner_radii: shape.corner_radii.into(),
+            color: shape.color.to_f32().to_vec4(),
+            border_width: shape.border_width,
+            border_color: shape.border_color.to_f32().to_vec4(),
+            backdrop_blur: shape.backdrop_blur,
+            view_size: self.view_info.view_size,
+        });
+    }
+
+    /// Debug shape helper.
+    pub fn draw_rect(
+        &mut self,
+        position: Vector2<f32>,
+        size: Vector2<f32>,
+        corner_radius: f32,
+        color: ColorU,
+    ) {
+        let shape = UiShape {
+            corner_radii: [corner_radius; 4],
+            color,
+            ..Default::default()
+        };
+
+        let mut transform = Transform2d::default();
+        transform.position = position;
+
+        self.draw_ui_shape(&shape, transform, size);
+    }
+
+    /// Debug shape helper.
+    pub fn draw_circle(&mut self, center: Vector2<f32>, radius: f32, color: ColorU) {
+        let shape = UiShape {
+            kind: UiShapeKind::Circle,
+            color,
+            ..Default::default()
+        };
+
+        let mut transform = Transform2d::default();
+        transform.position = center - Vector2::new(radius, radius);
+
+        self.draw_ui_shape(&shape, transform, Vector2::new(radius, radius) * 2.0);
+    }
+
+    /// Debug shape helper. Draws a line with round caps from `from` to `to`.
+    pub fn draw_capsule(
+        &mut self,
+        from: Vector2<f32>,
+        to: Vector2<f32>,
+        radius: f32,
+        color: ColorU,
+    ) {
+        let shape = UiShape {
+            kind: UiShapeKind::Capsule,
+            color,
+            ..Default::default()
+        };
+
+        let delta = to - from;
+        let length = delta.magnitude();
+        let direction = if length > 0.0 {
+            delta / length
+        } else {
+            Vector2::new(1.0, 0.0)
+        };
+        let normal = Vector2::new(-direction.y, direction.x);
+
+        // The shape rotates around its top-left corner.
+        let mut transform = Transform2d::default();
+        transform.position = from - (direction + normal) * radius;
+        transform.rotation = direction.y.atan2(direction.x);
+
+        self.draw_ui_shape(
+            &shape,
+            transform,
+            Vector2::new(length + radius * 2.0, radius * 2.0),
+        );
+    }
+
+    fn add_clip_command(&mut self, op: ClipOp, clip: usize) {
+        let command = ClipCommand {
+            op,
+            clip,
+            sprite_index: self.extracted.sprites.len(),
+            atlas_index: self.extracted.atlases.len(),
+            ui_shape_index: self.extracted.ui_shapes.len(),
+            line_index: self.extracted.lines_2d.len(),
+            curve_index: self.extracted.curves_2d.len(),
+        };
+        self.extracted.clip_commands.push(command);
+    }
 }
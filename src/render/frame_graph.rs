@@ -0,0 +1,268 @@
+use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use std::collections::HashMap;
+
+/// What a frame is finally drawn to.
+pub(crate) const SURFACE: &str = "surface";
+
+/// A pass and the targets it uses. Reads are sampled or copied from, writes are
+/// drawn to, whether cleared or loaded.
+#[derive(Debug, Clone, PartialEq)]
+struct PassNode {
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+/// The passes of a frame in the order they are encoded, with the targets they read
+/// and write. Built by `RenderWorld` when preparing a frame, it decides which transient
+/// targets can share a texture and is logged when `RenderWorld::validate_frame_graph`
+/// is set. Resource barriers between the passes are left to wgpu.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct FrameGraph {
+    passes: Vec<PassNode>,
+}
+
+impl FrameGraph {
+    pub(crate) fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &[&'static str],
+        writes: &[&'static str],
+    ) {
+        self.passes.push(PassNode {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+    }
+
+    /// First and last pass using each target.
+    fn lifetimes(&self) -> HashMap<&'static str, (usize, usize)> {
+        let mut lifetimes = HashMap::new();
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            for target in pass.reads.iter().chain(&pass.writes) {
+                lifetimes
+                    .entry(*target)
+                    .and_modify(|(_, last)| *last = i)
+                    .or_insert((i, i));
+            }
+        }
+
+        lifetimes
+    }
+
+    /// The passes that wrote what a pass reads, last writer first.
+    fn dependencies(&self, pass: usize) -> Vec<&'static str> {
+        let mut dependencies = vec![];
+
+        for target in &self.passes[pass].reads {
+            let writer = self.passes[..pass]
+                .iter()
+                .rev()
+                .find(|p| p.writes.contains(target));
+
+            if let Some(writer) = writer {
+                if !dependencies.contains(&writer.name) {
+                    dependencies.push(writer.name);
+                }
+            }
+        }
+
+        dependencies
+    }
+
+    /// Log the passes, what they depend on and how long targets live, and warn about
+    /// uses that would go wrong with shared textures.
+    pub(crate) fn validate(&self, transient_targets: &TransientTargets) {
+        log::info!("Frame graph with {} passes:", self.passes.len());
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            log::info!(
+                "  {} {}: reads {:?}, writes {:?}, after {:?}",
+                i,
+                pass.name,
+                pass.reads,
+                pass.writes,
+                self.dependencies(i)
+            );
+
+            for target in &pass.reads {
+                if pass.writes.contains(target) {
+                    log::warn!("Pass {} reads and writes {}", pass.name, target);
+                }
+            }
+        }
+
+        let mut lifetimes: Vec<(&str, (usize, usize))> = self.lifetimes().into_iter().collect();
+        lifetimes.sort_by_key(|(target, lifetime)| (*lifetime, *target));
+
+        for (target, (first, last)) in lifetimes {
+            match transient_targets.shared_with(target) {
+                Some(shared) if !shared.is_empty() => log::info!(
+                    "  {}: passes {}..={}, texture shared with {:?}",
+                    target,
+                    first,
+                    last,
+                    shared
+                ),
+                _ => log::info!("  {}: passes {}..={}", target, first, last),
+            }
+
+            // Whatever a transient target held before belongs to the targets it shares with.
+            if transient_targets.contains(target) {
+                if !self.passes[first].writes.contains(&target)
+                    || self.passes[first].reads.contains(&target)
+                {
+                    log::warn!("Transient target {} is read before it's written", target);
+                }
+
+                if !self.passes[first + 1..=last]
+                    .iter()
+                    .any(|p| p.reads.contains(&target))
+                {
+                    log::warn!("Transient target {} is written but never read", target);
+                }
+            }
+        }
+    }
+}
+
+struct TransientTarget {
+    name: &'static str,
+    id: TextureId,
+    format: wgpu::TextureFormat,
+    /// Index into `TransientTargets::textures`.
+    texture: Option<usize>,
+}
+
+/// Offscreen targets at the surface size that only live within a frame. Targets whose
+/// passes don't overlap in the frame graph share a texture, and each ID is an alias in
+/// the texture cache to the texture backing it. Bind groups using them have to be made
+/// after `assign`.
+#[derive(Default)]
+pub(crate) struct TransientTargets {
+    targets: Vec<TransientTarget>,
+    /// In the texture cache.
+    textures: Vec<(wgpu::TextureFormat, TextureId)>,
+    size: (u32, u32),
+}
+
+impl TransientTargets {
+    /// The name is how passes refer to the target in the frame graph.
+    pub(crate) fn create(&mut self, name: &'static str, format: wgpu::TextureFormat) -> TextureId {
+        let id = TextureId::new();
+
+        self.targets.push(TransientTarget {
+            name,
+            id,
+            format,
+            texture: None,
+        });
+
+        id
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.targets.iter().any(|t| t.name == name)
+    }
+
+    /// Other targets backed by the same texture. None if it's not a transient target.
+    fn shared_with(&self, name: &str) -> Option<Vec<&'static str>> {
+        let texture = self.targets.iter().find(|t| t.name == name)?.texture;
+
+        Some(
+            self.targets
+                .iter()
+                .filter(|t| t.name != name && t.texture == texture)
+                .map(|t| t.name)
+                .collect(),
+        )
+    }
+
+    /// Back the targets with as few textures as the frame graph allows, creating and
+    /// dropping textures if that changed or the surface was resized.
+    pub(crate) fn assign(
+        &mut self,
+        graph: &FrameGraph,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) {
+        let lifetimes = graph.lifetimes();
+
+        // Targets not used this frame take any texture of their format.
+        let mut order: Vec<usize> = (0..self.targets.len()).collect();
+        order.sort_by_key(|i| {
+            lifetimes
+                .get(self.targets[*i].name)
+                .map_or((1, 0), |(first, _)| (0, *first))
+        });
+
+        let mut textures: Vec<(wgpu::TextureFormat, Vec<(usize, usize)>)> = vec![];
+        let mut assigned = vec![0; self.targets.len()];
+
+        for i in order {
+            let target = &self.targets[i];
+            let lifetime = lifetimes.get(target.name).copied();
+
+            let free = textures.iter().position(|(format, used)| {
+                *format == target.format
+                    && lifetime.is_none_or(|(first, last)| {
+                        used.iter().all(|(f, l)| last < *f || first > *l)
+                    })
+            });
+
+            let texture = free.unwrap_or_else(|| {
+                textures.push((target.format, vec![]));
+                textures.len() - 1
+            });
+
+            if let Some(lifetime) = lifetime {
+                textures[texture].1.push(lifetime);
+            }
+            assigned[i] = texture;
+        }
+
+        let config = &render_server.surface_config;
+        let size = (config.width, config.height);
+
+        let unchanged = size == self.size
+            && textures.len() == self.textures.len()
+            && textures
+                .iter()
+                .zip(&self.textures)
+                .all(|((a, _), (b, _))| a == b)
+            && self
+                .targets
+                .iter()
+                .zip(&assigned)
+                .all(|(t, a)| t.texture == Some(*a));
+        if unchanged {
+            return;
+        }
+
+        for (_, id) in self.textures.drain(..) {
+            texture_cache.remove(id);
+        }
+
+        for (format, _) in textures {
+            let mut config = config.clone();
+            config.format = format;
+
+            let id = Texture::create_render_texture(
+                &render_server.device,
+                texture_cache,
+                &config,
+                Some("transient texture"),
+            );
+            self.textures.push((format, id));
+        }
+
+        for (target, texture) in self.targets.iter_mut().zip(assigned) {
+            target.texture = Some(texture);
+            texture_cache.alias(target.id, self.textures[texture].1);
+        }
+
+        self.size = size;
+    }
+}
@@ -0,0 +1,358 @@
+use crate::render::RenderServer;
+use anyhow::{bail, Context, Result};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use winit::keyboard::KeyCode;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RecordingFormat {
+    /// Encoded by ffmpeg, which has to be on the PATH.
+    Mp4,
+    Gif,
+}
+
+impl RecordingFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Mp4 => "mp4",
+            RecordingFormat::Gif => "gif",
+        }
+    }
+}
+
+/// Frames waiting for the encoder. Frames are dropped while it's full, since encoding
+/// can be much slower than rendering, e.g. for big GIFs.
+const MAX_QUEUED_FRAMES: usize = 8;
+
+/// A frame for the encoder thread, as tightly packed RGBA8.
+struct RecordedFrame {
+    pixels: Vec<u8>,
+    /// Times to write the frame, to keep up with the framerate.
+    count: u64,
+}
+
+struct Recording {
+    size: (u32, u32),
+    sender: mpsc::SyncSender<RecordedFrame>,
+    /// Seconds since the recording started.
+    time: f32,
+    frames: u64,
+    readback_buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+    /// Times the frame being copied should be written, to keep up with the framerate.
+    pending: u64,
+    /// Frames dropped because the encoder fell behind.
+    dropped: u64,
+}
+
+/// Records the presented frames to a video, for making demo clips.
+///
+/// The output runs at a fixed framerate: frames are dropped or repeated to match it.
+/// Reading frames back stalls the GPU, so expect a lower framerate while recording.
+pub struct FrameRecorder {
+    pub format: RecordingFormat,
+    pub fps: u32,
+    /// Toggles recording.
+    pub hotkey: Option<KeyCode>,
+    /// Recordings started with the hotkey are saved here, named by time.
+    pub output_dir: PathBuf,
+
+    recording: Option<Recording>,
+    /// The surface must be copyable, which isn't the case on all platforms.
+    pub(crate) supported: bool,
+}
+
+impl Default for FrameRecorder {
+    fn default() -> Self {
+        Self {
+            format: RecordingFormat::Mp4,
+            fps: 30,
+            hotkey: Some(KeyCode::F9),
+            output_dir: PathBuf::from("recordings"),
+            recording: None,
+            supported: false,
+        }
+    }
+}
+
+impl FrameRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Start recording to a file named by the current time in the output directory.
+    pub fn start(&mut self, render_server: &RenderServer) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.output_dir)?;
+
+        let name = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let path = self
+            .output_dir
+            .join(name)
+            .with_extension(self.format.extension());
+
+        self.start_to(&path, render_server)?;
+
+        Ok(path)
+    }
+
+    /// Start recording to a file. Stops the current recording.
+    pub fn start_to(&mut self, path: &Path, render_server: &RenderServer) -> Result<()> {
+        self.stop();
+
+        if !self.supported {
+            bail!("Recording isn't supported by this surface");
+        }
+
+        let config = &render_server.surface_config;
+        match config.format {
+            wgpu::TextureFormat::Rgba8Unorm
+            | wgpu::TextureFormat::Rgba8UnormSrgb
+            | wgpu::TextureFormat::Bgra8Unorm
+            | wgpu::TextureFormat::Bgra8UnormSrgb => {}
+            format => bail!("Can't record surface format {:?}", format),
+        }
+
+        let size = (config.width, config.height);
+        let fps = self.fps.max(1);
+
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_FRAMES);
+
+        match self.format {
+            RecordingFormat::Mp4 => spawn_ffmpeg_writer(path, size, fps, receiver)?,
+            RecordingFormat::Gif => spawn_gif_writer(path, size, fps, receiver)?,
+        }
+
+        let padded_bytes_per_row =
+            (size.0 * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let readback_buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame recorder readback buffer"),
+            size: (padded_bytes_per_row * size.1) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        self.recording = Some(Recording {
+            size,
+            sender,
+            time: 0.0,
+            frames: 0,
+            readback_buffer,
+            padded_bytes_per_row,
+            pending: 0,
+            dropped: 0,
+        });
+
+        log::info!("Started recording to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Stop recording. The file is finished in the background.
+    pub fn stop(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            log::info!(
+                "Stopped recording after {} frames, {} dropped",
+                recording.frames,
+                recording.dropped
+            );
+        }
+    }
+
+    pub(crate) fn on_key_pressed(&mut self, key: KeyCode, render_server: &RenderServer) {
+        if self.hotkey != Some(key) {
+            return;
+        }
+
+        if self.is_recording() {
+            self.stop();
+        } else if let Err(e) = self.start(render_server) {
+            log::error!("Failed to start recording: {}", e);
+        }
+    }
+
+    pub(crate) fn update(&mut self, dt: f32) {
+        if let Some(recording) = &mut self.recording {
+            recording.time += dt;
+        }
+    }
+
+    /// Copy the frame if one is due. Call after everything has been drawn to the surface texture.
+    pub(crate) fn capture(&mut self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        if (texture.width(), texture.height()) != recording.size {
+            log::warn!("Window resized, stopping recording.");
+            self.stop();
+            return;
+        }
+
+        // The first frame is at zero.
+        let due = (recording.time * self.fps.max(1) as f32) as u64 + 1;
+        recording.pending = due.saturating_sub(recording.frames);
+
+        if recording.pending == 0 {
+            return;
+        }
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &recording.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(recording.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+    }
+
+    /// Read the copied frame back and send it to the encoder. Call after submitting.
+    pub(crate) fn finish_frame(&mut self, render_server: &RenderServer) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        if recording.pending == 0 {
+            return;
+        }
+
+        let slice = recording.readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        render_server.device.poll(wgpu::Maintain::Wait);
+
+        let (width, height) = recording.size;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(recording.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..(width * 4) as usize]);
+            }
+        }
+        recording.readback_buffer.unmap();
+
+        if matches!(
+            render_server.surface_config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        // Repeat the frame if we're running below the recording framerate.
+        let frame = RecordedFrame {
+            pixels,
+            count: recording.pending,
+        };
+
+        match recording.sender.try_send(frame) {
+            Ok(()) => {
+                recording.frames += recording.pending;
+            }
+            // The next frame sent is repeated to make up for this one.
+            Err(mpsc::TrySendError::Full(_)) => {
+                if recording.dropped == 0 {
+                    log::warn!("Recording encoder can't keep up, dropping frames.");
+                }
+                recording.dropped += 1;
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => {
+                log::error!("Recording encoder stopped, stopping recording.");
+                self.stop();
+                return;
+            }
+        }
+
+        recording.pending = 0;
+    }
+}
+
+fn spawn_ffmpeg_writer(
+    path: &Path,
+    size: (u32, u32),
+    fps: u32,
+    receiver: mpsc::Receiver<RecordedFrame>,
+) -> Result<()> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", size.0, size.1)])
+        .args(["-r", &fps.to_string()])
+        .args(["-i", "-"])
+        // Most players need even sizes with yuv420p.
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run ffmpeg, is it installed?")?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let path = path.to_owned();
+
+    std::thread::spawn(move || {
+        'frames: for frame in receiver {
+            for _ in 0..frame.count {
+                if let Err(e) = stdin.write_all(&frame.pixels) {
+                    log::error!("Failed to write frame to ffmpeg: {}", e);
+                    break 'frames;
+                }
+            }
+        }
+
+        // Closing stdin lets ffmpeg finish the file.
+        drop(stdin);
+
+        match child.wait() {
+            Ok(status) if status.success() => log::info!("Saved recording to {}", path.display()),
+            _ => log::error!("ffmpeg failed to encode {}", path.display()),
+        }
+    });
+
+    Ok(())
+}
+
+fn spawn_gif_writer(
+    path: &Path,
+    size: (u32, u32),
+    fps: u32,
+    receiver: mpsc::Receiver<RecordedFrame>,
+) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let path = path.to_owned();
+
+    std::thread::spawn(move || {
+        // Faster and lower quality than the default speed of 1.
+        let mut encoder = GifEncoder::new_with_speed(file, 10);
+        let _ = encoder.set_repeat(Repeat::Infinite);
+
+        for frame in receiver {
+            let image = RgbaImage::from_raw(size.0, size.1, frame.pixels).unwrap();
+            // A repeated frame is shown longer.
+            let delay = Delay::from_numer_denom_ms(1000 * frame.count as u32, fps);
+
+            if let Err(e) = encoder.encode_frame(Frame::from_parts(image, 0, 0, delay)) {
+                log::error!("Failed to encode GIF frame: {}", e);
+                return;
+            }
+        }
+
+        log::info!("Saved recording to {}", path.display());
+    });
+
+    Ok(())
+}
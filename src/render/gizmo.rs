@@ -1,15 +1,115 @@
+use crate::math::color::ColorU;
 use crate::render::material::{MaterialCache, MaterialId};
-use crate::render::{InstanceMetadata, MeshId, RenderServer, Texture};
+use crate::render::vertex::VertexBuffer;
+use crate::render::{RenderServer, UniformBuffer};
+use cgmath::{Matrix4, SquareMatrix};
 use rustybuzz::ttf_parser::gpos::Device;
 use std::collections::HashMap;
-use wgpu::BindGroupLayout;
+use std::mem;
+use wgpu::BufferAddress;
 
 pub(crate) struct Gizmo {
     pub(crate) color: [f32; 3],
 }
 
+/// A vertex of a transform gizmo handle, in world space.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct GizmoVertex {
+    pub(crate) position: [f32; 3],
+    pub(crate) color: [f32; 4],
+}
+
+impl VertexBuffer for GizmoVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<GizmoVertex>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// Editor grid drawn under 3D cameras and, optionally, behind 2D content.
+#[derive(Debug, Clone)]
+pub struct GridSettings {
+    pub enabled_3d: bool,
+    /// Follows the 2D camera. Off by default as it covers the 3D scene.
+    pub enabled_2d: bool,
+    /// Distance between minor lines in world units.
+    pub minor_spacing: f32,
+    /// Distance between minor lines of the 2D grid in pixels.
+    pub minor_spacing_2d: f32,
+    /// Number of minor cells per major cell.
+    pub major_every: u32,
+    pub minor_color: ColorU,
+    pub major_color: ColorU,
+    pub x_axis_color: ColorU,
+    /// Also used for the Y axis in 2D.
+    pub z_axis_color: ColorU,
+    /// Fraction of the far plane distance where the 3D grid starts fading out.
+    pub fade_start: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled_3d: true,
+            enabled_2d: false,
+            minor_spacing: 1.0,
+            minor_spacing_2d: 32.0,
+            major_every: 10,
+            minor_color: ColorU::new(255, 255, 255, 26),
+            major_color: ColorU::new(255, 255, 255, 64),
+            x_axis_color: ColorU::new(230, 60, 60, 255),
+            z_axis_color: ColorU::new(60, 110, 230, 255),
+            fade_start: 0.25,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    x_axis_color: [f32; 4],
+    z_axis_color: [f32; 4],
+    inverse_view_2d: [[f32; 4]; 4],
+    minor_spacing: f32,
+    major_spacing: f32,
+    fade_start: f32,
+    /// 1.0 with a reverse-Z depth buffer.
+    reverse_z: f32,
+}
+
 pub(crate) struct GizmoRenderResources {
     pub(crate) pipeline: wgpu::RenderPipeline,
+    pipeline_2d: wgpu::RenderPipeline,
+
+    grid_buffer: UniformBuffer,
+    grid_bind_group: wgpu::BindGroup,
+    grid_2d_buffer: UniformBuffer,
+    grid_2d_bind_group: wgpu::BindGroup,
+    grid_3d_enabled: bool,
+    grid_2d_enabled: bool,
+
+    /// Draws transform gizmo handles on top of everything else.
+    handle_pipeline: wgpu::RenderPipeline,
+    handle_vertex_buffer: Option<wgpu::Buffer>,
+    handle_vertex_buffer_capacity: usize,
+    handle_vertex_count: u32,
 }
 
 impl GizmoRenderResources {
@@ -19,46 +119,88 @@ impl GizmoRenderResources {
     ) -> Self {
         let device = &render_server.device;
 
-        let pipeline = {
+        let grid_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("grid bind group layout"),
+            });
+
+        let (grid_buffer, grid_bind_group) =
+            Self::create_grid_uniform(render_server, &grid_bind_group_layout, "grid");
+        let (grid_2d_buffer, grid_2d_bind_group) =
+            Self::create_grid_uniform(render_server, &grid_bind_group_layout, "grid 2d");
+
+        let grid_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("gizmo pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &grid_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::create_grid_pipeline(
+            render_server,
+            &grid_pipeline_layout,
+            ("vs_main_grid", "fs_main_grid"),
+            wgpu::CompareFunction::Less,
+            "gizmo pipeline",
+        );
+
+        // The 2D grid sits behind 2D content, which doesn't use depth.
+        let pipeline_2d = Self::create_grid_pipeline(
+            render_server,
+            &grid_pipeline_layout,
+            ("vs_main_grid_2d", "fs_main_grid_2d"),
+            wgpu::CompareFunction::Always,
+            "gizmo 2d pipeline",
+        );
+
+        let handle_pipeline = {
             let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("gizmo pipeline layout"),
+                label: Some("gizmo handle pipeline layout"),
                 bind_group_layouts: &[camera_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("gizmo shader"),
+            let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("gizmo handle shader"),
                 source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/gizmo.wgsl").into()),
-            };
-            let shader_module = device.create_shader_module(shader);
+            });
 
             device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("gizmo pipeline"),
+                label: Some("gizmo handle pipeline"),
                 layout: Some(&pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &shader_module,
-                    entry_point: "vs_main_grid",
-                    buffers: &[],
+                    entry_point: "vs_main_handle",
+                    buffers: &[GizmoVertex::desc()],
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &shader_module,
-                    entry_point: "fs_main_grid",
+                    entry_point: "fs_main_handle",
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: render_server.surface_config.format,
-                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        format: render_server.scene_format(),
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
                 }),
                 primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleStrip, // Has to be triangle strip.
-                    front_face: wgpu::FrontFace::Cw,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
                     cull_mode: None,
                     ..Default::default()
                 },
+                // Handles should never be hidden by the object they edit.
                 depth_stencil: Some(wgpu::DepthStencilState {
-                    format: Texture::DEPTH_FORMAT,
+                    format: render_server.depth_format(),
                     depth_write_enabled: false,
-                    depth_compare: wgpu::CompareFunction::Less,
+                    depth_compare: wgpu::CompareFunction::Always,
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
                 }),
@@ -67,7 +209,189 @@ impl GizmoRenderResources {
             })
         };
 
-        Self { pipeline }
+        Self {
+            pipeline,
+            pipeline_2d,
+            grid_buffer,
+            grid_bind_group,
+            grid_2d_buffer,
+            grid_2d_bind_group,
+            handle_pipeline,
+            handle_vertex_buffer: None,
+            handle_vertex_buffer_capacity: 0,
+            handle_vertex_count: 0,
+            grid_3d_enabled: false,
+            grid_2d_enabled: false,
+        }
+    }
+
+    fn create_grid_uniform(
+        render_server: &RenderServer,
+        layout: &wgpu::BindGroupLayout,
+        label: &str,
+    ) -> (UniformBuffer, wgpu::BindGroup) {
+        let buffer = render_server.create_uniform_buffer(
+            &format!("{} uniform buffer", label),
+            mem::size_of::<GridUniform>() as BufferAddress,
+        );
+
+        let bind_group = render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some(&format!("{} bind group", label)),
+            });
+
+        (buffer, bind_group)
+    }
+
+    fn create_grid_pipeline(
+        render_server: &RenderServer,
+        layout: &wgpu::PipelineLayout,
+        (vs_entry, fs_entry): (&str, &str),
+        depth_compare: wgpu::CompareFunction,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        let device = &render_server.device;
+
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("gizmo shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/gizmo.wgsl").into()),
+        };
+        let shader_module = device.create_shader_module(shader);
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: vs_entry,
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: fs_entry,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip, // Has to be triangle strip.
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                depth_write_enabled: false,
+                depth_compare: render_server.depth_compare(depth_compare),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// `view_2d` is the view matrix of the 2D camera, if there is one.
+    pub(crate) fn prepare_grid(
+        &mut self,
+        render_server: &RenderServer,
+        settings: &GridSettings,
+        view_2d: Option<Matrix4<f32>>,
+    ) {
+        self.grid_3d_enabled = settings.enabled_3d;
+        self.grid_2d_enabled = settings.enabled_2d && view_2d.is_some();
+
+        let inverse_view_2d = view_2d
+            .and_then(|view| view.invert())
+            .unwrap_or(Matrix4::identity());
+
+        let major_every = settings.major_every.max(1) as f32;
+
+        let uniform = GridUniform {
+            minor_color: settings.minor_color.to_f32().to_array(),
+            major_color: settings.major_color.to_f32().to_array(),
+            x_axis_color: settings.x_axis_color.to_f32().to_array(),
+            z_axis_color: settings.z_axis_color.to_f32().to_array(),
+            inverse_view_2d: inverse_view_2d.into(),
+            minor_spacing: settings.minor_spacing,
+            major_spacing: settings.minor_spacing * major_every,
+            fade_start: settings.fade_start.clamp(0.0, 1.0),
+            reverse_z: if render_server.get_settings().reverse_z {
+                1.0
+            } else {
+                0.0
+            },
+        };
+
+        // The 2D grid is in pixels, so it has its own spacing.
+        let uniform_2d = GridUniform {
+            minor_spacing: settings.minor_spacing_2d,
+            major_spacing: settings.minor_spacing_2d * major_every,
+            ..uniform
+        };
+
+        render_server
+            .queue
+            .write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        render_server.queue.write_buffer(
+            &self.grid_2d_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform_2d]),
+        );
+    }
+
+    pub(crate) fn prepare_handles(
+        &mut self,
+        render_server: &RenderServer,
+        vertices: &[GizmoVertex],
+    ) {
+        self.handle_vertex_count = vertices.len() as u32;
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        // Reallocate the vertex buffer.
+        if self.handle_vertex_buffer_capacity < vertices.len() {
+            let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("gizmo handle vertex buffer"),
+                size: mem::size_of_val(vertices) as BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            self.handle_vertex_buffer_capacity = vertices.len();
+            self.handle_vertex_buffer = Some(buffer);
+        }
+
+        render_server.queue.write_buffer(
+            self.handle_vertex_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(vertices),
+        );
+    }
+
+    pub(crate) fn render_handles<'a, 'b: 'a>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'b wgpu::BindGroup,
+    ) {
+        if self.handle_vertex_count == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.handle_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[0]);
+        render_pass.set_vertex_buffer(0, self.handle_vertex_buffer.as_ref().unwrap().slice(..));
+        render_pass.draw(0..self.handle_vertex_count, 0..1);
     }
 
     pub(crate) fn render<'a, 'b: 'a>(
@@ -75,11 +399,33 @@ impl GizmoRenderResources {
         render_pass: &mut wgpu::RenderPass<'a>,
         camera_bind_group: &'b wgpu::BindGroup,
     ) {
+        if !self.grid_3d_enabled {
+            return;
+        }
+
         render_pass.set_pipeline(&self.pipeline);
 
-        // FIXME
-        // Set camera group.
+        // Set camera group. Offset 0 is the first camera, which all 3D passes draw with for now.
+        render_pass.set_bind_group(0, camera_bind_group, &[0]);
+        render_pass.set_bind_group(1, &self.grid_bind_group, &[]);
+
+        render_pass.draw(0..4, 0..1);
+    }
+
+    pub(crate) fn render_grid_2d<'a, 'b: 'a>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'b wgpu::BindGroup,
+    ) {
+        if !self.grid_2d_enabled {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline_2d);
+
+        // Unused by the 2D grid, but part of the shared pipeline layout.
         render_pass.set_bind_group(0, camera_bind_group, &[0]);
+        render_pass.set_bind_group(1, &self.grid_2d_bind_group, &[]);
 
         render_pass.draw(0..4, 0..1);
     }
@@ -0,0 +1,136 @@
+//! Values that are the same for every draw in a frame, so shaders don't need their own
+//! uniforms for them.
+//!
+//! Scene pipelines get them at group 0 binding 1, next to their camera, and fullscreen
+//! post passes at group 0 binding 0. Declare them in WGSL as:
+//!
+//! ```wgsl
+//! struct Camera {
+//!     view_pos: vec4<f32>,
+//!     view: mat4x4<f32>,
+//!     proj: mat4x4<f32>,
+//!     view_proj: mat4x4<f32>,
+//! }
+//!
+//! struct Globals {
+//!     camera: Camera,
+//!     inverse_view_proj: mat4x4<f32>,
+//!     viewport_size: vec2<f32>,
+//!     time: f32,
+//!     delta: f32,
+//! }
+//! ```
+
+use crate::render::camera::{CameraType, CameraUniform, ExtractedCameras};
+use crate::render::{RenderServer, UniformBuffer};
+use cgmath::{Matrix4, SquareMatrix};
+use std::mem;
+use wgpu::BufferAddress;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct GlobalsUniform {
+    /// The first 3D camera, or the 2D one if there is none.
+    camera: CameraUniform,
+    inverse_view_proj: [[f32; 4]; 4],
+    /// Size the 3D scene is drawn at in pixels, smaller than the surface with resolution scaling.
+    viewport_size: [f32; 2],
+    /// Game time in seconds, see `Engine::get_time`.
+    pub(crate) time: f32,
+    pub(crate) delta: f32,
+}
+
+impl Default for GlobalsUniform {
+    fn default() -> Self {
+        Self {
+            camera: CameraUniform::default(),
+            inverse_view_proj: Matrix4::identity().into(),
+            viewport_size: [0.0; 2],
+            time: 0.0,
+            delta: 0.0,
+        }
+    }
+}
+
+pub(crate) struct GlobalsRenderResources {
+    pub(crate) uniform: GlobalsUniform,
+    pub(crate) uniform_buffer: UniformBuffer,
+    /// For pipelines without a camera.
+    pub(crate) bind_group_layout: wgpu::BindGroupLayout,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl GlobalsRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let device = &render_server.device;
+
+        let uniform_buffer = render_server.create_uniform_buffer(
+            "globals uniform buffer",
+            mem::size_of::<GlobalsUniform>() as BufferAddress,
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[Self::layout_entry(0)],
+            label: Some("globals bind group layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("globals bind group"),
+        });
+
+        Self {
+            uniform: GlobalsUniform::default(),
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Also used by the camera bind group layout.
+    pub(crate) fn layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    pub(crate) fn prepare(
+        &mut self,
+        render_server: &RenderServer,
+        cameras: &ExtractedCameras,
+        viewport_size: (u32, u32),
+    ) {
+        let camera = cameras
+            .types
+            .iter()
+            .position(|t| *t == CameraType::D3)
+            .or_else(|| (!cameras.uniforms.is_empty()).then_some(0))
+            .map(|i| cameras.uniforms[i])
+            .unwrap_or_default();
+
+        let inverse_view_proj = Matrix4::from(camera.view_proj)
+            .invert()
+            .unwrap_or(Matrix4::identity());
+
+        self.uniform.camera = camera;
+        self.uniform.inverse_view_proj = inverse_view_proj.into();
+        self.uniform.viewport_size = [viewport_size.0 as f32, viewport_size.1 as f32];
+
+        render_server.queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::math::transform::Transform3d;
+use crate::render::atlas::{Atlas, AtlasInstance, AtlasInstanceRaw};
+use crate::render::bind_group::{DynamicUniformBuffer, TextureBindGroupCache};
+use crate::render::sprite3d::BillboardMode;
+use crate::render::vertex::VertexBuffer;
+use crate::render::{RenderServer, TextureCache, TextureId};
+use cgmath::{InnerSpace, Matrix4, Vector2, Vector3, Vector4};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+use wgpu::BufferAddress;
+
+/// Minimal data for rendering a 3D label.
+#[derive(Clone)]
+pub struct ExtractedLabel3d {
+    pub(crate) atlas: Atlas,
+    pub(crate) transform: Transform3d,
+    pub(crate) color: Vector4<f32>,
+    /// Size of a font pixel in world units.
+    pub(crate) pixel_size: f32,
+    pub(crate) billboard_mode: BillboardMode,
+    pub(crate) depth_test: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct Label3dParamsUniform {
+    model_matrix: [[f32; 4]; 4],
+    color: [f32; 4],
+    offset: [f32; 2],
+    pixel_size: f32,
+    billboard_mode: u32,
+}
+
+/// A label ready to be drawn.
+struct Label3dBatch {
+    texture_id: TextureId,
+    depth_test: bool,
+    instance_range: Range<u32>,
+}
+
+pub(crate) struct Label3dRenderResources {
+    params_buffer: DynamicUniformBuffer<Label3dParamsUniform>,
+
+    instance_buffer: Option<wgpu::Buffer>,
+    instance_buffer_capacity: usize,
+
+    texture_bind_groups: TextureBindGroupCache,
+
+    /// Keyed by whether depth test is enabled.
+    pipeline_cache: HashMap<bool, wgpu::RenderPipeline>,
+
+    batches: Vec<Label3dBatch>,
+}
+
+impl Label3dRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let device = &render_server.device;
+
+        Self {
+            params_buffer: DynamicUniformBuffer::new(
+                device,
+                wgpu::ShaderStages::VERTEX,
+                "label3d params",
+            ),
+            instance_buffer: None,
+            instance_buffer_capacity: 0,
+            texture_bind_groups: TextureBindGroupCache::new(device, "label3d texture"),
+            pipeline_cache: HashMap::new(),
+            batches: vec![],
+        }
+    }
+
+    fn create_pipeline(
+        &mut self,
+        depth_test: bool,
+        render_server: &RenderServer,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        if self.pipeline_cache.contains_key(&depth_test) {
+            return;
+        }
+
+        let device = &render_server.device;
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("label3d pipeline layout"),
+            bind_group_layouts: &[
+                camera_bind_group_layout,
+                self.params_buffer.layout(),
+                self.texture_bind_groups.layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("label3d shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/label3d.wgsl").into()),
+        };
+        let shader_module = device.create_shader_module(shader);
+
+        let depth_compare = if depth_test {
+            render_server.depth_compare(wgpu::CompareFunction::LessEqual)
+        } else {
+            wgpu::CompareFunction::Always
+        };
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("label3d pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[AtlasInstanceRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip, // Has to be triangle strip.
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                // Glyph quads overlap each other, so don't write depth.
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        self.pipeline_cache.insert(depth_test, pipeline);
+    }
+
+    /// Drop the bind group of a texture that was removed or replaced.
+    pub(crate) fn evict_texture(&mut self, texture_id: TextureId) {
+        self.texture_bind_groups.evict(texture_id);
+    }
+
+    /// One draw per label.
+    pub(crate) fn draw_count(&self) -> usize {
+        self.batches.len()
+    }
+}
+
+/// Returns the offset that moves the center of the text's bounding box to the origin.
+fn get_center_offset(instances: &[AtlasInstance]) -> Vector2<f32> {
+    let mut min = Vector2::new(f32::MAX, f32::MAX);
+    let mut max = Vector2::new(f32::MIN, f32::MIN);
+
+    for i in instances {
+        min.x = min.x.min(i.position.x);
+        min.y = min.y.min(i.position.y);
+        max.x = max.x.max(i.position.x + i.size.x);
+        max.y = max.y.max(i.position.y + i.size.y);
+    }
+
+    -(min + max) * 0.5
+}
+
+pub(crate) fn prepare_label3d(
+    labels: &[ExtractedLabel3d],
+    camera_position: Vector3<f32>,
+    render_resources: &mut Label3dRenderResources,
+    texture_cache: &TextureCache,
+    render_server: &RenderServer,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+) {
+    render_resources.batches.clear();
+
+    let mut sorted: Vec<&ExtractedLabel3d> = labels
+        .iter()
+        .filter(|l| l.atlas.texture.is_some() && !l.atlas.instances.is_empty())
+        .collect();
+
+    if sorted.is_empty() {
+        return;
+    }
+
+    // Labels with depth test enabled come first, then back to front.
+    sorted.sort_by(|a, b| {
+        let da = (a.transform.position - camera_position).magnitude2();
+        let db = (b.transform.position - camera_position).magnitude2();
+        b.depth_test
+            .cmp(&a.depth_test)
+            .then(db.partial_cmp(&da).unwrap_or(std::cmp::Ordering::Equal))
+    });
+
+    let label_count = sorted.len();
+    let instance_count: usize = sorted.iter().map(|l| l.atlas.instances.len()).sum();
+
+    // Reallocate the instance buffer.
+    if render_resources.instance_buffer_capacity < instance_count {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("label3d instance buffer (unique)"),
+            size: (mem::size_of::<AtlasInstanceRaw>() * instance_count) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.instance_buffer = Some(buffer);
+        render_resources.instance_buffer_capacity = instance_count;
+    }
+
+    let mut instance_data = Vec::with_capacity(instance_count);
+    let mut params = Vec::with_capacity(label_count);
+
+    for l in &sorted {
+        let texture_id = l.atlas.texture.unwrap();
+
+        render_resources.create_pipeline(l.depth_test, render_server, camera_bind_group_layout);
+        render_resources
+            .texture_bind_groups
+            .add(&render_server.device, texture_cache, texture_id);
+
+        let transform = &l.transform;
+        let model = Matrix4::from_translation(transform.position)
+            * Matrix4::from(transform.rotation)
+            * Matrix4::from_nonuniform_scale(
+                transform.scale.x,
+                transform.scale.y,
+                transform.scale.z,
+            );
+
+        params.push(Label3dParamsUniform {
+            model_matrix: model.into(),
+            color: l.color.into(),
+            offset: get_center_offset(&l.atlas.instances).into(),
+            pixel_size: l.pixel_size,
+            billboard_mode: l.billboard_mode as u32,
+        });
+
+        let first_instance = instance_data.len() as u32;
+        instance_data.extend(l.atlas.instances.iter().map(AtlasInstance::to_raw));
+
+        render_resources.batches.push(Label3dBatch {
+            texture_id,
+            depth_test: l.depth_test,
+            instance_range: first_instance..instance_data.len() as u32,
+        });
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.instance_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&instance_data),
+    );
+
+    render_resources.params_buffer.write(render_server, &params);
+}
+
+pub(crate) fn render_label3d<'a, 'b: 'a>(
+    render_resources: &'b Label3dRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+) {
+    if render_resources.batches.is_empty() {
+        return;
+    }
+
+    render_pass.set_vertex_buffer(
+        0,
+        render_resources.instance_buffer.as_ref().unwrap().slice(..),
+    );
+
+    // Set camera group. Offset 0 is the first camera, which all 3D passes draw with for now.
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+
+    for (i, b) in render_resources.batches.iter().enumerate() {
+        render_pass.set_pipeline(render_resources.pipeline_cache.get(&b.depth_test).unwrap());
+
+        render_pass.set_bind_group(
+            1,
+            render_resources.params_buffer.bind_group(),
+            &[DynamicUniformBuffer::<Label3dParamsUniform>::offset(i)],
+        );
+
+        render_pass.set_bind_group(
+            2,
+            render_resources.texture_bind_groups.get(b.texture_id),
+            &[],
+        );
+
+        render_pass.draw(0..4, b.instance_range.clone());
+    }
+}
@@ -0,0 +1,644 @@
+use crate::math::color::{ColorF, ColorU};
+use crate::render::camera::ViewportRect;
+use crate::render::vertex::VertexBuffer;
+use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use cgmath::{InnerSpace, Vector2, Vector4};
+use std::mem;
+use std::ops::Range;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+/// Shadows are extruded this far away from their occluders, in world units.
+const SHADOW_LENGTH: f32 = 100000.0;
+
+/// Each light is tagged with its own stencil value, which is 8 bits.
+const MAX_LIGHTS: usize = 255;
+
+const LIGHT_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Light2dKind {
+    /// Fades out to zero at `radius`.
+    Point { radius: f32 },
+    /// Lights everything, casting shadows along `direction`.
+    Directional { direction: Vector2<f32> },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ExtractedLight2d {
+    pub(crate) position: Vector2<f32>,
+    pub(crate) kind: Light2dKind,
+    /// Color multiplied by energy.
+    pub(crate) color: Vector4<f32>,
+    pub(crate) shadows: bool,
+}
+
+/// Closed polygon in world space.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtractedOccluder2d {
+    pub(crate) points: Vec<Vector2<f32>>,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Light2dInstance {
+    position: [f32; 2],
+    radius: f32,
+    kind: u32,
+    color: [f32; 4],
+}
+
+impl VertexBuffer for Light2dInstance {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Light2dInstance>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowVertex {
+    position: [f32; 2],
+}
+
+impl VertexBuffer for ShadowVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ShadowVertex>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x2,
+            }],
+        }
+    }
+}
+
+/// Light map that 2D sprites are modulated with.
+pub(crate) struct Light2dRenderResources {
+    light_map_texture: TextureId,
+
+    shadow_pipeline: wgpu::RenderPipeline,
+    light_pipeline: wgpu::RenderPipeline,
+
+    light_buffer: Option<wgpu::Buffer>,
+    light_buffer_capacity: usize,
+    shadow_vertex_buffer: Option<wgpu::Buffer>,
+    shadow_vertex_buffer_capacity: usize,
+    /// Shadow vertices of each light.
+    shadow_ranges: Vec<Range<u32>>,
+
+    ambient: wgpu::Color,
+
+    pub(crate) sample_bind_group_layout: wgpu::BindGroupLayout,
+    light_map_bind_group: wgpu::BindGroup,
+    /// A white texture, sampled when there are no lights so sprites stay unlit.
+    _white_texture: wgpu::Texture,
+    white_bind_group: wgpu::BindGroup,
+
+    /// If there is any 2D light this frame.
+    pub(crate) enabled: bool,
+}
+
+impl Light2dRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let light_map_texture = Self::create_light_map(render_server, texture_cache);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("light2d pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("light2d shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/light2d.wgsl").into()),
+        });
+
+        // Mark the shadowed area with the light's reference value.
+        let shadow_pipeline = Self::create_pipeline(
+            render_server,
+            &pipeline_layout,
+            &shader_module,
+            ("vs_main_shadow", "fs_main_shadow"),
+            ShadowVertex::desc(),
+            wgpu::PrimitiveTopology::TriangleList,
+            None,
+            wgpu::ColorWrites::empty(),
+            wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Always,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Replace,
+            },
+            "light2d shadow pipeline",
+        );
+
+        // Add the light everywhere but in its own shadows.
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        let light_pipeline = Self::create_pipeline(
+            render_server,
+            &pipeline_layout,
+            &shader_module,
+            ("vs_main_light", "fs_main_light"),
+            Light2dInstance::desc(),
+            wgpu::PrimitiveTopology::TriangleStrip,
+            Some(additive),
+            wgpu::ColorWrites::ALL,
+            wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::NotEqual,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Keep,
+            },
+            "light2d light pipeline",
+        );
+
+        let sample_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("light map sample bind group layout"),
+            });
+
+        let light_map = texture_cache.get(light_map_texture).unwrap();
+        let light_map_bind_group = Self::create_sample_bind_group(
+            render_server,
+            &sample_bind_group_layout,
+            &light_map.view,
+            &light_map.sampler,
+        );
+
+        let white_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("light map white texture"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        render_server.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &white_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &[255; 4],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: Some(1),
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        // Clamped, so any screen position reads the single texel.
+        let white_sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        let white_bind_group = Self::create_sample_bind_group(
+            render_server,
+            &sample_bind_group_layout,
+            &white_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &white_sampler,
+        );
+
+        Self {
+            light_map_texture,
+            shadow_pipeline,
+            light_pipeline,
+            light_buffer: None,
+            light_buffer_capacity: 0,
+            shadow_vertex_buffer: None,
+            shadow_vertex_buffer_capacity: 0,
+            shadow_ranges: vec![],
+            ambient: wgpu::Color::WHITE,
+            sample_bind_group_layout,
+            light_map_bind_group,
+            _white_texture: white_texture,
+            white_bind_group,
+            enabled: false,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipeline(
+        render_server: &RenderServer,
+        layout: &wgpu::PipelineLayout,
+        shader_module: &wgpu::ShaderModule,
+        entry_points: (&str, &str),
+        vertex_buffer: wgpu::VertexBufferLayout,
+        topology: wgpu::PrimitiveTopology,
+        blend: Option<wgpu::BlendState>,
+        write_mask: wgpu::ColorWrites,
+        stencil_face: wgpu::StencilFaceState,
+        label: &str,
+    ) -> wgpu::RenderPipeline {
+        render_server
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader_module,
+                    entry_point: entry_points.0,
+                    buffers: &[vertex_buffer],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader_module,
+                    entry_point: entry_points.1,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: LIGHT_MAP_FORMAT,
+                        blend,
+                        write_mask,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: render_server.depth_format(),
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState {
+                        front: stencil_face,
+                        back: stencil_face,
+                        read_mask: 0xff,
+                        write_mask: 0xff,
+                    },
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+    }
+
+    fn create_light_map(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> TextureId {
+        // Float, so that overlapping lights can go above one.
+        let mut config = render_server.surface_config.clone();
+        config.format = LIGHT_MAP_FORMAT;
+
+        Texture::create_render_texture(
+            &render_server.device,
+            texture_cache,
+            &config,
+            Some("light map texture"),
+        )
+    }
+
+    fn create_sample_bind_group(
+        render_server: &RenderServer,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+                label: Some("light map sample bind group"),
+            })
+    }
+
+    /// The light map has to follow the surface size.
+    pub(crate) fn recreate_textures(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) {
+        texture_cache.remove(self.light_map_texture);
+
+        self.light_map_texture = Self::create_light_map(render_server, texture_cache);
+
+        let light_map = texture_cache.get(self.light_map_texture).unwrap();
+        self.light_map_bind_group = Self::create_sample_bind_group(
+            render_server,
+            &self.sample_bind_group_layout,
+            &light_map.view,
+            &light_map.sampler,
+        );
+    }
+
+    /// What sprites should sample: the light map, or white if there are no lights.
+    pub(crate) fn get_sample_bind_group(&self) -> &wgpu::BindGroup {
+        if self.enabled {
+            &self.light_map_bind_group
+        } else {
+            &self.white_bind_group
+        }
+    }
+}
+
+/// Append two triangles per occluder edge, stretching from the edge away from the light.
+fn push_shadow_vertices(
+    light: &ExtractedLight2d,
+    occluder: &ExtractedOccluder2d,
+    vertices: &mut Vec<ShadowVertex>,
+) {
+    let points = &occluder.points;
+    if points.len() < 2 {
+        return;
+    }
+
+    if let Light2dKind::Point { radius } = light.kind {
+        // Skip occluders whose bounds are out of the light's reach.
+        let (min, max) = points.iter().fold((points[0], points[0]), |(min, max), p| {
+            (
+                Vector2::new(min.x.min(p.x), min.y.min(p.y)),
+                Vector2::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        });
+        let closest = Vector2::new(
+            light.position.x.clamp(min.x, max.x),
+            light.position.y.clamp(min.y, max.y),
+        );
+        if (closest - light.position).magnitude2() > radius * radius {
+            return;
+        }
+    }
+
+    let extrude = |p: Vector2<f32>| -> Option<Vector2<f32>> {
+        let direction = match light.kind {
+            Light2dKind::Point { .. } => p - light.position,
+            Light2dKind::Directional { direction } => direction,
+        };
+
+        if direction.magnitude2() < f32::EPSILON {
+            return None;
+        }
+
+        Some(p + direction.normalize() * SHADOW_LENGTH)
+    };
+
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+
+        let (Some(far_a), Some(far_b)) = (extrude(a), extrude(b)) else {
+            continue;
+        };
+
+        for p in [a, b, far_b, a, far_b, far_a] {
+            vertices.push(ShadowVertex { position: p.into() });
+        }
+    }
+}
+
+pub(crate) fn prepare_light2d(
+    lights: &[ExtractedLight2d],
+    occluders: &[ExtractedOccluder2d],
+    ambient: ColorU,
+    render_resources: &mut Light2dRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.enabled = !lights.is_empty();
+    render_resources.shadow_ranges.clear();
+
+    if lights.is_empty() {
+        return;
+    }
+
+    if lights.len() > MAX_LIGHTS {
+        log::warn!(
+            "Too many 2D lights ({}), only the first {} are drawn",
+            lights.len(),
+            MAX_LIGHTS
+        );
+    }
+    let lights = &lights[..lights.len().min(MAX_LIGHTS)];
+
+    render_resources.ambient = ColorF {
+        a: 1.0,
+        ..ambient.to_f32()
+    }
+    .into();
+
+    let instances: Vec<Light2dInstance> = lights
+        .iter()
+        .map(|light| {
+            let (radius, kind) = match light.kind {
+                Light2dKind::Point { radius } => (radius, 0),
+                Light2dKind::Directional { .. } => (0.0, 1),
+            };
+
+            Light2dInstance {
+                position: light.position.into(),
+                radius,
+                kind,
+                color: light.color.into(),
+            }
+        })
+        .collect();
+
+    let mut shadow_vertices = vec![];
+
+    for light in lights {
+        let start = shadow_vertices.len() as u32;
+
+        if light.shadows {
+            for occluder in occluders {
+                push_shadow_vertices(light, occluder, &mut shadow_vertices);
+            }
+        }
+
+        render_resources
+            .shadow_ranges
+            .push(start..shadow_vertices.len() as u32);
+    }
+
+    // Reallocate the instance buffer.
+    if render_resources.light_buffer_capacity < instances.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light2d instance buffer"),
+            size: mem::size_of_val(instances.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.light_buffer_capacity = instances.len();
+        render_resources.light_buffer = Some(buffer);
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.light_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&instances),
+    );
+
+    if shadow_vertices.is_empty() {
+        return;
+    }
+
+    // Reallocate the shadow vertex buffer.
+    if render_resources.shadow_vertex_buffer_capacity < shadow_vertices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light2d shadow vertex buffer"),
+            size: mem::size_of_val(shadow_vertices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.shadow_vertex_buffer_capacity = shadow_vertices.len();
+        render_resources.shadow_vertex_buffer = Some(buffer);
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.shadow_vertex_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&shadow_vertices),
+    );
+}
+
+/// Draw the light map. The depth texture is only borrowed for its stencil.
+pub(crate) fn render_light_map(
+    render_resources: &Light2dRenderResources,
+    texture_cache: &TextureCache,
+    depth_view: &wgpu::TextureView,
+    camera_bind_group: &wgpu::BindGroup,
+    viewport: Option<ViewportRect>,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    if !render_resources.enabled {
+        return;
+    }
+
+    let light_map = texture_cache
+        .get(render_resources.light_map_texture)
+        .unwrap();
+
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("light map render pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &light_map.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(render_resources.ambient),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: depth_view,
+            depth_ops: None,
+            stencil_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: wgpu::StoreOp::Discard,
+            }),
+        }),
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    // Lights have to line up with the sprites sampling them.
+    if let Some(viewport) = viewport {
+        viewport.set(&mut render_pass);
+    }
+
+    // Set camera group. Offset 0 is the first camera, like `SpriteBatch::camera_index`.
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+
+    for (i, shadow_range) in render_resources.shadow_ranges.iter().enumerate() {
+        // Zero is the cleared value, so lights start at one.
+        render_pass.set_stencil_reference(i as u32 + 1);
+
+        if !shadow_range.is_empty() {
+            render_pass.set_pipeline(&render_resources.shadow_pipeline);
+            render_pass.set_vertex_buffer(
+                0,
+                render_resources
+                    .shadow_vertex_buffer
+                    .as_ref()
+                    .unwrap()
+                    .slice(..),
+            );
+            render_pass.draw(shadow_range.clone(), 0..1);
+        }
+
+        render_pass.set_pipeline(&render_resources.light_pipeline);
+        render_pass.set_vertex_buffer(0, render_resources.light_buffer.as_ref().unwrap().slice(..));
+        render_pass.draw(0..4, i as u32..i as u32 + 1);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::render::sprite::SpriteRenderResources;
+use crate::render::vertex::Vertex2d;
+use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::mem;
+use std::ops::Range;
+use wgpu::BufferAddress;
+
+/// A tessellated 2D line, in world space.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtractedLine2d {
+    pub(crate) vertices: Vec<Vertex2d>,
+    pub(crate) indices: Vec<u32>,
+    pub(crate) texture_id: Option<TextureId>,
+}
+
+/// Lines are drawn with the sprite pipeline, so they are clipped and lit like sprites.
+pub(crate) struct Line2dRenderResources {
+    /// Used by lines without a texture.
+    white_texture: TextureId,
+
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_buffer_capacity: usize,
+    index_buffer: Option<wgpu::Buffer>,
+    index_buffer_capacity: usize,
+
+    /// Index range and texture of each line.
+    draws: Vec<(Range<u32>, TextureId)>,
+}
+
+impl Line2dRenderResources {
+    pub(crate) fn new(render_server: &RenderServer, texture_cache: &mut TextureCache) -> Self {
+        let white = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, Rgba([255; 4])));
+
+        let white_texture = Texture::from_image(
+            &render_server.device,
+            &render_server.queue,
+            texture_cache,
+            &white,
+            Some("line2d white texture"),
+        )
+        .unwrap();
+
+        Self {
+            white_texture,
+            vertex_buffer: None,
+            vertex_buffer_capacity: 0,
+            index_buffer: None,
+            index_buffer_capacity: 0,
+            draws: vec![],
+        }
+    }
+
+    pub(crate) fn draw_count(&self) -> usize {
+        self.draws
+            .iter()
+            .filter(|(indices, _)| !indices.is_empty())
+            .count()
+    }
+}
+
+pub(crate) fn prepare_lines(
+    lines: &[ExtractedLine2d],
+    render_resources: &mut Line2dRenderResources,
+    sprite_render_resources: &mut SpriteRenderResources,
+    texture_cache: &TextureCache,
+    render_server: &RenderServer,
+) {
+    render_resources.draws.clear();
+
+    if lines.is_empty() {
+        return;
+    }
+
+    let mut all_vertices = vec![];
+    let mut all_indices = vec![];
+
+    for line in lines {
+        let texture_id = line.texture_id.unwrap_or(render_resources.white_texture);
+
+        sprite_render_resources.add_texture_bind_group(
+            &render_server.device,
+            texture_cache,
+            texture_id,
+        );
+
+        let base_vertex = all_vertices.len() as u32;
+        let start = all_indices.len() as u32;
+
+        all_vertices.extend_from_slice(&line.vertices);
+        all_indices.extend(line.indices.iter().map(|i| i + base_vertex));
+
+        render_resources
+            .draws
+            .push((start..all_indices.len() as u32, texture_id));
+    }
+
+    if all_indices.is_empty() {
+        return;
+    }
+
+    // Reallocate the vertex buffer.
+    if render_resources.vertex_buffer_capacity < all_vertices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("line2d vertex buffer"),
+            size: mem::size_of_val(all_vertices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.vertex_buffer_capacity = all_vertices.len();
+        render_resources.vertex_buffer = Some(buffer);
+    }
+
+    // Reallocate the index buffer.
+    if render_resources.index_buffer_capacity < all_indices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("line2d index buffer"),
+            size: mem::size_of_val(all_indices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.index_buffer_capacity = all_indices.len();
+        render_resources.index_buffer = Some(buffer);
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.vertex_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&all_vertices),
+    );
+
+    render_server.queue.write_buffer(
+        render_resources.index_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&all_indices),
+    );
+}
+
+/// Draw the lines in `range`.
+pub(crate) fn render_lines<'a, 'b: 'a>(
+    range: Range<usize>,
+    render_resources: &'b Line2dRenderResources,
+    sprite_render_resources: &'b SpriteRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+    light_map_bind_group: &'b wgpu::BindGroup,
+) {
+    let draws = &render_resources.draws[range];
+
+    if draws.iter().all(|(indices, _)| indices.is_empty()) {
+        return;
+    }
+
+    render_pass.set_pipeline(sprite_render_resources.pipeline.as_ref().unwrap());
+
+    render_pass.set_vertex_buffer(
+        0,
+        render_resources.vertex_buffer.as_ref().unwrap().slice(..),
+    );
+
+    render_pass.set_index_buffer(
+        render_resources.index_buffer.as_ref().unwrap().slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+
+    // Set camera group. Offset 0 is the first camera, like `SpriteBatch::camera_index`.
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+
+    // Set 2D lighting group.
+    render_pass.set_bind_group(2, light_map_bind_group, &[]);
+
+    for (indices, texture_id) in draws {
+        if indices.is_empty() {
+            continue;
+        }
+
+        render_pass.set_bind_group(
+            1,
+            sprite_render_resources.get_texture_bind_group(*texture_id),
+            &[],
+        );
+
+        render_pass.draw_indexed(indices.clone(), 0, 0..1);
+    }
+}
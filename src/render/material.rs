@@ -1,6 +1,9 @@
+use crate::math::color::srgb_to_linear;
+use crate::math::color::ColorU;
 use crate::render::bind_group::{BindGroupCache, BindGroupId};
-use crate::render::{Texture, TextureCache, TextureId};
+use crate::render::{DepthStencilConfig, TextureCache, TextureId};
 use bitflags::bitflags;
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -8,9 +11,67 @@ pub struct MaterialStandard {
     pub name: String,
     pub color_texture: Option<TextureId>,
     pub normal_texture: Option<TextureId>,
+    /// Multiplied with `emissive_color`.
+    pub emissive_texture: Option<TextureId>,
+    /// Light given off by the surface, unaffected by lighting. Black means no emission.
+    pub emissive_color: ColorU,
+    /// Scales `emissive_color`. Values above 1.0 make the surface brighter than white,
+    /// so it blooms, see `PostProcessSettings::bloom_threshold`.
+    pub emissive_strength: f32,
     // Bind group for the textures.
     pub texture_bind_group: Option<BindGroupId>,
-    pub transparent: bool,
+    /// Applied to texture coordinates before sampling: scaled, rotated, then offset.
+    /// Scale above 1.0 tiles the textures, animating the offset scrolls them.
+    pub uv_offset: Vector2<f32>,
+    pub uv_scale: Vector2<f32>,
+    /// In radians.
+    pub uv_rotation: f32,
+    /// How the alpha of the color texture is used.
+    pub transparency: Transparency,
+    /// Draw back faces too, with flipped normals. For thin surfaces like leaves and cloth.
+    pub double_sided: bool,
+    /// E.g. to draw outlines behind everything, or only inside a stencil-marked portal.
+    pub depth_stencil: DepthStencilConfig,
+    /// Moves the vertices over time, e.g. for foliage swaying in the wind.
+    pub vertex_animation: VertexAnimation,
+}
+
+/// How a material handles alpha.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Transparency {
+    #[default]
+    Opaque,
+    /// Discard fragments with alpha below the cutoff, e.g. for foliage.
+    /// Drawn with the opaque meshes and writes depth.
+    ///
+    /// The scene isn't multisampled, so this can't use alpha-to-coverage yet.
+    AlphaCut { cutoff: f32 },
+    /// Blend with what's behind, e.g. for glass. Drawn after the opaque meshes,
+    /// back to front, and doesn't write depth.
+    AlphaBlend,
+}
+
+/// How a material moves its vertices, on the GPU. Only the drawn mesh moves,
+/// shadows and picking still use the rest pose.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum VertexAnimation {
+    #[default]
+    None,
+    /// Sine sway for foliage and flags. Vertices move more the higher they are above
+    /// the mesh origin, so a mesh with its base at the origin stays rooted.
+    Wind {
+        /// World direction the vertices sway along.
+        direction: Vector3<f32>,
+        /// Sway distance per unit of height.
+        strength: f32,
+        /// Sways per second.
+        frequency: f32,
+        /// Its red channel, sampled at the world XZ position, offsets the phase and
+        /// scales the sway, so neighbouring plants don't move in lockstep.
+        noise_texture: Option<TextureId>,
+        /// Noise texture repeats per world unit.
+        noise_scale: f32,
+    },
 }
 
 bitflags! {
@@ -18,9 +79,39 @@ bitflags! {
         const COLOR_TEXTURE = 1 << 0;
         const NORMAL_TEXTURE = 1 << 1;
         const TRANSPARENT = 1 << 2;
+        const EMISSIVE = 1 << 3;
+        const EMISSIVE_TEXTURE = 1 << 4;
+        const ALPHA_CUT = 1 << 5;
+        const DOUBLE_SIDED = 1 << 6;
+        const UV_TRANSFORM = 1 << 7;
+        const WIND = 1 << 8;
+        const WIND_NOISE_TEXTURE = 1 << 9;
     }
 }
 
+// Material bindings are fixed, so the shader doesn't depend on which textures are present.
+pub(crate) const COLOR_TEXTURE_BINDING: u32 = 0;
+pub(crate) const NORMAL_TEXTURE_BINDING: u32 = 2;
+pub(crate) const MATERIAL_UNIFORM_BINDING: u32 = 4;
+pub(crate) const EMISSIVE_TEXTURE_BINDING: u32 = 5;
+pub(crate) const WIND_NOISE_TEXTURE_BINDING: u32 = 7;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct MaterialUniform {
+    /// Linear color times strength. W is unused.
+    emissive: [f32; 4],
+    uv_offset: [f32; 2],
+    uv_scale: [f32; 2],
+    uv_rotation: f32,
+    alpha_cutoff: f32,
+    wind_strength: f32,
+    wind_frequency: f32,
+    /// Normalized.
+    wind_direction: [f32; 3],
+    wind_noise_scale: f32,
+}
+
 impl MaterialStandard {
     pub fn get_flags(&self) -> u32 {
         let mut flags = 0;
@@ -33,6 +124,36 @@ impl MaterialStandard {
             flags = flags | MaterialFlags::NORMAL_TEXTURE.bits();
         }
 
+        if self.is_emissive() {
+            flags |= MaterialFlags::EMISSIVE.bits();
+
+            if self.emissive_texture.is_some() {
+                flags |= MaterialFlags::EMISSIVE_TEXTURE.bits();
+            }
+        }
+
+        match self.transparency {
+            Transparency::Opaque => {}
+            Transparency::AlphaCut { .. } => flags |= MaterialFlags::ALPHA_CUT.bits(),
+            Transparency::AlphaBlend => flags |= MaterialFlags::TRANSPARENT.bits(),
+        }
+
+        if self.double_sided {
+            flags |= MaterialFlags::DOUBLE_SIDED.bits();
+        }
+
+        if self.has_uv_transform() {
+            flags |= MaterialFlags::UV_TRANSFORM.bits();
+        }
+
+        if let VertexAnimation::Wind { noise_texture, .. } = self.vertex_animation {
+            flags |= MaterialFlags::WIND.bits();
+
+            if noise_texture.is_some() {
+                flags |= MaterialFlags::WIND_NOISE_TEXTURE.bits();
+            }
+        }
+
         return flags;
     }
 
@@ -47,46 +168,177 @@ impl MaterialStandard {
             shader_defs.push("NORMAP_MAP");
         }
 
+        if self.is_emissive() {
+            shader_defs.push("EMISSIVE");
+
+            if self.emissive_texture.is_some() {
+                shader_defs.push("EMISSIVE_MAP");
+            }
+        }
+
+        match self.transparency {
+            Transparency::Opaque => {}
+            Transparency::AlphaCut { .. } => shader_defs.push("ALPHA_CUT"),
+            Transparency::AlphaBlend => shader_defs.push("ALPHA_BLEND"),
+        }
+
+        if self.has_uniform() {
+            shader_defs.push("MATERIAL_UNIFORM");
+        }
+
+        if self.double_sided {
+            shader_defs.push("DOUBLE_SIDED");
+        }
+
+        if self.has_uv_transform() {
+            shader_defs.push("UV_TRANSFORM");
+        }
+
+        if let VertexAnimation::Wind { noise_texture, .. } = self.vertex_animation {
+            shader_defs.push("WIND");
+
+            if noise_texture.is_some() {
+                shader_defs.push("WIND_NOISE_MAP");
+            }
+        }
+
         return shader_defs;
     }
 
+    /// Whether the surface gives off light.
+    pub fn is_emissive(&self) -> bool {
+        let color = self.emissive_color;
+
+        self.emissive_strength > 0.0 && (color.r > 0 || color.g > 0 || color.b > 0)
+    }
+
+    /// Whether the UV offset, scale or rotation differs from the identity.
+    pub fn has_uv_transform(&self) -> bool {
+        self.uv_offset != Vector2::zero()
+            || self.uv_scale != Vector2::new(1.0, 1.0)
+            || self.uv_rotation != 0.0
+    }
+
+    /// Blended materials are sorted and don't write depth.
+    pub fn is_blended(&self) -> bool {
+        self.transparency == Transparency::AlphaBlend
+    }
+
+    pub fn get_cull_mode(&self) -> Option<wgpu::Face> {
+        if self.double_sided {
+            None
+        } else {
+            Some(wgpu::Face::Back)
+        }
+    }
+
+    /// Depth/stencil state the material is actually drawn with.
+    pub fn get_depth_stencil(&self) -> DepthStencilConfig {
+        let mut depth_stencil = self.depth_stencil.clone();
+
+        if self.is_blended() {
+            depth_stencil.depth_write = false;
+        }
+
+        depth_stencil
+    }
+
+    /// Whether the material needs a uniform buffer for its factors.
+    pub(crate) fn has_uniform(&self) -> bool {
+        self.is_emissive()
+            || self.has_uv_transform()
+            || matches!(self.transparency, Transparency::AlphaCut { .. })
+            || self.has_wind()
+    }
+
+    /// Whether the vertex shader moves the vertices with the wind.
+    pub(crate) fn has_wind(&self) -> bool {
+        matches!(self.vertex_animation, VertexAnimation::Wind { .. })
+    }
+
+    pub(crate) fn get_uniform(&self) -> MaterialUniform {
+        let color = self.emissive_color;
+        let linear = |value: u8| srgb_to_linear(value as f32 / 255.0) * self.emissive_strength;
+
+        let alpha_cutoff = match self.transparency {
+            Transparency::AlphaCut { cutoff } => cutoff,
+            _ => 0.0,
+        };
+
+        let (wind_direction, wind_strength, wind_frequency, wind_noise_scale) =
+            match self.vertex_animation {
+                VertexAnimation::None => (Vector3::zero(), 0.0, 0.0, 0.0),
+                VertexAnimation::Wind {
+                    direction,
+                    strength,
+                    frequency,
+                    noise_scale,
+                    ..
+                } => (
+                    if direction.is_zero() {
+                        direction
+                    } else {
+                        direction.normalize()
+                    },
+                    strength,
+                    frequency,
+                    noise_scale,
+                ),
+            };
+
+        MaterialUniform {
+            emissive: [linear(color.r), linear(color.g), linear(color.b), 1.0],
+            uv_offset: self.uv_offset.into(),
+            uv_scale: self.uv_scale.into(),
+            uv_rotation: self.uv_rotation,
+            alpha_cutoff,
+            wind_strength,
+            wind_frequency,
+            wind_direction: wind_direction.into(),
+            wind_noise_scale,
+        }
+    }
+
+    /// `uniform_buffer` is required if the material has a uniform.
     pub fn get_bind_group_entries<'a>(
         &'a self,
         texture_cache: &'a TextureCache,
+        uniform_buffer: Option<&'a wgpu::Buffer>,
     ) -> Vec<wgpu::BindGroupEntry> {
         let mut bind_group_entries = vec![];
-        let mut next_binding = 0;
 
-        if self.color_texture.is_some() {
-            let color_texture = texture_cache.get(self.color_texture.unwrap()).unwrap();
+        let mut push_texture = |texture_id: Option<TextureId>, binding: u32| {
+            let Some(texture) = texture_id.and_then(|id| texture_cache.get(id)) else {
+                return;
+            };
 
             bind_group_entries.push(wgpu::BindGroupEntry {
-                binding: next_binding,
-                resource: wgpu::BindingResource::TextureView(&color_texture.view),
+                binding,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
             });
-            next_binding += 1;
 
             bind_group_entries.push(wgpu::BindGroupEntry {
-                binding: next_binding,
-                resource: wgpu::BindingResource::Sampler(&color_texture.sampler),
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
             });
-            next_binding += 1;
-        }
+        };
 
-        if self.normal_texture.is_some() {
-            let normal_texture = texture_cache.get(self.normal_texture.unwrap()).unwrap();
+        push_texture(self.color_texture, COLOR_TEXTURE_BINDING);
+        push_texture(self.normal_texture, NORMAL_TEXTURE_BINDING);
 
-            bind_group_entries.push(wgpu::BindGroupEntry {
-                binding: next_binding,
-                resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-            });
-            next_binding += 1;
+        if self.is_emissive() {
+            push_texture(self.emissive_texture, EMISSIVE_TEXTURE_BINDING);
+        }
 
+        if let VertexAnimation::Wind { noise_texture, .. } = self.vertex_animation {
+            push_texture(noise_texture, WIND_NOISE_TEXTURE_BINDING);
+        }
+
+        if self.has_uniform() {
             bind_group_entries.push(wgpu::BindGroupEntry {
-                binding: next_binding,
-                resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                binding: MATERIAL_UNIFORM_BINDING,
+                resource: uniform_buffer.unwrap().as_entire_binding(),
             });
-            next_binding += 1;
         }
 
         bind_group_entries
@@ -107,7 +359,7 @@ impl MaterialCache {
         }
     }
 
-    pub(crate) fn add(&mut self, material: MaterialStandard) -> MaterialId {
+    pub fn add(&mut self, material: MaterialStandard) -> MaterialId {
         let id = MaterialId(uuid::Uuid::new_v4());
         self.storage.insert(id, material);
         id
@@ -116,4 +368,8 @@ impl MaterialCache {
     pub(crate) fn get(&self, material_id: &MaterialId) -> Option<&MaterialStandard> {
         self.storage.get(material_id)
     }
+
+    pub(crate) fn remove(&mut self, material_id: &MaterialId) {
+        self.storage.remove(material_id);
+    }
 }
@@ -0,0 +1,56 @@
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// GPU memory in use, in bytes, per kind of resource.
+///
+/// Textures and meshes are summed from their caches, uniform buffers are counted
+/// when created through [`RenderServer::create_uniform_buffer`].
+///
+/// [`RenderServer::create_uniform_buffer`]: crate::render::RenderServer::create_uniform_buffer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GpuMemoryUsage {
+    pub textures: u64,
+    /// Vertex and index buffers, and blend shapes.
+    pub meshes: u64,
+    pub uniforms: u64,
+}
+
+impl GpuMemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.textures + self.meshes + self.uniforms
+    }
+}
+
+/// A buffer made by [`RenderServer::create_uniform_buffer`]. Its size is taken off the
+/// uniform memory count when it's dropped.
+///
+/// [`RenderServer::create_uniform_buffer`]: crate::render::RenderServer::create_uniform_buffer
+#[derive(Debug)]
+pub struct UniformBuffer {
+    buffer: wgpu::Buffer,
+    counter: Arc<AtomicU64>,
+}
+
+impl UniformBuffer {
+    pub(crate) fn new(buffer: wgpu::Buffer, counter: Arc<AtomicU64>) -> Self {
+        counter.fetch_add(buffer.size(), Ordering::Relaxed);
+
+        Self { buffer, counter }
+    }
+}
+
+impl Deref for UniformBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        self.counter
+            .fetch_sub(self.buffer.size(), Ordering::Relaxed);
+    }
+}
@@ -1,18 +1,32 @@
 use crate::math::transform::Transform3d;
-use crate::render::camera::{CameraRenderResources, CameraUniform};
+use crate::render::camera::CameraRenderResources;
+use crate::render::cluster::ClusterRenderResources;
 use crate::render::gizmo::GizmoRenderResources;
 use crate::render::light::{ExtractedLights, LightUniform};
-use crate::render::material::{MaterialCache, MaterialId, MaterialStandard};
+use crate::render::material::{
+    MaterialCache, MaterialId, MaterialStandard, MaterialUniform, VertexAnimation,
+    COLOR_TEXTURE_BINDING, EMISSIVE_TEXTURE_BINDING, MATERIAL_UNIFORM_BINDING,
+    NORMAL_TEXTURE_BINDING, WIND_NOISE_TEXTURE_BINDING,
+};
+use crate::render::morph::{MorphData, MorphTarget};
+use crate::render::oit::create_oit_pipeline;
+use crate::render::probe::ProbeRenderResources;
 use crate::render::shader_maker::ShaderMaker;
-use crate::render::vertex::{Vertex2d, Vertex3d, VertexBuffer, VertexSky};
-use crate::render::{create_render_pipeline, RenderServer, Texture, TextureCache, TextureId};
-use cgmath::{Deg, InnerSpace, Matrix3, Matrix4, Quaternion, Rotation3, Vector3, Zero};
-use lyon::path::Position;
+use crate::render::shadow::ShadowRenderResources;
+use crate::render::vertex::{
+    CustomVertex, Vertex2d, Vertex3d, VertexLayout, VertexSky, VERTEX_COLOR_LOCATION,
+};
+use crate::render::UniformBuffer;
+use crate::render::{
+    create_render_pipeline, DepthStencilConfig, RenderPath, RenderServer, TextureCache,
+};
+use anyhow::Result;
+use cgmath::{InnerSpace, Matrix3, Matrix4, Quaternion, Vector3};
 use std::collections::HashMap;
 use std::mem;
 use std::ops::Range;
 use wgpu::util::DeviceExt;
-use wgpu::{BufferAddress, Device, SamplerBindingType};
+use wgpu::{BufferAddress, SamplerBindingType};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct MeshId(uuid::Uuid);
@@ -28,7 +42,7 @@ impl MeshCache {
         }
     }
 
-    pub(crate) fn add(&mut self, mesh: Mesh) -> MeshId {
+    pub fn add(&mut self, mesh: Mesh) -> MeshId {
         let id = MeshId(uuid::Uuid::new_v4());
         self.storage.insert(id, mesh);
         id
@@ -54,9 +68,110 @@ pub struct Mesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
+    // CPU copy of vertex positions and indices, for collision and picking.
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+    /// Meshes with different layouts get different pipelines.
+    pub vertex_layout: VertexLayout,
+    /// Blended into the vertex buffer when their weights change.
+    pub(crate) morph: Option<MorphData>,
 }
 
 impl Mesh {
+    /// Build a mesh from vertices of any type, e.g. for procedural geometry.
+    ///
+    /// Meshes are drawn by the standard mesh shader, which reads locations 0 to 4
+    /// (position, UV, normal, tangent, bi-tangent), and the vertex color at
+    /// [`VERTEX_COLOR_LOCATION`] if there is one. Other attributes are ignored.
+    pub fn from_vertices<V: CustomVertex>(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[V],
+        indices: Vec<u32>,
+    ) -> Mesh {
+        Self::build(device, name, vertices, indices, wgpu::BufferUsages::VERTEX)
+    }
+
+    /// Like [`Mesh::from_vertices`], with blend shapes weighted by
+    /// [`Model::set_blend_shape_weight`](crate::scene::Model::set_blend_shape_weight).
+    ///
+    /// Targets move the position at location 0 and the normal at location 2,
+    /// both of which have to be `Float32x3`.
+    pub fn from_vertices_with_morph_targets<V: CustomVertex>(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[V],
+        indices: Vec<u32>,
+        targets: Vec<MorphTarget>,
+    ) -> Result<Mesh> {
+        let morph = MorphData::new(
+            device,
+            name,
+            &V::layout(),
+            bytemuck::cast_slice(vertices),
+            targets,
+        )?;
+
+        // Targets are blended into the vertex buffer by a compute shader, or the CPU.
+        let usage =
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+
+        let mut mesh = Self::build(device, name, vertices, indices, usage);
+        mesh.morph = Some(morph);
+
+        Ok(mesh)
+    }
+
+    fn build<V: CustomVertex>(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[V],
+        indices: Vec<u32>,
+        vertex_usage: wgpu::BufferUsages,
+    ) -> Mesh {
+        let vertex_layout = V::layout();
+
+        if !is_standard_layout(&vertex_layout) {
+            log::warn!(
+                "Mesh {} lacks attributes at locations 0 to 4, it won't be drawn.",
+                name
+            );
+        }
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} vertex buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: vertex_usage,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} index buffer", name)),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+            positions: vertices.iter().map(|v| v.position()).collect(),
+            indices,
+            vertex_layout,
+            morph: None,
+        }
+    }
+
+    /// GPU memory used by the vertex and index buffers, and the blend shapes if any.
+    pub fn get_memory_size(&self) -> u64 {
+        let morph_size = self
+            .morph
+            .as_ref()
+            .map_or(0, |morph| morph.get_memory_size());
+
+        self.vertex_buffer.size() + self.index_buffer.size() + morph_size
+    }
+
     pub fn default_2d(device: &wgpu::Device) -> Mesh {
         let vertices = [
             Vertex2d {
@@ -100,6 +215,13 @@ impl Mesh {
             vertex_buffer,
             index_buffer,
             index_count: indices.len() as u32,
+            positions: vertices
+                .iter()
+                .map(|v| [v.position[0], v.position[1], 0.0])
+                .collect(),
+            indices: indices.to_vec(),
+            vertex_layout: Vertex2d::layout(),
+            morph: None,
         }
     }
 
@@ -154,6 +276,10 @@ impl Mesh {
             vertex_buffer,
             index_buffer,
             index_count: indices.len() as u32,
+            positions: vertices.iter().map(|v| v.position).collect(),
+            indices: indices.to_vec(),
+            vertex_layout: Vertex3d::layout(),
+            morph: None,
         }
     }
 
@@ -207,10 +333,44 @@ impl Mesh {
             vertex_buffer,
             index_buffer,
             index_count: indices.len() as u32,
+            positions: vertices.iter().map(|v| v.position).collect(),
+            indices: indices.to_vec(),
+            vertex_layout: VertexSky::layout(),
+            morph: None,
         }
     }
 }
 
+/// Light bindings of the forward+ path, after the probes.
+const CLUSTER_FIRST_BINDING: u32 = 14;
+
+/// The point light's shadow map, after the forward+ bindings.
+const POINT_SHADOW_BINDING: u32 = 17;
+
+/// Shader defs for the render path.
+fn lighting_shader_defs(render_server: &RenderServer) -> Vec<&'static str> {
+    match render_server.get_settings().render_path {
+        RenderPath::Forward => vec![],
+        RenderPath::ForwardPlus => vec!["FORWARD_PLUS"],
+    }
+}
+
+/// Shader defs for the optional attributes in the layout.
+fn vertex_shader_defs(vertex_layout: &VertexLayout) -> Vec<&'static str> {
+    let mut shader_defs = vec![];
+
+    if vertex_layout.has_location(VERTEX_COLOR_LOCATION) {
+        shader_defs.push("VERTEX_COLOR");
+    }
+
+    shader_defs
+}
+
+/// Whether the standard mesh shader can read the layout.
+pub(crate) fn is_standard_layout(vertex_layout: &VertexLayout) -> bool {
+    (0..5).all(|location| vertex_layout.has_location(location))
+}
+
 /// Minimal data for rendering a mesh.
 #[derive(Debug, Copy, Clone)]
 pub struct ExtractedMesh {
@@ -431,20 +591,39 @@ where
     // }
 }
 
+/// Mesh pipelines vary by material features, depth/stencil state and vertex layout.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MeshPipelineKey {
+    pub(crate) material_flags: u32,
+    pub(crate) depth_stencil: DepthStencilConfig,
+    pub(crate) vertex_layout: VertexLayout,
+}
+
 /// All mesh related resources.
 pub struct MeshRenderResources {
     pub(crate) light_bind_group_layout: wgpu::BindGroupLayout,
     pub(crate) light_bind_group: Option<wgpu::BindGroup>,
-    pub(crate) light_uniform_buffer: Option<wgpu::Buffer>,
+    pub(crate) light_uniform_buffer: Option<UniformBuffer>,
+    /// Shadow map and probe generations the light bind group was created with.
+    light_bind_group_generations: (u32, u32),
 
     pub(crate) texture_bind_group_layout_cache: HashMap<u32, wgpu::BindGroupLayout>,
-    pub(crate) texture_bind_group_cache: HashMap<MaterialId, wgpu::BindGroup>,
+    /// With the material flags they were created for, as those decide the layout.
+    pub(crate) texture_bind_group_cache: HashMap<MaterialId, (u32, wgpu::BindGroup)>,
+    /// Factors of materials that have any, rewritten every frame.
+    pub(crate) material_uniform_buffer_cache: HashMap<MaterialId, UniformBuffer>,
 
-    pub(crate) pipeline_cache: HashMap<u32, wgpu::RenderPipeline>,
+    pub(crate) pipeline_cache: HashMap<MeshPipelineKey, wgpu::RenderPipeline>,
     pub material_cache: MaterialCache,
 
     // For mesh batching.
     pub(crate) instance_cache: HashMap<MeshId, InstanceMetadata>,
+
+    /// Indices of the extracted meshes in drawing order. Opaque and alpha-cut meshes
+    /// come first, then blended ones back to front.
+    draw_order: Vec<usize>,
+    /// Blended meshes, when they are drawn order-independently instead.
+    oit_draw_order: Vec<usize>,
 }
 
 pub(crate) struct InstanceMetadata {
@@ -454,33 +633,85 @@ pub(crate) struct InstanceMetadata {
 
 impl MeshRenderResources {
     pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let mut light_bind_group_entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Shadow cascades.
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(SamplerBindingType::Comparison),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: POINT_SHADOW_BINDING,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+        ];
+        // Reflection probes.
+        light_bind_group_entries.extend(ProbeRenderResources::layout_entries(4));
+        if render_server.get_settings().render_path == RenderPath::ForwardPlus {
+            light_bind_group_entries.extend(ClusterRenderResources::layout_entries(
+                CLUSTER_FIRST_BINDING,
+            ));
+        }
+
         let light_bind_group_layout =
             render_server
                 .device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
+                    entries: &light_bind_group_entries,
                     label: Some("mesh light bind group layout"),
                 });
 
         Self {
             light_bind_group_layout,
             light_uniform_buffer: None,
+            light_bind_group_generations: (0, 0),
             texture_bind_group_layout_cache: Default::default(),
             light_bind_group: None,
             texture_bind_group_cache: HashMap::new(),
+            material_uniform_buffer_cache: HashMap::new(),
 
             pipeline_cache: Default::default(),
             material_cache: MaterialCache::new(),
             instance_cache: HashMap::new(),
+            draw_order: vec![],
+            oit_draw_order: vec![],
         }
     }
 
@@ -499,13 +730,11 @@ impl MeshRenderResources {
             let label = "mesh textures bind group layout";
 
             let mut bind_group_layout_entries = vec![];
-            let mut next_binding = 0;
 
-            // Color texture.
-            if material.color_texture.is_some() {
+            let mut push_texture = |binding: u32, visibility: wgpu::ShaderStages| {
                 bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
-                    binding: next_binding,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    binding,
+                    visibility,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
                         view_dimension: wgpu::TextureViewDimension::D2,
@@ -513,42 +742,55 @@ impl MeshRenderResources {
                     },
                     count: None,
                 });
-                next_binding += 1;
 
                 bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
-                    binding: next_binding,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    binding: binding + 1,
+                    visibility,
                     ty: wgpu::BindingType::Sampler {
                         0: SamplerBindingType::Filtering,
                     },
                     count: None,
                 });
-                next_binding += 1;
+            };
+
+            // Color texture.
+            if material.color_texture.is_some() {
+                push_texture(COLOR_TEXTURE_BINDING, wgpu::ShaderStages::FRAGMENT);
             }
 
             // Normal texture.
             if material.normal_texture.is_some() {
-                bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
-                    binding: next_binding,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                });
-                next_binding += 1;
+                push_texture(NORMAL_TEXTURE_BINDING, wgpu::ShaderStages::FRAGMENT);
+            }
 
+            // Emissive texture.
+            if material.is_emissive() && material.emissive_texture.is_some() {
+                push_texture(EMISSIVE_TEXTURE_BINDING, wgpu::ShaderStages::FRAGMENT);
+            }
+
+            // Wind noise, sampled when moving the vertices.
+            if matches!(
+                material.vertex_animation,
+                VertexAnimation::Wind {
+                    noise_texture: Some(_),
+                    ..
+                }
+            ) {
+                push_texture(WIND_NOISE_TEXTURE_BINDING, wgpu::ShaderStages::VERTEX);
+            }
+
+            // Emissive factor, alpha cutoff and wind.
+            if material.has_uniform() {
                 bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
-                    binding: next_binding,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler {
-                        0: SamplerBindingType::Filtering,
+                    binding: MATERIAL_UNIFORM_BINDING,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
                     },
                     count: None,
                 });
-                next_binding += 1;
             }
 
             let mesh_textures_bind_group_layout =
@@ -571,31 +813,77 @@ impl MeshRenderResources {
         self.texture_bind_group_layout_cache.get(&flags).unwrap()
     }
 
-    pub fn prepare_lights(&mut self, render_server: &RenderServer, lights: &ExtractedLights) {
+    pub(crate) fn prepare_lights(
+        &mut self,
+        render_server: &RenderServer,
+        lights: &ExtractedLights,
+        shadow_render_resources: &ShadowRenderResources,
+        probe_render_resources: &ProbeRenderResources,
+        cluster_render_resources: Option<&ClusterRenderResources>,
+        texture_cache: &TextureCache,
+    ) {
         let light_uniform_size = mem::size_of::<LightUniform>();
 
-        if self.light_bind_group.is_none() {
+        if self.light_uniform_buffer.is_none() {
             // We'll want to update our lights position, so we use COPY_DST.
-            let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("light uniform buffer"),
-                size: light_uniform_size as BufferAddress,
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
+            let buffer = render_server
+                .create_uniform_buffer("light uniform buffer", light_uniform_size as BufferAddress);
+
+            self.light_uniform_buffer = Some(buffer);
+        }
+
+        // Shadow maps are recreated when their size changes, and probes change with the camera.
+        let generations = (
+            shadow_render_resources.generation,
+            probe_render_resources.generation,
+        );
+
+        if self.light_bind_group.is_none() || self.light_bind_group_generations != generations {
+            let mut entries = vec![
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self
+                        .light_uniform_buffer
+                        .as_ref()
+                        .unwrap()
+                        .as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: shadow_render_resources.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &shadow_render_resources.array_view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&shadow_render_resources.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: POINT_SHADOW_BINDING,
+                    resource: wgpu::BindingResource::TextureView(
+                        &shadow_render_resources.point_view,
+                    ),
+                },
+            ];
+            entries.extend(probe_render_resources.bind_group_entries(4, texture_cache));
+            if let Some(cluster_render_resources) = cluster_render_resources {
+                entries.extend(cluster_render_resources.bind_group_entries(CLUSTER_FIRST_BINDING));
+            }
 
             let bind_group = render_server
                 .device
                 .create_bind_group(&wgpu::BindGroupDescriptor {
                     layout: &self.light_bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: buffer.as_entire_binding(),
-                    }],
+                    entries: &entries,
                     label: None,
                 });
 
             self.light_bind_group = Some(bind_group);
-            self.light_uniform_buffer = Some(buffer);
+            self.light_bind_group_generations = generations;
         }
 
         let mut light_uniform = LightUniform::default();
@@ -637,14 +925,41 @@ impl MeshRenderResources {
 
             self.add_texture_bind_group_layout(&render_server.device, &pair.1);
 
+            // The factor may change at any time.
+            if pair.1.has_uniform() {
+                let buffer = self
+                    .material_uniform_buffer_cache
+                    .entry(pair.0)
+                    .or_insert_with(|| {
+                        render_server.create_uniform_buffer(
+                            "material uniform buffer",
+                            mem::size_of::<MaterialUniform>() as BufferAddress,
+                        )
+                    });
+
+                render_server.queue.write_buffer(
+                    buffer,
+                    0,
+                    bytemuck::cast_slice(&[pair.1.get_uniform()]),
+                );
+            }
+
             let bind_group_layout = self.get_texture_bind_group_layout(&pair.1);
 
-            let bind_group = self.texture_bind_group_cache.get(&pair.0);
-            if (bind_group.is_some()) {
-                continue;
+            // Toggling a feature at runtime changes the layout.
+            let flags = pair.1.get_flags();
+            if let Some((cached_flags, _)) = self.texture_bind_group_cache.get(&pair.0) {
+                if *cached_flags == flags {
+                    continue;
+                }
             }
 
-            let bind_group_entries = pair.1.get_bind_group_entries(&texture_cache);
+            let bind_group_entries = pair.1.get_bind_group_entries(
+                texture_cache,
+                self.material_uniform_buffer_cache
+                    .get(&pair.0)
+                    .map(|b| &**b),
+            );
 
             // Create a texture bind group for each material.
             let bind_group = render_server
@@ -655,7 +970,8 @@ impl MeshRenderResources {
                     label: None,
                 });
 
-            self.texture_bind_group_cache.insert(pair.0, bind_group);
+            self.texture_bind_group_cache
+                .insert(pair.0, (flags, bind_group));
         }
     }
 
@@ -665,11 +981,23 @@ impl MeshRenderResources {
         shader_maker: &mut ShaderMaker,
         camera_bind_group_layout: &wgpu::BindGroupLayout,
         material_id: Option<MaterialId>,
+        vertex_layout: &VertexLayout,
     ) {
+        // Not drawable by the standard mesh shader, see Mesh::from_vertices.
+        if !is_standard_layout(vertex_layout) {
+            return;
+        }
+
         if (material_id.is_none()) {
             const PLAIN_MATERTIAL_FLAGS: u32 = 0;
 
-            let pipeline = self.pipeline_cache.get(&PLAIN_MATERTIAL_FLAGS);
+            let key = MeshPipelineKey {
+                material_flags: PLAIN_MATERTIAL_FLAGS,
+                depth_stencil: DepthStencilConfig::opaque(),
+                vertex_layout: vertex_layout.clone(),
+            };
+
+            let pipeline = self.pipeline_cache.get(&key);
 
             // Create new pipeline.
             if pipeline.is_none() {
@@ -690,24 +1018,31 @@ impl MeshRenderResources {
                     let shader = wgpu::ShaderModuleDescriptor {
                         label: Some("standard material shader"),
                         source: shader_maker
-                            .make_shader(include_str!("../shaders/mesh.wgsl"), &[])
+                            .make_shader(
+                                include_str!("../shaders/mesh.wgsl"),
+                                &[
+                                    vertex_shader_defs(vertex_layout),
+                                    lighting_shader_defs(render_server),
+                                ]
+                                .concat(),
+                            )
                             .unwrap(),
                     };
 
                     create_render_pipeline(
-                        &render_server.device,
+                        render_server,
                         &pipeline_layout,
-                        render_server.surface_config.format,
-                        Some(Texture::DEPTH_FORMAT),
-                        &[Vertex3d::desc(), InstanceRaw::desc()],
+                        render_server.scene_format(),
+                        &[vertex_layout.as_buffer_layout(), InstanceRaw::desc()],
                         shader,
                         "standard material pipeline",
                         false,
                         Some(wgpu::Face::Back),
+                        key.depth_stencil.clone(),
                     )
                 };
 
-                self.pipeline_cache.insert(PLAIN_MATERTIAL_FLAGS, pipeline);
+                self.pipeline_cache.insert(key, pipeline);
             }
         } else {
             let material = self
@@ -720,7 +1055,13 @@ impl MeshRenderResources {
 
             self.add_texture_bind_group_layout(&render_server.device, &material);
 
-            let pipeline = self.pipeline_cache.get(&flags);
+            let key = MeshPipelineKey {
+                material_flags: flags,
+                depth_stencil: material.get_depth_stencil(),
+                vertex_layout: vertex_layout.clone(),
+            };
+
+            let pipeline = self.pipeline_cache.get(&key);
 
             // Create new pipeline.
             if pipeline.is_none() {
@@ -738,39 +1079,95 @@ impl MeshRenderResources {
                         },
                     );
 
+                    let oit = material.is_blended()
+                        && render_server.get_settings().order_independent_transparency;
+
+                    let mut shader_defs = material.get_shader_defs();
+                    shader_defs.extend(vertex_shader_defs(vertex_layout));
+                    shader_defs.extend(lighting_shader_defs(render_server));
+                    if oit {
+                        shader_defs.push("OIT");
+                    }
+
                     // Shader descriptor, not a shader module yet.
                     let shader = wgpu::ShaderModuleDescriptor {
                         label: Some("standard material shader"),
                         source: shader_maker
                             .make_shader(
                                 include_str!("../shaders/mesh.wgsl"),
-                                material.get_shader_defs().as_slice(),
+                                shader_defs.as_slice(),
                             )
                             .unwrap(),
                     };
 
-                    create_render_pipeline(
-                        &render_server.device,
-                        &pipeline_layout,
-                        render_server.surface_config.format,
-                        Some(Texture::DEPTH_FORMAT),
-                        &[Vertex3d::desc(), InstanceRaw::desc()],
-                        shader,
-                        "standard material pipeline",
-                        false,
-                        Some(wgpu::Face::Back),
-                    )
+                    if oit {
+                        create_oit_pipeline(
+                            render_server,
+                            &pipeline_layout,
+                            &[vertex_layout.as_buffer_layout(), InstanceRaw::desc()],
+                            shader,
+                            "standard material oit pipeline",
+                            material.get_cull_mode(),
+                            key.depth_stencil.clone(),
+                        )
+                    } else {
+                        create_render_pipeline(
+                            render_server,
+                            &pipeline_layout,
+                            render_server.scene_format(),
+                            &[vertex_layout.as_buffer_layout(), InstanceRaw::desc()],
+                            shader,
+                            "standard material pipeline",
+                            material.is_blended(),
+                            material.get_cull_mode(),
+                            key.depth_stencil.clone(),
+                        )
+                    }
                 };
 
-                self.pipeline_cache.insert(flags, pipeline);
+                self.pipeline_cache.insert(key, pipeline);
             }
         }
     }
 
-    pub fn get_pipeline(&self, material: &MaterialStandard) -> &wgpu::RenderPipeline {
-        let flags = material.get_flags();
+    /// Whether blended meshes are left for the OIT passes this frame.
+    pub(crate) fn has_oit_meshes(&self) -> bool {
+        !self.oit_draw_order.is_empty()
+    }
+
+    /// Meshes drawn this frame after culling, OIT ones included.
+    pub(crate) fn draw_count(&self) -> usize {
+        self.draw_order.len() + self.oit_draw_order.len()
+    }
 
-        self.pipeline_cache.get(&flags).unwrap()
+    /// Blended materials don't write depth, so they don't cast shadows either.
+    pub(crate) fn casts_shadow(&self, extracted: &ExtractedMesh) -> bool {
+        extracted
+            .material_id
+            .and_then(|id| self.material_cache.get(&id))
+            .is_none_or(|material| material.get_depth_stencil().depth_write)
+    }
+
+    /// Back faces are culled unless the material is double-sided.
+    pub(crate) fn cull_mode(&self, extracted: &ExtractedMesh) -> Option<wgpu::Face> {
+        extracted
+            .material_id
+            .and_then(|id| self.material_cache.get(&id))
+            .map_or(Some(wgpu::Face::Back), |material| material.get_cull_mode())
+    }
+
+    pub fn get_pipeline(
+        &self,
+        material: &MaterialStandard,
+        vertex_layout: &VertexLayout,
+    ) -> &wgpu::RenderPipeline {
+        let key = MeshPipelineKey {
+            material_flags: material.get_flags(),
+            depth_stencil: material.get_depth_stencil(),
+            vertex_layout: vertex_layout.clone(),
+        };
+
+        self.pipeline_cache.get(&key).unwrap()
     }
 
     /// Draw multiple models.
@@ -825,13 +1222,19 @@ impl MeshRenderResources {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn prepare_meshes(
     extracted_meshes: &Vec<ExtractedMesh>,
+    mesh_cache: &MeshCache,
     extracted_lights: &ExtractedLights,
     texture_cache: &TextureCache,
     shader_maker: &mut ShaderMaker,
     mesh_render_resources: &mut MeshRenderResources,
     camera_render_resources: &CameraRenderResources,
+    shadow_render_resources: &ShadowRenderResources,
+    probe_render_resources: &ProbeRenderResources,
+    cluster_render_resources: Option<&ClusterRenderResources>,
+    camera_position: Vector3<f32>,
     render_server: &RenderServer,
 ) {
     //
@@ -845,24 +1248,87 @@ pub(crate) fn prepare_meshes(
     //     usage: wgpu::BufferUsages::VERTEX,
     // });
 
-    for mesh in extracted_meshes {
+    for extracted in extracted_meshes {
+        let Some(mesh) = mesh_cache.get(extracted.mesh_id) else {
+            continue;
+        };
+
         mesh_render_resources.prepare_materials(&texture_cache, render_server);
 
         mesh_render_resources.prepare_pipeline(
             render_server,
             shader_maker,
             &camera_render_resources.bind_group_layout,
-            mesh.material_id,
+            extracted.material_id,
+            &mesh.vertex_layout,
         );
     }
 
-    mesh_render_resources.prepare_lights(render_server, extracted_lights);
+    mesh_render_resources.prepare_lights(
+        render_server,
+        extracted_lights,
+        shadow_render_resources,
+        probe_render_resources,
+        cluster_render_resources,
+        texture_cache,
+    );
 
     mesh_render_resources.prepare_instances(render_server, &extracted_meshes);
+
+    let (mut draw_order, blended) = sort_meshes(
+        extracted_meshes,
+        &mesh_render_resources.material_cache,
+        camera_position,
+    );
+
+    if render_server.get_settings().order_independent_transparency {
+        mesh_render_resources.oit_draw_order = blended;
+    } else {
+        draw_order.extend(blended);
+    }
+
+    mesh_render_resources.draw_order = draw_order;
+}
+
+/// Leave the meshes `is_hidden` returns true for out of this frame's draws.
+pub(crate) fn cull_meshes(
+    extracted_meshes: &[ExtractedMesh],
+    mesh_render_resources: &mut MeshRenderResources,
+    is_hidden: impl Fn(&MeshId) -> bool,
+) {
+    mesh_render_resources
+        .draw_order
+        .retain(|i| !is_hidden(&extracted_meshes[*i].mesh_id));
+    mesh_render_resources
+        .oit_draw_order
+        .retain(|i| !is_hidden(&extracted_meshes[*i].mesh_id));
+}
+
+/// Opaque meshes, then blended ones farthest first, so they blend over everything
+/// behind them.
+fn sort_meshes(
+    extracted_meshes: &[ExtractedMesh],
+    material_cache: &MaterialCache,
+    camera_position: Vector3<f32>,
+) -> (Vec<usize>, Vec<usize>) {
+    let is_blended = |mesh: &ExtractedMesh| {
+        mesh.material_id
+            .and_then(|id| material_cache.get(&id))
+            .is_some_and(|material| material.is_blended())
+    };
+
+    let (order, mut blended): (Vec<usize>, Vec<usize>) =
+        (0..extracted_meshes.len()).partition(|i| !is_blended(&extracted_meshes[*i]));
+
+    let distance =
+        |i: &usize| (extracted_meshes[*i].transform.position - camera_position).magnitude2();
+    blended.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+
+    (order, blended)
 }
 
 pub(crate) fn render_meshes<'a, 'b: 'a>(
-    extracted_meshes: &'b Vec<ExtractedMesh>,
+    extracted_meshes: &'b [ExtractedMesh],
     mesh_cache: &'b MeshCache,
     mesh_render_resources: &'b MeshRenderResources,
     camera_render_resources: &'b CameraRenderResources,
@@ -876,13 +1342,58 @@ pub(crate) fn render_meshes<'a, 'b: 'a>(
         return;
     }
 
-    let camera_bind_group = camera_render_resources.bind_group.as_ref().unwrap();
+    draw_meshes(
+        &mesh_render_resources.draw_order,
+        extracted_meshes,
+        mesh_cache,
+        mesh_render_resources,
+        camera_render_resources,
+        render_pass,
+    );
 
-    let light_bind_group = mesh_render_resources.light_bind_group.as_ref().unwrap();
+    gizmo_render_resources.render(
+        render_pass,
+        camera_render_resources.bind_group.as_ref().unwrap(),
+    );
+}
 
-    for extracted in extracted_meshes {
+/// Draw the blended meshes left out of `render_meshes`, into the OIT targets.
+pub(crate) fn render_oit_meshes<'a, 'b: 'a>(
+    extracted_meshes: &'b [ExtractedMesh],
+    mesh_cache: &'b MeshCache,
+    mesh_render_resources: &'b MeshRenderResources,
+    camera_render_resources: &'b CameraRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+) {
+    draw_meshes(
+        &mesh_render_resources.oit_draw_order,
+        extracted_meshes,
+        mesh_cache,
+        mesh_render_resources,
+        camera_render_resources,
+        render_pass,
+    );
+}
+
+fn draw_meshes<'a, 'b: 'a>(
+    draw_order: &'b [usize],
+    extracted_meshes: &'b [ExtractedMesh],
+    mesh_cache: &'b MeshCache,
+    mesh_render_resources: &'b MeshRenderResources,
+    camera_render_resources: &'b CameraRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+) {
+    let Some(camera_bind_group) = &camera_render_resources.bind_group else {
+        return;
+    };
+    let Some(light_bind_group) = &mesh_render_resources.light_bind_group else {
+        return;
+    };
+
+    for extracted in draw_order.iter().filter_map(|i| extracted_meshes.get(*i)) {
         let mut texture_bind_group = None;
         let mut flags = 0;
+        let mut depth_stencil = DepthStencilConfig::opaque();
 
         if (extracted.material_id.is_some()) {
             let material_id = &extracted.material_id.unwrap();
@@ -891,6 +1402,7 @@ pub(crate) fn render_meshes<'a, 'b: 'a>(
                 mesh_render_resources
                     .texture_bind_group_cache
                     .get(material_id)
+                    .map(|(_, bind_group)| bind_group)
                     .unwrap(),
             );
 
@@ -899,12 +1411,22 @@ pub(crate) fn render_meshes<'a, 'b: 'a>(
                 .get(material_id)
                 .unwrap();
             flags = material.get_flags();
+            depth_stencil = material.get_depth_stencil();
         }
 
-        let pipeline = mesh_render_resources.pipeline_cache.get(&flags).unwrap();
-
         let mesh = mesh_cache.get(extracted.mesh_id).unwrap();
 
+        let key = MeshPipelineKey {
+            material_flags: flags,
+            depth_stencil,
+            vertex_layout: mesh.vertex_layout.clone(),
+        };
+
+        // Missing if the vertex layout isn't supported.
+        let Some(pipeline) = mesh_render_resources.pipeline_cache.get(&key) else {
+            continue;
+        };
+
         let instance = mesh_render_resources
             .instance_cache
             .get(&extracted.mesh_id)
@@ -933,9 +1455,4 @@ pub(crate) fn render_meshes<'a, 'b: 'a>(
 
         render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
     }
-
-    gizmo_render_resources.render(
-        render_pass,
-        camera_render_resources.bind_group.as_ref().unwrap(),
-    );
 }
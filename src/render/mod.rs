@@ -1,24 +1,78 @@
 pub(crate) mod allocator;
+pub(crate) mod animated_texture;
 pub(crate) mod atlas;
+pub(crate) mod backdrop;
+pub(crate) mod bloom;
+pub(crate) mod cubemap;
+pub(crate) mod debug_draw;
+pub(crate) mod frame_recorder;
 pub(crate) mod gizmo;
+pub(crate) mod memory;
 pub(crate) mod mesh;
+pub(crate) mod render_context;
 pub(crate) mod render_server;
 pub(crate) mod texture;
 pub(crate) mod vertex;
 
 pub(crate) mod light;
+pub(crate) mod light2d;
+pub(crate) mod line2d;
 
+pub use animated_texture::*;
+pub use camera::{FovAngles, OffAxisProjection};
+pub use cubemap::*;
+pub use draw_command::{DrawCommands, DrawSource};
+pub use frame_recorder::{FrameRecorder, RecordingFormat};
+pub use gizmo::GridSettings;
+pub use light2d::Light2dKind;
+pub use material::{MaterialCache, MaterialId, MaterialStandard, Transparency, VertexAnimation};
+pub use memory::{GpuMemoryUsage, UniformBuffer};
 pub use mesh::*;
+pub use morph::{MorphTarget, MAX_MORPH_TARGETS};
+pub use post_process::*;
+pub use render_context::RenderContext;
 pub use render_server::*;
+pub use resolution_scale::{DynamicResolution, ResolutionScale, UpscaleFilter};
+pub use shadow::ShadowSettings;
+pub use sprite3d::BillboardMode;
+pub use sprite_atlas::{SpriteAtlas, SpriteAtlasRegion};
+pub use stats::RenderStats;
 pub use texture::*;
+pub use transition::{ScreenTransition, TransitionCallback, TransitionKind};
+pub use ui_shape::{UiShape, UiShapeKind};
+pub use vector_texture::VectorTexture;
+pub use vertex::{CustomVertex, VertexLayout, VertexLayoutBuilder, VERTEX_COLOR_LOCATION};
+pub use water::WaterReflection;
 
 mod bind_group;
 pub(crate) mod camera;
+pub(crate) mod clip;
+pub(crate) mod cluster;
+pub(crate) mod contact_shadow;
+pub(crate) mod curve2d;
+pub(crate) mod display_output;
 pub(crate) mod draw_command;
+pub(crate) mod frame_graph;
+pub(crate) mod globals;
+pub(crate) mod label3d;
 pub(crate) mod material;
+pub(crate) mod morph;
+pub(crate) mod occlusion;
+pub(crate) mod oit;
+pub(crate) mod post_process;
+pub(crate) mod probe;
 pub(crate) mod render_world;
+pub(crate) mod resolution_scale;
 pub(crate) mod shader_maker;
+pub(crate) mod shadow;
 pub(crate) mod sky;
 pub(crate) mod sprite;
+pub(crate) mod sprite3d;
+pub(crate) mod sprite_atlas;
+pub(crate) mod stats;
+pub(crate) mod trail;
+pub(crate) mod transition;
+pub(crate) mod ui_shape;
 pub(crate) mod vector_texture;
 pub(crate) mod view;
+pub(crate) mod water;
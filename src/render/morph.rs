@@ -0,0 +1,423 @@
+use crate::render::mesh::{MeshCache, MeshId};
+use crate::render::vertex::VertexLayout;
+use crate::render::{RenderServer, UniformBuffer};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::mem;
+use wgpu::util::DeviceExt;
+use wgpu::BufferAddress;
+
+/// Most morph targets a mesh can have.
+pub const MAX_MORPH_TARGETS: usize = 64;
+
+/// Vertices handled by one compute workgroup, see morph.wgsl.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// A blend shape: offsets added to the vertices of a mesh, scaled by a weight.
+#[derive(Debug, Clone)]
+pub struct MorphTarget {
+    pub name: String,
+    /// One per vertex, in model space.
+    pub position_deltas: Vec<[f32; 3]>,
+    /// One per vertex, or empty to leave the normals alone.
+    pub normal_deltas: Vec<[f32; 3]>,
+}
+
+/// Morph targets of a mesh and the vertices they're applied to.
+pub(crate) struct MorphData {
+    pub(crate) targets: Vec<MorphTarget>,
+    vertex_count: usize,
+    /// In bytes.
+    stride: usize,
+    position_offset: usize,
+    normal_offset: Option<usize>,
+    /// Vertex buffer contents with no target applied, for blending on the CPU.
+    base_vertices: Vec<u8>,
+    base_buffer: wgpu::Buffer,
+    /// Position and normal delta of each vertex, target after target.
+    delta_buffer: wgpu::Buffer,
+}
+
+impl MorphData {
+    /// Positions have to be `Float32x3` at location 0, normals `Float32x3` at location 2.
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        name: &str,
+        vertex_layout: &VertexLayout,
+        vertices: &[u8],
+        targets: Vec<MorphTarget>,
+    ) -> Result<Self> {
+        let float3_offset = |location| {
+            vertex_layout
+                .attributes
+                .iter()
+                .find(|a| {
+                    a.shader_location == location && a.format == wgpu::VertexFormat::Float32x3
+                })
+                .map(|a| a.offset as usize)
+        };
+
+        let Some(position_offset) = float3_offset(0) else {
+            bail!("Mesh {} has no Float32x3 position to morph", name);
+        };
+        let normal_offset = float3_offset(2);
+
+        if targets.len() > MAX_MORPH_TARGETS {
+            bail!(
+                "Mesh {} has {} morph targets, at most {} are supported",
+                name,
+                targets.len(),
+                MAX_MORPH_TARGETS
+            );
+        }
+
+        let stride = vertex_layout.array_stride as usize;
+        let vertex_count = vertices.len() / stride;
+
+        for target in &targets {
+            let normals_fit =
+                target.normal_deltas.is_empty() || target.normal_deltas.len() == vertex_count;
+
+            if target.position_deltas.len() != vertex_count || !normals_fit {
+                bail!(
+                    "Morph target {} of mesh {} doesn't have one delta per vertex",
+                    target.name,
+                    name
+                );
+            }
+        }
+
+        let mut deltas: Vec<[f32; 4]> = Vec::with_capacity(targets.len() * vertex_count * 2);
+        for target in &targets {
+            for (i, position) in target.position_deltas.iter().enumerate() {
+                let normal = target.normal_deltas.get(i).unwrap_or(&[0.0; 3]);
+
+                deltas.push([position[0], position[1], position[2], 0.0]);
+                deltas.push([normal[0], normal[1], normal[2], 0.0]);
+            }
+        }
+        // Storage buffers can't be empty.
+        if deltas.is_empty() {
+            deltas.push([0.0; 4]);
+        }
+
+        let base_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} morph base buffer", name)),
+            contents: vertices,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let delta_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} morph delta buffer", name)),
+            contents: bytemuck::cast_slice(&deltas),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        Ok(Self {
+            targets,
+            vertex_count,
+            stride,
+            position_offset,
+            normal_offset,
+            base_vertices: vertices.to_vec(),
+            base_buffer,
+            delta_buffer,
+        })
+    }
+
+    /// Apply the targets to the base vertices on the CPU.
+    fn blend(&self, weights: &[f32]) -> Vec<u8> {
+        let mut vertices = self.base_vertices.clone();
+
+        let read = |bytes: &[u8], offset: usize| -> [f32; 3] {
+            bytemuck::pod_read_unaligned(&bytes[offset..offset + 12])
+        };
+
+        for i in 0..self.vertex_count {
+            let vertex = i * self.stride;
+
+            let mut position = read(&vertices, vertex + self.position_offset);
+            let mut normal = self
+                .normal_offset
+                .map(|offset| read(&vertices, vertex + offset));
+
+            for (target, weight) in self.targets.iter().zip(weights) {
+                if *weight == 0.0 {
+                    continue;
+                }
+
+                for (value, delta) in position.iter_mut().zip(target.position_deltas[i]) {
+                    *value += delta * weight;
+                }
+
+                if let (Some(normal), Some(delta)) = (&mut normal, target.normal_deltas.get(i)) {
+                    for (value, delta) in normal.iter_mut().zip(delta) {
+                        *value += delta * weight;
+                    }
+                }
+            }
+
+            let offset = vertex + self.position_offset;
+            vertices[offset..offset + 12].copy_from_slice(bytemuck::cast_slice(&position));
+
+            if let (Some(offset), Some(normal)) = (self.normal_offset, normal) {
+                let length = normal.iter().map(|n| n * n).sum::<f32>().sqrt().max(1e-6);
+                let normal = normal.map(|n| n / length);
+
+                let offset = vertex + offset;
+                vertices[offset..offset + 12].copy_from_slice(bytemuck::cast_slice(&normal));
+            }
+        }
+
+        vertices
+    }
+
+    /// GPU memory used by the base vertices and target deltas.
+    pub(crate) fn get_memory_size(&self) -> u64 {
+        self.base_buffer.size() + self.delta_buffer.size()
+    }
+}
+
+/// Weights of the morph targets of a mesh, in the mesh's target order.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtractedMorphWeights {
+    pub(crate) mesh_id: MeshId,
+    pub(crate) weights: Vec<f32>,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MorphParamsUniform {
+    weights: [[f32; 4]; MAX_MORPH_TARGETS / 4],
+    vertex_count: u32,
+    target_count: u32,
+    /// The rest are in floats, not bytes.
+    stride: u32,
+    position_offset: u32,
+    normal_offset: u32,
+    has_normals: u32,
+    _pad: [u32; 2],
+}
+
+struct MorphMeshResources {
+    params_buffer: UniformBuffer,
+    bind_group: wgpu::BindGroup,
+    workgroup_count: u32,
+}
+
+/// Blends morph targets into the vertex buffers of their meshes, when the weights change.
+///
+/// Meshes are shared, so models using the same mesh also share its weights.
+pub(crate) struct MorphRenderResources {
+    /// None if compute shaders aren't supported, then targets are blended on the CPU.
+    pipeline: Option<(wgpu::BindGroupLayout, wgpu::ComputePipeline)>,
+    meshes: HashMap<MeshId, MorphMeshResources>,
+    /// Weights the vertex buffers currently hold.
+    applied_weights: HashMap<MeshId, Vec<f32>>,
+    /// Meshes to blend on the GPU this frame.
+    pending: Vec<MeshId>,
+}
+
+impl MorphRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let pipeline = render_server
+            .supports_compute()
+            .then(|| create_morph_pipeline(&render_server.device));
+
+        if pipeline.is_none() {
+            log::info!("Compute shaders aren't supported, morph targets are blended on the CPU");
+        }
+
+        Self {
+            pipeline,
+            meshes: HashMap::new(),
+            applied_weights: HashMap::new(),
+            pending: vec![],
+        }
+    }
+
+    pub(crate) fn remove(&mut self, mesh_id: MeshId) {
+        self.meshes.remove(&mesh_id);
+        self.applied_weights.remove(&mesh_id);
+    }
+}
+
+fn create_morph_pipeline(device: &wgpu::Device) -> (wgpu::BindGroupLayout, wgpu::ComputePipeline) {
+    let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            storage_entry(1, true),
+            storage_entry(2, true),
+            storage_entry(3, false),
+        ],
+        label: Some("morph bind group layout"),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("morph pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("morph shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/morph.wgsl").into()),
+    });
+
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("morph pipeline"),
+        layout: Some(&pipeline_layout),
+        module: &shader_module,
+        entry_point: "cs_main",
+    });
+
+    (bind_group_layout, pipeline)
+}
+
+pub(crate) fn prepare_morph_targets(
+    extracted_weights: &[ExtractedMorphWeights],
+    mesh_cache: &MeshCache,
+    render_resources: &mut MorphRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.pending.clear();
+
+    for extracted in extracted_weights {
+        let Some(mesh) = mesh_cache.get(extracted.mesh_id) else {
+            continue;
+        };
+        let Some(morph) = &mesh.morph else {
+            continue;
+        };
+
+        let mut weights = extracted.weights.clone();
+        weights.resize(morph.targets.len(), 0.0);
+
+        // The vertex buffer starts out with no target applied.
+        let applied = render_resources
+            .applied_weights
+            .entry(extracted.mesh_id)
+            .or_insert_with(|| vec![0.0; morph.targets.len()]);
+        if *applied == weights {
+            continue;
+        }
+        *applied = weights.clone();
+
+        let Some((bind_group_layout, _)) = &render_resources.pipeline else {
+            render_server
+                .queue
+                .write_buffer(&mesh.vertex_buffer, 0, &morph.blend(&weights));
+            continue;
+        };
+
+        let resources = render_resources
+            .meshes
+            .entry(extracted.mesh_id)
+            .or_insert_with(|| {
+                let device = &render_server.device;
+
+                let params_buffer = render_server.create_uniform_buffer(
+                    "morph params buffer",
+                    mem::size_of::<MorphParamsUniform>() as BufferAddress,
+                );
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: morph.base_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: morph.delta_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: mesh.vertex_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("morph bind group"),
+                });
+
+                MorphMeshResources {
+                    params_buffer,
+                    bind_group,
+                    workgroup_count: (morph.vertex_count as u32).div_ceil(WORKGROUP_SIZE),
+                }
+            });
+
+        let mut params = MorphParamsUniform {
+            weights: [[0.0; 4]; MAX_MORPH_TARGETS / 4],
+            vertex_count: morph.vertex_count as u32,
+            target_count: morph.targets.len() as u32,
+            stride: (morph.stride / 4) as u32,
+            position_offset: (morph.position_offset / 4) as u32,
+            normal_offset: morph.normal_offset.unwrap_or(0) as u32 / 4,
+            has_normals: morph.normal_offset.is_some() as u32,
+            _pad: [0; 2],
+        };
+        for (i, weight) in weights.iter().enumerate() {
+            params.weights[i / 4][i % 4] = *weight;
+        }
+
+        render_server.queue.write_buffer(
+            &resources.params_buffer,
+            0,
+            bytemuck::cast_slice(&[params]),
+        );
+
+        render_resources.pending.push(extracted.mesh_id);
+    }
+}
+
+/// Blend the meshes whose weights changed. Has to come before anything draws them.
+pub(crate) fn render_morph_targets(
+    render_resources: &MorphRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    let Some((_, pipeline)) = &render_resources.pipeline else {
+        return;
+    };
+    if render_resources.pending.is_empty() {
+        return;
+    }
+
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("morph pass"),
+        timestamp_writes: None,
+    });
+
+    compute_pass.set_pipeline(pipeline);
+
+    for mesh_id in &render_resources.pending {
+        let resources = &render_resources.meshes[mesh_id];
+
+        compute_pass.set_bind_group(0, &resources.bind_group, &[]);
+        compute_pass.dispatch_workgroups(resources.workgroup_count, 1, 1);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::math::transform::Transform3d;
+use crate::physics::Aabb;
+use crate::render::{MeshId, RenderServer};
+use cgmath::{Matrix4, Vector3};
+use std::collections::HashSet;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+use wgpu::BufferAddress;
+
+/// Flagged models beyond this are always drawn.
+const MAX_OCCLUSION_QUERIES: u32 = 256;
+
+/// A model that is only drawn if its bounds were visible, see `Model::occlusion_culling`.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtractedOcclusionQuery {
+    pub(crate) transform: Transform3d,
+    pub(crate) local_aabb: Aabb,
+    /// Skipped together if the bounds are hidden.
+    pub(crate) mesh_ids: Vec<MeshId>,
+}
+
+/// Where the query results are on their way back from the GPU.
+enum Readback {
+    /// New queries can be issued.
+    Idle,
+    /// Queries were resolved into the readback buffer this frame.
+    Copied,
+    /// Waiting for the readback buffer to be mapped.
+    Mapping(Arc<AtomicBool>),
+}
+
+/// Draws the bounding boxes of flagged models after everything else, counting the
+/// samples that pass the depth test. Models whose box had none are skipped in the
+/// following frames, until their box shows again.
+pub(crate) struct OcclusionRenderResources {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback: Readback,
+
+    pipeline: wgpu::RenderPipeline,
+    cube_vertex_buffer: wgpu::Buffer,
+    /// A box matrix per query.
+    instance_buffer: wgpu::Buffer,
+
+    /// Queries issued this frame.
+    query_count: u32,
+    /// Meshes of each query in the readback buffer.
+    in_flight: Vec<Vec<MeshId>>,
+    /// Meshes of all flagged models this frame.
+    flagged: HashSet<MeshId>,
+    /// Meshes whose bounds were hidden in the last results.
+    occluded: HashSet<MeshId>,
+}
+
+impl OcclusionRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("occlusion query set"),
+            ty: wgpu::QueryType::Occlusion,
+            count: MAX_OCCLUSION_QUERIES,
+        });
+
+        let results_size =
+            (MAX_OCCLUSION_QUERIES as usize * mem::size_of::<u64>()) as BufferAddress;
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion resolve buffer"),
+            size: results_size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion readback buffer"),
+            size: results_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let cube_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("occlusion cube vertex buffer"),
+            contents: bytemuck::cast_slice(&unit_cube_triangles()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("occlusion instance buffer"),
+            size: (MAX_OCCLUSION_QUERIES as usize * mem::size_of::<[[f32; 4]; 4]>())
+                as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("occlusion pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("occlusion shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/occlusion.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("occlusion pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<[f32; 3]>() as BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<[[f32; 4]; 4]>() as BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            1 => Float32x4, 2 => Float32x4, 3 => Float32x4, 4 => Float32x4
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                // Only the samples are counted.
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // The inside faces count too, in case the front ones are clipped.
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                depth_write_enabled: false,
+                depth_compare: render_server.depth_compare(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            readback: Readback::Idle,
+            pipeline,
+            cube_vertex_buffer,
+            instance_buffer,
+            query_count: 0,
+            in_flight: vec![],
+            flagged: HashSet::new(),
+            occluded: HashSet::new(),
+        }
+    }
+
+    /// For the main pass, if there are queries this frame.
+    pub(crate) fn query_set(&self) -> Option<&wgpu::QuerySet> {
+        (self.query_count > 0).then_some(&self.query_set)
+    }
+
+    /// Pick up the results of earlier queries, if they have arrived.
+    fn poll_readback(&mut self, render_server: &RenderServer) {
+        match &self.readback {
+            Readback::Idle => {}
+            Readback::Copied => {
+                let mapped = Arc::new(AtomicBool::new(false));
+                let callback_mapped = mapped.clone();
+
+                self.readback_buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        callback_mapped.store(result.is_ok(), Ordering::Release);
+                    });
+
+                self.readback = Readback::Mapping(mapped);
+            }
+            Readback::Mapping(mapped) => {
+                render_server.device.poll(wgpu::Maintain::Poll);
+
+                if !mapped.load(Ordering::Acquire) {
+                    return;
+                }
+
+                {
+                    let data = self.readback_buffer.slice(..).get_mapped_range();
+                    let samples: &[u64] = bytemuck::cast_slice(&data);
+
+                    self.occluded = self
+                        .in_flight
+                        .iter()
+                        .zip(samples)
+                        .filter(|(_, samples)| **samples == 0)
+                        .flat_map(|(mesh_ids, _)| mesh_ids.iter().copied())
+                        .collect();
+                }
+
+                self.readback_buffer.unmap();
+                self.readback = Readback::Idle;
+            }
+        }
+    }
+
+    /// Whether a mesh is hidden and shouldn't be drawn this frame.
+    pub(crate) fn is_occluded(&self, mesh_id: &MeshId) -> bool {
+        self.flagged.contains(mesh_id) && self.occluded.contains(mesh_id)
+    }
+}
+
+/// 36 vertices of the [0, 1] cube.
+fn unit_cube_triangles() -> Vec<[f32; 3]> {
+    let corner = |i: usize| [(i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32];
+
+    // Two triangles per face, winding doesn't matter without culling.
+    let faces = [
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+    ];
+
+    faces
+        .iter()
+        .flat_map(|f| [f[0], f[1], f[2], f[0], f[2], f[3]])
+        .map(corner)
+        .collect()
+}
+
+/// Collect results and decide which flagged models get tested this frame.
+pub(crate) fn prepare_occlusion(
+    queries: &[ExtractedOcclusionQuery],
+    camera_position: Option<Vector3<f32>>,
+    render_resources: &mut OcclusionRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.poll_readback(render_server);
+
+    render_resources.query_count = 0;
+    render_resources.flagged = queries
+        .iter()
+        .flat_map(|q| q.mesh_ids.iter().copied())
+        .collect();
+
+    let Some(camera_position) = camera_position else {
+        return;
+    };
+
+    let mut in_flight = vec![];
+    let mut boxes: Vec<[[f32; 4]; 4]> = vec![];
+
+    for query in queries {
+        let aabb = query
+            .local_aabb
+            .transformed_by(&query.transform.to_matrix().into());
+
+        // The near plane may cut a box the camera is in, so always draw those.
+        let margin = Vector3::new(0.5, 0.5, 0.5);
+        if Aabb::new(aabb.min - margin, aabb.max + margin).contains_point(camera_position) {
+            for mesh_id in &query.mesh_ids {
+                render_resources.occluded.remove(mesh_id);
+            }
+            continue;
+        }
+
+        if boxes.len() == MAX_OCCLUSION_QUERIES as usize {
+            break;
+        }
+
+        let size = aabb.max - aabb.min;
+        let box_matrix = Matrix4::from_translation(aabb.min)
+            * Matrix4::from_nonuniform_scale(size.x, size.y, size.z);
+
+        boxes.push(box_matrix.into());
+        in_flight.push(query.mesh_ids.clone());
+    }
+
+    // Wait for the previous results before reusing the buffers.
+    if boxes.is_empty() || !matches!(render_resources.readback, Readback::Idle) {
+        return;
+    }
+
+    render_server.queue.write_buffer(
+        &render_resources.instance_buffer,
+        0,
+        bytemuck::cast_slice(&boxes),
+    );
+
+    render_resources.query_count = boxes.len() as u32;
+    render_resources.in_flight = in_flight;
+    // Resolved by `resolve_occlusion_queries` at the end of this frame.
+    render_resources.readback = Readback::Copied;
+}
+
+/// Draw the boxes in the main pass, which has to use `query_set` and the scene depth.
+pub(crate) fn render_occlusion_queries<'a, 'b: 'a>(
+    render_resources: &'b OcclusionRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+) {
+    if render_resources.query_count == 0 {
+        return;
+    }
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+    render_pass.set_vertex_buffer(0, render_resources.cube_vertex_buffer.slice(..));
+    render_pass.set_vertex_buffer(1, render_resources.instance_buffer.slice(..));
+
+    for i in 0..render_resources.query_count {
+        render_pass.begin_occlusion_query(i);
+        render_pass.draw(0..36, i..i + 1);
+        render_pass.end_occlusion_query();
+    }
+}
+
+/// Copy the sample counts to where they can be read back.
+pub(crate) fn resolve_occlusion_queries(
+    render_resources: &OcclusionRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    if render_resources.query_count == 0 {
+        return;
+    }
+
+    let size = (render_resources.query_count as usize * mem::size_of::<u64>()) as BufferAddress;
+
+    encoder.resolve_query_set(
+        &render_resources.query_set,
+        0..render_resources.query_count,
+        &render_resources.resolve_buffer,
+        0,
+    );
+    encoder.copy_buffer_to_buffer(
+        &render_resources.resolve_buffer,
+        0,
+        &render_resources.readback_buffer,
+        0,
+        size,
+    );
+}
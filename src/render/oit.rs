@@ -0,0 +1,353 @@
+use crate::render::camera::CameraRenderResources;
+use crate::render::mesh::{render_oit_meshes, ExtractedMesh, MeshCache, MeshRenderResources};
+use crate::render::post_process::create_fullscreen_pipeline_with_target;
+use crate::render::{DepthStencilConfig, RenderServer, Texture, TextureCache, TextureId};
+
+/// Sum of the weighted premultiplied colors. Needs the range of a float target.
+const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+/// How much of the background still shows, starts at 1.
+const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// Weighted blended order-independent transparency. Blended meshes are drawn unsorted
+/// into an accumulation and a revealage target, which are then resolved over the scene.
+/// See `RenderSettings::order_independent_transparency`.
+pub(crate) struct OitRenderResources {
+    accum_texture: TextureId,
+    revealage_texture: TextureId,
+
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Rebuilt every frame, the targets may be recreated at any time.
+    bind_group: Option<wgpu::BindGroup>,
+    resolve_pipeline: wgpu::RenderPipeline,
+
+    /// If there are blended meshes to draw this frame.
+    pub(crate) enabled: bool,
+}
+
+impl OitRenderResources {
+    pub(crate) fn new(render_server: &RenderServer, texture_cache: &mut TextureCache) -> Self {
+        let device = &render_server.device;
+
+        let config = &render_server.surface_config;
+        let (accum_texture, revealage_texture) =
+            create_targets(render_server, texture_cache, (config.width, config.height));
+
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry(0), texture_entry(1)],
+            label: Some("oit bind group layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("oit resolve pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("oit resolve shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/oit.wgsl").into()),
+        });
+
+        // The resolved color isn't premultiplied. Keeps the alpha of the scene.
+        let over = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        let resolve_pipeline = create_fullscreen_pipeline_with_target(
+            render_server,
+            &pipeline_layout,
+            &shader_module,
+            "fs_main",
+            render_server.scene_format(),
+            over,
+            "oit resolve pipeline",
+        );
+
+        Self {
+            accum_texture,
+            revealage_texture,
+            bind_group_layout,
+            bind_group: None,
+            resolve_pipeline,
+            enabled: false,
+        }
+    }
+
+    /// Offscreen textures have to follow the size the 3D scene is drawn at.
+    pub(crate) fn recreate_textures(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        size: (u32, u32),
+    ) {
+        texture_cache.remove(self.accum_texture);
+        texture_cache.remove(self.revealage_texture);
+
+        (self.accum_texture, self.revealage_texture) =
+            create_targets(render_server, texture_cache, size);
+
+        self.bind_group = None;
+    }
+}
+
+fn create_targets(
+    render_server: &RenderServer,
+    texture_cache: &mut TextureCache,
+    size: (u32, u32),
+) -> (TextureId, TextureId) {
+    let mut config = render_server.surface_config.clone();
+    (config.width, config.height) = size;
+
+    config.format = ACCUM_FORMAT;
+    let accum_texture = Texture::create_render_texture(
+        &render_server.device,
+        texture_cache,
+        &config,
+        Some("oit accum texture"),
+    );
+
+    config.format = REVEALAGE_FORMAT;
+    let revealage_texture = Texture::create_render_texture(
+        &render_server.device,
+        texture_cache,
+        &config,
+        Some("oit revealage texture"),
+    );
+
+    (accum_texture, revealage_texture)
+}
+
+/// Like `create_render_pipeline`, for blended materials drawn into the OIT targets.
+pub(crate) fn create_oit_pipeline(
+    render_server: &RenderServer,
+    layout: &wgpu::PipelineLayout,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+    label: &str,
+    cull_mode: Option<wgpu::Face>,
+    depth_stencil: DepthStencilConfig,
+) -> wgpu::RenderPipeline {
+    let device = &render_server.device;
+
+    let shader = device.create_shader_module(shader);
+
+    let add = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    // Revealage is multiplied by one minus the alpha of each layer.
+    let reveal = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[
+                Some(wgpu::ColorTargetState {
+                    format: ACCUM_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: add,
+                        alpha: add,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+                Some(wgpu::ColorTargetState {
+                    format: REVEALAGE_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: reveal,
+                        alpha: reveal,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }),
+            ],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: render_server.depth_format(),
+            depth_write_enabled: depth_stencil.depth_write,
+            depth_compare: render_server.depth_compare(depth_stencil.depth_compare),
+            stencil: depth_stencil.stencil,
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+pub(crate) fn prepare_oit(
+    render_resources: &mut OitRenderResources,
+    mesh_render_resources: &MeshRenderResources,
+    texture_cache: &TextureCache,
+    render_server: &RenderServer,
+) {
+    render_resources.enabled = mesh_render_resources.has_oit_meshes();
+
+    if !render_resources.enabled {
+        return;
+    }
+
+    let accum = texture_cache.get(render_resources.accum_texture).unwrap();
+    let revealage = texture_cache
+        .get(render_resources.revealage_texture)
+        .unwrap();
+
+    render_resources.bind_group = Some(render_server.device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            layout: &render_resources.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&accum.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&revealage.view),
+                },
+            ],
+            label: Some("oit bind group"),
+        },
+    ));
+}
+
+/// Accumulate the blended meshes, tested against the depth of the opaque ones,
+/// then resolve them over `scene_view`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_oit(
+    render_resources: &OitRenderResources,
+    extracted_meshes: &[ExtractedMesh],
+    mesh_cache: &MeshCache,
+    mesh_render_resources: &MeshRenderResources,
+    camera_render_resources: &CameraRenderResources,
+    texture_cache: &TextureCache,
+    depth_view: &wgpu::TextureView,
+    encoder: &mut wgpu::CommandEncoder,
+    scene_view: &wgpu::TextureView,
+) {
+    if !render_resources.enabled {
+        return;
+    }
+
+    let accum = texture_cache.get(render_resources.accum_texture).unwrap();
+    let revealage = texture_cache
+        .get(render_resources.revealage_texture)
+        .unwrap();
+
+    // GL keeps pass labels next to push constants, a length that isn't a multiple
+    // of 4 would misalign them.
+    // The GL backend can only clear the first color attachment of a pass.
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("oit clear pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &revealage.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("oit accumulation"),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &accum.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &revealage.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Discard,
+                }),
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_oit_meshes(
+            extracted_meshes,
+            mesh_cache,
+            mesh_render_resources,
+            camera_render_resources,
+            &mut render_pass,
+        );
+    }
+
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("oit resolve pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: scene_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(&render_resources.resolve_pipeline);
+    render_pass.set_bind_group(0, render_resources.bind_group.as_ref().unwrap(), &[]);
+    render_pass.draw(0..3, 0..1);
+}
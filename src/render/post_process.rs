@@ -0,0 +1,626 @@
+use crate::render::bloom::{prepare_bloom, render_bloom, BloomRenderResources};
+use crate::render::frame_graph::{FrameGraph, TransientTargets};
+use crate::render::{RenderServer, Texture, TextureCache, TextureId, UniformBuffer};
+use std::mem;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+/// Screen space effects applied after the scene has been drawn.
+#[derive(Debug, Clone)]
+pub struct PostProcessSettings {
+    /// 3D LUT used for color grading, see Texture::load_lut. None means no grading.
+    pub color_grading_lut: Option<TextureId>,
+    /// How much the screen corners get darkened. Zero disables the vignette.
+    pub vignette_intensity: f32,
+    /// Width of the vignette falloff, in [0, 1].
+    pub vignette_smoothness: f32,
+    /// Channel offset at the screen edges in UV units. Zero disables the effect.
+    pub chromatic_aberration: f32,
+    /// Apply FXAA as the last pass of the chain. Cheaper than MSAA and also smooths
+    /// tessellated vector shapes.
+    pub fxaa: bool,
+    /// Strength of the glow around colors brighter than `bloom_threshold`. Zero disables bloom.
+    pub bloom_intensity: f32,
+    /// Brightness where bloom starts, in linear scene color. At 1.0 only colors brighter
+    /// than white glow, e.g. emissive materials, which needs `RenderSettings::hdr`.
+    pub bloom_threshold: f32,
+    /// Fraction of the threshold below it over which bloom fades in, in [0, 1].
+    /// Zero is a hard cutoff.
+    pub bloom_knee: f32,
+    /// Brightness of white (1.0) in nits, with HDR display output. See `DisplayOutput`.
+    pub paper_white: f32,
+    /// Brightest the display gets in nits, with HDR display output. Brighter colors
+    /// roll off towards it.
+    pub peak_brightness: f32,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        Self {
+            color_grading_lut: None,
+            vignette_intensity: 0.0,
+            vignette_smoothness: 0.5,
+            chromatic_aberration: 0.0,
+            fxaa: false,
+            bloom_intensity: 0.0,
+            bloom_threshold: 1.0,
+            bloom_knee: 0.5,
+            paper_white: 200.0,
+            peak_brightness: 1000.0,
+        }
+    }
+}
+
+impl PostProcessSettings {
+    /// If no effect is enabled, the scene is drawn to the surface directly.
+    pub fn is_enabled(&self) -> bool {
+        self.color_grading_enabled() || self.fxaa || self.bloom_enabled()
+    }
+
+    /// Color grading, vignette and chromatic aberration share a single pass.
+    pub fn color_grading_enabled(&self) -> bool {
+        self.color_grading_lut.is_some()
+            || self.vignette_intensity > 0.0
+            || self.chromatic_aberration > 0.0
+    }
+
+    pub fn bloom_enabled(&self) -> bool {
+        self.bloom_intensity > 0.0
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PostProcessParamsUniform {
+    vignette_intensity: f32,
+    vignette_smoothness: f32,
+    chromatic_aberration: f32,
+    lut_size: f32,
+}
+
+/// Names of the targets in the frame graph.
+pub(crate) const SCENE_COLOR_TARGET: &str = "scene color";
+const RESOLVED_TARGET: &str = "post process resolved";
+const INTERMEDIATE_TARGET: &str = "post process intermediate";
+
+pub(crate) struct PostProcessRenderResources {
+    /// The scene is drawn into this texture when post processing is enabled.
+    /// Uses `RenderServer::scene_format`.
+    pub(crate) scene_color_texture: TextureId,
+    /// Output of the resolve pass when there are more passes after it. Transient.
+    resolved_texture: TextureId,
+    /// Output of the color grading pass when there are more passes after it. Transient.
+    intermediate_texture: TextureId,
+
+    bloom: BloomRenderResources,
+
+    identity_lut: TextureId,
+
+    params_buffer: UniformBuffer,
+    params_bind_group: wgpu::BindGroup,
+
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group: Option<wgpu::BindGroup>,
+
+    pipeline: wgpu::RenderPipeline,
+
+    fxaa_bind_group_layout: wgpu::BindGroupLayout,
+    fxaa_bind_group: Option<wgpu::BindGroup>,
+    fxaa_pipeline: wgpu::RenderPipeline,
+
+    // Passes to run this frame, in order.
+    /// Adds bloom and brings an HDR scene to the surface format.
+    resolve_enabled: bool,
+    color_grading_enabled: bool,
+    fxaa_enabled: bool,
+}
+
+impl PostProcessRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        transient_targets: &mut TransientTargets,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let scene_color_texture = create_scene_color_texture(render_server, texture_cache);
+
+        let resolved_texture =
+            transient_targets.create(RESOLVED_TARGET, render_server.output_format());
+
+        let intermediate_texture =
+            transient_targets.create(INTERMEDIATE_TARGET, render_server.output_format());
+
+        let identity_lut = Texture::identity_lut(device, &render_server.queue, texture_cache);
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("post process params bind group layout"),
+            });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("post process texture bind group layout"),
+            });
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "post process params buffer",
+            mem::size_of::<PostProcessParamsUniform>() as BufferAddress,
+        );
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("post process params bind group"),
+        });
+
+        let pipeline = {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("post process pipeline layout"),
+                bind_group_layouts: &[
+                    globals_bind_group_layout,
+                    &params_bind_group_layout,
+                    &texture_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("post process shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/post_process.wgsl").into(),
+                ),
+            };
+
+            create_fullscreen_pipeline_with_target(
+                render_server,
+                &pipeline_layout,
+                &device.create_shader_module(shader),
+                "fs_main",
+                render_server.output_format(),
+                wgpu::BlendState::REPLACE,
+                "post process pipeline",
+            )
+        };
+
+        let fxaa_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("fxaa texture bind group layout"),
+            });
+
+        let fxaa_pipeline = {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("fxaa pipeline layout"),
+                bind_group_layouts: &[globals_bind_group_layout, &fxaa_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("fxaa shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/fxaa.wgsl").into()),
+            };
+
+            create_fullscreen_pipeline_with_target(
+                render_server,
+                &pipeline_layout,
+                &device.create_shader_module(shader),
+                "fs_main",
+                render_server.output_format(),
+                wgpu::BlendState::REPLACE,
+                "fxaa pipeline",
+            )
+        };
+
+        Self {
+            scene_color_texture,
+            resolved_texture,
+            intermediate_texture,
+            bloom: BloomRenderResources::new(render_server),
+            identity_lut,
+            params_buffer,
+            params_bind_group,
+            texture_bind_group_layout,
+            texture_bind_group: None,
+            pipeline,
+            fxaa_bind_group_layout,
+            fxaa_bind_group: None,
+            fxaa_pipeline,
+            resolve_enabled: false,
+            color_grading_enabled: false,
+            fxaa_enabled: false,
+        }
+    }
+
+    /// Offscreen textures have to follow the surface size.
+    pub(crate) fn recreate_textures(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) {
+        texture_cache.remove(self.scene_color_texture);
+
+        self.scene_color_texture = create_scene_color_texture(render_server, texture_cache);
+
+        self.bloom.recreate_textures(render_server);
+
+        // Bind groups referencing the old textures are no longer valid.
+        self.texture_bind_group = None;
+        self.fxaa_bind_group = None;
+    }
+}
+
+fn create_scene_color_texture(
+    render_server: &RenderServer,
+    texture_cache: &mut TextureCache,
+) -> TextureId {
+    let mut config = render_server.surface_config.clone();
+    config.format = render_server.scene_format();
+
+    Texture::create_render_texture(
+        &render_server.device,
+        texture_cache,
+        &config,
+        Some("scene color texture"),
+    )
+}
+
+/// Create a pipeline that draws a single fullscreen triangle.
+pub(crate) fn create_fullscreen_pipeline(
+    render_server: &RenderServer,
+    layout: &wgpu::PipelineLayout,
+    shader: wgpu::ShaderModuleDescriptor,
+    label: &str,
+) -> wgpu::RenderPipeline {
+    let shader_module = render_server.device.create_shader_module(shader);
+
+    create_fullscreen_pipeline_with_target(
+        render_server,
+        layout,
+        &shader_module,
+        "fs_main",
+        render_server.surface_config.format,
+        wgpu::BlendState::REPLACE,
+        label,
+    )
+}
+
+/// Like `create_fullscreen_pipeline`, for passes that don't write the surface format
+/// or share a shader module.
+pub(crate) fn create_fullscreen_pipeline_with_target(
+    render_server: &RenderServer,
+    layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    fragment_entry_point: &str,
+    format: wgpu::TextureFormat,
+    blend: wgpu::BlendState,
+    label: &str,
+) -> wgpu::RenderPipeline {
+    render_server
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point: fragment_entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+}
+
+pub(crate) fn prepare_post_process(
+    settings: &PostProcessSettings,
+    render_resources: &mut PostProcessRenderResources,
+    render_server: &RenderServer,
+    texture_cache: &TextureCache,
+) {
+    (
+        render_resources.resolve_enabled,
+        render_resources.color_grading_enabled,
+        render_resources.fxaa_enabled,
+    ) = enabled_passes(settings, render_server);
+
+    let scene_color = texture_cache
+        .get(render_resources.scene_color_texture)
+        .unwrap();
+
+    if render_resources.resolve_enabled {
+        prepare_bloom(
+            settings,
+            scene_color,
+            &mut render_resources.bloom,
+            render_server,
+        );
+    }
+
+    // Each pass reads the output of the previous one.
+    let resolved = if render_resources.resolve_enabled {
+        texture_cache
+            .get(render_resources.resolved_texture)
+            .unwrap()
+    } else {
+        scene_color
+    };
+
+    if render_resources.color_grading_enabled {
+        let lut_id = settings
+            .color_grading_lut
+            .unwrap_or(render_resources.identity_lut);
+        let lut = texture_cache.get(lut_id).unwrap();
+
+        let params = PostProcessParamsUniform {
+            vignette_intensity: settings.vignette_intensity,
+            vignette_smoothness: settings.vignette_smoothness.clamp(0.0, 1.0),
+            chromatic_aberration: settings.chromatic_aberration,
+            lut_size: lut.size.0 as f32,
+        };
+
+        render_server.queue.write_buffer(
+            &render_resources.params_buffer,
+            0,
+            bytemuck::cast_slice(&[params]),
+        );
+
+        // The LUT may be switched at any time, so rebuild the bind group every frame.
+        let bind_group = render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &render_resources.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&resolved.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&resolved.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&lut.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&lut.sampler),
+                    },
+                ],
+                label: Some("post process texture bind group"),
+            });
+
+        render_resources.texture_bind_group = Some(bind_group);
+    }
+
+    if render_resources.fxaa_enabled {
+        let input = if render_resources.color_grading_enabled {
+            texture_cache
+                .get(render_resources.intermediate_texture)
+                .unwrap()
+        } else {
+            resolved
+        };
+
+        let bind_group = render_server
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &render_resources.fxaa_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&input.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&input.sampler),
+                    },
+                ],
+                label: Some("fxaa texture bind group"),
+            });
+
+        render_resources.fxaa_bind_group = Some(bind_group);
+    }
+}
+
+/// Whether to resolve, color grade and apply FXAA.
+fn enabled_passes(
+    settings: &PostProcessSettings,
+    render_server: &RenderServer,
+) -> (bool, bool, bool) {
+    (
+        settings.bloom_enabled() || render_server.get_settings().hdr,
+        settings.color_grading_enabled(),
+        settings.fxaa,
+    )
+}
+
+/// The passes of `render_post_process`, from the scene color to `output`.
+pub(crate) fn add_post_process_passes(
+    settings: &PostProcessSettings,
+    render_server: &RenderServer,
+    graph: &mut FrameGraph,
+    output: &'static str,
+) {
+    let (resolve_enabled, color_grading_enabled, fxaa_enabled) =
+        enabled_passes(settings, render_server);
+
+    let mut input = SCENE_COLOR_TARGET;
+
+    if resolve_enabled {
+        let target = if color_grading_enabled || fxaa_enabled {
+            RESOLVED_TARGET
+        } else {
+            output
+        };
+        graph.add_pass("bloom", &[input], &[target]);
+        input = target;
+    }
+
+    if color_grading_enabled {
+        let target = if fxaa_enabled {
+            INTERMEDIATE_TARGET
+        } else {
+            output
+        };
+        graph.add_pass("color grading", &[input], &[target]);
+        input = target;
+    }
+
+    if fxaa_enabled {
+        graph.add_pass("fxaa", &[input], &[output]);
+    }
+}
+
+pub(crate) fn render_post_process(
+    render_resources: &PostProcessRenderResources,
+    globals_bind_group: &wgpu::BindGroup,
+    texture_cache: &TextureCache,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+) {
+    if render_resources.resolve_enabled {
+        // Write to the resolved texture if other passes come after.
+        let output_view = if render_resources.color_grading_enabled || render_resources.fxaa_enabled
+        {
+            &texture_cache
+                .get(render_resources.resolved_texture)
+                .unwrap()
+                .view
+        } else {
+            target_view
+        };
+
+        render_bloom(&render_resources.bloom, encoder, output_view);
+    }
+
+    if render_resources.color_grading_enabled {
+        // Write to the intermediate texture if FXAA comes after.
+        let output_view = if render_resources.fxaa_enabled {
+            &texture_cache
+                .get(render_resources.intermediate_texture)
+                .unwrap()
+                .view
+        } else {
+            target_view
+        };
+
+        let mut render_pass =
+            begin_fullscreen_pass(encoder, output_view, "post process render pass");
+
+        render_pass.set_pipeline(&render_resources.pipeline);
+        render_pass.set_bind_group(0, globals_bind_group, &[]);
+        render_pass.set_bind_group(1, &render_resources.params_bind_group, &[]);
+        render_pass.set_bind_group(
+            2,
+            render_resources.texture_bind_group.as_ref().unwrap(),
+            &[],
+        );
+        render_pass.draw(0..3, 0..1);
+    }
+
+    if render_resources.fxaa_enabled {
+        let mut render_pass = begin_fullscreen_pass(encoder, target_view, "fxaa render pass");
+
+        render_pass.set_pipeline(&render_resources.fxaa_pipeline);
+        render_pass.set_bind_group(0, globals_bind_group, &[]);
+        render_pass.set_bind_group(1, render_resources.fxaa_bind_group.as_ref().unwrap(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+pub(crate) fn begin_fullscreen_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    target_view: &'a wgpu::TextureView,
+    label: &str,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
@@ -0,0 +1,338 @@
+use crate::math::color::{linear_to_srgb, srgb_to_linear};
+use crate::render::camera::{CameraUniform, PerspectiveProjection, Projection};
+use crate::render::cubemap::cube_face_direction;
+use crate::render::{RenderServer, Texture, TextureCache, TextureId, UniformBuffer};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, Vector3};
+use image::{imageops, DynamicImage, RgbaImage};
+use std::mem;
+use wgpu::BufferAddress;
+
+/// Probes nearest to the camera are blended, the rest are ignored.
+pub(crate) const MAX_PROBES: usize = 4;
+
+/// Face size of baked irradiance cubemaps. Irradiance has no detail, so it can be tiny.
+const IRRADIANCE_SIZE: u32 = 8;
+
+/// Radiance is downsampled to this face size before convolving.
+const IRRADIANCE_SOURCE_SIZE: u32 = 16;
+
+/// Where baked probes are saved. Copy them to "assets/probes" to ship them.
+pub(crate) const PROBE_BAKE_DIR: &str = "user://probes";
+
+/// Baked probes are looked up in these directories, in order.
+pub(crate) const PROBE_LOAD_DIRS: [&str; 2] = ["asset://probes", PROBE_BAKE_DIR];
+
+/// Cubemaps of a baked probe.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct BakedProbe {
+    /// The surroundings, for reflections.
+    pub(crate) radiance: TextureId,
+    /// Cosine-convolved radiance, for ambient light.
+    pub(crate) irradiance: TextureId,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ExtractedProbe {
+    pub(crate) position: Vector3<f32>,
+    pub(crate) radius: f32,
+    pub(crate) baked: BakedProbe,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProbeUniform {
+    /// Position in xyz, radius in w.
+    spheres: [[f32; 4]; MAX_PROBES],
+    count: u32,
+    _pad: [u32; 3],
+}
+
+pub(crate) struct ProbeRenderResources {
+    uniform_buffer: UniformBuffer,
+    /// Black, bound to unused probe slots.
+    empty_texture: TextureId,
+    sampler: wgpu::Sampler,
+    /// Probes bound this frame.
+    bound: Vec<BakedProbe>,
+    /// Changes when different probes are bound, so bind groups using them can be recreated.
+    pub(crate) generation: u32,
+}
+
+impl ProbeRenderResources {
+    pub(crate) fn new(render_server: &RenderServer, texture_cache: &mut TextureCache) -> Self {
+        let device = &render_server.device;
+
+        let uniform_buffer = render_server.create_uniform_buffer(
+            "probe uniform buffer",
+            mem::size_of::<ProbeUniform>() as BufferAddress,
+        );
+
+        let black = DynamicImage::ImageRgba8(RgbaImage::new(1, 1));
+        let empty_texture = Texture::from_cube_faces(
+            device,
+            &render_server.queue,
+            texture_cache,
+            &vec![black; 6],
+            Some("empty probe texture"),
+        )
+        .unwrap();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("probe sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            uniform_buffer,
+            empty_texture,
+            sampler,
+            bound: vec![],
+            generation: 0,
+        }
+    }
+
+    /// Layout entries of the probe bindings, starting at `first_binding`.
+    pub(crate) fn layout_entries(first_binding: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        let cube = wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::Cube,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        };
+
+        let mut types = vec![wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }];
+        // Radiance, then irradiance.
+        types.extend([cube; MAX_PROBES * 2]);
+        types.push(wgpu::BindingType::Sampler(
+            wgpu::SamplerBindingType::Filtering,
+        ));
+
+        types
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| wgpu::BindGroupLayoutEntry {
+                binding: first_binding + i as u32,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty,
+                count: None,
+            })
+            .collect()
+    }
+
+    /// Bind group entries matching `layout_entries`.
+    pub(crate) fn bind_group_entries<'a>(
+        &'a self,
+        first_binding: u32,
+        texture_cache: &'a TextureCache,
+    ) -> Vec<wgpu::BindGroupEntry<'a>> {
+        let empty = &texture_cache.get(self.empty_texture).unwrap().view;
+
+        let view = |slot: usize, irradiance: bool| {
+            self.bound
+                .get(slot)
+                .map(|probe| {
+                    if irradiance {
+                        probe.irradiance
+                    } else {
+                        probe.radiance
+                    }
+                })
+                .and_then(|texture| texture_cache.get(texture))
+                .map_or(empty, |texture| &texture.view)
+        };
+
+        let mut resources = vec![self.uniform_buffer.as_entire_binding()];
+        for irradiance in [false, true] {
+            for slot in 0..MAX_PROBES {
+                resources.push(wgpu::BindingResource::TextureView(view(slot, irradiance)));
+            }
+        }
+        resources.push(wgpu::BindingResource::Sampler(&self.sampler));
+
+        resources
+            .into_iter()
+            .enumerate()
+            .map(|(i, resource)| wgpu::BindGroupEntry {
+                binding: first_binding + i as u32,
+                resource,
+            })
+            .collect()
+    }
+}
+
+/// Bind the probes nearest to the camera.
+pub(crate) fn prepare_probes(
+    extracted_probes: &[ExtractedProbe],
+    camera: Option<&CameraUniform>,
+    render_resources: &mut ProbeRenderResources,
+    render_server: &RenderServer,
+) {
+    let mut probes = extracted_probes.to_vec();
+
+    if let Some(camera) = camera {
+        let eye = Vector3::new(
+            camera.view_position[0],
+            camera.view_position[1],
+            camera.view_position[2],
+        );
+
+        // Distance to the edge of the probe's influence, negative inside it.
+        let distance = |probe: &ExtractedProbe| probe.position.distance(eye) - probe.radius;
+        probes.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+    }
+
+    probes.truncate(MAX_PROBES);
+
+    let mut uniform = ProbeUniform::default();
+    for (i, probe) in probes.iter().enumerate() {
+        uniform.spheres[i] = probe.position.extend(probe.radius).into();
+    }
+    uniform.count = probes.len() as u32;
+
+    render_server.queue.write_buffer(
+        &render_resources.uniform_buffer,
+        0,
+        bytemuck::cast_slice(&[uniform]),
+    );
+
+    let bound: Vec<BakedProbe> = probes.iter().map(|probe| probe.baked).collect();
+    if bound != render_resources.bound {
+        render_resources.bound = bound;
+        render_resources.generation += 1;
+    }
+}
+
+/// Camera looking through a cube face from `position`, in the order +X, -X, +Y, -Y, +Z, -Z.
+///
+/// Cube faces are mirrored compared to a right-handed camera, so the rendered
+/// image has to be flipped horizontally, see `mirror_face`.
+pub(crate) fn cube_face_camera(
+    face: usize,
+    position: Vector3<f32>,
+    near: f32,
+    far: f32,
+    reverse_z: bool,
+) -> CameraUniform {
+    let (forward, up) = match face {
+        0 => (Vector3::unit_x(), Vector3::unit_y()),
+        1 => (-Vector3::unit_x(), Vector3::unit_y()),
+        2 => (Vector3::unit_y(), -Vector3::unit_z()),
+        3 => (-Vector3::unit_y(), Vector3::unit_z()),
+        4 => (Vector3::unit_z(), Vector3::unit_y()),
+        _ => (-Vector3::unit_z(), Vector3::unit_y()),
+    };
+
+    let view = Matrix4::look_to_rh(Point3::from_vec(position), forward, up);
+
+    let mut projection: Projection =
+        PerspectiveProjection::new(1, 1, cgmath::Deg(90.0), near, far).into();
+    projection.set_reverse_z(reverse_z);
+    let proj = projection.calc_matrix();
+
+    CameraUniform {
+        view_position: position.extend(1.0).into(),
+        view: view.into(),
+        proj: proj.into(),
+        view_proj: (proj * view).into(),
+    }
+}
+
+/// Turn a face rendered with `cube_face_camera` into a cube face image.
+pub(crate) fn mirror_face(image: &RgbaImage) -> RgbaImage {
+    imageops::flip_horizontal(image)
+}
+
+/// Cosine-convolve radiance faces into irradiance faces, for diffuse lighting.
+///
+/// Values are divided by pi, so they can be multiplied with the albedo directly.
+pub(crate) fn convolve_irradiance(radiance: &[RgbaImage]) -> Vec<RgbaImage> {
+    // Direction, solid angle and linear color of each source texel.
+    let mut samples = vec![];
+
+    for (face, image) in radiance.iter().enumerate() {
+        let small = imageops::resize(
+            image,
+            IRRADIANCE_SOURCE_SIZE,
+            IRRADIANCE_SOURCE_SIZE,
+            imageops::FilterType::Triangle,
+        );
+
+        for (x, y, pixel) in small.enumerate_pixels() {
+            let (u, v) = face_uv(x, y, IRRADIANCE_SOURCE_SIZE);
+            let direction = Vector3::from(cube_face_direction(face, u, v));
+
+            // Texels near the face corners cover less of the sphere.
+            let solid_angle = 1.0 / direction.magnitude().powi(3);
+
+            let color = Vector3::new(
+                srgb_to_linear(pixel[0] as f32 / 255.0),
+                srgb_to_linear(pixel[1] as f32 / 255.0),
+                srgb_to_linear(pixel[2] as f32 / 255.0),
+            );
+
+            samples.push((direction.normalize(), solid_angle, color));
+        }
+    }
+
+    (0..6)
+        .map(|face| {
+            RgbaImage::from_fn(IRRADIANCE_SIZE, IRRADIANCE_SIZE, |x, y| {
+                let (u, v) = face_uv(x, y, IRRADIANCE_SIZE);
+                let normal = Vector3::from(cube_face_direction(face, u, v)).normalize();
+
+                let mut sum = Vector3::new(0.0, 0.0, 0.0);
+                let mut weight = 0.0;
+
+                for (direction, solid_angle, color) in &samples {
+                    let cosine = normal.dot(*direction);
+                    if cosine > 0.0 {
+                        sum += color * cosine * *solid_angle;
+                        weight += cosine * solid_angle;
+                    }
+                }
+
+                let color = sum / weight.max(f32::EPSILON);
+                let encode = |value: f32| (linear_to_srgb(value) * 255.0).round() as u8;
+
+                image::Rgba([encode(color.x), encode(color.y), encode(color.z), 255])
+            })
+        })
+        .collect()
+}
+
+/// Texel center in [-1, 1] face coordinates.
+fn face_uv(x: u32, y: u32, size: u32) -> (f32, f32) {
+    (
+        (x as f32 + 0.5) / size as f32 * 2.0 - 1.0,
+        (y as f32 + 0.5) / size as f32 * 2.0 - 1.0,
+    )
+}
+
+/// Faces side by side, as read by `CubemapLayout::HorizontalStrip`.
+pub(crate) fn faces_to_strip(faces: &[RgbaImage]) -> RgbaImage {
+    let size = faces[0].height();
+    let mut strip = RgbaImage::new(size * faces.len() as u32, size);
+
+    for (i, face) in faces.iter().enumerate() {
+        imageops::replace(&mut strip, face, (i as u32 * size) as i64, 0);
+    }
+
+    strip
+}
+
+pub(crate) fn radiance_file_name(name: &str) -> String {
+    format!("{}_radiance.png", name)
+}
+
+pub(crate) fn irradiance_file_name(name: &str) -> String {
+    format!("{}_irradiance.png", name)
+}
@@ -0,0 +1,246 @@
+use crate::asset::TextureImportSettings;
+use crate::render::texture::TextureWrite;
+use crate::render::{Texture, TextureCache, TextureId};
+use anyhow::Result;
+use image::DynamicImage;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use wgpu::util::DeviceExt;
+
+/// Creates GPU resources from any thread, e.g. from asset loading jobs.
+///
+/// Get one with `RenderServer::get_context`. Resources are created right away, but
+/// texture data and buffer writes are uploaded on the main thread before frames are
+/// drawn, a few megabytes per frame, see `RenderWorld::upload_budget`. A returned
+/// `TextureId` is in the texture cache once all of its data is uploaded.
+/// ```ignore
+/// let context = render_server.get_context();
+/// std::thread::spawn(move || {
+///     let img = image::open("big.png").unwrap();
+///     let id = context.create_texture(&img, Some("big.png"), &Default::default());
+///     sender.send(id).unwrap();
+/// });
+/// ```
+#[derive(Clone)]
+pub struct RenderContext {
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    uploads: UploadQueue,
+}
+
+impl RenderContext {
+    pub(crate) fn new(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        uploads: UploadQueue,
+    ) -> Self {
+        Self {
+            device,
+            queue,
+            uploads,
+        }
+    }
+
+    /// Create a texture from an image. Its data is uploaded before the next frame.
+    pub fn create_texture(
+        &self,
+        img: &DynamicImage,
+        label: Option<&str>,
+        settings: &TextureImportSettings,
+    ) -> Result<TextureId> {
+        let mut writes = vec![];
+        let texture =
+            Texture::create_from_image(&self.device, img, label, settings, |_, write| {
+                writes.push(write.into_owned())
+            })?;
+
+        let id = TextureId::new();
+        self.uploads.push(Upload::Texture(Box::new(PendingTexture {
+            id,
+            texture,
+            writes,
+            uploaded_rows: 0,
+        })));
+
+        Ok(id)
+    }
+
+    /// A buffer filled with `contents`. Usable right away, since the data is
+    /// copied in when it's created.
+    pub fn create_buffer(
+        &self,
+        label: &str,
+        contents: &[u8],
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage,
+            })
+    }
+
+    /// Write to a buffer before frames are drawn. It needs `BufferUsages::COPY_DST`.
+    /// Writes are done in order, but a big one may take several frames.
+    pub fn write_buffer(
+        &self,
+        buffer: &Arc<wgpu::Buffer>,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        self.uploads.push(Upload::Buffer(PendingBufferWrite {
+            buffer: buffer.clone(),
+            offset,
+            data: data.to_vec(),
+            uploaded: 0,
+        }));
+    }
+}
+
+struct PendingTexture {
+    id: TextureId,
+    texture: Texture,
+    /// Mip levels, written front to back.
+    writes: Vec<TextureWrite<'static>>,
+    /// Of the first write.
+    uploaded_rows: u32,
+}
+
+impl PendingTexture {
+    /// Upload rows until the budget runs out. True once all levels are written.
+    fn upload(&mut self, queue: &wgpu::Queue, budget: &mut u64, mut at_least_one: bool) -> bool {
+        while let Some(write) = self.writes.first() {
+            let bytes_per_row = write.bytes_per_row as u64;
+            let remaining = write.size.1 - self.uploaded_rows;
+
+            let mut rows = (*budget / bytes_per_row).min(remaining as u64) as u32;
+            if rows == 0 {
+                if !at_least_one {
+                    break;
+                }
+                rows = 1;
+            }
+            at_least_one = false;
+
+            let start = self.uploaded_rows;
+            write.submit_rows(queue, &self.texture.texture, start..start + rows);
+
+            self.uploaded_rows += rows;
+            *budget = budget.saturating_sub(rows as u64 * bytes_per_row);
+
+            if self.uploaded_rows == write.size.1 {
+                self.writes.remove(0);
+                self.uploaded_rows = 0;
+            }
+        }
+
+        self.writes.is_empty()
+    }
+}
+
+struct PendingBufferWrite {
+    buffer: Arc<wgpu::Buffer>,
+    offset: wgpu::BufferAddress,
+    data: Vec<u8>,
+    /// Bytes written so far.
+    uploaded: usize,
+}
+
+impl PendingBufferWrite {
+    /// Write a chunk as big as the budget allows. True once all data is written.
+    fn upload(&mut self, queue: &wgpu::Queue, budget: &mut u64, at_least_one: bool) -> bool {
+        // Chunks have to stay aligned for `write_buffer`.
+        let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let mut chunk = *budget as usize / align * align;
+        if chunk == 0 {
+            if !at_least_one {
+                return false;
+            }
+            chunk = align;
+        }
+        let end = self.uploaded + chunk.min(self.data.len() - self.uploaded);
+
+        queue.write_buffer(
+            &self.buffer,
+            self.offset + self.uploaded as wgpu::BufferAddress,
+            &self.data[self.uploaded..end],
+        );
+
+        *budget = budget.saturating_sub((end - self.uploaded) as u64);
+        self.uploaded = end;
+
+        self.uploaded == self.data.len()
+    }
+}
+
+enum Upload {
+    Texture(Box<PendingTexture>),
+    Buffer(PendingBufferWrite),
+}
+
+/// Uploads queued from other threads, done on the main thread by `flush`.
+#[derive(Clone, Default)]
+pub(crate) struct UploadQueue {
+    uploads: Arc<Mutex<VecDeque<Upload>>>,
+    /// Asks for a frame, so that uploads queued while the app is idle get done.
+    waker: Arc<OnceLock<Box<dyn Fn() + Send + Sync>>>,
+}
+
+impl UploadQueue {
+    /// Called whenever something is queued, from the queueing thread.
+    pub(crate) fn set_waker(&self, waker: impl Fn() + Send + Sync + 'static) {
+        let _ = self.waker.set(Box::new(waker));
+    }
+
+    fn push(&self, upload: Upload) {
+        self.lock().push_back(upload);
+
+        if let Some(waker) = self.waker.get() {
+            waker();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Upload>> {
+        // A thread that panicked mid-push leaves nothing half written.
+        self.uploads.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Upload what was queued, in order, up to `budget` bytes, or everything if None.
+    /// Textures are added to the cache once they are complete. True if uploads are left
+    /// for the next frames.
+    pub(crate) fn flush(
+        &self,
+        queue: &wgpu::Queue,
+        texture_cache: &mut TextureCache,
+        budget: Option<u64>,
+    ) -> bool {
+        let mut budget = budget.unwrap_or(u64::MAX);
+        // A chunk bigger than the budget, e.g. a very wide row, still goes through alone.
+        let mut at_least_one = true;
+
+        loop {
+            // Not locked while uploading, so that other threads can keep queueing.
+            let Some(mut upload) = self.lock().pop_front() else {
+                break;
+            };
+
+            let done = match &mut upload {
+                Upload::Texture(pending) => pending.upload(queue, &mut budget, at_least_one),
+                Upload::Buffer(pending) => pending.upload(queue, &mut budget, at_least_one),
+            };
+            at_least_one = false;
+
+            if !done {
+                self.lock().push_front(upload);
+                break;
+            }
+
+            if let Upload::Texture(pending) = upload {
+                texture_cache.insert(pending.id, pending.texture);
+            }
+        }
+
+        !self.lock().is_empty()
+    }
+}
@@ -6,12 +6,15 @@ use crate::render::texture::Texture;
 
 use crate::render::bind_group::BindGroupCache;
 use crate::render::camera::CameraUniform;
+use crate::render::render_context::{RenderContext, UploadQueue};
 use crate::render::shader_maker::ShaderMaker;
 use crate::render::sprite::{DrawSprite2d, ExtractedSprite2d, SpriteRenderResources};
-use crate::render::TextureCache;
+use crate::render::UniformBuffer;
 use cgmath::Point2;
 use std::collections::HashMap;
 use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Instant;
 use wgpu::util::DeviceExt;
 use wgpu::PolygonMode::Point;
@@ -19,10 +22,19 @@ use wgpu::{BufferAddress, TextureFormat};
 
 /// Contains render context (but not GPU resources)
 pub struct RenderServer<'a> {
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub surface: wgpu::Surface<'a>,
+    /// Shared with `RenderContext`, which can be used off the main thread.
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    /// None when rendering headlessly.
+    pub surface: Option<wgpu::Surface<'a>>,
+    /// Also describes the render target when headless.
     pub surface_config: wgpu::SurfaceConfiguration,
+    /// Fixed at startup, since pipelines are built from it.
+    settings: RenderSettings,
+    /// Bytes of the uniform buffers alive, see `create_uniform_buffer`.
+    uniform_memory: Arc<AtomicU64>,
+    /// Filled by `RenderContext`s on other threads.
+    pub(crate) uploads: UploadQueue,
     // bind_group_layout_cache: HashMap<&'static str, wgpu::BindGroupLayout>,
     // material_3d_bind_group_layout_cache: HashMap<u32, wgpu::BindGroupLayout>,
     // render_pipeline_cache: HashMap<&'static str, wgpu::RenderPipeline>,
@@ -31,13 +43,19 @@ pub struct RenderServer<'a> {
 
 impl<'a> RenderServer<'a> {
     pub(crate) fn new<'b: 'a>(
-        surface: wgpu::Surface<'b>,
+        surface: Option<wgpu::Surface<'b>>,
         surface_config: wgpu::SurfaceConfiguration,
         device: wgpu::Device,
         queue: wgpu::Queue,
+        mut settings: RenderSettings,
     ) -> Self {
         let now = Instant::now();
 
+        // The display encoding needs the scene's colors above 1.0.
+        if settings.display_output.is_hdr() {
+            settings.hdr = true;
+        }
+
         // let mut bind_group_layout_cache = HashMap::new();
         // let material_3d_bind_group_layout_cache = HashMap::new();
 
@@ -67,10 +85,13 @@ impl<'a> RenderServer<'a> {
         // }
 
         let mut server = Self {
-            device,
-            queue,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
             surface,
             surface_config,
+            settings,
+            uniform_memory: Arc::new(AtomicU64::new(0)),
+            uploads: UploadQueue::default(),
         };
 
         let elapsed_time = now.elapsed();
@@ -165,18 +186,320 @@ impl<'a> RenderServer<'a> {
     // }
 }
 
+impl<'a> RenderServer<'a> {
+    pub fn get_settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Format of the main depth buffer.
+    pub fn depth_format(&self) -> wgpu::TextureFormat {
+        // We still need a stencil aspect for UI clipping.
+        if self.settings.reverse_z
+            && self
+                .device
+                .features()
+                .contains(wgpu::Features::DEPTH32FLOAT_STENCIL8)
+        {
+            wgpu::TextureFormat::Depth32FloatStencil8
+        } else {
+            Texture::DEPTH_FORMAT
+        }
+    }
+
+    /// Format the scene is drawn in. Only post processing and transitions draw to the surface.
+    pub fn scene_format(&self) -> wgpu::TextureFormat {
+        if self.settings.hdr {
+            HDR_FORMAT
+        } else {
+            self.surface_config.format
+        }
+    }
+
+    /// Format of the passes that make the final image: post processing and transitions.
+    /// With HDR display output they draw linear colors, which are encoded for the display
+    /// at the end, see `RenderSettings::display_output`.
+    pub fn output_format(&self) -> wgpu::TextureFormat {
+        if self.settings.display_output.is_hdr() {
+            HDR_FORMAT
+        } else {
+            self.surface_config.format
+        }
+    }
+
+    /// Flip a depth compare written for standard depth (near is 0) if reverse-Z is on.
+    pub fn depth_compare(&self, compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        use wgpu::CompareFunction::*;
+
+        if !self.settings.reverse_z {
+            return compare;
+        }
+
+        match compare {
+            Less => Greater,
+            LessEqual => GreaterEqual,
+            Greater => Less,
+            GreaterEqual => LessEqual,
+            other => other,
+        }
+    }
+
+    /// Depth of the far plane, which the depth buffer is cleared to.
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.settings.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Per-draw data can be sent as push constants instead of through uniform buffers.
+    pub fn supports_push_constants(&self) -> bool {
+        self.device
+            .features()
+            .contains(wgpu::Features::PUSH_CONSTANTS)
+    }
+
+    /// Compute shaders are missing on downlevel devices like WebGL2.
+    pub fn supports_compute(&self) -> bool {
+        self.device.limits().max_compute_workgroups_per_dimension > 0
+    }
+
+    /// A handle for creating GPU resources from other threads.
+    pub fn get_context(&self) -> RenderContext {
+        RenderContext::new(
+            self.device.clone(),
+            self.queue.clone(),
+            self.uploads.clone(),
+        )
+    }
+
+    /// A uniform buffer that can be written to, counted in the GPU memory stats
+    /// until it's dropped.
+    pub fn create_uniform_buffer(&self, label: &str, size: BufferAddress) -> UniformBuffer {
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        UniformBuffer::new(buffer, self.uniform_memory.clone())
+    }
+
+    /// Bytes of uniform buffers made by `create_uniform_buffer` and not dropped.
+    pub fn get_uniform_memory(&self) -> u64 {
+        self.uniform_memory.load(Ordering::Relaxed)
+    }
+
+    /// Ranges for a pipeline layout, or none if push constants aren't supported.
+    pub fn push_constant_ranges(
+        &self,
+        stages: wgpu::ShaderStages,
+        size: u32,
+    ) -> Vec<wgpu::PushConstantRange> {
+        if !self.supports_push_constants() || size > PUSH_CONSTANT_SIZE {
+            return vec![];
+        }
+
+        vec![wgpu::PushConstantRange {
+            stages,
+            range: 0..size,
+        }]
+    }
+}
+
+impl RenderServer<'static> {
+    /// A render server without a window, rendering to textures of this size.
+    pub fn new_headless(
+        width: u32,
+        height: u32,
+        mut settings: RenderSettings,
+    ) -> anyhow::Result<Self> {
+        // Frames are read back as 8-bit images.
+        if settings.display_output.is_hdr() {
+            log::warn!("No HDR display output when headless, falling back to SDR");
+            settings.display_output = DisplayOutput::Sdr;
+        }
+
+        pollster::block_on(async {
+            let instance = wgpu::Instance::default();
+
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface: None,
+                    force_fallback_adapter: false,
+                })
+                .await
+                .ok_or_else(|| anyhow::anyhow!("Failed to find an appropriate adapter!"))?;
+
+            let (device, queue) = adapter
+                .request_device(&device_descriptor(&adapter), None)
+                .await?;
+
+            let surface_config = wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                format: TextureFormat::Rgba8UnormSrgb,
+                width,
+                height,
+                present_mode: wgpu::PresentMode::Fifo,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            };
+
+            Ok(Self::new(None, surface_config, device, queue, settings))
+        })
+    }
+}
+
+/// Options that have to be chosen before the render server is created.
+#[derive(Debug, Clone, Default)]
+pub struct RenderSettings {
+    /// Map the near plane to depth 1 and the far plane to 0, with a float depth buffer.
+    /// Much more precise at a distance, which reduces z-fighting in large 3D scenes.
+    pub reverse_z: bool,
+    /// Draw the scene into a half float target, so colors can go above 1.0.
+    /// Needed for emissive materials to bloom, see `PostProcessSettings::bloom_intensity`.
+    pub hdr: bool,
+    pub render_path: RenderPath,
+    /// Draw blended materials with weighted blended order-independent transparency
+    /// instead of sorting them. Intersecting glass no longer pops, at the cost of
+    /// an approximate result where many layers overlap.
+    pub order_independent_transparency: bool,
+    /// Color space of the surface. Falls back to SDR if the surface doesn't support it.
+    pub display_output: DisplayOutput,
+}
+
+/// How the final image is encoded for the display.
+///
+/// HDR output turns `RenderSettings::hdr` on. Brightness is set with
+/// `PostProcessSettings::paper_white` and `PostProcessSettings::peak_brightness`.
+/// Whether the compositor shows it as HDR is up to the platform, and the egui layer
+/// is drawn on top as is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayOutput {
+    /// 8-bit sRGB, colors are clamped to 1.0.
+    #[default]
+    Sdr,
+    /// 10-bit, PQ encoded with BT.2020 primaries.
+    Hdr10,
+    /// Linear half float with sRGB primaries, where 1.0 is 80 nits.
+    ScRgb,
+}
+
+impl DisplayOutput {
+    pub fn is_hdr(&self) -> bool {
+        *self != DisplayOutput::Sdr
+    }
+
+    /// Surface format needed for this output. None for SDR, which takes the surface's default.
+    pub fn surface_format(&self) -> Option<wgpu::TextureFormat> {
+        match self {
+            DisplayOutput::Sdr => None,
+            DisplayOutput::Hdr10 => Some(wgpu::TextureFormat::Rgb10a2Unorm),
+            DisplayOutput::ScRgb => Some(wgpu::TextureFormat::Rgba16Float),
+        }
+    }
+}
+
+/// How meshes are lit by point lights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenderPath {
+    /// Every mesh shades every point light, up to 10 of them.
+    #[default]
+    Forward,
+    /// Clustered forward+. Lights are sorted into a grid over the view frustum first,
+    /// and each fragment only shades the lights of its cell. Scales to hundreds of lights,
+    /// at a fixed cost per frame.
+    ForwardPlus,
+}
+
+/// Format of the scene color target when `RenderSettings::hdr` is on.
+pub(crate) const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Push constants are only used if at least this many bytes are available.
+pub(crate) const PUSH_CONSTANT_SIZE: u32 = 128;
+
+/// Enables the optional features we can use on this adapter.
+pub(crate) fn device_descriptor(adapter: &wgpu::Adapter) -> wgpu::DeviceDescriptor<'static> {
+    // Dual-source blending is for subpixel text.
+    let mut required_features = adapter.features() & wgpu::Features::DUAL_SOURCE_BLENDING;
+    let mut required_limits = wgpu::Limits::default();
+
+    if adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+        && adapter.limits().max_push_constant_size >= PUSH_CONSTANT_SIZE
+    {
+        required_features |= wgpu::Features::PUSH_CONSTANTS;
+        required_limits.max_push_constant_size = PUSH_CONSTANT_SIZE;
+    }
+
+    // For a reverse-Z depth buffer with a stencil aspect.
+    required_features |= adapter.features() & wgpu::Features::DEPTH32FLOAT_STENCIL8;
+
+    wgpu::DeviceDescriptor {
+        label: None,
+        required_features,
+        required_limits,
+    }
+}
+
+/// How a pipeline tests and writes depth and stencil. Part of pipeline cache keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DepthStencilConfig {
+    pub depth_compare: wgpu::CompareFunction,
+    pub depth_write: bool,
+    pub stencil: wgpu::StencilState,
+}
+
+impl Default for DepthStencilConfig {
+    fn default() -> Self {
+        Self::opaque()
+    }
+}
+
+impl DepthStencilConfig {
+    /// Test and write depth.
+    pub fn opaque() -> Self {
+        Self {
+            // The depth_compare function tells us when to discard a new pixel.
+            // Using LESS means pixels will be drawn front to back.
+            // This has to be LESS_OR_EQUAL for correct skybox rendering.
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            depth_write: true,
+            stencil: wgpu::StencilState::default(),
+        }
+    }
+
+    /// Test depth without writing it, so blended surfaces don't hide what's behind them.
+    pub fn transparent() -> Self {
+        Self {
+            depth_write: false,
+            ..Self::opaque()
+        }
+    }
+
+    pub fn with_stencil(mut self, stencil: wgpu::StencilState) -> Self {
+        self.stencil = stencil;
+        self
+    }
+}
+
 /// Set up resource pipeline using the pipeline layout.
+/// Depth compares are given for standard depth, and flipped when using reverse-Z.
 pub fn create_render_pipeline(
-    device: &wgpu::Device,
+    render_server: &RenderServer,
     layout: &wgpu::PipelineLayout,
     color_format: wgpu::TextureFormat,
-    depth_format: Option<wgpu::TextureFormat>,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
     label: &str,
     transparency: bool,
     cull_mode: Option<wgpu::Face>,
+    depth_stencil: DepthStencilConfig,
 ) -> wgpu::RenderPipeline {
+    let device = &render_server.device;
+
     // Create actual shader module using the shader descriptor.
     let shader = device.create_shader_module(shader);
 
@@ -227,14 +550,11 @@ pub fn create_render_pipeline(
             // Requires Features::CONSERVATIVE_RASTERIZATION
             conservative: false,
         },
-        depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
-            format,
-            depth_write_enabled: !transparency,
-            // The depth_compare function tells us when to discard a new pixel.
-            // Using LESS means pixels will be drawn front to back.
-            // This has to be LESS_OR_EQUAL for correct skybox rendering.
-            depth_compare: wgpu::CompareFunction::LessEqual,
-            stencil: wgpu::StencilState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: render_server.depth_format(),
+            depth_write_enabled: depth_stencil.depth_write,
+            depth_compare: render_server.depth_compare(depth_stencil.depth_compare),
+            stencil: depth_stencil.stencil,
             bias: wgpu::DepthBiasState::default(),
         }),
         multisample: wgpu::MultisampleState {
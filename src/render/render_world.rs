@@ -1,25 +1,88 @@
 use crate::asset::AssetServer;
 use crate::core::engine::Engine;
 use crate::math::alignup_u32;
+use crate::math::color::{ColorF, ColorU};
 use crate::render::atlas::{prepare_atlas, render_atlas, AtlasRenderResources, ExtractedAtlas};
+use crate::render::backdrop::{
+    prepare_backdrop, render_backdrop, render_backdrop_copy, BackdropRenderResources,
+};
 use crate::render::bind_group::BindGroupCache;
-use crate::render::camera::{CameraRenderResources, CameraType, CameraUniform, ExtractedCameras};
+use crate::render::camera::{CameraRenderResources, CameraType, ExtractedCameras, ViewportRect};
+use crate::render::clip::{
+    prepare_clip, render_clip, ClipCommand, ClipOp, ClipRenderResources, ExtractedClip,
+};
+use crate::render::cluster::{prepare_clusters, render_clusters, ClusterRenderResources};
+use crate::render::contact_shadow::{
+    prepare_contact_shadows, render_contact_shadows, ContactShadowRenderResources,
+};
+use crate::render::curve2d::{
+    prepare_curves, render_curves, Curve2dRenderResources, ExtractedCurve2d,
+};
+use crate::render::debug_draw::{
+    prepare_debug_draw, render_debug_draw, DebugDrawRenderResources, ExtractedMeshNormals,
+};
+use crate::render::display_output::{
+    prepare_display_output, render_display_output, DisplayOutputRenderResources,
+    DISPLAY_OUTPUT_TARGET,
+};
 use crate::render::draw_command::DrawCommands;
-use crate::render::gizmo::GizmoRenderResources;
+use crate::render::frame_graph::{FrameGraph, TransientTargets, SURFACE};
+use crate::render::gizmo::{GizmoRenderResources, GizmoVertex, GridSettings};
+use crate::render::globals::GlobalsRenderResources;
+use crate::render::label3d::{
+    prepare_label3d, render_label3d, ExtractedLabel3d, Label3dRenderResources,
+};
 use crate::render::light::{ExtractedLights, LightUniform};
+use crate::render::light2d::{
+    prepare_light2d, render_light_map, ExtractedLight2d, ExtractedOccluder2d,
+    Light2dRenderResources,
+};
+use crate::render::line2d::{prepare_lines, render_lines, ExtractedLine2d, Line2dRenderResources};
+use crate::render::morph::{
+    prepare_morph_targets, render_morph_targets, ExtractedMorphWeights, MorphRenderResources,
+};
+use crate::render::occlusion::{
+    prepare_occlusion, render_occlusion_queries, resolve_occlusion_queries,
+    ExtractedOcclusionQuery, OcclusionRenderResources,
+};
+use crate::render::oit::{prepare_oit, render_oit, OitRenderResources};
+use crate::render::post_process::{
+    add_post_process_passes, prepare_post_process, render_post_process, PostProcessRenderResources,
+    PostProcessSettings, SCENE_COLOR_TARGET,
+};
+use crate::render::probe::{prepare_probes, ExtractedProbe, ProbeRenderResources};
+use crate::render::resolution_scale::{
+    prepare_resolution_scale, render_upscale, ResolutionScale, ResolutionScaleRenderResources,
+};
 use crate::render::shader_maker::ShaderMaker;
+use crate::render::shadow::{
+    prepare_shadows, render_shadows, ShadowRenderResources, ShadowSettings,
+};
 use crate::render::sky::{prepare_sky, render_sky, ExtractedSky, SkyRenderResources};
 use crate::render::sprite::{
     prepare_sprite, render_sprite, ExtractedSprite2d, SpriteBatch, SpriteRenderResources,
 };
+use crate::render::sprite3d::{
+    prepare_sprite3d, render_soft_sprite3d, render_sprite3d, ExtractedSprite3d,
+    Sprite3dRenderResources,
+};
+use crate::render::trail::{prepare_trails, render_trails, ExtractedTrail3d, TrailRenderResources};
+use crate::render::transition::{
+    prepare_transition, render_transition, ScreenTransition, TransitionRenderResources,
+};
+use crate::render::ui_shape::{
+    prepare_ui_shapes, render_ui_shapes, ExtractedUiShape, UiShapeRenderResources,
+};
+use crate::render::water::{prepare_water, render_water, ExtractedWater, WaterRenderResources};
 use crate::render::{
-    prepare_meshes, render_meshes, DrawModel, ExtractedMesh, MeshCache, MeshRenderResources,
-    RenderServer, Texture, TextureCache, TextureId,
+    cull_meshes, prepare_meshes, render_meshes, ExtractedMesh, GpuMemoryUsage, MeshCache,
+    MeshRenderResources, RenderPath, RenderServer, RenderStats, Texture, TextureCache, TextureId,
 };
 use crate::scene::{Camera2d, World};
 use crate::window::InputServer;
-use cgmath::Point2;
+use cgmath::{Matrix4, Vector3};
 use std::mem;
+use std::ops::Range;
 use wgpu::{BufferAddress, DynamicOffset, SamplerBindingType};
 use winit::event_loop::{EventLoop, EventLoopWindowTarget};
 use winit::window::{Window, WindowBuilder};
@@ -30,13 +93,52 @@ pub struct Extracted {
 
     pub(crate) meshes: Vec<ExtractedMesh>,
 
+    pub(crate) morph_weights: Vec<ExtractedMorphWeights>,
+
     pub(crate) cameras: ExtractedCameras,
 
     pub(crate) lights: ExtractedLights,
 
+    pub(crate) lights_2d: Vec<ExtractedLight2d>,
+
+    pub(crate) occluders_2d: Vec<ExtractedOccluder2d>,
+
     pub(crate) atlases: Vec<ExtractedAtlas>,
 
     pub(crate) sky: Option<ExtractedSky>,
+
+    pub(crate) sprites3d: Vec<ExtractedSprite3d>,
+
+    pub(crate) labels3d: Vec<ExtractedLabel3d>,
+
+    /// UI clip shapes, in the order they are pushed.
+    pub(crate) clips: Vec<ExtractedClip>,
+
+    pub(crate) clip_commands: Vec<ClipCommand>,
+
+    pub(crate) ui_shapes: Vec<ExtractedUiShape>,
+
+    pub(crate) lines_2d: Vec<ExtractedLine2d>,
+
+    pub(crate) curves_2d: Vec<ExtractedCurve2d>,
+
+    /// Transform gizmo handle triangles.
+    pub(crate) gizmo_vertices: Vec<GizmoVertex>,
+
+    /// Depth tested debug lines, two vertices per line.
+    pub(crate) debug_lines: Vec<GizmoVertex>,
+
+    pub(crate) mesh_normals: Vec<ExtractedMeshNormals>,
+
+    pub(crate) trails: Vec<ExtractedTrail3d>,
+
+    /// Baked reflection probes.
+    pub(crate) probes: Vec<ExtractedProbe>,
+
+    pub(crate) waters: Vec<ExtractedWater>,
+
+    /// Models only drawn while their bounds are visible.
+    pub(crate) occlusion_queries: Vec<ExtractedOcclusionQuery>,
 }
 
 /// Contains GPU resources
@@ -46,13 +148,36 @@ pub struct RenderWorld {
     pub texture_cache: TextureCache,
     pub(crate) shader_maker: ShaderMaker,
     pub camera_render_resources: CameraRenderResources,
+    pub(crate) globals_render_resources: GlobalsRenderResources,
 
     // Sprites.
     pub(crate) sprite_render_resources: SpriteRenderResources,
 
+    /// What the scene is cleared to before anything is drawn.
+    pub clear_color: ColorF,
+
+    // 2D lighting.
+    /// Light level where no 2D light reaches. Only used if there is any 2D light.
+    pub ambient_light_2d: ColorU,
+    pub(crate) light2d_render_resources: Light2dRenderResources,
+
+    // Lines.
+    pub(crate) line2d_render_resources: Line2dRenderResources,
+
+    // GPU curves.
+    pub(crate) curve2d_render_resources: Curve2dRenderResources,
+
+    // UI clipping.
+    pub(crate) clip_render_resources: ClipRenderResources,
+
+    // UI primitives.
+    pub(crate) ui_shape_render_resources: UiShapeRenderResources,
+
     // Meshes.
     pub mesh_cache: MeshCache,
     pub mesh_render_resources: MeshRenderResources,
+    pub(crate) morph_render_resources: MorphRenderResources,
+    pub(crate) occlusion_render_resources: OcclusionRenderResources,
 
     // Temporary.
     pub(crate) extracted: Extracted,
@@ -61,13 +186,85 @@ pub struct RenderWorld {
     // Cameras.
 
     // Lights.
+    pub shadow_settings: ShadowSettings,
+    pub(crate) shadow_render_resources: ShadowRenderResources,
+    pub(crate) contact_shadow_render_resources: ContactShadowRenderResources,
+    pub(crate) probe_render_resources: ProbeRenderResources,
+    /// Only with the forward+ render path.
+    pub(crate) cluster_render_resources: Option<ClusterRenderResources>,
+    /// Only with order-independent transparency.
+    pub(crate) oit_render_resources: Option<OitRenderResources>,
 
     // Extra.
+    pub grid_settings: GridSettings,
     pub gizmo_render_resources: GizmoRenderResources,
 
+    pub(crate) debug_draw_render_resources: DebugDrawRenderResources,
+
     pub atlas_render_resources: AtlasRenderResources,
 
     pub sky_render_resources: SkyRenderResources,
+
+    pub(crate) sprite3d_render_resources: Sprite3dRenderResources,
+
+    pub(crate) label3d_render_resources: Label3dRenderResources,
+
+    pub(crate) trail_render_resources: TrailRenderResources,
+
+    pub(crate) water_render_resources: WaterRenderResources,
+
+    // Post processing.
+    pub post_process_settings: PostProcessSettings,
+    pub(crate) post_process_render_resources: PostProcessRenderResources,
+    /// Only with HDR display output.
+    pub(crate) display_output_render_resources: Option<DisplayOutputRenderResources>,
+
+    // Resolution scaling.
+    pub resolution_scale: ResolutionScale,
+    pub(crate) resolution_scale_render_resources: ResolutionScaleRenderResources,
+
+    // Screen transitions.
+    pub screen_transition: ScreenTransition,
+    pub(crate) transition_render_resources: TransitionRenderResources,
+
+    // UI backdrop blur.
+    /// Blur extent in pixels for UI shapes with a blurred backdrop.
+    pub backdrop_blur_radius: f32,
+    pub(crate) backdrop_render_resources: BackdropRenderResources,
+
+    // GPU memory.
+    /// Warn when the GPU memory in use goes over this many bytes.
+    pub gpu_memory_budget: Option<u64>,
+    pub(crate) gpu_memory: GpuMemoryUsage,
+    /// To only warn when crossing the budget.
+    over_gpu_memory_budget: bool,
+    /// Bytes of texture data and buffer writes from `RenderContext` uploaded per frame,
+    /// so that big assets finishing loading don't hitch a frame. None uploads everything
+    /// right away.
+    pub upload_budget: Option<u64>,
+    /// Uploads didn't fit in the budget of the last frame.
+    uploads_pending: bool,
+
+    // Frame graph.
+    /// Log the passes of a frame and the lifetimes of their targets whenever they change.
+    pub validate_frame_graph: bool,
+    frame_graph: FrameGraph,
+    transient_targets: TransientTargets,
+}
+
+/// Optional passes that run this frame.
+struct FramePasses {
+    post_process: bool,
+    backdrop: bool,
+    soft_sprites: bool,
+    water: bool,
+    contact_shadows: bool,
+    oit: bool,
+    scaled: bool,
+    /// The UI goes on top of everything drawn after the main pass.
+    separate_ui_pass: bool,
+    /// The scene is drawn to the scene color texture instead of the surface.
+    offscreen: bool,
 }
 
 impl RenderWorld {
@@ -79,57 +276,359 @@ impl RenderWorld {
             &render_server.device,
             &mut texture_cache,
             &render_server.surface_config,
+            render_server.depth_format(),
             Some("surface depth texture"),
         );
 
         let camera_render_resources = CameraRenderResources::new(render_server);
 
-        let sprite_render_resources = SpriteRenderResources::new(render_server);
+        let globals_render_resources = GlobalsRenderResources::new(render_server);
+
+        let light2d_render_resources = Light2dRenderResources::new(
+            render_server,
+            &mut texture_cache,
+            &camera_render_resources.bind_group_layout,
+        );
+
+        let sprite_render_resources = SpriteRenderResources::new(
+            render_server,
+            &camera_render_resources.bind_group_layout,
+            &light2d_render_resources.sample_bind_group_layout,
+        );
+
+        let line2d_render_resources = Line2dRenderResources::new(render_server, &mut texture_cache);
+
+        let curve2d_render_resources = Curve2dRenderResources::new(
+            render_server,
+            &mut texture_cache,
+            &camera_render_resources.bind_group_layout,
+            &sprite_render_resources,
+            &light2d_render_resources.sample_bind_group_layout,
+        );
+
+        let clip_render_resources = ClipRenderResources::new(render_server);
+
+        let backdrop_render_resources =
+            BackdropRenderResources::new(render_server, &mut texture_cache);
+
+        let ui_shape_render_resources = UiShapeRenderResources::new(
+            render_server,
+            &backdrop_render_resources.sample_bind_group_layout,
+        );
 
         let mesh_render_resources = MeshRenderResources::new(render_server);
 
+        let morph_render_resources = MorphRenderResources::new(render_server);
+
+        let occlusion_render_resources = OcclusionRenderResources::new(
+            render_server,
+            &camera_render_resources.bind_group_layout,
+        );
+
+        let shadow_settings = ShadowSettings::default();
+        let shadow_render_resources = ShadowRenderResources::new(render_server, &shadow_settings);
+
+        let contact_shadow_render_resources = ContactShadowRenderResources::new(
+            render_server,
+            &globals_render_resources.bind_group_layout,
+        );
+
+        let probe_render_resources = ProbeRenderResources::new(render_server, &mut texture_cache);
+
+        let cluster_render_resources = (render_server.get_settings().render_path
+            == RenderPath::ForwardPlus)
+            .then(|| ClusterRenderResources::new(render_server));
+
+        let oit_render_resources = render_server
+            .get_settings()
+            .order_independent_transparency
+            .then(|| OitRenderResources::new(render_server, &mut texture_cache));
+
         let gizmo_render_resources =
             GizmoRenderResources::new(render_server, &camera_render_resources.bind_group_layout);
 
+        let debug_draw_render_resources = DebugDrawRenderResources::new(
+            render_server,
+            &camera_render_resources.bind_group_layout,
+        );
+
         let atlas_render_resources = AtlasRenderResources::new(render_server);
 
         let sky_render_resources = SkyRenderResources::new(render_server);
 
+        let sprite3d_render_resources = Sprite3dRenderResources::new(render_server);
+
+        let label3d_render_resources = Label3dRenderResources::new(render_server);
+
+        let trail_render_resources =
+            TrailRenderResources::new(render_server, &camera_render_resources.bind_group_layout);
+
+        let water_render_resources = WaterRenderResources::new(
+            render_server,
+            &mut texture_cache,
+            &camera_render_resources.bind_group_layout,
+        );
+
+        let mut transient_targets = TransientTargets::default();
+
+        let post_process_render_resources = PostProcessRenderResources::new(
+            render_server,
+            &mut texture_cache,
+            &mut transient_targets,
+            &globals_render_resources.bind_group_layout,
+        );
+
+        let display_output_render_resources =
+            DisplayOutputRenderResources::new(render_server, &mut transient_targets);
+
+        let resolution_scale_render_resources = ResolutionScaleRenderResources::new(render_server);
+
+        let transition_render_resources = TransitionRenderResources::new(
+            render_server,
+            &globals_render_resources.bind_group_layout,
+        );
+
         Self {
             surface_depth_texture: depth_texture,
             texture_cache,
             mesh_cache: MeshCache::new(),
             camera_render_resources,
+            globals_render_resources,
             sprite_render_resources,
+            clear_color: ColorF::rgb(0.1, 0.2, 0.3),
+            ambient_light_2d: ColorU::new(40, 40, 48, 255),
+            light2d_render_resources,
+            line2d_render_resources,
+            curve2d_render_resources,
+            clip_render_resources,
+            ui_shape_render_resources,
             mesh_render_resources,
+            morph_render_resources,
+            occlusion_render_resources,
             shader_maker: ShaderMaker::new(),
             extracted: Extracted::default(),
             sprite_batches: vec![],
+            shadow_settings,
+            shadow_render_resources,
+            contact_shadow_render_resources,
+            probe_render_resources,
+            cluster_render_resources,
+            oit_render_resources,
+            grid_settings: GridSettings::default(),
             gizmo_render_resources,
+            debug_draw_render_resources,
             atlas_render_resources,
             sky_render_resources,
+            sprite3d_render_resources,
+            label3d_render_resources,
+            trail_render_resources,
+            water_render_resources,
+            post_process_settings: PostProcessSettings::default(),
+            post_process_render_resources,
+            display_output_render_resources,
+            resolution_scale: ResolutionScale::default(),
+            resolution_scale_render_resources,
+            screen_transition: ScreenTransition::default(),
+            transition_render_resources,
+            backdrop_blur_radius: 24.0,
+            backdrop_render_resources,
+            gpu_memory_budget: None,
+            gpu_memory: GpuMemoryUsage::default(),
+            over_gpu_memory_budget: false,
+            upload_budget: Some(8 * 1024 * 1024),
+            uploads_pending: false,
+            validate_frame_graph: false,
+            frame_graph: FrameGraph::default(),
+            transient_targets,
         }
     }
 
+    /// Uploads from `RenderContext` are left for the next frames, which should be drawn
+    /// even if nothing else changes.
+    pub fn has_pending_uploads(&self) -> bool {
+        self.uploads_pending
+    }
+
     pub fn extract(&mut self, draw_commands: &DrawCommands) {
         self.extracted = draw_commands.extracted.clone();
     }
 
+    /// Time as seen by shaders, pushed by the app every frame.
+    pub fn set_time(&mut self, time: f32, delta: f32) {
+        self.globals_render_resources.uniform.time = time;
+        self.globals_render_resources.uniform.delta = delta;
+    }
+
     // Prepare GPU resources.
     pub fn prepare(&mut self, render_server: &RenderServer) {
-        self.camera_render_resources
-            .prepare_cameras(render_server, &self.extracted.cameras);
+        // Resources made on other threads, as much as the budget allows.
+        self.uploads_pending = render_server.uploads.flush(
+            &render_server.queue,
+            &mut self.texture_cache,
+            self.upload_budget,
+        );
+
+        // Targets drawn along with the 3D scene follow its size.
+        if prepare_resolution_scale(
+            &self.resolution_scale,
+            &mut self.resolution_scale_render_resources,
+            render_server,
+            &mut self.texture_cache,
+        ) {
+            let size = self.resolution_scale_render_resources.size;
+
+            self.water_render_resources.recreate_textures(
+                render_server,
+                &mut self.texture_cache,
+                size,
+            );
+
+            if let Some(oit_render_resources) = &mut self.oit_render_resources {
+                oit_render_resources.recreate_textures(
+                    render_server,
+                    &mut self.texture_cache,
+                    size,
+                );
+            }
+        }
+
+        // Textures removed or replaced since the last frame.
+        for texture_id in self.texture_cache.take_evicted() {
+            self.sprite3d_render_resources.evict_texture(texture_id);
+            self.label3d_render_resources.evict_texture(texture_id);
+            self.sprite_render_resources
+                .remove_texture_bind_group(texture_id);
+            self.atlas_render_resources
+                .texture_bind_group_cache
+                .remove(&texture_id);
+        }
+
+        let scene_depth_texture = self.scene_depth_texture();
+
+        self.globals_render_resources.prepare(
+            render_server,
+            &self.extracted.cameras,
+            self.resolution_scale_render_resources.size,
+        );
+
+        self.camera_render_resources.prepare_cameras(
+            render_server,
+            &self.extracted.cameras,
+            &self.globals_render_resources,
+        );
+
+        let view_2d = self
+            .extracted
+            .cameras
+            .types
+            .iter()
+            .position(|t| *t == CameraType::D2)
+            .map(|i| Matrix4::from(self.extracted.cameras.uniforms[i].view));
+
+        self.gizmo_render_resources
+            .prepare_grid(render_server, &self.grid_settings, view_2d);
+
+        // Shadow cascades and probes follow the first 3D camera.
+        let camera_3d = self
+            .extracted
+            .cameras
+            .types
+            .iter()
+            .position(|t| *t == CameraType::D3)
+            .map(|i| &self.extracted.cameras.uniforms[i]);
+
+        prepare_morph_targets(
+            &self.extracted.morph_weights,
+            &self.mesh_cache,
+            &mut self.morph_render_resources,
+            render_server,
+        );
+
+        prepare_shadows(
+            &self.shadow_settings,
+            &self.extracted.lights,
+            camera_3d,
+            &self.extracted.meshes,
+            &self.mesh_cache,
+            &self.mesh_render_resources,
+            &mut self.shadow_render_resources,
+            render_server,
+        );
+
+        prepare_contact_shadows(
+            &self.shadow_settings,
+            &self.extracted.lights,
+            camera_3d,
+            &mut self.contact_shadow_render_resources,
+            &self.texture_cache,
+            scene_depth_texture,
+            render_server,
+        );
+
+        prepare_probes(
+            &self.extracted.probes,
+            camera_3d,
+            &mut self.probe_render_resources,
+            render_server,
+        );
+
+        if let Some(cluster_render_resources) = &mut self.cluster_render_resources {
+            prepare_clusters(
+                &self.extracted.lights.point_lights,
+                camera_3d,
+                cluster_render_resources,
+                render_server,
+            );
+        }
+
+        prepare_occlusion(
+            &self.extracted.occlusion_queries,
+            camera_3d
+                .map(|c| Vector3::new(c.view_position[0], c.view_position[1], c.view_position[2])),
+            &mut self.occlusion_render_resources,
+            render_server,
+        );
 
         for i in 0..self.extracted.cameras.uniforms.len() {
             if self.extracted.cameras.types[i] == CameraType::D2 {
+                let pixel_snap = self.extracted.cameras.pixel_snap;
+
+                self.sprite_render_resources
+                    .set_nearest_filtering(pixel_snap);
+
                 self.sprite_batches = prepare_sprite(
                     &self.extracted.sprites,
+                    pixel_snap,
                     &mut self.sprite_render_resources,
                     &self.texture_cache,
                     render_server,
                     &self.camera_render_resources.bind_group_layout,
                 );
 
+                prepare_light2d(
+                    &self.extracted.lights_2d,
+                    &self.extracted.occluders_2d,
+                    self.ambient_light_2d,
+                    &mut self.light2d_render_resources,
+                    render_server,
+                );
+
+                prepare_lines(
+                    &self.extracted.lines_2d,
+                    &mut self.line2d_render_resources,
+                    &mut self.sprite_render_resources,
+                    &self.texture_cache,
+                    render_server,
+                );
+
+                prepare_curves(
+                    &self.extracted.curves_2d,
+                    &mut self.curve2d_render_resources,
+                    &mut self.sprite_render_resources,
+                    &self.texture_cache,
+                    render_server,
+                );
+
                 prepare_atlas(
                     &self.extracted.atlases,
                     &mut self.atlas_render_resources,
@@ -137,47 +636,723 @@ impl RenderWorld {
                     &self.texture_cache,
                     &mut self.shader_maker,
                 );
+
+                prepare_clip(
+                    &self.extracted.clips,
+                    &mut self.clip_render_resources,
+                    render_server,
+                );
+
+                prepare_ui_shapes(
+                    &self.extracted.ui_shapes,
+                    &mut self.ui_shape_render_resources,
+                    render_server,
+                );
             } else {
+                let view_position = self.extracted.cameras.uniforms[i].view_position;
+                let view_position =
+                    Vector3::new(view_position[0], view_position[1], view_position[2]);
+
                 prepare_meshes(
                     &self.extracted.meshes,
+                    &self.mesh_cache,
                     &self.extracted.lights,
                     &self.texture_cache,
                     &mut self.shader_maker,
                     &mut self.mesh_render_resources,
                     &self.camera_render_resources,
+                    &self.shadow_render_resources,
+                    &self.probe_render_resources,
+                    self.cluster_render_resources.as_ref(),
+                    view_position,
                     &render_server,
                 );
 
+                cull_meshes(
+                    &self.extracted.meshes,
+                    &mut self.mesh_render_resources,
+                    |mesh_id| self.occlusion_render_resources.is_occluded(mesh_id),
+                );
+
+                if let Some(oit_render_resources) = &mut self.oit_render_resources {
+                    prepare_oit(
+                        oit_render_resources,
+                        &self.mesh_render_resources,
+                        &self.texture_cache,
+                        render_server,
+                    );
+                }
+
                 if (self.extracted.sky.is_some()) {
                     prepare_sky(
                         &mut self.sky_render_resources,
                         render_server,
                         &self.texture_cache,
                         &self.extracted.sky.unwrap().texture,
+                        &mut self.shader_maker,
                         &self.camera_render_resources.bind_group_layout,
                     );
                 }
+
+                prepare_sprite3d(
+                    &self.extracted.sprites3d,
+                    view_position,
+                    &mut self.sprite3d_render_resources,
+                    &self.texture_cache,
+                    scene_depth_texture,
+                    render_server,
+                    &mut self.shader_maker,
+                    &self.camera_render_resources.bind_group_layout,
+                );
+
+                prepare_label3d(
+                    &self.extracted.labels3d,
+                    view_position,
+                    &mut self.label3d_render_resources,
+                    &self.texture_cache,
+                    render_server,
+                    &self.camera_render_resources.bind_group_layout,
+                );
+
+                self.gizmo_render_resources
+                    .prepare_handles(render_server, &self.extracted.gizmo_vertices);
+
+                prepare_debug_draw(
+                    &self.extracted.debug_lines,
+                    &self.extracted.mesh_normals,
+                    &mut self.debug_draw_render_resources,
+                    render_server,
+                );
+
+                prepare_trails(
+                    &self.extracted.trails,
+                    &mut self.trail_render_resources,
+                    render_server,
+                );
+            }
+        }
+
+        prepare_water(
+            &self.extracted.waters,
+            camera_3d
+                .map(|c| Vector3::new(c.view_position[0], c.view_position[1], c.view_position[2])),
+            &mut self.water_render_resources,
+            &self.texture_cache,
+            scene_depth_texture,
+            render_server,
+        );
+
+        self.backdrop_render_resources.enabled =
+            self.extracted.ui_shapes.iter().any(|s| s.backdrop_blur);
+
+        let post_process_enabled =
+            self.post_process_settings.is_enabled() || render_server.get_settings().hdr;
+
+        // Without post processing, the backdrop copy brings an offscreen scene to the surface.
+        let copy_to_surface = !post_process_enabled
+            && (self.backdrop_render_resources.enabled || self.water_render_resources.has_water());
+
+        prepare_backdrop(
+            self.backdrop_blur_radius,
+            copy_to_surface,
+            self.post_process_render_resources.scene_color_texture,
+            &mut self.backdrop_render_resources,
+            render_server,
+            &self.texture_cache,
+        );
+
+        prepare_transition(
+            &self.screen_transition,
+            &mut self.transition_render_resources,
+            render_server,
+        );
+
+        // Before the bind groups of transient targets are made.
+        self.prepare_frame_graph(render_server);
+
+        if post_process_enabled {
+            prepare_post_process(
+                &self.post_process_settings,
+                &mut self.post_process_render_resources,
+                render_server,
+                &self.texture_cache,
+            );
+        }
+
+        if let Some(display_output_render_resources) = &mut self.display_output_render_resources {
+            prepare_display_output(
+                &self.post_process_settings,
+                display_output_render_resources,
+                render_server,
+                &self.texture_cache,
+            );
+        }
+
+        self.update_gpu_memory(render_server);
+    }
+
+    /// Render scale of the 3D scene in use, see `ResolutionScale`.
+    pub fn get_render_scale(&self) -> f32 {
+        self.resolution_scale_render_resources.get_scale()
+    }
+
+    /// Size the 3D scene is drawn at, in pixels.
+    pub fn get_render_size(&self) -> (u32, u32) {
+        self.resolution_scale_render_resources.size
+    }
+
+    /// Time the GPU work of the frame just submitted, for dynamic resolution.
+    pub(crate) fn track_gpu_frame_time(&self, render_server: &RenderServer) {
+        self.resolution_scale_render_resources
+            .track_submission(render_server);
+    }
+
+    /// Area the 2D camera draws to, if not the whole view.
+    fn viewport_2d(&self) -> Option<ViewportRect> {
+        let cameras = &self.extracted.cameras;
+
+        cameras
+            .types
+            .iter()
+            .position(|t| *t == CameraType::D2)
+            .and_then(|i| cameras.viewports[i])
+    }
+
+    /// Depth of the 3D scene, smaller than the surface with resolution scaling.
+    fn scene_depth_texture(&self) -> TextureId {
+        self.resolution_scale_render_resources
+            .depth_texture()
+            .unwrap_or(self.surface_depth_texture)
+    }
+
+    /// GPU memory in use as of the last prepared frame.
+    pub fn get_gpu_memory(&self) -> GpuMemoryUsage {
+        self.gpu_memory
+    }
+
+    /// Draws of the last prepared frame.
+    pub fn get_render_stats(&self) -> RenderStats {
+        let extracted = &self.extracted;
+
+        let sprite_batches = self.sprite_batches.len();
+        let drawn_meshes = self.mesh_render_resources.draw_count();
+
+        // UI shapes are all drawn at once.
+        let draw_calls = sprite_batches
+            + extracted.atlases.len()
+            + drawn_meshes
+            + !extracted.ui_shapes.is_empty() as usize
+            + self.line2d_render_resources.draw_count()
+            + self.curve2d_render_resources.draw_count()
+            + self.sprite3d_render_resources.draw_count()
+            + self.label3d_render_resources.draw_count();
+
+        RenderStats {
+            draw_calls: draw_calls as u32,
+            sprites: extracted.sprites.len() as u32,
+            sprite_batches: sprite_batches as u32,
+            atlases: extracted.atlases.len() as u32,
+            meshes: extracted.meshes.len() as u32,
+            drawn_meshes: drawn_meshes as u32,
+        }
+    }
+
+    fn update_gpu_memory(&mut self, render_server: &RenderServer) {
+        self.gpu_memory = GpuMemoryUsage {
+            textures: self
+                .texture_cache
+                .storage
+                .values()
+                .map(|texture| texture.get_memory_size())
+                .sum(),
+            meshes: self
+                .mesh_cache
+                .storage
+                .values()
+                .map(|mesh| mesh.get_memory_size())
+                .sum(),
+            uniforms: render_server.get_uniform_memory(),
+        };
+
+        let Some(budget) = self.gpu_memory_budget else {
+            self.over_gpu_memory_budget = false;
+            return;
+        };
+
+        let over_budget = self.gpu_memory.total() > budget;
+
+        if over_budget && !self.over_gpu_memory_budget {
+            let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+            log::warn!(
+                "GPU memory over budget: {:.1} MiB of {:.1} MiB (textures {:.1}, meshes {:.1}, uniforms {:.1})",
+                mib(self.gpu_memory.total()),
+                mib(budget),
+                mib(self.gpu_memory.textures),
+                mib(self.gpu_memory.meshes),
+                mib(self.gpu_memory.uniforms),
+            );
+        }
+
+        self.over_gpu_memory_budget = over_budget;
+    }
+
+    fn frame_passes(&self, render_server: &RenderServer) -> FramePasses {
+        // Draw the scene into an offscreen texture if there are post effects to apply,
+        // if the UI needs a blurred copy of it, if water refracts it, or if it's HDR
+        // and has to be resolved.
+        let post_process =
+            self.post_process_settings.is_enabled() || render_server.get_settings().hdr;
+        let backdrop = self.backdrop_render_resources.enabled;
+        let soft_sprites = self.sprite3d_render_resources.has_soft_sprites();
+        let water = self.water_render_resources.has_water();
+        let contact_shadows = self.contact_shadow_render_resources.enabled;
+        let occlusion_queries = self.occlusion_render_resources.query_set().is_some();
+        let oit = self
+            .oit_render_resources
+            .as_ref()
+            .is_some_and(|oit| oit.enabled);
+        let scaled = self.resolution_scale_render_resources.is_scaled();
+
+        FramePasses {
+            post_process,
+            backdrop,
+            soft_sprites,
+            water,
+            contact_shadows,
+            oit,
+            scaled,
+            separate_ui_pass: scaled
+                || backdrop
+                || soft_sprites
+                || water
+                || contact_shadows
+                || occlusion_queries
+                || oit,
+            offscreen: post_process || backdrop || water,
+        }
+    }
+
+    /// Describe the passes `encode_frame` is going to encode, so that transient targets
+    /// can share textures. Has to follow the same choices.
+    fn build_frame_graph(&self, render_server: &RenderServer) -> FrameGraph {
+        let passes = self.frame_passes(render_server);
+        let mut graph = FrameGraph::default();
+
+        let scene = if passes.offscreen {
+            SCENE_COLOR_TARGET
+        } else {
+            SURFACE
+        };
+        let scene_3d = if passes.scaled {
+            "scaled scene color"
+        } else {
+            scene
+        };
+
+        if self.light2d_render_resources.enabled {
+            graph.add_pass("light map", &[], &["light map"]);
+        }
+        graph.add_pass("morph targets", &[], &["morphed vertices"]);
+        if self.cluster_render_resources.is_some() {
+            graph.add_pass("light clusters", &[], &["light clusters"]);
+        }
+        graph.add_pass("shadows", &["morphed vertices"], &["shadow maps"]);
+
+        graph.add_pass(
+            "main",
+            &[
+                "light map",
+                "morphed vertices",
+                "light clusters",
+                "shadow maps",
+            ],
+            &[scene_3d, "scene depth"],
+        );
+
+        if passes.contact_shadows {
+            graph.add_pass("contact shadows", &["scene depth"], &[scene_3d]);
+        }
+        if passes.oit {
+            graph.add_pass("oit", &["scene depth"], &[scene_3d]);
+        }
+        if passes.water {
+            graph.add_pass("water", &[scene_3d, "scene depth"], &[scene_3d]);
+        }
+        if passes.soft_sprites {
+            graph.add_pass("sprite3d depth fade", &["scene depth"], &[scene_3d]);
+        }
+        if passes.scaled {
+            graph.add_pass("upscale", &[scene_3d], &[scene]);
+        }
+
+        if passes.separate_ui_pass {
+            if passes.backdrop {
+                graph.add_pass("backdrop blur", &[scene], &["backdrop"]);
+            }
+
+            let ui_target = if passes.offscreen && !passes.post_process {
+                graph.add_pass("backdrop copy", &[scene], &[SURFACE]);
+                SURFACE
+            } else {
+                scene
+            };
+
+            graph.add_pass("ui", &["backdrop"], &[ui_target, "surface depth"]);
+        }
+
+        let output = if self.display_output_render_resources.is_some() {
+            DISPLAY_OUTPUT_TARGET
+        } else {
+            SURFACE
+        };
+
+        if passes.post_process {
+            add_post_process_passes(
+                &self.post_process_settings,
+                render_server,
+                &mut graph,
+                output,
+            );
+        }
+        if self.transition_render_resources.enabled {
+            graph.add_pass("transition", &[], &[output]);
+        }
+        if self.display_output_render_resources.is_some() {
+            graph.add_pass("display output", &[DISPLAY_OUTPUT_TARGET], &[SURFACE]);
+        }
+
+        graph
+    }
+
+    fn prepare_frame_graph(&mut self, render_server: &RenderServer) {
+        let graph = self.build_frame_graph(render_server);
+
+        self.transient_targets
+            .assign(&graph, render_server, &mut self.texture_cache);
+
+        if self.validate_frame_graph && graph != self.frame_graph {
+            graph.validate(&self.transient_targets);
+        }
+
+        self.frame_graph = graph;
+    }
+
+    /// Encode all passes of a frame into `view`, which has the surface's size and format.
+    pub(crate) fn encode_frame(
+        &self,
+        render_server: &RenderServer,
+        view: &wgpu::TextureView,
+    ) -> wgpu::CommandEncoder {
+        let depth_texture = self.texture_cache.get(self.surface_depth_texture).unwrap();
+        let scene_depth_texture = self.texture_cache.get(self.scene_depth_texture()).unwrap();
+
+        // Keep `build_frame_graph` in sync when changing which passes run.
+        let FramePasses {
+            post_process: post_process_enabled,
+            backdrop: backdrop_enabled,
+            soft_sprites,
+            water,
+            scaled,
+            separate_ui_pass,
+            offscreen,
+            ..
+        } = self.frame_passes(render_server);
+        let occlusion_query_set = self.occlusion_render_resources.query_set();
+
+        let scene_view = if offscreen {
+            &self
+                .texture_cache
+                .get(self.post_process_render_resources.scene_color_texture)
+                .unwrap()
+                .view
+        } else {
+            view
+        };
+
+        // With resolution scaling, the 3D scene is drawn smaller and upscaled into `scene_view`.
+        let scene_3d_texture = self
+            .resolution_scale_render_resources
+            .color_texture()
+            .unwrap_or(self.post_process_render_resources.scene_color_texture);
+        let scene_3d_view = if scaled {
+            &self.texture_cache.get(scene_3d_texture).unwrap().view
+        } else {
+            scene_view
+        };
+
+        // Builds a command buffer that we can then send to the GPU.
+        let mut encoder =
+            render_server
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("main render encoder"),
+                });
+
+        // Sprites are lit by the 2D light map, so draw it first.
+        if let Some(camera_bind_group) = &self.camera_render_resources.bind_group {
+            render_light_map(
+                &self.light2d_render_resources,
+                &self.texture_cache,
+                &depth_texture.view,
+                camera_bind_group,
+                self.viewport_2d(),
+                &mut encoder,
+            );
+        }
+
+        // Shadows and the main pass draw the blended vertices.
+        render_morph_targets(&self.morph_render_resources, &mut encoder);
+
+        if let Some(cluster_render_resources) = &self.cluster_render_resources {
+            render_clusters(cluster_render_resources, &mut encoder);
+        }
+
+        render_shadows(
+            &self.shadow_render_resources,
+            &self.extracted.meshes,
+            &self.mesh_cache,
+            &self.mesh_render_resources,
+            &mut encoder,
+        );
+
+        // The RenderPass has all the methods to do the actual drawing.
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("main render pass"),
+                color_attachments: &[
+                    // This is what @location(0) in the fragment shader targets.
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: scene_3d_view, // Change this to change where to draw.
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(self.clear_color.into()),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &scene_depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(render_server.depth_clear_value()),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    // Used for UI clipping.
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set,
+            });
+
+            if separate_ui_pass {
+                // The UI is drawn in a separate pass once the scene has been blurred.
+                self.render_cameras(&mut render_pass, |t| *t != CameraType::D2);
+            } else {
+                self.render(&mut render_pass);
+            }
+
+            // Tested against the whole scene, so last.
+            if let Some(camera_bind_group) = &self.camera_render_resources.bind_group {
+                render_occlusion_queries(
+                    &self.occlusion_render_resources,
+                    &mut render_pass,
+                    camera_bind_group,
+                );
+            }
+        }
+
+        resolve_occlusion_queries(&self.occlusion_render_resources, &mut encoder);
+
+        // Before the passes that don't write depth, which the rays can't see.
+        render_contact_shadows(
+            &self.contact_shadow_render_resources,
+            &self.globals_render_resources.bind_group,
+            &mut encoder,
+            scene_3d_view,
+        );
+
+        // Where blended meshes would have been drawn in the main pass.
+        if let Some(oit_render_resources) = &self.oit_render_resources {
+            render_oit(
+                oit_render_resources,
+                &self.extracted.meshes,
+                &self.mesh_cache,
+                &self.mesh_render_resources,
+                &self.camera_render_resources,
+                &self.texture_cache,
+                &scene_depth_texture.view,
+                &mut encoder,
+                scene_3d_view,
+            );
+        }
+
+        // Before soft sprites, so that particles above the water show.
+        if water {
+            render_water(
+                &self.water_render_resources,
+                &self.texture_cache,
+                scene_3d_texture,
+                self.camera_render_resources.bind_group.as_ref().unwrap(),
+                &mut encoder,
+            );
+        }
+
+        if soft_sprites {
+            // Without depth, so that the sprites can sample it.
+            // GL keeps pass labels next to push constants, a length that isn't a multiple
+            // of 4 would misalign them.
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("sprite3d depth fade pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: scene_3d_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_soft_sprite3d(
+                &self.sprite3d_render_resources,
+                &mut render_pass,
+                self.camera_render_resources.bind_group.as_ref().unwrap(),
+            );
+        }
+
+        if scaled {
+            render_upscale(
+                &self.resolution_scale_render_resources,
+                &mut encoder,
+                scene_view,
+            );
+        }
+
+        if separate_ui_pass {
+            if backdrop_enabled {
+                render_backdrop(
+                    &self.backdrop_render_resources,
+                    &self.texture_cache,
+                    &mut encoder,
+                );
+            }
+
+            // Without post processing, nothing else brings the offscreen scene to the surface.
+            let ui_view = if offscreen && !post_process_enabled {
+                render_backdrop_copy(&self.backdrop_render_resources, &mut encoder, view);
+                view
+            } else {
+                scene_view
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("ui render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: ui_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        // The scaled 3D scene has its own depth.
+                        load: if scaled {
+                            wgpu::LoadOp::Clear(render_server.depth_clear_value())
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.render_cameras(&mut render_pass, |t| *t == CameraType::D2);
+        }
+
+        // With HDR display output, the final image is encoded for the display at the end.
+        let output_view = match &self.display_output_render_resources {
+            Some(display_output_render_resources) => {
+                &self
+                    .texture_cache
+                    .get(display_output_render_resources.output_texture)
+                    .unwrap()
+                    .view
             }
+            None => view,
+        };
+
+        if post_process_enabled {
+            render_post_process(
+                &self.post_process_render_resources,
+                &self.globals_render_resources.bind_group,
+                &self.texture_cache,
+                &mut encoder,
+                output_view,
+            );
+        }
+
+        // Transitions cover everything, including the UI and post effects.
+        if self.transition_render_resources.enabled {
+            render_transition(
+                &self.transition_render_resources,
+                &self.globals_render_resources.bind_group,
+                &mut encoder,
+                output_view,
+            );
         }
+
+        if let Some(display_output_render_resources) = &self.display_output_render_resources {
+            render_display_output(display_output_render_resources, &mut encoder, view);
+        }
+
+        encoder
     }
 
     // Send draw calls.
     pub(crate) fn render<'a, 'b: 'a>(&'b self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.render_cameras(render_pass, |_| true);
+    }
+
+    /// Draw only the cameras whose type passes `filter`. Used to split 3D and UI
+    /// into separate passes for the backdrop blur.
+    pub(crate) fn render_cameras<'a, 'b: 'a>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        filter: impl Fn(&CameraType) -> bool,
+    ) {
         for i in 0..self.extracted.cameras.uniforms.len() {
+            if !filter(&self.extracted.cameras.types[i]) {
+                continue;
+            }
+
             if self.extracted.cameras.types[i] == CameraType::D2 {
-                render_atlas(
-                    &self.extracted.atlases,
-                    &self.atlas_render_resources,
-                    render_pass,
-                );
+                let viewport = self.extracted.cameras.viewports[i];
 
-                // Draw sprites.
-                render_sprite(
-                    &self.sprite_batches,
-                    &self.sprite_render_resources,
-                    render_pass,
-                    self.camera_render_resources.bind_group.as_ref().unwrap(),
-                );
+                // Leave the letterbox out.
+                if let Some(viewport) = viewport {
+                    viewport.set(render_pass);
+                }
+
+                self.render_2d(render_pass);
+
+                if let Some(viewport) = viewport {
+                    viewport.reset(render_pass);
+                }
             } else {
                 if (self.camera_render_resources.bind_group.is_some()) {
                     render_sky(
@@ -195,10 +1370,152 @@ impl RenderWorld {
                     &self.gizmo_render_resources,
                     render_pass,
                 );
+
+                render_debug_draw(
+                    &self.extracted.mesh_normals,
+                    &self.mesh_cache,
+                    &self.debug_draw_render_resources,
+                    self.camera_render_resources.bind_group.as_ref().unwrap(),
+                    render_pass,
+                );
+
+                // Draw 3D sprites after opaque meshes.
+                render_sprite3d(
+                    &self.sprite3d_render_resources,
+                    render_pass,
+                    self.camera_render_resources.bind_group.as_ref().unwrap(),
+                );
+
+                render_label3d(
+                    &self.label3d_render_resources,
+                    render_pass,
+                    self.camera_render_resources.bind_group.as_ref().unwrap(),
+                );
+
+                render_trails(
+                    &self.trail_render_resources,
+                    render_pass,
+                    self.camera_render_resources.bind_group.as_ref().unwrap(),
+                );
+
+                // Gizmo handles go on top of the 3D scene.
+                self.gizmo_render_resources.render_handles(
+                    render_pass,
+                    self.camera_render_resources.bind_group.as_ref().unwrap(),
+                );
             }
         }
     }
 
+    /// Draw sprites and atlases in the order of the clip commands,
+    /// so that each part is stencil tested against the clip depth at that point.
+    fn render_2d<'a, 'b: 'a>(&'b self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let extracted = &self.extracted;
+
+        self.gizmo_render_resources.render_grid_2d(
+            render_pass,
+            self.camera_render_resources.bind_group.as_ref().unwrap(),
+        );
+
+        let mut depth = 0;
+        let mut sprite_start = 0;
+        let mut atlas_start = 0;
+        let mut ui_shape_start = 0;
+        let mut line_start = 0;
+        let mut curve_start = 0;
+
+        for command in &extracted.clip_commands {
+            self.render_2d_range(
+                sprite_start..command.sprite_index,
+                atlas_start..command.atlas_index,
+                ui_shape_start..command.ui_shape_index,
+                line_start..command.line_index,
+                curve_start..command.curve_index,
+                depth,
+                render_pass,
+            );
+            sprite_start = command.sprite_index;
+            atlas_start = command.atlas_index;
+            ui_shape_start = command.ui_shape_index;
+            line_start = command.line_index;
+            curve_start = command.curve_index;
+
+            render_clip(command, depth, &self.clip_render_resources, render_pass);
+
+            match command.op {
+                ClipOp::Push => depth += 1,
+                ClipOp::Pop => depth -= 1,
+            }
+        }
+
+        self.render_2d_range(
+            sprite_start..extracted.sprites.len(),
+            atlas_start..extracted.atlases.len(),
+            ui_shape_start..extracted.ui_shapes.len(),
+            line_start..extracted.lines_2d.len(),
+            curve_start..extracted.curves_2d.len(),
+            depth,
+            render_pass,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_2d_range<'a, 'b: 'a>(
+        &'b self,
+        sprites: Range<usize>,
+        atlases: Range<usize>,
+        ui_shapes: Range<usize>,
+        lines: Range<usize>,
+        curves: Range<usize>,
+        depth: u32,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        render_pass.set_stencil_reference(depth);
+
+        // Shapes are mostly backgrounds, so draw them first.
+        render_ui_shapes(
+            ui_shapes,
+            &self.ui_shape_render_resources,
+            &self.backdrop_render_resources.sample_bind_group,
+            render_pass,
+        );
+
+        render_atlas(
+            &self.extracted.atlases,
+            atlases,
+            &self.atlas_render_resources,
+            render_pass,
+        );
+
+        // Draw sprites.
+        render_sprite(
+            &self.sprite_batches,
+            sprites,
+            &self.sprite_render_resources,
+            render_pass,
+            self.camera_render_resources.bind_group.as_ref().unwrap(),
+            self.light2d_render_resources.get_sample_bind_group(),
+        );
+
+        render_lines(
+            lines,
+            &self.line2d_render_resources,
+            &self.sprite_render_resources,
+            render_pass,
+            self.camera_render_resources.bind_group.as_ref().unwrap(),
+            self.light2d_render_resources.get_sample_bind_group(),
+        );
+
+        render_curves(
+            curves,
+            &self.curve2d_render_resources,
+            &self.sprite_render_resources,
+            render_pass,
+            self.camera_render_resources.bind_group.as_ref().unwrap(),
+            self.light2d_render_resources.get_sample_bind_group(),
+        );
+    }
+
     pub fn recreate_depth_texture(&mut self, render_server: &RenderServer) {
         // Remove the previous depth texture.
         self.texture_cache.remove(self.surface_depth_texture);
@@ -210,6 +1527,7 @@ impl RenderWorld {
             &render_server.device,
             &mut self.texture_cache,
             &render_server.surface_config,
+            render_server.depth_format(),
             Some("surface depth texture"),
         );
     }
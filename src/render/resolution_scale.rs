@@ -0,0 +1,411 @@
+use crate::render::post_process::{begin_fullscreen_pass, create_fullscreen_pipeline_with_target};
+use crate::render::{RenderServer, Texture, TextureCache, TextureId, UniformBuffer};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+/// Lowest render scale, dynamic or not.
+const MIN_RENDER_SCALE: f32 = 0.1;
+
+/// Render scale change of one dynamic resolution adjustment.
+const DYNAMIC_SCALE_STEP: f32 = 0.05;
+
+/// Frames measured between dynamic resolution adjustments.
+const DYNAMIC_ADJUST_INTERVAL: u32 = 30;
+
+/// The render scale only goes up when frames take less than this fraction of the target time.
+/// One step up adds about 20% more pixels at a scale of 0.5.
+const DYNAMIC_HEADROOM: f32 = 0.75;
+
+/// Weight of the newest frame in the smoothed GPU frame time.
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+
+/// How the 3D scene is brought up to the window size when drawn at a lower resolution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    #[default]
+    Bilinear,
+    /// Bilinear, then contrast adaptive sharpening like AMD FSR 1, to recover some of the
+    /// detail lost to the upscale.
+    Sharpen,
+}
+
+/// Adjusts the render scale to hold a frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicResolution {
+    pub target_fps: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self {
+            target_fps: 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+        }
+    }
+}
+
+/// Draws the 3D scene at a fraction of the window size and upscales it.
+/// 2D cameras, and so the UI, are always drawn at the full size.
+#[derive(Debug, Clone)]
+pub struct ResolutionScale {
+    /// Fraction of the window size in each dimension, in (0, 1]. Unused with `dynamic`.
+    pub render_scale: f32,
+    pub filter: UpscaleFilter,
+    /// Strength of `UpscaleFilter::Sharpen`, in [0, 1].
+    pub sharpness: f32,
+    /// Pick the render scale from the GPU frame time instead. The GPU frame time is taken from
+    /// submission to completion, so it also counts waiting on earlier frames.
+    pub dynamic: Option<DynamicResolution>,
+}
+
+impl Default for ResolutionScale {
+    fn default() -> Self {
+        Self {
+            render_scale: 1.0,
+            filter: UpscaleFilter::Bilinear,
+            sharpness: 0.5,
+            dynamic: None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct UpscaleParamsUniform {
+    sharpness: f32,
+    _pad: [f32; 3],
+}
+
+pub(crate) struct ResolutionScaleRenderResources {
+    /// Render scale in use.
+    scale: f32,
+    /// Size the 3D scene is drawn at.
+    pub(crate) size: (u32, u32),
+    /// Color and depth of the 3D scene, when smaller than the surface.
+    targets: Option<(TextureId, TextureId)>,
+
+    // Dynamic resolution.
+    dynamic: bool,
+    /// Written once the GPU is done with the last tracked submission.
+    gpu_frame_time: Arc<Mutex<Option<f32>>>,
+    smoothed_frame_time: Option<f32>,
+    measured_frames: u32,
+
+    filter: UpscaleFilter,
+    params_buffer: UniformBuffer,
+    params_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group: Option<wgpu::BindGroup>,
+    bilinear_pipeline: wgpu::RenderPipeline,
+    sharpen_pipeline: wgpu::RenderPipeline,
+}
+
+impl ResolutionScaleRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let device = &render_server.device;
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("upscale params bind group layout"),
+            });
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "upscale params buffer",
+            mem::size_of::<UpscaleParamsUniform>() as BufferAddress,
+        );
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("upscale params bind group"),
+        });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+                label: Some("upscale texture bind group layout"),
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("upscale pipeline layout"),
+            bind_group_layouts: &[&params_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("upscale shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/upscale.wgsl").into()),
+        });
+
+        // Written into the scene color, before the UI and post processing.
+        let bilinear_pipeline = create_fullscreen_pipeline_with_target(
+            render_server,
+            &pipeline_layout,
+            &shader_module,
+            "fs_bilinear",
+            render_server.scene_format(),
+            wgpu::BlendState::REPLACE,
+            "upscale bilinear pipeline",
+        );
+
+        let sharpen_pipeline = create_fullscreen_pipeline_with_target(
+            render_server,
+            &pipeline_layout,
+            &shader_module,
+            "fs_sharpen",
+            render_server.scene_format(),
+            wgpu::BlendState::REPLACE,
+            "upscale sharpen pipeline",
+        );
+
+        let config = &render_server.surface_config;
+
+        Self {
+            scale: 1.0,
+            size: (config.width, config.height),
+            targets: None,
+            dynamic: false,
+            gpu_frame_time: Arc::new(Mutex::new(None)),
+            smoothed_frame_time: None,
+            measured_frames: 0,
+            filter: UpscaleFilter::Bilinear,
+            params_buffer,
+            params_bind_group,
+            texture_bind_group_layout,
+            texture_bind_group: None,
+            bilinear_pipeline,
+            sharpen_pipeline,
+        }
+    }
+
+    pub(crate) fn get_scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Whether the 3D scene is drawn smaller than the surface.
+    pub(crate) fn is_scaled(&self) -> bool {
+        self.targets.is_some()
+    }
+
+    pub(crate) fn color_texture(&self) -> Option<TextureId> {
+        self.targets.map(|(color, _)| color)
+    }
+
+    pub(crate) fn depth_texture(&self) -> Option<TextureId> {
+        self.targets.map(|(_, depth)| depth)
+    }
+
+    /// Time the work submitted last, for dynamic resolution. Call right after submitting a frame.
+    pub(crate) fn track_submission(&self, render_server: &RenderServer) {
+        if !self.dynamic {
+            return;
+        }
+
+        let submitted = Instant::now();
+        let gpu_frame_time = self.gpu_frame_time.clone();
+
+        render_server.queue.on_submitted_work_done(move || {
+            *gpu_frame_time.lock().unwrap() = Some(submitted.elapsed().as_secs_f32());
+        });
+    }
+
+    /// Step the render scale towards the target frame rate.
+    fn update_dynamic_scale(&mut self, dynamic: &DynamicResolution) {
+        let min_scale = dynamic.min_scale.clamp(MIN_RENDER_SCALE, 1.0);
+        let max_scale = dynamic.max_scale.clamp(min_scale, 1.0);
+
+        let measured = self.gpu_frame_time.lock().unwrap().take();
+
+        if let Some(frame_time) = measured {
+            self.smoothed_frame_time = Some(match self.smoothed_frame_time {
+                Some(smoothed) => smoothed + (frame_time - smoothed) * FRAME_TIME_SMOOTHING,
+                None => frame_time,
+            });
+            self.measured_frames += 1;
+        }
+
+        let mut scale = self.scale.clamp(min_scale, max_scale);
+
+        if self.measured_frames >= DYNAMIC_ADJUST_INTERVAL {
+            let frame_time = self.smoothed_frame_time.unwrap();
+            let target = 1.0 / dynamic.target_fps.max(1.0);
+
+            if frame_time > target {
+                scale -= DYNAMIC_SCALE_STEP;
+            } else if frame_time < target * DYNAMIC_HEADROOM {
+                scale += DYNAMIC_SCALE_STEP;
+            }
+
+            scale = scale.clamp(min_scale, max_scale);
+
+            // Times measured at the previous scale no longer apply.
+            if scale != self.scale {
+                self.smoothed_frame_time = None;
+            }
+            self.measured_frames = 0;
+        }
+
+        self.scale = scale;
+    }
+}
+
+/// Returns true if the size the 3D scene is drawn at has changed, so that targets
+/// following it have to be recreated.
+pub(crate) fn prepare_resolution_scale(
+    settings: &ResolutionScale,
+    render_resources: &mut ResolutionScaleRenderResources,
+    render_server: &RenderServer,
+    texture_cache: &mut TextureCache,
+) -> bool {
+    render_resources.dynamic = settings.dynamic.is_some();
+    render_resources.filter = settings.filter;
+
+    match &settings.dynamic {
+        Some(dynamic) => render_resources.update_dynamic_scale(dynamic),
+        None => {
+            render_resources.scale = settings.render_scale.clamp(MIN_RENDER_SCALE, 1.0);
+            render_resources.smoothed_frame_time = None;
+            render_resources.measured_frames = 0;
+        }
+    }
+
+    if settings.filter == UpscaleFilter::Sharpen {
+        let params = UpscaleParamsUniform {
+            sharpness: settings.sharpness,
+            _pad: [0.0; 3],
+        };
+
+        render_server.queue.write_buffer(
+            &render_resources.params_buffer,
+            0,
+            bytemuck::bytes_of(&params),
+        );
+    }
+
+    let config = &render_server.surface_config;
+    let scaled = |length: u32| ((length as f32 * render_resources.scale).round() as u32).max(1);
+    let size = (scaled(config.width), scaled(config.height));
+
+    let targets_match = match render_resources.targets {
+        Some((color, _)) => texture_cache.get(color).unwrap().size == size,
+        None => size == (config.width, config.height),
+    };
+
+    if render_resources.size == size && targets_match {
+        return false;
+    }
+
+    if let Some((color, depth)) = render_resources.targets.take() {
+        texture_cache.remove(color);
+        texture_cache.remove(depth);
+    }
+
+    render_resources.size = size;
+    render_resources.texture_bind_group = None;
+
+    // At full size, the 3D scene is drawn into the regular targets.
+    if size == (config.width, config.height) {
+        return true;
+    }
+
+    let mut scaled_config = config.clone();
+    scaled_config.width = size.0;
+    scaled_config.height = size.1;
+    scaled_config.format = render_server.scene_format();
+
+    let color = Texture::create_render_texture(
+        &render_server.device,
+        texture_cache,
+        &scaled_config,
+        Some("scaled scene color texture"),
+    );
+
+    let depth = Texture::create_depth_texture(
+        &render_server.device,
+        texture_cache,
+        &scaled_config,
+        render_server.depth_format(),
+        Some("scaled scene depth texture"),
+    );
+
+    let color_texture = texture_cache.get(color).unwrap();
+
+    render_resources.texture_bind_group = Some(render_server.device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            layout: &render_resources.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&color_texture.sampler),
+                },
+            ],
+            label: Some("upscale texture bind group"),
+        },
+    ));
+
+    render_resources.targets = Some((color, depth));
+
+    true
+}
+
+/// Draw the scaled 3D scene over all of `target_view`.
+pub(crate) fn render_upscale(
+    render_resources: &ResolutionScaleRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+) {
+    let Some(texture_bind_group) = &render_resources.texture_bind_group else {
+        return;
+    };
+
+    let pipeline = match render_resources.filter {
+        UpscaleFilter::Bilinear => &render_resources.bilinear_pipeline,
+        UpscaleFilter::Sharpen => &render_resources.sharpen_pipeline,
+    };
+
+    let mut render_pass = begin_fullscreen_pass(encoder, target_view, "upscale render pass");
+
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, &render_resources.params_bind_group, &[]);
+    render_pass.set_bind_group(1, texture_bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
@@ -10,7 +10,12 @@ pub struct ShaderMaker {
 
 impl ShaderMaker {
     pub fn new() -> Self {
-        let composer = Composer::default();
+        // Dual-source blending and push constants are only used when the device supports them.
+        let composer = Composer::default().with_capabilities(
+            naga::valid::Capabilities::default()
+                | naga::valid::Capabilities::DUAL_SOURCE_BLENDING
+                | naga::valid::Capabilities::PUSH_CONSTANT,
+        );
 
         Self { composer }
     }
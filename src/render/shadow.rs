@@ -0,0 +1,736 @@
+use crate::math::alignup_u32;
+use crate::render::camera::CameraUniform;
+use crate::render::light::ExtractedLights;
+use crate::render::mesh::{is_standard_layout, InstanceRaw};
+use crate::render::{
+    ExtractedMesh, MeshCache, MeshRenderResources, RenderServer, UniformBuffer, VertexLayout,
+};
+use crate::scene::OPENGL_TO_WGPU_MATRIX;
+use cgmath::{
+    Deg, EuclideanSpace, InnerSpace, Matrix4, MetricSpace, Point3, SquareMatrix, Vector3, Vector4,
+};
+use std::collections::HashMap;
+use std::mem;
+use wgpu::BufferAddress;
+
+pub(crate) const MAX_CASCADES: usize = 4;
+
+/// A point light is rendered from its position along each axis, both ways.
+const POINT_FACES: usize = 6;
+
+/// Point light faces are tiled in one texture, this many across and down. A texture with
+/// six layers is taken for a cube map by the GL backend.
+const POINT_TILES: (u32, u32) = (3, 2);
+
+/// Near plane of the point light faces.
+const POINT_NEAR: f32 = 0.05;
+
+const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Cascaded shadow maps for the directional light, and a shadow map for the first point
+/// light with `PointLight::cast_shadows` set.
+#[derive(Debug, Clone)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Between 1 and 4. More cascades keep shadows sharp further from the camera.
+    pub cascade_count: u32,
+    /// Width and height of each cascade's shadow map.
+    pub map_size: u32,
+    /// Shadows end this far from the camera, or at its far plane if that's closer.
+    pub max_distance: f32,
+    /// Blends the cascade splits between uniform (0) and logarithmic (1).
+    pub split_lambda: f32,
+    /// Fraction of each cascade over which it fades into the next one.
+    pub blend_fraction: f32,
+    /// PCF kernel radius in texels, 0 for a single filtered sample.
+    pub pcf_radius: u32,
+    /// Offsets receivers along their normals, in shadow map texels. Fights shadow acne.
+    pub normal_bias: f32,
+    /// Width and height of each of the six faces of the point light's shadow map.
+    pub point_map_size: u32,
+    /// Point light shadows end this far from the light.
+    pub point_max_distance: f32,
+    /// Also march short rays towards the light in screen space, which restores the
+    /// shadows where objects touch the ground that the bias removes. They darken the
+    /// final color, ambient light included.
+    pub contact_shadows: bool,
+    /// Length of the contact shadow rays in world units.
+    pub contact_shadow_length: f32,
+    /// How dark contact shadows get, in [0, 1].
+    pub contact_shadow_strength: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cascade_count: 3,
+            map_size: 2048,
+            max_distance: 50.0,
+            split_lambda: 0.75,
+            blend_fraction: 0.1,
+            pcf_radius: 1,
+            normal_bias: 1.0,
+            point_map_size: 512,
+            point_max_distance: 25.0,
+            contact_shadows: false,
+            contact_shadow_length: 0.3,
+            contact_shadow_strength: 0.6,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ShadowUniform {
+    light_view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+    /// Camera view depth where each cascade ends.
+    split_depths: [f32; MAX_CASCADES],
+    /// World size of a shadow map texel in each cascade.
+    texel_sizes: [f32; MAX_CASCADES],
+    /// Zero if there are no shadows.
+    cascade_count: u32,
+    blend_fraction: f32,
+    pcf_radius: u32,
+    normal_bias: f32,
+    /// View-projection of each face of the point light's shadow map.
+    point_view_proj: [[[f32; 4]; 4]; POINT_FACES],
+    point_light_position: [f32; 3],
+    /// Index of the point light with shadows, `u32::MAX` if there is none.
+    point_light_index: u32,
+    /// World size of a shadow map texel one unit away from the point light.
+    point_texel_size: f32,
+    _pad: [f32; 3],
+}
+
+impl Default for ShadowUniform {
+    fn default() -> Self {
+        Self {
+            light_view_proj: [Matrix4::identity().into(); MAX_CASCADES],
+            split_depths: [0.0; MAX_CASCADES],
+            texel_sizes: [0.0; MAX_CASCADES],
+            cascade_count: 0,
+            blend_fraction: 0.0,
+            pcf_radius: 0,
+            normal_bias: 0.0,
+            point_view_proj: [Matrix4::identity().into(); POINT_FACES],
+            point_light_position: [0.0; 3],
+            point_light_index: u32::MAX,
+            point_texel_size: 0.0,
+            _pad: [0.0; 3],
+        }
+    }
+}
+
+pub(crate) struct ShadowRenderResources {
+    map_size: u32,
+    texture: wgpu::Texture,
+    /// All cascades, for sampling.
+    pub(crate) array_view: wgpu::TextureView,
+    /// One per cascade, for rendering.
+    layer_views: Vec<wgpu::TextureView>,
+    pub(crate) sampler: wgpu::Sampler,
+    /// Changes when a shadow map is recreated, so bind groups using it can be too.
+    pub(crate) generation: u32,
+
+    /// Size of a point light face.
+    point_map_size: u32,
+    point_texture: wgpu::Texture,
+    /// The faces of the point light, tiled.
+    pub(crate) point_view: wgpu::TextureView,
+
+    pub(crate) uniform_buffer: UniformBuffer,
+
+    /// A light view-projection matrix per cascade, then per point light face, at
+    /// dynamic offsets.
+    cascade_buffer: UniformBuffer,
+    cascade_offset_unit: u32,
+    cascade_bind_group: wgpu::BindGroup,
+    cascade_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Double-sided materials cast shadows from back faces too, so culling is part of the key.
+    pipeline_cache: HashMap<(VertexLayout, Option<wgpu::Face>), wgpu::RenderPipeline>,
+
+    /// Cascades to render this frame.
+    cascade_count: usize,
+    /// Whether point light faces are rendered this frame.
+    point_shadows: bool,
+}
+
+impl ShadowRenderResources {
+    pub(crate) fn new(render_server: &RenderServer, settings: &ShadowSettings) -> Self {
+        let device = &render_server.device;
+
+        let map_size = settings.map_size.max(1);
+        let (texture, array_view, layer_views) = Self::create_shadow_map(device, map_size);
+
+        let point_map_size = point_map_size(device, settings);
+        let (point_texture, point_view) = Self::create_point_shadow_map(device, point_map_size);
+
+        // Bilinear filtering compares the four nearest texels, which smooths PCF.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow map sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform_buffer = render_server.create_uniform_buffer(
+            "shadow uniform buffer",
+            mem::size_of::<ShadowUniform>() as BufferAddress,
+        );
+
+        let offset_limit = device.limits().min_uniform_buffer_offset_alignment;
+        let cascade_offset_unit =
+            alignup_u32(mem::size_of::<[[f32; 4]; 4]>() as u32, offset_limit) * offset_limit;
+
+        let cascade_buffer = render_server.create_uniform_buffer(
+            "shadow cascade uniform buffer",
+            (cascade_offset_unit * (MAX_CASCADES + POINT_FACES) as u32) as BufferAddress,
+        );
+
+        let cascade_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("shadow cascade bind group layout"),
+            });
+
+        let cascade_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &cascade_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &cascade_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(mem::size_of::<[[f32; 4]; 4]>() as u64),
+                }),
+            }],
+            label: Some("shadow cascade bind group"),
+        });
+
+        Self {
+            map_size,
+            texture,
+            array_view,
+            layer_views,
+            sampler,
+            generation: 0,
+            point_map_size,
+            point_texture,
+            point_view,
+            uniform_buffer,
+            cascade_buffer,
+            cascade_offset_unit,
+            cascade_bind_group,
+            cascade_bind_group_layout,
+            pipeline_cache: HashMap::new(),
+            cascade_count: 0,
+            point_shadows: false,
+        }
+    }
+
+    fn create_shadow_map(
+        device: &wgpu::Device,
+        map_size: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView, Vec<wgpu::TextureView>) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow map"),
+            size: wgpu::Extent3d {
+                width: map_size,
+                height: map_size,
+                depth_or_array_layers: MAX_CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_MAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("shadow map array view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let layer_views = (0..MAX_CASCADES as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("shadow map cascade view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        (texture, array_view, layer_views)
+    }
+
+    fn create_point_shadow_map(
+        device: &wgpu::Device,
+        face_size: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("point shadow map"),
+            size: wgpu::Extent3d {
+                width: face_size * POINT_TILES.0,
+                height: face_size * POINT_TILES.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_MAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (texture, view)
+    }
+
+    fn prepare_pipeline(
+        &mut self,
+        render_server: &RenderServer,
+        vertex_layout: &VertexLayout,
+        cull_mode: Option<wgpu::Face>,
+    ) {
+        let key = (vertex_layout.clone(), cull_mode);
+
+        // Meshes the standard shader can't draw don't cast shadows either.
+        if !is_standard_layout(vertex_layout) || self.pipeline_cache.contains_key(&key) {
+            return;
+        }
+
+        let device = &render_server.device;
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shadow pipeline layout"),
+            bind_group_layouts: &[&self.cascade_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shadow shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/shadow.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shadow pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[vertex_layout.as_buffer_layout(), InstanceRaw::desc()],
+            },
+            // Depth only.
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode,
+                ..Default::default()
+            },
+            // Shadow maps always use standard depth.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_MAP_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        self.pipeline_cache.insert(key, pipeline);
+    }
+}
+
+/// Size of a point light face, so that the tiled faces fit in a texture.
+fn point_map_size(device: &wgpu::Device, settings: &ShadowSettings) -> u32 {
+    let max_size = device.limits().max_texture_dimension_2d / POINT_TILES.0;
+    settings.point_map_size.clamp(1, max_size)
+}
+
+/// Fit the cascades to the camera's frustum and point the faces of the point light's
+/// shadow map along the axes, and write their matrices.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prepare_shadows(
+    settings: &ShadowSettings,
+    extracted_lights: &ExtractedLights,
+    camera: Option<&CameraUniform>,
+    extracted_meshes: &[ExtractedMesh],
+    mesh_cache: &MeshCache,
+    mesh_render_resources: &MeshRenderResources,
+    render_resources: &mut ShadowRenderResources,
+    render_server: &RenderServer,
+) {
+    let map_size = settings.map_size.max(1);
+    if map_size != render_resources.map_size {
+        let (texture, array_view, layer_views) =
+            ShadowRenderResources::create_shadow_map(&render_server.device, map_size);
+
+        render_resources.map_size = map_size;
+        render_resources.texture = texture;
+        render_resources.array_view = array_view;
+        render_resources.layer_views = layer_views;
+        render_resources.generation += 1;
+    }
+
+    let point_map_size = point_map_size(&render_server.device, settings);
+    if point_map_size != render_resources.point_map_size {
+        let (point_texture, point_view) =
+            ShadowRenderResources::create_point_shadow_map(&render_server.device, point_map_size);
+
+        render_resources.point_map_size = point_map_size;
+        render_resources.point_texture = point_texture;
+        render_resources.point_view = point_view;
+        render_resources.generation += 1;
+    }
+
+    let mut uniform = ShadowUniform::default();
+    render_resources.cascade_count = 0;
+    render_resources.point_shadows = false;
+
+    let offset_unit = render_resources.cascade_offset_unit as usize;
+    let mut cascade_data = vec![0u8; offset_unit * (MAX_CASCADES + POINT_FACES)];
+
+    let direction = extracted_lights
+        .directional_light
+        .filter(|_| settings.enabled && extracted_lights.directional_light_shadows)
+        .map(|light| Vector3::from(light.direction))
+        .filter(|direction| direction.magnitude2() > 0.0);
+
+    if let (Some(direction), Some(camera)) = (direction, camera) {
+        let cascade_count = settings.cascade_count.clamp(1, MAX_CASCADES as u32) as usize;
+
+        let cascades = fit_cascades(
+            camera,
+            direction.normalize(),
+            cascade_count,
+            settings,
+            map_size,
+            render_server.get_settings().reverse_z,
+        );
+
+        for (i, cascade) in cascades.iter().enumerate() {
+            let matrix: [[f32; 4]; 4] = cascade.view_proj.into();
+            uniform.light_view_proj[i] = matrix;
+            uniform.split_depths[i] = cascade.split_depth;
+            uniform.texel_sizes[i] = cascade.texel_size;
+
+            let offset = i * offset_unit;
+            let bytes = bytemuck::cast_slice(&matrix);
+            cascade_data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        uniform.cascade_count = cascade_count as u32;
+        render_resources.cascade_count = cascade_count;
+    }
+
+    let point_light = extracted_lights
+        .point_light_shadows
+        .filter(|_| settings.enabled)
+        .and_then(|index| Some((index, extracted_lights.point_lights.get(index)?)));
+
+    if let Some((index, light)) = point_light {
+        let position = Point3::from(light.position);
+        let far = settings.point_max_distance.max(POINT_NEAR * 2.0);
+        let proj = OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Deg(90.0), 1.0, POINT_NEAR, far);
+
+        // +X, -X, +Y, -Y, +Z, -Z, the order the shader picks faces in.
+        let faces = [
+            (Vector3::unit_x(), Vector3::unit_y()),
+            (-Vector3::unit_x(), Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+            (-Vector3::unit_y(), Vector3::unit_z()),
+            (Vector3::unit_z(), Vector3::unit_y()),
+            (-Vector3::unit_z(), Vector3::unit_y()),
+        ];
+
+        for (i, (direction, up)) in faces.into_iter().enumerate() {
+            let view = Matrix4::look_at_rh(position, position + direction, up);
+            let matrix: [[f32; 4]; 4] = (proj * view).into();
+            uniform.point_view_proj[i] = matrix;
+
+            let offset = (MAX_CASCADES + i) * offset_unit;
+            let bytes = bytemuck::cast_slice(&matrix);
+            cascade_data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        uniform.point_light_position = light.position;
+        uniform.point_light_index = index as u32;
+        // A face spans 90 degrees, two units at one unit away.
+        uniform.point_texel_size = 2.0 / point_map_size as f32;
+        render_resources.point_shadows = true;
+    }
+
+    if render_resources.cascade_count > 0 || render_resources.point_shadows {
+        uniform.blend_fraction = settings.blend_fraction.clamp(0.0, 1.0);
+        uniform.pcf_radius = settings.pcf_radius;
+        uniform.normal_bias = settings.normal_bias;
+
+        render_server
+            .queue
+            .write_buffer(&render_resources.cascade_buffer, 0, &cascade_data);
+
+        for extracted in extracted_meshes {
+            if let Some(mesh) = mesh_cache.get(extracted.mesh_id) {
+                render_resources.prepare_pipeline(
+                    render_server,
+                    &mesh.vertex_layout,
+                    mesh_render_resources.cull_mode(extracted),
+                );
+            }
+        }
+    }
+
+    render_server.queue.write_buffer(
+        &render_resources.uniform_buffer,
+        0,
+        bytemuck::cast_slice(&[uniform]),
+    );
+}
+
+struct Cascade {
+    view_proj: Matrix4<f32>,
+    split_depth: f32,
+    texel_size: f32,
+}
+
+/// Each cascade is fit to a bounding sphere of its slice of the view frustum, which doesn't
+/// change size as the camera turns, and snapped to whole texels, so shadow edges don't swim.
+fn fit_cascades(
+    camera: &CameraUniform,
+    light_direction: Vector3<f32>,
+    cascade_count: usize,
+    settings: &ShadowSettings,
+    map_size: u32,
+    reverse_z: bool,
+) -> Vec<Cascade> {
+    let view = Matrix4::from(camera.view);
+    let proj = Matrix4::from(camera.proj);
+
+    let Some(inverse_view_proj) = (proj * view).invert() else {
+        return vec![];
+    };
+
+    let (near_z, far_z) = if reverse_z { (1.0, 0.0) } else { (0.0, 1.0) };
+
+    let unproject = |x: f32, y: f32, z: f32| {
+        let point = inverse_view_proj * Vector4::new(x, y, z, 1.0);
+        Point3::from_homogeneous(point)
+    };
+
+    // Rays along the frustum edges, from the near to the far plane.
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .map(|(x, y)| (unproject(x, y, near_z), unproject(x, y, far_z)));
+
+    let view_depth = |point: Point3<f32>| -(view * point.to_homogeneous()).z;
+    let camera_near = view_depth(corners[0].0);
+    let camera_far = view_depth(corners[0].1);
+    let shadow_far = camera_far.min(settings.max_distance.max(camera_near));
+
+    let split = |i: usize| {
+        let fraction = i as f32 / cascade_count as f32;
+        let log = camera_near * (shadow_far / camera_near).powf(fraction);
+        let uniform = camera_near + (shadow_far - camera_near) * fraction;
+        settings.split_lambda * log + (1.0 - settings.split_lambda) * uniform
+    };
+
+    let up = if light_direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    };
+
+    (0..cascade_count)
+        .map(|i| {
+            let (start, end) = (split(i), split(i + 1));
+
+            let mut slice = vec![];
+            for (near, far) in &corners {
+                let along = |depth: f32| {
+                    near + (far - near) * ((depth - camera_near) / (camera_far - camera_near))
+                };
+                slice.push(along(start));
+                slice.push(along(end));
+            }
+
+            let center = Point3::from_vec(
+                slice.iter().map(|p| p.to_vec()).sum::<Vector3<f32>>() / slice.len() as f32,
+            );
+            let radius = slice
+                .iter()
+                .map(|p| p.distance(center))
+                .fold(0.0f32, f32::max);
+            // Round up, so small float errors don't change the size.
+            let radius = (radius * 16.0).ceil() / 16.0;
+
+            // Back the light up to include casters in front of the slice.
+            let pull_back = settings.max_distance.max(radius);
+            let eye = center + light_direction * (radius + pull_back);
+            let light_view = Matrix4::look_at_rh(eye, center, up);
+            let mut light_proj = OPENGL_TO_WGPU_MATRIX
+                * cgmath::ortho(
+                    -radius,
+                    radius,
+                    -radius,
+                    radius,
+                    0.0,
+                    radius * 2.0 + pull_back,
+                );
+
+            // Snap the world origin to a texel, which snaps everything else too.
+            let half_size = map_size as f32 * 0.5;
+            let origin = (light_proj * light_view) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+            let texel = origin.truncate().truncate() * half_size;
+            let offset = (texel.map(f32::round) - texel) / half_size;
+            light_proj.w.x += offset.x;
+            light_proj.w.y += offset.y;
+
+            Cascade {
+                view_proj: light_proj * light_view,
+                split_depth: end,
+                texel_size: radius * 2.0 / map_size as f32,
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn render_shadows(
+    render_resources: &ShadowRenderResources,
+    extracted_meshes: &[ExtractedMesh],
+    mesh_cache: &MeshCache,
+    mesh_render_resources: &MeshRenderResources,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    for cascade in 0..render_resources.cascade_count {
+        let mut render_pass = begin_shadow_pass(encoder, &render_resources.layer_views[cascade]);
+
+        render_pass.set_bind_group(
+            0,
+            &render_resources.cascade_bind_group,
+            &[cascade as u32 * render_resources.cascade_offset_unit],
+        );
+
+        draw_casters(
+            &mut render_pass,
+            render_resources,
+            extracted_meshes,
+            mesh_cache,
+            mesh_render_resources,
+        );
+    }
+
+    if render_resources.point_shadows {
+        let mut render_pass = begin_shadow_pass(encoder, &render_resources.point_view);
+
+        let size = render_resources.point_map_size;
+
+        for face in 0..POINT_FACES as u32 {
+            let (x, y) = (face % POINT_TILES.0, face / POINT_TILES.0);
+            render_pass.set_viewport(
+                (x * size) as f32,
+                (y * size) as f32,
+                size as f32,
+                size as f32,
+                0.0,
+                1.0,
+            );
+
+            render_pass.set_bind_group(
+                0,
+                &render_resources.cascade_bind_group,
+                &[(MAX_CASCADES as u32 + face) * render_resources.cascade_offset_unit],
+            );
+
+            draw_casters(
+                &mut render_pass,
+                render_resources,
+                extracted_meshes,
+                mesh_cache,
+                mesh_render_resources,
+            );
+        }
+    }
+}
+
+fn begin_shadow_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    view: &'a wgpu::TextureView,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("shadow render pass"),
+        color_attachments: &[],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }),
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+fn draw_casters<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>,
+    render_resources: &'a ShadowRenderResources,
+    extracted_meshes: &[ExtractedMesh],
+    mesh_cache: &'a MeshCache,
+    mesh_render_resources: &'a MeshRenderResources,
+) {
+    for extracted in extracted_meshes {
+        if !mesh_render_resources.casts_shadow(extracted) {
+            continue;
+        }
+
+        let Some(mesh) = mesh_cache.get(extracted.mesh_id) else {
+            continue;
+        };
+
+        let key = (
+            mesh.vertex_layout.clone(),
+            mesh_render_resources.cull_mode(extracted),
+        );
+        let Some(pipeline) = render_resources.pipeline_cache.get(&key) else {
+            continue;
+        };
+
+        let Some(instance) = mesh_render_resources.instance_cache.get(&extracted.mesh_id) else {
+            continue;
+        };
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instance.buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+    }
+}
@@ -1,5 +1,8 @@
+use crate::render::shader_maker::ShaderMaker;
 use crate::render::vertex::{VertexBuffer, VertexSky};
-use crate::render::{create_render_pipeline, Mesh, RenderServer, Texture, TextureCache, TextureId};
+use crate::render::{
+    create_render_pipeline, DepthStencilConfig, Mesh, RenderServer, TextureCache, TextureId,
+};
 use wgpu::RenderPass;
 
 #[derive(Copy, Clone)]
@@ -64,6 +67,7 @@ pub(crate) fn prepare_sky(
     render_server: &RenderServer,
     texture_cache: &TextureCache,
     texture_id: &TextureId,
+    shader_maker: &mut ShaderMaker,
     camera_bind_group_layout: &wgpu::BindGroupLayout,
 ) {
     let device = &render_server.device;
@@ -104,21 +108,28 @@ pub(crate) fn prepare_sky(
                 push_constant_ranges: &[],
             });
 
+            let mut shader_defs = vec![];
+            if render_server.get_settings().reverse_z {
+                shader_defs.push("REVERSE_Z");
+            }
+
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("skybox shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/skybox.wgsl").into()),
+                source: shader_maker
+                    .make_shader(include_str!("../shaders/skybox.wgsl"), &shader_defs)
+                    .unwrap(),
             };
 
             create_render_pipeline(
-                device,
+                render_server,
                 &pipeline_layout,
-                render_server.surface_config.format,
-                Some(Texture::DEPTH_FORMAT),
+                render_server.scene_format(),
                 &[VertexSky::desc()],
                 shader,
                 pipeline_label,
                 false,
                 Some(wgpu::Face::Back),
+                DepthStencilConfig::opaque(),
             )
         };
 
@@ -2,9 +2,12 @@ use crate::math::alignup_u32;
 use crate::math::transform::Transform2d;
 use crate::render::bind_group::{BindGroupCache, BindGroupId};
 use crate::render::camera::CameraUniform;
+use crate::render::clip::clipped_stencil_state;
 use crate::render::vertex::{Vertex2d, VertexBuffer};
-use crate::render::{create_render_pipeline, Mesh, RenderServer, Texture, TextureCache, TextureId};
-use cgmath::{ElementWise, Vector2};
+use crate::render::{
+    create_render_pipeline, DepthStencilConfig, Mesh, RenderServer, TextureCache, TextureId,
+};
+use cgmath::{ElementWise, Vector2, Vector4};
 use naga::TypeInner::Vector;
 use std::collections::HashMap;
 use std::mem;
@@ -17,6 +20,8 @@ pub struct ExtractedSprite2d {
     pub(crate) transform: Transform2d,
     pub(crate) size: Option<(f32, f32)>,
     pub(crate) texture_id: TextureId,
+    /// Normalized region of the texture to draw (x, y, width, height).
+    pub(crate) region: Vector4<f32>,
     pub(crate) centered: bool,
     pub(crate) flip_x: bool,
     pub(crate) flip_y: bool,
@@ -34,6 +39,10 @@ pub struct SpriteRenderResources {
     pub(crate) texture_bind_group_layout: wgpu::BindGroupLayout,
     pub(crate) texture_bind_group_cache: HashMap<TextureId, wgpu::BindGroup>,
 
+    /// Replaces the samplers of the textures, for pixel art.
+    nearest_sampler: wgpu::Sampler,
+    nearest_filtering: bool,
+
     pub(crate) pipeline: Option<wgpu::RenderPipeline>,
 
     // A big buffer for all sprites. Use index range to use different parts of the data.
@@ -44,24 +53,11 @@ pub struct SpriteRenderResources {
 }
 
 impl SpriteRenderResources {
-    pub(crate) fn new(render_server: &RenderServer) -> Self {
-        let camera_bind_group_layout =
-            render_server
-                .device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: true,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                    label: Some("sprite2d camera bind group layout"),
-                });
-
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        light_map_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
         let texture_bind_group_layout =
             render_server
                 .device
@@ -99,8 +95,9 @@ impl SpriteRenderResources {
                     .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                         label: Some("sprite2d pipeline layout"),
                         bind_group_layouts: &[
-                            &camera_bind_group_layout,
+                            camera_bind_group_layout,
                             &texture_bind_group_layout,
+                            light_map_bind_group_layout,
                         ],
                         push_constant_ranges: &[],
                     });
@@ -112,21 +109,36 @@ impl SpriteRenderResources {
             };
 
             create_render_pipeline(
-                &render_server.device,
+                render_server,
                 &pipeline_layout,
-                render_server.surface_config.format,
-                Some(Texture::DEPTH_FORMAT),
+                render_server.scene_format(),
                 &[Vertex2d::desc()],
                 shader,
                 pipeline_label,
                 true,
                 Some(wgpu::Face::Back),
+                DepthStencilConfig::transparent().with_stencil(clipped_stencil_state()),
             )
         };
 
+        let nearest_sampler = render_server
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("sprite2d nearest sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
         Self {
             texture_bind_group_layout,
             texture_bind_group_cache: HashMap::new(),
+            nearest_sampler,
+            nearest_filtering: false,
             pipeline: Some(pipeline),
             vertex_buffer: None,
             vertex_buffer_capacity: 0,
@@ -148,6 +160,12 @@ impl SpriteRenderResources {
 
         let texture = texture_cache.get(texture_id).unwrap();
 
+        let sampler = if self.nearest_filtering {
+            &self.nearest_sampler
+        } else {
+            &texture.sampler
+        };
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &self.texture_bind_group_layout,
             entries: &[
@@ -157,7 +175,7 @@ impl SpriteRenderResources {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
             label: None,
@@ -173,6 +191,14 @@ impl SpriteRenderResources {
     pub fn remove_texture_bind_group(&mut self, texture_id: TextureId) {
         self.texture_bind_group_cache.remove(&texture_id);
     }
+
+    /// Sample all 2D textures with nearest filtering, rather than with their own samplers.
+    pub(crate) fn set_nearest_filtering(&mut self, nearest: bool) {
+        if self.nearest_filtering != nearest {
+            self.nearest_filtering = nearest;
+            self.texture_bind_group_cache.clear();
+        }
+    }
 }
 
 pub trait DrawSprite2d<'a> {
@@ -275,6 +301,7 @@ const QUAD_UVS: [Vector2<f32>; 4] = [
 
 pub(crate) fn prepare_sprite(
     sprites: &Vec<ExtractedSprite2d>,
+    pixel_snap: bool,
     render_resources: &mut SpriteRenderResources,
     texture_cache: &TextureCache,
     render_server: &RenderServer,
@@ -324,8 +351,13 @@ pub(crate) fn prepare_sprite(
 
         // Calculate vertex data for this item.
 
-        // Default UVs.
-        let mut uvs = QUAD_UVS;
+        // Default UVs, mapped into the region.
+        let mut uvs = QUAD_UVS.map(|uv| {
+            Vector2::new(
+                e.region.x + uv.x * e.region.z,
+                e.region.y + uv.y * e.region.w,
+            )
+        });
 
         // Consider flip.
         if (e.flip_x) {
@@ -339,7 +371,10 @@ pub(crate) fn prepare_sprite(
             size.unwrap()
         } else {
             let texture = texture_cache.get(e.texture_id).unwrap();
-            (texture.size.0 as f32, texture.size.1 as f32)
+            (
+                texture.size.0 as f32 * e.region.z,
+                texture.size.1 as f32 * e.region.w,
+            )
         };
 
         // By default, the size of the quad is the size of the texture.
@@ -354,7 +389,14 @@ pub(crate) fn prepare_sprite(
             if !e.centered {
                 quad_pos += Vector2::new(0.5, 0.5);
             }
-            let new_pos = transform.transform_point(&quad_pos.mul_element_wise(quad_size));
+            let mut new_pos =
+                Vector2::from(transform.transform_point(quad_pos.mul_element_wise(quad_size)));
+
+            // Snap corners rather than the position, which is half a pixel off for
+            // centered sprites of odd sizes.
+            if pixel_snap && transform.rotation == 0.0 {
+                new_pos = new_pos.map(f32::round);
+            }
 
             vertices.push(Vertex2d {
                 position: new_pos.into(),
@@ -429,20 +471,32 @@ pub(crate) fn prepare_sprite(
     batches
 }
 
+/// Draw the sprites in `range`, splitting batches that cross its ends.
 pub(crate) fn render_sprite<'a, 'b: 'a>(
     batches: &'b Vec<SpriteBatch>,
+    range: Range<usize>,
     render_resources: &'b SpriteRenderResources,
     render_pass: &mut wgpu::RenderPass<'a>,
     camera_bind_group: &'b wgpu::BindGroup,
+    light_map_bind_group: &'b wgpu::BindGroup,
 ) {
-    if batches.is_empty() {
+    if batches.is_empty() || range.is_empty() {
         return;
     }
 
     let offset_unit = CameraUniform::get_uniform_offset_unit();
 
+    let index_range = range.start as u32 * QUAD_INDICES.len() as u32
+        ..range.end as u32 * QUAD_INDICES.len() as u32;
+
     // Draw sprites batch by batch.
     for b in batches {
+        let start = b.index_range.start.max(index_range.start);
+        let end = b.index_range.end.min(index_range.end);
+        if start >= end {
+            continue;
+        }
+
         let uniform_offset = offset_unit * b.camera_index;
 
         let texture_bind_group = render_resources.get_texture_bind_group(b.texture_id.unwrap());
@@ -468,6 +522,9 @@ pub(crate) fn render_sprite<'a, 'b: 'a>(
         // Set texture group.
         render_pass.set_bind_group(1, texture_bind_group, &[]);
 
-        render_pass.draw_indexed(b.index_range.clone(), 0, 0..1);
+        // Set 2D lighting group.
+        render_pass.set_bind_group(2, light_map_bind_group, &[]);
+
+        render_pass.draw_indexed(start..end, 0, 0..1);
     }
 }
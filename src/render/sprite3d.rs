@@ -0,0 +1,417 @@
+use crate::math::transform::Transform3d;
+use crate::render::bind_group::{DynamicUniformBuffer, TextureBindGroupCache};
+use crate::render::shader_maker::ShaderMaker;
+use crate::render::{RenderServer, TextureCache, TextureId};
+use cgmath::{InnerSpace, Matrix4, Vector3};
+use std::mem;
+
+/// How a 3D sprite is oriented relative to the camera.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum BillboardMode {
+    /// Use the node's rotation.
+    #[default]
+    Disabled = 0,
+    /// Always face the camera.
+    Enabled = 1,
+    /// Face the camera while staying upright (rotate around the Y axis only).
+    FixedY = 2,
+}
+
+/// Minimal data for rendering a 3D sprite.
+#[derive(Debug, Copy, Clone)]
+pub struct ExtractedSprite3d {
+    pub(crate) transform: Transform3d,
+    pub(crate) texture_id: TextureId,
+    /// Size of a texture pixel in world units.
+    pub(crate) pixel_size: f32,
+    pub(crate) billboard_mode: BillboardMode,
+    pub(crate) alpha_cut: Option<f32>,
+    /// Fade distance near geometry, see `Sprite3d::depth_fade`.
+    pub(crate) depth_fade: Option<f32>,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct Sprite3dParamsUniform {
+    model_matrix: [[f32; 4]; 4],
+    size: [f32; 2],
+    billboard_mode: u32,
+    alpha_cut: f32,
+    depth_fade: f32,
+    _pad: [f32; 3],
+}
+
+pub(crate) struct Sprite3dRenderResources {
+    params_buffer: DynamicUniformBuffer<Sprite3dParamsUniform>,
+    texture_bind_groups: TextureBindGroupCache,
+
+    pipeline: Option<wgpu::RenderPipeline>,
+    /// Params are pushed per draw instead of using the params buffer.
+    push_constants: bool,
+
+    /// For depth-faded sprites, which read the scene depth instead of writing to it.
+    soft_pipeline: Option<wgpu::RenderPipeline>,
+    scene_depth_bind_group_layout: wgpu::BindGroupLayout,
+    /// Recreated along with the depth texture.
+    scene_depth_bind_group: Option<(TextureId, wgpu::BindGroup)>,
+
+    /// Sprites to draw this frame, sorted back to front.
+    sorted_sprites: Vec<ExtractedSprite3d>,
+    params: Vec<Sprite3dParamsUniform>,
+}
+
+impl Sprite3dRenderResources {
+    pub(crate) fn new(render_server: &RenderServer) -> Self {
+        let device = &render_server.device;
+
+        let scene_depth_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        // Read as floats, since GL can't load from depth textures.
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                }],
+                label: Some("sprite3d scene depth bind group layout"),
+            });
+
+        Self {
+            params_buffer: DynamicUniformBuffer::new(
+                device,
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                "sprite3d params",
+            ),
+            texture_bind_groups: TextureBindGroupCache::new(device, "sprite3d texture"),
+            pipeline: None,
+            push_constants: false,
+            soft_pipeline: None,
+            scene_depth_bind_group_layout,
+            scene_depth_bind_group: None,
+            sorted_sprites: vec![],
+            params: vec![],
+        }
+    }
+
+    fn create_pipeline(
+        &mut self,
+        render_server: &RenderServer,
+        shader_maker: &mut ShaderMaker,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        depth_fade: bool,
+    ) {
+        let existing = if depth_fade {
+            &self.soft_pipeline
+        } else {
+            &self.pipeline
+        };
+        if existing.is_some() {
+            return;
+        }
+
+        let device = &render_server.device;
+
+        let push_constant_ranges = render_server.push_constant_ranges(
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            mem::size_of::<Sprite3dParamsUniform>() as u32,
+        );
+        self.push_constants = !push_constant_ranges.is_empty();
+
+        let mut bind_group_layouts =
+            vec![camera_bind_group_layout, self.texture_bind_groups.layout()];
+        if !self.push_constants {
+            bind_group_layouts.push(self.params_buffer.layout());
+        }
+        if depth_fade {
+            bind_group_layouts.push(&self.scene_depth_bind_group_layout);
+        }
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sprite3d pipeline layout"),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &push_constant_ranges,
+        });
+
+        let mut shader_defs = vec![];
+        if self.push_constants {
+            shader_defs.push("PUSH_CONSTANTS");
+        }
+        if depth_fade {
+            shader_defs.push("DEPTH_FADE");
+        }
+
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("sprite3d shader"),
+            source: shader_maker
+                .make_shader(include_str!("../shaders/sprite3d.wgsl"), &shader_defs)
+                .unwrap(),
+        };
+        let shader_module = device.create_shader_module(shader);
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(if depth_fade {
+                "sprite3d soft pipeline"
+            } else {
+                "sprite3d pipeline"
+            }),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip, // Has to be triangle strip.
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Faded sprites sample the depth buffer, so it can't be attached.
+            // They test against it in the shader instead.
+            depth_stencil: (!depth_fade).then(|| wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                // Sprites are sorted back to front, so writing depth is fine
+                // and keeps alpha-cut sprites correct against each other.
+                depth_write_enabled: true,
+                depth_compare: render_server.depth_compare(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        if depth_fade {
+            self.soft_pipeline = Some(pipeline);
+        } else {
+            self.pipeline = Some(pipeline);
+        }
+    }
+
+    /// Whether a separate pass is needed for depth-faded sprites, see `render_soft_sprite3d`.
+    pub(crate) fn has_soft_sprites(&self) -> bool {
+        self.sorted_sprites.iter().any(|s| s.depth_fade.is_some())
+    }
+
+    /// One draw per sprite.
+    pub(crate) fn draw_count(&self) -> usize {
+        self.sorted_sprites.len()
+    }
+
+    fn prepare_scene_depth_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        texture_cache: &TextureCache,
+        depth_texture: TextureId,
+    ) {
+        if self
+            .scene_depth_bind_group
+            .as_ref()
+            .is_some_and(|(id, _)| *id == depth_texture)
+        {
+            return;
+        }
+
+        // The stencil aspect can't be sampled along with depth.
+        let view = texture_cache
+            .get(depth_texture)
+            .unwrap()
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                aspect: wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.scene_depth_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+            label: Some("sprite3d scene depth bind group"),
+        });
+
+        self.scene_depth_bind_group = Some((depth_texture, bind_group));
+    }
+
+    /// Drop the bind group of a texture that was removed or replaced.
+    pub(crate) fn evict_texture(&mut self, texture_id: TextureId) {
+        self.texture_bind_groups.evict(texture_id);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prepare_sprite3d(
+    sprites: &[ExtractedSprite3d],
+    camera_position: Vector3<f32>,
+    render_resources: &mut Sprite3dRenderResources,
+    texture_cache: &TextureCache,
+    depth_texture: TextureId,
+    render_server: &RenderServer,
+    shader_maker: &mut ShaderMaker,
+    camera_bind_group_layout: &wgpu::BindGroupLayout,
+) {
+    render_resources.sorted_sprites.clear();
+    render_resources.params.clear();
+
+    if sprites.is_empty() {
+        return;
+    }
+
+    render_resources.create_pipeline(render_server, shader_maker, camera_bind_group_layout, false);
+
+    if sprites.iter().any(|s| s.depth_fade.is_some()) {
+        render_resources.create_pipeline(
+            render_server,
+            shader_maker,
+            camera_bind_group_layout,
+            true,
+        );
+        render_resources.prepare_scene_depth_bind_group(
+            &render_server.device,
+            texture_cache,
+            depth_texture,
+        );
+    }
+
+    // Sort back to front for correct blending.
+    let mut sorted = sprites.to_vec();
+    sorted.sort_by(|a, b| {
+        let da = (a.transform.position - camera_position).magnitude2();
+        let db = (b.transform.position - camera_position).magnitude2();
+        db.partial_cmp(&da).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for s in &sorted {
+        render_resources.texture_bind_groups.add(
+            &render_server.device,
+            texture_cache,
+            s.texture_id,
+        );
+
+        let texture = texture_cache.get(s.texture_id).unwrap();
+
+        let transform = &s.transform;
+        let model = Matrix4::from_translation(transform.position)
+            * Matrix4::from(transform.rotation)
+            * Matrix4::from_nonuniform_scale(
+                transform.scale.x,
+                transform.scale.y,
+                transform.scale.z,
+            );
+
+        let uniform = Sprite3dParamsUniform {
+            model_matrix: model.into(),
+            size: [
+                texture.size.0 as f32 * s.pixel_size,
+                texture.size.1 as f32 * s.pixel_size,
+            ],
+            billboard_mode: s.billboard_mode as u32,
+            alpha_cut: s.alpha_cut.unwrap_or(0.0),
+            depth_fade: s.depth_fade.unwrap_or(0.0),
+            _pad: [0.0; 3],
+        };
+
+        render_resources.params.push(uniform);
+    }
+
+    if !render_resources.push_constants {
+        render_resources
+            .params_buffer
+            .write(render_server, &render_resources.params);
+    }
+
+    render_resources.sorted_sprites = sorted;
+}
+
+pub(crate) fn render_sprite3d<'a, 'b: 'a>(
+    render_resources: &'b Sprite3dRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+) {
+    draw_sprites(render_resources, render_pass, camera_bind_group, false);
+}
+
+/// Draw the depth-faded sprites, in a pass without a depth attachment,
+/// so that they can sample the depth buffer.
+pub(crate) fn render_soft_sprite3d<'a, 'b: 'a>(
+    render_resources: &'b Sprite3dRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+) {
+    if !render_resources.has_soft_sprites() {
+        return;
+    }
+
+    // After the params group, if there is one.
+    let depth_group = if render_resources.push_constants {
+        2
+    } else {
+        3
+    };
+    let (_, depth_bind_group) = render_resources.scene_depth_bind_group.as_ref().unwrap();
+
+    render_pass.set_pipeline(render_resources.soft_pipeline.as_ref().unwrap());
+    render_pass.set_bind_group(depth_group, depth_bind_group, &[]);
+
+    draw_sprites(render_resources, render_pass, camera_bind_group, true);
+}
+
+fn draw_sprites<'a, 'b: 'a>(
+    render_resources: &'b Sprite3dRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+    soft: bool,
+) {
+    if render_resources.sorted_sprites.is_empty() {
+        return;
+    }
+
+    if !soft {
+        render_pass.set_pipeline(render_resources.pipeline.as_ref().unwrap());
+    }
+
+    // Set camera group. Offset 0 is the first camera, which all 3D passes draw with for now.
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+
+    for (i, s) in render_resources.sorted_sprites.iter().enumerate() {
+        if s.depth_fade.is_some() != soft {
+            continue;
+        }
+
+        if render_resources.push_constants {
+            render_pass.set_push_constants(
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&render_resources.params[i]),
+            );
+        } else {
+            render_pass.set_bind_group(
+                2,
+                render_resources.params_buffer.bind_group(),
+                &[DynamicUniformBuffer::<Sprite3dParamsUniform>::offset(i)],
+            );
+        }
+
+        render_pass.set_bind_group(
+            1,
+            render_resources.texture_bind_groups.get(s.texture_id),
+            &[],
+        );
+
+        render_pass.draw(0..4, 0..1);
+    }
+}
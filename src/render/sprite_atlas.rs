@@ -0,0 +1,246 @@
+use crate::render::{
+    AnimatedFrame, AnimatedTexture, AnimationTag, Texture, TextureCache, TextureId,
+};
+use anyhow::{bail, Context, Result};
+use cgmath::Vector4;
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Shown for frames without a duration, like the animated image loader.
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+/// A named region of a sprite atlas.
+#[derive(Debug, Clone)]
+pub struct SpriteAtlasRegion {
+    pub name: String,
+    /// Normalized region of the texture (x, y, width, height).
+    pub region: Vector4<f32>,
+    /// In pixels.
+    pub size: (u32, u32),
+    /// How long the region is shown when played as an animation, in seconds.
+    pub duration: f32,
+}
+
+/// A texture packed by TexturePacker or exported by Aseprite, with the JSON data file
+/// that names its regions.
+///
+/// Both the hash and the array layouts of the data file are read. Animations come
+/// from Aseprite's frame tags, or TexturePacker's `animations` table.
+/// Trimmed regions are drawn at their trimmed size. Rotated ones aren't supported.
+#[derive(Debug, Clone)]
+pub struct SpriteAtlas {
+    pub texture: TextureId,
+    pub regions: Vec<SpriteAtlasRegion>,
+    pub tags: Vec<AnimationTag>,
+}
+
+impl SpriteAtlas {
+    /// Load a JSON data file, and the image it refers to, relative to it.
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        path: P,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Invalid sprite atlas path {:?}", path))?;
+
+        let data: SheetJson = serde_json::from_str(&json)
+            .with_context(|| format!("Invalid sprite atlas data {:?}", path))?;
+
+        let image_path = path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(&data.meta.image);
+        let texture = Texture::load(device, queue, cache, &image_path)?;
+        let texture_size = cache.get(texture).unwrap().size;
+
+        Self::from_data(data, texture, texture_size)
+            .with_context(|| format!("Invalid sprite atlas data {:?}", path))
+    }
+
+    fn from_data(data: SheetJson, texture: TextureId, texture_size: (u32, u32)) -> Result<Self> {
+        let (width, height) = (texture_size.0 as f32, texture_size.1 as f32);
+
+        let mut regions = Vec::with_capacity(data.frames.0.len());
+
+        for (name, frame) in data.frames.0 {
+            if frame.rotated {
+                bail!("Rotated region {} isn't supported", name);
+            }
+
+            let rect = frame.frame;
+
+            regions.push(SpriteAtlasRegion {
+                name,
+                region: Vector4::new(
+                    rect.x as f32 / width,
+                    rect.y as f32 / height,
+                    rect.w as f32 / width,
+                    rect.h as f32 / height,
+                ),
+                size: (rect.w, rect.h),
+                // In milliseconds.
+                duration: frame
+                    .duration
+                    .map_or(DEFAULT_FRAME_DURATION, |d| d / 1000.0),
+            });
+        }
+
+        let mut tags = vec![];
+
+        for tag in data.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= regions.len() {
+                bail!("Tag {} is out of range", tag.name);
+            }
+
+            let forward = tag.from..=tag.to;
+            // The ends aren't repeated when turning around.
+            let back = (tag.from + 1..tag.to).rev();
+
+            let frames = match tag.direction.as_str() {
+                "reverse" => forward.rev().collect(),
+                "pingpong" => forward.chain(back).collect(),
+                "pingpong_reverse" => forward.rev().chain(back.rev()).collect(),
+                _ => forward.collect(),
+            };
+
+            tags.push(AnimationTag {
+                name: tag.name,
+                frames,
+            });
+        }
+
+        for (name, frame_names) in data.animations {
+            let frames = frame_names
+                .iter()
+                .map(|frame| {
+                    regions
+                        .iter()
+                        .position(|r| r.name == *frame)
+                        .with_context(|| format!("Animation {} has no region {}", name, frame))
+                })
+                .collect::<Result<_>>()?;
+
+            tags.push(AnimationTag { name, frames });
+        }
+
+        Ok(Self {
+            texture,
+            regions,
+            tags,
+        })
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<&SpriteAtlasRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+
+    /// All regions as frames of one animation, with the tags to play parts of it.
+    pub fn to_animation(&self) -> AnimatedTexture {
+        AnimatedTexture {
+            texture: self.texture,
+            frame_size: self.regions.first().map_or((0, 0), |r| r.size),
+            frames: self
+                .regions
+                .iter()
+                .map(|r| AnimatedFrame {
+                    region: r.region,
+                    delay: r.duration,
+                })
+                .collect(),
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SheetJson {
+    frames: SheetFrames,
+    meta: SheetMeta,
+    /// TexturePacker only, names of the frames of each animation.
+    #[serde(default)]
+    animations: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct SheetMeta {
+    image: String,
+    /// Aseprite only.
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<FrameTagJson>,
+}
+
+#[derive(Deserialize)]
+struct FrameTagJson {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Deserialize)]
+struct FrameJson {
+    frame: RectJson,
+    #[serde(default)]
+    rotated: bool,
+    /// Aseprite only, in milliseconds.
+    duration: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct RectJson {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct NamedFrameJson {
+    filename: String,
+    #[serde(flatten)]
+    frame: FrameJson,
+}
+
+/// Frames in file order, which frame tags count in. Read from either an array of
+/// frames with a `filename` each, or an object keyed by name.
+struct SheetFrames(Vec<(String, FrameJson)>);
+
+impl<'de> Deserialize<'de> for SheetFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = SheetFrames;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an array or a map of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = vec![];
+                while let Some(frame) = seq.next_element::<NamedFrameJson>()? {
+                    frames.push((frame.filename, frame.frame));
+                }
+                Ok(SheetFrames(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = vec![];
+                while let Some(entry) = map.next_entry::<String, FrameJson>()? {
+                    frames.push(entry);
+                }
+                Ok(SheetFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
@@ -0,0 +1,18 @@
+use serde::Serialize;
+
+/// What the last prepared frame draws, to see how well batching and culling work.
+///
+/// Draw calls are counted for the scene and UI, not for shadow maps, post processing
+/// and the other fullscreen passes. UI clips split draws further, which isn't counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    /// 2D sprites, and the batches they are drawn in.
+    pub sprites: u32,
+    pub sprite_batches: u32,
+    /// Labels and other atlases, one draw each.
+    pub atlases: u32,
+    /// Meshes in the scene, and those left after culling.
+    pub meshes: u32,
+    pub drawn_meshes: u32,
+}
@@ -1,7 +1,12 @@
+use crate::asset::{TextureFilter, TextureImportSettings, TextureWrap, Vfs};
+use crate::math::color::{linear_to_srgb, srgb_to_linear};
 use crate::render::render_server::RenderServer;
 use anyhow::*;
+use half::f16;
 use image::{DynamicImage, GenericImageView, ImageBuffer, Rgb};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
 use std::time::Instant;
 use uuid;
@@ -21,33 +26,78 @@ pub struct Texture {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TextureId(uuid::Uuid);
 
+impl TextureId {
+    pub(crate) fn new() -> Self {
+        Self(uuid::Uuid::new_v4())
+    }
+}
+
 pub struct TextureCache {
     pub(crate) storage: HashMap<TextureId, Texture>,
+    /// IDs that resolve to another texture, see `TransientTargets`.
+    aliases: HashMap<TextureId, TextureId>,
+    /// IDs removed or pointed at another texture since the last `take_evicted`.
+    evicted: Vec<TextureId>,
 }
 
 impl TextureCache {
     pub(crate) fn new() -> Self {
         Self {
             storage: HashMap::new(),
+            aliases: HashMap::new(),
+            evicted: vec![],
         }
     }
 
     pub(crate) fn add(&mut self, texture: Texture) -> TextureId {
-        let id = TextureId(uuid::Uuid::new_v4());
+        let id = TextureId::new();
         self.storage.insert(id, texture);
         id
     }
 
+    /// Add a texture under an id handed out before it was ready, see `RenderContext`.
+    pub(crate) fn insert(&mut self, texture_id: TextureId, texture: Texture) {
+        self.storage.insert(texture_id, texture);
+    }
+
     pub(crate) fn get(&self, texture_id: TextureId) -> Option<&Texture> {
-        self.storage.get(&texture_id)
+        self.storage.get(&self.resolve(texture_id))
     }
 
     pub(crate) fn get_mut(&mut self, texture_id: TextureId) -> Option<&mut Texture> {
-        self.storage.get_mut(&texture_id)
+        self.storage.get_mut(&self.resolve(texture_id))
     }
 
     pub(crate) fn remove(&mut self, texture_id: TextureId) {
-        self.storage.remove(&texture_id);
+        let removed = self.storage.remove(&texture_id).is_some();
+        if self.aliases.remove(&texture_id).is_some() || removed {
+            self.evicted.push(texture_id);
+        }
+    }
+
+    /// Move the texture of `source` to `texture_id`, dropping the one there.
+    /// Handles to `texture_id` stay valid, only bind groups of it are stale.
+    pub(crate) fn replace(&mut self, texture_id: TextureId, source: TextureId) {
+        if let Some(texture) = self.storage.remove(&source) {
+            self.storage.insert(texture_id, texture);
+            self.evicted.push(texture_id);
+        }
+    }
+
+    /// Make `texture_id` refer to the texture of `target` from now on.
+    pub(crate) fn alias(&mut self, texture_id: TextureId, target: TextureId) {
+        if self.aliases.insert(texture_id, target) != Some(target) {
+            self.evicted.push(texture_id);
+        }
+    }
+
+    /// Textures whose bind groups are stale, because the ID is gone or means another texture now.
+    pub(crate) fn take_evicted(&mut self) -> Vec<TextureId> {
+        std::mem::take(&mut self.evicted)
+    }
+
+    fn resolve(&self, texture_id: TextureId) -> TextureId {
+        self.aliases.get(&texture_id).copied().unwrap_or(texture_id)
     }
 }
 
@@ -63,8 +113,30 @@ impl Texture {
         let label = path_copy.to_str();
 
         let img = image::open(path).context("Invalid image path")?;
+        let settings = TextureImportSettings::load_for(&path_copy);
+
+        Self::from_image_with_settings(device, queue, cache, &img, label, &settings)
+    }
+
+    /// Load a texture through the VFS, e.g. "asset://images/happy-tree.png".
+    pub fn load_from_vfs(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        vfs: &Vfs,
+        uri: &str,
+    ) -> Result<TextureId> {
+        let bytes = vfs.read(uri)?;
+        let img = image::load_from_memory(&bytes)?;
+
+        let meta_uri = format!("{}.{}", uri, TextureImportSettings::META_EXTENSION);
+        let settings = if vfs.exists(&meta_uri) {
+            TextureImportSettings::from_ron_or_default(&vfs.read_to_string(&meta_uri)?, &meta_uri)
+        } else {
+            TextureImportSettings::default()
+        };
 
-        Self::from_image(device, queue, cache, &img, label)
+        Self::from_image_with_settings(device, queue, cache, &img, Some(uri), &settings)
     }
 
     pub fn empty(
@@ -111,35 +183,103 @@ impl Texture {
         img: &DynamicImage,
         label: Option<&str>,
     ) -> Result<TextureId> {
+        Self::from_image_with_settings(
+            device,
+            queue,
+            cache,
+            img,
+            label,
+            &TextureImportSettings::default(),
+        )
+    }
+
+    /// Create texture from image, using the given import settings.
+    pub fn from_image_with_settings(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        img: &DynamicImage,
+        label: Option<&str>,
+        settings: &TextureImportSettings,
+    ) -> Result<TextureId> {
+        let texture = Self::create_from_image(device, img, label, settings, |texture, write| {
+            write.submit(queue, texture)
+        })?;
+
+        Ok(cache.add(texture))
+    }
+
+    /// Create the texture for an image, passing each mip level's data to `write`
+    /// instead of uploading it.
+    pub(crate) fn create_from_image(
+        device: &wgpu::Device,
+        img: &DynamicImage,
+        label: Option<&str>,
+        settings: &TextureImportSettings,
+        mut write: impl FnMut(&wgpu::Texture, TextureWrite<'_>),
+    ) -> Result<Texture> {
         // Image size.
         let size = img.dimensions();
 
-        let data: &[u8];
-        let bytes_per_row;
-        let format;
-        let rgba_converted_from_rgb;
-
-        match img {
-            DynamicImage::ImageLuma8(gray) => {
-                data = &gray;
-                bytes_per_row = 1 * size.0;
-                format = wgpu::TextureFormat::R8Unorm;
-            }
-            DynamicImage::ImageRgb8(_) => {
-                rgba_converted_from_rgb = img.to_rgba8();
-                data = &rgba_converted_from_rgb;
-                bytes_per_row = 4 * size.0;
-                format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        // Reduce the source to a few layouts we know how to upload.
+        // 16-bit color sources are widened to float.
+        let mut img = match img {
+            DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageRgba8(_)
+            | DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageRgba32F(_) => Cow::Borrowed(img),
+            DynamicImage::ImageRgb8(_) | DynamicImage::ImageLumaA8(_) => {
+                Cow::Owned(DynamicImage::ImageRgba8(img.to_rgba8()))
             }
-            DynamicImage::ImageRgba8(rgba) => {
-                data = rgba;
-                bytes_per_row = 4 * size.0;
-                format = wgpu::TextureFormat::Rgba8UnormSrgb;
+            DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb32F(_) => {
+                Cow::Owned(DynamicImage::ImageRgba32F(img.to_rgba32f()))
             }
             _ => {
-                panic!("Unsupported image format!");
+                bail!("Unsupported image format: {:?}", img.color());
+            }
+        };
+
+        if settings.premultiply_alpha {
+            match img.to_mut() {
+                DynamicImage::ImageRgba8(rgba) => premultiply_alpha(rgba, settings.srgb),
+                DynamicImage::ImageRgba32F(rgba) => {
+                    for pixel in rgba.pixels_mut() {
+                        let alpha = pixel[3];
+                        for channel in &mut pixel.0[..3] {
+                            *channel *= alpha;
+                        }
+                    }
+                }
+                _ => {}
             }
         }
+        let img = img.as_ref();
+
+        // High precision data is always linear. 32-bit float is only used if it can be filtered.
+        let format = match img {
+            DynamicImage::ImageLuma8(_) => wgpu::TextureFormat::R8Unorm,
+            DynamicImage::ImageLuma16(_) => wgpu::TextureFormat::R16Float,
+            DynamicImage::ImageRgba32F(_)
+                if device
+                    .features()
+                    .contains(wgpu::Features::FLOAT32_FILTERABLE) =>
+            {
+                wgpu::TextureFormat::Rgba32Float
+            }
+            DynamicImage::ImageRgba32F(_) => wgpu::TextureFormat::Rgba16Float,
+            _ if settings.srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            _ => wgpu::TextureFormat::Rgba8Unorm,
+        };
+        let bytes_per_pixel = format.block_copy_size(None).unwrap();
+
+        let mip_level_count = if settings.mipmaps {
+            32 - size.0.max(size.1).max(1).leading_zeros()
+        } else {
+            1
+        };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
@@ -148,7 +288,7 @@ impl Texture {
                 height: size.1,
                 depth_or_array_layers: 1,
             },
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
@@ -158,58 +298,83 @@ impl Texture {
             view_formats: &[],
         });
 
-        let img_copy_texture = wgpu::ImageCopyTexture {
-            aspect: wgpu::TextureAspect::All,
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-        };
-
-        // Write image data to texture.
-        queue.write_texture(
-            img_copy_texture,
-            data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(bytes_per_row),
-                rows_per_image: Some(size.1),
-            },
-            Extent3d {
-                width: size.0,
-                height: size.1,
-                depth_or_array_layers: 1,
-            },
-        );
+        // Write image data to texture, generating smaller levels on the CPU.
+        for mip_level in 0..mip_level_count {
+            let width = (size.0 >> mip_level).max(1);
+            let height = (size.1 >> mip_level).max(1);
+
+            let level = if mip_level == 0 {
+                None
+            } else {
+                Some(img.resize_exact(width, height, image::imageops::FilterType::Triangle))
+            };
+            let data = encode_pixels(level.as_ref().unwrap_or(img), format);
+
+            write(
+                &texture,
+                TextureWrite {
+                    mip_level,
+                    data,
+                    bytes_per_row: bytes_per_pixel * width,
+                    size: (width, height),
+                },
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let address_mode = match settings.wrap {
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
+            TextureWrap::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            TextureWrap::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        };
+
+        let filter = match settings.filter {
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+        };
+
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
             ..Default::default()
         });
 
-        let texture = Texture {
+        Ok(Texture {
             size,
             texture,
             view,
             sampler,
             format,
-        };
+        })
+    }
 
-        Ok(cache.add(texture))
+    /// Approximate GPU memory used by the texture, including all mip levels.
+    pub fn get_memory_size(&self) -> u64 {
+        let block_size = self.format.block_copy_size(None).unwrap_or(4) as u64;
+        let layers = self.texture.depth_or_array_layers() as u64;
+
+        (0..self.texture.mip_level_count())
+            .map(|level| {
+                let width = (self.size.0 >> level).max(1) as u64;
+                let height = (self.size.1 >> level).max(1) as u64;
+                width * height * block_size * layers
+            })
+            .sum()
     }
 
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// Standard depth format. Has a stencil aspect for UI clipping.
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         cache: &mut TextureCache,
         config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
         label: Option<&str>,
     ) -> TextureId {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -222,7 +387,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
+            format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
@@ -247,18 +412,64 @@ impl Texture {
             texture,
             view,
             sampler,
-            format: Self::DEPTH_FORMAT,
+            format,
         };
 
         cache.add(texture)
     }
 
-    /// Set a new sampler for this texture.
-    pub fn set_sampler(&mut self, new_sampler: wgpu::Sampler) {
-        self.sampler = new_sampler;
+    /// Create a color texture with the same size and format as the surface,
+    /// which can be used as an offscreen render target.
+    pub fn create_render_texture(
+        device: &wgpu::Device,
+        cache: &mut TextureCache,
+        config: &wgpu::SurfaceConfiguration,
+        label: Option<&str>,
+    ) -> TextureId {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            // Copyable so that passes can take a snapshot of what has been drawn so far.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture = Texture {
+            size: (config.width, config.height),
+            texture,
+            view,
+            sampler,
+            format: config.format,
+        };
+
+        cache.add(texture)
     }
 
-    pub fn load_cube<P: AsRef<Path>>(
+    /// Load a color grading LUT from a horizontal PNG strip (e.g. 1024x32 for a 32^3 LUT).
+    pub fn load_lut<P: AsRef<Path>>(
         render_server: &RenderServer,
         cache: &mut TextureCache,
         path: P,
@@ -269,7 +480,7 @@ impl Texture {
 
         let img = image::open(path).context("Invalid image path")?;
 
-        Self::from_cube_image(
+        Self::from_lut_image(
             &render_server.device,
             &render_server.queue,
             cache,
@@ -278,28 +489,96 @@ impl Texture {
         )
     }
 
-    /// Create texture from image.
-    pub fn from_cube_image(
+    /// Create a 3D LUT texture from a strip image.
+    ///
+    /// The strip contains N slices of NxN pixels placed side by side,
+    /// where red increases along X, green along Y, and blue across slices.
+    pub fn from_lut_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         cache: &mut TextureCache,
-        img: &image::DynamicImage,
+        img: &DynamicImage,
         label: Option<&str>,
     ) -> Result<TextureId> {
-        // Make a rgba8 copy.
-        let rgba = img.to_rgba8();
+        let (width, height) = img.dimensions();
 
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        if height == 0 || width != height * height {
+            bail!("LUT strip must be N*N pixels wide and N pixels high, got {width}x{height}");
+        }
 
-        // Image size.
-        let dimensions = img.dimensions();
+        // Keep the precision of 16-bit and float LUTs.
+        let (format, pixels) = if is_high_precision(img) {
+            let rgba = DynamicImage::ImageRgba32F(img.to_rgba32f());
+            let format = wgpu::TextureFormat::Rgba16Float;
+            (format, encode_pixels(&rgba, format).into_owned())
+        } else {
+            (wgpu::TextureFormat::Rgba8Unorm, img.to_rgba8().into_raw())
+        };
+        let bytes_per_pixel = format.block_copy_size(None).unwrap();
+
+        let lut_size = height;
+
+        // Rearrange the strip into consecutive slices.
+        let mut data =
+            Vec::with_capacity((lut_size * lut_size * lut_size * bytes_per_pixel) as usize);
+        for slice in 0..lut_size {
+            for y in 0..lut_size {
+                let row_start = ((y * width + slice * lut_size) * bytes_per_pixel) as usize;
+                let row_end = row_start + (lut_size * bytes_per_pixel) as usize;
+                data.extend_from_slice(&pixels[row_start..row_end]);
+            }
+        }
+
+        Ok(Self::create_lut(
+            device, queue, cache, &data, format, lut_size, label,
+        ))
+    }
+
+    /// Create a LUT which leaves colors untouched.
+    pub fn identity_lut(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+    ) -> TextureId {
+        // With linear filtering, the corners of the color cube are enough.
+        let lut_size = 2;
+
+        let mut data = vec![];
+        for b in 0..lut_size {
+            for g in 0..lut_size {
+                for r in 0..lut_size {
+                    data.extend([r as u8 * 255, g as u8 * 255, b as u8 * 255, 255]);
+                }
+            }
+        }
 
-        assert_eq!(dimensions.1 % 6, 0, "Skybox texture has invalid size!");
+        Self::create_lut(
+            device,
+            queue,
+            cache,
+            &data,
+            wgpu::TextureFormat::Rgba8Unorm,
+            lut_size,
+            Some("identity lut"),
+        )
+    }
+
+    fn create_lut(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cache: &mut TextureCache,
+        data: &[u8],
+        format: wgpu::TextureFormat,
+        lut_size: u32,
+        label: Option<&str>,
+    ) -> TextureId {
+        // LUT values are stored as they are (never sRGB), gamma conversion is done in the shader.
+        let bytes_per_pixel = format.block_copy_size(None).unwrap();
 
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1 / 6,
-            depth_or_array_layers: 6,
+            width: lut_size,
+            height: lut_size,
+            depth_or_array_layers: lut_size,
         };
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -307,13 +586,12 @@ impl Texture {
             size,
             mip_level_count: 1,
             sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
+            dimension: wgpu::TextureDimension::D3,
             format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
 
-        // Write image data to texture.
         queue.write_texture(
             wgpu::ImageCopyTexture {
                 aspect: wgpu::TextureAspect::All,
@@ -321,24 +599,19 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * size.width),
-                rows_per_image: Some(size.height),
+                bytes_per_row: Some(bytes_per_pixel * lut_size),
+                rows_per_image: Some(lut_size),
             },
             size,
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("cubemap texture view"),
-            format: Some(format),
-            dimension: Some(wgpu::TextureViewDimension::Cube),
-            aspect: wgpu::TextureAspect::default(),
-            base_mip_level: 0,
-            mip_level_count: Some(1),
-            base_array_layer: 0,
-            array_layer_count: Some(6),
+            label: Some("lut texture view"),
+            dimension: Some(wgpu::TextureViewDimension::D3),
+            ..Default::default()
         });
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -347,18 +620,135 @@ impl Texture {
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
         });
 
         let texture = Self {
-            size: (0, 0),
+            size: (lut_size, lut_size),
             texture,
             view,
             sampler,
             format,
         };
 
-        Ok(cache.add(texture))
+        cache.add(texture)
+    }
+
+    /// Set a new sampler for this texture.
+    pub fn set_sampler(&mut self, new_sampler: wgpu::Sampler) {
+        self.sampler = new_sampler;
+    }
+}
+
+/// Multiply color channels by alpha in place, so that blending with
+/// `BlendState::PREMULTIPLIED_ALPHA_BLENDING` doesn't produce dark fringes.
+/// For sRGB data the multiplication happens in linear space.
+/// Data for one mip level of a 2D texture, tightly packed.
+pub(crate) struct TextureWrite<'a> {
+    pub(crate) mip_level: u32,
+    pub(crate) data: Cow<'a, [u8]>,
+    pub(crate) bytes_per_row: u32,
+    pub(crate) size: (u32, u32),
+}
+
+impl TextureWrite<'_> {
+    pub(crate) fn into_owned(self) -> TextureWrite<'static> {
+        TextureWrite {
+            mip_level: self.mip_level,
+            data: Cow::Owned(self.data.into_owned()),
+            bytes_per_row: self.bytes_per_row,
+            size: self.size,
+        }
+    }
+
+    pub(crate) fn submit(&self, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        self.submit_rows(queue, texture, 0..self.size.1);
+    }
+
+    /// Write only some rows, to spread a big upload over several frames.
+    pub(crate) fn submit_rows(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        rows: Range<u32>,
+    ) {
+        let bytes_per_row = self.bytes_per_row as usize;
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: self.mip_level,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: rows.start,
+                    z: 0,
+                },
+            },
+            &self.data[rows.start as usize * bytes_per_row..rows.end as usize * bytes_per_row],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.bytes_per_row),
+                rows_per_image: Some(rows.len() as u32),
+            },
+            Extent3d {
+                width: self.size.0,
+                height: rows.len() as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+pub(crate) fn premultiply_alpha(rgba: &mut image::RgbaImage, srgb: bool) {
+    for pixel in rgba.pixels_mut() {
+        let alpha = pixel[3];
+        if alpha == 255 {
+            continue;
+        }
+
+        let alpha = alpha as f32 / 255.0;
+        for channel in &mut pixel.0[..3] {
+            let value = *channel as f32 / 255.0;
+            let premultiplied = if srgb {
+                linear_to_srgb(srgb_to_linear(value) * alpha)
+            } else {
+                value * alpha
+            };
+            *channel = (premultiplied * 255.0).round() as u8;
+        }
+    }
+}
+
+/// Whether an image has more than 8 bits per channel.
+pub(crate) fn is_high_precision(img: &DynamicImage) -> bool {
+    matches!(
+        img,
+        DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_)
+            | DynamicImage::ImageRgb32F(_)
+            | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+/// Pixel data of an image laid out for a texture format.
+pub(crate) fn encode_pixels(img: &DynamicImage, format: wgpu::TextureFormat) -> Cow<'_, [u8]> {
+    match format {
+        wgpu::TextureFormat::R16Float => Cow::Owned(
+            img.to_luma32f()
+                .iter()
+                .flat_map(|v| f16::from_f32(*v).to_le_bytes())
+                .collect(),
+        ),
+        wgpu::TextureFormat::Rgba16Float => Cow::Owned(
+            img.to_rgba32f()
+                .iter()
+                .flat_map(|v| f16::from_f32(*v).to_le_bytes())
+                .collect(),
+        ),
+        _ => Cow::Borrowed(img.as_bytes()),
     }
 }
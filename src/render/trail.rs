@@ -0,0 +1,214 @@
+use crate::render::vertex::VertexBuffer;
+use crate::render::RenderServer;
+use std::mem;
+use wgpu::BufferAddress;
+
+/// A ribbon vertex. It is pushed sideways by `offset` in the vertex shader,
+/// perpendicular to both `tangent` and the view direction.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct TrailVertex {
+    pub(crate) position: [f32; 3],
+    pub(crate) tangent: [f32; 3],
+    pub(crate) color: [f32; 4],
+    /// Signed half width.
+    pub(crate) offset: f32,
+}
+
+impl VertexBuffer for TrailVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<TrailVertex>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 10]>() as BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// A tessellated trail ribbon, in world space.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtractedTrail3d {
+    pub(crate) vertices: Vec<TrailVertex>,
+    pub(crate) indices: Vec<u32>,
+}
+
+pub(crate) struct TrailRenderResources {
+    pipeline: wgpu::RenderPipeline,
+
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_buffer_capacity: usize,
+    index_buffer: Option<wgpu::Buffer>,
+    index_buffer_capacity: usize,
+    index_count: u32,
+}
+
+impl TrailRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("trail shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/trail.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("trail pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Trails are transparent, so they are depth tested but don't write depth.
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("trail pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[TrailVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                depth_write_enabled: false,
+                depth_compare: render_server.depth_compare(wgpu::CompareFunction::LessEqual),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer: None,
+            vertex_buffer_capacity: 0,
+            index_buffer: None,
+            index_buffer_capacity: 0,
+            index_count: 0,
+        }
+    }
+}
+
+pub(crate) fn prepare_trails(
+    trails: &[ExtractedTrail3d],
+    render_resources: &mut TrailRenderResources,
+    render_server: &RenderServer,
+) {
+    let mut all_vertices = vec![];
+    let mut all_indices = vec![];
+
+    for trail in trails {
+        let base_vertex = all_vertices.len() as u32;
+
+        all_vertices.extend_from_slice(&trail.vertices);
+        all_indices.extend(trail.indices.iter().map(|i| i + base_vertex));
+    }
+
+    render_resources.index_count = all_indices.len() as u32;
+
+    if all_indices.is_empty() {
+        return;
+    }
+
+    // Reallocate the vertex buffer.
+    if render_resources.vertex_buffer_capacity < all_vertices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("trail vertex buffer"),
+            size: mem::size_of_val(all_vertices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.vertex_buffer_capacity = all_vertices.len();
+        render_resources.vertex_buffer = Some(buffer);
+    }
+
+    // Reallocate the index buffer.
+    if render_resources.index_buffer_capacity < all_indices.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("trail index buffer"),
+            size: mem::size_of_val(all_indices.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.index_buffer_capacity = all_indices.len();
+        render_resources.index_buffer = Some(buffer);
+    }
+
+    render_server.queue.write_buffer(
+        render_resources.vertex_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&all_vertices),
+    );
+
+    render_server.queue.write_buffer(
+        render_resources.index_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&all_indices),
+    );
+}
+
+pub(crate) fn render_trails<'a, 'b: 'a>(
+    render_resources: &'b TrailRenderResources,
+    render_pass: &mut wgpu::RenderPass<'a>,
+    camera_bind_group: &'b wgpu::BindGroup,
+) {
+    if render_resources.index_count == 0 {
+        return;
+    }
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+
+    // Set camera group. Offset 0 is the first camera, which all 3D passes draw with for now.
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+
+    render_pass.set_vertex_buffer(
+        0,
+        render_resources.vertex_buffer.as_ref().unwrap().slice(..),
+    );
+
+    render_pass.set_index_buffer(
+        render_resources.index_buffer.as_ref().unwrap().slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+
+    render_pass.draw_indexed(0..render_resources.index_count, 0, 0..1);
+}
@@ -0,0 +1,299 @@
+use crate::core::singleton::Singletons;
+use crate::math::color::ColorU;
+use crate::render::{RenderServer, UniformBuffer};
+use crate::scene::World;
+use cgmath::{InnerSpace, Vector2};
+use std::mem;
+use wgpu::BufferAddress;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransitionKind {
+    Fade,
+    /// Covers the screen from one side. The direction is in screen space, +Y is down.
+    Wipe {
+        direction: Vector2<f32>,
+    },
+    /// A circle closing on a point. The center is in UV space, from (0, 0) at the top left
+    /// to (1, 1) at the bottom right.
+    Iris {
+        center: Vector2<f32>,
+    },
+}
+
+/// Called once a transition finishes. It can change the scene and start another transition.
+pub type TransitionCallback = fn(&mut World, &mut Singletons, &mut ScreenTransition);
+
+/// Covers or reveals the screen, e.g. around scene changes.
+pub struct ScreenTransition {
+    pub kind: TransitionKind,
+    pub color: ColorU,
+    /// Width of the soft edge of wipes and irises, in UV units.
+    pub softness: f32,
+
+    /// 0 shows the scene, 1 covers it.
+    coverage: f32,
+    target: f32,
+    /// Coverage change per second.
+    speed: f32,
+
+    on_complete: Option<TransitionCallback>,
+}
+
+impl Default for ScreenTransition {
+    fn default() -> Self {
+        Self {
+            kind: TransitionKind::Fade,
+            color: ColorU::black(),
+            softness: 0.05,
+            coverage: 0.0,
+            target: 0.0,
+            speed: 1.0,
+            on_complete: None,
+        }
+    }
+}
+
+impl ScreenTransition {
+    /// Cover the screen over `duration` seconds.
+    pub fn cover(
+        &mut self,
+        kind: TransitionKind,
+        duration: f32,
+        on_complete: Option<TransitionCallback>,
+    ) {
+        self.start(kind, 1.0, duration, on_complete);
+    }
+
+    /// Reveal the screen over `duration` seconds.
+    pub fn reveal(
+        &mut self,
+        kind: TransitionKind,
+        duration: f32,
+        on_complete: Option<TransitionCallback>,
+    ) {
+        self.start(kind, 0.0, duration, on_complete);
+    }
+
+    fn start(
+        &mut self,
+        kind: TransitionKind,
+        target: f32,
+        duration: f32,
+        on_complete: Option<TransitionCallback>,
+    ) {
+        self.kind = kind;
+        self.target = target;
+        self.on_complete = on_complete;
+
+        if duration > 0.0 {
+            self.speed = 1.0 / duration;
+        } else {
+            self.coverage = target;
+        }
+    }
+
+    /// Jump to a coverage without animating, e.g. to reveal a new scene from black.
+    /// Cancels the running transition.
+    pub fn set_coverage(&mut self, coverage: f32) {
+        self.coverage = coverage.clamp(0.0, 1.0);
+        self.target = self.coverage;
+        self.on_complete = None;
+    }
+
+    pub fn get_coverage(&self) -> f32 {
+        self.coverage
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.coverage != self.target || self.on_complete.is_some()
+    }
+
+    /// Advance the transition, returning the callback to run if it has just finished.
+    pub(crate) fn update(&mut self, dt: f32) -> Option<TransitionCallback> {
+        let step = self.speed * dt;
+
+        self.coverage = if self.target > self.coverage {
+            (self.coverage + step).min(self.target)
+        } else {
+            (self.coverage - step).max(self.target)
+        };
+
+        if self.coverage == self.target {
+            self.on_complete.take()
+        } else {
+            None
+        }
+    }
+}
+
+const KIND_FADE: u32 = 0;
+const KIND_WIPE: u32 = 1;
+const KIND_IRIS: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TransitionUniform {
+    color: [f32; 4],
+    direction: [f32; 2],
+    center: [f32; 2],
+    kind: u32,
+    coverage: f32,
+    softness: f32,
+    _pad: f32,
+}
+
+pub(crate) struct TransitionRenderResources {
+    pipeline: wgpu::RenderPipeline,
+    params_buffer: UniformBuffer,
+    params_bind_group: wgpu::BindGroup,
+    /// If the transition covers any part of the screen this frame.
+    pub(crate) enabled: bool,
+}
+
+impl TransitionRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("transition params bind group layout"),
+            });
+
+        let params_buffer = render_server.create_uniform_buffer(
+            "transition params buffer",
+            mem::size_of::<TransitionUniform>() as BufferAddress,
+        );
+
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("transition params bind group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("transition pipeline layout"),
+            bind_group_layouts: &[globals_bind_group_layout, &params_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("transition shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/transition.wgsl").into()),
+        });
+
+        // Blended over the final image, unlike the other post passes.
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("transition pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.output_format(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            params_buffer,
+            params_bind_group,
+            enabled: false,
+        }
+    }
+}
+
+pub(crate) fn prepare_transition(
+    transition: &ScreenTransition,
+    render_resources: &mut TransitionRenderResources,
+    render_server: &RenderServer,
+) {
+    render_resources.enabled = transition.coverage > 0.0;
+
+    if !render_resources.enabled {
+        return;
+    }
+
+    let (kind, direction, center) = match transition.kind {
+        TransitionKind::Fade => (KIND_FADE, Vector2::new(1.0, 0.0), Vector2::new(0.5, 0.5)),
+        TransitionKind::Wipe { direction } => {
+            let direction = if direction.magnitude2() > 0.0 {
+                direction.normalize()
+            } else {
+                Vector2::new(1.0, 0.0)
+            };
+            (KIND_WIPE, direction, Vector2::new(0.5, 0.5))
+        }
+        TransitionKind::Iris { center } => (KIND_IRIS, Vector2::new(1.0, 0.0), center),
+    };
+
+    let uniform = TransitionUniform {
+        color: transition.color.to_f32().to_array(),
+        direction: direction.into(),
+        center: center.into(),
+        kind,
+        coverage: transition.coverage,
+        softness: transition.softness.max(0.0001),
+        _pad: 0.0,
+    };
+
+    render_server.queue.write_buffer(
+        &render_resources.params_buffer,
+        0,
+        bytemuck::cast_slice(&[uniform]),
+    );
+}
+
+pub(crate) fn render_transition(
+    render_resources: &TransitionRenderResources,
+    globals_bind_group: &wgpu::BindGroup,
+    encoder: &mut wgpu::CommandEncoder,
+    target_view: &wgpu::TextureView,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("transition render pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+    render_pass.set_bind_group(0, globals_bind_group, &[]);
+    render_pass.set_bind_group(1, &render_resources.params_bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
@@ -0,0 +1,249 @@
+use crate::math::color::ColorU;
+use crate::math::transform::Transform2d;
+use crate::render::clip::clipped_stencil_state;
+use crate::render::vertex::VertexBuffer;
+use crate::render::RenderServer;
+use cgmath::{Vector2, Vector4};
+use std::mem;
+use std::ops::Range;
+use wgpu::BufferAddress;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum UiShapeKind {
+    /// Uses the per-corner radii.
+    #[default]
+    RoundedRect = 0,
+    /// Fits the shorter side of the rectangle.
+    Circle = 1,
+    /// Rectangle with fully rounded ends.
+    Capsule = 2,
+}
+
+/// Appearance of a UI shape.
+#[derive(Debug, Copy, Clone)]
+pub struct UiShape {
+    pub kind: UiShapeKind,
+    /// Top-left, top-right, bottom-right, bottom-left. Only used by rounded rects.
+    pub corner_radii: [f32; 4],
+    pub color: ColorU,
+    /// Drawn inside the shape edge.
+    pub border_width: f32,
+    pub border_color: ColorU,
+    /// Show a blurred copy of the 3D scene behind the shape, tinted by `color`.
+    /// See `RenderWorld::backdrop_blur_radius`.
+    pub backdrop_blur: bool,
+}
+
+impl Default for UiShape {
+    fn default() -> Self {
+        Self {
+            kind: UiShapeKind::RoundedRect,
+            corner_radii: [0.0; 4],
+            color: ColorU::white(),
+            border_width: 0.0,
+            border_color: ColorU::transparent_black(),
+            backdrop_blur: false,
+        }
+    }
+}
+
+/// An SDF primitive in view (pixel) space, like UI nodes.
+/// The rectangle spans from the transform origin to `size`, rotated around the origin.
+#[derive(Debug, Copy, Clone)]
+pub struct ExtractedUiShape {
+    pub(crate) transform: Transform2d,
+    pub(crate) size: Vector2<f32>,
+    pub(crate) kind: UiShapeKind,
+    pub(crate) corner_radii: Vector4<f32>,
+    pub(crate) color: Vector4<f32>,
+    pub(crate) border_width: f32,
+    pub(crate) border_color: Vector4<f32>,
+    pub(crate) backdrop_blur: bool,
+    pub(crate) view_size: Vector2<u32>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct UiShapeInstanceRaw {
+    position: [f32; 2],
+    size: [f32; 2],
+    view_size: [f32; 2],
+    rotation: f32,
+    border_width: f32,
+    corner_radii: [f32; 4],
+    color: [f32; 4],
+    border_color: [f32; 4],
+    kind: u32,
+    backdrop_blur: u32,
+    /// Size of the render target, where the view may be stretched to.
+    screen_size: [f32; 2],
+}
+
+impl ExtractedUiShape {
+    fn to_raw(self, screen_size: [f32; 2]) -> UiShapeInstanceRaw {
+        UiShapeInstanceRaw {
+            position: self.transform.position.into(),
+            size: [
+                self.size.x * self.transform.scale.x,
+                self.size.y * self.transform.scale.y,
+            ],
+            view_size: [self.view_size.x as f32, self.view_size.y as f32],
+            rotation: self.transform.rotation,
+            border_width: self.border_width,
+            corner_radii: self.corner_radii.into(),
+            color: self.color.into(),
+            border_color: self.border_color.into(),
+            kind: self.kind as u32,
+            backdrop_blur: self.backdrop_blur as u32,
+            screen_size,
+        }
+    }
+}
+
+impl VertexBuffer for UiShapeInstanceRaw {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 11] = wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32,
+            4 => Float32,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Uint32,
+            9 => Uint32,
+            10 => Float32x2,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<UiShapeInstanceRaw>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+pub struct UiShapeRenderResources {
+    pipeline: wgpu::RenderPipeline,
+
+    instance_buffer: Option<wgpu::Buffer>,
+    instance_buffer_capacity: usize,
+}
+
+impl UiShapeRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        backdrop_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("ui shape pipeline layout"),
+            bind_group_layouts: &[backdrop_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("ui shape shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/ui_shape.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("ui shape pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[UiShapeInstanceRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip, // Has to be triangle strip.
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: render_server.depth_format(),
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: clipped_stencil_state(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            instance_buffer: None,
+            instance_buffer_capacity: 0,
+        }
+    }
+}
+
+pub(crate) fn prepare_ui_shapes(
+    shapes: &[ExtractedUiShape],
+    render_resources: &mut UiShapeRenderResources,
+    render_server: &RenderServer,
+) {
+    if shapes.is_empty() {
+        return;
+    }
+
+    // Reallocate the instance buffer.
+    if render_resources.instance_buffer_capacity < shapes.len() {
+        let buffer = render_server.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ui shape instance buffer (unique)"),
+            size: (mem::size_of::<UiShapeInstanceRaw>() * shapes.len()) as BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        render_resources.instance_buffer_capacity = shapes.len();
+        render_resources.instance_buffer = Some(buffer);
+    }
+
+    let config = &render_server.surface_config;
+    let screen_size = [config.width as f32, config.height as f32];
+
+    let instance_data = shapes
+        .iter()
+        .map(|shape| shape.to_raw(screen_size))
+        .collect::<Vec<_>>();
+
+    render_server.queue.write_buffer(
+        render_resources.instance_buffer.as_ref().unwrap(),
+        0,
+        bytemuck::cast_slice(&instance_data),
+    );
+}
+
+pub(crate) fn render_ui_shapes<'a, 'b: 'a>(
+    range: Range<usize>,
+    render_resources: &'b UiShapeRenderResources,
+    backdrop_bind_group: &'b wgpu::BindGroup,
+    render_pass: &mut wgpu::RenderPass<'a>,
+) {
+    if range.is_empty() {
+        return;
+    }
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+    render_pass.set_bind_group(0, backdrop_bind_group, &[]);
+    render_pass.set_vertex_buffer(
+        0,
+        render_resources.instance_buffer.as_ref().unwrap().slice(..),
+    );
+
+    // All shapes share one pipeline, so draw the whole range at once.
+    render_pass.draw(0..4, range.start as u32..range.end as u32);
+}
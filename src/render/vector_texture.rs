@@ -1,298 +1,1130 @@
-// use crate::render::vertex::{VertexBuffer};
-// use crate::render::RenderServer;
-// use lyon::geom::Point;
-// use lyon::math::point;
-// use lyon::path::builder::Build;
-// use lyon::path::path::Builder;
-// use lyon::path::Path;
-// use lyon::tessellation::{
-//     BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
-//     StrokeVertex, VertexBuffers,
-// };
-// use std::fs;
-// use usvg::{Paint, TreeParsing};
-// use wgpu::util::DeviceExt;
-//
-// #[repr(C)]
-// #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-// pub(crate) struct VectorVertex {
-//     pub(crate) position: [f32; 2],
-//     pub(crate) color: [f32; 3],
-// }
-//
-// impl VertexBuffer for VectorVertex {
-//     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-//         wgpu::VertexBufferLayout {
-//             array_stride: std::mem::size_of::<VectorVertex>() as wgpu::BufferAddress,
-//             step_mode: wgpu::VertexStepMode::Vertex,
-//             attributes: &[
-//                 // Position.
-//                 wgpu::VertexAttribute {
-//                     offset: 0,
-//                     shader_location: 0,
-//                     format: wgpu::VertexFormat::Float32x2,
-//                 },
-//                 // Color.
-//                 wgpu::VertexAttribute {
-//                     offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-//                     shader_location: 1,
-//                     format: wgpu::VertexFormat::Float32x3,
-//                 },
-//             ],
-//         }
-//     }
-// }
-//
-// pub struct VectorMesh {
-//     // For debugging.
-//     pub name: String,
-//     pub vertex_buffer: wgpu::Buffer,
-//     pub index_buffer: wgpu::Buffer,
-//     pub index_count: u32,
-// }
-//
-// /// An vector analogy to ImageTexture.
-// pub struct VectorTexture {
-//     pub size: (f32, f32),
-//     // pub paths: Vec<Path>,
-//     /// CPU mesh.
-//     geometry: VertexBuffers<VectorVertex, u32>,
-//     vertices: Vec<VectorVertex>,
-//     indices: Vec<u32>,
-//     max_index: u32,
-//     /// GPU mesh.
-//     pub(crate) mesh: Option<VectorMesh>,
-//     builder: Builder,
-// }
-//
-// impl VectorTexture {
-//     /// Load from a SVG file.
-//     pub fn from_tree(tree: &usvg::Tree) -> Self {
-//         let mut tex = VectorTexture::new((tree.size.width() as f32, tree.size.height() as f32));
-//
-//         let root = &tree.root;
-//
-//         for kid in root.children() {
-//             tex.process_node(&kid);
-//         }
-//
-//         // tex.build();
-//
-//         tex.prepare_gpu_resources(render_server);
-//
-//         tex
-//     }
-//
-//     pub(crate) fn new(size: (f32, f32)) -> Self {
-//         // // Build a Path.
-//         let mut builder = Path::builder();
-//
-//         let mut geometry: VertexBuffers<VectorVertex, u32> = VertexBuffers::new();
-//
-//         Self {
-//             size,
-//             geometry,
-//             vertices: vec![],
-//             indices: vec![],
-//             max_index: 0,
-//             mesh: None,
-//             builder,
-//         }
-//     }
-//
-//     pub(crate) fn build(&mut self) {
-//         let mut builder = std::mem::replace(&mut self.builder, Path::builder());
-//
-//         let path = builder.build();
-//
-//         // Will contain the result of the tessellation.
-//         let mut tessellator = FillTessellator::new();
-//
-//         // Compute the tessellation.
-//         tessellator
-//             .tessellate_path(
-//                 &path,
-//                 &FillOptions::default(),
-//                 &mut BuffersBuilder::new(&mut self.geometry, |vertex: FillVertex| VectorVertex {
-//                     position: vertex.position().to_array(),
-//                     color: [1.0, 1.0, 1.0],
-//                 }),
-//             )
-//             .unwrap();
-//
-//         // The tessellated geometry is ready to be uploaded to the GPU.
-//         log::info!(
-//             "Vector sprite info: {} vertices, {} indices",
-//             self.geometry.vertices.len(),
-//             self.geometry.indices.len()
-//         );
-//     }
-//
-//     pub(crate) fn prepare_gpu_resources(&mut self, render_server: &RenderServer) {
-//         let device = &render_server.device;
-//
-//         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-//             label: Some(&format!("vertex buffer for vector sprite")),
-//             contents: bytemuck::cast_slice(&self.vertices),
-//             usage: wgpu::BufferUsages::VERTEX,
-//         });
-//
-//         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-//             label: Some(&format!("index buffer for vector sprite")),
-//             contents: bytemuck::cast_slice(&self.indices),
-//             usage: wgpu::BufferUsages::INDEX,
-//         });
-//
-//         self.mesh = Some(VectorMesh {
-//             name: "".to_string(),
-//             vertex_buffer,
-//             index_buffer,
-//             index_count: self.indices.len() as u32,
-//         });
-//     }
-//
-//     fn process_node(&mut self, node: &usvg::Node) {
-//         match *node.borrow() {
-//             usvg::NodeKind::Group(_) => {
-//                 for kid in node.children() {
-//                     self.process_node(&kid);
-//                 }
-//             }
-//             usvg::NodeKind::Path(ref path) => {
-//                 let mut builder = Path::builder();
-//
-//                 let mut subpath_ended = false;
-//
-//                 for segment in path.data.segments() {
-//                     match segment {
-//                         usvg::PathSegment::MoveTo { x, y } => {
-//                             builder.begin(point(x as f32, y as f32));
-//                         }
-//                         usvg::PathSegment::LineTo { x, y } => {
-//                             builder.line_to(point(x as f32, y as f32));
-//                         }
-//                         usvg::PathSegment::CurveTo {
-//                             x1,
-//                             y1,
-//                             x2,
-//                             y2,
-//                             x,
-//                             y,
-//                         } => {
-//                             builder.cubic_bezier_to(
-//                                 point(x1 as f32, y1 as f32),
-//                                 point(x2 as f32, y2 as f32),
-//                                 point(x as f32, y as f32),
-//                             );
-//                         }
-//                         usvg::PathSegment::ClosePath => {
-//                             builder.close();
-//                             subpath_ended = true;
-//                         }
-//                     }
-//                 }
-//
-//                 if !subpath_ended {
-//                     builder.end(false);
-//                 }
-//
-//                 let lyon_path = builder.build();
-//
-//                 let mut geometry: VertexBuffers<VectorVertex, u32> = VertexBuffers::new();
-//
-//                 if let Some(ref fill) = path.fill {
-//                     // Will contain the result of the tessellation.
-//                     let mut tessellator = FillTessellator::new();
-//
-//                     match fill.paint {
-//                         Paint::Color(color) => {
-//                             // Compute the tessellation.
-//                             let result = tessellator.tessellate_path(
-//                                 &lyon_path,
-//                                 &FillOptions::default(),
-//                                 &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
-//                                     VectorVertex {
-//                                         position: vertex.position().to_array(),
-//                                         color: [
-//                                             color.red as f32 / 255.0,
-//                                             color.green as f32 / 255.0,
-//                                             color.blue as f32 / 255.0,
-//                                         ],
-//                                     }
-//                                 }),
-//                             );
-//                             assert!(result.is_ok());
-//                         }
-//                         Paint::LinearGradient(_) => {}
-//                         Paint::RadialGradient(_) => {}
-//                         Paint::Pattern(_) => {}
-//                     }
-//                 }
-//
-//                 if let Some(ref stroke) = path.stroke {
-//                     // Create the tessellator.
-//                     let mut tessellator = StrokeTessellator::new();
-//
-//                     match stroke.paint {
-//                         Paint::Color(color) => {
-//                             let mut options = StrokeOptions::default()
-//                                 .with_line_width(stroke.width.get() as f32)
-//                                 .with_line_join(convert_line_join(stroke.linejoin))
-//                                 .with_line_cap(convert_line_cap(stroke.linecap));
-//                             if stroke.linejoin == usvg::LineJoin::Miter {
-//                                 options = options.with_miter_limit(stroke.miterlimit.get() as f32);
-//                             }
-//
-//                             // Compute the tessellation.
-//                             let result = tessellator.tessellate_path(
-//                                 &lyon_path,
-//                                 &options,
-//                                 &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
-//                                     VectorVertex {
-//                                         position: vertex.position().to_array(),
-//                                         color: [
-//                                             color.red as f32 / 255.0,
-//                                             color.green as f32 / 255.0,
-//                                             color.blue as f32 / 255.0,
-//                                         ],
-//                                     }
-//                                 }),
-//                             );
-//                             assert!(result.is_ok());
-//                         }
-//                         Paint::LinearGradient(_) => {}
-//                         Paint::RadialGradient(_) => {}
-//                         Paint::Pattern(_) => {}
-//                     }
-//                 }
-//
-//                 let vertex_count = geometry.vertices.len();
-//
-//                 self.vertices.extend(geometry.vertices);
-//
-//                 for index in geometry.indices {
-//                     self.indices.push(index + self.max_index);
-//                 }
-//
-//                 self.max_index += vertex_count as u32;
-//             }
-//             _ => {}
-//         }
-//     }
-// }
-//
-// fn convert_line_join(usvg_line_join: usvg::LineJoin) -> lyon::path::LineJoin {
-//     match usvg_line_join {
-//         usvg::LineJoin::Bevel => lyon::path::LineJoin::Bevel,
-//         usvg::LineJoin::Miter => lyon::path::LineJoin::Miter,
-//         usvg::LineJoin::Round => lyon::path::LineJoin::Round,
-//     }
-// }
-//
-// fn convert_line_cap(usvg_line_cap: usvg::LineCap) -> lyon::path::LineCap {
-//     match usvg_line_cap {
-//         usvg::LineCap::Round => lyon::path::LineCap::Round,
-//         usvg::LineCap::Butt => lyon::path::LineCap::Butt,
-//         usvg::LineCap::Square => lyon::path::LineCap::Square,
-//     }
-// }
+use crate::asset::{TextureFilter, TextureImportSettings, TextureWrap};
+use crate::math::color::ColorU;
+use crate::math::shape2d::Shape2d;
+use crate::math::transform::Transform2d;
+use crate::render::clip::ExtractedClip;
+use crate::render::curve2d::ExtractedCurve2d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::vertex::{Vertex2d, Vertex2dCurve};
+use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use anyhow::{Context, Result};
+use cgmath::{Vector2, Zero};
+use image::{DynamicImage, GenericImageView};
+use lyon::algorithms::hit_test::hit_test_path;
+use lyon::geom::{CubicBezierSegment, LineSegment, QuadraticBezierSegment};
+use lyon::math::point;
+use lyon::path::iterator::PathIterator;
+use lyon::path::{FillRule, Path, PathEvent};
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, LineCap, LineJoin, StrokeOptions,
+    StrokeTessellator, StrokeVertex, VertexBuffers,
+};
+use usvg::{TreeParsing, TreePostProc};
+
+/// How far flattened curves may stray from the SVG paths, in SVG units.
+const TOLERANCE: f32 = 0.1;
+
+/// A filled and/or stroked shape of an SVG, or a raster image.
+struct VectorElement {
+    /// ID of the path, or of the closest group around it that has one. Can be empty.
+    id: String,
+
+    /// Outline in SVG canvas space, kept for hit testing.
+    path: Path,
+
+    fill_rule: Option<FillRule>,
+    fill_color: [f32; 3],
+    stroke_width: Option<f32>,
+
+    /// Triangles of the fill and the stroke, with the colors from the SVG.
+    geometry: VertexBuffers<Vertex2d, u32>,
+    /// The fill comes first in the geometry indices, then the stroke.
+    fill_index_count: usize,
+
+    /// The fill as GPU curves and the stroke as is, built when curve rendering is turned on.
+    curves: Option<VertexBuffers<Vertex2dCurve, u32>>,
+
+    /// Images are drawn with their texture once it's loaded, and skipped before.
+    image: bool,
+    texture: Option<TextureId>,
+
+    /// Clip shapes this is drawn inside of, outermost first.
+    clips: Vec<usize>,
+
+    modulate: ColorU,
+    visible: bool,
+}
+
+impl VectorElement {
+    fn build_curves(&mut self) -> Result<()> {
+        if self.curves.is_some() || self.image {
+            return Ok(());
+        }
+
+        let mut curves = VertexBuffers::new();
+
+        if let Some(fill_rule) = self.fill_rule {
+            tessellate_curve_fill(&self.path, fill_rule, self.fill_color, &mut curves)?;
+        }
+
+        let start = curves.vertices.len() as u32;
+        curves
+            .vertices
+            .extend(self.geometry.vertices.iter().map(Vertex2dCurve::solid));
+        curves.indices.extend(
+            self.geometry.indices[self.fill_index_count..]
+                .iter()
+                .map(|i| start + i),
+        );
+
+        self.curves = Some(curves);
+
+        Ok(())
+    }
+
+    fn contains_point(&self, p: lyon::math::Point) -> bool {
+        if let Some(fill_rule) = self.fill_rule {
+            if hit_test_path(&p, self.path.iter(), fill_rule, TOLERANCE) {
+                return true;
+            }
+        }
+
+        if let Some(width) = self.stroke_width {
+            let half_width = width * 0.5;
+
+            for event in self.path.iter().flattened(TOLERANCE) {
+                let segment = match event {
+                    PathEvent::Line { from, to } => LineSegment { from, to },
+                    PathEvent::End {
+                        last,
+                        first,
+                        close: true,
+                    } => LineSegment {
+                        from: last,
+                        to: first,
+                    },
+                    _ => continue,
+                };
+
+                if segment.distance_to_point(p) <= half_width {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// The union of the shapes of a clip path or mask, in SVG canvas space.
+struct VectorClip {
+    paths: Vec<(Path, FillRule)>,
+
+    /// Three points each.
+    triangles: Vec<Vector2<f32>>,
+}
+
+impl VectorClip {
+    fn contains_point(&self, p: lyon::math::Point) -> bool {
+        self.paths
+            .iter()
+            .any(|(path, fill_rule)| hit_test_path(&p, path.iter(), *fill_rule, TOLERANCE))
+    }
+}
+
+/// A raster image decoded from the SVG, waiting for `VectorTexture::load_images`.
+struct PendingImage {
+    element: usize,
+    image: DynamicImage,
+    settings: TextureImportSettings,
+}
+
+/// A vector analogy to ImageTexture, made of the paths of an SVG.
+///
+/// Elements keep their IDs and outlines, so they can be recolored, hidden and
+/// hit tested one by one, e.g. for clickable maps and diagrams.
+/// Only solid colors are drawn. Gradients use their first stop, and patterns and text
+/// are skipped. Clip paths and masks clip with the stencil buffer, so masks are binary:
+/// their shapes show everything under them, whatever their color or opacity.
+///
+/// Fills are flattened into triangles when loaded, which shows at large scales.
+/// See [`VectorTexture::set_curve_rendering`] for textures that are scaled up a lot.
+pub struct VectorTexture {
+    /// Size of the SVG canvas.
+    pub size: Vector2<f32>,
+
+    /// In drawing order, bottom to top.
+    elements: Vec<VectorElement>,
+
+    clips: Vec<VectorClip>,
+
+    pending_images: Vec<PendingImage>,
+
+    curve_rendering: bool,
+}
+
+impl VectorTexture {
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        let data = std::fs::read(path).with_context(|| format!("Invalid SVG path {:?}", path))?;
+
+        // Relative image paths are resolved from the SVG.
+        let options = usvg::Options {
+            resources_dir: path.parent().map(|dir| dir.to_path_buf()),
+            ..Default::default()
+        };
+
+        Self::from_data_with_options(&data, &options)
+            .with_context(|| format!("Invalid SVG {:?}", path))
+    }
+
+    /// An empty canvas, for shapes added at runtime.
+    pub fn new(size: Vector2<f32>) -> Self {
+        Self {
+            size,
+            elements: vec![],
+            clips: vec![],
+            pending_images: vec![],
+            curve_rendering: false,
+        }
+    }
+
+    pub fn from_data(data: &[u8]) -> Result<Self> {
+        Self::from_data_with_options(data, &usvg::Options::default())
+    }
+
+    fn from_data_with_options(data: &[u8], options: &usvg::Options) -> Result<Self> {
+        let mut tree = usvg::Tree::from_data(data, options)?;
+
+        // For clip paths and masks in bounding box units.
+        tree.postprocess(
+            usvg::PostProcessingSteps {
+                convert_text_into_paths: false,
+            },
+            &usvg::fontdb::Database::new(),
+        );
+
+        let mut texture = Self::new(Vector2::new(tree.size.width(), tree.size.height()));
+
+        texture.add_tree(&tree, usvg::Transform::identity(), "", &[])?;
+
+        Ok(texture)
+    }
+
+    /// Upload the raster images of the SVG. They aren't drawn until this is called.
+    pub fn load_images(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> Result<()> {
+        for pending in self.pending_images.drain(..) {
+            let texture = Texture::from_image_with_settings(
+                &render_server.device,
+                &render_server.queue,
+                texture_cache,
+                &pending.image,
+                Some("vector texture image"),
+                &pending.settings,
+            )?;
+
+            self.elements[pending.element].texture = Some(texture);
+        }
+
+        Ok(())
+    }
+
+    fn add_tree(
+        &mut self,
+        tree: &usvg::Tree,
+        transform: usvg::Transform,
+        id: &str,
+        clips: &[usize],
+    ) -> Result<()> {
+        let view_box_transform =
+            usvg::utils::view_box_to_transform(tree.view_box.rect, tree.view_box.aspect, tree.size);
+
+        self.add_group(
+            &tree.root,
+            transform.pre_concat(view_box_transform),
+            id,
+            clips,
+        )
+    }
+
+    fn add_group(
+        &mut self,
+        group: &usvg::Group,
+        parent_transform: usvg::Transform,
+        parent_id: &str,
+        parent_clips: &[usize],
+    ) -> Result<()> {
+        let transform = parent_transform.pre_concat(group.transform);
+        let id = if group.id.is_empty() {
+            parent_id
+        } else {
+            &group.id
+        };
+
+        let mut clips = parent_clips.to_vec();
+        if let Some(clip_path) = &group.clip_path {
+            self.add_clip_path(
+                &clip_path.borrow(),
+                transform,
+                group.bounding_box,
+                &mut clips,
+            )?;
+        }
+        if let Some(mask) = &group.mask {
+            self.add_mask(&mask.borrow(), transform, group.bounding_box, &mut clips)?;
+        }
+
+        for child in &group.children {
+            match child {
+                usvg::Node::Group(group) => self.add_group(group, transform, id, &clips)?,
+                usvg::Node::Path(path) => {
+                    if path.visibility != usvg::Visibility::Visible {
+                        continue;
+                    }
+
+                    let path_id = if path.id.is_empty() { id } else { &path.id };
+                    self.add_path(path, transform, path_id, &clips)?;
+                }
+                usvg::Node::Image(image) => {
+                    if image.visibility != usvg::Visibility::Visible {
+                        continue;
+                    }
+
+                    let image_id = if image.id.is_empty() { id } else { &image.id };
+                    self.add_image(image, transform, image_id, &clips)?;
+                }
+                usvg::Node::Text(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_path(
+        &mut self,
+        path: &usvg::Path,
+        transform: usvg::Transform,
+        id: &str,
+        clips: &[usize],
+    ) -> Result<()> {
+        let lyon_path = convert_path(path, transform);
+
+        let mut geometry: VertexBuffers<Vertex2d, u32> = VertexBuffers::new();
+
+        let fill = path
+            .fill
+            .as_ref()
+            .and_then(|fill| Some((fill, paint_color(&fill.paint)?)));
+
+        let fill_rule = match fill {
+            Some((fill, color)) => {
+                let fill_rule = convert_fill_rule(fill.rule);
+                tessellate_fill(&lyon_path, fill_rule, color, &mut geometry)?;
+
+                Some(fill_rule)
+            }
+            None => None,
+        };
+        let fill_index_count = geometry.indices.len();
+
+        let stroke = path
+            .stroke
+            .as_ref()
+            .and_then(|stroke| Some((stroke, paint_color(&stroke.paint)?)));
+
+        let stroke_width = match stroke {
+            Some((stroke, color)) => {
+                // Widths scale with the path, by the average of the axes.
+                let scale = (transform.sx * transform.sy - transform.kx * transform.ky)
+                    .abs()
+                    .sqrt();
+                let width = stroke.width.get() * scale;
+
+                let mut options = StrokeOptions::tolerance(TOLERANCE)
+                    .with_line_width(width)
+                    .with_line_join(match stroke.linejoin {
+                        usvg::LineJoin::Miter | usvg::LineJoin::MiterClip => LineJoin::Miter,
+                        usvg::LineJoin::Round => LineJoin::Round,
+                        usvg::LineJoin::Bevel => LineJoin::Bevel,
+                    })
+                    .with_line_cap(match stroke.linecap {
+                        usvg::LineCap::Butt => LineCap::Butt,
+                        usvg::LineCap::Round => LineCap::Round,
+                        usvg::LineCap::Square => LineCap::Square,
+                    });
+                if stroke.linejoin == usvg::LineJoin::Miter {
+                    options = options.with_miter_limit(stroke.miterlimit.get().max(1.0));
+                }
+
+                StrokeTessellator::new()
+                    .tessellate_path(
+                        &lyon_path,
+                        &options,
+                        &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| Vertex2d {
+                            position: vertex.position().to_array(),
+                            uv: [0.0, 0.0],
+                            color,
+                        }),
+                    )
+                    .map_err(|e| anyhow::anyhow!("Failed to tessellate SVG stroke: {:?}", e))?;
+
+                Some(width)
+            }
+            None => None,
+        };
+
+        self.elements.push(VectorElement {
+            id: id.to_string(),
+            path: lyon_path,
+            fill_rule,
+            fill_color: fill.map_or([0.0; 3], |(_, color)| color),
+            stroke_width,
+            geometry,
+            fill_index_count,
+            curves: None,
+            image: false,
+            texture: None,
+            clips: clips.to_vec(),
+            modulate: ColorU::white(),
+            visible: true,
+        });
+
+        Ok(())
+    }
+
+    fn add_image(
+        &mut self,
+        image: &usvg::Image,
+        transform: usvg::Transform,
+        id: &str,
+        clips: &[usize],
+    ) -> Result<()> {
+        let view_box = image.view_box;
+
+        let data = match &image.kind {
+            usvg::ImageKind::JPEG(data)
+            | usvg::ImageKind::PNG(data)
+            | usvg::ImageKind::GIF(data) => data,
+            usvg::ImageKind::SVG(tree) => {
+                // Nested SVGs become part of this one.
+                let mut clips = clips.to_vec();
+                let fit = self.fit_image(view_box, tree.size, transform, &mut clips);
+
+                return self.add_tree(tree, fit, id, &clips);
+            }
+        };
+
+        let decoded = match image::load_from_memory(data) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                log::warn!("Skipped an SVG image that failed to decode: {}", e);
+                return Ok(());
+            }
+        };
+
+        let (width, height) = decoded.dimensions();
+        let Some(size) = usvg::Size::from_wh(width as f32, height as f32) else {
+            return Ok(());
+        };
+
+        let mut clips = clips.to_vec();
+        let fit = self.fit_image(view_box, size, transform, &mut clips);
+
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].map(|(u, v)| {
+            let mut p = usvg::tiny_skia_path::Point::from_xy(u * size.width(), v * size.height());
+            fit.map_point(&mut p);
+
+            Vertex2d {
+                position: [p.x, p.y],
+                uv: [u, v],
+                color: [1.0, 1.0, 1.0],
+            }
+        });
+
+        let mut builder = Path::builder();
+        builder.begin(point(corners[0].position[0], corners[0].position[1]));
+        for corner in &corners[1..] {
+            builder.line_to(point(corner.position[0], corner.position[1]));
+        }
+        builder.end(true);
+
+        let mut geometry = VertexBuffers::new();
+        geometry.vertices.extend(corners);
+        geometry.indices.extend([0, 1, 2, 0, 2, 3]);
+
+        self.pending_images.push(PendingImage {
+            element: self.elements.len(),
+            image: decoded,
+            settings: TextureImportSettings {
+                filter: match image.rendering_mode {
+                    usvg::ImageRendering::OptimizeSpeed => TextureFilter::Nearest,
+                    usvg::ImageRendering::OptimizeQuality => TextureFilter::Linear,
+                },
+                wrap: TextureWrap::ClampToEdge,
+                ..Default::default()
+            },
+        });
+
+        self.elements.push(VectorElement {
+            id: id.to_string(),
+            path: builder.build(),
+            fill_rule: Some(FillRule::NonZero),
+            fill_color: [1.0; 3],
+            stroke_width: None,
+            geometry,
+            fill_index_count: 0,
+            curves: None,
+            image: true,
+            texture: None,
+            clips,
+            modulate: ColorU::white(),
+            visible: true,
+        });
+
+        Ok(())
+    }
+
+    /// Transform from image space into the view box of an image element, following
+    /// `preserveAspectRatio`. Images that are sliced are clipped to the view box.
+    fn fit_image(
+        &mut self,
+        view_box: usvg::ViewBox,
+        size: usvg::Size,
+        transform: usvg::Transform,
+        clips: &mut Vec<usize>,
+    ) -> usvg::Transform {
+        let rect = view_box.rect;
+
+        if view_box.aspect.slice {
+            let mut builder = Path::builder();
+            for (i, (x, y)) in [
+                (rect.left(), rect.top()),
+                (rect.right(), rect.top()),
+                (rect.right(), rect.bottom()),
+                (rect.left(), rect.bottom()),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let mut p = usvg::tiny_skia_path::Point::from_xy(x, y);
+                transform.map_point(&mut p);
+                if i == 0 {
+                    builder.begin(point(p.x, p.y));
+                } else {
+                    builder.line_to(point(p.x, p.y));
+                }
+            }
+            builder.end(true);
+
+            clips.push(self.add_clip(vec![(builder.build(), FillRule::NonZero)]));
+        }
+
+        let fit = usvg::utils::view_box_to_transform(
+            size.to_non_zero_rect(0.0, 0.0),
+            view_box.aspect,
+            rect.size(),
+        );
+
+        transform.pre_translate(rect.x(), rect.y()).pre_concat(fit)
+    }
+
+    /// `bounding_box` is the one of the clipped group, in its space.
+    fn add_clip_path(
+        &mut self,
+        clip_path: &usvg::ClipPath,
+        transform: usvg::Transform,
+        bounding_box: Option<usvg::Rect>,
+        clips: &mut Vec<usize>,
+    ) -> Result<()> {
+        // A clip path can be clipped itself.
+        if let Some(inner) = &clip_path.clip_path {
+            self.add_clip_path(&inner.borrow(), transform, bounding_box, clips)?;
+        }
+
+        let Some(units_transform) = units_transform(clip_path.units, transform, bounding_box)
+        else {
+            return Ok(());
+        };
+
+        let mut paths = vec![];
+        collect_paths(
+            &clip_path.root,
+            units_transform.pre_concat(clip_path.transform),
+            &mut paths,
+        );
+
+        clips.push(self.add_clip(paths));
+
+        Ok(())
+    }
+
+    /// Masks clip to their rectangle, and to their shapes.
+    fn add_mask(
+        &mut self,
+        mask: &usvg::Mask,
+        transform: usvg::Transform,
+        bounding_box: Option<usvg::Rect>,
+        clips: &mut Vec<usize>,
+    ) -> Result<()> {
+        if let Some(inner) = &mask.mask {
+            self.add_mask(&inner.borrow(), transform, bounding_box, clips)?;
+        }
+
+        let (Some(rect_transform), Some(content_transform)) = (
+            units_transform(mask.units, transform, bounding_box),
+            units_transform(mask.content_units, transform, bounding_box),
+        ) else {
+            return Ok(());
+        };
+
+        let rect = usvg::tiny_skia_path::PathBuilder::from_rect(mask.rect.to_rect());
+        let rect_clip = self.add_clip(vec![(
+            convert_data(&rect, rect_transform),
+            FillRule::NonZero,
+        )]);
+        clips.push(rect_clip);
+
+        let mut paths = vec![];
+        collect_paths(&mask.root, content_transform, &mut paths);
+        clips.push(self.add_clip(paths));
+
+        Ok(())
+    }
+
+    fn add_clip(&mut self, paths: Vec<(Path, FillRule)>) -> usize {
+        let mut geometry: VertexBuffers<Vector2<f32>, u32> = VertexBuffers::new();
+
+        for (path, fill_rule) in &paths {
+            let result = FillTessellator::new().tessellate_path(
+                path,
+                &FillOptions::tolerance(TOLERANCE).with_fill_rule(*fill_rule),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
+                    Vector2::new(vertex.position().x, vertex.position().y)
+                }),
+            );
+
+            if let Err(e) = result {
+                log::warn!("Failed to tessellate SVG clip path: {:?}", e);
+            }
+        }
+
+        let triangles = geometry
+            .indices
+            .iter()
+            .map(|i| geometry.vertices[*i as usize])
+            .collect();
+
+        self.clips.push(VectorClip { paths, triangles });
+
+        self.clips.len() - 1
+    }
+
+    /// Add a filled shape on top, e.g. one built with boolean operations.
+    pub fn add_shape(&mut self, id: &str, shape: &Shape2d, color: ColorU) -> Result<()> {
+        let path = shape.to_path();
+
+        let fill_color = color.to_vec3().into();
+
+        let mut geometry = VertexBuffers::new();
+        tessellate_fill(&path, FillRule::NonZero, fill_color, &mut geometry)?;
+        let fill_index_count = geometry.indices.len();
+
+        let mut element = VectorElement {
+            id: id.to_string(),
+            path,
+            fill_rule: Some(FillRule::NonZero),
+            fill_color,
+            stroke_width: None,
+            geometry,
+            fill_index_count,
+            curves: None,
+            image: false,
+            texture: None,
+            clips: vec![],
+            modulate: ColorU::white(),
+            visible: true,
+        };
+
+        if self.curve_rendering {
+            element.build_curves()?;
+        }
+
+        self.elements.push(element);
+
+        Ok(())
+    }
+
+    /// Draw fills as quadratic curves on the GPU rather than as flattened triangles,
+    /// so their edges stay smooth and anti-aliased however much the texture is scaled.
+    /// Strokes and images are drawn the same either way.
+    ///
+    /// Overlapping curve triangles of thin, sharply bent shapes can show seams where
+    /// the modulate is translucent.
+    pub fn set_curve_rendering(&mut self, enabled: bool) -> Result<()> {
+        if enabled {
+            for element in &mut self.elements {
+                element.build_curves()?;
+            }
+        }
+
+        self.curve_rendering = enabled;
+
+        Ok(())
+    }
+
+    pub fn is_curve_rendering(&self) -> bool {
+        self.curve_rendering
+    }
+
+    /// Remove the elements with an ID. Returns false if there are none.
+    pub fn remove_element(&mut self, id: &str) -> bool {
+        let mut new_indices = vec![];
+        let mut kept = 0;
+        for element in &self.elements {
+            if element.id == id {
+                new_indices.push(None);
+            } else {
+                new_indices.push(Some(kept));
+                kept += 1;
+            }
+        }
+
+        if kept == self.elements.len() {
+            return false;
+        }
+
+        self.elements.retain(|e| e.id != id);
+
+        self.pending_images
+            .retain_mut(|pending| match new_indices[pending.element] {
+                Some(index) => {
+                    pending.element = index;
+                    true
+                }
+                None => false,
+            });
+
+        true
+    }
+
+    /// IDs of the elements, bottom to top. Elements without one are left out,
+    /// and shapes of a group with an ID share it.
+    pub fn get_element_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = vec![];
+        for element in &self.elements {
+            if !element.id.is_empty() && !ids.contains(&element.id.as_str()) {
+                ids.push(&element.id);
+            }
+        }
+        ids
+    }
+
+    /// Multiply the colors of the element by `modulate`. Alpha is ignored.
+    /// Returns false if there's no element with the ID.
+    pub fn set_element_modulate(&mut self, id: &str, modulate: ColorU) -> bool {
+        self.for_each_element(id, |e| e.modulate = modulate)
+    }
+
+    pub fn get_element_modulate(&self, id: &str) -> Option<ColorU> {
+        self.elements
+            .iter()
+            .find(|e| e.id == id)
+            .map(|e| e.modulate)
+    }
+
+    /// Hidden elements are neither drawn nor hit.
+    /// Returns false if there's no element with the ID.
+    pub fn set_element_visible(&mut self, id: &str, visible: bool) -> bool {
+        self.for_each_element(id, |e| e.visible = visible)
+    }
+
+    pub fn is_element_visible(&self, id: &str) -> Option<bool> {
+        self.elements.iter().find(|e| e.id == id).map(|e| e.visible)
+    }
+
+    fn for_each_element(&mut self, id: &str, f: impl Fn(&mut VectorElement)) -> bool {
+        let mut found = false;
+        for element in self.elements.iter_mut().filter(|e| e.id == id) {
+            f(element);
+            found = true;
+        }
+        found
+    }
+
+    /// ID of the topmost visible element under a point in SVG canvas space.
+    /// Elements without an ID are ignored, so unnamed decorations don't block clicks.
+    pub fn hit_test(&self, position: Vector2<f32>) -> Option<&str> {
+        let p = point(position.x, position.y);
+
+        self.elements
+            .iter()
+            .rev()
+            .filter(|e| e.visible && !e.id.is_empty())
+            .filter(|e| e.clips.iter().all(|c| self.clips[*c].contains_point(p)))
+            .find(|e| e.contains_point(p))
+            .map(|e| e.id.as_str())
+    }
+
+    /// Add the visible elements to the 2D curves, transformed from SVG canvas space,
+    /// with clip commands around the clipped ones. Without curve rendering, all triangles
+    /// are solid.
+    pub(crate) fn draw(&self, transform: &Transform2d, draw_cmds: &mut DrawCommands) {
+        let mut clips: &[usize] = &[];
+        let mut batch: Option<ExtractedCurve2d> = None;
+
+        for element in &self.elements {
+            if !element.visible || (element.image && element.texture.is_none()) {
+                continue;
+            }
+
+            let same_clips = element.clips == clips;
+
+            // Each texture and clip needs its own draw.
+            let compatible = batch
+                .as_ref()
+                .is_some_and(|b| same_clips && b.texture_id == element.texture);
+            if !compatible {
+                if let Some(batch) = batch.take() {
+                    draw_cmds.extracted.curves_2d.push(batch);
+                }
+            }
+
+            if !same_clips {
+                let common = clips
+                    .iter()
+                    .zip(&element.clips)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                for _ in common..clips.len() {
+                    draw_cmds.pop_clip();
+                }
+                for clip in &element.clips[common..] {
+                    let triangles = self.clips[*clip]
+                        .triangles
+                        .iter()
+                        .map(|p| transform.transform_point(p).into())
+                        .collect();
+
+                    draw_cmds.push_clip(ExtractedClip {
+                        transform: Transform2d::default(),
+                        size: Vector2::zero(),
+                        corner_radius: 0.0,
+                        view_size: draw_cmds.view_info.view_size,
+                        triangles,
+                    });
+                }
+
+                clips = &element.clips;
+            }
+
+            let batch = batch.get_or_insert_with(|| ExtractedCurve2d {
+                vertices: vec![],
+                indices: vec![],
+                texture_id: element.texture,
+            });
+
+            let start = batch.vertices.len() as u32;
+            let modulate = element.modulate.to_vec3();
+
+            let mut add_vertex = |v: Vertex2dCurve| {
+                let position = transform.transform_point(v.position);
+
+                batch.vertices.push(Vertex2dCurve {
+                    position: position.into(),
+                    color: [
+                        v.color[0] * modulate.x,
+                        v.color[1] * modulate.y,
+                        v.color[2] * modulate.z,
+                    ],
+                    ..v
+                });
+            };
+
+            let curves = element.curves.as_ref().filter(|_| self.curve_rendering);
+            let indices = match curves {
+                Some(curves) => {
+                    curves.vertices.iter().copied().for_each(&mut add_vertex);
+                    &curves.indices
+                }
+                None => {
+                    element
+                        .geometry
+                        .vertices
+                        .iter()
+                        .map(Vertex2dCurve::solid)
+                        .for_each(&mut add_vertex);
+                    &element.geometry.indices
+                }
+            };
+
+            batch.indices.extend(indices.iter().map(|i| start + i));
+        }
+
+        if let Some(batch) = batch {
+            draw_cmds.extracted.curves_2d.push(batch);
+        }
+
+        for _ in clips {
+            draw_cmds.pop_clip();
+        }
+    }
+}
+
+/// Transform into the coordinate system of clip path or mask units.
+/// None for bounding box units if the box is empty, when nothing would show.
+fn units_transform(
+    units: usvg::Units,
+    transform: usvg::Transform,
+    bounding_box: Option<usvg::Rect>,
+) -> Option<usvg::Transform> {
+    match units {
+        usvg::Units::UserSpaceOnUse => Some(transform),
+        usvg::Units::ObjectBoundingBox => {
+            let bounding_box = bounding_box?.to_non_zero_rect()?;
+            Some(transform.pre_concat(usvg::Transform::from_bbox(bounding_box)))
+        }
+    }
+}
+
+/// Paths of a clip path or mask, whatever their paint.
+fn collect_paths(
+    group: &usvg::Group,
+    parent_transform: usvg::Transform,
+    paths: &mut Vec<(Path, FillRule)>,
+) {
+    let transform = parent_transform.pre_concat(group.transform);
+
+    for child in &group.children {
+        match child {
+            usvg::Node::Group(group) => collect_paths(group, transform, paths),
+            usvg::Node::Path(path) => {
+                if path.visibility != usvg::Visibility::Visible {
+                    continue;
+                }
+
+                // Clip rules are stored as fill rules.
+                let fill_rule = path
+                    .fill
+                    .as_ref()
+                    .map_or(FillRule::NonZero, |fill| convert_fill_rule(fill.rule));
+
+                paths.push((convert_path(path, transform), fill_rule));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn convert_path(path: &usvg::Path, transform: usvg::Transform) -> Path {
+    convert_data(&path.data, transform)
+}
+
+fn convert_data(data: &usvg::tiny_skia_path::Path, transform: usvg::Transform) -> Path {
+    let map = |mut p: usvg::tiny_skia_path::Point| {
+        transform.map_point(&mut p);
+        point(p.x, p.y)
+    };
+
+    let mut builder = Path::builder();
+    let mut open = false;
+
+    for segment in data.segments() {
+        match segment {
+            usvg::tiny_skia_path::PathSegment::MoveTo(p) => {
+                if open {
+                    builder.end(false);
+                }
+                builder.begin(map(p));
+                open = true;
+            }
+            usvg::tiny_skia_path::PathSegment::LineTo(p) => {
+                builder.line_to(map(p));
+            }
+            usvg::tiny_skia_path::PathSegment::QuadTo(p1, p) => {
+                builder.quadratic_bezier_to(map(p1), map(p));
+            }
+            usvg::tiny_skia_path::PathSegment::CubicTo(p1, p2, p) => {
+                builder.cubic_bezier_to(map(p1), map(p2), map(p));
+            }
+            usvg::tiny_skia_path::PathSegment::Close => {
+                builder.end(true);
+                open = false;
+            }
+        }
+    }
+
+    if open {
+        builder.end(false);
+    }
+
+    builder.build()
+}
+
+fn tessellate_fill(
+    path: &Path,
+    fill_rule: FillRule,
+    color: [f32; 3],
+    geometry: &mut VertexBuffers<Vertex2d, u32>,
+) -> Result<()> {
+    FillTessellator::new()
+        .tessellate_path(
+            path,
+            &FillOptions::tolerance(TOLERANCE).with_fill_rule(fill_rule),
+            &mut BuffersBuilder::new(geometry, |vertex: FillVertex| Vertex2d {
+                position: vertex.position().to_array(),
+                uv: [0.0, 0.0],
+                color,
+            }),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to tessellate vector fill: {:?}", e))?;
+
+    Ok(())
+}
+
+/// Split a fill into solid triangles for its inside, and one triangle per quadratic curve
+/// for the curve pipeline to cut along the curve. Cubic curves are approximated by
+/// quadratic ones.
+fn tessellate_curve_fill(
+    path: &Path,
+    fill_rule: FillRule,
+    color: [f32; 3],
+    geometry: &mut VertexBuffers<Vertex2dCurve, u32>,
+) -> Result<()> {
+    let path = to_quadratic(path);
+
+    // The outline with the curves replaced by straight lines, around the curve triangles.
+    let mut inside = Path::builder();
+
+    for event in path.iter() {
+        match event {
+            PathEvent::Begin { at } => {
+                inside.begin(at);
+            }
+            PathEvent::Line { to, .. } => {
+                inside.line_to(to);
+            }
+            PathEvent::Quadratic { from, ctrl, to } => {
+                // Control points on the chord make a straight line.
+                if (ctrl - from).cross(to - from).abs() < 1e-6 {
+                    inside.line_to(to);
+                    continue;
+                }
+
+                // Whether the fill is between the chord and the curve, or on the side of the
+                // control point. The chord side is kept with 1 and the other side with -1.
+                let apex = QuadraticBezierSegment { from, ctrl, to }.sample(0.5);
+                let chord_side = from.lerp(to, 0.5).lerp(apex, 0.5);
+
+                let side = if hit_test_path(&chord_side, path.iter(), fill_rule, TOLERANCE * 0.1) {
+                    inside.line_to(to);
+                    1.0
+                } else {
+                    inside.line_to(ctrl);
+                    inside.line_to(to);
+                    -1.0
+                };
+
+                let start = geometry.vertices.len() as u32;
+                for (p, u, v) in [(from, 0.0, 0.0), (ctrl, 0.5, 0.0), (to, 1.0, 1.0)] {
+                    geometry.vertices.push(Vertex2dCurve {
+                        position: p.to_array(),
+                        uv: [0.0, 0.0],
+                        color,
+                        curve: [u, v, side],
+                    });
+                }
+                geometry.indices.extend([start, start + 1, start + 2]);
+            }
+            PathEvent::Cubic { to, .. } => {
+                // Not left by to_quadratic.
+                inside.line_to(to);
+            }
+            PathEvent::End { close, .. } => {
+                inside.end(close);
+            }
+        }
+    }
+
+    FillTessellator::new()
+        .tessellate_path(
+            &inside.build(),
+            &FillOptions::tolerance(TOLERANCE).with_fill_rule(fill_rule),
+            &mut BuffersBuilder::new(geometry, |vertex: FillVertex| Vertex2dCurve {
+                position: vertex.position().to_array(),
+                uv: [0.0, 0.0],
+                color,
+                curve: [0.0; 3],
+            }),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to tessellate vector fill: {:?}", e))?;
+
+    Ok(())
+}
+
+/// The path with its cubic curves split into quadratic ones.
+fn to_quadratic(path: &Path) -> Path {
+    let mut builder = Path::builder();
+
+    for event in path.iter() {
+        match event {
+            PathEvent::Begin { at } => {
+                builder.begin(at);
+            }
+            PathEvent::Line { to, .. } => {
+                builder.line_to(to);
+            }
+            PathEvent::Quadratic { ctrl, to, .. } => {
+                builder.quadratic_bezier_to(ctrl, to);
+            }
+            PathEvent::Cubic {
+                from,
+                ctrl1,
+                ctrl2,
+                to,
+            } => {
+                let cubic = CubicBezierSegment {
+                    from,
+                    ctrl1,
+                    ctrl2,
+                    to,
+                };
+                cubic.for_each_quadratic_bezier(TOLERANCE * 0.1, &mut |quadratic| {
+                    builder.quadratic_bezier_to(quadratic.ctrl, quadratic.to);
+                });
+            }
+            PathEvent::End { close, .. } => {
+                builder.end(close);
+            }
+        }
+    }
+
+    builder.build()
+}
+
+fn convert_fill_rule(rule: usvg::FillRule) -> FillRule {
+    match rule {
+        usvg::FillRule::NonZero => FillRule::NonZero,
+        usvg::FillRule::EvenOdd => FillRule::EvenOdd,
+    }
+}
+
+fn paint_color(paint: &usvg::Paint) -> Option<[f32; 3]> {
+    let color = match paint {
+        usvg::Paint::Color(color) => *color,
+        usvg::Paint::LinearGradient(gradient) => gradient.stops.first()?.color,
+        usvg::Paint::RadialGradient(gradient) => gradient.stops.first()?.color,
+        usvg::Paint::Pattern(_) => return None,
+    };
+
+    Some(
+        ColorU::new(color.red, color.green, color.blue, 255)
+            .to_vec3()
+            .into(),
+    )
+}
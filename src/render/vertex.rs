@@ -3,6 +3,112 @@ pub trait VertexBuffer {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
 
+/// A vertex type that meshes can be built from, see [`Mesh::from_vertices`](crate::render::Mesh::from_vertices).
+pub trait CustomVertex: bytemuck::Pod {
+    /// Declare it with [`VertexLayoutBuilder`].
+    fn layout() -> VertexLayout;
+
+    /// Kept on the CPU for bounds, collision and picking.
+    fn position(&self) -> [f32; 3];
+}
+
+/// An owned vertex buffer layout. Part of pipeline cache keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub array_stride: wgpu::BufferAddress,
+    pub step_mode: wgpu::VertexStepMode,
+    pub attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl VertexLayout {
+    pub fn as_buffer_layout(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+
+    pub fn has_location(&self, location: u32) -> bool {
+        self.attributes
+            .iter()
+            .any(|a| a.shader_location == location)
+    }
+}
+
+/// Declares the attributes of a vertex struct in field order.
+///
+/// ```ignore
+/// VertexLayoutBuilder::new::<MyVertex>()
+///     .attribute(0, wgpu::VertexFormat::Float32x3) // position
+///     .skip(4) // unused padding
+///     .attribute(2, wgpu::VertexFormat::Float32x3) // normal
+///     .build()
+/// ```
+pub struct VertexLayoutBuilder {
+    layout: VertexLayout,
+    offset: wgpu::BufferAddress,
+}
+
+impl VertexLayoutBuilder {
+    /// The stride is the size of `V`.
+    pub fn new<V>() -> Self {
+        Self {
+            layout: VertexLayout {
+                array_stride: std::mem::size_of::<V>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: vec![],
+            },
+            offset: 0,
+        }
+    }
+
+    /// Advance per instance rather than per vertex.
+    pub fn instanced(mut self) -> Self {
+        self.layout.step_mode = wgpu::VertexStepMode::Instance;
+        self
+    }
+
+    /// Add an attribute right after the previous one.
+    pub fn attribute(mut self, shader_location: u32, format: wgpu::VertexFormat) -> Self {
+        self.layout.attributes.push(wgpu::VertexAttribute {
+            format,
+            offset: self.offset,
+            shader_location,
+        });
+        self.offset += format.size();
+        self
+    }
+
+    /// Skip bytes not read by the shader.
+    pub fn skip(mut self, bytes: wgpu::BufferAddress) -> Self {
+        self.offset += bytes;
+        self
+    }
+
+    /// Panics if the attributes don't fit the struct or a location is used twice.
+    pub fn build(self) -> VertexLayout {
+        assert!(
+            self.offset <= self.layout.array_stride,
+            "Vertex attributes take {} bytes, but the vertex is {} bytes",
+            self.offset,
+            self.layout.array_stride
+        );
+
+        for (i, a) in self.layout.attributes.iter().enumerate() {
+            assert!(
+                !self.layout.attributes[..i]
+                    .iter()
+                    .any(|b| b.shader_location == a.shader_location),
+                "Vertex attribute location {} is used twice",
+                a.shader_location
+            );
+        }
+
+        self.layout
+    }
+}
+
 /// 3D vertex data sent to GPU.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -56,6 +162,52 @@ impl VertexBuffer for Vertex3d {
     }
 }
 
+impl CustomVertex for Vertex3d {
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new::<Vertex3d>()
+            .attribute(0, wgpu::VertexFormat::Float32x3)
+            .attribute(1, wgpu::VertexFormat::Float32x2)
+            .attribute(2, wgpu::VertexFormat::Float32x3)
+            .attribute(3, wgpu::VertexFormat::Float32x3)
+            .attribute(4, wgpu::VertexFormat::Float32x3)
+            .build()
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
+/// Meshes whose layout has an attribute here get it multiplied with their color,
+/// as a `Float32x4` in linear RGBA. Comes after the instance attributes of the mesh shader.
+pub const VERTEX_COLOR_LOCATION: u32 = 12;
+
+/// [`Vertex3d`] with a vertex color, for meshes that have one.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct Vertex3dColored {
+    pub(crate) vertex: Vertex3d,
+    /// Linear RGBA.
+    pub(crate) color: [f32; 4],
+}
+
+impl CustomVertex for Vertex3dColored {
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new::<Vertex3dColored>()
+            .attribute(0, wgpu::VertexFormat::Float32x3)
+            .attribute(1, wgpu::VertexFormat::Float32x2)
+            .attribute(2, wgpu::VertexFormat::Float32x3)
+            .attribute(3, wgpu::VertexFormat::Float32x3)
+            .attribute(4, wgpu::VertexFormat::Float32x3)
+            .attribute(VERTEX_COLOR_LOCATION, wgpu::VertexFormat::Float32x4)
+            .build()
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.vertex.position
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct Vertex2d {
@@ -93,6 +245,59 @@ impl VertexBuffer for Vertex2d {
     }
 }
 
+impl CustomVertex for Vertex2d {
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new::<Vertex2d>()
+            .attribute(0, wgpu::VertexFormat::Float32x2)
+            .attribute(1, wgpu::VertexFormat::Float32x2)
+            .attribute(2, wgpu::VertexFormat::Float32x3)
+            .build()
+    }
+
+    fn position(&self) -> [f32; 3] {
+        [self.position[0], self.position[1], 0.0]
+    }
+}
+
+/// A 2D vertex with Loop-Blinn curve coordinates, see [`crate::render::curve2d`].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct Vertex2dCurve {
+    pub(crate) position: [f32; 2],
+    pub(crate) uv: [f32; 2],
+    pub(crate) color: [f32; 3],
+    /// (u, v, side). Side 0 is solid, 1 keeps u² < v and -1 keeps u² > v.
+    pub(crate) curve: [f32; 3],
+}
+
+impl Vertex2dCurve {
+    pub(crate) fn solid(vertex: &Vertex2d) -> Self {
+        Self {
+            position: vertex.position,
+            uv: vertex.uv,
+            color: vertex.color,
+            curve: [0.0; 3],
+        }
+    }
+}
+
+impl VertexBuffer for Vertex2dCurve {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x3,
+            3 => Float32x3,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex2dCurve>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct VertexSky {
@@ -115,3 +320,15 @@ impl VertexBuffer for VertexSky {
         }
     }
 }
+
+impl CustomVertex for VertexSky {
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new::<VertexSky>()
+            .attribute(0, wgpu::VertexFormat::Float32x3)
+            .build()
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
@@ -0,0 +1,521 @@
+use crate::asset::TextureImportSettings;
+use crate::math::color::ColorU;
+use crate::math::transform::Transform3d;
+use crate::render::{RenderServer, Texture, TextureCache, TextureId, UniformBuffer};
+use cgmath::{InnerSpace, Matrix4, Vector2, Vector3};
+use image::{DynamicImage, RgbaImage};
+use std::f32::consts::TAU;
+use std::mem;
+use wgpu::{BufferAddress, SamplerBindingType};
+
+/// How a water surface reflects its surroundings.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum WaterReflection {
+    /// Only reflect the sky color.
+    None = 0,
+    /// March the reflected ray against the depth buffer and reuse the scene color.
+    /// Falls back to the sky color where the ray leaves the screen.
+    #[default]
+    ScreenSpace = 1,
+}
+
+/// Minimal data for rendering a water plane, see `WaterPlane`.
+#[derive(Debug, Copy, Clone)]
+pub struct ExtractedWater {
+    pub(crate) transform: Transform3d,
+    pub(crate) size: Vector2<f32>,
+    pub(crate) shallow_color: ColorU,
+    pub(crate) deep_color: ColorU,
+    pub(crate) absorption_depth: f32,
+    pub(crate) normal_map: Option<TextureId>,
+    pub(crate) wave_scale: f32,
+    pub(crate) normal_strength: f32,
+    pub(crate) flow: Vector2<f32>,
+    pub(crate) flow_map: Option<TextureId>,
+    pub(crate) flow_map_speed: f32,
+    pub(crate) refraction_strength: f32,
+    pub(crate) reflection: WaterReflection,
+    pub(crate) sky_color: ColorU,
+    pub(crate) foam_color: ColorU,
+    pub(crate) foam_distance: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaterParamsUniform {
+    model_matrix: [[f32; 4]; 4],
+    shallow_color: [f32; 4],
+    deep_color: [f32; 4],
+    sky_color: [f32; 4],
+    foam_color: [f32; 4],
+    size: [f32; 2],
+    flow: [f32; 2],
+    wave_scale: f32,
+    normal_strength: f32,
+    flow_map_speed: f32,
+    refraction_strength: f32,
+    absorption_depth: f32,
+    foam_distance: f32,
+    reflection: u32,
+    _pad: f32,
+}
+
+/// GPU resources of one water plane drawn this frame.
+struct WaterInstance {
+    params_buffer: UniformBuffer,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+pub(crate) struct WaterRenderResources {
+    /// Copy of the scene color taken before the water is drawn, since a pass can't
+    /// sample its own target. Uses `RenderServer::scene_format`.
+    refraction_texture: TextureId,
+
+    /// Used for planes without a normal map.
+    default_normal_map: TextureId,
+    /// Used for planes without a flow map. Doesn't move anything.
+    neutral_flow_map: TextureId,
+
+    params_bind_group_layout: wgpu::BindGroupLayout,
+    scene_bind_group_layout: wgpu::BindGroupLayout,
+    /// Rebuilt every frame, the scene textures may be recreated at any time.
+    scene_bind_group: Option<wgpu::BindGroup>,
+    scene_sampler: wgpu::Sampler,
+
+    pipeline: wgpu::RenderPipeline,
+
+    /// Reused between frames, one per plane.
+    instances: Vec<WaterInstance>,
+    /// Planes drawn this frame.
+    instance_count: usize,
+}
+
+impl WaterRenderResources {
+    pub(crate) fn new(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let device = &render_server.device;
+
+        let config = &render_server.surface_config;
+        let refraction_texture = Self::create_refraction_texture(
+            render_server,
+            texture_cache,
+            (config.width, config.height),
+        );
+
+        let data_settings = TextureImportSettings {
+            srgb: false,
+            premultiply_alpha: false,
+            mipmaps: true,
+            ..Default::default()
+        };
+
+        let default_normal_map = Texture::from_image_with_settings(
+            device,
+            &render_server.queue,
+            texture_cache,
+            &DynamicImage::ImageRgba8(ripple_normal_map(64)),
+            Some("water default normal map"),
+            &data_settings,
+        )
+        .unwrap();
+
+        let neutral_flow_map = Texture::from_image_with_settings(
+            device,
+            &render_server.queue,
+            texture_cache,
+            &DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 0, 255]))),
+            Some("water neutral flow map"),
+            &data_settings,
+        )
+        .unwrap();
+
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+            },
+            count: None,
+        };
+
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let params_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture_entry(1, true),
+                    sampler_entry(2),
+                    texture_entry(3, true),
+                    sampler_entry(4),
+                ],
+                label: Some("water params bind group layout"),
+            });
+
+        let scene_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    texture_entry(0, true),
+                    // Read as floats, since GL can't load from depth textures.
+                    texture_entry(1, false),
+                    sampler_entry(2),
+                ],
+                label: Some("water scene bind group layout"),
+            });
+
+        let scene_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("water pipeline layout"),
+            bind_group_layouts: &[
+                camera_bind_group_layout,
+                &params_bind_group_layout,
+                &scene_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("water shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/water.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("water pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_server.scene_format(),
+                    // The refracted scene is already part of the output.
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // The water samples the depth buffer, so it can't be attached.
+            // It tests against it in the shader instead.
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            refraction_texture,
+            default_normal_map,
+            neutral_flow_map,
+            params_bind_group_layout,
+            scene_bind_group_layout,
+            scene_bind_group: None,
+            scene_sampler,
+            pipeline,
+            instances: vec![],
+            instance_count: 0,
+        }
+    }
+
+    fn create_refraction_texture(
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        size: (u32, u32),
+    ) -> TextureId {
+        let mut config = render_server.surface_config.clone();
+        (config.width, config.height) = size;
+        config.format = render_server.scene_format();
+
+        Texture::create_render_texture(
+            &render_server.device,
+            texture_cache,
+            &config,
+            Some("water refraction texture"),
+        )
+    }
+
+    /// Offscreen textures have to follow the size the 3D scene is drawn at.
+    pub(crate) fn recreate_textures(
+        &mut self,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+        size: (u32, u32),
+    ) {
+        texture_cache.remove(self.refraction_texture);
+
+        self.refraction_texture =
+            Self::create_refraction_texture(render_server, texture_cache, size);
+
+        self.scene_bind_group = None;
+    }
+
+    /// Whether the scene has to be drawn offscreen for the water to refract it.
+    pub(crate) fn has_water(&self) -> bool {
+        self.instance_count > 0
+    }
+}
+
+/// Tileable ripples made of a few sine waves, as a tangent space normal map.
+fn ripple_normal_map(size: u32) -> RgbaImage {
+    // Whole numbers of periods per tile, with amplitudes.
+    let waves = [
+        (3.0, 1.0, 0.05),
+        (-2.0, 5.0, 0.03),
+        (7.0, -4.0, 0.015),
+        (1.0, 9.0, 0.01),
+    ];
+
+    RgbaImage::from_fn(size, size, |x, y| {
+        let u = x as f32 / size as f32;
+        let v = y as f32 / size as f32;
+
+        let mut slope = Vector2::new(0.0, 0.0);
+        for (kx, ky, amplitude) in waves {
+            let phase = TAU * (kx * u + ky * v);
+            slope += Vector2::new(kx, ky) * (TAU * amplitude * phase.cos());
+        }
+
+        let normal = Vector3::new(-slope.x, -slope.y, 1.0).normalize();
+        let encode = |n: f32| ((n * 0.5 + 0.5) * 255.0).round() as u8;
+
+        image::Rgba([encode(normal.x), encode(normal.y), encode(normal.z), 255])
+    })
+}
+
+pub(crate) fn prepare_water(
+    waters: &[ExtractedWater],
+    camera_position: Option<Vector3<f32>>,
+    render_resources: &mut WaterRenderResources,
+    texture_cache: &TextureCache,
+    depth_texture: TextureId,
+    render_server: &RenderServer,
+) {
+    render_resources.instance_count = 0;
+
+    // Water needs a 3D camera to look through.
+    let Some(camera_position) = camera_position else {
+        return;
+    };
+    if waters.is_empty() {
+        return;
+    }
+
+    let device = &render_server.device;
+
+    // Far to near, so that closer planes cover the ones behind.
+    let mut sorted = waters.to_vec();
+    sorted.sort_by(|a, b| {
+        let da = (a.transform.position - camera_position).magnitude2();
+        let db = (b.transform.position - camera_position).magnitude2();
+        db.partial_cmp(&da).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    while render_resources.instances.len() < sorted.len() {
+        let params_buffer = render_server.create_uniform_buffer(
+            "water params buffer",
+            mem::size_of::<WaterParamsUniform>() as BufferAddress,
+        );
+
+        render_resources.instances.push(WaterInstance {
+            params_buffer,
+            bind_group: None,
+        });
+    }
+
+    for (w, instance) in sorted.iter().zip(render_resources.instances.iter_mut()) {
+        let transform = &w.transform;
+        let model = Matrix4::from_translation(transform.position)
+            * Matrix4::from(transform.rotation)
+            * Matrix4::from_nonuniform_scale(
+                transform.scale.x,
+                transform.scale.y,
+                transform.scale.z,
+            );
+
+        let uniform = WaterParamsUniform {
+            model_matrix: model.into(),
+            shallow_color: w.shallow_color.to_f32().to_array(),
+            deep_color: w.deep_color.to_f32().to_array(),
+            sky_color: w.sky_color.to_f32().to_array(),
+            foam_color: w.foam_color.to_f32().to_array(),
+            size: w.size.into(),
+            flow: w.flow.into(),
+            wave_scale: w.wave_scale.max(0.001),
+            normal_strength: w.normal_strength,
+            flow_map_speed: if w.flow_map.is_some() {
+                w.flow_map_speed
+            } else {
+                0.0
+            },
+            refraction_strength: w.refraction_strength,
+            absorption_depth: w.absorption_depth.max(0.001),
+            foam_distance: w.foam_distance,
+            reflection: w.reflection as u32,
+            _pad: 0.0,
+        };
+
+        render_server
+            .queue
+            .write_buffer(&instance.params_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let normal_map = texture_cache
+            .get(w.normal_map.unwrap_or(render_resources.default_normal_map))
+            .unwrap();
+        let flow_map = texture_cache
+            .get(w.flow_map.unwrap_or(render_resources.neutral_flow_map))
+            .unwrap();
+
+        // Cheap enough to rebuild every frame for the few planes in a scene.
+        instance.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_resources.params_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: instance.params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&normal_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&normal_map.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&flow_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&flow_map.sampler),
+                },
+            ],
+            label: Some("water params bind group"),
+        }));
+    }
+
+    render_resources.instance_count = sorted.len();
+
+    let refraction = texture_cache
+        .get(render_resources.refraction_texture)
+        .unwrap();
+
+    // The stencil aspect can't be sampled along with depth.
+    let depth_view = texture_cache
+        .get(depth_texture)
+        .unwrap()
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+
+    render_resources.scene_bind_group =
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_resources.scene_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&refraction.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&render_resources.scene_sampler),
+                },
+            ],
+            label: Some("water scene bind group"),
+        }));
+}
+
+/// Snapshot the scene color and draw the water over it, without a depth attachment
+/// so that the water can sample the depth buffer.
+pub(crate) fn render_water(
+    render_resources: &WaterRenderResources,
+    texture_cache: &TextureCache,
+    scene_color_texture: TextureId,
+    camera_bind_group: &wgpu::BindGroup,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    if !render_resources.has_water() {
+        return;
+    }
+
+    let scene_color = texture_cache.get(scene_color_texture).unwrap();
+    let refraction = texture_cache
+        .get(render_resources.refraction_texture)
+        .unwrap();
+
+    encoder.copy_texture_to_texture(
+        scene_color.texture.as_image_copy(),
+        refraction.texture.as_image_copy(),
+        wgpu::Extent3d {
+            width: scene_color.size.0,
+            height: scene_color.size.1,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    // GL keeps pass labels next to push constants, a length that isn't a multiple
+    // of 4 would misalign them.
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("water plane pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: &scene_color.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(&render_resources.pipeline);
+    render_pass.set_bind_group(0, camera_bind_group, &[0]);
+    render_pass.set_bind_group(2, render_resources.scene_bind_group.as_ref().unwrap(), &[]);
+
+    for instance in &render_resources.instances[..render_resources.instance_count] {
+        render_pass.set_bind_group(1, instance.bind_group.as_ref().unwrap(), &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
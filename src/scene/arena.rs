@@ -0,0 +1,411 @@
+use crate::scene::AsNode;
+use std::ops::{Index, IndexMut};
+
+/// A handle to a node in the scene tree.
+///
+/// A slot freed by removing a node is reused by later nodes, but with a new generation,
+/// so an ID kept around after its node was removed never reaches the new node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+impl NodeId {
+    /// Slot in the arena. Unique among live nodes, but reused after removal.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+struct Slot {
+    generation: u32,
+    /// None while the slot is free.
+    node: Option<Box<dyn AsNode>>,
+
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+/// Storage of the scene tree: nodes and the links between them.
+#[derive(Default)]
+pub struct NodeArena {
+    slots: Vec<Slot>,
+    /// Indices of free slots.
+    free: Vec<u32>,
+}
+
+impl NodeArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a node, not yet attached to any parent.
+    pub fn new_node(&mut self, node: Box<dyn AsNode>) -> NodeId {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.node = Some(node);
+
+            return NodeId {
+                index,
+                generation: slot.generation,
+            };
+        }
+
+        self.slots.push(Slot {
+            generation: 0,
+            node: Some(node),
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+        });
+
+        NodeId {
+            index: self.slots.len() as u32 - 1,
+            generation: 0,
+        }
+    }
+
+    /// Number of live nodes.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// False once the node is removed.
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.slot(id).is_some()
+    }
+
+    fn slot(&self, id: NodeId) -> Option<&Slot> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation && slot.node.is_some())
+    }
+
+    fn slot_mut(&mut self, id: NodeId) -> Option<&mut Slot> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation && slot.node.is_some())
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&(dyn AsNode + 'static)> {
+        self.slot(id)?.node.as_deref()
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut (dyn AsNode + 'static)> {
+        self.slot_mut(id)?.node.as_deref_mut()
+    }
+
+    /// Borrow several nodes mutably at once. None if any of them is removed, or if an
+    /// ID is given twice.
+    /// ```ignore
+    /// let [a, b] = world.arena.get_many_mut([a, b]).unwrap();
+    /// ```
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        ids: [NodeId; N],
+    ) -> Option<[&mut (dyn AsNode + 'static); N]> {
+        if !ids.iter().all(|id| self.contains(*id)) {
+            return None;
+        }
+
+        let slots = self
+            .slots
+            .get_disjoint_mut(ids.map(|id| id.index as usize))
+            .ok()?;
+
+        Some(slots.map(|slot| slot.node.as_deref_mut().unwrap()))
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.first_child
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.slot(id)?.next_sibling
+    }
+
+    /// Children of a node, in order.
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.first_child(id), |child| self.next_sibling(*child))
+    }
+
+    /// The node itself and everything below it, parents before their children.
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = if self.contains(id) { vec![id] } else { vec![] };
+
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+
+            // Pushed in reverse, so that the first child comes out first.
+            let first = stack.len();
+            stack.extend(self.children(id));
+            stack[first..].reverse();
+
+            Some(id)
+        })
+    }
+
+    /// Make `child` the last child of `parent`, detaching it from its old parent.
+    ///
+    /// Panics if either node is removed, or if `child` is `parent` or one of its ancestors.
+    pub fn append(&mut self, parent: NodeId, child: NodeId) {
+        assert!(
+            self.contains(parent) && self.contains(child),
+            "Appending a removed node"
+        );
+        assert!(
+            std::iter::successors(Some(parent), |id| self.parent(*id)).all(|id| id != child),
+            "Appending a node to itself or to one of its children"
+        );
+
+        self.detach(child);
+
+        let previous_sibling = self.slots[parent.index as usize].last_child;
+        match previous_sibling {
+            Some(sibling) => self.slots[sibling.index as usize].next_sibling = Some(child),
+            None => self.slots[parent.index as usize].first_child = Some(child),
+        }
+        self.slots[parent.index as usize].last_child = Some(child);
+
+        let slot = &mut self.slots[child.index as usize];
+        slot.parent = Some(parent);
+        slot.previous_sibling = previous_sibling;
+    }
+
+    /// Unlink a node from its parent and siblings. Its children stay with it.
+    fn detach(&mut self, id: NodeId) {
+        let slot = &mut self.slots[id.index as usize];
+        let parent = slot.parent.take();
+        let previous_sibling = slot.previous_sibling.take();
+        let next_sibling = slot.next_sibling.take();
+
+        match previous_sibling {
+            Some(sibling) => self.slots[sibling.index as usize].next_sibling = next_sibling,
+            None => {
+                if let Some(parent) = parent {
+                    self.slots[parent.index as usize].first_child = next_sibling;
+                }
+            }
+        }
+
+        match next_sibling {
+            Some(sibling) => self.slots[sibling.index as usize].previous_sibling = previous_sibling,
+            None => {
+                if let Some(parent) = parent {
+                    self.slots[parent.index as usize].last_child = previous_sibling;
+                }
+            }
+        }
+    }
+
+    /// Remove a node and everything below it. Their IDs become invalid.
+    pub fn remove_subtree(&mut self, id: NodeId) {
+        if !self.contains(id) {
+            return;
+        }
+
+        let removed: Vec<NodeId> = self.descendants(id).collect();
+
+        self.detach(id);
+
+        for id in removed {
+            let slot = &mut self.slots[id.index as usize];
+            slot.node = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            slot.parent = None;
+            slot.first_child = None;
+            slot.last_child = None;
+            slot.previous_sibling = None;
+            slot.next_sibling = None;
+
+            self.free.push(id.index);
+        }
+    }
+}
+
+impl Index<NodeId> for NodeArena {
+    type Output = dyn AsNode;
+
+    /// Panics if the node is removed.
+    fn index(&self, id: NodeId) -> &Self::Output {
+        self.get(id).expect("Node was removed")
+    }
+}
+
+impl IndexMut<NodeId> for NodeArena {
+    fn index_mut(&mut self, id: NodeId) -> &mut Self::Output {
+        self.get_mut(id).expect("Node was removed")
+    }
+}
+
+/// Data attached to nodes, kept outside of them so that it can be read and written
+/// without going through the nodes. Entries of removed nodes are never returned.
+///
+/// Visibility and Y sorting live here. Transforms are still fields of the nodes, since
+/// nodes draw themselves from them and `custom_update` callbacks move them through
+/// `&mut Self`. Moving them here changes `AsNode::draw` and the node setters, and is
+/// left for a change of its own.
+pub struct Components<T> {
+    /// By node index, with the generation of the node it belongs to.
+    entries: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self { entries: vec![] }
+    }
+}
+
+impl<T> Components<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        match self.entries.get(id.index as usize)? {
+            Some((generation, value)) if *generation == id.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        match self.entries.get_mut(id.index as usize)? {
+            Some((generation, value)) if *generation == id.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Replaces the value of the node, or one left by a removed node in the same slot.
+    pub fn insert(&mut self, id: NodeId, value: T) {
+        let index = id.index as usize;
+        if index >= self.entries.len() {
+            self.entries.resize_with(index + 1, || None);
+        }
+
+        self.entries[index] = Some((id.generation, value));
+    }
+
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        self.get(id)?;
+
+        self.entries[id.index as usize]
+            .take()
+            .map(|(_, value)| value)
+    }
+
+    /// The value of the node, inserting the default one first if it has none.
+    pub fn get_or_insert_default(&mut self, id: NodeId) -> &mut T
+    where
+        T: Default,
+    {
+        if !self.contains(id) {
+            self.insert(id, T::default());
+        }
+
+        self.get_mut(id).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Control;
+
+    fn node() -> Box<dyn AsNode> {
+        Box::new(Control::default())
+    }
+
+    #[test]
+    fn stale_id_after_slot_reuse() {
+        let mut arena = NodeArena::new();
+        let root = arena.new_node(node());
+        let child = arena.new_node(node());
+        arena.append(root, child);
+
+        arena.remove_subtree(root);
+        assert!(!arena.contains(root));
+        assert!(!arena.contains(child));
+        assert!(arena.is_empty());
+
+        let reused = arena.new_node(node());
+        assert!(reused.index() == root.index() || reused.index() == child.index());
+        assert!(arena.get(root).is_none());
+        assert!(arena.get(child).is_none());
+        assert!(arena.get(reused).is_some());
+
+        let mut components = Components::new();
+        components.insert(child, 1);
+        components.insert(root, 1);
+        assert_eq!(components.get(reused), None);
+    }
+
+    #[test]
+    fn get_many_mut_rejects_duplicate_and_removed() {
+        let mut arena = NodeArena::new();
+        let a = arena.new_node(node());
+        let b = arena.new_node(node());
+
+        assert!(arena.get_many_mut([a, b]).is_some());
+        assert!(arena.get_many_mut([a, a]).is_none());
+
+        arena.remove_subtree(b);
+        assert!(arena.get_many_mut([a, b]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Appending a node to itself or to one of its children")]
+    fn append_rejects_cycle() {
+        let mut arena = NodeArena::new();
+        let root = arena.new_node(node());
+        let child = arena.new_node(node());
+        let grandchild = arena.new_node(node());
+        arena.append(root, child);
+        arena.append(child, grandchild);
+
+        arena.append(grandchild, root);
+    }
+
+    #[test]
+    fn descendants_parents_first_in_child_order() {
+        let mut arena = NodeArena::new();
+        let root = arena.new_node(node());
+        let a = arena.new_node(node());
+        let b = arena.new_node(node());
+        let a1 = arena.new_node(node());
+        let a2 = arena.new_node(node());
+        arena.append(root, a);
+        arena.append(root, b);
+        arena.append(a, a1);
+        arena.append(a, a2);
+
+        let order: Vec<_> = arena.descendants(root).collect();
+        assert_eq!(order, [root, a, a1, a2, b]);
+
+        // Moving a node takes its children along.
+        arena.append(b, a);
+        let order: Vec<_> = arena.descendants(root).collect();
+        assert_eq!(order, [root, b, a, a1, a2]);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::math::noise::perlin1;
+use cgmath::Vector3;
+
+/// Trauma based camera shake. Trauma is added by hits or explosions and decays over time,
+/// the shake amount is trauma squared, so small hits stay subtle.
+#[derive(Debug, Clone)]
+pub struct CameraShake {
+    /// Largest position offset at full trauma, in world units.
+    pub max_offset: f32,
+
+    /// Largest rotation at full trauma, in degrees.
+    pub max_rotation: f32,
+
+    /// How fast the shake changes direction.
+    pub frequency: f32,
+
+    /// Trauma lost per second.
+    pub decay: f32,
+
+    trauma: f32,
+    time: f32,
+}
+
+/// Offset to apply to a camera this frame.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ShakeOffset {
+    pub(crate) translation: Vector3<f32>,
+    /// Rotation around each axis, in degrees.
+    pub(crate) rotation: Vector3<f32>,
+}
+
+impl CameraShake {
+    pub fn new(max_offset: f32, max_rotation: f32) -> Self {
+        Self {
+            max_offset,
+            max_rotation,
+            frequency: 15.0,
+            decay: 1.0,
+            trauma: 0.0,
+            time: 0.0,
+        }
+    }
+
+    /// Add trauma, in [0, 1]. Full trauma is clamped to 1.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn get_trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Stop shaking immediately.
+    pub fn stop(&mut self) {
+        self.trauma = 0.0;
+    }
+
+    pub(crate) fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.decay * dt).max(0.0);
+    }
+
+    pub(crate) fn sample(&self) -> ShakeOffset {
+        let shake = self.trauma * self.trauma;
+
+        // Each channel reads its own noise sequence.
+        let t = self.time * self.frequency;
+        let channel = |i: u32| perlin1(i, t) * shake;
+
+        ShakeOffset {
+            translation: Vector3::new(channel(0), channel(1), channel(2)) * self.max_offset,
+            rotation: Vector3::new(channel(3), channel(4), channel(5)) * self.max_rotation,
+        }
+    }
+}
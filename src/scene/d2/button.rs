@@ -1,102 +1,208 @@
-// use crate::math::transform::Transform2d;
-// use crate::render::atlas::{AtlasMode, DrawAtlas};
-// use crate::render::render_server::RenderServer;
-// use crate::scene::vector_sprite::VectorSprite;
-// use crate::scene::{CameraInfo, Label, NodeType};
-// use crate::window::{InputEvent, InputServer};
-// use crate::{AsNode, Atlas, AtlasInstance, Singletons, TextServer, Texture};
-// use cgmath::{Point2, Vector2, Vector3, Vector4};
-// use image::DynamicImage;
-// use lyon::geom::Transform;
-// use std::any::Any;
-//
-// pub struct Button {
-//     label: Label,
-//
-//     pub transform: Transform2d,
-//
-//     pub(crate) size: Vector2<f32>,
-//
-//     hovered: bool,
-//     pressed: bool,
-//
-//     sprite_v: VectorSprite,
-// }
-//
-// impl Button {
-//     pub fn new(render_server: &RenderServer) -> Button {
-//         let size = Vector2::new(128.0_f32, 128.0);
-//
-//         let mut label = Label::new(render_server);
-//         label.set_text("button".to_string());
-//
-//         Self {
-//             label,
-//             transform: Transform2d::default(),
-//             size,
-//             hovered: false,
-//             pressed: false,
-//             sprite_v: VectorSprite::new(render_server),
-//         }
-//     }
-//
-//     pub fn set_text(&mut self, text: String) {
-//         self.label.set_text(text);
-//     }
-// }
-//
-// impl AsNode for Button {
-//     fn node_type(&self) -> NodeType {
-//         NodeType::Label
-//     }
-//
-//     fn as_any(&self) -> &dyn Any {
-//         self
-//     }
-//
-//     fn as_any_mut(&mut self) -> &mut dyn Any {
-//         self
-//     }
-//
-//     fn ready(&mut self) {}
-//
-//     fn input(&mut self, input_event: &mut InputEvent, input_server: &mut InputServer) {
-//         match input_event {
-//             InputEvent::MouseButton(_) => {}
-//             InputEvent::MouseMotion(args) => {
-//                 self.hovered = false;
-//                 if args.position.0 > self.transform.position.x
-//                     && args.position.0 < (self.transform.position.x + self.size.x)
-//                 {
-//                     if args.position.1 > self.transform.position.y
-//                         && args.position.1 < (self.transform.position.y + self.size.y)
-//                     {
-//                         self.hovered = true;
-//                     }
-//                 }
-//             }
-//             InputEvent::MouseScroll(_) => {}
-//             InputEvent::Key(_) => {}
-//             InputEvent::Invalid => {}
-//         }
-//     }
-//
-//     fn update(&mut self, dt: f32, camera_info: &CameraInfo, singletons: &mut Singletons) {
-//         self.label.transform.position = self.transform.position;
-//         if self.hovered {
-//             self.label.set_text("hovered".to_string());
-//         } else {
-//             self.label.set_text("normal".to_string());
-//         }
-//         self.label.update(dt, camera_info, singletons);
-//     }
-//
-//     fn draw<'a, 'b: 'a>(
-//         &'b self,
-//         render_pass: &mut wgpu::RenderPass<'a>,
-//         camera_info: &'b CameraInfo,
-//         singletons: &'b mut Singletons,
-//     ) {
-//         self.label.draw(render_pass, camera_info, singletons);
-//     }
-// }
+use crate::math::color::ColorU;
+use crate::math::rect::Rect2;
+use crate::render::draw_command::DrawCommands;
+use crate::scene::d2::node_ui::{AsNodeUi, NodeUi};
+use crate::scene::d2::style_box::StyleBoxFlat;
+use crate::scene::{AsNode, NodeType, UiEventKind};
+use crate::window::{InputEvent, InputServer};
+use cgmath::Vector2;
+use std::any::Any;
+
+/// A clickable UI rectangle. Add a `Label` as a child to show text on it.
+pub struct Button {
+    node_ui: NodeUi,
+
+    /// Announced in UI events, so it should match the visible text.
+    pub text: String,
+
+    pub style_normal: StyleBoxFlat,
+    pub style_hovered: StyleBoxFlat,
+    pub style_pressed: StyleBoxFlat,
+
+    /// Ignores the mouse.
+    pub disabled: bool,
+
+    /// Stay pressed when clicked, until clicked again.
+    pub toggle_mode: bool,
+    toggled: bool,
+
+    hovered: bool,
+    /// The mouse went down on the button and hasn't been released yet.
+    held: bool,
+
+    ui_events: Vec<UiEventKind>,
+}
+
+impl Button {
+    pub fn new(text: &str) -> Self {
+        Self {
+            node_ui: NodeUi {
+                size: Vector2::new(128.0, 32.0),
+                ..NodeUi::default()
+            },
+            text: text.to_string(),
+            style_normal: StyleBoxFlat::default(),
+            style_hovered: StyleBoxFlat {
+                bg_color: ColorU::new(178, 178, 178, 255),
+                ..StyleBoxFlat::default()
+            },
+            style_pressed: StyleBoxFlat {
+                bg_color: ColorU::new(115, 115, 115, 255),
+                ..StyleBoxFlat::default()
+            },
+            disabled: false,
+            toggle_mode: false,
+            toggled: false,
+            hovered: false,
+            held: false,
+            ui_events: vec![],
+        }
+    }
+
+    pub fn is_hovered(&self) -> bool {
+        self.hovered
+    }
+
+    /// Held down, or toggled on in toggle mode.
+    pub fn is_pressed(&self) -> bool {
+        self.held || self.toggled
+    }
+
+    pub fn is_toggled(&self) -> bool {
+        self.toggled
+    }
+
+    pub fn set_toggled(&mut self, toggled: bool) {
+        if self.toggled == toggled {
+            return;
+        }
+
+        self.toggled = toggled;
+        self.ui_events.push(UiEventKind::ValueChanged {
+            value: if toggled { "on" } else { "off" }.to_string(),
+        });
+    }
+
+    /// Activate the button as if it was clicked.
+    pub fn press(&mut self) {
+        self.ui_events.push(UiEventKind::Pressed);
+
+        if self.toggle_mode {
+            self.set_toggled(!self.toggled);
+        }
+    }
+
+    fn contains(&self, position: (f32, f32)) -> bool {
+        Rect2::from_position_size(self.node_ui.transform.position, self.node_ui.size)
+            .contains_point(Vector2::new(position.0, position.1))
+    }
+}
+
+impl AsNode for Button {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Button
+    }
+
+    fn input(&mut self, input_event: &mut InputEvent, _input_server: &mut InputServer) {
+        if self.disabled {
+            self.hovered = false;
+            self.held = false;
+            return;
+        }
+
+        match input_event {
+            InputEvent::MouseMotion(motion) => {
+                self.hovered = self.contains(motion.position);
+            }
+            InputEvent::MouseButton(button) => {
+                if button.button != winit::event::MouseButton::Left {
+                    return;
+                }
+
+                if button.pressed {
+                    self.held = self.contains(button.position);
+                } else if self.held {
+                    self.held = false;
+
+                    // Dragging off the button before releasing cancels the click.
+                    if self.contains(button.position) {
+                        self.press();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, draw_commands: &mut DrawCommands) {
+        let style = if self.is_pressed() {
+            &self.style_pressed
+        } else if self.hovered {
+            &self.style_hovered
+        } else {
+            &self.style_normal
+        };
+
+        style.draw(self.node_ui.transform, self.node_ui.size, draw_commands);
+
+        self.node_ui.push_clip(draw_commands);
+    }
+}
+
+impl AsNodeUi for Button {
+    fn get_size(&self) -> Vector2<f32> {
+        self.node_ui.size
+    }
+
+    fn set_size(&mut self, size: Vector2<f32>) {
+        self.node_ui.size = size;
+    }
+
+    fn get_position(&self) -> Vector2<f32> {
+        self.node_ui.transform.position
+    }
+
+    fn set_position(&mut self, position: Vector2<f32>) {
+        self.node_ui.transform.position = position;
+    }
+
+    fn get_rotation(&self) -> f32 {
+        self.node_ui.transform.rotation
+    }
+
+    fn set_rotation(&mut self, rotation: f32) {
+        self.node_ui.transform.rotation = rotation;
+    }
+
+    fn get_clip_contents(&self) -> bool {
+        self.node_ui.clip_contents
+    }
+
+    fn set_clip_contents(&mut self, clip_contents: bool) {
+        self.node_ui.clip_contents = clip_contents;
+    }
+
+    fn get_clip_corner_radius(&self) -> f32 {
+        self.node_ui.clip_corner_radius
+    }
+
+    fn set_clip_corner_radius(&mut self, radius: f32) {
+        self.node_ui.clip_corner_radius = radius;
+    }
+
+    fn get_ui_name(&self) -> String {
+        self.text.clone()
+    }
+
+    fn take_ui_events(&mut self) -> Vec<UiEventKind> {
+        std::mem::take(&mut self.ui_events)
+    }
+}
@@ -1,20 +1,82 @@
 use crate::core::singleton::Singletons;
 use crate::math::transform::Transform2d;
-use crate::render::camera::{CameraType, CameraUniform, OrthographicProjection, Projection};
+use crate::math::types::Vec2;
+use crate::render::camera::{
+    CameraType, CameraUniform, OrthographicProjection, Projection, ViewportRect,
+};
 use crate::render::draw_command::DrawCommands;
-use crate::scene::{AsNode, NodeType};
-use cgmath::{Angle, InnerSpace, Matrix4, Perspective, Point2, Point3, Vector2, Vector3};
+use crate::scene::{AsNode, CameraShake, NodeType};
+use cgmath::{ElementWise, Matrix4, Vector2};
 use std::any::Any;
 
+/// Pixel art settings for a [`Camera2d`].
+///
+/// The camera shows a fixed number of art pixels, scaled up by the largest whole number
+/// that fits the view so that every art pixel is the same number of screen pixels.
+/// The area left around it is a letterbox, which 2D nodes aren't drawn into.
+///
+/// The camera and unrotated sprites are snapped to whole art pixels, and 2D textures are
+/// sampled with nearest filtering, whatever their import settings. Together this keeps
+/// pixel art from shimmering as the camera moves.
+#[derive(Debug, Copy, Clone)]
+pub struct PixelPerfect {
+    /// Size of the visible area, in art pixels (world units).
+    pub resolution: Vector2<u32>,
+}
+
+impl PixelPerfect {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            resolution: Vector2::new(width, height),
+        }
+    }
+}
+
+/// How a [`Camera2d`] fits its design resolution to the window.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum StretchMode {
+    /// A view unit is a window pixel, whatever the design resolution.
+    #[default]
+    Disabled,
+    /// Show exactly the design resolution, scaled to fit the window. Bars are left
+    /// where the aspect ratios differ (letterbox or pillarbox).
+    KeepAspect,
+    /// Scale like `KeepAspect`, but show more of the view along the longer side
+    /// instead of leaving bars.
+    Expand,
+}
+
 pub struct Camera2d {
     pub transform: Transform2d,
 
+    /// Size of the view in view units, which the UI is laid out in.
+    /// The window size unless stretched. Set by the camera.
     pub view_size: Vector2<u32>,
 
     /// Where to draw. None for screen.
     pub view: Option<u32>,
 
+    pub shake: CameraShake,
+
+    /// None draws a world unit to each screen pixel, over the whole view.
+    pub pixel_perfect: Option<PixelPerfect>,
+
+    /// Ignored with `pixel_perfect`, which only scales by whole numbers.
+    pub stretch_mode: StretchMode,
+    /// Size the 2D scene and UI are made for, in view units.
+    pub design_resolution: Vector2<u32>,
+
     projection: Projection,
+
+    /// Screen pixels per art pixel, 1 without pixel_perfect.
+    pixel_scale: u32,
+    /// Letterboxed area, with pixel_perfect or StretchMode::KeepAspect.
+    viewport: Option<ViewportRect>,
+
+    /// Window pixels per view unit.
+    stretch_scale: Vector2<f32>,
+    /// Where the view starts in the window, in pixels.
+    stretch_offset: Vector2<f32>,
 }
 
 impl Camera2d {
@@ -23,23 +85,152 @@ impl Camera2d {
             transform: Transform2d::default(),
             view_size: Vector2::new(0, 0),
             view: None,
+            shake: CameraShake::new(16.0, 4.0),
+            pixel_perfect: None,
+            stretch_mode: StretchMode::Disabled,
+            design_resolution: Vector2::new(0, 0),
             projection: OrthographicProjection::default().into(),
+            pixel_scale: 1,
+            viewport: None,
+            stretch_scale: Vector2::new(1.0, 1.0),
+            stretch_offset: Vector2::new(0.0, 0.0),
         }
     }
 
     pub fn calc_view_matrix(&self) -> Matrix4<f32> {
-        let rotation_mat = Matrix4::from_angle_z(-cgmath::Deg(self.transform.rotation));
-        let translation_mat = Matrix4::from_translation(Vector3::new(
-            self.transform.position.x,
-            self.transform.position.y,
-            0.0,
-        ));
+        let shake = self.shake.sample();
+
+        let rotation_mat =
+            Matrix4::from_angle_z(-cgmath::Deg(self.transform.rotation + shake.rotation.z));
+
+        let mut translation = Vector2::new(
+            self.transform.position.x + shake.translation.x,
+            self.transform.position.y + shake.translation.y,
+        );
+
+        if let Some(viewport) = self.viewport {
+            // Whole art pixels, then move into the letterbox.
+            translation = translation.map(f32::round)
+                + viewport.position.cast::<f32>().unwrap() / self.pixel_scale as f32;
+        }
+
+        let translation_mat = Matrix4::from_translation(translation.extend(0.0));
 
         translation_mat * rotation_mat
     }
 
+    /// Screen pixels per art pixel. 1 without [`Camera2d::pixel_perfect`].
+    pub fn get_pixel_scale(&self) -> u32 {
+        self.pixel_scale
+    }
+
+    /// Window pixel to view units, e.g. for the mouse position. Only stretching changes it.
+    pub fn window_to_view(&self, position: impl Into<Vec2>) -> Vec2 {
+        (Vector2::from(position.into()) - self.stretch_offset)
+            .div_element_wise(self.stretch_scale)
+            .into()
+    }
+
+    /// View units to window pixels.
+    pub fn view_to_window(&self, position: impl Into<Vec2>) -> Vec2 {
+        (Vector2::from(position.into()).mul_element_wise(self.stretch_scale) + self.stretch_offset)
+            .into()
+    }
+
+    /// Window pixels per view unit, in each axis.
+    pub fn get_stretch_scale(&self) -> Vec2 {
+        self.stretch_scale.into()
+    }
+
+    fn update_projection(&mut self, view_size: Vector2<u32>) {
+        self.stretch_scale = Vector2::new(1.0, 1.0);
+        self.stretch_offset = Vector2::new(0.0, 0.0);
+        self.view_size = view_size;
+
+        let Some(pixel_perfect) = self.pixel_perfect else {
+            self.pixel_scale = 1;
+            self.update_stretch(view_size);
+            self.projection
+                .update(self.view_size.x as f32, self.view_size.y as f32);
+            return;
+        };
+
+        let resolution = pixel_perfect.resolution.map(|v| v.max(1));
+
+        let scale = (view_size.x / resolution.x)
+            .min(view_size.y / resolution.y)
+            .max(1);
+
+        // Centered, and cut off if the view is smaller than the resolution.
+        let size = Vector2::new(
+            (resolution.x * scale).min(view_size.x),
+            (resolution.y * scale).min(view_size.y),
+        );
+        let position = (view_size - size) / 2;
+
+        self.pixel_scale = scale;
+        self.viewport = Some(ViewportRect {
+            position,
+            size,
+            view_size,
+            fit: false,
+        });
+        self.projection.update(
+            view_size.x as f32 / scale as f32,
+            view_size.y as f32 / scale as f32,
+        );
+    }
+
+    /// Fit the design resolution to the window, see [`StretchMode`].
+    fn update_stretch(&mut self, window_size: Vector2<u32>) {
+        self.viewport = None;
+
+        let design = self.design_resolution;
+
+        if self.stretch_mode == StretchMode::Disabled || design.x == 0 || design.y == 0 {
+            return;
+        }
+
+        let window = window_size.cast::<f32>().unwrap();
+        let design_f = design.cast::<f32>().unwrap();
+        let scale = (window.x / design_f.x).min(window.y / design_f.y);
+
+        match self.stretch_mode {
+            StretchMode::Disabled => {}
+            StretchMode::KeepAspect => {
+                // Whole pixels, so the bars are crisp.
+                let size = (design_f * scale)
+                    .map(|v| v.round() as u32)
+                    .zip(window_size, |v, max| v.clamp(1, max.max(1)));
+                let position = (window_size - size) / 2;
+
+                self.view_size = design;
+                self.stretch_scale = size.cast::<f32>().unwrap().div_element_wise(design_f);
+                self.stretch_offset = position.cast::<f32>().unwrap();
+                self.viewport = Some(ViewportRect {
+                    position,
+                    size,
+                    view_size: window_size,
+                    fit: true,
+                });
+            }
+            StretchMode::Expand => {
+                // The design resolution, grown to the aspect ratio of the window.
+                let view_size = (window / scale).map(|v| (v.round() as u32).max(1));
+
+                self.view_size = view_size;
+                self.stretch_scale = window.div_element_wise(view_size.cast::<f32>().unwrap());
+            }
+        }
+    }
+
+    /// Shake the camera, see [`CameraShake::add_trauma`].
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.shake.add_trauma(amount);
+    }
+
     pub fn when_view_size_changes(&mut self, new_size: Vector2<u32>) {
-        self.projection.update(new_size.x as f32, new_size.y as f32);
+        self.update_projection(new_size);
     }
 }
 
@@ -57,10 +248,14 @@ impl AsNode for Camera2d {
     }
 
     fn update(&mut self, dt: f32, singletons: &mut Singletons) {
-        self.projection.update(
-            singletons.render_server.surface_config.width as f32,
-            singletons.render_server.surface_config.height as f32,
-        );
+        self.update_projection(Vector2::new(
+            singletons.render_server.surface_config.width,
+            singletons.render_server.surface_config.height,
+        ));
+        self.projection
+            .set_reverse_z(singletons.render_server.get_settings().reverse_z);
+
+        self.shake.update(dt);
     }
 
     fn draw(&self, draw_cmds: &mut DrawCommands) {
@@ -75,6 +270,10 @@ impl AsNode for Camera2d {
         uniform.proj = proj_mat.into();
         uniform.view_proj = (proj_mat * view_mat).into();
 
-        draw_cmds.extracted.cameras.add(CameraType::D2, uniform);
+        draw_cmds
+            .extracted
+            .cameras
+            .add_with_viewport(CameraType::D2, uniform, self.viewport);
+        draw_cmds.extracted.cameras.pixel_snap = self.pixel_perfect.is_some();
     }
 }
@@ -1,3 +1,4 @@
+use crate::asset::AssetHandle;
 use crate::core::singleton::Singletons;
 use crate::math::rect_to_vector4;
 use crate::math::transform::Transform2d;
@@ -18,9 +19,15 @@ pub struct Label {
     text: String,
 
     text_is_dirty: bool,
+
+    /// Treat the text as a message ID and show its translation, see `TextServer::tr`.
+    pub auto_translate: bool,
+
+    /// Text server atlas version the current layout was made with.
+    atlas_version: u32,
     layout_is_dirty: bool,
 
-    font_id: Option<String>,
+    font: Option<AssetHandle>,
 
     single_line: bool,
 
@@ -37,8 +44,10 @@ impl Label {
             node_ui: NodeUi::default(),
             text: "Label".to_string(),
             text_is_dirty: true,
+            auto_translate: true,
+            atlas_version: 0,
             layout_is_dirty: true,
-            font_id: None,
+            font: None,
             single_line: false,
             leading: 20.0,
             tracking: 0.0,
@@ -51,8 +60,10 @@ impl Label {
         self.text_is_dirty = true;
     }
 
-    pub fn set_font(&mut self, font_id: String) {
-        self.font_id = Some(font_id);
+    /// Use a font loaded by `AssetServer::load_font` or `AssetServer::load_bitmap_font`.
+    pub fn set_font(&mut self, font: AssetHandle) {
+        self.font = Some(font);
+        self.text_is_dirty = true;
     }
 }
 
@@ -70,15 +81,28 @@ impl AsNode for Label {
     }
 
     fn update(&mut self, dt: f32, singletons: &mut Singletons) {
-        if self.text_is_dirty {
-            self.atlas = Some(singletons.text_server.get_atlas(
-                self.text.as_str(),
-                self.font_id.clone(),
-                self.node_ui.transform,
-                self.leading,
-            ));
+        let atlas_version = singletons.text_server.get_atlas_version();
+        if self.text_is_dirty || self.atlas_version != atlas_version {
+            let text = if self.auto_translate {
+                singletons.text_server.tr(&self.text)
+            } else {
+                self.text.clone()
+            };
+
+            self.atlas = Some(
+                singletons.text_server.get_atlas(
+                    &text,
+                    self.font
+                        .as_ref()
+                        .and_then(|f| f.get_font())
+                        .map(str::to_string),
+                    self.node_ui.transform,
+                    self.leading,
+                ),
+            );
 
             self.text_is_dirty = false;
+            self.atlas_version = atlas_version;
         }
     }
 
@@ -87,6 +111,8 @@ impl AsNode for Label {
             atlas: self.atlas.clone().unwrap(),
             view_size: draw_commands.view_info.view_size.into(),
         });
+
+        self.node_ui.push_clip(draw_commands);
     }
 }
 
@@ -114,4 +140,24 @@ impl AsNodeUi for Label {
     fn set_rotation(&mut self, rotation: f32) {
         self.node_ui.transform.rotation = rotation;
     }
+
+    fn get_clip_contents(&self) -> bool {
+        self.node_ui.clip_contents
+    }
+
+    fn set_clip_contents(&mut self, clip_contents: bool) {
+        self.node_ui.clip_contents = clip_contents;
+    }
+
+    fn get_clip_corner_radius(&self) -> f32 {
+        self.node_ui.clip_corner_radius
+    }
+
+    fn set_clip_corner_radius(&mut self, radius: f32) {
+        self.node_ui.clip_corner_radius = radius;
+    }
+
+    fn get_ui_name(&self) -> String {
+        self.text.clone()
+    }
 }
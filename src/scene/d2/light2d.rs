@@ -0,0 +1,125 @@
+use crate::core::singleton::Singletons;
+use crate::math::color::ColorU;
+use crate::math::transform::Transform2d;
+use crate::math::types::Vec2;
+use crate::render::draw_command::DrawCommands;
+use crate::render::light2d::{ExtractedLight2d, ExtractedOccluder2d, Light2dKind};
+use crate::scene::{AsNode, NodeType};
+use cgmath::{Vector2, Vector4};
+use std::any::Any;
+
+/// Lights sprites in a 2D scene. Without any 2D light, sprites are drawn unlit.
+pub struct Light2d {
+    pub transform: Transform2d,
+    pub kind: Light2dKind,
+    pub color: ColorU,
+    pub energy: f32,
+    /// Cast shadows from [`LightOccluder2d`]s.
+    pub shadows: bool,
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Light2d {
+    pub fn new(kind: Light2dKind) -> Self {
+        Self {
+            transform: Transform2d::default(),
+            kind,
+            color: ColorU::white(),
+            energy: 1.0,
+            shadows: true,
+            custom_update: None,
+        }
+    }
+
+    pub fn new_point(radius: f32) -> Self {
+        Self::new(Light2dKind::Point { radius })
+    }
+
+    pub fn new_directional(direction: impl Into<Vec2>) -> Self {
+        Self::new(Light2dKind::Directional {
+            direction: direction.into().into(),
+        })
+    }
+}
+
+impl AsNode for Light2d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Light2d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        let color = self.color.to_vec3() * self.energy;
+
+        draw_cmds.extracted.lights_2d.push(ExtractedLight2d {
+            position: self.transform.position,
+            kind: self.kind,
+            color: Vector4::new(color.x, color.y, color.z, 1.0),
+            shadows: self.shadows,
+        });
+    }
+}
+
+/// Blocks light from [`Light2d`]s, casting shadows behind it.
+pub struct LightOccluder2d {
+    pub transform: Transform2d,
+    /// Closed outline in local space.
+    pub polygon: Vec<Vector2<f32>>,
+}
+
+impl LightOccluder2d {
+    pub fn new(polygon: Vec<Vector2<f32>>) -> Self {
+        Self {
+            transform: Transform2d::default(),
+            polygon,
+        }
+    }
+
+    /// A rectangle with its top-left corner at the origin, like a non-centered sprite.
+    pub fn from_rect(size: impl Into<Vec2>) -> Self {
+        let size = size.into();
+        Self::new(vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(size.x, 0.0),
+            Vector2::new(size.x, size.y),
+            Vector2::new(0.0, size.y),
+        ])
+    }
+}
+
+impl AsNode for LightOccluder2d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::LightOccluder2d
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        draw_cmds.extracted.occluders_2d.push(ExtractedOccluder2d {
+            points: self
+                .polygon
+                .iter()
+                .map(|p| self.transform.transform_point(p).into())
+                .collect(),
+        });
+    }
+}
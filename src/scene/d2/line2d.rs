@@ -0,0 +1,211 @@
+use crate::math::color::ColorU;
+use crate::math::transform::Transform2d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::line2d::ExtractedLine2d;
+use crate::render::vertex::Vertex2d;
+use crate::render::TextureId;
+use crate::scene::{AsNode, NodeType};
+use cgmath::{InnerSpace, Vector2};
+use lyon::math::point;
+use lyon::path::Path;
+use lyon::tessellation::{
+    BuffersBuilder, LineJoin, Side, StrokeOptions, StrokeTessellator, StrokeVertex, VertexBuffers,
+};
+use std::any::Any;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineJoint {
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Square,
+    Round,
+}
+
+/// How a texture is mapped along a line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineTextureMode {
+    /// The texture covers the whole line once.
+    Stretch,
+    /// The texture repeats every `width` units. Needs a repeating texture.
+    Tile,
+}
+
+/// A polyline with a width, drawn like a sprite.
+pub struct Line2d {
+    pub transform: Transform2d,
+
+    /// Points in local space.
+    pub points: Vec<Vector2<f32>>,
+
+    /// Per-point colors, blended along the line. Points without one use `color`.
+    pub colors: Vec<ColorU>,
+    pub color: ColorU,
+
+    pub width: f32,
+    pub joint: LineJoint,
+    pub begin_cap: LineCap,
+    pub end_cap: LineCap,
+
+    /// Connect the last point back to the first.
+    pub closed: bool,
+
+    pub texture: Option<TextureId>,
+    pub texture_mode: LineTextureMode,
+}
+
+/// Alias for [`Line2d`], which already handles any number of points.
+pub type Polyline2d = Line2d;
+
+impl Line2d {
+    pub fn new(points: Vec<Vector2<f32>>) -> Self {
+        Self {
+            transform: Transform2d::default(),
+            points,
+            colors: vec![],
+            color: ColorU::white(),
+            width: 4.0,
+            joint: LineJoint::Miter,
+            begin_cap: LineCap::Butt,
+            end_cap: LineCap::Butt,
+            closed: false,
+            texture: None,
+            texture_mode: LineTextureMode::Stretch,
+        }
+    }
+
+    /// A single segment.
+    pub fn from_segment(from: Vector2<f32>, to: Vector2<f32>) -> Self {
+        Self::new(vec![from, to])
+    }
+
+    fn get_point_color(&self, index: usize) -> [f32; 3] {
+        self.colors
+            .get(index)
+            .copied()
+            .unwrap_or(self.color)
+            .to_vec3()
+            .into()
+    }
+
+    /// Stroke the line into triangles, in world space.
+    fn tessellate(&self) -> Option<ExtractedLine2d> {
+        if self.points.len() < 2 || self.width <= 0.0 {
+            return None;
+        }
+
+        let points: Vec<Vector2<f32>> = self
+            .points
+            .iter()
+            .map(|p| self.transform.transform_point(p).into())
+            .collect();
+
+        // Colors are carried as custom path attributes, so lyon interpolates them.
+        let mut builder = Path::builder_with_attributes(3);
+        builder.begin(point(points[0].x, points[0].y), &self.get_point_color(0));
+        for (i, p) in points.iter().enumerate().skip(1) {
+            builder.line_to(point(p.x, p.y), &self.get_point_color(i));
+        }
+        builder.end(self.closed);
+        let path = builder.build();
+
+        let mut length: f32 = points.windows(2).map(|w| (w[1] - w[0]).magnitude()).sum();
+        if self.closed {
+            length += (points[0] - points[points.len() - 1]).magnitude();
+        }
+
+        let texture_length = match self.texture_mode {
+            LineTextureMode::Stretch => length.max(f32::EPSILON),
+            LineTextureMode::Tile => self.width,
+        };
+
+        let options = StrokeOptions::default()
+            .with_line_width(self.width)
+            .with_line_join(match self.joint {
+                LineJoint::Miter => LineJoin::Miter,
+                LineJoint::Bevel => LineJoin::Bevel,
+                LineJoint::Round => LineJoin::Round,
+            })
+            .with_start_cap(to_lyon_cap(self.begin_cap))
+            .with_end_cap(to_lyon_cap(self.end_cap));
+
+        let mut geometry: VertexBuffers<Vertex2d, u32> = VertexBuffers::new();
+
+        let result = StrokeTessellator::new().tessellate_path(
+            &path,
+            &options,
+            &mut BuffersBuilder::new(&mut geometry, |mut vertex: StrokeVertex| {
+                let position = vertex.position();
+                let u = vertex.advancement() / texture_length;
+                let v = if vertex.side() == Side::Positive {
+                    0.0
+                } else {
+                    1.0
+                };
+                let color = vertex.interpolated_attributes();
+
+                Vertex2d {
+                    position: [position.x, position.y],
+                    uv: [u, v],
+                    color: [color[0], color[1], color[2]],
+                }
+            }),
+        );
+
+        if let Err(e) = result {
+            log::warn!("Failed to tessellate line: {:?}", e);
+            return None;
+        }
+
+        // Match the winding of sprite quads, so that back-face culling keeps the triangles.
+        let mut indices = geometry.indices;
+        for triangle in indices.chunks_exact_mut(3) {
+            let a = Vector2::from(geometry.vertices[triangle[0] as usize].position);
+            let b = Vector2::from(geometry.vertices[triangle[1] as usize].position);
+            let c = Vector2::from(geometry.vertices[triangle[2] as usize].position);
+
+            if (b - a).perp_dot(c - a) > 0.0 {
+                triangle.swap(1, 2);
+            }
+        }
+
+        Some(ExtractedLine2d {
+            vertices: geometry.vertices,
+            indices,
+            texture_id: self.texture,
+        })
+    }
+}
+
+fn to_lyon_cap(cap: LineCap) -> lyon::tessellation::LineCap {
+    match cap {
+        LineCap::Butt => lyon::tessellation::LineCap::Butt,
+        LineCap::Square => lyon::tessellation::LineCap::Square,
+        LineCap::Round => lyon::tessellation::LineCap::Round,
+    }
+}
+
+impl AsNode for Line2d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Line2d
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if let Some(line) = self.tessellate() {
+            draw_cmds.extracted.lines_2d.push(line);
+        }
+    }
+}
@@ -1,13 +1,29 @@
 pub(crate) mod button;
 pub(crate) mod camera2d;
+pub(crate) mod control;
 pub(crate) mod label;
+pub(crate) mod light2d;
+pub(crate) mod line2d;
 mod node_ui;
+pub(crate) mod panel;
+pub(crate) mod parallax;
+pub(crate) mod path2d;
 pub(crate) mod sprite2d;
+pub(crate) mod style_box;
+pub(crate) mod tile_map;
 pub(crate) mod vector_sprite;
 
 pub use button::*;
 pub use camera2d::*;
+pub use control::*;
 pub use label::*;
+pub use light2d::*;
+pub use line2d::*;
 pub use node_ui::*;
+pub use panel::*;
+pub use parallax::*;
+pub use path2d::*;
 pub use sprite2d::*;
+pub use style_box::*;
+pub use tile_map::*;
 pub use vector_sprite::*;
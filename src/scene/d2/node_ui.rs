@@ -1,7 +1,8 @@
 use crate::core::Singletons;
 use crate::math::transform::Transform2d;
+use crate::render::clip::ExtractedClip;
 use crate::render::draw_command::DrawCommands;
-use crate::scene::NodeType;
+use crate::scene::UiEventKind;
 use crate::window::{InputEvent, InputServer};
 use cgmath::Vector2;
 use std::any::Any;
@@ -10,6 +11,12 @@ pub struct NodeUi {
     pub transform: Transform2d,
 
     pub size: Vector2<f32>,
+
+    /// Clip children to this node's rectangle. Works with rotation, unlike scissor rects.
+    pub clip_contents: bool,
+
+    /// Rounds the corners of the clip rectangle.
+    pub clip_corner_radius: f32,
 }
 
 impl Default for NodeUi {
@@ -17,7 +24,27 @@ impl Default for NodeUi {
         Self {
             transform: Transform2d::default(),
             size: Vector2::new(128.0_f32, 128.0),
+            clip_contents: false,
+            clip_corner_radius: 0.0,
+        }
+    }
+}
+
+impl NodeUi {
+    /// Call at the end of `draw`, so that only children are clipped.
+    /// The scene tree pops the clip once all children are drawn.
+    pub(crate) fn push_clip(&self, draw_cmds: &mut DrawCommands) {
+        if !self.clip_contents {
+            return;
         }
+
+        draw_cmds.push_clip(ExtractedClip {
+            transform: self.transform,
+            size: self.size,
+            corner_radius: self.clip_corner_radius,
+            view_size: draw_cmds.view_info.view_size,
+            triangles: vec![],
+        });
     }
 }
 
@@ -33,4 +60,22 @@ pub trait AsNodeUi {
     fn get_rotation(&self) -> f32;
 
     fn set_rotation(&mut self, rotation: f32);
+
+    fn get_clip_contents(&self) -> bool;
+
+    fn set_clip_contents(&mut self, clip_contents: bool);
+
+    fn get_clip_corner_radius(&self) -> f32;
+
+    fn set_clip_corner_radius(&mut self, radius: f32);
+
+    /// Name of the node in UI events, see [`crate::scene::UiEvent`].
+    fn get_ui_name(&self) -> String {
+        String::new()
+    }
+
+    /// Semantic UI events since the last call, collected by the world.
+    fn take_ui_events(&mut self) -> Vec<UiEventKind> {
+        vec![]
+    }
 }
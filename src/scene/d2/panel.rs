@@ -0,0 +1,77 @@
+use crate::render::draw_command::DrawCommands;
+use crate::scene::d2::node_ui::{AsNodeUi, NodeUi};
+use crate::scene::d2::style_box::StyleBoxFlat;
+use crate::scene::{AsNode, NodeType};
+use cgmath::Vector2;
+use std::any::Any;
+
+/// A UI rectangle drawn with a style box.
+#[derive(Default)]
+pub struct Panel {
+    node_ui: NodeUi,
+
+    pub style: StyleBoxFlat,
+}
+
+impl AsNode for Panel {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Panel
+    }
+
+    fn draw(&self, draw_commands: &mut DrawCommands) {
+        self.style
+            .draw(self.node_ui.transform, self.node_ui.size, draw_commands);
+
+        self.node_ui.push_clip(draw_commands);
+    }
+}
+
+impl AsNodeUi for Panel {
+    fn get_size(&self) -> Vector2<f32> {
+        self.node_ui.size
+    }
+
+    fn set_size(&mut self, size: Vector2<f32>) {
+        self.node_ui.size = size;
+    }
+
+    fn get_position(&self) -> Vector2<f32> {
+        self.node_ui.transform.position
+    }
+
+    fn set_position(&mut self, position: Vector2<f32>) {
+        self.node_ui.transform.position = position;
+    }
+
+    fn get_rotation(&self) -> f32 {
+        self.node_ui.transform.rotation
+    }
+
+    fn set_rotation(&mut self, rotation: f32) {
+        self.node_ui.transform.rotation = rotation;
+    }
+
+    fn get_clip_contents(&self) -> bool {
+        self.node_ui.clip_contents
+    }
+
+    fn set_clip_contents(&mut self, clip_contents: bool) {
+        self.node_ui.clip_contents = clip_contents;
+    }
+
+    fn get_clip_corner_radius(&self) -> f32 {
+        self.node_ui.clip_corner_radius
+    }
+
+    fn set_clip_corner_radius(&mut self, radius: f32) {
+        self.node_ui.clip_corner_radius = radius;
+    }
+}
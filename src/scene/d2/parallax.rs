@@ -0,0 +1,124 @@
+use crate::core::singleton::Singletons;
+use crate::scene::{AsNode, NodeType};
+use cgmath::{ElementWise, Vector2};
+use std::any::Any;
+
+/// Scrolls its [`ParallaxLayer`] children as the current [`Camera2d`](crate::scene::Camera2d) moves.
+pub struct ParallaxBackground {
+    /// Extra scroll on top of the camera's, e.g. for auto-scrolling backgrounds.
+    pub scroll_offset: Vector2<f32>,
+
+    /// Scales the scrolling of all layers.
+    pub scroll_base_scale: Vector2<f32>,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Default for ParallaxBackground {
+    fn default() -> Self {
+        Self {
+            scroll_offset: Vector2::new(0.0, 0.0),
+            scroll_base_scale: Vector2::new(1.0, 1.0),
+            custom_update: None,
+        }
+    }
+}
+
+impl ParallaxBackground {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AsNode for ParallaxBackground {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::ParallaxBackground
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+}
+
+/// Moves all its descendants at a different speed than the camera.
+/// Has to be a child of a [`ParallaxBackground`].
+pub struct ParallaxLayer {
+    /// Scroll speed relative to the camera. 1 moves with the world, 0 stays fixed on screen.
+    pub scroll_scale: Vector2<f32>,
+
+    /// Static offset of the layer, in addition to scrolling.
+    pub offset: Vector2<f32>,
+
+    /// Period to repeat the layer with, for infinite scrolling. 0 disables wrapping on that axis.
+    /// Children have to cover the view plus one period.
+    pub mirroring: Vector2<f32>,
+
+    /// Offset currently applied to the descendants.
+    applied_offset: Vector2<f32>,
+}
+
+impl ParallaxLayer {
+    pub fn new(scroll_scale: Vector2<f32>) -> Self {
+        Self {
+            scroll_scale,
+            offset: Vector2::new(0.0, 0.0),
+            mirroring: Vector2::new(0.0, 0.0),
+            applied_offset: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// World space offset of the layer for a camera position.
+    pub(crate) fn calc_offset(
+        &self,
+        camera_position: Vector2<f32>,
+        background: &ParallaxBackground,
+    ) -> Vector2<f32> {
+        // How far the layer appears to have moved on screen.
+        let mut scroll = (camera_position + background.scroll_offset)
+            .mul_element_wise(background.scroll_base_scale)
+            .mul_element_wise(self.scroll_scale)
+            + self.offset;
+
+        // Wrap into one period behind the origin, so that the children always cover the view.
+        if self.mirroring.x > 0.0 {
+            scroll.x = scroll.x.rem_euclid(self.mirroring.x) - self.mirroring.x;
+        }
+        if self.mirroring.y > 0.0 {
+            scroll.y = scroll.y.rem_euclid(self.mirroring.y) - self.mirroring.y;
+        }
+
+        // The camera already moves everything by its position.
+        scroll - camera_position
+    }
+
+    /// Set the offset of the layer, returning how far the descendants have to move.
+    pub(crate) fn apply_offset(&mut self, offset: Vector2<f32>) -> Vector2<f32> {
+        let delta = offset - self.applied_offset;
+        self.applied_offset = offset;
+        delta
+    }
+}
+
+impl AsNode for ParallaxLayer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::ParallaxLayer
+    }
+}
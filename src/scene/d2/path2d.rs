@@ -0,0 +1,139 @@
+use crate::core::singleton::Singletons;
+use crate::math::bezier::BezierPath;
+use crate::math::transform::Transform2d;
+use crate::scene::{AsNode, NodeType};
+use cgmath::Vector2;
+use std::any::Any;
+
+/// A curve in 2D space for [`PathFollow2d`] children to move along, e.g. an enemy's
+/// patrol route. Use [`BezierPath::tessellate`] to show it with a
+/// [`Line2d`](crate::scene::Line2d).
+pub struct Path2d {
+    pub transform: Transform2d,
+
+    /// In local space.
+    pub path: BezierPath<Vector2<f32>>,
+}
+
+impl Path2d {
+    pub fn new(path: BezierPath<Vector2<f32>>) -> Self {
+        Self {
+            transform: Transform2d::default(),
+            path,
+        }
+    }
+
+    /// World transform at a distance along the path, facing the direction of travel.
+    pub fn get_transform_at(&self, offset: f32) -> Option<Transform2d> {
+        let position = self.path.sample_at_distance(offset)?;
+        let tangent = self.path.tangent_at_distance(offset);
+
+        let mut transform = Transform2d::default();
+        transform.position = self.transform.transform_point(position).into();
+        transform.rotation = self.transform.rotation + tangent.map_or(0.0, |t| t.y.atan2(t.x));
+
+        Some(transform)
+    }
+}
+
+impl AsNode for Path2d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Path2d
+    }
+}
+
+/// Moves along its parent [`Path2d`], carrying its children along. Children are only
+/// translated, the rotation applies to this node.
+pub struct PathFollow2d {
+    pub transform: Transform2d,
+
+    /// Distance along the path.
+    pub offset: f32,
+
+    /// Added to the offset every second. Negative goes backwards.
+    pub speed: f32,
+
+    /// Wrap around at the ends instead of stopping.
+    pub looping: bool,
+
+    /// Face the direction of travel.
+    pub rotates: bool,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Default for PathFollow2d {
+    fn default() -> Self {
+        Self {
+            transform: Transform2d::default(),
+            offset: 0.0,
+            speed: 0.0,
+            looping: true,
+            rotates: true,
+            custom_update: None,
+        }
+    }
+}
+
+impl PathFollow2d {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            ..Default::default()
+        }
+    }
+
+    /// Wrap or clamp the offset to a path of `length`, and return it.
+    pub(crate) fn wrap_offset(&mut self, length: f32) -> f32 {
+        self.offset = if self.looping && length > 0.0 {
+            self.offset.rem_euclid(length)
+        } else {
+            self.offset.clamp(0.0, length)
+        };
+
+        self.offset
+    }
+
+    /// Move to a transform on the path, and return how far it moved.
+    /// Called by the world after nodes are updated.
+    pub(crate) fn follow(&mut self, transform: Transform2d) -> Vector2<f32> {
+        let old = self.transform.position;
+
+        self.transform.position = transform.position;
+        if self.rotates {
+            self.transform.rotation = transform.rotation;
+        }
+
+        self.transform.position - old
+    }
+}
+
+impl AsNode for PathFollow2d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::PathFollow2d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        self.offset += self.speed * dt;
+
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+}
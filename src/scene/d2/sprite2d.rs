@@ -1,10 +1,11 @@
+use crate::asset::AssetHandle;
 use crate::core::singleton::Singletons;
 use crate::math::transform::Transform2d;
 use crate::render::camera::CameraUniform;
 use crate::render::draw_command::DrawCommands;
 use crate::render::sprite::ExtractedSprite2d;
 use crate::render::view::ViewInfo;
-use crate::render::{Mesh, Texture, TextureCache, TextureId};
+use crate::render::{AnimatedTexture, TextureCache};
 use crate::scene::d2::node_ui::{AsNodeUi, NodeUi};
 use crate::scene::{AsNode, Label, NodeType};
 use cgmath::{Vector2, Vector3, Vector4};
@@ -33,7 +34,8 @@ pub struct Sprite2d {
 
     pub sprite_sheet: SpriteSheet,
 
-    pub texture: Option<TextureId>,
+    /// Keeps a texture loaded through the `AssetServer` alive while the sprite uses it.
+    pub texture: Option<AssetHandle>,
 
     // pub camera_uniform: CameraUniform,
     pub centered: bool,
@@ -41,12 +43,22 @@ pub struct Sprite2d {
     pub flip_x: bool,
     pub flip_y: bool,
 
+    /// Animated image to play instead of a still texture.
+    animation: Option<AnimatedTexture>,
+    /// Tag of the animation being played, all frames if none.
+    tag: Option<usize>,
+    animation_time: f32,
+    pub playing: bool,
+    pub looping: bool,
+    pub speed_scale: f32,
+
     pub custom_update: Option<fn(f32, &mut Self)>,
 }
 
 impl Sprite2d {
-    pub fn new(texture_cache: &TextureCache, texture_id: TextureId) -> Sprite2d {
-        let texture = texture_cache.get(texture_id).unwrap();
+    pub fn new(texture_cache: &TextureCache, texture: impl Into<AssetHandle>) -> Sprite2d {
+        let handle = texture.into();
+        let texture = texture_cache.get(handle.get_texture().unwrap()).unwrap();
 
         let size = Vector2::new(texture.size.0 as f32, texture.size.1 as f32);
 
@@ -62,16 +74,81 @@ impl Sprite2d {
                 v_frames: 0,
                 frame: 0,
             },
-            texture: Some(texture_id),
+            texture: Some(handle),
             centered: false,
             flip_x: false,
             flip_y: false,
+            animation: None,
+            tag: None,
+            animation_time: 0.0,
+            playing: false,
+            looping: true,
+            speed_scale: 1.0,
             custom_update: None,
         }
     }
 
-    pub fn set_texture(&mut self, texture_id: TextureId) {
-        self.texture = Some(texture_id);
+    /// Create a sprite playing an animated image.
+    pub fn new_animated(texture_cache: &TextureCache, animation: AnimatedTexture) -> Sprite2d {
+        let mut sprite = Self::new(texture_cache, animation.texture);
+        sprite.set_animation(animation);
+        sprite
+    }
+
+    pub fn set_texture(&mut self, texture: impl Into<AssetHandle>) {
+        self.texture = Some(texture.into());
+        self.animation = None;
+        self.tag = None;
+        self.region = Vector4::new(0.0, 0.0, 1.0, 1.0);
+    }
+
+    /// Play an animated image from the start.
+    pub fn set_animation(&mut self, animation: AnimatedTexture) {
+        self.texture = Some(animation.texture.into());
+        self.region = animation.frames[0].region;
+        self.animation = Some(animation);
+        self.tag = None;
+        self.animation_time = 0.0;
+        self.playing = true;
+    }
+
+    /// Play the frames of a tag of the animation from the start, e.g. "walk" from a
+    /// sprite sheet exported by Aseprite.
+    pub fn play_tag(&mut self, name: &str) {
+        let Some(tag) = self.animation.as_ref().and_then(|a| a.get_tag(name)) else {
+            log::warn!("Sprite animation has no tag {}", name);
+            return;
+        };
+
+        self.tag = Some(tag);
+        self.playing = true;
+        self.seek(0.0);
+    }
+
+    pub fn get_tag(&self) -> Option<&str> {
+        let animation = self.animation.as_ref()?;
+        Some(animation.tags[self.tag?].name.as_str())
+    }
+
+    pub fn get_animation(&self) -> Option<&AnimatedTexture> {
+        self.animation.as_ref()
+    }
+
+    /// Index of the current animation frame.
+    pub fn get_frame(&self) -> usize {
+        self.animation.as_ref().map_or(0, |a| match self.tag {
+            Some(tag) => a.get_tag_frame_at(tag, self.animation_time, self.looping),
+            None => a.get_frame_at(self.animation_time, self.looping),
+        })
+    }
+
+    /// Jump to a time in the animation, in seconds.
+    pub fn seek(&mut self, time: f32) {
+        self.animation_time = time.max(0.0);
+
+        if let Some(animation) = &self.animation {
+            self.region = animation.frames[self.get_frame()].region;
+        }
     }
 
     pub fn calc_render_params(&self, view_info: &ViewInfo) -> CameraUniform {
@@ -134,15 +211,20 @@ impl AsNode for Sprite2d {
     fn ready(&mut self) {}
 
     fn update(&mut self, dt: f32, singletons: &mut Singletons) {
+        if self.playing && self.animation.is_some() {
+            self.seek(self.animation_time + dt * self.speed_scale);
+        }
+
         if self.custom_update.is_some() {
             self.custom_update.unwrap()(dt, self);
         }
     }
 
     fn draw(&self, draw_cmds: &mut DrawCommands) {
-        if (self.texture.is_none()) {
+        let Some(texture_id) = self.texture.as_ref().and_then(AssetHandle::get_texture) else {
+            self.node_ui.push_clip(draw_cmds);
             return;
-        }
+        };
 
         let extracted = ExtractedSprite2d {
             transform: self.node_ui.transform,
@@ -151,13 +233,35 @@ impl AsNode for Sprite2d {
             } else {
                 Some(self.node_ui.size.into())
             },
-            texture_id: self.texture.unwrap(),
+            texture_id,
+            region: self.region,
             centered: self.centered,
             flip_x: self.flip_x,
             flip_y: self.flip_y,
         };
 
         draw_cmds.extracted.sprites.push(extracted);
+
+        self.node_ui.push_clip(draw_cmds);
+    }
+
+    fn save_state(&self) -> Option<serde_json::Value> {
+        self.animation.as_ref()?;
+
+        Some(serde_json::json!({
+            "animation_time": self.animation_time,
+            "playing": self.playing,
+            "tag": self.get_tag(),
+        }))
+    }
+
+    fn load_state(&mut self, state: &serde_json::Value) -> anyhow::Result<()> {
+        if let Some(tag) = state["tag"].as_str() {
+            self.play_tag(tag);
+        }
+        self.playing = serde_json::from_value(state["playing"].clone())?;
+        self.seek(serde_json::from_value(state["animation_time"].clone())?);
+        Ok(())
     }
 }
 
@@ -185,4 +289,20 @@ impl AsNodeUi for Sprite2d {
     fn set_rotation(&mut self, rotation: f32) {
         self.node_ui.transform.rotation = rotation;
     }
+
+    fn get_clip_contents(&self) -> bool {
+        self.node_ui.clip_contents
+    }
+
+    fn set_clip_contents(&mut self, clip_contents: bool) {
+        self.node_ui.clip_contents = clip_contents;
+    }
+
+    fn get_clip_corner_radius(&self) -> f32 {
+        self.node_ui.clip_corner_radius
+    }
+
+    fn set_clip_corner_radius(&mut self, radius: f32) {
+        self.node_ui.clip_corner_radius = radius;
+    }
 }
@@ -0,0 +1,54 @@
+use crate::math::color::ColorU;
+use crate::math::transform::Transform2d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::{UiShape, UiShapeKind};
+use cgmath::Vector2;
+
+/// A flat colored box with an optional border and rounded corners, for UI backgrounds.
+#[derive(Debug, Copy, Clone)]
+pub struct StyleBoxFlat {
+    pub bg_color: ColorU,
+    pub border_color: ColorU,
+    /// Drawn inside the box.
+    pub border_width: f32,
+    /// Top-left, top-right, bottom-right, bottom-left.
+    pub corner_radii: [f32; 4],
+    /// Frosted glass look: the 3D scene behind the box is blurred and tinted by `bg_color`.
+    pub backdrop_blur: bool,
+}
+
+impl Default for StyleBoxFlat {
+    fn default() -> Self {
+        Self {
+            bg_color: ColorU::new(153, 153, 153, 255),
+            border_color: ColorU::new(204, 204, 204, 255),
+            border_width: 0.0,
+            corner_radii: [0.0; 4],
+            backdrop_blur: false,
+        }
+    }
+}
+
+impl StyleBoxFlat {
+    pub fn set_corner_radius_all(&mut self, radius: f32) {
+        self.corner_radii = [radius; 4];
+    }
+
+    pub(crate) fn draw(
+        &self,
+        transform: Transform2d,
+        size: Vector2<f32>,
+        draw_cmds: &mut DrawCommands,
+    ) {
+        let shape = UiShape {
+            kind: UiShapeKind::RoundedRect,
+            corner_radii: self.corner_radii,
+            color: self.bg_color,
+            border_width: self.border_width,
+            border_color: self.border_color,
+            backdrop_blur: self.backdrop_blur,
+        };
+
+        draw_cmds.draw_ui_shape(&shape, transform, size);
+    }
+}
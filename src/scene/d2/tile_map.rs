@@ -0,0 +1,214 @@
+use crate::core::singleton::Singletons;
+use crate::math::transform::Transform2d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::sprite::ExtractedSprite2d;
+use crate::render::TextureId;
+use crate::scene::{AsNode, NodeType};
+use cgmath::{Vector2, Vector4};
+use std::any::Any;
+use std::collections::HashMap;
+
+/// Tiles cut from one image, in rows of `columns`.
+#[derive(Debug, Clone)]
+pub struct TileSet {
+    pub texture: TextureId,
+    /// In pixels.
+    pub texture_size: (u32, u32),
+    /// In pixels. May differ from the cell size of the map, tiles are bottom aligned.
+    pub tile_size: (u32, u32),
+    pub columns: u32,
+    /// Around the image, in pixels.
+    pub margin: u32,
+    /// Between tiles, in pixels.
+    pub spacing: u32,
+    /// Frames of animated tiles, as tile indices and durations in seconds.
+    pub animations: HashMap<u32, Vec<(u32, f32)>>,
+}
+
+impl TileSet {
+    /// Normalized region of the texture (x, y, width, height).
+    pub fn get_region(&self, tile: u32) -> Vector4<f32> {
+        let columns = self.columns.max(1);
+        let x = self.margin + (tile % columns) * (self.tile_size.0 + self.spacing);
+        let y = self.margin + (tile / columns) * (self.tile_size.1 + self.spacing);
+
+        Vector4::new(
+            x as f32 / self.texture_size.0 as f32,
+            y as f32 / self.texture_size.1 as f32,
+            self.tile_size.0 as f32 / self.texture_size.0 as f32,
+            self.tile_size.1 as f32 / self.texture_size.1 as f32,
+        )
+    }
+
+    /// The tile shown in place of `tile` at some time, if it's animated.
+    pub fn get_animated_tile(&self, tile: u32, time: f32) -> u32 {
+        let Some(frames) = self.animations.get(&tile) else {
+            return tile;
+        };
+
+        let duration: f32 = frames.iter().map(|(_, d)| d).sum();
+        if duration <= 0.0 {
+            return tile;
+        }
+
+        let mut time = time.rem_euclid(duration);
+
+        for (frame, delay) in frames {
+            if time < *delay {
+                return *frame;
+            }
+            time -= delay;
+        }
+
+        frames.last().map_or(tile, |(frame, _)| *frame)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TileCell {
+    /// Index into `TileMap::tile_sets`.
+    pub tile_set: usize,
+    pub tile: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+/// A grid of cells, row by row.
+#[derive(Debug, Clone)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<Option<TileCell>>,
+    /// In pixels.
+    pub offset: Vector2<f32>,
+    pub visible: bool,
+}
+
+impl TileLayer {
+    pub fn new(name: &str, width: u32, height: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            width,
+            height,
+            cells: vec![None; (width * height) as usize],
+            offset: Vector2::new(0.0, 0.0),
+            visible: true,
+        }
+    }
+
+    pub fn get_cell(&self, x: u32, y: u32) -> Option<TileCell> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.cells[(y * self.width + x) as usize]
+    }
+
+    pub fn set_cell(&mut self, x: u32, y: u32, cell: Option<TileCell>) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        self.cells[(y * self.width + x) as usize] = cell;
+    }
+}
+
+/// Layers of tiles on a grid, drawn as sprites. Can be loaded from Tiled,
+/// see `TiledMap::load`.
+pub struct TileMap {
+    pub transform: Transform2d,
+
+    /// Size of a cell, in pixels.
+    pub cell_size: Vector2<f32>,
+
+    pub tile_sets: Vec<TileSet>,
+
+    /// Drawn in order, the first one at the back.
+    pub layers: Vec<TileLayer>,
+
+    /// Drives animated tiles.
+    animation_time: f32,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl TileMap {
+    pub fn new(cell_size: Vector2<f32>) -> Self {
+        Self {
+            transform: Transform2d::default(),
+            cell_size,
+            tile_sets: vec![],
+            layers: vec![],
+            animation_time: 0.0,
+            custom_update: None,
+        }
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    pub fn get_layer_mut(&mut self, name: &str) -> Option<&mut TileLayer> {
+        self.layers.iter_mut().find(|l| l.name == name)
+    }
+}
+
+impl AsNode for TileMap {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::TileMap
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        self.animation_time += dt;
+
+        if let Some(custom_update) = self.custom_update {
+            custom_update(dt, self);
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            for (i, cell) in layer.cells.iter().enumerate() {
+                let Some(cell) = cell else {
+                    continue;
+                };
+
+                let Some(tile_set) = self.tile_sets.get(cell.tile_set) else {
+                    continue;
+                };
+
+                let tile = tile_set.get_animated_tile(cell.tile, self.animation_time);
+                let tile_size =
+                    Vector2::new(tile_set.tile_size.0 as f32, tile_set.tile_size.1 as f32);
+
+                let x = (i as u32 % layer.width) as f32 * self.cell_size.x;
+                let y = (i as u32 / layer.width + 1) as f32 * self.cell_size.y - tile_size.y;
+
+                let mut transform = self.transform;
+                transform.position = self
+                    .transform
+                    .transform_point(layer.offset + Vector2::new(x, y))
+                    .into();
+
+                draw_cmds.extracted.sprites.push(ExtractedSprite2d {
+                    transform,
+                    size: Some(tile_size.into()),
+                    texture_id: tile_set.texture,
+                    region: tile_set.get_region(tile),
+                    centered: false,
+                    flip_x: cell.flip_x,
+                    flip_y: cell.flip_y,
+                });
+            }
+        }
+    }
+}
@@ -1,115 +1,115 @@
-// extern crate lyon;
-//
-// use std::any::Any;
-// use std::fs;
-//
-// use crate::math::transform::Transform2d;
-// use crate::scene::{AsNode, NodeType};
-// use crate::{Camera2d, RenderServer, Singletons};
-// use cgmath::{Vector2, Vector3};
-// use lyon::math::point;
-// use lyon::path::Path;
-// use lyon::tessellation::*;
-// use usvg::TreeParsing;
-// use wgpu::util::DeviceExt;
-// use crate::render::draw_command::DrawCommands;
-// use crate::render::vector_texture::VectorTexture;
-//
-// pub struct VectorSprite {
-//     pub transform: Transform2d,
-//     pub size: cgmath::Vector2<f32>,
-//
-//     svg_data: Option<usvg::Tree>,
-//
-//     need_to_rebuild: bool,
-// }
-//
-// impl VectorSprite {
-//     pub fn default() -> Self {
-//         Self {
-//             transform: Transform2d::default(),
-//             size: Vector2::new(0.0, 0.0),
-//             svg_data: None,
-//             need_to_rebuild: false,
-//         }
-//     }
-//
-//     pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Self {
-//         let data = fs::read(path).expect("No SVG file found!");
-//
-//         let tree: usvg::Tree = usvg::Tree::from_data(&data, &usvg::Options::default()).unwrap();
-//
-//         Self {
-//             transform: Transform2d::default(),
-//             size: Vector2::new(tree.size.width() as f32, tree.size.height() as f32),
-//             svg_data: Some(tree),
-//             need_to_rebuild: true,
-//         }
-//     }
-// }
-//
-// impl AsNode for VectorSprite {
-//     fn node_type(&self) -> NodeType {
-//         NodeType::VectorSprite
-//     }
-//
-//     fn as_any(&self) -> &dyn Any {
-//         self
-//     }
-//
-//     fn as_any_mut(&mut self) -> &mut dyn Any {
-//         self
-//     }
-//
-//     fn ready(&mut self) {}
-//
-//     fn update(&mut self, dt: f32, singletons: &mut Singletons) {
-//         // let translation = cgmath::Matrix4::from_translation(Vector3::new(-1.0, 1.0, 0.0));
-//         //
-//         // let scale = cgmath::Matrix4::from_nonuniform_scale(
-//         //     1.0 / camera_info.view_size.x as f32 * 2.0,
-//         //     -1.0 / camera_info.view_size.y as f32 * 2.0,
-//         //     1.0,
-//         // );
-//         //
-//         // // Note the multiplication direction (left multiplication).
-//         // // So, scale first, translation second.
-//         // self.camera_uniform.proj = (translation * scale).into();
-//     }
-//
-//     fn draw(&self, draw_commands: &mut DrawCommands,
-//     ) {}
-// }
-//
-// pub trait DrawVector<'a> {
-//     fn draw_path(
-//         &mut self,
-//         pipeline: &'a wgpu::RenderPipeline,
-//         mesh: &'a VectorMesh,
-//         camera_bind_group: &'a wgpu::BindGroup,
-//     );
-// }
-//
-// impl<'a, 'b> DrawVector<'b> for wgpu::RenderPass<'a>
-//     where
-//         'b: 'a,
-// {
-//     fn draw_path(
-//         &mut self,
-//         pipeline: &'b wgpu::RenderPipeline,
-//         mesh: &'b VectorMesh,
-//         camera_bind_group: &'b wgpu::BindGroup,
-//     ) {
-//         self.set_pipeline(&pipeline);
-//
-//         // Set vertex buffer for VertexInput.
-//         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-//
-//         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-//
-//         // Bind camera at 0.
-//         self.set_bind_group(0, camera_bind_group, &[]);
-//
-//         self.draw_indexed(0..mesh.index_count, 0, 0..1);
-//     }
-// }
+use crate::math::transform::Transform2d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::VectorTexture;
+use crate::scene::{AsNode, NodeType};
+use crate::window::{InputEvent, InputServer};
+use anyhow::Result;
+use cgmath::Vector2;
+use std::any::Any;
+
+/// Draws a [`VectorTexture`], with its top left at the position.
+///
+/// Tracks the element under the mouse, and the elements clicked, so that SVG shapes
+/// can be used as buttons. Mouse positions and clip paths are in view space, like for UI
+/// nodes, so they line up with the shapes while the 2D camera is at the origin.
+///
+/// Raster images in the SVG show up once `VectorTexture::load_images` is called.
+pub struct VectorSprite {
+    pub transform: Transform2d,
+
+    pub texture: VectorTexture,
+
+    /// Ignores the mouse.
+    pub disabled: bool,
+
+    hovered_element: Option<String>,
+    /// The element the mouse went down on.
+    held_element: Option<String>,
+    clicked_elements: Vec<String>,
+}
+
+impl VectorSprite {
+    pub fn new(texture: VectorTexture) -> Self {
+        Self {
+            transform: Transform2d::default(),
+            texture,
+            disabled: false,
+            hovered_element: None,
+            held_element: None,
+            clicked_elements: vec![],
+        }
+    }
+
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(VectorTexture::from_file(path)?))
+    }
+
+    /// ID of the topmost visible element at a world position.
+    pub fn hit_test(&self, position: Vector2<f32>) -> Option<&str> {
+        self.texture
+            .hit_test(self.transform.inverse_transform_point(position).into())
+    }
+
+    pub fn get_hovered_element(&self) -> Option<&str> {
+        self.hovered_element.as_deref()
+    }
+
+    /// IDs of the elements clicked since the last call, oldest first.
+    pub fn take_clicked_elements(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.clicked_elements)
+    }
+
+    fn hit_test_owned(&self, position: (f32, f32)) -> Option<String> {
+        self.hit_test(Vector2::new(position.0, position.1))
+            .map(|id| id.to_string())
+    }
+}
+
+impl AsNode for VectorSprite {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::VectorSprite
+    }
+
+    fn input(&mut self, input_event: &mut InputEvent, _input_server: &mut InputServer) {
+        if self.disabled {
+            self.hovered_element = None;
+            self.held_element = None;
+            return;
+        }
+
+        match input_event {
+            InputEvent::MouseMotion(motion) => {
+                self.hovered_element = self.hit_test_owned(motion.position);
+            }
+            InputEvent::MouseButton(button) => {
+                if button.button != winit::event::MouseButton::Left {
+                    return;
+                }
+
+                let element = self.hit_test_owned(button.position);
+
+                if button.pressed {
+                    self.held_element = element;
+                } else if let Some(held) = self.held_element.take() {
+                    // Releasing over another element cancels the click.
+                    if element.as_ref() == Some(&held) {
+                        self.clicked_elements.push(held);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        self.texture.draw(&self.transform, draw_cmds);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::math::transform::Transform3d;
+use crate::scene::d3::skeleton::{Pose, Skeleton};
+use cgmath::{InnerSpace, One, Quaternion, Vector3, VectorSpace, Zero};
+use std::rc::Rc;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Keyframe<T> {
+    /// In seconds.
+    pub time: f32,
+    pub value: T,
+}
+
+/// Keyframes of one joint. Channels without keyframes keep the rest transform.
+#[derive(Debug, Clone, Default)]
+pub struct JointTrack {
+    pub joint: usize,
+    pub translations: Vec<Keyframe<Vector3<f32>>>,
+    pub rotations: Vec<Keyframe<Quaternion<f32>>>,
+    pub scales: Vec<Keyframe<Vector3<f32>>>,
+}
+
+/// Linearly interpolate between the keyframes around `time`. Keyframes have to be sorted.
+fn sample_keyframes<T: Copy>(
+    keyframes: &[Keyframe<T>],
+    time: f32,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> Option<T> {
+    let next = keyframes.iter().position(|k| k.time > time);
+
+    match next {
+        _ if keyframes.is_empty() => None,
+        Some(0) => Some(keyframes[0].value),
+        Some(i) => {
+            let (a, b) = (&keyframes[i - 1], &keyframes[i]);
+            let t = (time - a.time) / (b.time - a.time);
+            Some(lerp(a.value, b.value, t))
+        }
+        None => keyframes.last().map(|k| k.value),
+    }
+}
+
+fn sample_track(track: &JointTrack, time: f32, transform: &mut Transform3d) {
+    if let Some(position) = sample_keyframes(&track.translations, time, |a, b, t| a.lerp(b, t)) {
+        transform.position = position;
+    }
+    if let Some(rotation) = sample_keyframes(&track.rotations, time, |a, b, t| {
+        let b = if a.dot(b) < 0.0 { -b } else { b };
+        a.nlerp(b, t)
+    }) {
+        transform.rotation = rotation;
+    }
+    if let Some(scale) = sample_keyframes(&track.scales, time, |a, b, t| a.lerp(b, t)) {
+        transform.scale = scale;
+    }
+}
+
+/// Movement of the root joint, in model space.
+#[derive(Debug, Copy, Clone)]
+pub struct RootMotion {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+}
+
+impl RootMotion {
+    pub fn identity() -> Self {
+        Self {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+        }
+    }
+
+    /// This motion followed by `next`.
+    fn then(self, next: RootMotion) -> RootMotion {
+        RootMotion {
+            translation: self.translation + next.translation,
+            rotation: (next.rotation * self.rotation).normalize(),
+        }
+    }
+
+    fn blend(self, other: RootMotion, t: f32) -> RootMotion {
+        let other_rotation = if self.rotation.dot(other.rotation) < 0.0 {
+            -other.rotation
+        } else {
+            other.rotation
+        };
+
+        RootMotion {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.nlerp(other_rotation, t),
+        }
+    }
+}
+
+/// Joint animation of a skeleton, e.g. a walk cycle.
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    /// In seconds.
+    pub duration: f32,
+    /// Start over after reaching the end, or hold the last frame.
+    pub looping: bool,
+    pub tracks: Vec<JointTrack>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, duration: f32, tracks: Vec<JointTrack>) -> Self {
+        Self {
+            name: name.to_string(),
+            duration,
+            looping: true,
+            tracks,
+        }
+    }
+
+    /// The pose at `time` seconds. Joints without tracks are left as in `rest`.
+    pub fn sample(&self, time: f32, rest: &Pose) -> Pose {
+        let mut pose = rest.clone();
+
+        for track in &self.tracks {
+            if let Some(transform) = pose.transforms.get_mut(track.joint) {
+                sample_track(track, time, transform);
+            }
+        }
+
+        pose
+    }
+
+    /// Transform of a single joint at `time` seconds.
+    fn sample_joint(&self, joint: usize, time: f32, rest: Transform3d) -> Transform3d {
+        let mut transform = rest;
+
+        if let Some(track) = self.tracks.iter().find(|t| t.joint == joint) {
+            sample_track(track, time, &mut transform);
+        }
+
+        transform
+    }
+
+    /// How far a joint moves from `from` to `to` seconds, without wrapping.
+    fn joint_motion(&self, joint: usize, rest: Transform3d, from: f32, to: f32) -> RootMotion {
+        let start = self.sample_joint(joint, from, rest);
+        let end = self.sample_joint(joint, to, rest);
+
+        RootMotion {
+            translation: end.position - start.position,
+            rotation: (end.rotation * start.rotation.conjugate()).normalize(),
+        }
+    }
+
+    /// Advance `time` by `dt`, wrapping or clamping it to the clip.
+    pub(crate) fn advance(&self, time: f32, dt: f32) -> f32 {
+        let time = time + dt;
+
+        if self.duration <= 0.0 {
+            0.0
+        } else if self.looping {
+            time.rem_euclid(self.duration)
+        } else {
+            time.clamp(0.0, self.duration)
+        }
+    }
+}
+
+/// A clip being played, and where it's at.
+#[derive(Debug, Clone)]
+struct Playback {
+    clip: Rc<AnimationClip>,
+    time: f32,
+}
+
+impl Playback {
+    /// Advance by `dt` seconds and return how far `root` moved along the way.
+    fn advance(&mut self, dt: f32, root: Option<(usize, Transform3d)>) -> RootMotion {
+        let clip = &self.clip;
+        let from = self.time;
+        let to = clip.advance(from, dt);
+        self.time = to;
+
+        let Some((joint, rest)) = root else {
+            return RootMotion::identity();
+        };
+        let motion = |from, to| clip.joint_motion(joint, rest, from, to);
+
+        // Wrapping around, the motion goes on from the other end of the clip.
+        if clip.looping && dt > 0.0 && to < from {
+            motion(from, clip.duration).then(motion(0.0, to))
+        } else if clip.looping && dt < 0.0 && to > from {
+            motion(from, 0.0).then(motion(clip.duration, to))
+        } else {
+            motion(from, to)
+        }
+    }
+}
+
+/// Plays one clip at a time on a skeleton, optionally crossfading from the previous one.
+///
+/// For blending several clips by parameters, use an [`AnimationTree`](crate::scene::AnimationTree).
+pub struct AnimationPlayer {
+    clips: Vec<Rc<AnimationClip>>,
+    current: Option<Playback>,
+    /// The clip fading out, and how far the crossfade is, from 0.0 to 1.0.
+    fading: Option<(Playback, f32)>,
+    fade_duration: f32,
+    /// Playback rate. Negative plays backwards.
+    pub speed: f32,
+    pub paused: bool,
+    /// Take the motion of the root joint out of the skeleton, so it can move the node
+    /// instead. See [`AnimationPlayer::take_root_motion`].
+    pub root_motion: bool,
+    /// Joint whose translation and rotation is extracted.
+    pub root_joint: usize,
+    /// Extracted since the last take.
+    pending_root_motion: RootMotion,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self {
+            clips: vec![],
+            current: None,
+            fading: None,
+            fade_duration: 0.0,
+            speed: 1.0,
+            paused: false,
+            root_motion: false,
+            root_joint: 0,
+            pending_root_motion: RootMotion::identity(),
+        }
+    }
+
+    /// Add a clip, replacing one with the same name.
+    pub fn add_clip(&mut self, clip: AnimationClip) -> Rc<AnimationClip> {
+        let clip = Rc::new(clip);
+
+        self.clips.retain(|c| c.name != clip.name);
+        self.clips.push(clip.clone());
+
+        clip
+    }
+
+    /// Shared with animation trees.
+    pub fn get_clip(&self, name: &str) -> Option<Rc<AnimationClip>> {
+        self.clips.iter().find(|c| c.name == name).cloned()
+    }
+
+    pub fn get_clip_names(&self) -> Vec<&str> {
+        self.clips.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// Play a clip from the start. Returns false if there is no clip by that name.
+    pub fn play(&mut self, name: &str) -> bool {
+        self.crossfade(name, 0.0)
+    }
+
+    /// Blend from the current clip to another over `duration` seconds.
+    pub fn crossfade(&mut self, name: &str, duration: f32) -> bool {
+        let Some(clip) = self.get_clip(name) else {
+            return false;
+        };
+
+        self.fading = self
+            .current
+            .take()
+            .filter(|_| duration > 0.0)
+            .map(|playback| (playback, 0.0));
+        self.fade_duration = duration;
+        self.current = Some(Playback { clip, time: 0.0 });
+
+        true
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.fading = None;
+    }
+
+    pub fn get_current_clip(&self) -> Option<&str> {
+        self.current.as_ref().map(|p| p.clip.name.as_str())
+    }
+
+    /// Seconds into the current clip.
+    pub fn get_time(&self) -> f32 {
+        self.current.as_ref().map_or(0.0, |p| p.time)
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        if let Some(playback) = &mut self.current {
+            playback.time = playback.clip.advance(time, 0.0);
+        }
+    }
+
+    /// Whether a non-looping clip has reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.current.as_ref().is_none_or(|p| {
+            let at_end = if self.speed < 0.0 {
+                p.time <= 0.0
+            } else {
+                p.time >= p.clip.duration
+            };
+
+            !p.clip.looping && at_end
+        })
+    }
+
+    /// Root motion extracted by updates since the last call. Models apply it to themselves.
+    pub fn take_root_motion(&mut self) -> RootMotion {
+        std::mem::replace(&mut self.pending_root_motion, RootMotion::identity())
+    }
+
+    /// Advance the clips and pose the skeleton.
+    pub fn update(&mut self, dt: f32, skeleton: &mut Skeleton) {
+        let Some(current) = &mut self.current else {
+            return;
+        };
+
+        let dt = if self.paused { 0.0 } else { dt * self.speed };
+
+        let rest = skeleton.get_rest_pose();
+        let root = rest
+            .transforms
+            .get(self.root_joint)
+            .filter(|_| self.root_motion)
+            .map(|transform| (self.root_joint, *transform));
+
+        let mut motion = current.advance(dt, root);
+        let mut pose = current.clip.sample(current.time, &rest);
+
+        if let Some((fading, progress)) = &mut self.fading {
+            let fading_motion = fading.advance(dt, root);
+            *progress += dt.abs() / self.fade_duration;
+
+            if *progress >= 1.0 {
+                self.fading = None;
+            } else {
+                pose = fading
+                    .clip
+                    .sample(fading.time, &rest)
+                    .blend(&pose, *progress);
+                motion = fading_motion.blend(motion, *progress);
+            }
+        }
+
+        // The root stays in place, the node moves instead.
+        if let Some((joint, rest)) = root {
+            pose.transforms[joint].position = rest.position;
+            pose.transforms[joint].rotation = rest.rotation;
+
+            self.pending_root_motion = self.pending_root_motion.then(motion);
+        }
+
+        skeleton.set_pose(pose);
+    }
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
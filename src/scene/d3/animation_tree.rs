@@ -0,0 +1,279 @@
+use crate::scene::d3::animation::AnimationClip;
+use crate::scene::d3::skeleton::{Pose, Skeleton};
+use cgmath::{MetricSpace, Vector2};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Clips placed along one parameter, e.g. idle at 0, walk at 2 and run at 5 by speed.
+/// The two clips around the parameter value are blended.
+#[derive(Debug, Clone)]
+pub struct BlendSpace1d {
+    pub parameter: String,
+    /// Sorted by position.
+    points: Vec<(f32, Rc<AnimationClip>)>,
+}
+
+impl BlendSpace1d {
+    pub fn new(parameter: &str) -> Self {
+        Self {
+            parameter: parameter.to_string(),
+            points: vec![],
+        }
+    }
+
+    pub fn add_point(mut self, position: f32, clip: Rc<AnimationClip>) -> Self {
+        let index = self.points.partition_point(|(p, _)| *p < position);
+        self.points.insert(index, (position, clip));
+        self
+    }
+
+    fn weights(&self, value: f32) -> Vec<(Rc<AnimationClip>, f32)> {
+        let next = self.points.iter().position(|(p, _)| *p > value);
+
+        match next {
+            _ if self.points.is_empty() => vec![],
+            Some(0) => vec![(self.points[0].1.clone(), 1.0)],
+            Some(i) => {
+                let (a, b) = (&self.points[i - 1], &self.points[i]);
+                let t = (value - a.0) / (b.0 - a.0);
+                vec![(a.1.clone(), 1.0 - t), (b.1.clone(), t)]
+            }
+            None => vec![(self.points.last().unwrap().1.clone(), 1.0)],
+        }
+    }
+}
+
+/// Clips placed on a plane of two parameters, e.g. strafing by velocity along X and Z.
+/// Clips are weighted by inverse squared distance to the parameter point.
+#[derive(Debug, Clone)]
+pub struct BlendSpace2d {
+    pub parameter_x: String,
+    pub parameter_y: String,
+    points: Vec<(Vector2<f32>, Rc<AnimationClip>)>,
+}
+
+impl BlendSpace2d {
+    pub fn new(parameter_x: &str, parameter_y: &str) -> Self {
+        Self {
+            parameter_x: parameter_x.to_string(),
+            parameter_y: parameter_y.to_string(),
+            points: vec![],
+        }
+    }
+
+    pub fn add_point(mut self, position: Vector2<f32>, clip: Rc<AnimationClip>) -> Self {
+        self.points.push((position, clip));
+        self
+    }
+
+    fn weights(&self, value: Vector2<f32>) -> Vec<(Rc<AnimationClip>, f32)> {
+        // Right on a point.
+        if let Some((_, clip)) = self.points.iter().find(|(p, _)| p.distance2(value) < 1e-6) {
+            return vec![(clip.clone(), 1.0)];
+        }
+
+        self.points
+            .iter()
+            .map(|(p, clip)| (clip.clone(), 1.0 / p.distance2(value)))
+            .collect()
+    }
+}
+
+/// What a state of an animation tree plays.
+#[derive(Debug, Clone)]
+pub enum AnimationTreeNode {
+    Clip(Rc<AnimationClip>),
+    BlendSpace1d(BlendSpace1d),
+    BlendSpace2d(BlendSpace2d),
+}
+
+/// A state that is playing or fading out.
+#[derive(Debug, Clone)]
+struct ActiveState {
+    state: usize,
+    /// Progress through the clips, from 0.0 to 1.0. Clips of a blend space stay in sync,
+    /// so feet land at the same time in a walk and a run.
+    phase: f32,
+    weight: f32,
+}
+
+/// Animation states with crossfade transitions between them, each playing a clip or a
+/// blend space driven by parameters.
+///
+/// ```ignore
+/// let locomotion = BlendSpace1d::new("speed")
+///     .add_point(0.0, idle)
+///     .add_point(2.0, walk)
+///     .add_point(5.0, run);
+///
+/// let mut tree = AnimationTree::new();
+/// tree.add_state("move", AnimationTreeNode::BlendSpace1d(locomotion));
+/// tree.add_state("jump", AnimationTreeNode::Clip(jump));
+/// tree.travel("move", 0.0);
+///
+/// // Each frame.
+/// tree.set_parameter("speed", velocity.magnitude());
+/// ```
+pub struct AnimationTree {
+    states: Vec<(String, AnimationTreeNode)>,
+    parameters: HashMap<String, f32>,
+    /// The last one is the state being transitioned to.
+    active: Vec<ActiveState>,
+    /// Of the current transition, in seconds.
+    fade_duration: f32,
+    /// Playback rate.
+    pub speed: f32,
+}
+
+impl AnimationTree {
+    pub fn new() -> Self {
+        Self {
+            states: vec![],
+            parameters: HashMap::new(),
+            active: vec![],
+            fade_duration: 0.0,
+            speed: 1.0,
+        }
+    }
+
+    /// Add a state, replacing one with the same name.
+    pub fn add_state(&mut self, name: &str, node: AnimationTreeNode) {
+        match self.states.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = node,
+            None => self.states.push((name.to_string(), node)),
+        }
+    }
+
+    pub fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.insert(name.to_string(), value);
+    }
+
+    /// Unset parameters are 0.0.
+    pub fn get_parameter(&self, name: &str) -> f32 {
+        self.parameters.get(name).copied().unwrap_or(0.0)
+    }
+
+    /// The state being played or transitioned to.
+    pub fn get_current_state(&self) -> Option<&str> {
+        self.active
+            .last()
+            .map(|active| self.states[active.state].0.as_str())
+    }
+
+    /// Crossfade to a state over `duration` seconds. Returns false if there is no state by
+    /// that name.
+    pub fn travel(&mut self, name: &str, duration: f32) -> bool {
+        let Some(state) = self.states.iter().position(|(n, _)| n == name) else {
+            return false;
+        };
+
+        if self.active.last().is_some_and(|a| a.state == state) {
+            return true;
+        }
+
+        // A state still fading out picks up where it is.
+        let active = match self.active.iter().position(|a| a.state == state) {
+            Some(i) => self.active.remove(i),
+            None => ActiveState {
+                state,
+                phase: 0.0,
+                weight: 0.0,
+            },
+        };
+
+        if duration <= 0.0 || self.active.is_empty() {
+            self.active.clear();
+            self.active.push(ActiveState {
+                weight: 1.0,
+                ..active
+            });
+        } else {
+            self.active.push(active);
+        }
+        self.fade_duration = duration;
+
+        true
+    }
+
+    /// Clips of a state and their weights, for the current parameters.
+    fn clip_weights(&self, state: usize) -> Vec<(Rc<AnimationClip>, f32)> {
+        match &self.states[state].1 {
+            AnimationTreeNode::Clip(clip) => vec![(clip.clone(), 1.0)],
+            AnimationTreeNode::BlendSpace1d(space) => {
+                space.weights(self.get_parameter(&space.parameter))
+            }
+            AnimationTreeNode::BlendSpace2d(space) => space.weights(Vector2::new(
+                self.get_parameter(&space.parameter_x),
+                self.get_parameter(&space.parameter_y),
+            )),
+        }
+    }
+
+    /// Advance the transition and the active states, then pose the skeleton.
+    pub fn update(&mut self, dt: f32, skeleton: &mut Skeleton) {
+        let Some(target) = self.active.last() else {
+            return;
+        };
+
+        let dt = dt * self.speed;
+
+        // Shift weight to the target, scaling down the others so they still add up to 1.
+        if target.weight < 1.0 {
+            let old = target.weight;
+            let new = (old + dt.abs() / self.fade_duration).min(1.0);
+            let scale = (1.0 - new) / (1.0 - old);
+
+            let count = self.active.len();
+            for active in &mut self.active[..count - 1] {
+                active.weight *= scale;
+            }
+            self.active[count - 1].weight = new;
+
+            self.active.retain(|a| a.weight > 0.0);
+        }
+
+        let rest = skeleton.get_rest_pose();
+        let mut poses = vec![];
+
+        for i in 0..self.active.len() {
+            let clips = self.clip_weights(self.active[i].state);
+            let total: f32 = clips.iter().map(|(_, w)| w).sum();
+            if total <= 0.0 {
+                continue;
+            }
+
+            // Weighted clip duration, so the phase moves at the blended speed.
+            let duration: f32 = clips.iter().map(|(c, w)| c.duration * w).sum::<f32>() / total;
+            let looping = clips.iter().all(|(c, _)| c.looping);
+
+            let active = &mut self.active[i];
+            if duration > 0.0 {
+                let phase = active.phase + dt / duration;
+                active.phase = if looping {
+                    phase.rem_euclid(1.0)
+                } else {
+                    phase.clamp(0.0, 1.0)
+                };
+            }
+
+            let clip_poses: Vec<(Pose, f32)> = clips
+                .iter()
+                .map(|(clip, w)| (clip.sample(active.phase * clip.duration, &rest), *w))
+                .collect();
+
+            if let Some(pose) = Pose::blend_weighted(&clip_poses) {
+                poses.push((pose, active.weight));
+            }
+        }
+
+        if let Some(pose) = Pose::blend_weighted(&poses) {
+            skeleton.set_pose(pose);
+        }
+    }
+}
+
+impl Default for AnimationTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
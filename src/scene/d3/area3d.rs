@@ -0,0 +1,104 @@
+use crate::core::singleton::Singletons;
+use crate::math::transform::Transform3d;
+use crate::physics::Shape3d;
+use crate::scene::NodeId;
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use std::any::Any;
+use std::collections::BTreeSet;
+
+/// A trigger volume that reports collision shapes and other areas entering and exiting it.
+pub struct Area3d {
+    pub node_3d: Node3d,
+
+    pub shape: Shape3d,
+
+    /// Layers this area is in, so other areas can detect it.
+    pub collision_layer: u32,
+
+    /// Layers this area detects.
+    pub collision_mask: u32,
+
+    overlapping: BTreeSet<NodeId>,
+
+    /// Called when a node starts overlapping this area.
+    pub on_enter: Option<fn(&mut Self, NodeId)>,
+
+    /// Called when a node stops overlapping this area.
+    pub on_exit: Option<fn(&mut Self, NodeId)>,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Area3d {
+    pub fn new(shape: Shape3d) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            shape,
+            collision_layer: 1,
+            collision_mask: 1,
+            overlapping: BTreeSet::new(),
+            on_enter: None,
+            on_exit: None,
+            custom_update: None,
+        }
+    }
+
+    /// Ordered by ID, like the enter and exit events.
+    pub fn get_overlapping_nodes(&self) -> Vec<NodeId> {
+        self.overlapping.iter().copied().collect()
+    }
+
+    pub fn overlaps_node(&self, node: NodeId) -> bool {
+        self.overlapping.contains(&node)
+    }
+
+    /// Replace the overlapping set and fire enter/exit events for the difference.
+    pub(crate) fn set_overlapping(&mut self, overlapping: BTreeSet<NodeId>) {
+        let entered: Vec<NodeId> = overlapping.difference(&self.overlapping).copied().collect();
+        let exited: Vec<NodeId> = self.overlapping.difference(&overlapping).copied().collect();
+
+        self.overlapping = overlapping;
+
+        if let Some(on_exit) = self.on_exit {
+            for id in exited {
+                on_exit(self, id);
+            }
+        }
+
+        if let Some(on_enter) = self.on_enter {
+            for id in entered {
+                on_enter(self, id);
+            }
+        }
+    }
+}
+
+impl AsNode for Area3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Area3d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+}
+
+impl AsNode3d for Area3d {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
@@ -0,0 +1,60 @@
+use crate::math::transform::Transform3d;
+use crate::scene::NodeId;
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use std::any::Any;
+
+/// Follows a joint of a model's skeleton, e.g. a hand or a head, carrying its children
+/// along. For swords, hats and particle effects on animated characters.
+pub struct BoneAttachment {
+    pub node_3d: Node3d,
+
+    /// The model whose skeleton is followed.
+    pub model: Option<NodeId>,
+
+    /// Name of the joint to follow.
+    pub joint: String,
+
+    /// Relative to the joint.
+    pub offset: Transform3d,
+}
+
+impl BoneAttachment {
+    pub fn new(model: NodeId, joint: &str) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            model: Some(model),
+            joint: joint.to_string(),
+            offset: Transform3d::default(),
+        }
+    }
+
+    /// Move to the joint's world transform, and return how to carry a child along.
+    /// Called by the world after animations and IK are applied.
+    pub(crate) fn follow(&mut self, transform: Transform3d) -> impl Fn(Transform3d) -> Transform3d {
+        self.node_3d.move_to(transform)
+    }
+}
+
+impl AsNode for BoneAttachment {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::BoneAttachment
+    }
+}
+
+impl AsNode3d for BoneAttachment {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
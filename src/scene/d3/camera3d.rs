@@ -1,8 +1,14 @@
 use crate::core::singleton::Singletons;
-use crate::render::camera::{CameraType, CameraUniform, PerspectiveProjection, Projection};
+use crate::math::frustum::Frustum;
+use crate::math::transform::Transform3d;
+use crate::math::types::{Vec2, Vec3};
+use crate::physics::Ray3d;
+use crate::render::camera::{
+    CameraType, CameraUniform, OffAxisProjection, PerspectiveProjection, Projection,
+};
 use crate::render::draw_command::DrawCommands;
 use crate::render::RenderServer;
-use crate::scene::{AsNode, NodeType};
+use crate::scene::{AsNode, CameraShake, NodeType};
 use crate::window::{InputEvent, InputServer, Key};
 use cgmath::num_traits::clamp;
 use cgmath::*;
@@ -30,18 +36,20 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
 pub struct Camera3d {
-    position: Point3<f32>,
-    yaw: Rad<f32>,
-    pitch: Rad<f32>,
+    /// Looks along [`Transform3d::forward`]. Scale is ignored.
+    pub transform: Transform3d,
     fov: f32,
 
+    pub shake: CameraShake,
+
     projection: Projection,
 
     controller: Camera3dController,
 }
 
 impl Camera3d {
-    pub fn new<V: Into<Point3<f32>>, Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(
+    /// Yaw 0 looks along +X and goes towards +Z, pitch goes up.
+    pub fn new<V: Into<Vec3>, Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(
         position: V,
         yaw: Y,
         pitch: P,
@@ -52,50 +60,108 @@ impl Camera3d {
 
         let fov = 45.0;
 
-        let projection = PerspectiveProjection::new(
+        let mut projection: Projection = PerspectiveProjection::new(
             config.width, // Render target size
             config.height,
             cgmath::Deg(fov),
             0.1,
             100.0,
-        );
+        )
+        .into();
+        projection.set_reverse_z(render_server.get_settings().reverse_z);
 
         let controller = Camera3dController::new(4.0, 0.4);
 
+        let mut transform = Transform3d::from_position(position);
+        transform.rotation = yaw_pitch_to_rotation(yaw.into(), pitch.into());
+
         Self {
-            position: position.into(),
-            yaw: yaw.into(),
-            pitch: pitch.into(),
+            transform,
             fov,
-            projection: projection.into(),
+            shake: CameraShake::new(0.1, 2.0),
+            projection,
             controller,
         }
     }
 
+    /// Project off-axis, e.g. to render one eye of a headset whose views the app gets from
+    /// the XR runtime.
+    pub fn set_off_axis_projection(&mut self, projection: OffAxisProjection) {
+        self.projection = projection.into();
+    }
+
+    /// Shake the camera, see [`CameraShake::add_trauma`].
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.shake.add_trauma(amount);
+    }
+
+    /// Turn to face `target`, keeping the horizon level.
+    pub fn look_at(&mut self, target: impl Into<Vec3>) {
+        self.transform.look_at(target, Vec3::Y);
+    }
+
     /// Get view matrix.
     pub fn calc_view_matrix(&self) -> Matrix4<f32> {
-        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
-        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
-
-        // Refer to https://learnopengl.com/Getting-started/Camera.
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        )
+        let shake = self.shake.sample();
+
+        // Yaw around the world up and pitch around the camera's right, like the controller.
+        let rotation = Quaternion::from_angle_y(Deg(-shake.rotation.y))
+            * self.transform.rotation
+            * Quaternion::from_angle_x(Deg(shake.rotation.x));
+
+        Matrix4::from(rotation.conjugate())
+            * Matrix4::from_translation(-(self.transform.position + shake.translation))
     }
 
     pub fn calc_view_matrix_without_pos(&self) -> Matrix4<f32> {
-        Matrix4::look_to_rh(
-            Point3::new(0.0, 0.0, 0.0),
-            Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize(),
-            Vector3::unit_y(),
-        )
+        Matrix4::from(self.transform.rotation.conjugate())
     }
 
     pub fn when_view_size_changes(&mut self, new_size: Vector2<u32>) {
         self.projection.update(new_size.x as f32, new_size.y as f32);
     }
+
+    /// Ray from the camera through a point in view (pixel) space, e.g. the mouse position.
+    pub fn screen_to_ray(&self, position: impl Into<Vec2>, view_size: Vector2<u32>) -> Ray3d {
+        let position = position.into();
+        let x = position.x / view_size.x as f32 * 2.0 - 1.0;
+        let y = 1.0 - position.y / view_size.y as f32 * 2.0;
+
+        let inverse = (self.projection.calc_matrix() * self.calc_view_matrix())
+            .invert()
+            .unwrap();
+
+        let (near_z, far_z) = if self.projection.is_reverse_z() {
+            (1.0, 0.0)
+        } else {
+            (0.0, 1.0)
+        };
+
+        let near = inverse * Vector4::new(x, y, near_z, 1.0);
+        let far = inverse * Vector4::new(x, y, far_z, 1.0);
+        let near = near.truncate() / near.w;
+        let far = far.truncate() / far.w;
+
+        Ray3d::new(near, far - near)
+    }
+
+    /// What the camera sees, for culling.
+    pub fn get_frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.projection.calc_matrix() * self.calc_view_matrix())
+    }
+
+    /// Size of a view pixel in world units at a point, for keeping things a constant size on screen.
+    pub fn pixel_size_at(&self, point: impl Into<Vec3>, view_size: Vector2<u32>) -> f32 {
+        let projection = self.projection.calc_matrix();
+        let clip = projection * self.calc_view_matrix() * Vector4::from(point.into().extend(1.0));
+
+        2.0 * clip.w.max(0.0001) / (projection.y.y * view_size.y as f32)
+    }
+}
+
+/// Rotation that turns -Z towards the yaw and pitch, see [`Camera3d::new`].
+fn yaw_pitch_to_rotation(yaw: Rad<f32>, pitch: Rad<f32>) -> Quaternion<f32> {
+    Quaternion::from_angle_y(-yaw - Rad(FRAC_PI_2)) * Quaternion::from_angle_x(pitch)
 }
 
 // We need this for Rust to store our data correctly for the shaders.
@@ -270,20 +336,19 @@ impl AsNode for Camera3d {
             singletons.render_server.surface_config.width as f32,
             singletons.render_server.surface_config.height as f32,
         );
+        self.projection
+            .set_reverse_z(singletons.render_server.get_settings().reverse_z);
 
         // Update camera transform.
         {
-            // Move forward/backward and left/right.
-            let (yaw_sin, yaw_cos) = self.yaw.0.sin_cos();
-            let (pitch_sin, pitch_cos) = self.pitch.0.sin_cos();
-            let forward =
-                Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-            let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-            self.position += forward
+            // Move forward/backward and left/right. There is no roll, so right stays level.
+            let forward = Vector3::from(self.transform.forward());
+            let right = Vector3::from(self.transform.right());
+            self.transform.position += forward
                 * (self.controller.amount_forward - self.controller.amount_backward)
                 * self.controller.speed
                 * dt;
-            self.position += right
+            self.transform.position += right
                 * (self.controller.amount_right - self.controller.amount_left)
                 * self.controller.speed
                 * dt;
@@ -295,30 +360,35 @@ impl AsNode for Camera3d {
 
             // Move up/down. Since we don't use roll, we can just
             // modify the y coordinate directly.
-            self.position.y += (self.controller.amount_up - self.controller.amount_down)
+            self.transform.position.y += (self.controller.amount_up - self.controller.amount_down)
                 * self.controller.speed
                 * dt;
 
-            // Horizontal rotation.
-            self.yaw += Rad(self.controller.rotate_horizontal) * self.controller.sensitivity * dt;
-
-            // Vertical rotation.
-            self.pitch += Rad(-self.controller.rotate_vertical) * self.controller.sensitivity * dt;
+            // Horizontal rotation, around the world up.
+            let yaw = Rad(-self.controller.rotate_horizontal) * self.controller.sensitivity * dt;
+            self.transform.rotation =
+                (Quaternion::from_angle_y(yaw) * self.transform.rotation).normalize();
+
+            // Vertical rotation, around the camera's right. Keep the camera's angle from
+            // going too high/low.
+            let pitch = forward.y.clamp(-1.0, 1.0).asin();
+            let new_pitch = clamp(
+                pitch - self.controller.rotate_vertical * self.controller.sensitivity * dt,
+                -SAFE_FRAC_PI_2,
+                SAFE_FRAC_PI_2,
+            );
+            self.transform
+                .rotate_local(Quaternion::from_angle_x(Rad(new_pitch - pitch)));
 
             // If process_mouse isn't called every frame, these values
             // will not get set to zero, and the camera will rotate
             // when moving in a non cardinal direction.
             self.controller.rotate_horizontal = 0.0;
             self.controller.rotate_vertical = 0.0;
-
-            // Keep the camera's angle from going too high/low.
-            if self.pitch < -Rad(SAFE_FRAC_PI_2) {
-                self.pitch = -Rad(SAFE_FRAC_PI_2);
-            } else if self.pitch > Rad(SAFE_FRAC_PI_2) {
-                self.pitch = Rad(SAFE_FRAC_PI_2);
-            }
         }
 
+        self.shake.update(dt);
+
         // Update camera uniform and its buffer.
         {}
     }
@@ -329,7 +399,7 @@ impl AsNode for Camera3d {
         let view_mat = self.calc_view_matrix();
         let proj_mat = self.projection.calc_matrix();
 
-        uniform.view_position = self.position.to_homogeneous().into();
+        uniform.view_position = self.transform.position.extend(1.0).into();
         uniform.view = view_mat.into();
         uniform.proj = proj_mat.into();
 
@@ -337,4 +407,35 @@ impl AsNode for Camera3d {
 
         draw_cmds.extracted.cameras.add(CameraType::D3, uniform);
     }
+
+    fn save_state(&self) -> Option<serde_json::Value> {
+        let Transform3d {
+            position: p,
+            rotation: r,
+            ..
+        } = self.transform;
+
+        Some(serde_json::json!({
+            "position": [p.x, p.y, p.z],
+            "rotation": [r.v.x, r.v.y, r.v.z, r.s],
+        }))
+    }
+
+    fn load_state(&mut self, state: &serde_json::Value) -> anyhow::Result<()> {
+        let position: [f32; 3] = serde_json::from_value(state["position"].clone())?;
+        self.transform.position = Vector3::from(position);
+
+        self.transform.rotation = match state.get("rotation") {
+            Some(rotation) => {
+                let [x, y, z, w]: [f32; 4] = serde_json::from_value(rotation.clone())?;
+                Quaternion::new(w, x, y, z)
+            }
+            // Saved before the camera had a transform.
+            None => yaw_pitch_to_rotation(
+                Rad(serde_json::from_value(state["yaw"].clone())?),
+                Rad(serde_json::from_value(state["pitch"].clone())?),
+            ),
+        };
+        Ok(())
+    }
 }
@@ -0,0 +1,58 @@
+use crate::core::singleton::Singletons;
+use crate::math::transform::Transform3d;
+use crate::physics::Shape3d;
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use std::any::Any;
+
+/// A static or kinematic collider that can be hit by ray queries and detected by areas.
+pub struct CollisionShape3d {
+    pub node_3d: Node3d,
+
+    pub shape: Shape3d,
+
+    /// Layers this shape is in. Queries and areas only see shapes matching their mask.
+    pub collision_layer: u32,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl CollisionShape3d {
+    pub fn new(shape: Shape3d) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            shape,
+            collision_layer: 1,
+            custom_update: None,
+        }
+    }
+}
+
+impl AsNode for CollisionShape3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::CollisionShape3d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+}
+
+impl AsNode3d for CollisionShape3d {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
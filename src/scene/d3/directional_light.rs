@@ -19,6 +19,8 @@ pub struct DirectionalLight {
     pub transform: Transform3d,
     pub color: ColorU,
     pub strength: f32,
+    /// Shadows are set up in `RenderWorld::shadow_settings`.
+    pub cast_shadows: bool,
     // pub(crate) sprite: Sprite3d,
 }
 
@@ -31,6 +33,7 @@ impl DirectionalLight {
             transform: Transform3d::default(),
             color: ColorU::white(),
             strength: 1.0,
+            cast_shadows: true,
             // sprite: sprite3d,
         }
     }
@@ -72,5 +75,6 @@ impl AsNode for DirectionalLight {
         };
 
         draw_cmds.extracted.lights.directional_light = Some(directional_light);
+        draw_cmds.extracted.lights.directional_light_shadows = self.cast_shadows;
     }
 }
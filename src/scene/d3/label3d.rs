@@ -0,0 +1,143 @@
+use crate::asset::AssetHandle;
+use crate::core::singleton::Singletons;
+use crate::math::color::ColorU;
+use crate::math::transform::Transform2d;
+use crate::math::transform::Transform3d;
+use crate::render::atlas::Atlas;
+use crate::render::draw_command::DrawCommands;
+use crate::render::label3d::ExtractedLabel3d;
+use crate::render::sprite3d::BillboardMode;
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use std::any::Any;
+
+/// Text rendered in world space, e.g. name tags and annotations.
+pub struct Label3d {
+    pub node_3d: Node3d,
+
+    text: String,
+
+    text_is_dirty: bool,
+
+    /// Treat the text as a message ID and show its translation, see `TextServer::tr`.
+    pub auto_translate: bool,
+
+    /// Text server atlas version the current layout was made with.
+    atlas_version: u32,
+
+    font: Option<AssetHandle>,
+
+    leading: f32,
+
+    pub color: ColorU,
+
+    pub billboard_mode: BillboardMode,
+
+    /// Size of a font pixel in world units.
+    pub pixel_size: f32,
+
+    /// If disabled, the label is drawn on top of everything.
+    pub depth_test: bool,
+
+    /// For rendering glyph sprites.
+    atlas: Option<Atlas>,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Label3d {
+    pub fn new(text: String) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            text,
+            text_is_dirty: true,
+            auto_translate: true,
+            atlas_version: 0,
+            font: None,
+            leading: 20.0,
+            color: ColorU::white(),
+            billboard_mode: BillboardMode::Disabled,
+            pixel_size: 0.01,
+            depth_test: true,
+            atlas: None,
+            custom_update: None,
+        }
+    }
+
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+        self.text_is_dirty = true;
+    }
+
+    /// Use a font loaded by `AssetServer::load_font`.
+    pub fn set_font(&mut self, font: AssetHandle) {
+        self.font = Some(font);
+        self.text_is_dirty = true;
+    }
+}
+
+impl AsNode for Label3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Label3d
+    }
+
+    fn update(&mut self, dt: f32, singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+
+        let atlas_version = singletons.text_server.get_atlas_version();
+        if self.text_is_dirty || self.atlas_version != atlas_version {
+            let text = if self.auto_translate {
+                singletons.text_server.tr(&self.text)
+            } else {
+                self.text.clone()
+            };
+
+            self.atlas = Some(
+                singletons.text_server.get_atlas(
+                    &text,
+                    self.font
+                        .as_ref()
+                        .and_then(|f| f.get_font())
+                        .map(str::to_string),
+                    Transform2d::default(),
+                    self.leading,
+                ),
+            );
+
+            self.text_is_dirty = false;
+            self.atlas_version = atlas_version;
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if let Some(atlas) = &self.atlas {
+            draw_cmds.extracted.labels3d.push(ExtractedLabel3d {
+                atlas: atlas.clone(),
+                transform: self.node_3d.transform,
+                color: self.color.to_f32().to_vec4(),
+                pixel_size: self.pixel_size,
+                billboard_mode: self.billboard_mode,
+                depth_test: self.depth_test,
+            });
+        }
+    }
+}
+
+impl AsNode3d for Label3d {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
@@ -1,14 +1,41 @@
+pub(crate) mod animation;
+pub(crate) mod animation_tree;
+pub(crate) mod area3d;
+pub(crate) mod bone_attachment;
 pub(crate) mod camera3d;
+pub(crate) mod collision_shape3d;
 pub(crate) mod directional_light;
+pub(crate) mod label3d;
 pub(crate) mod model;
 mod node_3d;
+pub(crate) mod path3d;
 pub(crate) mod point_light;
+pub(crate) mod reflection_probe;
+pub(crate) mod skeleton;
 pub(crate) mod sky;
 pub(crate) mod sprite3d;
+pub(crate) mod text3d_mesh;
+pub(crate) mod trail3d;
+pub(crate) mod transform_gizmo;
+pub(crate) mod water_plane;
 
+pub use animation::*;
+pub use animation_tree::*;
+pub use area3d::*;
+pub use bone_attachment::*;
 pub use camera3d::*;
+pub use collision_shape3d::*;
 pub use directional_light::*;
+pub use label3d::*;
 pub use model::*;
 pub use node_3d::*;
+pub use path3d::*;
 pub use point_light::*;
+pub use reflection_probe::*;
+pub use skeleton::*;
 pub use sky::*;
+pub use sprite3d::*;
+pub use text3d_mesh::*;
+pub use trail3d::*;
+pub use transform_gizmo::*;
+pub use water_plane::*;
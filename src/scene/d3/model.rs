@@ -8,14 +8,28 @@ use std::time::Instant;
 use tobj::LoadOptions;
 use wgpu::util::DeviceExt;
 
+use crate::asset::{load_texture, AssetHandle, AssetKey, AssetRegistry};
+use crate::core::singleton::Singletons;
+use crate::math::color::linear_to_srgb;
+use crate::math::color::ColorU;
+use crate::math::ik::IkConstraint;
 use crate::math::transform::Transform3d;
+use crate::physics::Aabb;
+use crate::render::debug_draw::ExtractedMeshNormals;
 use crate::render::draw_command::DrawCommands;
-use crate::render::material::{MaterialCache, MaterialId, MaterialStandard};
-use crate::render::vertex::Vertex3d;
+use crate::render::material::{
+    MaterialCache, MaterialId, MaterialStandard, Transparency, VertexAnimation,
+};
+use crate::render::morph::ExtractedMorphWeights;
+use crate::render::occlusion::ExtractedOcclusionQuery;
+use crate::render::vertex::{CustomVertex, Vertex3d, Vertex3dColored};
 use crate::render::{
-    ExtractedMesh, Instance, Mesh, MeshCache, MeshId, RenderServer, Texture, TextureCache,
+    DepthStencilConfig, ExtractedMesh, Mesh, MeshCache, MeshId, RenderServer, TextureCache,
 };
+use crate::scene::d3::animation::AnimationPlayer;
+use crate::scene::d3::animation_tree::AnimationTree;
 use crate::scene::d3::node_3d::{AsNode3d, Node3d};
+use crate::scene::d3::skeleton::Skeleton;
 use crate::scene::{AsNode, NodeType};
 
 pub struct Model {
@@ -27,12 +41,56 @@ pub struct Model {
     // Mesh materials. Same length as the meshes.
     pub materials: Vec<Option<MaterialId>>,
 
+    /// Morph target names of all meshes, with their weights.
+    blend_shapes: Vec<(String, f32)>,
+
+    /// Index into `blend_shapes` of each morph target, per mesh.
+    mesh_blend_shapes: Vec<Vec<usize>>,
+
+    /// Joints the model is animated by.
+    pub skeleton: Option<Skeleton>,
+
+    /// Poses the skeleton, unless there is an animation tree. Root motion moves the model.
+    pub animation_player: Option<AnimationPlayer>,
+
+    /// Poses the skeleton by blending clips.
+    pub animation_tree: Option<AnimationTree>,
+
+    /// Applied to the skeleton after the animation, in order.
+    pub ik_constraints: Vec<IkConstraint>,
+
+    // Keeps the meshes and materials (and their textures) loaded.
+    assets: Vec<AssetHandle>,
+
     // // For instancing.
     // instances: Vec<Instance>,
     // instance_buffer: wgpu::Buffer,
 
+    // Bounds of all meshes, in model space.
+    local_aabb: Aabb,
+
     // For debugging.
     pub name: String,
+
+    /// Draw the bounding box.
+    pub debug_aabb: bool,
+
+    /// Draw per-vertex normals.
+    pub debug_normals: bool,
+
+    /// Also draw tangents and bi-tangents along with the normals.
+    pub debug_tangents: bool,
+
+    /// Length of the normal and tangent lines, in world units.
+    pub debug_normal_length: f32,
+
+    /// Draw lines from each joint to its parent.
+    pub debug_skeleton: bool,
+
+    /// Skip drawing the model while its bounding box is hidden behind other geometry.
+    /// Worth it for expensive models only, and the results lag a frame or two behind,
+    /// so a model can pop in late when it comes out of hiding.
+    pub occlusion_culling: bool,
 }
 
 impl Model {
@@ -41,13 +99,13 @@ impl Model {
         texture_cache: &mut TextureCache,
         material_cache: &mut MaterialCache,
         mesh_cache: &mut MeshCache,
+        asset_registry: &mut AssetRegistry,
         render_server: &RenderServer,
         path: P,
     ) -> Result<Self> {
         let now = Instant::now();
 
         let device = &render_server.device;
-        let queue = &render_server.queue;
 
         let (obj_meshes, obj_materials) = tobj::load_obj(
             path.as_ref(),
@@ -72,13 +130,13 @@ impl Model {
             let mut color_texture = None;
 
             if m.diffuse_texture.is_some() {
-                color_texture = match Texture::load(
-                    device,
-                    queue,
+                color_texture = match load_texture(
+                    asset_registry,
+                    render_server,
                     texture_cache,
                     containing_folder.join(&m.diffuse_texture.clone().unwrap()),
                 ) {
-                    Ok(i) => Some(i),
+                    Ok(handle) => Some(handle),
                     Err(e) => {
                         log::warn!(
                             "Failed to load diffuse texture {:?}: {}",
@@ -94,13 +152,13 @@ impl Model {
             let mut normal_texture = None;
 
             if m.normal_texture.is_some() {
-                normal_texture = match Texture::load(
-                    device,
-                    queue,
+                normal_texture = match load_texture(
+                    asset_registry,
+                    render_server,
                     texture_cache,
                     containing_folder.join(&m.normal_texture.clone().unwrap()),
                 ) {
-                    Ok(i) => Some(i),
+                    Ok(handle) => Some(handle),
                     Err(e) => {
                         log::warn!(
                             "Failed to load normal texture {:?}: {}",
@@ -112,20 +170,63 @@ impl Model {
                 };
             }
 
+            // Load emissive texture. "map_Ke" is a common MTL extension.
+            let mut emissive_texture = None;
+
+            if let Some(file_name) = m.unknown_param.get("map_Ke") {
+                emissive_texture = match load_texture(
+                    asset_registry,
+                    render_server,
+                    texture_cache,
+                    containing_folder.join(file_name),
+                ) {
+                    Ok(handle) => Some(handle),
+                    Err(e) => {
+                        log::warn!("Failed to load emissive texture {:?}: {}", file_name, e);
+                        None
+                    }
+                };
+            }
+
+            let (emissive_color, emissive_strength) = mtl_emissive(&m);
+
             let material = MaterialStandard {
-                name: m.name,
-                color_texture,
-                normal_texture,
+                name: m.name.clone(),
+                color_texture: color_texture.as_ref().and_then(|h| h.get_texture()),
+                normal_texture: normal_texture.as_ref().and_then(|h| h.get_texture()),
+                emissive_texture: emissive_texture.as_ref().and_then(|h| h.get_texture()),
+                emissive_color,
+                emissive_strength,
                 texture_bind_group: None,
-                transparent: false,
+                uv_offset: Vector2::zero(),
+                uv_scale: Vector2::new(1.0, 1.0),
+                uv_rotation: 0.0,
+                transparency: Transparency::Opaque,
+                double_sided: false,
+                depth_stencil: DepthStencilConfig::opaque(),
+                vertex_animation: VertexAnimation::None,
             };
 
-            local_materials.push(material_cache.add(material));
+            let material_id = material_cache.add(material);
+            let material_handle = asset_registry.add(
+                AssetKey::Material(material_id),
+                &format!("{}#{}", path.as_ref().display(), m.name),
+                0,
+                color_texture
+                    .into_iter()
+                    .chain(normal_texture)
+                    .chain(emissive_texture)
+                    .collect(),
+            );
+
+            local_materials.push((material_id, material_handle));
         }
 
         // Handle meshes.
         let mut meshes = Vec::new();
         let mut materials = Vec::new();
+        let mut assets = Vec::new();
+        let mut positions = Vec::new();
 
         for m in obj_meshes {
             let mut vertices = Vec::new();
@@ -150,76 +251,31 @@ impl Model {
                 });
             }
 
-            let indices = &m.mesh.indices;
-            let mut triangles_included = (0..vertices.len()).collect::<Vec<_>>();
-
-            // Calculate tangents and bi-tangets. We're going to
-            // use the triangles, so we need to loop through the
-            // indices in chunks of 3.
-            for c in indices.chunks(3) {
-                let v0 = vertices[c[0] as usize];
-                let v1 = vertices[c[1] as usize];
-                let v2 = vertices[c[2] as usize];
-
-                let pos0: Vector3<_> = v0.position.into();
-                let pos1: Vector3<_> = v1.position.into();
-                let pos2: Vector3<_> = v2.position.into();
-
-                let uv0: Vector2<_> = v0.uv.into();
-                let uv1: Vector2<_> = v1.uv.into();
-                let uv2: Vector2<_> = v2.uv.into();
-
-                // Calculate the edges of the triangle.
-                let delta_pos1 = pos1 - pos0;
-                let delta_pos2 = pos2 - pos0;
-
-                // This will give us a direction to calculate the
-                // tangent and bi-tangent.
-                let delta_uv1 = uv1 - uv0;
-                let delta_uv2 = uv2 - uv0;
-
-                // Solving the following system of equations will
-                // give us the tangent and bi-tangent.
-                //     delta_pos1 = delta_uv1.x * T + delta_uv1.y * B
-                //     delta_pos2 = delta_uv2.x * T + delta_uv2.y * B
-                // Luckily, the place I found this equation provided the solution!
-                let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
-                let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
-                // We flip the bi-tangent to enable right-handed normal
-                // maps with wgpu texture coordinate system.
-                let bi_tangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
-
-                // We'll use the same tangent/bi-tangent for each vertex in the triangle.
-                vertices[c[0] as usize].tangent =
-                    (tangent + Vector3::from(vertices[c[0] as usize].tangent)).into();
-                vertices[c[1] as usize].tangent =
-                    (tangent + Vector3::from(vertices[c[1] as usize].tangent)).into();
-                vertices[c[2] as usize].tangent =
-                    (tangent + Vector3::from(vertices[c[2] as usize].tangent)).into();
-                vertices[c[0] as usize].bi_tangent =
-                    (bi_tangent + Vector3::from(vertices[c[0] as usize].bi_tangent)).into();
-                vertices[c[1] as usize].bi_tangent =
-                    (bi_tangent + Vector3::from(vertices[c[1] as usize].bi_tangent)).into();
-                vertices[c[2] as usize].bi_tangent =
-                    (bi_tangent + Vector3::from(vertices[c[2] as usize].bi_tangent)).into();
-
-                // Used to average the tangents/bi-tangents.
-                triangles_included[c[0] as usize] += 1;
-                triangles_included[c[1] as usize] += 1;
-                triangles_included[c[2] as usize] += 1;
-            }
-
-            // Average the tangents/bi-tangents.
-            for (i, n) in triangles_included.into_iter().enumerate() {
-                let denom = 1.0 / n as f32;
-                let mut v = &mut vertices[i];
-                v.tangent = (Vector3::from(v.tangent) * denom).normalize().into();
-                v.bi_tangent = (Vector3::from(v.bi_tangent) * denom).normalize().into();
-            }
+            compute_tangents(&mut vertices, &m.mesh.indices);
+
+            // Only meshes with vertex colors pay for them.
+            let vertex_colors = &m.mesh.vertex_color;
+            let (vertex_data, vertex_layout) = if vertex_colors.len() == vertices.len() * 3 {
+                let colored: Vec<Vertex3dColored> = vertices
+                    .iter()
+                    .zip(vertex_colors.chunks(3))
+                    .map(|(vertex, color)| Vertex3dColored {
+                        vertex: *vertex,
+                        color: [color[0], color[1], color[2], 1.0],
+                    })
+                    .collect();
+
+                (
+                    bytemuck::cast_slice(&colored).to_vec(),
+                    Vertex3dColored::layout(),
+                )
+            } else {
+                (bytemuck::cast_slice(&vertices).to_vec(), Vertex3d::layout())
+            };
 
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Vertex Buffer", path.as_ref())),
-                contents: bytemuck::cast_slice(&vertices),
+                contents: &vertex_data,
                 usage: wgpu::BufferUsages::VERTEX,
             });
 
@@ -229,18 +285,36 @@ impl Model {
                 usage: wgpu::BufferUsages::INDEX,
             });
 
+            positions.extend(vertices.iter().map(|v| Vector3::from(v.position)));
+
+            let mesh_label = format!("{}#{}", path.as_ref().display(), m.name);
+            let mesh_size = vertex_buffer.size() + index_buffer.size();
+
             let mesh = Mesh {
                 name: m.name,
                 vertex_buffer,
                 index_buffer,
                 index_count: m.mesh.indices.len() as u32,
+                positions: vertices.iter().map(|v| v.position).collect(),
+                indices: m.mesh.indices,
+                vertex_layout,
+                morph: None,
             };
 
-            meshes.push(mesh_cache.add(mesh));
+            let mesh_id = mesh_cache.add(mesh);
+            meshes.push(mesh_id);
+            assets.push(asset_registry.add(
+                AssetKey::Mesh(mesh_id),
+                &mesh_label,
+                mesh_size,
+                vec![],
+            ));
 
             // Prepare a material id for each mesh.
-            if (m.mesh.material_id.is_some()) {
-                materials.push(Some(local_materials[m.mesh.material_id.unwrap()]));
+            if let Some(index) = m.mesh.material_id {
+                let (material_id, material_handle) = &local_materials[index];
+                materials.push(Some(*material_id));
+                assets.push(material_handle.clone());
             } else {
                 materials.push(None);
             }
@@ -255,13 +329,274 @@ impl Model {
             elapsed_time.as_millis()
         );
 
-        Ok(Self {
+        Ok(Self::from_parts(
+            meshes,
+            materials,
+            assets,
+            Aabb::from_points(&positions),
+        ))
+    }
+
+    /// Model with meshes that are already in the cache and no morph targets, e.g. from
+    /// a file. `assets` are kept loaded as long as the model lives.
+    pub(crate) fn from_parts(
+        meshes: Vec<MeshId>,
+        materials: Vec<Option<MaterialId>>,
+        assets: Vec<AssetHandle>,
+        local_aabb: Aabb,
+    ) -> Self {
+        let mesh_blend_shapes = vec![vec![]; meshes.len()];
+
+        Self {
             node_3d: Node3d::default(),
             meshes,
             materials,
+            blend_shapes: vec![],
+            mesh_blend_shapes,
+            skeleton: None,
+            animation_player: None,
+            animation_tree: None,
+            ik_constraints: vec![],
+            assets,
+            local_aabb,
             name: "".to_string(),
+            debug_aabb: false,
+            debug_normals: false,
+            debug_tangents: false,
+            debug_normal_length: 0.1,
+            debug_skeleton: false,
+            occlusion_culling: false,
             // instances,
-        })
+        }
+    }
+}
+
+/// Fill in the tangents and bi-tangents of triangles, from their positions and UVs.
+pub(crate) fn compute_tangents(vertices: &mut [Vertex3d], indices: &[u32]) {
+    let mut triangles_included = (0..vertices.len()).collect::<Vec<_>>();
+
+    // Calculate tangents and bi-tangets. We're going to
+    // use the triangles, so we need to loop through the
+    // indices in chunks of 3.
+    for c in indices.chunks(3) {
+        let v0 = vertices[c[0] as usize];
+        let v1 = vertices[c[1] as usize];
+        let v2 = vertices[c[2] as usize];
+
+        let pos0: Vector3<_> = v0.position.into();
+        let pos1: Vector3<_> = v1.position.into();
+        let pos2: Vector3<_> = v2.position.into();
+
+        let uv0: Vector2<_> = v0.uv.into();
+        let uv1: Vector2<_> = v1.uv.into();
+        let uv2: Vector2<_> = v2.uv.into();
+
+        // Calculate the edges of the triangle.
+        let delta_pos1 = pos1 - pos0;
+        let delta_pos2 = pos2 - pos0;
+
+        // This will give us a direction to calculate the
+        // tangent and bi-tangent.
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        // Solving the following system of equations will
+        // give us the tangent and bi-tangent.
+        //     delta_pos1 = delta_uv1.x * T + delta_uv1.y * B
+        //     delta_pos2 = delta_uv2.x * T + delta_uv2.y * B
+        // Luckily, the place I found this equation provided the solution!
+        let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        // We flip the bi-tangent to enable right-handed normal
+        // maps with wgpu texture coordinate system.
+        let bi_tangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        // We'll use the same tangent/bi-tangent for each vertex in the triangle.
+        vertices[c[0] as usize].tangent =
+            (tangent + Vector3::from(vertices[c[0] as usize].tangent)).into();
+        vertices[c[1] as usize].tangent =
+            (tangent + Vector3::from(vertices[c[1] as usize].tangent)).into();
+        vertices[c[2] as usize].tangent =
+            (tangent + Vector3::from(vertices[c[2] as usize].tangent)).into();
+        vertices[c[0] as usize].bi_tangent =
+            (bi_tangent + Vector3::from(vertices[c[0] as usize].bi_tangent)).into();
+        vertices[c[1] as usize].bi_tangent =
+            (bi_tangent + Vector3::from(vertices[c[1] as usize].bi_tangent)).into();
+        vertices[c[2] as usize].bi_tangent =
+            (bi_tangent + Vector3::from(vertices[c[2] as usize].bi_tangent)).into();
+
+        // Used to average the tangents/bi-tangents.
+        triangles_included[c[0] as usize] += 1;
+        triangles_included[c[1] as usize] += 1;
+        triangles_included[c[2] as usize] += 1;
+    }
+
+    // Average the tangents/bi-tangents.
+    for (i, n) in triangles_included.into_iter().enumerate() {
+        let denom = 1.0 / n as f32;
+        let v = &mut vertices[i];
+        v.tangent = (Vector3::from(v.tangent) * denom).normalize().into();
+        v.bi_tangent = (Vector3::from(v.bi_tangent) * denom).normalize().into();
+    }
+}
+
+/// Emissive color and strength from the "Ke" MTL extension. Ke is linear and may go
+/// above 1.0, which becomes the strength. Emissive textures without Ke emit as is.
+fn mtl_emissive(material: &tobj::Material) -> (ColorU, f32) {
+    let ke = material.unknown_param.get("Ke").and_then(|value| {
+        let channels: Vec<f32> = value
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+
+        match channels.as_slice() {
+            [r, g, b] => Some([*r, *g, *b]),
+            [v] => Some([*v; 3]),
+            _ => None,
+        }
+    });
+
+    let ke = match ke {
+        Some(ke) => ke,
+        None if material.unknown_param.contains_key("map_Ke") => [1.0; 3],
+        None => return (ColorU::black(), 1.0),
+    };
+
+    emissive_from_linear(ke)
+}
+
+/// Emissive color and strength for a linear color, which may go above 1.0.
+pub(crate) fn emissive_from_linear(color: [f32; 3]) -> (ColorU, f32) {
+    let strength = color.iter().cloned().fold(1.0, f32::max);
+    let encode = |value: f32| (linear_to_srgb(value / strength) * 255.0).round() as u8;
+
+    (
+        ColorU::new(encode(color[0]), encode(color[1]), encode(color[2]), 255),
+        strength,
+    )
+}
+
+impl Model {
+    /// Model with a single mesh that is already in the cache, e.g. one built with [`Csg`].
+    ///
+    /// [`Csg`]: crate::math::csg::Csg
+    pub fn from_mesh(
+        mesh_cache: &MeshCache,
+        mesh_id: MeshId,
+        material_id: Option<MaterialId>,
+    ) -> Self {
+        let mesh = mesh_cache.get(mesh_id);
+
+        let positions: Vec<Vector3<f32>> = mesh
+            .map(|mesh| mesh.positions.iter().map(|p| Vector3::from(*p)).collect())
+            .unwrap_or_default();
+
+        let blend_shapes: Vec<(String, f32)> = mesh
+            .and_then(|mesh| mesh.morph.as_ref())
+            .map(|morph| {
+                morph
+                    .targets
+                    .iter()
+                    .map(|target| (target.name.clone(), 0.0))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            node_3d: Node3d::default(),
+            meshes: vec![mesh_id],
+            materials: vec![material_id],
+            mesh_blend_shapes: vec![(0..blend_shapes.len()).collect()],
+            blend_shapes,
+            skeleton: None,
+            animation_player: None,
+            animation_tree: None,
+            ik_constraints: vec![],
+            assets: vec![],
+            local_aabb: Aabb::from_points(&positions),
+            name: "".to_string(),
+            debug_aabb: false,
+            debug_normals: false,
+            debug_tangents: false,
+            debug_normal_length: 0.1,
+            debug_skeleton: false,
+            occlusion_culling: false,
+        }
+    }
+
+    /// Handles to the meshes and materials used by the model.
+    pub fn get_assets(&self) -> &[AssetHandle] {
+        &self.assets
+    }
+
+    /// Bounds of all meshes, in model space.
+    pub fn get_local_aabb(&self) -> Aabb {
+        self.local_aabb
+    }
+
+    /// Solve the IK constraints with their target and pole positions. Called by the world
+    /// after nodes are updated.
+    pub(crate) fn solve_ik(&mut self) {
+        let Some(skeleton) = &mut self.skeleton else {
+            return;
+        };
+        let Some(world_to_model) = Matrix4::from(self.node_3d.transform.to_matrix()).invert()
+        else {
+            return;
+        };
+        let to_model = |p: Vector3<f32>| (world_to_model * p.extend(1.0)).truncate();
+
+        for constraint in &self.ik_constraints {
+            constraint.solve(
+                skeleton,
+                to_model(constraint.target_position),
+                constraint.pole_position.map(to_model),
+            );
+        }
+    }
+
+    /// Morph targets of the meshes, with their initial weights, and for each mesh the
+    /// indices into `blend_shapes` of its targets.
+    pub(crate) fn set_blend_shapes(
+        &mut self,
+        blend_shapes: Vec<(String, f32)>,
+        mesh_blend_shapes: Vec<Vec<usize>>,
+    ) {
+        self.blend_shapes = blend_shapes;
+        self.mesh_blend_shapes = mesh_blend_shapes;
+    }
+
+    /// Names of the morph targets of the meshes, in order.
+    pub fn get_blend_shape_names(&self) -> Vec<&str> {
+        self.blend_shapes
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn get_blend_shape_weight(&self, name: &str) -> Option<f32> {
+        self.blend_shapes
+            .iter()
+            .find(|(shape, _)| shape == name)
+            .map(|(_, weight)| *weight)
+    }
+
+    /// Weight of a morph target, usually from 0.0 to 1.0. Returns false if no mesh has one
+    /// by that name.
+    ///
+    /// Meshes are shared, so this also affects other models using the same mesh.
+    pub fn set_blend_shape_weight(&mut self, name: &str, weight: f32) -> bool {
+        match self
+            .blend_shapes
+            .iter_mut()
+            .find(|(shape, _)| shape == name)
+        {
+            Some((_, w)) => {
+                *w = weight;
+                true
+            }
+            None => false,
+        }
     }
 }
 
@@ -278,6 +613,29 @@ impl AsNode for Model {
         NodeType::Model
     }
 
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        let Some(skeleton) = &mut self.skeleton else {
+            return;
+        };
+
+        if let Some(tree) = &mut self.animation_tree {
+            tree.update(dt, skeleton);
+        } else if let Some(player) = &mut self.animation_player {
+            player.update(dt, skeleton);
+
+            if player.root_motion {
+                let motion = player.take_root_motion();
+                let transform = &mut self.node_3d.transform;
+
+                transform.position +=
+                    transform.rotation * motion.translation.mul_element_wise(transform.scale);
+                transform.rotation = (transform.rotation * motion.rotation).normalize();
+            }
+        }
+
+        skeleton.update();
+    }
+
     fn draw(&self, draw_cmds: &mut DrawCommands) {
         for i in 0..self.meshes.len() {
             let mesh = self.meshes[i];
@@ -290,6 +648,66 @@ impl AsNode for Model {
             };
 
             draw_cmds.extracted.meshes.push(extracted_mesh);
+
+            // Meshes added to `meshes` afterwards have no blend shapes.
+            if let Some(shapes) = self.mesh_blend_shapes.get(i).filter(|s| !s.is_empty()) {
+                let weights = shapes
+                    .iter()
+                    .map(|shape| self.blend_shapes[*shape].1)
+                    .collect();
+
+                draw_cmds
+                    .extracted
+                    .morph_weights
+                    .push(ExtractedMorphWeights {
+                        mesh_id: mesh,
+                        weights,
+                    });
+            }
+
+            if self.debug_normals {
+                draw_cmds.draw_mesh_normals(ExtractedMeshNormals {
+                    transform: self.node_3d.transform,
+                    mesh_id: mesh,
+                    line_length: self.debug_normal_length,
+                    show_tangents: self.debug_tangents,
+                });
+            }
+        }
+
+        if let Some(skeleton) = self.skeleton.as_ref().filter(|_| self.debug_skeleton) {
+            let model = Matrix4::from(self.node_3d.transform.to_matrix());
+            let joint_position =
+                |joint| (model * skeleton.get_global_transform(joint)).w.truncate();
+
+            for (i, joint) in skeleton.get_joints().iter().enumerate() {
+                if let Some(parent) = joint.parent {
+                    draw_cmds.draw_line_3d(
+                        joint_position(parent),
+                        joint_position(i),
+                        ColorU::new(0, 200, 255, 255),
+                    );
+                }
+            }
+        }
+
+        if self.occlusion_culling {
+            draw_cmds
+                .extracted
+                .occlusion_queries
+                .push(ExtractedOcclusionQuery {
+                    transform: self.node_3d.transform,
+                    local_aabb: self.local_aabb,
+                    mesh_ids: self.meshes.clone(),
+                });
+        }
+
+        if self.debug_aabb {
+            draw_cmds.draw_aabb(
+                &self.local_aabb,
+                &self.node_3d.transform,
+                ColorU::new(255, 200, 0, 255),
+            );
         }
 
         // // Set vertex buffer for InstanceInput.
@@ -306,27 +724,11 @@ impl AsNode for Model {
 }
 
 impl AsNode3d for Model {
-    fn get_position(&self) -> Vector3<f32> {
-        self.node_3d.transform.position
-    }
-
-    fn set_position(&mut self, position: Vector3<f32>) {
-        self.node_3d.transform.position = position;
-    }
-
-    fn get_rotation(&self) -> Quaternion<f32> {
-        self.node_3d.transform.rotation
-    }
-
-    fn set_rotation(&mut self, rotation: Quaternion<f32>) {
-        self.node_3d.transform.rotation = rotation;
-    }
-
-    fn get_scale(&self) -> Vector3<f32> {
-        self.node_3d.transform.scale
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
     }
 
-    fn set_scale(&mut self, scale: Vector3<f32>) {
-        self.node_3d.transform.scale = scale;
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
     }
 }
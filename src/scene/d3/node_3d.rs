@@ -1,6 +1,7 @@
 use crate::math::transform::{Transform2d, Transform3d};
+use crate::math::types::{Quat, Vec3};
 use crate::scene::{AsNodeUi, Sprite2d};
-use cgmath::{Quaternion, Vector2, Vector3};
+use cgmath::{ElementWise, Rotation};
 
 pub struct Node3d {
     pub transform: Transform3d,
@@ -14,16 +15,70 @@ impl Default for Node3d {
     }
 }
 
+impl Node3d {
+    /// Jump to `transform`, and return how to carry a child along so it keeps its place
+    /// relative to this node.
+    pub(crate) fn move_to(
+        &mut self,
+        transform: Transform3d,
+    ) -> impl Fn(Transform3d) -> Transform3d {
+        let old = std::mem::replace(&mut self.transform, transform);
+
+        let rotation = transform.rotation * old.rotation.invert();
+        let scale = transform.scale.div_element_wise(old.scale);
+
+        move |child: Transform3d| Transform3d {
+            position: transform.position
+                + rotation * (child.position - old.position).mul_element_wise(scale),
+            rotation: rotation * child.rotation,
+            scale: child.scale.mul_element_wise(scale),
+        }
+    }
+}
+
+/// Position, rotation and scale of a 3D node. Through `dyn AsNode3d`, where the generic
+/// setters can't be called, use `get_transform` and `set_transform`.
 pub trait AsNode3d {
-    fn get_position(&self) -> Vector3<f32>;
+    fn get_transform(&self) -> Transform3d;
+
+    fn set_transform(&mut self, transform: Transform3d);
 
-    fn set_position(&mut self, position: Vector3<f32>);
+    fn get_position(&self) -> Vec3 {
+        self.get_transform().position.into()
+    }
 
-    fn get_rotation(&self) -> Quaternion<f32>;
+    fn set_position(&mut self, position: impl Into<Vec3>)
+    where
+        Self: Sized,
+    {
+        let mut transform = self.get_transform();
+        transform.position = position.into().into();
+        self.set_transform(transform);
+    }
 
-    fn set_rotation(&mut self, rotation: Quaternion<f32>);
+    fn get_rotation(&self) -> Quat {
+        self.get_transform().rotation.into()
+    }
 
-    fn get_scale(&self) -> Vector3<f32>;
+    fn set_rotation(&mut self, rotation: impl Into<Quat>)
+    where
+        Self: Sized,
+    {
+        let mut transform = self.get_transform();
+        transform.rotation = rotation.into().into();
+        self.set_transform(transform);
+    }
 
-    fn set_scale(&mut self, scale: Vector3<f32>);
+    fn get_scale(&self) -> Vec3 {
+        self.get_transform().scale.into()
+    }
+
+    fn set_scale(&mut self, scale: impl Into<Vec3>)
+    where
+        Self: Sized,
+    {
+        let mut transform = self.get_transform();
+        transform.scale = scale.into().into();
+        self.set_transform(transform);
+    }
 }
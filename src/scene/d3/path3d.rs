@@ -0,0 +1,181 @@
+use crate::core::singleton::Singletons;
+use crate::math::bezier::BezierPath;
+use crate::math::color::ColorU;
+use crate::math::transform::Transform3d;
+use crate::render::draw_command::DrawCommands;
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use cgmath::Vector3;
+use std::any::Any;
+
+/// A curve in 3D space for [`PathFollow3d`] children to move along, e.g. a camera rail.
+pub struct Path3d {
+    pub node_3d: Node3d,
+
+    /// In local space.
+    pub path: BezierPath<Vector3<f32>>,
+
+    /// Draw the path as debug lines.
+    pub debug: bool,
+    pub debug_color: ColorU,
+}
+
+impl Path3d {
+    pub fn new(path: BezierPath<Vector3<f32>>) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            path,
+            debug: false,
+            debug_color: ColorU::new(255, 200, 0, 255),
+        }
+    }
+
+    /// World transform at a distance along the path, facing the direction of travel.
+    pub fn get_transform_at(&self, offset: f32) -> Option<Transform3d> {
+        let mut local = Transform3d::from_position(self.path.sample_at_distance(offset)?);
+        if let Some(tangent) = self.path.tangent_at_distance(offset) {
+            local.look_at(local.position + tangent, Vector3::unit_y());
+        }
+
+        Some(self.node_3d.transform.mul_transform(&local))
+    }
+}
+
+impl AsNode for Path3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Path3d
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if !self.debug {
+            return;
+        }
+
+        let points: Vec<Vector3<f32>> = self
+            .path
+            .tessellate(16)
+            .into_iter()
+            .map(|p| self.node_3d.transform.transform_point(p).into())
+            .collect();
+
+        for pair in points.windows(2) {
+            draw_cmds.draw_line_3d(pair[0], pair[1], self.debug_color);
+        }
+    }
+}
+
+impl AsNode3d for Path3d {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
+
+/// Moves along its parent [`Path3d`], carrying its children along. For camera rails and
+/// patrol routes.
+pub struct PathFollow3d {
+    pub node_3d: Node3d,
+
+    /// Distance along the path.
+    pub offset: f32,
+
+    /// Added to the offset every second. Negative goes backwards.
+    pub speed: f32,
+
+    /// Wrap around at the ends instead of stopping.
+    pub looping: bool,
+
+    /// Face the direction of travel. Otherwise only the position changes.
+    pub rotates: bool,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Default for PathFollow3d {
+    fn default() -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            offset: 0.0,
+            speed: 0.0,
+            looping: true,
+            rotates: true,
+            custom_update: None,
+        }
+    }
+}
+
+impl PathFollow3d {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            ..Default::default()
+        }
+    }
+
+    /// Wrap or clamp the offset to a path of `length`, and return it.
+    pub(crate) fn wrap_offset(&mut self, length: f32) -> f32 {
+        self.offset = if self.looping && length > 0.0 {
+            self.offset.rem_euclid(length)
+        } else {
+            self.offset.clamp(0.0, length)
+        };
+
+        self.offset
+    }
+
+    /// Move to a transform on the path, and return how to carry a child along.
+    /// Called by the world after nodes are updated.
+    pub(crate) fn follow(
+        &mut self,
+        mut transform: Transform3d,
+    ) -> impl Fn(Transform3d) -> Transform3d {
+        transform.scale = self.node_3d.transform.scale;
+        if !self.rotates {
+            transform.rotation = self.node_3d.transform.rotation;
+        }
+
+        self.node_3d.move_to(transform)
+    }
+}
+
+impl AsNode for PathFollow3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::PathFollow3d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        self.offset += self.speed * dt;
+
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+}
+
+impl AsNode3d for PathFollow3d {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
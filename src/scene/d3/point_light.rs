@@ -2,7 +2,6 @@ use crate::core::singleton::Singletons;
 use crate::math::color::ColorU;
 use crate::math::transform::Transform3d;
 use cgmath::prelude::*;
-use cgmath::{Quaternion, Vector3};
 use std::any::Any;
 use std::ops::Range;
 use std::path::Path;
@@ -18,6 +17,9 @@ pub struct PointLight {
     pub node_3d: Node3d,
     pub color: ColorU,
     pub strength: f32,
+    /// Only the first point light drawn with this set casts shadows. Shadows are set up in
+    /// `RenderWorld::shadow_settings`.
+    pub cast_shadows: bool,
     // pub(crate) sprite: Sprite3d,
     pub custom_update: Option<fn(f32, &mut Self)>,
 }
@@ -31,6 +33,7 @@ impl PointLight {
             node_3d: Node3d::default(),
             color: ColorU::white(),
             strength: 1.0,
+            cast_shadows: false,
             // sprite: sprite3d,
             custom_update: None,
         }
@@ -79,32 +82,21 @@ impl AsNode for PointLight {
             ..Default::default()
         };
 
-        draw_cmds.extracted.lights.point_lights.push(point_light);
+        let lights = &mut draw_cmds.extracted.lights;
+        if self.cast_shadows && lights.point_light_shadows.is_none() {
+            lights.point_light_shadows = Some(lights.point_lights.len());
+        }
+
+        lights.point_lights.push(point_light);
     }
 }
 
 impl AsNode3d for PointLight {
-    fn get_position(&self) -> Vector3<f32> {
-        self.node_3d.transform.position
-    }
-
-    fn set_position(&mut self, position: Vector3<f32>) {
-        self.node_3d.transform.position = position;
-    }
-
-    fn get_rotation(&self) -> Quaternion<f32> {
-        self.node_3d.transform.rotation
-    }
-
-    fn set_rotation(&mut self, rotation: Quaternion<f32>) {
-        self.node_3d.transform.rotation = rotation;
-    }
-
-    fn get_scale(&self) -> Vector3<f32> {
-        self.node_3d.transform.scale
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
     }
 
-    fn set_scale(&mut self, scale: Vector3<f32>) {
-        self.node_3d.transform.scale = scale;
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
     }
 }
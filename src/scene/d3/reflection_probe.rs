@@ -0,0 +1,120 @@
+use crate::math::transform::Transform3d;
+use anyhow::{Context, Result};
+use std::any::Any;
+
+use crate::asset::AssetServer;
+use crate::render::draw_command::DrawCommands;
+use crate::render::probe::{
+    irradiance_file_name, radiance_file_name, BakedProbe, ExtractedProbe, PROBE_LOAD_DIRS,
+};
+use crate::render::{CubemapLayout, RenderServer, Texture, TextureCache};
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+
+/// Captures its surroundings, which light and reflect in nearby meshes.
+///
+/// Probes do nothing until baked with `HeadlessApp::bake_probes`, or loaded with `load_baked`.
+pub struct ReflectionProbe {
+    pub node_3d: Node3d,
+    /// Baked files are named after it, so it should be unique in the scene.
+    pub name: String,
+    /// Meshes within this distance use the probe, more so closer to it.
+    pub radius: f32,
+    pub(crate) baked: Option<BakedProbe>,
+}
+
+impl ReflectionProbe {
+    pub fn new(name: &str, radius: f32) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            name: name.to_string(),
+            radius,
+            baked: None,
+        }
+    }
+
+    pub fn is_baked(&self) -> bool {
+        self.baked.is_some()
+    }
+
+    /// Load the cubemaps saved by the last bake.
+    pub fn load_baked(
+        &mut self,
+        asset_server: &AssetServer,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> Result<()> {
+        let mut load = |file_name: String| {
+            let uri = PROBE_LOAD_DIRS
+                .iter()
+                .map(|dir| format!("{}/{}", dir, file_name))
+                .find(|uri| asset_server.vfs.exists(uri))
+                .with_context(|| format!("Probe {} hasn't been baked", self.name))?;
+
+            let image = image::load_from_memory(&asset_server.vfs.read(&uri)?)?;
+
+            Texture::from_cube_image(
+                &render_server.device,
+                &render_server.queue,
+                texture_cache,
+                &image,
+                CubemapLayout::HorizontalStrip,
+                Some(&uri),
+            )
+        };
+
+        let radiance = load(radiance_file_name(&self.name))?;
+        let irradiance = load(irradiance_file_name(&self.name))?;
+
+        self.set_baked(
+            BakedProbe {
+                radiance,
+                irradiance,
+            },
+            texture_cache,
+        );
+
+        Ok(())
+    }
+
+    /// Replace the baked cubemaps, freeing the previous ones.
+    pub(crate) fn set_baked(&mut self, baked: BakedProbe, texture_cache: &mut TextureCache) {
+        if let Some(old) = self.baked.replace(baked) {
+            texture_cache.remove(old.radiance);
+            texture_cache.remove(old.irradiance);
+        }
+    }
+}
+
+impl AsNode for ReflectionProbe {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::ReflectionProbe
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if let Some(baked) = self.baked {
+            draw_cmds.extracted.probes.push(ExtractedProbe {
+                position: self.node_3d.transform.position,
+                radius: self.radius,
+                baked,
+            });
+        }
+    }
+}
+
+impl AsNode3d for ReflectionProbe {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
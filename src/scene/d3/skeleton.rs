@@ -0,0 +1,179 @@
+use crate::math::transform::Transform3d;
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, VectorSpace};
+
+/// A bone of a skeleton.
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+    /// Index of the parent joint. Parents always come before their children.
+    pub parent: Option<usize>,
+    /// Transform relative to the parent when no animation is applied.
+    pub rest: Transform3d,
+    /// Model space to joint space, in the rest pose.
+    pub inverse_bind: Matrix4<f32>,
+}
+
+/// Local transform of each joint, relative to its parent.
+#[derive(Debug, Clone)]
+pub struct Pose {
+    pub transforms: Vec<Transform3d>,
+}
+
+impl Pose {
+    /// Interpolate towards `other`, `t` going from 0.0 (self) to 1.0 (other).
+    pub fn blend(&self, other: &Pose, t: f32) -> Pose {
+        let transforms = self
+            .transforms
+            .iter()
+            .zip(&other.transforms)
+            .map(|(a, b)| {
+                // Take the short way around.
+                let b_rotation = if a.rotation.dot(b.rotation) < 0.0 {
+                    -b.rotation
+                } else {
+                    b.rotation
+                };
+
+                Transform3d {
+                    position: a.position.lerp(b.position, t),
+                    rotation: a.rotation.nlerp(b_rotation, t),
+                    scale: a.scale.lerp(b.scale, t),
+                }
+            })
+            .collect();
+
+        Pose { transforms }
+    }
+
+    /// Blend any number of poses. Weights don't have to add up to 1.
+    pub fn blend_weighted(poses: &[(Pose, f32)]) -> Option<Pose> {
+        let mut blended: Option<Pose> = None;
+        let mut total = 0.0;
+
+        for (pose, weight) in poses.iter().filter(|(_, w)| *w > 0.0) {
+            total += weight;
+
+            blended = Some(match blended {
+                None => pose.clone(),
+                Some(blended) => blended.blend(pose, weight / total),
+            });
+        }
+
+        blended
+    }
+}
+
+/// A hierarchy of joints, posed by an [`AnimationPlayer`](crate::scene::AnimationPlayer)
+/// or [`AnimationTree`](crate::scene::AnimationTree).
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+    pose: Pose,
+    /// Model space transform of each joint, in the current pose.
+    global_transforms: Vec<Matrix4<f32>>,
+    /// Global transform times inverse bind, what skinned vertices are transformed by.
+    joint_matrices: Vec<Matrix4<f32>>,
+}
+
+impl Skeleton {
+    pub fn new() -> Self {
+        Self {
+            joints: vec![],
+            pose: Pose { transforms: vec![] },
+            global_transforms: vec![],
+            joint_matrices: vec![],
+        }
+    }
+
+    /// Add a joint and return its index. The inverse bind matrix is derived from the rest
+    /// transforms, use [`Skeleton::set_inverse_bind`] if the mesh was bound in another pose.
+    pub fn add_joint(&mut self, name: &str, parent: Option<usize>, rest: Transform3d) -> usize {
+        let index = self.joints.len();
+        assert!(
+            parent.is_none_or(|parent| parent < index),
+            "Parent joints have to be added first"
+        );
+
+        let global = match parent {
+            Some(parent) => self.global_transforms[parent] * Matrix4::from(rest.to_matrix()),
+            None => rest.to_matrix().into(),
+        };
+
+        self.joints.push(Joint {
+            name: name.to_string(),
+            parent,
+            rest,
+            inverse_bind: global.invert().unwrap_or(Matrix4::identity()),
+        });
+        self.pose.transforms.push(rest);
+        self.global_transforms.push(global);
+        self.joint_matrices.push(Matrix4::identity());
+
+        index
+    }
+
+    pub fn set_inverse_bind(&mut self, joint: usize, inverse_bind: Matrix4<f32>) {
+        self.joints[joint].inverse_bind = inverse_bind;
+    }
+
+    pub fn get_joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    pub fn get_joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn find_joint(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|j| j.name == name)
+    }
+
+    pub fn get_rest_pose(&self) -> Pose {
+        Pose {
+            transforms: self.joints.iter().map(|j| j.rest).collect(),
+        }
+    }
+
+    pub fn get_pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    /// Takes effect on the next [`Skeleton::update`]. Missing joints keep their transforms.
+    pub fn set_pose(&mut self, pose: Pose) {
+        for (current, new) in self.pose.transforms.iter_mut().zip(pose.transforms) {
+            *current = new;
+        }
+    }
+
+    pub fn set_joint_transform(&mut self, joint: usize, transform: Transform3d) {
+        self.pose.transforms[joint] = transform;
+    }
+
+    /// Recompute the global transforms and joint matrices from the pose.
+    pub fn update(&mut self) {
+        for (i, joint) in self.joints.iter().enumerate() {
+            let local = Matrix4::from(self.pose.transforms[i].to_matrix());
+
+            self.global_transforms[i] = match joint.parent {
+                Some(parent) => self.global_transforms[parent] * local,
+                None => local,
+            };
+            self.joint_matrices[i] = self.global_transforms[i] * joint.inverse_bind;
+        }
+    }
+
+    /// Model space transform of a joint, as of the last update.
+    pub fn get_global_transform(&self, joint: usize) -> Matrix4<f32> {
+        self.global_transforms[joint]
+    }
+
+    pub fn get_joint_matrices(&self) -> &[Matrix4<f32>] {
+        &self.joint_matrices
+    }
+}
+
+impl Default for Skeleton {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,175 +1,92 @@
-// use crate::pbr::Material2d;
-// use crate::render::{Mesh, Texture, TextureId};
-// use crate::scene::{AsNode, CameraInfo, CameraUniform, NodeType};
-// use crate::{Camera2d, RenderServer, SamplerBindingType, Singletons, Zero};
-// use cgmath::{InnerSpace, Rotation3, Vector3, Quaternion, Deg, Matrix4};
-// use std::any::Any;
-// use std::mem;
-// use wgpu::util::DeviceExt;
-// use wgpu::BufferAddress;
-//
-// #[repr(C)]
-// #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-// pub struct SpriteParamsUniform {
-//     model_matrix: [[f32; 4]; 4],
-//     billboard_mode: f32,
-//     pad0: f32,
-//     pad1: f32,
-//     pad2: f32,
-// }
-//
-// impl SpriteParamsUniform {
-//     fn default() -> Self {
-//         Self {
-//             model_matrix: [[0.0; 4]; 4],
-//             billboard_mode: 0.0,
-//             pad0: 0.0,
-//             pad1: 0.0,
-//             pad2: 0.0,
-//         }
-//     }
-// }
-//
-// pub struct Sprite3d {
-//     pub name: String,
-//
-//     pub position: Vector3<f32>,
-//     pub rotation: Quaternion<f32>,
-//     pub scale: Vector3<f32>,
-//
-//     pub billboard_mode: BillboardMode,
-//
-//     pub texture: Option<TextureId>,
-//     // pub bind_group: wgpu::BindGroup,
-//     //
-//     pub params_uniform: SpriteParamsUniform,
-//     // pub params_buffer: wgpu::Buffer,
-//     // pub params_bind_group: wgpu::BindGroup,
-//
-//     pub mesh: Mesh,
-// }
-//
-// impl Sprite3d {
-//     pub(crate) fn new(render_server: &RenderServer, texture_id: TextureId) -> Sprite3d {
-//         let device = &render_server.device;
-//
-//         let position = Vector3::new(0.0f32, 0.0, 0.0);
-//         let rotation = if position.is_zero() {
-//             // This is needed so an object at (0, 0, 0) won't get scaled to zero
-//             // as Quaternions can affect scale if they're not created correctly.
-//             Quaternion::from_axis_angle(Vector3::unit_z(), Deg(0.0))
-//         } else {
-//             Quaternion::from_axis_angle(position.normalize(), Deg(45.0))
-//         };
-//         let scale = Vector3::new(1.0f32, 1.0, 1.0);
-//
-//         let mesh = Mesh::default_3d(device);
-//
-//         let texture = render_server.texture_cache.get(texture_id).unwrap();
-//
-//         let texture_bind_group = texture.create_bind_group(device, render_server);
-//
-//         let billboard_mode = BillboardMode::Spherical;
-//
-//         // Create a buffer for the parameters.
-//         // ------------------------------------------
-//         let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-//             label: Some("sprite params buffer"),
-//             size: mem::size_of::<SpriteParamsUniform>() as BufferAddress,
-//             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-//             mapped_at_creation: false,
-//         });
-//
-//         let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-//             layout: render_server
-//                 .get_bind_group_layout("sprite3d params bind group layout")
-//                 .unwrap(),
-//             entries: &[wgpu::BindGroupEntry {
-//                 binding: 0,
-//                 resource: params_buffer.as_entire_binding(),
-//             }],
-//             label: Some("sprite params bind group"),
-//         });
-//         // ------------------------------------------
-//
-//         Self {
-//             name: "".to_string(),
-//             position,
-//             rotation,
-//             scale,
-//             billboard_mode,
-//             texture: Some(texture_id),
-//             // texture_bind_group,
-//             params_uniform: SpriteParamsUniform::default(),
-//             // params_buffer,
-//             // params_bind_group,
-//             mesh,
-//         }
-//     }
-// }
-//
-// #[derive(PartialEq)]
-// pub enum BillboardMode {
-//     None,
-//     Spherical,
-// }
-//
-// impl AsNode for Sprite3d {
-//     fn node_type(&self) -> NodeType {
-//         NodeType::Sprite3d
-//     }
-//
-//     fn as_any(&self) -> &dyn Any {
-//         self
-//     }
-//
-//     fn as_any_mut(&mut self) -> &mut dyn Any {
-//         self
-//     }
-//
-//     fn update(&mut self, dt: f32, camera_info: &CameraInfo, singletons: &mut Singletons) {
-//         self.params_uniform = SpriteParamsUniform {
-//             model_matrix: Matrix4::from_translation(self.position).into(),
-//             billboard_mode: if self.billboard_mode == BillboardMode::Spherical {
-//                 1.0
-//             } else {
-//                 0.0
-//             },
-//             pad0: 0.0,
-//             pad1: 0.0,
-//             pad2: 0.0,
-//         };
-//     }
-//
-//     fn draw<'a, 'b: 'a>(
-//         &'b self,
-//         render_pass: &mut wgpu::RenderPass<'a>,
-//         camera_info: &'b CameraInfo,
-//         singletons: &'b mut Singletons,
-//     ) {
-//
-//         // queue_sprite_3d();
-//         // // Update buffer.
-//         // singletons.render_server.queue.write_buffer(
-//         //     &self.params_buffer,
-//         //     0,
-//         //     bytemuck::cast_slice(&[self.params_uniform]),
-//         // );
-//         //
-//         // match &camera_info.bind_group {
-//         //     Some(b) => {
-//         //         render_pass.draw_sprite(
-//         //             singletons
-//         //                 .render_server
-//         //                 .get_render_pipeline("sprite3d pipeline")
-//         //                 .unwrap(),
-//         //             &self.mesh,
-//         //             &self.texture_bind_group,
-//         //             b,
-//         //             &self.params_bind_group,
-//         //         );
-//         //     }
-//         //     None => {}
-//         // }
-//     }
-// }
+use crate::asset::AssetHandle;
+use crate::core::singleton::Singletons;
+use crate::math::transform::Transform3d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::sprite3d::{BillboardMode, ExtractedSprite3d};
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use std::any::Any;
+
+/// A textured quad in 3D space.
+pub struct Sprite3d {
+    pub node_3d: Node3d,
+
+    /// Keeps a texture loaded through the `AssetServer` alive while the sprite uses it.
+    pub texture: Option<AssetHandle>,
+
+    pub billboard_mode: BillboardMode,
+
+    /// Size of a texture pixel in world units.
+    pub pixel_size: f32,
+
+    /// Fragments with an alpha lower than this are discarded.
+    /// Useful for foliage and other cutout sprites that need correct depth.
+    pub alpha_cut: Option<f32>,
+
+    /// Fade out over this distance in world units where the sprite gets close to geometry
+    /// behind it, instead of clipping with a hard line. For smoke, fire and other soft
+    /// particles. Faded sprites don't write depth.
+    pub depth_fade: Option<f32>,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Sprite3d {
+    pub fn new(texture: impl Into<AssetHandle>) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            texture: Some(texture.into()),
+            billboard_mode: BillboardMode::Disabled,
+            pixel_size: 0.01,
+            alpha_cut: None,
+            depth_fade: None,
+            custom_update: None,
+        }
+    }
+
+    pub fn set_texture(&mut self, texture: impl Into<AssetHandle>) {
+        self.texture = Some(texture.into());
+    }
+}
+
+impl AsNode for Sprite3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Sprite3d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if let Some(texture_id) = self.texture.as_ref().and_then(AssetHandle::get_texture) {
+            draw_cmds.extracted.sprites3d.push(ExtractedSprite3d {
+                transform: self.node_3d.transform,
+                texture_id,
+                pixel_size: self.pixel_size,
+                billboard_mode: self.billboard_mode,
+                alpha_cut: self.alpha_cut,
+                depth_fade: self.depth_fade,
+            });
+        }
+    }
+}
+
+impl AsNode3d for Sprite3d {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
@@ -0,0 +1,176 @@
+use crate::asset::AssetHandle;
+use crate::core::singleton::Singletons;
+use crate::math::transform::Transform3d;
+use crate::physics::Aabb;
+use crate::render::draw_command::DrawCommands;
+use crate::render::material::MaterialId;
+use crate::render::{ExtractedMesh, Mesh, MeshCache, MeshId, RenderServer};
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use crate::text::glyph_mesh::build_text_mesh;
+use crate::text::TextServer;
+use anyhow::{Context, Result};
+use cgmath::{Vector3, Zero};
+use std::any::Any;
+
+/// Text extruded into a solid mesh, e.g. for title screens and logos.
+///
+/// The first line starts at the origin, on its baseline, and the front faces +Z.
+/// Call `build` after changing the text, font or size.
+pub struct Text3dMesh {
+    pub node_3d: Node3d,
+
+    text: String,
+
+    font: Option<AssetHandle>,
+
+    /// Em size, in world units.
+    pub size: f32,
+
+    /// Distance from the front to the back, in world units.
+    pub depth: f32,
+
+    /// How far flattened curves may stray from the glyph outlines, in world units.
+    pub tolerance: f32,
+
+    /// For the front and back faces.
+    pub front_material: Option<MaterialId>,
+
+    /// For the walls along the outlines. Uses the front material if none.
+    pub side_material: Option<MaterialId>,
+
+    front_mesh: Option<MeshId>,
+    side_mesh: Option<MeshId>,
+
+    /// Bounds of both meshes, in model space.
+    local_aabb: Aabb,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Text3dMesh {
+    pub fn new(text: &str) -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            text: text.to_string(),
+            font: None,
+            size: 1.0,
+            depth: 0.2,
+            tolerance: 0.005,
+            front_material: None,
+            side_material: None,
+            front_mesh: None,
+            side_mesh: None,
+            local_aabb: Aabb::new(Vector3::zero(), Vector3::zero()),
+            custom_update: None,
+        }
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+    }
+
+    /// Use a font loaded by `AssetServer::load_font`. Bitmap fonts have no outlines.
+    pub fn set_font(&mut self, font: AssetHandle) {
+        self.font = Some(font);
+    }
+
+    /// Tessellate the glyphs of the text, replacing the previous meshes.
+    pub fn build(
+        &mut self,
+        text_server: &TextServer,
+        render_server: &RenderServer,
+        mesh_cache: &mut MeshCache,
+    ) -> Result<()> {
+        let font_data = text_server
+            .get_font_data(self.font.as_ref().and_then(|f| f.get_font()))
+            .context("3D text needs a font with glyph outlines")?;
+
+        let data = build_text_mesh(font_data, &self.text, self.size, self.depth, self.tolerance)?;
+
+        for mesh in [self.front_mesh.take(), self.side_mesh.take()]
+            .into_iter()
+            .flatten()
+        {
+            mesh_cache.remove(mesh);
+        }
+
+        let device = &render_server.device;
+
+        let (vertices, indices) = data.caps;
+        let positions: Vec<Vector3<f32>> =
+            vertices.iter().map(|v| Vector3::from(v.position)).collect();
+        self.local_aabb = Aabb::from_points(&positions);
+
+        // Empty buffers can't be drawn, e.g. for text of spaces only.
+        if !indices.is_empty() {
+            let mesh = Mesh::from_vertices(device, "text3d front", &vertices, indices);
+            self.front_mesh = Some(mesh_cache.add(mesh));
+        }
+
+        let (vertices, indices) = data.sides;
+        if !indices.is_empty() {
+            let mesh = Mesh::from_vertices(device, "text3d side", &vertices, indices);
+            self.side_mesh = Some(mesh_cache.add(mesh));
+        }
+
+        Ok(())
+    }
+
+    /// Bounds of the built meshes, in model space.
+    pub fn get_local_aabb(&self) -> Aabb {
+        self.local_aabb
+    }
+}
+
+impl AsNode for Text3dMesh {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Text3dMesh
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if let Some(custom_update) = self.custom_update {
+            custom_update(dt, self);
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        let parts = [
+            (self.front_mesh, self.front_material),
+            (self.side_mesh, self.side_material.or(self.front_material)),
+        ];
+
+        for (mesh, material) in parts {
+            let Some(mesh_id) = mesh else {
+                continue;
+            };
+
+            draw_cmds.extracted.meshes.push(ExtractedMesh {
+                transform: self.node_3d.transform,
+                mesh_id,
+                material_id: material,
+            });
+        }
+    }
+}
+
+impl AsNode3d for Text3dMesh {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
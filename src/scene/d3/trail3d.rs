@@ -0,0 +1,164 @@
+use crate::core::singleton::Singletons;
+use crate::math::color::ColorU;
+use crate::render::draw_command::DrawCommands;
+use crate::render::trail::{ExtractedTrail3d, TrailVertex};
+use crate::scene::NodeId;
+use crate::scene::{AsNode, NodeType};
+use cgmath::{InnerSpace, Vector3};
+use std::any::Any;
+use std::collections::VecDeque;
+
+struct TrailPoint {
+    position: Vector3<f32>,
+    age: f32,
+}
+
+/// A camera facing ribbon left behind by another 3D node as it moves.
+pub struct Trail3d {
+    /// The node being followed. It has to implement AsNode3d.
+    pub target: Option<NodeId>,
+
+    /// Seconds before a point disappears.
+    pub lifetime: f32,
+
+    /// Width at the head. The ribbon narrows towards the tail.
+    pub width: f32,
+
+    /// Color at the head. The ribbon fades out towards the tail.
+    pub color: ColorU,
+
+    /// The target has to move this far before a new point is added.
+    pub min_distance: f32,
+
+    /// Stop adding points, but let the existing ones fade out.
+    pub emitting: bool,
+
+    /// Oldest point first.
+    points: VecDeque<TrailPoint>,
+}
+
+impl Default for Trail3d {
+    fn default() -> Self {
+        Self {
+            target: None,
+            lifetime: 1.0,
+            width: 0.2,
+            color: ColorU::white(),
+            min_distance: 0.05,
+            emitting: true,
+            points: VecDeque::new(),
+        }
+    }
+}
+
+impl Trail3d {
+    pub fn new(target: NodeId) -> Self {
+        Self {
+            target: Some(target),
+            ..Default::default()
+        }
+    }
+
+    /// Remove all points.
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Follow the target to `position`. Called by the world after nodes are updated.
+    pub(crate) fn emit(&mut self, position: Vector3<f32>) {
+        if !self.emitting {
+            return;
+        }
+
+        let len = self.points.len();
+
+        // Keep the head attached to the target until it has moved far enough.
+        if len >= 2 && (position - self.points[len - 2].position).magnitude() < self.min_distance {
+            let head = self.points.back_mut().unwrap();
+            head.position = position;
+            head.age = 0.0;
+            return;
+        }
+
+        self.points.push_back(TrailPoint { position, age: 0.0 });
+    }
+
+    fn tessellate(&self) -> Option<ExtractedTrail3d> {
+        if self.points.len() < 2 || self.lifetime <= 0.0 {
+            return None;
+        }
+
+        let color = self.color.to_f32();
+
+        let count = self.points.len();
+        let mut vertices = Vec::with_capacity(count * 2);
+
+        for i in 0..count {
+            let prev = self.points[i.saturating_sub(1)].position;
+            let next = self.points[(i + 1).min(count - 1)].position;
+            let point = &self.points[i];
+
+            let tangent = next - prev;
+            let tangent = if tangent.magnitude2() > 0.0 {
+                tangent.normalize()
+            } else {
+                tangent
+            };
+
+            let head_factor = (1.0 - point.age / self.lifetime).clamp(0.0, 1.0);
+            let half_width = self.width * 0.5 * head_factor;
+
+            for offset in [-half_width, half_width] {
+                vertices.push(TrailVertex {
+                    position: point.position.into(),
+                    tangent: tangent.into(),
+                    color: [color.r, color.g, color.b, color.a * head_factor],
+                    offset,
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity((count - 1) * 6);
+
+        for i in 0..count as u32 - 1 {
+            let base = i * 2;
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 1, base + 3, base + 2]);
+        }
+
+        Some(ExtractedTrail3d { vertices, indices })
+    }
+}
+
+impl AsNode for Trail3d {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::Trail3d
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        for point in self.points.iter_mut() {
+            point.age += dt;
+        }
+
+        while self
+            .points
+            .front()
+            .is_some_and(|point| point.age >= self.lifetime)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if let Some(trail) = self.tessellate() {
+            draw_cmds.extracted.trails.push(trail);
+        }
+    }
+}
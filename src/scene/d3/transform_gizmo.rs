@@ -0,0 +1,549 @@
+use crate::math::plane::Plane;
+use crate::math::transform::Transform3d;
+use crate::physics::Ray3d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::gizmo::GizmoVertex;
+use crate::scene::NodeId;
+use crate::scene::{AsNode, NodeType};
+use crate::window::{InputEvent, InputServer};
+use cgmath::{InnerSpace, Quaternion, Rad, Rotation, Rotation3, Vector2, Vector3};
+use std::any::Any;
+use std::f32::consts::TAU;
+
+// Handle dimensions, in multiples of the gizmo size.
+const AXIS_LENGTH: f32 = 1.0;
+const TIP_LENGTH: f32 = 0.22;
+const TIP_RADIUS: f32 = 0.07;
+const SHAFT_RADIUS: f32 = 0.015;
+const PLANE_MIN: f32 = 0.25;
+const PLANE_MAX: f32 = 0.45;
+const RING_RADIUS: f32 = 1.0;
+const RING_WIDTH: f32 = 0.02;
+const CENTER_SIZE: f32 = 0.08;
+/// How close the mouse ray has to pass a handle to grab it.
+const PICK_TOLERANCE: f32 = 0.08;
+
+const RING_SEGMENTS: usize = 64;
+const CONE_SEGMENTS: usize = 12;
+
+const AXIS_COLORS: [[f32; 4]; 3] = [
+    [0.9, 0.2, 0.2, 1.0],
+    [0.3, 0.8, 0.2, 1.0],
+    [0.2, 0.4, 0.9, 1.0],
+];
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.85, 0.1, 1.0];
+const CENTER_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum GizmoMode {
+    /// Arrows for axes and squares for planes.
+    #[default]
+    Translate,
+    /// Rings around each axis.
+    Rotate,
+    /// Boxes for local axes and a center box for uniform scaling.
+    Scale,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GizmoHandle {
+    Axis(usize),
+    /// Indexed by the plane normal axis.
+    Plane(usize),
+    Ring(usize),
+    Center,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct GizmoDrag {
+    handle: GizmoHandle,
+    start_transform: Transform3d,
+    /// Where the handle was grabbed: the axis parameter for axes,
+    /// the plane hit for planes and the offset from the center for rings.
+    start_point: Vector3<f32>,
+    start_mouse: Vector2<f32>,
+}
+
+/// What the world knows about the camera and target, filled in each update.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct GizmoView {
+    pub(crate) mouse_ray: Ray3d,
+    /// World units per view pixel at the target.
+    pub(crate) pixel_size: f32,
+}
+
+/// Interactive handles that edit another 3D node's transform with the left mouse button.
+pub struct TransformGizmo {
+    /// The node being edited. It has to implement AsNode3d.
+    pub target: Option<NodeId>,
+
+    pub mode: GizmoMode,
+
+    /// Handle length in pixels, independent of the distance to the camera.
+    pub size: f32,
+
+    mouse_position: Vector2<f32>,
+    mouse_pressed: bool,
+    /// Drags only start on the frame the button goes down.
+    mouse_just_pressed: bool,
+
+    hovered: Option<GizmoHandle>,
+    drag: Option<GizmoDrag>,
+
+    // Cached by the world for drawing.
+    target_transform: Option<Transform3d>,
+    scale: f32,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        Self {
+            target: None,
+            mode: GizmoMode::Translate,
+            size: 100.0,
+            mouse_position: Vector2::new(0.0, 0.0),
+            mouse_pressed: false,
+            mouse_just_pressed: false,
+            hovered: None,
+            drag: None,
+            target_transform: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl TransformGizmo {
+    pub fn new(target: NodeId) -> Self {
+        Self {
+            target: Some(target),
+            ..Default::default()
+        }
+    }
+
+    /// If a handle is being dragged.
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    pub(crate) fn get_mouse_position(&self) -> Vector2<f32> {
+        self.mouse_position
+    }
+
+    /// Pick and drag handles. Returns the new target transform while dragging.
+    pub(crate) fn process(
+        &mut self,
+        view: GizmoView,
+        transform: Transform3d,
+    ) -> Option<Transform3d> {
+        self.target_transform = Some(transform);
+        self.scale = self.size * view.pixel_size;
+
+        let ray = view.mouse_ray;
+        let just_pressed = std::mem::take(&mut self.mouse_just_pressed);
+
+        if !self.mouse_pressed {
+            self.drag = None;
+            self.hovered = self.pick(&ray, &transform);
+            return None;
+        }
+
+        if self.drag.is_none() {
+            if !just_pressed {
+                return None;
+            }
+
+            // Start dragging the hovered handle.
+            let handle = self.pick(&ray, &transform)?;
+            let start_point = self.drag_point(handle, &ray, &transform)?;
+
+            self.hovered = Some(handle);
+            self.drag = Some(GizmoDrag {
+                handle,
+                start_transform: transform,
+                start_point,
+                start_mouse: self.mouse_position,
+            });
+
+            return None;
+        }
+
+        let drag = self.drag.unwrap();
+        let start = drag.start_transform;
+        let axes = self.axes(&start);
+
+        let mut new_transform = transform;
+
+        match (drag.handle, self.mode) {
+            (GizmoHandle::Axis(i), GizmoMode::Translate) => {
+                let point = self.drag_point(drag.handle, &ray, &start)?;
+                new_transform.position = start.position + axes[i] * (point.x - drag.start_point.x);
+            }
+            (GizmoHandle::Axis(i), _) => {
+                let point = self.drag_point(drag.handle, &ray, &start)?;
+                if drag.start_point.x.abs() > f32::EPSILON {
+                    let factor = point.x / drag.start_point.x;
+                    new_transform.scale[i] = start.scale[i] * factor;
+                }
+            }
+            (GizmoHandle::Plane(_), _) => {
+                let point = self.drag_point(drag.handle, &ray, &start)?;
+                new_transform.position = start.position + (point - drag.start_point);
+            }
+            (GizmoHandle::Ring(i), _) => {
+                let from = drag.start_point;
+                let to = self.drag_point(drag.handle, &ray, &start)?;
+                let angle = axes[i].dot(from.cross(to)).atan2(from.dot(to));
+                new_transform.rotation =
+                    Quaternion::from_axis_angle(axes[i], Rad(angle)) * start.rotation;
+            }
+            (GizmoHandle::Center, _) => {
+                // Drag right to grow, left to shrink.
+                let delta = self.mouse_position.x - drag.start_mouse.x;
+                let factor = (1.0 + delta * 0.01).max(0.01);
+                new_transform.scale = start.scale * factor;
+            }
+        }
+
+        self.target_transform = Some(new_transform);
+
+        Some(new_transform)
+    }
+
+    /// World axes, except for scaling which works in local space.
+    fn axes(&self, transform: &Transform3d) -> [Vector3<f32>; 3] {
+        let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+
+        match self.mode {
+            GizmoMode::Scale => axes.map(|axis| transform.rotation.rotate_vector(axis)),
+            _ => axes,
+        }
+    }
+
+    fn handles(&self) -> Vec<GizmoHandle> {
+        match self.mode {
+            GizmoMode::Translate => (0..3)
+                .map(GizmoHandle::Axis)
+                .chain((0..3).map(GizmoHandle::Plane))
+                .collect(),
+            GizmoMode::Rotate => (0..3).map(GizmoHandle::Ring).collect(),
+            GizmoMode::Scale => (0..3)
+                .map(GizmoHandle::Axis)
+                .chain([GizmoHandle::Center])
+                .collect(),
+        }
+    }
+
+    /// The closest handle under the mouse ray.
+    fn pick(&self, ray: &Ray3d, transform: &Transform3d) -> Option<GizmoHandle> {
+        let origin = transform.position;
+        let axes = self.axes(transform);
+        let scale = self.scale;
+        let tolerance = PICK_TOLERANCE * scale;
+
+        let mut closest: Option<(f32, GizmoHandle)> = None;
+
+        for handle in self.handles() {
+            let distance = match handle {
+                GizmoHandle::Axis(i) => {
+                    closest_on_axis(ray, origin, axes[i]).and_then(|(s, t, distance)| {
+                        let length = (AXIS_LENGTH + TIP_LENGTH) * scale;
+                        (s >= 0.0 && s <= length && distance < tolerance).then_some(t)
+                    })
+                }
+                GizmoHandle::Plane(i) => Plane::from_point_normal(origin, axes[i])
+                    .intersect_ray(ray, f32::MAX)
+                    .and_then(|t| {
+                        let local = ray.at(t) - origin;
+                        let u = local.dot(axes[(i + 1) % 3]) / scale;
+                        let v = local.dot(axes[(i + 2) % 3]) / scale;
+                        let range = PLANE_MIN..=PLANE_MAX;
+                        (range.contains(&u) && range.contains(&v)).then_some(t)
+                    }),
+                GizmoHandle::Ring(i) => Plane::from_point_normal(origin, axes[i])
+                    .intersect_ray(ray, f32::MAX)
+                    .and_then(|t| {
+                        let radius = (ray.at(t) - origin).magnitude();
+                        ((radius - RING_RADIUS * scale).abs() < tolerance).then_some(t)
+                    }),
+                GizmoHandle::Center => {
+                    let t = (origin - ray.origin).dot(ray.direction);
+                    let distance = (ray.at(t) - origin).magnitude();
+                    (t > 0.0 && distance < (CENTER_SIZE * 2.0) * scale).then_some(t)
+                }
+            };
+
+            if let Some(t) = distance {
+                if closest.is_none_or(|(closest_t, _)| t < closest_t) {
+                    closest = Some((t, handle));
+                }
+            }
+        }
+
+        closest.map(|(_, handle)| handle)
+    }
+
+    /// Where the mouse ray meets the handle's constraint. For axes only `x` is used,
+    /// which is the parameter along the axis.
+    fn drag_point(
+        &self,
+        handle: GizmoHandle,
+        ray: &Ray3d,
+        transform: &Transform3d,
+    ) -> Option<Vector3<f32>> {
+        let origin = transform.position;
+        let axes = self.axes(transform);
+
+        match handle {
+            GizmoHandle::Axis(i) => {
+                closest_on_axis(ray, origin, axes[i]).map(|(s, _, _)| Vector3::new(s, 0.0, 0.0))
+            }
+            GizmoHandle::Plane(i) => Plane::from_point_normal(origin, axes[i])
+                .intersect_ray(ray, f32::MAX)
+                .map(|t| ray.at(t)),
+            GizmoHandle::Ring(i) => Plane::from_point_normal(origin, axes[i])
+                .intersect_ray(ray, f32::MAX)
+                .map(|t| ray.at(t) - origin),
+            GizmoHandle::Center => Some(origin),
+        }
+    }
+
+    fn handle_color(&self, handle: GizmoHandle, color: [f32; 4]) -> [f32; 4] {
+        let active = self.drag.map(|drag| drag.handle).or(self.hovered);
+
+        if active == Some(handle) {
+            HIGHLIGHT_COLOR
+        } else {
+            color
+        }
+    }
+
+    fn build_vertices(&self, transform: &Transform3d) -> Vec<GizmoVertex> {
+        let mut vertices = vec![];
+
+        let origin = transform.position;
+        let axes = self.axes(transform);
+        let scale = self.scale;
+
+        for handle in self.handles() {
+            match handle {
+                GizmoHandle::Axis(i) => {
+                    let color = self.handle_color(handle, AXIS_COLORS[i]);
+                    let (u, v) = (axes[(i + 1) % 3], axes[(i + 2) % 3]);
+                    let end = origin + axes[i] * AXIS_LENGTH * scale;
+
+                    push_box(
+                        &mut vertices,
+                        origin + axes[i] * AXIS_LENGTH * 0.5 * scale,
+                        [
+                            axes[i] * AXIS_LENGTH * 0.5 * scale,
+                            u * SHAFT_RADIUS * scale,
+                            v * SHAFT_RADIUS * scale,
+                        ],
+                        color,
+                    );
+
+                    if self.mode == GizmoMode::Translate {
+                        push_cone(
+                            &mut vertices,
+                            end,
+                            end + axes[i] * TIP_LENGTH * scale,
+                            [u * TIP_RADIUS * scale, v * TIP_RADIUS * scale],
+                            color,
+                        );
+                    } else {
+                        let half = TIP_RADIUS * scale;
+                        push_box(
+                            &mut vertices,
+                            end + axes[i] * half,
+                            [axes[i] * half, u * half, v * half],
+                            color,
+                        );
+                    }
+                }
+                GizmoHandle::Plane(i) => {
+                    let mut color = self.handle_color(handle, AXIS_COLORS[i]);
+                    color[3] = 0.6;
+
+                    let (u, v) = (axes[(i + 1) % 3] * scale, axes[(i + 2) % 3] * scale);
+                    push_quad(
+                        &mut vertices,
+                        [
+                            origin + u * PLANE_MIN + v * PLANE_MIN,
+                            origin + u * PLANE_MAX + v * PLANE_MIN,
+                            origin + u * PLANE_MAX + v * PLANE_MAX,
+                            origin + u * PLANE_MIN + v * PLANE_MAX,
+                        ],
+                        color,
+                    );
+                }
+                GizmoHandle::Ring(i) => {
+                    let color = self.handle_color(handle, AXIS_COLORS[i]);
+                    let (u, v) = (axes[(i + 1) % 3], axes[(i + 2) % 3]);
+                    let width = RING_WIDTH * scale;
+
+                    for segment in 0..RING_SEGMENTS {
+                        let a0 = segment as f32 / RING_SEGMENTS as f32 * TAU;
+                        let a1 = (segment + 1) as f32 / RING_SEGMENTS as f32 * TAU;
+                        let d0 = u * a0.cos() + v * a0.sin();
+                        let d1 = u * a1.cos() + v * a1.sin();
+                        let p0 = origin + d0 * RING_RADIUS * scale;
+                        let p1 = origin + d1 * RING_RADIUS * scale;
+
+                        // Two crossed strips, so the ring is visible from any angle.
+                        push_quad(
+                            &mut vertices,
+                            [
+                                p0 - d0 * width,
+                                p1 - d1 * width,
+                                p1 + d1 * width,
+                                p0 + d0 * width,
+                            ],
+                            color,
+                        );
+                        push_quad(
+                            &mut vertices,
+                            [
+                                p0 - axes[i] * width,
+                                p1 - axes[i] * width,
+                                p1 + axes[i] * width,
+                                p0 + axes[i] * width,
+                            ],
+                            color,
+                        );
+                    }
+                }
+                GizmoHandle::Center => {
+                    let color = self.handle_color(handle, CENTER_COLOR);
+                    let half = CENTER_SIZE * scale;
+                    push_box(
+                        &mut vertices,
+                        origin,
+                        [axes[0] * half, axes[1] * half, axes[2] * half],
+                        color,
+                    );
+                }
+            }
+        }
+
+        vertices
+    }
+}
+
+impl AsNode for TransformGizmo {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::TransformGizmo
+    }
+
+    fn input(&mut self, input_event: &mut InputEvent, _input_server: &mut InputServer) {
+        match input_event {
+            InputEvent::MouseButton(event) if event.button == winit::event::MouseButton::Left => {
+                self.mouse_just_pressed = event.pressed && !self.mouse_pressed;
+                self.mouse_pressed = event.pressed;
+            }
+            InputEvent::MouseMotion(event) => {
+                self.mouse_position = Vector2::new(event.position.0, event.position.1);
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        if let Some(transform) = self.target_transform {
+            draw_cmds.draw_gizmo_triangles(&self.build_vertices(&transform));
+        }
+    }
+}
+
+/// Closest approach between a ray and an infinite axis line.
+/// Returns the parameter along the axis, the distance along the ray and the gap between them.
+fn closest_on_axis(
+    ray: &Ray3d,
+    origin: Vector3<f32>,
+    axis: Vector3<f32>,
+) -> Option<(f32, f32, f32)> {
+    let w = ray.origin - origin;
+    let b = ray.direction.dot(axis);
+    let d = ray.direction.dot(w);
+    let e = axis.dot(w);
+
+    // Parallel lines have no single closest point.
+    let denom = 1.0 - b * b;
+    if denom < 0.0001 {
+        return None;
+    }
+
+    let t = (b * e - d) / denom;
+    let s = (e - b * d) / denom;
+
+    if t < 0.0 {
+        return None;
+    }
+
+    let gap = (ray.at(t) - (origin + axis * s)).magnitude();
+
+    Some((s, t, gap))
+}
+
+fn push_triangle(vertices: &mut Vec<GizmoVertex>, points: [Vector3<f32>; 3], color: [f32; 4]) {
+    for point in points {
+        vertices.push(GizmoVertex {
+            position: point.into(),
+            color,
+        });
+    }
+}
+
+fn push_quad(vertices: &mut Vec<GizmoVertex>, corners: [Vector3<f32>; 4], color: [f32; 4]) {
+    push_triangle(vertices, [corners[0], corners[1], corners[2]], color);
+    push_triangle(vertices, [corners[0], corners[2], corners[3]], color);
+}
+
+/// An oriented box given by its center and three half extent vectors.
+fn push_box(
+    vertices: &mut Vec<GizmoVertex>,
+    center: Vector3<f32>,
+    half_extents: [Vector3<f32>; 3],
+    color: [f32; 4],
+) {
+    for i in 0..3 {
+        let normal = half_extents[i];
+        let u = half_extents[(i + 1) % 3];
+        let v = half_extents[(i + 2) % 3];
+
+        for side in [-1.0, 1.0] {
+            let c = center + normal * side;
+            push_quad(
+                vertices,
+                [c - u - v, c + u - v, c + u + v, c - u + v],
+                color,
+            );
+        }
+    }
+}
+
+/// A cone from a base circle spanned by `radii` to the tip.
+fn push_cone(
+    vertices: &mut Vec<GizmoVertex>,
+    base: Vector3<f32>,
+    tip: Vector3<f32>,
+    radii: [Vector3<f32>; 2],
+    color: [f32; 4],
+) {
+    for segment in 0..CONE_SEGMENTS {
+        let a0 = segment as f32 / CONE_SEGMENTS as f32 * TAU;
+        let a1 = (segment + 1) as f32 / CONE_SEGMENTS as f32 * TAU;
+        let p0 = base + radii[0] * a0.cos() + radii[1] * a0.sin();
+        let p1 = base + radii[0] * a1.cos() + radii[1] * a1.sin();
+
+        push_triangle(vertices, [p0, p1, tip], color);
+        push_triangle(vertices, [p1, p0, base], color);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::core::singleton::Singletons;
+use crate::math::color::ColorU;
+use crate::math::transform::Transform3d;
+use crate::render::draw_command::DrawCommands;
+use crate::render::water::{ExtractedWater, WaterReflection};
+use crate::render::TextureId;
+use crate::scene::{AsNode, AsNode3d, Node3d, NodeType};
+use cgmath::Vector2;
+use std::any::Any;
+
+/// A flat water surface on the local XZ plane, drawn over the opaque scene.
+///
+/// It refracts and reflects what has been drawn before it, and gets foam where it
+/// meets geometry. Rendering it needs an offscreen scene color, so adding water
+/// has the cost of post processing.
+pub struct WaterPlane {
+    pub node_3d: Node3d,
+
+    /// Extent in local units along X and Z.
+    pub size: Vector2<f32>,
+
+    /// Tint of what is seen through shallow water.
+    pub shallow_color: ColorU,
+
+    /// Color of deep water, where the ground can't be seen anymore.
+    pub deep_color: ColorU,
+
+    /// Depth in world units over which the water goes from shallow to deep.
+    pub absorption_depth: f32,
+
+    /// Tangent space wave normals. A built-in ripple pattern is used if None.
+    pub normal_map: Option<TextureId>,
+
+    /// World units covered by one tile of the normal map.
+    pub wave_scale: f32,
+
+    /// How much the waves bend the surface, 0 is a mirror.
+    pub normal_strength: f32,
+
+    /// Drift of the waves in world units per second.
+    pub flow: Vector2<f32>,
+
+    /// Per-location flow direction in the red and green channels, centered on 0.5,
+    /// stretched over the whole plane. Added to `flow`.
+    pub flow_map: Option<TextureId>,
+
+    /// Speed in world units per second of a fully saturated flow map direction.
+    pub flow_map_speed: f32,
+
+    /// Screen space offset of the refracted image, in UV units at full wave strength.
+    pub refraction_strength: f32,
+
+    pub reflection: WaterReflection,
+
+    /// Reflected where nothing on screen is hit.
+    pub sky_color: ColorU,
+
+    /// Alpha is the foam opacity.
+    pub foam_color: ColorU,
+
+    /// Water shallower than this gets foam, in world units. Zero disables foam.
+    pub foam_distance: f32,
+
+    pub custom_update: Option<fn(f32, &mut Self)>,
+}
+
+impl Default for WaterPlane {
+    fn default() -> Self {
+        Self {
+            node_3d: Node3d::default(),
+            size: Vector2::new(10.0, 10.0),
+            shallow_color: ColorU::new(200, 240, 235, 255),
+            deep_color: ColorU::new(10, 50, 70, 255),
+            absorption_depth: 2.0,
+            normal_map: None,
+            wave_scale: 4.0,
+            normal_strength: 0.5,
+            flow: Vector2::new(0.2, 0.1),
+            flow_map: None,
+            flow_map_speed: 0.5,
+            refraction_strength: 0.03,
+            reflection: WaterReflection::default(),
+            sky_color: ColorU::new(150, 190, 230, 255),
+            foam_color: ColorU::new(240, 250, 255, 220),
+            foam_distance: 0.3,
+            custom_update: None,
+        }
+    }
+}
+
+impl WaterPlane {
+    pub fn new(size: Vector2<f32>) -> Self {
+        Self {
+            size,
+            ..Default::default()
+        }
+    }
+}
+
+impl AsNode for WaterPlane {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::WaterPlane
+    }
+
+    fn update(&mut self, dt: f32, _singletons: &mut Singletons) {
+        if self.custom_update.is_some() {
+            self.custom_update.unwrap()(dt, self);
+        }
+    }
+
+    fn draw(&self, draw_cmds: &mut DrawCommands) {
+        draw_cmds.extracted.waters.push(ExtractedWater {
+            transform: self.node_3d.transform,
+            size: self.size,
+            shallow_color: self.shallow_color,
+            deep_color: self.deep_color,
+            absorption_depth: self.absorption_depth,
+            normal_map: self.normal_map,
+            wave_scale: self.wave_scale,
+            normal_strength: self.normal_strength,
+            flow: self.flow,
+            flow_map: self.flow_map,
+            flow_map_speed: self.flow_map_speed,
+            refraction_strength: self.refraction_strength,
+            reflection: self.reflection,
+            sky_color: self.sky_color,
+            foam_color: self.foam_color,
+            foam_distance: self.foam_distance,
+        });
+    }
+}
+
+impl AsNode3d for WaterPlane {
+    fn get_transform(&self) -> Transform3d {
+        self.node_3d.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform3d) {
+        self.node_3d.transform = transform;
+    }
+}
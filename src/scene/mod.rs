@@ -1,10 +1,20 @@
+pub(crate) mod arena;
+pub(crate) mod camera_shake;
 pub(crate) mod d2;
 pub(crate) mod d3;
 
 pub(crate) mod node;
+pub(crate) mod scene_manager;
+pub(crate) mod snapshot;
+pub(crate) mod ui_event;
 pub(crate) mod world;
 
+pub use arena::*;
+pub use camera_shake::*;
 pub use d2::*;
 pub use d3::*;
 pub use node::*;
+pub use scene_manager::*;
+pub use snapshot::*;
+pub use ui_event::*;
 pub use world::*;
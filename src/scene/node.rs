@@ -11,14 +11,35 @@ pub enum NodeType {
     VectorSprite,
     Label,
     Button,
+    Control,
+    Panel,
+    Light2d,
+    LightOccluder2d,
+    Line2d,
+    ParallaxBackground,
+    ParallaxLayer,
+    Path2d,
+    PathFollow2d,
+    TileMap,
 
     // 3D
     Camera3d,
     Sprite3d,
+    Label3d,
     Model,
+    CollisionShape3d,
+    Area3d,
     Sky,
     PointLight,
     DirectionalLight,
+    ReflectionProbe,
+    TransformGizmo,
+    Trail3d,
+    BoneAttachment,
+    Path3d,
+    PathFollow3d,
+    WaterPlane,
+    Text3dMesh,
 }
 
 impl std::fmt::Display for NodeType {
@@ -29,16 +50,61 @@ impl std::fmt::Display for NodeType {
             NodeType::VectorSprite => write!(f, "VectorSprite"),
             NodeType::Label => write!(f, "Label"),
             NodeType::Button => write!(f, "Button"),
+            NodeType::Control => write!(f, "Control"),
+            NodeType::Panel => write!(f, "Panel"),
+            NodeType::Light2d => write!(f, "Light2d"),
+            NodeType::LightOccluder2d => write!(f, "LightOccluder2d"),
+            NodeType::Line2d => write!(f, "Line2d"),
+            NodeType::ParallaxBackground => write!(f, "ParallaxBackground"),
+            NodeType::ParallaxLayer => write!(f, "ParallaxLayer"),
+            NodeType::Path2d => write!(f, "Path2d"),
+            NodeType::PathFollow2d => write!(f, "PathFollow2d"),
+            NodeType::TileMap => write!(f, "TileMap"),
             NodeType::Camera3d => write!(f, "Camera3d"),
             NodeType::Sprite3d => write!(f, "Sprite3d"),
+            NodeType::Label3d => write!(f, "Label3d"),
             NodeType::Model => write!(f, "Model"),
+            NodeType::CollisionShape3d => write!(f, "CollisionShape3d"),
+            NodeType::Area3d => write!(f, "Area3d"),
             NodeType::Sky => write!(f, "Sky"),
             NodeType::PointLight => write!(f, "PointLight"),
             NodeType::DirectionalLight => write!(f, "DirectionalLight"),
+            NodeType::ReflectionProbe => write!(f, "ReflectionProbe"),
+            NodeType::TransformGizmo => write!(f, "TransformGizmo"),
+            NodeType::Trail3d => write!(f, "Trail3d"),
+            NodeType::BoneAttachment => write!(f, "BoneAttachment"),
+            NodeType::Path3d => write!(f, "Path3d"),
+            NodeType::PathFollow3d => write!(f, "PathFollow3d"),
+            NodeType::WaterPlane => write!(f, "WaterPlane"),
+            NodeType::Text3dMesh => write!(f, "Text3dMesh"),
         }
     }
 }
 
+impl NodeType {
+    /// 2D nodes get mouse positions in view units, see [`StretchMode`](crate::scene::StretchMode).
+    pub fn is_2d(&self) -> bool {
+        matches!(
+            self,
+            NodeType::Camera2d
+                | NodeType::Sprite2d
+                | NodeType::VectorSprite
+                | NodeType::Label
+                | NodeType::Button
+                | NodeType::Control
+                | NodeType::Panel
+                | NodeType::Light2d
+                | NodeType::LightOccluder2d
+                | NodeType::Line2d
+                | NodeType::ParallaxBackground
+                | NodeType::ParallaxLayer
+                | NodeType::Path2d
+                | NodeType::PathFollow2d
+                | NodeType::TileMap
+        )
+    }
+}
+
 pub trait AsNode {
     fn as_any(&self) -> &dyn Any;
 
@@ -68,4 +134,14 @@ pub trait AsNode {
     fn draw(&self, draw_cmds: &mut DrawCommands) {
         // Default implementation
     }
+
+    /// Dynamic state to keep in world snapshots, besides the transform.
+    fn save_state(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Restore the state returned by `save_state`.
+    fn load_state(&mut self, _state: &serde_json::Value) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
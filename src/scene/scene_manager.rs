@@ -0,0 +1,174 @@
+use crate::asset::AssetServer;
+use crate::render::render_world::RenderWorld;
+use crate::render::RenderServer;
+use crate::scene::NodeId;
+use crate::scene::{AsNode, World};
+use crate::text::TextServer;
+use cgmath::Vector2;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// What a scene loading step can use to build its scene.
+pub struct SceneContext<'a, 'b> {
+    /// The scene being built.
+    pub world: &'a mut World,
+    pub render_server: &'a RenderServer<'b>,
+    pub render_world: &'a mut RenderWorld,
+    pub asset_server: &'a mut AssetServer,
+    pub text_server: &'a mut TextServer,
+}
+
+impl SceneContext<'_, '_> {
+    pub fn add_node(&mut self, new_node: impl AsNode + 'static, parent: Option<NodeId>) -> NodeId {
+        self.world.add_node(Box::new(new_node), parent)
+    }
+}
+
+type SceneStep = Box<dyn FnOnce(&mut SceneContext)>;
+
+/// Builds a scene a few steps per frame, so it can load while another scene is running.
+///
+/// Each step should do a small amount of work, like loading one model.
+/// Assets should be loaded through the asset server, so they are freed with the scene.
+pub struct SceneLoader {
+    world: World,
+    steps: VecDeque<SceneStep>,
+    step_count: usize,
+}
+
+impl Default for SceneLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneLoader {
+    pub fn new() -> Self {
+        Self {
+            // The view size is set when the scene is switched to.
+            world: World::new(Vector2::new(0, 0)),
+            steps: VecDeque::new(),
+            step_count: 0,
+        }
+    }
+
+    pub fn add_step(&mut self, step: impl FnOnce(&mut SceneContext) + 'static) {
+        self.steps.push_back(Box::new(step));
+        self.step_count += 1;
+    }
+
+    /// In [0, 1].
+    pub fn get_progress(&self) -> f32 {
+        if self.step_count == 0 {
+            return 1.0;
+        }
+
+        1.0 - self.steps.len() as f32 / self.step_count as f32
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// Preloads scenes by name and switches between them.
+///
+/// The switch happens between frames, so it's safe to request from node updates.
+/// The old scene is dropped and its assets are freed at the end of the frame.
+pub struct SceneManager {
+    loaders: HashMap<String, SceneLoader>,
+
+    /// Scene to switch to once it's loaded.
+    next_scene: Option<String>,
+
+    /// Time to spend on loading steps per frame. At least one step runs per frame.
+    pub frame_budget: Duration,
+}
+
+impl Default for SceneManager {
+    fn default() -> Self {
+        Self {
+            loaders: HashMap::new(),
+            next_scene: None,
+            frame_budget: Duration::from_millis(4),
+        }
+    }
+}
+
+impl SceneManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start loading a scene in the background. Replaces a scene being loaded under the same name.
+    pub fn preload(&mut self, name: &str, loader: SceneLoader) {
+        self.loaders.insert(name.to_string(), loader);
+    }
+
+    /// Switch to a preloaded scene as soon as it has finished loading.
+    pub fn change_scene(&mut self, name: &str) {
+        if !self.loaders.contains_key(name) {
+            log::warn!("No scene named {} has been preloaded.", name);
+            return;
+        }
+
+        self.next_scene = Some(name.to_string());
+    }
+
+    /// Loading progress of a preloaded scene, in [0, 1].
+    pub fn get_progress(&self, name: &str) -> Option<f32> {
+        self.loaders.get(name).map(|l| l.get_progress())
+    }
+
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaders.get(name).is_some_and(|l| l.is_loaded())
+    }
+
+    /// Drop a preloaded scene, along with the assets only it uses.
+    pub fn cancel(&mut self, name: &str) {
+        self.loaders.remove(name);
+
+        if self.next_scene.as_deref() == Some(name) {
+            self.next_scene = None;
+        }
+    }
+
+    /// Run loading steps within the frame budget. Returns the scene to switch to, if it's ready.
+    pub(crate) fn update(
+        &mut self,
+        render_server: &RenderServer,
+        render_world: &mut RenderWorld,
+        asset_server: &mut AssetServer,
+        text_server: &mut TextServer,
+    ) -> Option<World> {
+        let start = Instant::now();
+
+        // The scene being waited for goes first.
+        let mut names: Vec<String> = self.loaders.keys().cloned().collect();
+        names.sort_by_key(|name| Some(name) != self.next_scene.as_ref());
+
+        'loaders: for name in names {
+            let loader = self.loaders.get_mut(&name).unwrap();
+
+            while let Some(step) = loader.steps.pop_front() {
+                step(&mut SceneContext {
+                    world: &mut loader.world,
+                    render_server,
+                    render_world,
+                    asset_server,
+                    text_server,
+                });
+
+                if start.elapsed() >= self.frame_budget {
+                    break 'loaders;
+                }
+            }
+        }
+
+        let name = self
+            .next_scene
+            .take_if(|name| self.loaders[name].is_loaded())?;
+
+        self.loaders.remove(&name).map(|loader| loader.world)
+    }
+}
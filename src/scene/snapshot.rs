@@ -0,0 +1,256 @@
+use crate::math::transform::Transform3d;
+use crate::scene::NodeId;
+use crate::scene::World;
+use anyhow::{anyhow, bail, Context, Result};
+use cgmath::{Quaternion, Vector2};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of the snapshot layout itself. Game data is versioned with [`Snapshot::version`].
+const FORMAT_VERSION: u32 = 1;
+
+/// Upgrades serialized snapshot data from `version` to `version + 1`.
+pub type SnapshotMigration = fn(version: u32, data: &mut serde_json::Value) -> Result<()>;
+
+/// Transform of a 2D, UI or 3D node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum TransformState {
+    D2 {
+        position: [f32; 2],
+        rotation: f32,
+        scale: [f32; 2],
+    },
+    Ui {
+        position: [f32; 2],
+        rotation: f32,
+    },
+    D3 {
+        position: [f32; 3],
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NodeState {
+    /// Checked on restore, to catch snapshots taken from a different scene.
+    node_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformState>,
+    /// From `AsNode::save_state`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<serde_json::Value>,
+}
+
+/// Dynamic state of a world, e.g. for save games.
+///
+/// Nodes are matched by their order in the scene tree, so a snapshot has to be restored
+/// into the same scene it was taken from, built the same way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    format_version: u32,
+
+    /// Version of the game's save data, passed to migrations.
+    pub version: u32,
+
+    nodes: Vec<NodeState>,
+
+    /// Game-wide state that doesn't belong to a node, like RNG seeds and timers.
+    pub globals: BTreeMap<String, serde_json::Value>,
+}
+
+impl Snapshot {
+    /// Serialize the snapshot. The output is the same for the same state.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Deserialize a snapshot, migrating it up to `version` if it's older.
+    pub fn from_bytes(
+        bytes: &[u8],
+        version: u32,
+        migrate: Option<SnapshotMigration>,
+    ) -> Result<Self> {
+        let mut data: serde_json::Value =
+            serde_json::from_slice(bytes).context("Invalid snapshot data")?;
+
+        let format_version = data["format_version"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Snapshot has no format version"))?;
+        if format_version > FORMAT_VERSION as u64 {
+            bail!("Snapshot format {} is newer than supported", format_version);
+        }
+
+        let mut data_version = data["version"].as_u64().unwrap_or(0) as u32;
+        if data_version > version {
+            bail!(
+                "Snapshot version {} is newer than {}",
+                data_version,
+                version
+            );
+        }
+
+        while data_version < version {
+            let migrate = migrate
+                .ok_or_else(|| anyhow!("Snapshot version {} needs a migration", data_version))?;
+            migrate(data_version, &mut data)
+                .with_context(|| format!("Failed to migrate snapshot from {}", data_version))?;
+
+            data_version += 1;
+            data["version"] = data_version.into();
+        }
+
+        Ok(serde_json::from_value(data)?)
+    }
+
+    /// Store a global value, e.g. an RNG seed.
+    pub fn set_global<T: Serialize>(&mut self, key: &str, value: &T) -> Result<()> {
+        self.globals
+            .insert(key.to_string(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    pub fn get_global<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<T> {
+        self.globals
+            .get(key)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+}
+
+impl World {
+    /// Capture the transforms and saved state of all nodes.
+    pub fn snapshot(&mut self, version: u32) -> Snapshot {
+        let mut nodes = vec![];
+
+        for id in self.traverse() {
+            let node_type = self.arena[id].node_type().to_string();
+            let state = self.arena[id].save_state();
+
+            let transform = self.get_transform_state(id);
+
+            nodes.push(NodeState {
+                node_type,
+                transform,
+                state,
+            });
+        }
+
+        Snapshot {
+            format_version: FORMAT_VERSION,
+            version,
+            nodes,
+            globals: BTreeMap::new(),
+        }
+    }
+
+    /// A hash of the same state as [`World::snapshot`], to tell whether two worlds that
+    /// should be in lockstep, e.g. a replay and its recording or two network peers,
+    /// diverged. It's the same across runs and machines for the same state.
+    pub fn checksum(&mut self) -> u64 {
+        let bytes = self
+            .snapshot(0)
+            .to_bytes()
+            .expect("Snapshot should always serialize");
+
+        // FNV-1a, since the std hasher may change between Rust versions.
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Restore a snapshot taken from this scene. Nothing is changed if the scene doesn't match.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let ids = self.traverse();
+
+        if ids.len() != snapshot.nodes.len() {
+            bail!(
+                "Snapshot has {} nodes, but the scene has {}",
+                snapshot.nodes.len(),
+                ids.len()
+            );
+        }
+
+        for (i, (id, node)) in ids.iter().zip(&snapshot.nodes).enumerate() {
+            let node_type = self.arena[*id].node_type().to_string();
+            if node_type != node.node_type {
+                bail!(
+                    "Node {} is a {}, but the snapshot has a {}",
+                    i,
+                    node_type,
+                    node.node_type
+                );
+            }
+        }
+
+        for (id, node) in ids.into_iter().zip(&snapshot.nodes) {
+            if let Some(transform) = &node.transform {
+                self.set_transform_state(id, transform);
+            }
+
+            if let Some(state) = &node.state {
+                self.arena[id].load_state(state)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn get_transform_state(&mut self, id: NodeId) -> Option<TransformState> {
+        if let Some(node_3d) = self.get_node_3d_mut(id) {
+            let transform = node_3d.get_transform();
+            Some(TransformState::D3 {
+                position: transform.position.into(),
+                rotation: transform.rotation.into(),
+                scale: transform.scale.into(),
+            })
+        } else if let Some(node_ui) = self.get_node_ui_mut(id) {
+            Some(TransformState::Ui {
+                position: node_ui.get_position().into(),
+                rotation: node_ui.get_rotation(),
+            })
+        } else {
+            self.get_transform_2d_mut(id)
+                .map(|transform| TransformState::D2 {
+                    position: transform.position.into(),
+                    rotation: transform.rotation,
+                    scale: transform.scale.into(),
+                })
+        }
+    }
+
+    /// Does nothing if the node doesn't have this kind of transform.
+    pub(crate) fn set_transform_state(&mut self, id: NodeId, transform: &TransformState) {
+        match *transform {
+            TransformState::D3 {
+                position,
+                rotation,
+                scale,
+            } => {
+                if let Some(node_3d) = self.get_node_3d_mut(id) {
+                    node_3d.set_transform(Transform3d {
+                        position: position.into(),
+                        rotation: Quaternion::from(rotation),
+                        scale: scale.into(),
+                    });
+                }
+            }
+            TransformState::Ui { position, rotation } => {
+                if let Some(node_ui) = self.get_node_ui_mut(id) {
+                    node_ui.set_position(Vector2::from(position));
+                    node_ui.set_rotation(rotation);
+                }
+            }
+            TransformState::D2 {
+                position,
+                rotation,
+                scale,
+            } => {
+                if let Some(transform) = self.get_transform_2d_mut(id) {
+                    transform.position = Vector2::from(position);
+                    transform.rotation = rotation;
+                    transform.scale = Vector2::from(scale);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! What users do with the UI, described by meaning rather than by raw input, for screen
+//! readers, narration and test drivers. See [`crate::scene::World::subscribe_ui_events`].
+
+use crate::scene::NodeId;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEventKind {
+    /// A button was activated.
+    Pressed,
+    /// The value of a control changed, e.g. a toggle button switched to "on".
+    ValueChanged {
+        value: String,
+    },
+    FocusGained,
+    FocusLost,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiEvent {
+    pub node: NodeId,
+    /// Human readable name of the node, e.g. a button's text. Can be empty.
+    pub name: String,
+    pub kind: UiEventKind,
+}
@@ -1,33 +1,71 @@
 use crate::core::singleton::Singletons;
+use crate::math::bvh::Bvh;
+use crate::math::transform::{Transform2d, Transform3d};
+use crate::physics::shape::PosedShape;
+use crate::physics::{Aabb, Ray3d, RayHit};
 use crate::render::draw_command::DrawCommands;
-use crate::scene::{AsNode, Camera2d, Camera3d, NodeType};
-use crate::window::InputServer;
-use cgmath::Vector2;
-use indextree::{Arena, NodeEdge, NodeId};
+use crate::scene::{
+    Area3d, AsNode, AsNode3d, AsNodeUi, BoneAttachment, Button, Camera2d, Camera3d,
+    CollisionShape3d, Control, GizmoView, Label, Label3d, Light2d, LightOccluder2d, Line2d, Model,
+    NodeType, Panel, ParallaxBackground, ParallaxLayer, Path2d, Path3d, PathFollow2d, PathFollow3d,
+    PointLight, Sprite2d, Sprite3d, Text3dMesh, TileMap, Trail3d, TransformGizmo, UiEvent,
+    UiEventKind, VectorSprite, WaterPlane,
+};
+use crate::scene::{Components, NodeArena, NodeId};
+use crate::window::{InputEvent, InputServer};
+use cgmath::{Matrix4, Vector2};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::mpsc;
+
+/// A collision shape or area gathered from the scene tree.
+struct Collider<'a> {
+    id: NodeId,
+    posed: PosedShape<'a>,
+    aabb: Aabb,
+    layer: u32,
+    /// Only areas have a mask.
+    mask: Option<u32>,
+}
+
+/// How a node is drawn, kept apart from the node itself.
+#[derive(Default)]
+struct Visibility {
+    /// Not drawn, along with its children.
+    hidden: bool,
+    /// Children are drawn in the order of their Y position.
+    y_sorted: bool,
+}
 
 pub struct World {
     // Type Box<dyn AsNode> is a trait object;
     // it's a stand-in for any type inside a Box that implements the AsNode trait.
-    pub arena: Arena<Box<dyn AsNode>>,
+    pub arena: NodeArena,
 
     root_node: Option<NodeId>,
 
     current_camera2d: Option<NodeId>,
     current_camera3d: Option<NodeId>,
 
+    visibility: Components<Visibility>,
+
     view_size: Vector2<u32>,
+
+    /// UI node that has keyboard focus.
+    ui_focus: Option<NodeId>,
+    ui_event_senders: Vec<mpsc::Sender<UiEvent>>,
 }
 
 impl World {
     pub fn new(view_size: Vector2<u32>) -> Self {
-        let mut arena = Arena::new();
-
         Self {
-            arena,
+            arena: NodeArena::new(),
             root_node: None,
             current_camera2d: None,
             current_camera3d: None,
+            visibility: Components::new(),
             view_size,
+            ui_focus: None,
+            ui_event_senders: vec![],
         }
     }
 
@@ -57,61 +95,207 @@ impl World {
             // Set the root as the parent if no parent is provided.
             let parent = parent.unwrap_or_else(|| self.root_node.unwrap());
 
-            parent.append(id, &mut self.arena);
+            self.arena.append(parent, id);
         }
 
         // After the node is added to the tree, call its ready() function.
-        self.arena[id].get_mut().ready();
+        self.arena[id].ready();
 
         id
     }
 
-    fn traverse(&self) -> Vec<NodeId> {
-        let mut ids: Vec<NodeId> = vec![];
-
-        // Node depth in the tree.
-        let mut depths: Vec<i32> = vec![];
-        let mut current_depth = 0;
+    /// Remove a node and all its children from the tree. Assets only they
+    /// referenced are freed at the end of the frame.
+    pub fn remove_node(&mut self, id: NodeId) {
+        if !self.arena.contains(id) {
+            return;
+        }
 
-        match self.root_node {
-            None => {
-                log::warn!("No root node in the scene tree.");
-            }
-            Some(root) => {
-                let iter = root.traverse(&self.arena).filter_map(|edge| match edge {
-                    NodeEdge::Start(id) => {
-                        depths.push(current_depth);
-                        current_depth += 1;
+        let removed: Vec<NodeId> = self.arena.descendants(id).collect();
 
-                        Some(id)
-                    }
-                    NodeEdge::End(_) => {
-                        current_depth -= 1;
+        if self.root_node == Some(id) {
+            self.root_node = None;
+        }
+        if self.current_camera2d.is_some_and(|c| removed.contains(&c)) {
+            self.current_camera2d = None;
+        }
+        if self.current_camera3d.is_some_and(|c| removed.contains(&c)) {
+            self.current_camera3d = None;
+        }
 
-                        None
-                    }
-                });
+        if self.ui_focus.is_some_and(|c| removed.contains(&c)) {
+            self.ui_focus = None;
+        }
 
-                ids = iter.map(|id| id).collect();
-            }
+        for id in &removed {
+            self.visibility.remove(*id);
         }
 
-        ids
+        self.arena.remove_subtree(id);
+    }
+
+    /// Hide or show a node and its children. Hidden nodes are still updated.
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        self.visibility.get_or_insert_default(id).hidden = !visible;
+    }
+
+    pub fn is_visible(&self, id: NodeId) -> bool {
+        !self.visibility.get(id).is_some_and(|v| v.hidden)
+    }
+
+    /// Draw the children of a node from top to bottom by their Y position, so that
+    /// lower ones cover higher ones, e.g. for characters walking behind each other
+    /// in a top-down view. Each child is drawn along with its own children.
+    ///
+    /// Children without a 2D position are drawn first, and ties keep the tree order.
+    /// Like the tree order, this only orders draws of the same kind: sprites still go
+    /// under lines and vector sprites.
+    pub fn set_y_sort(&mut self, id: NodeId, enabled: bool) {
+        self.visibility.get_or_insert_default(id).y_sorted = enabled;
+    }
+
+    pub fn is_y_sorted(&self, id: NodeId) -> bool {
+        self.visibility.get(id).is_some_and(|v| v.y_sorted)
+    }
+
+    /// All nodes, parents before their children and children in the order they were
+    /// added. Updates, snapshots and checksums go in this order, so it's the same on
+    /// every run that builds the scene the same way.
+    pub(crate) fn traverse(&self) -> Vec<NodeId> {
+        match self.root_node {
+            // Fine if everything is drawn through draw sources.
+            None => vec![],
+            Some(root) => self.arena.descendants(root).collect(),
+        }
     }
 
     pub fn input(&mut self, input_server: &mut InputServer) {
         for mut event in input_server.input_events.clone() {
+            let mut view_event = self.to_view_event(event);
+
             // Input events propagate reversely.
             for id in self.traverse().iter().rev() {
-                self.arena[*id].get_mut().input(&mut event, input_server);
+                let node = &mut self.arena[*id];
+
+                if node.node_type().is_2d() {
+                    node.input(&mut view_event, input_server);
+                } else {
+                    node.input(&mut event, input_server);
+                }
             }
         }
+
+        self.collect_ui_events();
+    }
+
+    /// Mouse positions in window pixels to view units of the current 2D camera.
+    fn to_view_event(&self, event: InputEvent) -> InputEvent {
+        let Some(camera) = self
+            .current_camera2d
+            .and_then(|id| self.get_node::<Camera2d>(id))
+        else {
+            return event;
+        };
+
+        let to_view = |position: (f32, f32)| -> (f32, f32) {
+            let view = camera.window_to_view(position);
+            (view.x, view.y)
+        };
+
+        match event {
+            InputEvent::MouseMotion(mut motion) => {
+                let scale = camera.get_stretch_scale();
+                motion.position = to_view(motion.position);
+                motion.delta = (motion.delta.0 / scale.x, motion.delta.1 / scale.y);
+                InputEvent::MouseMotion(motion)
+            }
+            InputEvent::MouseButton(mut button) => {
+                button.position = to_view(button.position);
+                InputEvent::MouseButton(button)
+            }
+            _ => event,
+        }
+    }
+
+    /// Size the UI is laid out in. The window size, unless the 2D camera stretches it.
+    pub fn get_ui_view_size(&self) -> Vector2<u32> {
+        self.current_camera2d
+            .and_then(|id| self.get_node::<Camera2d>(id))
+            .map(|camera| camera.view_size)
+            .filter(|size| size.x > 0 && size.y > 0)
+            .unwrap_or(self.view_size)
+    }
+
+    /// Receive semantic UI events, e.g. for narration. Events are sent as nodes report them,
+    /// during input handling and updates. Dropping the receiver unsubscribes.
+    pub fn subscribe_ui_events(&mut self) -> mpsc::Receiver<UiEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.ui_event_senders.push(sender);
+
+        receiver
+    }
+
+    pub fn get_ui_focus(&self) -> Option<NodeId> {
+        self.ui_focus
+    }
+
+    /// Move keyboard focus to a UI node, or clear it.
+    pub fn set_ui_focus(&mut self, id: Option<NodeId>) {
+        if self.ui_focus == id {
+            return;
+        }
+
+        if let Some(old) = self.ui_focus {
+            self.send_ui_event(old, UiEventKind::FocusLost);
+        }
+
+        self.ui_focus = id;
+
+        if let Some(new) = id {
+            self.send_ui_event(new, UiEventKind::FocusGained);
+        }
+    }
+
+    fn collect_ui_events(&mut self) {
+        for id in self.traverse() {
+            let Some(node) = self.get_node_ui_mut(id) else {
+                continue;
+            };
+
+            for kind in node.take_ui_events() {
+                // Clicking a control focuses it.
+                if kind == UiEventKind::Pressed {
+                    self.set_ui_focus(Some(id));
+                }
+
+                self.send_ui_event(id, kind);
+            }
+        }
+    }
+
+    fn send_ui_event(&mut self, id: NodeId, kind: UiEventKind) {
+        if self.ui_event_senders.is_empty() {
+            return;
+        }
+
+        let event = UiEvent {
+            node: id,
+            name: self
+                .get_node_ui_mut(id)
+                .map(|node| node.get_ui_name())
+                .unwrap_or_default(),
+            kind,
+        };
+
+        // Forget receivers that were dropped.
+        self.ui_event_senders
+            .retain(|sender| sender.send(event.clone()).is_ok());
     }
 
     /// Get a reference to a node by its ID.
     pub fn get_node<T: 'static>(&self, id: NodeId) -> Option<&T> {
         // Get the pointer to the node.
-        let node_ptr = self.arena[id].get();
+        let node_ptr = self.arena.get(id)?;
 
         // Downcast it to the original type.
         match node_ptr.as_any().downcast_ref::<T>() {
@@ -123,7 +307,7 @@ impl World {
     /// Get a mutable reference to a node by its ID.
     pub fn get_node_mut<T: 'static>(&mut self, id: NodeId) -> Option<&mut T> {
         // Get the pointer to the node.
-        let node_ptr = self.arena[id].get_mut();
+        let node_ptr = self.arena.get_mut(id)?;
 
         // Downcast it to the original type.
         match node_ptr.as_any_mut().downcast_mut::<T>() {
@@ -132,27 +316,591 @@ impl World {
         }
     }
 
+    /// Get mutable references to two different nodes at once, e.g. to update one
+    /// from the other. None if the IDs are the same or either type doesn't match.
+    pub fn get_node_pair_mut<A: 'static, B: 'static>(
+        &mut self,
+        a: NodeId,
+        b: NodeId,
+    ) -> Option<(&mut A, &mut B)> {
+        let [a, b] = self.arena.get_many_mut([a, b])?;
+
+        Some((
+            a.as_any_mut().downcast_mut::<A>()?,
+            b.as_any_mut().downcast_mut::<B>()?,
+        ))
+    }
+
     pub fn update(&mut self, dt: f32, singletons: &mut Singletons) {
         for id in self.traverse() {
-            self.arena[id].get_mut().update(dt, singletons);
+            self.arena[id].update(dt, singletons);
         }
 
+        self.collect_ui_events();
+
+        self.update_ik();
+
+        self.update_bone_attachments();
+
+        self.update_path_follows();
+
+        self.update_collisions();
+
+        self.update_gizmos();
+
+        self.update_trails();
+
+        self.update_parallax();
+
         // Reload assets.
         singletons.asset_server.update();
     }
 
+    fn collect_colliders(&self) -> Vec<Collider<'_>> {
+        let mut colliders = vec![];
+
+        for id in self.traverse() {
+            let (shape, transform, layer, mask) = match self.arena[id].node_type() {
+                NodeType::CollisionShape3d => {
+                    let node = self.get_node::<CollisionShape3d>(id).unwrap();
+                    (
+                        &node.shape,
+                        node.node_3d.transform,
+                        node.collision_layer,
+                        None,
+                    )
+                }
+                NodeType::Area3d => {
+                    let node = self.get_node::<Area3d>(id).unwrap();
+                    (
+                        &node.shape,
+                        node.node_3d.transform,
+                        node.collision_layer,
+                        Some(node.collision_mask),
+                    )
+                }
+                _ => continue,
+            };
+
+            let posed = PosedShape::new(shape, transform);
+
+            colliders.push(Collider {
+                id,
+                aabb: posed.world_aabb(),
+                posed,
+                layer,
+                mask,
+            });
+        }
+
+        colliders
+    }
+
+    /// Detect overlaps for all areas and fire their enter/exit events. Ordered by ID, so
+    /// that events come in the same order on every run.
+    fn update_collisions(&mut self) {
+        let mut overlaps: BTreeMap<NodeId, BTreeSet<NodeId>> = BTreeMap::new();
+
+        {
+            let mut colliders = self.collect_colliders();
+
+            // Broadphase: sweep and prune along the X axis.
+            colliders.sort_by(|a, b| a.aabb.min.x.total_cmp(&b.aabb.min.x));
+
+            for (i, a) in colliders.iter().enumerate() {
+                if a.mask.is_some() {
+                    overlaps.entry(a.id).or_default();
+                }
+
+                for b in &colliders[i + 1..] {
+                    if b.aabb.min.x > a.aabb.max.x {
+                        break;
+                    }
+
+                    let a_sees_b = a.mask.is_some_and(|mask| mask & b.layer != 0);
+                    let b_sees_a = b.mask.is_some_and(|mask| mask & a.layer != 0);
+
+                    if !(a_sees_b || b_sees_a) || !a.aabb.overlaps(&b.aabb) {
+                        continue;
+                    }
+
+                    // Narrowphase.
+                    if !a.posed.overlaps(&b.posed) {
+                        continue;
+                    }
+
+                    if a_sees_b {
+                        overlaps.entry(a.id).or_default().insert(b.id);
+                    }
+                    if b_sees_a {
+                        overlaps.entry(b.id).or_default().insert(a.id);
+                    }
+                }
+            }
+        }
+
+        for (id, overlapping) in overlaps {
+            self.get_node_mut::<Area3d>(id)
+                .unwrap()
+                .set_overlapping(overlapping);
+        }
+    }
+
+    /// Get a 3D node as AsNode3d, for code that only cares about its transform.
+    pub fn get_node_3d_mut(&mut self, id: NodeId) -> Option<&mut dyn AsNode3d> {
+        let node = &mut self.arena[id];
+
+        let node_3d: &mut dyn AsNode3d = match node.node_type() {
+            NodeType::Model => node.as_any_mut().downcast_mut::<Model>()?,
+            NodeType::Sprite3d => node.as_any_mut().downcast_mut::<Sprite3d>()?,
+            NodeType::Label3d => node.as_any_mut().downcast_mut::<Label3d>()?,
+            NodeType::CollisionShape3d => node.as_any_mut().downcast_mut::<CollisionShape3d>()?,
+            NodeType::Area3d => node.as_any_mut().downcast_mut::<Area3d>()?,
+            NodeType::PointLight => node.as_any_mut().downcast_mut::<PointLight>()?,
+            NodeType::BoneAttachment => node.as_any_mut().downcast_mut::<BoneAttachment>()?,
+            NodeType::Path3d => node.as_any_mut().downcast_mut::<Path3d>()?,
+            NodeType::PathFollow3d => node.as_any_mut().downcast_mut::<PathFollow3d>()?,
+            NodeType::WaterPlane => node.as_any_mut().downcast_mut::<WaterPlane>()?,
+            NodeType::Text3dMesh => node.as_any_mut().downcast_mut::<Text3dMesh>()?,
+            _ => return None,
+        };
+
+        Some(node_3d)
+    }
+
+    /// Get a UI node as AsNodeUi, for code that only cares about its transform.
+    pub fn get_node_ui_mut(&mut self, id: NodeId) -> Option<&mut dyn AsNodeUi> {
+        let node = &mut self.arena[id];
+
+        let node_ui: &mut dyn AsNodeUi = match node.node_type() {
+            NodeType::Sprite2d => node.as_any_mut().downcast_mut::<Sprite2d>()?,
+            NodeType::Label => node.as_any_mut().downcast_mut::<Label>()?,
+            NodeType::Button => node.as_any_mut().downcast_mut::<Button>()?,
+            NodeType::Panel => node.as_any_mut().downcast_mut::<Panel>()?,
+            NodeType::Control => node.as_any_mut().downcast_mut::<Control>()?,
+            _ => return None,
+        };
+
+        Some(node_ui)
+    }
+
+    /// Get the transform of a 2D node that isn't a UI node.
+    pub(crate) fn get_transform_2d_mut(&mut self, id: NodeId) -> Option<&mut Transform2d> {
+        let node = &mut self.arena[id];
+
+        let transform = match node.node_type() {
+            NodeType::Camera2d => &mut node.as_any_mut().downcast_mut::<Camera2d>()?.transform,
+            NodeType::Light2d => &mut node.as_any_mut().downcast_mut::<Light2d>()?.transform,
+            NodeType::LightOccluder2d => {
+                &mut node
+                    .as_any_mut()
+                    .downcast_mut::<LightOccluder2d>()?
+                    .transform
+            }
+            NodeType::Line2d => &mut node.as_any_mut().downcast_mut::<Line2d>()?.transform,
+            NodeType::Path2d => &mut node.as_any_mut().downcast_mut::<Path2d>()?.transform,
+            NodeType::PathFollow2d => {
+                &mut node.as_any_mut().downcast_mut::<PathFollow2d>()?.transform
+            }
+            NodeType::TileMap => &mut node.as_any_mut().downcast_mut::<TileMap>()?.transform,
+            NodeType::VectorSprite => {
+                &mut node.as_any_mut().downcast_mut::<VectorSprite>()?.transform
+            }
+            _ => return None,
+        };
+
+        Some(transform)
+    }
+
+    /// Position of a UI node or of a node with a 2D transform.
+    fn get_position_2d(&mut self, id: NodeId) -> Option<Vector2<f32>> {
+        if let Some(node_ui) = self.get_node_ui_mut(id) {
+            return Some(node_ui.get_position());
+        }
+
+        self.get_transform_2d_mut(id)
+            .map(|transform| transform.position)
+    }
+
+    /// Move a 2D node by `delta`. Nodes without a 2D transform are left as is.
+    fn translate_node_2d(&mut self, id: NodeId, delta: Vector2<f32>) {
+        if let Some(node_ui) = self.get_node_ui_mut(id) {
+            let position = node_ui.get_position();
+            node_ui.set_position(position + delta);
+            return;
+        }
+
+        if let Some(transform) = self.get_transform_2d_mut(id) {
+            transform.position += delta;
+        }
+    }
+
+    /// Offset the contents of parallax layers to follow the 2D camera.
+    fn update_parallax(&mut self) {
+        let camera_position = self.current_camera2d.map_or(Vector2::new(0.0, 0.0), |id| {
+            self.get_node::<Camera2d>(id).unwrap().transform.position
+        });
+
+        let background_ids: Vec<NodeId> = self
+            .traverse()
+            .into_iter()
+            .filter(|id| matches!(self.arena[*id].node_type(), NodeType::ParallaxBackground))
+            .collect();
+
+        for background_id in background_ids {
+            let layer_ids: Vec<NodeId> = self
+                .arena
+                .children(background_id)
+                .filter(|id| matches!(self.arena[*id].node_type(), NodeType::ParallaxLayer))
+                .collect();
+
+            for layer_id in layer_ids {
+                let (background, layer) = self
+                    .get_node_pair_mut::<ParallaxBackground, ParallaxLayer>(background_id, layer_id)
+                    .unwrap();
+
+                let offset = layer.calc_offset(camera_position, background);
+                let delta = layer.apply_offset(offset);
+
+                if delta == Vector2::new(0.0, 0.0) {
+                    continue;
+                }
+
+                let descendants: Vec<NodeId> = self.arena.descendants(layer_id).skip(1).collect();
+
+                for id in descendants {
+                    self.translate_node_2d(id, delta);
+                }
+            }
+        }
+    }
+
+    /// Solve IK constraints of models, now that their animations are applied.
+    fn update_ik(&mut self) {
+        let model_ids: Vec<NodeId> = self
+            .traverse()
+            .into_iter()
+            .filter(|id| matches!(self.arena[*id].node_type(), NodeType::Model))
+            .collect();
+
+        for id in model_ids {
+            let model = self.get_node::<Model>(id).unwrap();
+            if model.skeleton.is_none() || model.ik_constraints.is_empty() {
+                continue;
+            }
+
+            let nodes: Vec<(Option<NodeId>, Option<NodeId>)> = model
+                .ik_constraints
+                .iter()
+                .map(|c| (c.target, c.pole))
+                .collect();
+
+            // Follow the target and pole nodes.
+            let mut positions = vec![];
+            for (target, pole) in nodes {
+                let mut position_of = |node: Option<NodeId>| {
+                    let node = node.filter(|node| self.arena.contains(*node))?;
+                    self.get_node_3d_mut(node)
+                        .map(|n| n.get_transform().position)
+                };
+
+                positions.push((position_of(target), position_of(pole)));
+            }
+
+            let model = self.get_node_mut::<Model>(id).unwrap();
+            for (constraint, (target, pole)) in model.ik_constraints.iter_mut().zip(positions) {
+                if let Some(target) = target {
+                    constraint.target_position = target;
+                }
+                if pole.is_some() {
+                    constraint.pole_position = pole;
+                }
+            }
+
+            model.solve_ik();
+        }
+    }
+
+    /// Move bone attachments to their joints, along with their children.
+    fn update_bone_attachments(&mut self) {
+        let attachment_ids: Vec<NodeId> = self
+            .traverse()
+            .into_iter()
+            .filter(|id| matches!(self.arena[*id].node_type(), NodeType::BoneAttachment))
+            .collect();
+
+        for id in attachment_ids {
+            let attachment = self.get_node::<BoneAttachment>(id).unwrap();
+
+            let Some(model) = attachment
+                .model
+                .filter(|model| self.arena.contains(*model))
+                .and_then(|model| self.get_node::<Model>(model))
+            else {
+                continue;
+            };
+            let Some(skeleton) = &model.skeleton else {
+                continue;
+            };
+            let Some(joint) = skeleton.find_joint(&attachment.joint) else {
+                continue;
+            };
+
+            let matrix = Matrix4::from(model.get_transform().to_matrix())
+                * skeleton.get_global_transform(joint)
+                * Matrix4::from(attachment.offset.to_matrix());
+
+            let carry = self
+                .get_node_mut::<BoneAttachment>(id)
+                .unwrap()
+                .follow(Transform3d::from_matrix(matrix));
+
+            self.carry_descendants_3d(id, carry);
+        }
+    }
+
+    /// Move path followers along their parent paths, carrying their descendants along.
+    fn update_path_follows(&mut self) {
+        let follow_ids: Vec<(NodeId, NodeId)> = self
+            .traverse()
+            .into_iter()
+            .filter_map(|id| {
+                let parent = self.arena.parent(id)?;
+
+                match (self.arena[id].node_type(), self.arena[parent].node_type()) {
+                    (NodeType::PathFollow3d, NodeType::Path3d)
+                    | (NodeType::PathFollow2d, NodeType::Path2d) => Some((id, parent)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        for (id, path_id) in follow_ids {
+            if let Some(path) = self.get_node::<Path3d>(path_id) {
+                let length = path.path.get_length();
+                let offset = self
+                    .get_node_mut::<PathFollow3d>(id)
+                    .unwrap()
+                    .wrap_offset(length);
+
+                let Some(transform) = self
+                    .get_node::<Path3d>(path_id)
+                    .unwrap()
+                    .get_transform_at(offset)
+                else {
+                    continue;
+                };
+
+                let carry = self
+                    .get_node_mut::<PathFollow3d>(id)
+                    .unwrap()
+                    .follow(transform);
+                self.carry_descendants_3d(id, carry);
+            } else {
+                let path = self.get_node::<Path2d>(path_id).unwrap();
+                let length = path.path.get_length();
+                let offset = self
+                    .get_node_mut::<PathFollow2d>(id)
+                    .unwrap()
+                    .wrap_offset(length);
+
+                let Some(transform) = self
+                    .get_node::<Path2d>(path_id)
+                    .unwrap()
+                    .get_transform_at(offset)
+                else {
+                    continue;
+                };
+
+                let delta = self
+                    .get_node_mut::<PathFollow2d>(id)
+                    .unwrap()
+                    .follow(transform);
+                if delta == Vector2::new(0.0, 0.0) {
+                    continue;
+                }
+
+                let descendants: Vec<NodeId> = self.arena.descendants(id).skip(1).collect();
+                for id in descendants {
+                    self.translate_node_2d(id, delta);
+                }
+            }
+        }
+    }
+
+    /// Apply `carry` to the transforms of the 3D descendants of a node.
+    fn carry_descendants_3d(&mut self, id: NodeId, carry: impl Fn(Transform3d) -> Transform3d) {
+        let descendants: Vec<NodeId> = self.arena.descendants(id).skip(1).collect();
+
+        for id in descendants {
+            let Some(node_3d) = self.get_node_3d_mut(id) else {
+                continue;
+            };
+
+            node_3d.set_transform(carry(node_3d.get_transform()));
+        }
+    }
+
+    /// Move the head of each trail to its target.
+    fn update_trails(&mut self) {
+        let trail_ids: Vec<NodeId> = self
+            .traverse()
+            .into_iter()
+            .filter(|id| matches!(self.arena[*id].node_type(), NodeType::Trail3d))
+            .collect();
+
+        for id in trail_ids {
+            let Some(target) = self
+                .get_node::<Trail3d>(id)
+                .unwrap()
+                .target
+                .filter(|target| self.arena.contains(*target))
+            else {
+                continue;
+            };
+
+            let Some(node_3d) = self.get_node_3d_mut(target) else {
+                continue;
+            };
+            let position = node_3d.get_transform().position;
+
+            self.get_node_mut::<Trail3d>(id).unwrap().emit(position);
+        }
+    }
+
+    /// Let transform gizmos pick and drag their handles, then apply the edits to their targets.
+    fn update_gizmos(&mut self) {
+        let Some(camera_id) = self.current_camera3d else {
+            return;
+        };
+
+        let gizmo_ids: Vec<NodeId> = self
+            .traverse()
+            .into_iter()
+            .filter(|id| matches!(self.arena[*id].node_type(), NodeType::TransformGizmo))
+            .collect();
+
+        for id in gizmo_ids {
+            let gizmo = self.get_node::<TransformGizmo>(id).unwrap();
+
+            let Some(target) = gizmo.target.filter(|target| self.arena.contains(*target)) else {
+                continue;
+            };
+            let mouse_position = gizmo.get_mouse_position();
+
+            let Some(node_3d) = self.get_node_3d_mut(target) else {
+                continue;
+            };
+            let transform = node_3d.get_transform();
+
+            let camera = self.get_node::<Camera3d>(camera_id).unwrap();
+            let view = GizmoView {
+                mouse_ray: camera.screen_to_ray(mouse_position, self.view_size),
+                pixel_size: camera.pixel_size_at(transform.position, self.view_size),
+            };
+
+            let edited = self
+                .get_node_mut::<TransformGizmo>(id)
+                .unwrap()
+                .process(view, transform);
+
+            if let Some(edited) = edited {
+                self.get_node_3d_mut(target).unwrap().set_transform(edited);
+            }
+        }
+    }
+
+    /// Cast a ray against all collision shapes matching the mask and return the closest hit.
+    /// Areas are ignored.
+    pub fn intersect_ray(
+        &self,
+        ray: &Ray3d,
+        max_distance: f32,
+        collision_mask: u32,
+    ) -> Option<RayHit> {
+        let colliders: Vec<Collider> = self
+            .collect_colliders()
+            .into_iter()
+            .filter(|c| c.mask.is_none() && c.layer & collision_mask != 0)
+            .collect();
+
+        let (bvh, _) = Bvh::build(colliders.iter().map(|c| (c.aabb, c)).collect());
+
+        let mut closest: Option<RayHit> = None;
+
+        // Closest boxes first, so the rest can be skipped once a hit is in front of them.
+        for (_, c, entry) in bvh.query_ray(ray, max_distance) {
+            let max_distance = closest.map_or(max_distance, |hit| hit.distance);
+            if entry > max_distance {
+                break;
+            }
+
+            if let Some((distance, normal)) = c.posed.intersect_ray(ray, max_distance) {
+                closest = Some(RayHit {
+                    node: c.id,
+                    position: ray.at(distance),
+                    normal,
+                    distance,
+                });
+            }
+        }
+
+        closest
+    }
+
     pub fn queue_draw(&mut self) -> DrawCommands {
         let mut draw_cmds = DrawCommands::default();
-        draw_cmds.view_info.view_size = self.view_size;
+        draw_cmds.view_info.view_size = self.get_ui_view_size();
 
         // Collect draw commands from the scene tree.
-        for id in self.traverse() {
-            self.arena[id].get().draw(&mut draw_cmds);
+        if let Some(root) = self.root_node {
+            self.queue_draw_node(root, &mut draw_cmds);
         }
 
         draw_cmds
     }
 
+    /// Draw a node, then its children.
+    fn queue_draw_node(&mut self, id: NodeId, draw_cmds: &mut DrawCommands) {
+        if !self.is_visible(id) {
+            return;
+        }
+
+        // Clips pushed by a node apply to its subtree, so pop them when leaving the node.
+        let depth = draw_cmds.get_clip_depth();
+
+        self.arena[id].draw(draw_cmds);
+
+        if self.is_y_sorted(id) {
+            let children: Vec<NodeId> = self.arena.children(id).collect();
+
+            let mut sorted: Vec<(f32, NodeId)> = children
+                .into_iter()
+                .map(|child| {
+                    let y = self
+                        .get_position_2d(child)
+                        .map_or(f32::NEG_INFINITY, |position| position.y);
+                    (y, child)
+                })
+                .collect();
+            sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            for (_, child) in sorted {
+                self.queue_draw_node(child, draw_cmds);
+            }
+        } else {
+            let mut child = self.arena.first_child(id);
+            while let Some(id) = child {
+                self.queue_draw_node(id, draw_cmds);
+                child = self.arena.next_sibling(id);
+            }
+        }
+
+        while draw_cmds.get_clip_depth() > depth {
+            draw_cmds.pop_clip();
+        }
+    }
+
     pub fn when_view_size_changes(&mut self, new_size: Vector2<u32>) {
         self.view_size = new_size;
 
@@ -5,6 +5,8 @@
 struct AtlasParams {
     camera_view_size: vec2<f32>,
     atlas_size: vec2<f32>,
+    // Coverage exponent for text, 1.0 leaves glyph coverage untouched.
+    text_gamma: f32,
 }
 
 @group(0) @binding(0)
@@ -30,7 +32,12 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32, instance: InstanceInput)
     let u0 = ((in_vertex_index << 1u) & 2u) >> 1u; // [0, 1]
     let v0 = ((in_vertex_index & 2u)) >> 1u; // [0, 1]
 
+    // On the near plane.
+#ifdef REVERSE_Z
+    let position = vec4<f32>(f32(u0), 1.0 - f32(v0), 1.0, 1.0);
+#else
     let position = vec4<f32>(f32(u0), 1.0 - f32(v0), 0.0, 1.0);
+#endif
 
     let u = instance.region[u0 * 2u];
     let v = instance.region[v0 * 2u + 1u];
@@ -68,11 +75,43 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 
+// Coverage is blended in linear space, which makes thin strokes look too light or too heavy.
+fn adjust_coverage(coverage: vec3<f32>) -> vec3<f32> {
+    return pow(coverage, vec3<f32>(1.0 / params.text_gamma));
+}
+
+#ifdef DUAL_SOURCE
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    // Per-channel coverage, the pipeline blends with dst * (1 - mask).
+    @location(0) @second_blend_source mask: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let mask = adjust_coverage(textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb);
+    let alpha = in.color.a * max(mask.r, max(mask.g, mask.b));
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color.rgb * mask, alpha);
+    out.mask = vec4<f32>(in.color.a * mask, alpha);
+
+    return out;
+}
+#else
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 #ifdef TEXT
-        return in.color * textureSample(t_diffuse, s_diffuse, in.tex_coords).r;
+#ifdef SUBPIXEL
+        // No dual-source blending, average the subpixel coverage.
+        let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb;
+        let coverage = adjust_coverage(vec3<f32>((texel.r + texel.g + texel.b) / 3.0)).r;
+#else
+        let coverage = adjust_coverage(textureSample(t_diffuse, s_diffuse, in.tex_coords).rrr).r;
+#endif
+        return in.color * coverage;
 #else
         return in.color * textureSample(t_diffuse, s_diffuse, in.tex_coords);
 #endif
 }
+#endif
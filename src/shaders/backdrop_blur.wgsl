@@ -0,0 +1,55 @@
+// One direction of a separable gaussian blur, used for UI backdrop blur.
+// A zero step turns it into a plain copy.
+
+struct Params {
+    // Offset between two taps, in UV units of the source texture.
+    step: vec2<f32>,
+    _pad: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_source: sampler;
+
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Vertex shader //
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+// Fragment shader //
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // 9-tap gaussian weights, the center tap comes first.
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+    var color = textureSample(t_source, s_source, in.uv) * weights[0];
+
+    for (var i = 1; i < 5; i++) {
+        let offset = params.step * f32(i);
+        color += textureSample(t_source, s_source, in.uv + offset) * weights[i];
+        color += textureSample(t_source, s_source, in.uv - offset) * weights[i];
+    }
+
+    return color;
+}
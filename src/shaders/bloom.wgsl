@@ -0,0 +1,122 @@
+// Bloom: bright pass, downsample and upsample chain, then resolve to the surface.
+
+struct Params {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    mip_count: f32,
+    max_color: f32,
+    _pad0: f32,
+    _pad1: f32,
+    _pad2: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(0)
+var t_source: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s_source: sampler;
+
+// Only used by the resolve pass.
+@group(1) @binding(2)
+var t_bloom: texture_2d<f32>;
+
+@group(1) @binding(3)
+var s_bloom: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Vertex shader //
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+// Fragment shader //
+
+// Center plus four diagonal bilinear taps, which averages a 4x4 texel block.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    var color = textureSample(t_source, s_source, uv).rgb * 4.0;
+    color += textureSample(t_source, s_source, uv + texel * vec2<f32>(-1.0, -1.0)).rgb;
+    color += textureSample(t_source, s_source, uv + texel * vec2<f32>(1.0, -1.0)).rgb;
+    color += textureSample(t_source, s_source, uv + texel * vec2<f32>(-1.0, 1.0)).rgb;
+    color += textureSample(t_source, s_source, uv + texel * vec2<f32>(1.0, 1.0)).rgb;
+
+    return color / 8.0;
+}
+
+// Keep what's brighter than the threshold, fading in over the knee with a quadratic curve.
+fn bright_pass(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = params.threshold * params.knee + 0.00001;
+
+    var soft = clamp(brightness - params.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.00001);
+
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Very bright pixels would flicker as single-pixel highlights move.
+    let color = min(downsample(in.uv), vec3<f32>(64.0));
+
+    return vec4<f32>(bright_pass(color), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, blended additively onto the next larger mip.
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    var color = textureSample(t_source, s_source, in.uv).rgb * 4.0;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(-1.0, 0.0)).rgb * 2.0;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(1.0, 0.0)).rgb * 2.0;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(0.0, -1.0)).rgb * 2.0;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(0.0, 1.0)).rgb * 2.0;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(-1.0, -1.0)).rgb;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(1.0, -1.0)).rgb;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(-1.0, 1.0)).rgb;
+    color += textureSample(t_source, s_source, in.uv + texel * vec2<f32>(1.0, 1.0)).rgb;
+
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+// Add the bloom to the scene and clamp it for the surface, unless the display is HDR.
+@fragment
+fn fs_resolve(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene = textureSample(t_source, s_source, in.uv);
+    // Every mip was added up, so average them.
+    let bloom = textureSample(t_bloom, s_bloom, in.uv).rgb / params.mip_count;
+
+    var color = scene.rgb;
+    if params.intensity > 0.0 {
+        color += bloom * params.intensity;
+    }
+
+    return vec4<f32>(min(color, vec3<f32>(params.max_color)), scene.a);
+}
@@ -0,0 +1,46 @@
+// Writes UI clip shapes into the stencil buffer. Color writes are masked off.
+
+// Vertex shader //
+
+struct VertexInput {
+    // Already in clip space.
+    @location(0) position: vec2<f32>,
+    // Position relative to the shape center, in pixels.
+    @location(1) local: vec2<f32>,
+    @location(2) half_size: vec2<f32>,
+    @location(3) corner_radius: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) corner_radius: f32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.local = in.local;
+    out.half_size = in.half_size;
+    out.corner_radius = in.corner_radius;
+
+    return out;
+}
+
+// Fragment shader //
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Rounded box SDF.
+    let q = abs(in.local) - in.half_size + vec2<f32>(in.corner_radius);
+    let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - in.corner_radius;
+
+    if (distance > 0.0) {
+        discard;
+    }
+
+    return vec4<f32>(0.0);
+}
@@ -0,0 +1,71 @@
+// Assigns point lights to the clusters of the view frustum they reach.
+
+// Same as in cluster.rs.
+const CLUSTER_COUNT = 3456u; // 16 * 9 * 24
+const MAX_CLUSTER_LIGHTS = 64u;
+const CLUSTER_STRIDE = 65u;
+
+struct ClusteredPointLight {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+    constant: f32,
+    linear0: f32,
+    quadratic: f32,
+    _pad: vec2<f32>,
+}
+
+struct ClusterBounds {
+    min: vec4<f32>,
+    max: vec4<f32>,
+}
+
+struct ClusterParams {
+    view: mat4x4<f32>,
+    light_count: u32,
+    near: f32,
+    far: f32,
+    _pad: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: ClusterParams;
+
+@group(0) @binding(1)
+var<storage, read> lights: array<ClusteredPointLight>;
+
+// View space bounds of each cluster.
+@group(0) @binding(2)
+var<storage, read> bounds: array<ClusterBounds>;
+
+// A light count, then the light indices, for each cluster.
+@group(0) @binding(3)
+var<storage, read_write> cluster_lights: array<u32>;
+
+@compute
+@workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cluster = id.x;
+    if (cluster >= CLUSTER_COUNT) {
+        return;
+    }
+
+    let box = bounds[cluster];
+    let base = cluster * CLUSTER_STRIDE;
+    var count = 0u;
+
+    for (var i = 0u; i < params.light_count && count < MAX_CLUSTER_LIGHTS; i++) {
+        let light = lights[i];
+        let center = (params.view * vec4<f32>(light.position, 1.0)).xyz;
+
+        // Closest point of the box to the sphere.
+        let offset = clamp(center, box.min.xyz, box.max.xyz) - center;
+
+        if (dot(offset, offset) <= light.radius * light.radius) {
+            cluster_lights[base + 1u + count] = i;
+            count++;
+        }
+    }
+
+    cluster_lights[base] = count;
+}
@@ -0,0 +1,132 @@
+// Contact shadows: short rays towards the sun, marched against the depth buffer.
+// The output multiplies the scene color.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct Globals {
+    camera: Camera,
+    inverse_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+    time: f32,
+    delta: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct Params {
+    // Towards the light, normalized.
+    light_direction: vec3<f32>,
+    // Ray length in world units.
+    length: f32,
+    // How dark a fully blocked ray makes the scene.
+    strength: f32,
+    // Depth where nothing was drawn.
+    depth_clear: f32,
+    _pad: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(1)
+var t_scene_depth: texture_2d<f32>;
+
+const STEPS: i32 = 16;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+
+    return out;
+}
+
+fn clamp_pixel(pixel: vec2<i32>) -> vec2<i32> {
+    return clamp(pixel, vec2<i32>(0), vec2<i32>(globals.viewport_size) - 1);
+}
+
+fn world_position(pixel: vec2<i32>, depth: f32) -> vec3<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5) / globals.viewport_size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = globals.inverse_view_proj * ndc;
+
+    return world.xyz / world.w;
+}
+
+// Distance from the camera to what was drawn at a pixel.
+fn scene_distance(pixel: vec2<i32>) -> f32 {
+    let depth = textureLoad(t_scene_depth, clamp_pixel(pixel), 0).r;
+    if (depth == params.depth_clear) {
+        return 1000000.0;
+    }
+
+    return distance(world_position(pixel, depth), globals.camera.view_pos.xyz);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let depth = textureLoad(t_scene_depth, pixel, 0).r;
+    let position = world_position(pixel, depth);
+
+    // Face normal from the neighboring pixels, before any branching.
+    let to_camera = globals.camera.view_pos.xyz - position;
+    var normal = normalize(cross(dpdy(position), dpdx(position)));
+    if (dot(normal, to_camera) < 0.0) {
+        normal = -normal;
+    }
+
+    // Nothing drawn, or facing away from the light and unlit anyway.
+    if (depth == params.depth_clear || dot(normal, params.light_direction) <= 0.0) {
+        return vec4<f32>(1.0);
+    }
+
+    let step_length = params.length / f32(STEPS);
+
+    // Occluders thicker than this are assumed to be something far behind the ray.
+    let thickness = max(params.length * 0.5, 0.05);
+
+    // Start off the surface, so that it doesn't shadow itself.
+    let origin = position + normal * step_length * 0.5;
+
+    var shadow = 0.0;
+
+    for (var i = 1; i <= STEPS; i += 1) {
+        let p = origin + params.light_direction * step_length * f32(i);
+
+        let clip = globals.camera.view_proj * vec4<f32>(p, 1.0);
+        if (clip.w <= 0.0) {
+            break;
+        }
+
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+            break;
+        }
+
+        let behind = distance(p, globals.camera.view_pos.xyz) - scene_distance(vec2<i32>(uv * globals.viewport_size));
+
+        if (behind > 0.0 && behind < thickness) {
+            // Blockers further along the ray cast lighter shadows, so they end softly.
+            shadow = 1.0 - f32(i - 1) / f32(STEPS);
+            break;
+        }
+    }
+
+    return vec4<f32>(vec3<f32>(1.0 - shadow * params.strength), 1.0);
+}
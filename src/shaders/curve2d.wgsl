@@ -0,0 +1,79 @@
+// Vertex shader //
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec3<f32>,
+    @location(3) curve: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) curve: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 0.0, 1.0);
+    out.tex_coords = model.tex_coords;
+    out.color = model.color;
+    out.curve = model.curve;
+
+    return out;
+}
+
+// Fragment shader //
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+// Screen space light map from 2D lights, white when there are none.
+@group(2) @binding(0)
+var t_light_map: texture_2d<f32>;
+
+@group(2) @binding(1)
+var s_light_map: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Implicit form of the quadratic curve, negative on the side of its chord.
+    let u = in.curve.x;
+    let v = in.curve.y;
+    let f = u * u - v;
+
+    // Screen space gradient of f, for a distance to the curve in pixels.
+    // Derivatives have to be taken before any branch.
+    let du = vec2<f32>(dpdx(u), dpdy(u));
+    let dv = vec2<f32>(dpdx(v), dpdy(v));
+    let gradient = 2.0 * u * du - dv;
+
+    let distance = in.curve.z * f / max(length(gradient), 1e-6);
+    let coverage = select(clamp(0.5 - distance, 0.0, 1.0), 1.0, in.curve.z == 0.0);
+
+    let color = vec4<f32>(in.color, 1.0) * textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    let light_uv = in.clip_position.xy / vec2<f32>(textureDimensions(t_light_map));
+    let light = textureSample(t_light_map, s_light_map, light_uv).rgb;
+
+    // Premultiplied, like the blending.
+    return vec4<f32>(color.rgb * light, color.a) * coverage;
+}
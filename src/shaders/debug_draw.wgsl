@@ -0,0 +1,87 @@
+// Depth tested debug lines, and normal/tangent lines expanded from mesh vertices.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct DebugMesh {
+    model: mat4x4<f32>,
+    // Inverse transpose of the model matrix.
+    normal_matrix: mat4x4<f32>,
+    line_length: f32,
+    // Non-zero to also draw tangents and bi-tangents.
+    show_tangents: u32,
+    _pad: vec2<f32>,
+}
+
+@group(1) @binding(0) var<uniform> mesh: DebugMesh;
+
+struct LineOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+// Lines //
+
+struct LineInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main_line(in: LineInput) -> LineOutput {
+    return LineOutput(camera.view_proj * vec4<f32>(in.position, 1.0), in.color);
+}
+
+// Normals //
+
+// Same layout as Vertex3d, stepped per instance.
+struct MeshVertex {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bi_tangent: vec3<f32>,
+}
+
+@vertex
+fn vs_main_normals(@builtin(vertex_index) in_vertex_index: u32, vertex: MeshVertex) -> LineOutput {
+    // Each mesh vertex expands into up to three lines: normal, tangent and bi-tangent.
+    let line = in_vertex_index / 2u;
+    let end = f32(in_vertex_index % 2u);
+
+    var direction: vec3<f32>;
+    var color: vec4<f32>;
+
+    switch line {
+        case 1u: {
+            direction = (mesh.model * vec4<f32>(vertex.tangent, 0.0)).xyz;
+            color = vec4<f32>(0.9, 0.2, 0.2, 1.0);
+        }
+        case 2u: {
+            direction = (mesh.model * vec4<f32>(vertex.bi_tangent, 0.0)).xyz;
+            color = vec4<f32>(0.3, 0.8, 0.2, 1.0);
+        }
+        default: {
+            direction = (mesh.normal_matrix * vec4<f32>(vertex.normal, 0.0)).xyz;
+            color = vec4<f32>(0.2, 0.4, 0.9, 1.0);
+        }
+    }
+
+    let world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+    // Missing tangents are zero, so avoid normalizing them.
+    let offset = select(vec3<f32>(0.0), normalize(direction), dot(direction, direction) > 0.0);
+    let position = world_position.xyz / world_position.w + offset * mesh.line_length * end;
+
+    return LineOutput(camera.view_proj * vec4<f32>(position, 1.0), color);
+}
+
+@fragment
+fn fs_main(in: LineOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
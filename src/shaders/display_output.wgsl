@@ -0,0 +1,87 @@
+// Encodes the final linear image for an HDR display.
+
+struct Params {
+    paper_white: f32,
+    peak_brightness: f32,
+    // 0 for HDR10, 1 for scRGB.
+    encoding: u32,
+    _pad: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(0)
+var t_source: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s_source: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Vertex shader //
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+// Fragment shader //
+
+// Linear up to the knee, then an exponential shoulder that reaches the peak at infinity.
+fn roll_off(nits: vec3<f32>) -> vec3<f32> {
+    let peak = params.peak_brightness;
+    let knee = min(params.paper_white, peak * 0.75);
+    let range = peak - knee;
+
+    let shoulder = knee + range * (1.0 - exp(-(nits - knee) / range));
+    return select(nits, shoulder, nits > vec3<f32>(knee));
+}
+
+// BT.709 primaries to BT.2020, both linear.
+fn rec709_to_rec2020(color: vec3<f32>) -> vec3<f32> {
+    let m = mat3x3<f32>(
+        vec3<f32>(0.6274, 0.0691, 0.0164),
+        vec3<f32>(0.3293, 0.9195, 0.0880),
+        vec3<f32>(0.0433, 0.0114, 0.8956),
+    );
+    return m * color;
+}
+
+// SMPTE ST 2084, from nits to the 0..1 signal.
+fn pq_encode(nits: vec3<f32>) -> vec3<f32> {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+
+    let y = pow(clamp(nits / 10000.0, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3<f32>(m2));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let source = textureSample(t_source, s_source, in.uv);
+    let color = max(source.rgb, vec3<f32>(0.0));
+
+    if params.encoding == 0u {
+        let nits = roll_off(rec709_to_rec2020(color) * params.paper_white);
+        return vec4<f32>(pq_encode(nits), 1.0);
+    }
+
+    // scRGB: 1.0 is 80 nits.
+    let nits = roll_off(color * params.paper_white);
+    return vec4<f32>(nits / 80.0, 1.0);
+}
@@ -0,0 +1,98 @@
+// Fast approximate anti-aliasing.
+// Based on the well-known simplified FXAA by Timothy Lottes.
+
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct Globals {
+    camera: Camera,
+    inverse_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+    time: f32,
+    delta: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+@group(1) @binding(0)
+var t_input: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s_input: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Vertex shader //
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+// Fragment shader //
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The viewport size in globals is the 3D scene, which may be drawn smaller.
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+
+    let color_m = textureSample(t_input, s_input, in.uv);
+
+    let luma_nw = luma(textureSample(t_input, s_input, in.uv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let luma_ne = luma(textureSample(t_input, s_input, in.uv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let luma_sw = luma(textureSample(t_input, s_input, in.uv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let luma_se = luma(textureSample(t_input, s_input, in.uv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let luma_m = luma(color_m.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Blur direction is perpendicular to the local luma gradient.
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (
+        textureSample(t_input, s_input, in.uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        textureSample(t_input, s_input, in.uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        textureSample(t_input, s_input, in.uv + dir * -0.5).rgb +
+        textureSample(t_input, s_input, in.uv + dir * 0.5).rgb);
+
+    // Fall back to the narrower blur if the wide one samples outside the local luma range.
+    let luma_b = luma(rgb_b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return vec4<f32>(rgb_a, color_m.a);
+    }
+
+    return vec4<f32>(rgb_b, color_m.a);
+}
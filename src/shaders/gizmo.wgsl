@@ -9,6 +9,25 @@ struct Camera {
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 
+struct Grid {
+    // Straight alpha colors.
+    minor_color: vec4<f32>,
+    major_color: vec4<f32>,
+    x_axis_color: vec4<f32>,
+    // Also used for the Y axis in 2D.
+    z_axis_color: vec4<f32>,
+    // Maps view (pixel) space to 2D world space.
+    inverse_view_2d: mat4x4<f32>,
+    minor_spacing: f32,
+    major_spacing: f32,
+    // Fraction of the far plane distance where fading starts.
+    fade_start: f32,
+    // 1.0 with a reverse-Z depth buffer.
+    reverse_z: f32,
+}
+
+@group(1) @binding(0) var<uniform> grid: Grid;
+
 fn inverse4x4(m: mat4x4<f32>) -> mat4x4<f32> {
     let a00 = m[0][0]; let a01 = m[0][1]; let a02 = m[0][2]; let a03 = m[0][3];
     let a10 = m[1][0]; let a11 = m[1][1]; let a12 = m[1][2]; let a13 = m[1][3];
@@ -68,43 +87,42 @@ fn vs_main_grid(@builtin(vertex_index) in_vertex_index: u32) -> GridOutput {
     u = u - 1.0;
     v = 1.0 - v;
 
+    // Depth of the near plane is 1 with reverse-Z.
+    let near_z = grid.reverse_z;
+
     return GridOutput(
         vec4<f32>(u, v, 0.0, 1.0),
-        unproject_point(u, v, 0.0),
-        unproject_point(u, v, 1.0),
+        unproject_point(u, v, near_z),
+        unproject_point(u, v, 1.0 - near_z),
     );
 }
 
-fn grid(pos: vec3<f32>, scale: f32, axis: bool) -> vec4<f32> {
-    // Use the scale variable to set the distance between the lines.
-    let coord = pos.xz * scale;
-    let derivative = fwidth(coord);
-    let grid = abs(fract(coord - 0.5) - 0.5) / derivative;
-    let grid_line = min(grid.x, grid.y);
-    let minimumz = min(derivative.y, 1.0);
-    let minimumx = min(derivative.x, 1.0);
-    let alpha = 1.0 - min(grid_line, 1.0);
-    var color = vec4<f32>(0.1) * alpha;
-
-    // If we should draw axes.
-    if (axis) {
-        let extra = 1.0 / scale;
-
-        // Draw z axis.
-        if (pos.x > -extra * minimumx && pos.x < extra * minimumx) {
-            color.x = 0.0;
-            color.y = 0.0;
-            color.z = alpha;
-        }
-
-        // Draw x axis.
-        if (pos.z > -extra * minimumz && pos.z < extra * minimumz) {
-            color.x = alpha;
-            color.y = 0.0;
-            color.z = 0.0;
-        }
-    }
+// Coverage of lines at multiples of `spacing`, about one pixel wide.
+fn grid_lines(coord: vec2<f32>, spacing: f32) -> f32 {
+    let scaled = coord / spacing;
+    let derivative = fwidth(scaled);
+    let grid = abs(fract(scaled - 0.5) - 0.5) / derivative;
+    return 1.0 - min(min(grid.x, grid.y), 1.0);
+}
+
+// Coverage of the line where `value` is zero.
+fn axis_line(value: f32) -> f32 {
+    return 1.0 - min(abs(value) / fwidth(value), 1.0);
+}
 
+// Blend a straight alpha color over a premultiplied one.
+fn over(dst: vec4<f32>, src: vec4<f32>, coverage: f32) -> vec4<f32> {
+    let alpha = src.a * coverage;
+    return vec4<f32>(src.rgb * alpha, alpha) + dst * (1.0 - alpha);
+}
+
+// Premultiplied grid color on a plane. `coord.y` is Z in 3D and Y in 2D.
+fn grid_color(coord: vec2<f32>) -> vec4<f32> {
+    var color = vec4<f32>(0.0);
+    color = over(color, grid.minor_color, grid_lines(coord, grid.minor_spacing));
+    color = over(color, grid.major_color, grid_lines(coord, grid.major_spacing));
+    color = over(color, grid.z_axis_color, axis_line(coord.x));
+    color = over(color, grid.x_axis_color, axis_line(coord.y));
     return color;
 }
 
@@ -120,11 +138,56 @@ fn fs_main_grid(in: GridOutput) -> FragOut {
     let pos = in.near + t * (in.far - in.near);
 
     let clip = camera.view_proj * vec4<f32>(pos.xyz, 1.0);
-    let depth = clip.z / clip.w;
-    let fading = 1.0 - t;
+    let depth = clamp(clip.z / clip.w, 0.0, 1.0);
+
+    // Fade out towards the far plane, which is recovered from the projection matrix.
+    var far = camera.proj[3][2] / (camera.proj[2][2] + 1.0);
+    if (grid.reverse_z > 0.5) {
+        far = camera.proj[3][2] / camera.proj[2][2];
+    }
+    let distance = -(camera.view * vec4<f32>(pos, 1.0)).z;
+    let fading = 1.0 - smoothstep(far * grid.fade_start, far, distance);
+
+    let color = grid_color(pos.xz) * fading * f32(t > 0.0);
+
+    return FragOut(depth, color);
+}
+
+// 2D grid, drawn behind 2D content.
+
+@vertex
+fn vs_main_grid_2d(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    return vec4<f32>(u - 1.0, 1.0 - v, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main_grid_2d(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let world = (grid.inverse_view_2d * vec4<f32>(position.xy, 0.0, 1.0)).xy;
 
-    // Two grids, one with axes drawn.
-    let color = (grid(pos, 1.0, false) + grid(pos, 0.1, true)) * f32(t > 0.0);
+    return grid_color(world);
+}
+
+// Transform gizmo handles, drawn on top of the scene.
+
+struct HandleInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+}
 
-    return FragOut(depth, color * fading);
+struct HandleOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main_handle(in: HandleInput) -> HandleOutput {
+    return HandleOutput(camera.view_proj * vec4<f32>(in.position, 1.0), in.color);
+}
+
+@fragment
+fn fs_main_handle(in: HandleOutput) -> @location(0) vec4<f32> {
+    return in.color;
 }
@@ -0,0 +1,101 @@
+// Text rendered on world-space quads.
+
+//////////////////////////////// Vertex shader ////////////////////////////////
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Params {
+    model_matrix: mat4x4<f32>,
+    color: vec4<f32>,
+    // Offset applied to glyph positions (in pixels) to center the text.
+    offset: vec2<f32>,
+    // Size of a font pixel in world units.
+    pixel_size: f32,
+    // 0: disabled, 1: enabled, 2: fixed Y.
+    billboard_mode: u32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+struct InstanceInput {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) region: vec4<f32>,
+    @location(3) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let u0 = ((in_vertex_index << 1u) & 2u) >> 1u; // [0, 1]
+    let v0 = ((in_vertex_index & 2u)) >> 1u; // [0, 1]
+
+    // Glyph layout is in pixels with Y pointing down.
+    let pixel_position = instance.position + vec2<f32>(f32(u0), f32(v0)) * instance.size + params.offset;
+    let local = vec2<f32>(pixel_position.x, -pixel_position.y) * params.pixel_size;
+
+    let model = params.model_matrix;
+    let center = model[3].xyz;
+    let scale = vec2<f32>(length(model[0].xyz), length(model[1].xyz));
+
+    var world_position: vec3<f32>;
+
+    if (params.billboard_mode == 1u) {
+        // Face the camera, using the camera's right and up vectors.
+        let right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
+        let up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+        world_position = center + right * local.x * scale.x + up * local.y * scale.y;
+    } else if (params.billboard_mode == 2u) {
+        // Rotate around the Y axis only.
+        let to_camera = camera.view_pos.xyz - center;
+        var forward = normalize(vec3<f32>(to_camera.x, 0.0, to_camera.z));
+        if (length(vec2<f32>(to_camera.x, to_camera.z)) < 0.0001) {
+            forward = vec3<f32>(0.0, 0.0, 1.0);
+        }
+        let right = cross(vec3<f32>(0.0, 1.0, 0.0), forward);
+        world_position = center + right * local.x * scale.x + vec3<f32>(0.0, 1.0, 0.0) * local.y * scale.y;
+    } else {
+        world_position = (model * vec4<f32>(local, 0.0, 1.0)).xyz;
+    }
+
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.tex_coords = vec2<f32>(instance.region[u0 * 2u], instance.region[v0 * 2u + 1u]);
+    out.color = instance.color * params.color;
+
+    return out;
+}
+
+//////////////////////////////// Fragment shader ////////////////////////////////
+
+@group(2) @binding(0)
+var t_diffuse: texture_2d<f32>;
+
+@group(2) @binding(1)
+var s_diffuse: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Grayscale atlases only have coverage in the red channel, subpixel ones in RGB.
+    // World-space text doesn't do subpixel blending, so take the strongest channel. Output premultiplied alpha.
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let coverage = max(texel.r, max(texel.g, texel.b));
+    let alpha = in.color.a * coverage;
+
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
@@ -0,0 +1,78 @@
+// 2D light map: shadow volumes are drawn into the stencil buffer,
+// then each light is added where its own shadows are absent.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Shadows //
+
+@vertex
+fn vs_main_shadow(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4<f32>(position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main_shadow() -> @location(0) vec4<f32> {
+    // Color writes are masked off, only the stencil matters.
+    return vec4<f32>(0.0);
+}
+
+// Lights //
+
+const KIND_POINT: u32 = 0u;
+
+struct LightInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) radius: f32,
+    @location(3) kind: u32,
+}
+
+struct LightOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Offset from the light center, in units of the radius.
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) kind: u32,
+}
+
+@vertex
+fn vs_main_light(@builtin(vertex_index) in_vertex_index: u32, light: LightInput) -> LightOutput {
+    var out: LightOutput;
+    out.color = light.color;
+    out.kind = light.kind;
+
+    if light.kind == KIND_POINT {
+        // A quad around the light, drawn as a triangle strip.
+        let corner = vec2<f32>(f32(in_vertex_index & 1u), f32((in_vertex_index >> 1u) & 1u)) * 2.0 - 1.0;
+        out.clip_position = camera.view_proj * vec4<f32>(light.position + corner * light.radius, 0.0, 1.0);
+        out.local = corner;
+    } else {
+        // Directional lights cover the whole screen with a single triangle.
+        let u = f32((in_vertex_index << 1u) & 2u);
+        let v = f32(in_vertex_index & 2u);
+        out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+        out.local = vec2<f32>(0.0);
+    }
+
+    return out;
+}
+
+@fragment
+fn fs_main_light(in: LightOutput) -> @location(0) vec4<f32> {
+    var attenuation = 1.0;
+
+    if in.kind == KIND_POINT {
+        let falloff = clamp(1.0 - length(in.local), 0.0, 1.0);
+        attenuation = falloff * falloff;
+    }
+
+    return vec4<f32>(in.color.rgb * attenuation, 1.0);
+}
@@ -10,6 +10,18 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
+struct Globals {
+    // The main camera, which isn't always the one drawing.
+    camera: Camera,
+    inverse_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+    time: f32,
+    delta: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> globals: Globals;
+
 struct PointLight {
     position: vec3<f32>,
     strength: f32,
@@ -42,12 +54,170 @@ struct Lights {
 @group(1) @binding(0)
 var<uniform> lights: Lights;
 
+const MAX_CASCADES = 4;
+
+struct Shadow {
+    light_view_proj: array<mat4x4<f32>, MAX_CASCADES>,
+    // Camera view depth where each cascade ends.
+    split_depths: vec4<f32>,
+    // World size of a shadow map texel in each cascade.
+    texel_sizes: vec4<f32>,
+    // Zero if the directional light has no shadows.
+    cascade_count: u32,
+    blend_fraction: f32,
+    pcf_radius: u32,
+    normal_bias: f32,
+    // +X, -X, +Y, -Y, +Z, -Z.
+    point_view_proj: array<mat4x4<f32>, 6>,
+    point_light_position: vec3<f32>,
+    // Of the point light with shadows, 0xffffffff if there is none.
+    point_light_index: u32,
+    // World size of a shadow map texel one unit away from the point light.
+    point_texel_size: f32,
+}
+
+@group(1) @binding(1)
+var<uniform> shadow: Shadow;
+
+@group(1) @binding(2)
+var t_shadow: texture_depth_2d_array;
+
+@group(1) @binding(3)
+var s_shadow: sampler_comparison;
+
+// The faces of the point light, in 3 by 2 tiles.
+@group(1) @binding(17)
+var t_point_shadow: texture_depth_2d;
+
+const MAX_PROBES = 4;
+
+struct Probes {
+    // Position in xyz, radius in w.
+    spheres: array<vec4<f32>, MAX_PROBES>,
+    count: u32,
+}
+
+@group(1) @binding(4)
+var<uniform> probes: Probes;
+
+@group(1) @binding(5)
+var t_probe_radiance0: texture_cube<f32>;
+@group(1) @binding(6)
+var t_probe_radiance1: texture_cube<f32>;
+@group(1) @binding(7)
+var t_probe_radiance2: texture_cube<f32>;
+@group(1) @binding(8)
+var t_probe_radiance3: texture_cube<f32>;
+
+@group(1) @binding(9)
+var t_probe_irradiance0: texture_cube<f32>;
+@group(1) @binding(10)
+var t_probe_irradiance1: texture_cube<f32>;
+@group(1) @binding(11)
+var t_probe_irradiance2: texture_cube<f32>;
+@group(1) @binding(12)
+var t_probe_irradiance3: texture_cube<f32>;
+
+@group(1) @binding(13)
+var s_probe: sampler;
+
+#ifdef FORWARD_PLUS
+// Same as in cluster.rs.
+const CLUSTER_GRID = vec3<u32>(16u, 9u, 24u);
+const MAX_CLUSTER_LIGHTS = 64u;
+const CLUSTER_STRIDE = 65u;
+
+struct ClusteredPointLight {
+    position: vec3<f32>,
+    // Where the light is cut off.
+    radius: f32,
+    color: vec3<f32>,
+    constant: f32,
+    linear0: f32,
+    quadratic: f32,
+    _pad: vec2<f32>,
+}
+
+struct ClusterParams {
+    // Of the camera the clusters were built for.
+    view: mat4x4<f32>,
+    light_count: u32,
+    // View depth of the first and last slice.
+    near: f32,
+    far: f32,
+    _pad: f32,
+}
+
+@group(1) @binding(14)
+var<uniform> cluster_params: ClusterParams;
+
+@group(1) @binding(15)
+var<storage, read> clustered_lights: array<ClusteredPointLight>;
+
+// A light count, then the light indices, for each cluster.
+@group(1) @binding(16)
+var<storage, read> cluster_lights: array<u32>;
+
+// Index of the cluster a fragment is in.
+fn find_cluster(frag_coord: vec2<f32>, world_position: vec3<f32>) -> u32 {
+    let depth = -(cluster_params.view * vec4<f32>(world_position, 1.0)).z;
+
+    let tile = vec2<u32>(clamp(
+        frag_coord / globals.viewport_size * vec2<f32>(CLUSTER_GRID.xy),
+        vec2<f32>(0.0),
+        vec2<f32>(CLUSTER_GRID.xy - 1u),
+    ));
+
+    // Slices are spaced exponentially, see slice_depth in cluster.rs.
+    let slice_f = log(max(depth, cluster_params.near) / cluster_params.near)
+        / log(cluster_params.far / cluster_params.near) * f32(CLUSTER_GRID.z);
+    let slice = u32(clamp(slice_f, 0.0, f32(CLUSTER_GRID.z - 1u)));
+
+    return (slice * CLUSTER_GRID.y + tile.y) * CLUSTER_GRID.x + tile.x;
+}
+
+// Blinn-Phong of the lights reaching the fragment's cluster, in world space.
+fn clustered_point_lights(frag_coord: vec2<f32>, world_position: vec3<f32>, world_normal: vec3<f32>, view_dir: vec3<f32>) -> vec3<f32> {
+    let base = find_cluster(frag_coord, world_position) * CLUSTER_STRIDE;
+    let count = min(cluster_lights[base], MAX_CLUSTER_LIGHTS);
+
+    var result = vec3<f32>(0.0);
+
+    for (var i = 0u; i < count; i++) {
+        let light = clustered_lights[cluster_lights[base + 1u + i]];
+
+        let to_light = light.position - world_position;
+        let distance = length(to_light);
+        let light_dir = to_light / distance;
+        let half_dir = normalize(view_dir + light_dir);
+
+        let diffuse_strength = max(dot(world_normal, light_dir), 0.0);
+        let specular_strength = pow(max(dot(world_normal, half_dir), 0.0), 4.0);
+
+        let attenuation = 1.0 / (light.constant + light.linear0 * distance + light.quadratic * (distance * distance));
+
+        // Fade to zero at the cutoff, so that cluster edges don't show.
+        let falloff = saturate(1.0 - pow(distance / light.radius, 4.0));
+
+        let shadow_factor = point_shadow(cluster_lights[base + 1u + i], world_position, world_normal);
+
+        result += light.color * (diffuse_strength + specular_strength) * attenuation * falloff * falloff * shadow_factor;
+    }
+
+    return result;
+}
+#endif
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
     @location(4) bitangent: vec3<f32>,
+#ifdef VERTEX_COLOR
+    // After the instance locations.
+    @location(12) color: vec4<f32>,
+#endif
 }
 
 struct InstanceInput {
@@ -73,6 +243,12 @@ struct VertexOutput {
     @location(3) tbn_matrix0: vec3<f32>,
     @location(4) tbn_matrix1: vec3<f32>,
     @location(5) tbn_matrix2: vec3<f32>,
+    // For shadows.
+    @location(6) world_position: vec3<f32>,
+    @location(7) world_normal: vec3<f32>,
+#ifdef VERTEX_COLOR
+    @location(8) color: vec4<f32>,
+#endif
 }
 
 @vertex
@@ -99,11 +275,19 @@ fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
         world_normal));
 
     // Vertex's world position.
-    let vertex_world_position = model_matrix * vec4<f32>(vertex.position, 1.0);
+    var vertex_world_position = model_matrix * vec4<f32>(vertex.position, 1.0);
+
+#ifdef WIND
+    let wind = wind_offset(vertex_world_position.xyz, vertex.position.y);
+    vertex_world_position = vec4<f32>(vertex_world_position.xyz + wind, 1.0);
+#endif
 
     var out: VertexOutput;
     out.clip_position = camera.view_proj * vertex_world_position;
     out.tex_coords = vertex.tex_coords;
+#ifdef VERTEX_COLOR
+    out.color = vertex.color;
+#endif
 
     /*
     So instead of sending the inverse of the TBN matrix to the fragment shader,
@@ -118,6 +302,8 @@ fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
     out.tbn_matrix0 = tbn_matrix[0];
     out.tbn_matrix1 = tbn_matrix[1];
     out.tbn_matrix2 = tbn_matrix[2];
+    out.world_position = vertex_world_position.xyz;
+    out.world_normal = world_normal;
 
     return out;
 }
@@ -143,34 +329,333 @@ var t_normal: texture_2d<f32>;
 @group(2) @binding(3)
 var s_normal: sampler;
 #endif
+
+#ifdef MATERIAL_UNIFORM
+struct Material {
+    // Linear color times strength, may go above 1.0.
+    emissive: vec4<f32>,
+    uv_offset: vec2<f32>,
+    uv_scale: vec2<f32>,
+    uv_rotation: f32,
+    alpha_cutoff: f32,
+    wind_strength: f32,
+    wind_frequency: f32,
+    wind_direction: vec3<f32>,
+    wind_noise_scale: f32,
+}
+
+@group(2) @binding(4)
+var<uniform> material: Material;
+#endif
+
+#ifdef EMISSIVE_MAP
+@group(2) @binding(5)
+var t_emissive: texture_2d<f32>;
+
+@group(2) @binding(6)
+var s_emissive: sampler;
+#endif
+
+#ifdef WIND_NOISE_MAP
+@group(2) @binding(7)
+var t_wind_noise: texture_2d<f32>;
+
+@group(2) @binding(8)
+var s_wind_noise: sampler;
+#endif
 // -------------------------
 
+#ifdef WIND
+const TAU = 6.28318530718;
+
+// Sway of a vertex at a world position, growing with its height above the mesh origin.
+fn wind_offset(world_position: vec3<f32>, height: f32) -> vec3<f32> {
+    // Vary the phase along the wind, so the sway travels over the scene like a wave.
+    var phase = dot(world_position.xz, material.wind_direction.xz);
+    var amplitude = 1.0;
+
+#ifdef WIND_NOISE_MAP
+    let noise_uv = world_position.xz * material.wind_noise_scale;
+    let noise = textureSampleLevel(t_wind_noise, s_wind_noise, noise_uv, 0.0).r;
+    phase += noise * TAU;
+    amplitude = 0.5 + noise;
+#endif
+
+    let sway = sin(globals.time * material.wind_frequency * TAU + phase);
+
+    return material.wind_direction * sway * amplitude * material.wind_strength * max(height, 0.0);
+}
+#endif
+
+// Returns 1 if lit, 0 if in shadow. Positions outside the cascade are lit.
+fn sample_cascade(cascade: u32, world_position: vec3<f32>, world_normal: vec3<f32>) -> f32 {
+    // Offset along the normal to avoid shadow acne.
+    let offset = world_normal * shadow.normal_bias * shadow.texel_sizes[cascade];
+    let clip = shadow.light_view_proj[cascade] * vec4<f32>(world_position + offset, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    let radius = i32(shadow.pcf_radius);
+
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let sample_uv = uv + vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, sample_uv, cascade, ndc.z);
+        }
+    }
+
+    let width = f32(radius * 2 + 1);
+    return lit / (width * width);
+}
+
+fn directional_shadow(world_position: vec3<f32>, world_normal: vec3<f32>) -> f32 {
+    if (shadow.cascade_count == 0u) {
+        return 1.0;
+    }
+
+    let depth = -(camera.view * vec4<f32>(world_position, 1.0)).z;
+
+    var cascade = 0u;
+    while (cascade < shadow.cascade_count && depth > shadow.split_depths[cascade]) {
+        cascade++;
+    }
+
+    if (cascade == shadow.cascade_count) {
+        return 1.0;
+    }
+
+    let lit = sample_cascade(cascade, world_position, world_normal);
+
+    // Fade into the next cascade, or out of shadow after the last one, so the seams don't show.
+    var start = 0.0;
+    if (cascade > 0u) {
+        start = shadow.split_depths[cascade - 1u];
+    }
+    let end = shadow.split_depths[cascade];
+    let band = (end - start) * shadow.blend_fraction;
+
+    if (band <= 0.0 || depth < end - band) {
+        return lit;
+    }
+
+    let blend = smoothstep(end - band, end, depth);
+
+    var next = 1.0;
+    if (cascade + 1u < shadow.cascade_count) {
+        next = sample_cascade(cascade + 1u, world_position, world_normal);
+    }
+
+    return mix(lit, next, blend);
+}
+
+fn point_shadow(light_index: u32, world_position: vec3<f32>, world_normal: vec3<f32>) -> f32 {
+    if (light_index != shadow.point_light_index) {
+        return 1.0;
+    }
+
+    let from_light = world_position - shadow.point_light_position;
+    let distance = length(from_light);
+
+    // Texels get bigger away from the light.
+    let offset = world_normal * shadow.normal_bias * shadow.point_texel_size * distance;
+    let position = world_position + offset;
+
+    // The face the fragment is on, along the major axis.
+    let axis = abs(from_light);
+    var face = 0u;
+    if (axis.x >= axis.y && axis.x >= axis.z) {
+        face = select(1u, 0u, from_light.x > 0.0);
+    } else if (axis.y >= axis.z) {
+        face = select(3u, 2u, from_light.y > 0.0);
+    } else {
+        face = select(5u, 4u, from_light.z > 0.0);
+    }
+
+    let clip = shadow.point_view_proj[face] * vec4<f32>(position, 1.0);
+    let ndc = clip.xyz / clip.w;
+
+    if (ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let tiles = vec2<f32>(3.0, 2.0);
+    let tile = vec2<f32>(f32(face % 3u), f32(face / 3u));
+    let texel = tiles / vec2<f32>(textureDimensions(t_point_shadow));
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let radius = i32(shadow.pcf_radius);
+
+    var lit = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            // Stay inside the face, so filtering doesn't reach into the next one.
+            let sample_uv = clamp(uv + vec2<f32>(f32(x), f32(y)) * texel, texel * 0.5, 1.0 - texel * 0.5);
+            lit += textureSampleCompareLevel(t_point_shadow, s_shadow, (tile + sample_uv) / tiles, ndc.z);
+        }
+    }
+
+    let width = f32(radius * 2 + 1);
+    return lit / (width * width);
+}
+
+fn sample_probe_radiance(i: u32, direction: vec3<f32>) -> vec3<f32> {
+    switch i {
+        case 0u: { return textureSampleLevel(t_probe_radiance0, s_probe, direction, 0.0).rgb; }
+        case 1u: { return textureSampleLevel(t_probe_radiance1, s_probe, direction, 0.0).rgb; }
+        case 2u: { return textureSampleLevel(t_probe_radiance2, s_probe, direction, 0.0).rgb; }
+        default: { return textureSampleLevel(t_probe_radiance3, s_probe, direction, 0.0).rgb; }
+    }
+}
+
+fn sample_probe_irradiance(i: u32, direction: vec3<f32>) -> vec3<f32> {
+    switch i {
+        case 0u: { return textureSampleLevel(t_probe_irradiance0, s_probe, direction, 0.0).rgb; }
+        case 1u: { return textureSampleLevel(t_probe_irradiance1, s_probe, direction, 0.0).rgb; }
+        case 2u: { return textureSampleLevel(t_probe_irradiance2, s_probe, direction, 0.0).rgb; }
+        default: { return textureSampleLevel(t_probe_irradiance3, s_probe, direction, 0.0).rgb; }
+    }
+}
+
+struct ProbeLighting {
+    irradiance: vec3<f32>,
+    radiance: vec3<f32>,
+    // How much the probes replace the ambient light, in [0, 1].
+    coverage: f32,
+}
+
+// Blend the probes around a point, weighted by how close it is to each.
+fn probe_lighting(world_position: vec3<f32>, normal: vec3<f32>, reflection: vec3<f32>) -> ProbeLighting {
+    var out: ProbeLighting;
+    out.irradiance = vec3<f32>(0.0);
+    out.radiance = vec3<f32>(0.0);
+    out.coverage = 0.0;
+
+    var total_weight = 0.0;
+
+    for (var i = 0u; i < probes.count; i++) {
+        let sphere = probes.spheres[i];
+        let distance = length(world_position - sphere.xyz);
+
+        if (distance >= sphere.w) {
+            continue;
+        }
+
+        let weight = 1.0 - distance / sphere.w;
+        out.irradiance += sample_probe_irradiance(i, normal) * weight;
+        out.radiance += sample_probe_radiance(i, reflection) * weight;
+        total_weight += weight;
+
+        // Fade out over the outer quarter of the radius.
+        out.coverage = max(out.coverage, saturate(weight * 4.0));
+    }
+
+    if (total_weight > 0.0) {
+        out.irradiance /= total_weight;
+        out.radiance /= total_weight;
+    }
+
+    return out;
+}
+
+#ifdef OIT
+// Weighted blended order-independent transparency (McGuire and Bavoil, 2013).
+struct OitOutput {
+    // Weighted sum of the premultiplied colors.
+    @location(0) accum: vec4<f32>,
+    // Multiplied by one minus alpha, so it ends up as how much of the background shows.
+    @location(1) revealage: f32,
+}
+
+fn oit_output(color: vec4<f32>, view_distance: f32) -> OitOutput {
+    // Nearer surfaces weigh more, so they win where colors overlap.
+    let weight = color.a * clamp(10.0 / (1e-5 + pow(view_distance / 5.0, 2.0) + pow(view_distance / 200.0, 6.0)), 1e-2, 3e3);
+
+    var out: OitOutput;
+    out.accum = color * weight;
+    out.revealage = color.a;
+    return out;
+}
+#endif
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef OIT
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> OitOutput {
+#else
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+#endif
+#ifdef UV_TRANSFORM
+    // Scale, rotate, then offset.
+    let uv_rotation = mat2x2<f32>(
+        vec2<f32>(cos(material.uv_rotation), sin(material.uv_rotation)),
+        vec2<f32>(-sin(material.uv_rotation), cos(material.uv_rotation)),
+    );
+    let uv = uv_rotation * (in.tex_coords * material.uv_scale) + material.uv_offset;
+#else
+    let uv = in.tex_coords;
+#endif
+
     // Sample diffuse texture.
 #ifdef COLOR_MAP
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    var object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, uv);
 #else
-    let object_color: vec4<f32> = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    var object_color: vec4<f32> = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+#endif
+
+#ifdef VERTEX_COLOR
+    object_color *= in.color;
 #endif
 
 #ifdef NORMAP_MAP
     // The normal map is defined in TBN space.
-    let tbn_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    var tbn_normal = textureSample(t_normal, s_normal, uv).xyz * 2.0 - 1.0;
+#ifdef UV_TRANSFORM
+    // The tangents follow the mesh UVs, so undo the rotation.
+    tbn_normal = vec3<f32>(transpose(uv_rotation) * tbn_normal.xy, tbn_normal.z);
+#endif
 #else
     // Use the unit normal in TBN space.
-    let tbn_normal = vec3<f32>(0.0, 0.0, 1.0);
+    var tbn_normal = vec3<f32>(0.0, 0.0, 1.0);
 #endif
 
-    let ambient_color = lights.ambient_color * lights.ambient_strength;
+    var geometry_normal = normalize(in.world_normal);
+
+#ifdef DOUBLE_SIDED
+    // Back faces are shaded as seen from their own side.
+    if !front_facing {
+        tbn_normal = -tbn_normal;
+        geometry_normal = -geometry_normal;
+    }
+#endif
 
     let tbn_matrix = mat3x3<f32>(
         in.tbn_matrix0,
         in.tbn_matrix1,
         in.tbn_matrix2);
 
+    // The TBN matrix is orthonormal, so its transpose goes back to world space.
+    let world_normal = normalize(transpose(tbn_matrix) * tbn_normal);
+    let world_view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let world_reflection = reflect(-world_view_dir, world_normal);
+
+    let probe = probe_lighting(in.world_position, world_normal, world_reflection);
+
+    // Baked probes replace the flat ambient light.
+    let ambient_color = mix(lights.ambient_color * lights.ambient_strength, probe.irradiance, probe.coverage);
+
+    // Schlick's approximation, for a dielectric.
+    let fresnel = 0.04 + 0.96 * pow(1.0 - max(dot(world_normal, world_view_dir), 0.0), 5.0);
+    let reflection_color = probe.radiance * fresnel * probe.coverage;
+
     var point_lights_result = vec3<f32>(0.0, 0.0, 0.0);
 
+#ifdef FORWARD_PLUS
+    point_lights_result = clustered_point_lights(in.clip_position.xy, in.world_position, world_normal, world_view_dir);
+#else
     for (var i: u32 = 0; i < lights.point_light_count; i++) {
         // We have to calculate the TBN light position in the fragment shader, since we cannot pass an array of that from vertex to fragment.
         let tbn_light_position = tbn_matrix * lights.point_lights[i].position;
@@ -196,8 +681,11 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         let attenuation = 1.0 / (lights.point_lights[0].constant + lights.point_lights[0].linear0 * distance +
                     lights.point_lights[0].quadratic * (distance * distance));
 
-        point_lights_result = point_lights_result + (diffuse_color + specular_color) * attenuation;
+        let shadow_factor = point_shadow(i, in.world_position, geometry_normal);
+
+        point_lights_result = point_lights_result + (diffuse_color + specular_color) * attenuation * shadow_factor;
     }
+#endif
 
     var directional_light_result = vec3<f32>(0.0, 0.0, 0.0);
     {
@@ -216,10 +704,34 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         let specular_strength = pow(max(dot(tbn_normal, half_dir), 0.0), 4.0);
         let specular_color = light_color * specular_strength;
 
-        directional_light_result = (diffuse_color + specular_color) * lights.directional_light.strength;
+        let shadow_factor = directional_shadow(in.world_position, geometry_normal);
+
+        directional_light_result = (diffuse_color + specular_color) * lights.directional_light.strength * shadow_factor;
     }
 
-    let result = (ambient_color + point_lights_result + directional_light_result) * object_color.xyz;
+    var result = (ambient_color + point_lights_result + directional_light_result) * object_color.xyz + reflection_color;
 
+#ifdef EMISSIVE
+    var emissive_color = material.emissive.rgb;
+#ifdef EMISSIVE_MAP
+    emissive_color = emissive_color * textureSample(t_emissive, s_emissive, uv).rgb;
+#endif
+    result = result + emissive_color;
+#endif
+
+#ifdef ALPHA_CUT
+    if object_color.a < material.alpha_cutoff {
+        discard;
+    }
+    return vec4<f32>(result, 1.0);
+#else ifdef ALPHA_BLEND
+    // Blending expects premultiplied alpha.
+#ifdef OIT
+    return oit_output(vec4<f32>(result * object_color.a, object_color.a), length(camera.view_pos.xyz - in.world_position));
+#else
+    return vec4<f32>(result * object_color.a, object_color.a);
+#endif
+#else
     return vec4<f32>(result, object_color.a);
+#endif
 }
@@ -0,0 +1,65 @@
+// Morph targets: add the weighted deltas to the base vertices and write them to the vertex buffer.
+
+struct Params {
+    weights: array<vec4<f32>, 16>,
+    vertex_count: u32,
+    target_count: u32,
+    // In floats.
+    stride: u32,
+    position_offset: u32,
+    normal_offset: u32,
+    has_normals: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(1)
+var<storage, read> base_vertices: array<f32>;
+
+// Position and normal delta of each vertex, target after target.
+@group(0) @binding(2)
+var<storage, read> deltas: array<vec4<f32>>;
+
+@group(0) @binding(3)
+var<storage, read_write> vertices: array<f32>;
+
+fn read_vec3(offset: u32) -> vec3<f32> {
+    return vec3<f32>(base_vertices[offset], base_vertices[offset + 1u], base_vertices[offset + 2u]);
+}
+
+fn write_vec3(offset: u32, value: vec3<f32>) {
+    vertices[offset] = value.x;
+    vertices[offset + 1u] = value.y;
+    vertices[offset + 2u] = value.z;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.vertex_count {
+        return;
+    }
+
+    let vertex = index * params.stride;
+
+    var position = read_vec3(vertex + params.position_offset);
+    var normal = read_vec3(vertex + params.normal_offset);
+
+    for (var i = 0u; i < params.target_count; i++) {
+        let weight = params.weights[i / 4u][i % 4u];
+        if weight == 0.0 {
+            continue;
+        }
+
+        let delta = (i * params.vertex_count + index) * 2u;
+        position += deltas[delta].xyz * weight;
+        normal += deltas[delta + 1u].xyz * weight;
+    }
+
+    write_vec3(vertex + params.position_offset, position);
+
+    if params.has_normals != 0u {
+        write_vec3(vertex + params.normal_offset, normalize(normal));
+    }
+}
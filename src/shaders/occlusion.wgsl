@@ -0,0 +1,35 @@
+// World space bounding boxes, drawn only to count the samples passing the depth test.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexInput {
+    // Corner of the [0, 1] cube.
+    @location(0) position: vec3<f32>,
+}
+
+struct BoxInput {
+    @location(1) matrix_0: vec4<f32>,
+    @location(2) matrix_1: vec4<f32>,
+    @location(3) matrix_2: vec4<f32>,
+    @location(4) matrix_3: vec4<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput, box: BoxInput) -> @builtin(position) vec4<f32> {
+    let box_matrix = mat4x4<f32>(box.matrix_0, box.matrix_1, box.matrix_2, box.matrix_3);
+
+    return camera.view_proj * box_matrix * vec4<f32>(vertex.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    // Discarded by the write mask.
+    return vec4<f32>(0.0);
+}
@@ -0,0 +1,42 @@
+// Resolves weighted blended transparency over the scene.
+
+@group(0) @binding(0)
+var t_accum: texture_2d<f32>;
+
+@group(0) @binding(1)
+var t_revealage: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+
+    let revealage = textureLoad(t_revealage, pixel, 0).r;
+
+    // Nothing transparent was drawn here.
+    if revealage >= 1.0 {
+        discard;
+    }
+
+    let accum = textureLoad(t_accum, pixel, 0);
+
+    // Weighted average of the colors, blended over the scene by the total coverage.
+    let color = accum.rgb / clamp(accum.a, 1e-4, 5e4);
+
+    return vec4<f32>(color, 1.0 - revealage);
+}
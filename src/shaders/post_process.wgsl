@@ -0,0 +1,100 @@
+// Color grading, vignette and chromatic aberration.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct Globals {
+    camera: Camera,
+    inverse_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+    time: f32,
+    delta: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct Params {
+    vignette_intensity: f32,
+    vignette_smoothness: f32,
+    chromatic_aberration: f32,
+    lut_size: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(2) @binding(0)
+var t_scene: texture_2d<f32>;
+
+@group(2) @binding(1)
+var s_scene: sampler;
+
+@group(2) @binding(2)
+var t_lut: texture_3d<f32>;
+
+@group(2) @binding(3)
+var s_lut: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Vertex shader //
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+// Fragment shader //
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let to_center = in.uv - vec2<f32>(0.5);
+
+    // Chromatic aberration: split red and blue channels towards the screen edges.
+    let offset = to_center * params.chromatic_aberration;
+    let center_sample = textureSample(t_scene, s_scene, in.uv);
+    let r = textureSample(t_scene, s_scene, in.uv + offset).r;
+    let b = textureSample(t_scene, s_scene, in.uv - offset).b;
+    var color = vec3<f32>(r, center_sample.g, b);
+
+    // Color grading. LUTs are authored in sRGB space.
+    let lut_scale = (params.lut_size - 1.0) / params.lut_size;
+    let lut_offset = 0.5 / params.lut_size;
+    let lut_coords = clamp(linear_to_srgb(color), vec3<f32>(0.0), vec3<f32>(1.0)) * lut_scale + lut_offset;
+    color = srgb_to_linear(textureSample(t_lut, s_lut, lut_coords).rgb);
+
+    // Vignette.
+    let distance = length(to_center) * 1.41421356;
+    let vignette = 1.0 - smoothstep(1.0 - params.vignette_smoothness, 1.0, distance) * params.vignette_intensity;
+    color = color * vignette;
+
+    return vec4<f32>(color, center_sample.a);
+}
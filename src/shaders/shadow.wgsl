@@ -1,21 +1,14 @@
 // Vertex shader //
 
-struct Light {
-    view_pos: vec4<f32>,
-    view: mat4x4<f32>,
-    proj: mat4x4<f32>,
+struct Cascade {
     view_proj: mat4x4<f32>,
 }
 
 @group(0) @binding(0)
-var<uniform> light: Light;
+var<uniform> cascade: Cascade;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) normal: vec3<f32>,
-    @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>,
 }
 
 struct InstanceInput {
@@ -24,37 +17,19 @@ struct InstanceInput {
     @location(6) model_matrix_1: vec4<f32>,
     @location(7) model_matrix_2: vec4<f32>,
     @location(8) model_matrix_3: vec4<f32>,
-
-    // Normal matrix.
-    @location(9) normal_matrix_0: vec3<f32>,
-    @location(10) normal_matrix_1: vec3<f32>,
-    @location(11) normal_matrix_2: vec3<f32>,
-}
-
-struct VertexOutput {
-    // Analogous to GLSL's gl_Position.
-    @builtin(position) clip_position: vec4<f32>,
 }
 
 @vertex
-fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
     let model_matrix = mat4x4<f32>(
         instance.model_matrix_0,
         instance.model_matrix_1,
         instance.model_matrix_2,
         instance.model_matrix_3);
 
-    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    let world_position = model_matrix * vec4<f32>(vertex.position, 1.0);
 
-    var out: VertexOutput;
-    out.clip_position = camera.view_proj * world_position;
-
-    return out;
+    return cascade.view_proj * world_position;
 }
 
-// Fragment shader //
-
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Do nothing.
-}
+// No fragment shader, only depth is written.
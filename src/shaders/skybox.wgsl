@@ -31,7 +31,12 @@ fn vs_main(model: VertexInput) -> VertexOutput {
     modified_view[3][2] = 0.0;
 
     let pos = camera.proj * modified_view * vec4<f32>(model.position, 1.0);
+    // Put the sky on the far plane.
+#ifdef REVERSE_Z
+    out.clip_position = vec4<f32>(pos.xy, 0.0, pos.w);
+#else
     out.clip_position = pos.xyww;
+#endif
 
     out.tex_coords = model.position;
 
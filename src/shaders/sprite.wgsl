@@ -43,7 +43,19 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 
+// Screen space light map from 2D lights, white when there are none.
+@group(2) @binding(0)
+var t_light_map: texture_2d<f32>;
+
+@group(2) @binding(1)
+var s_light_map: sampler;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0) * textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let color = vec4<f32>(in.color, 1.0) * textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    let light_uv = in.clip_position.xy / vec2<f32>(textureDimensions(t_light_map));
+    let light = textureSample(t_light_map, s_light_map, light_uv).rgb;
+
+    return vec4<f32>(color.rgb * light, color.a);
 }
@@ -4,6 +4,7 @@ struct Camera {
     view_pos: vec4<f32>,
     view: mat4x4<f32>,
     proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
 }
 
 @group(0) @binding(0)
@@ -11,22 +12,22 @@ var<uniform> camera: Camera;
 
 struct Params {
     model_matrix: mat4x4<f32>,
-    billboard_mode: f32,
-    pad0: f32,
-    pad1: f32,
-    pad2: f32,
+    // Quad size in world units.
+    size: vec2<f32>,
+    // 0: disabled, 1: enabled, 2: fixed Y.
+    billboard_mode: u32,
+    // Fragments with a lower alpha are discarded.
+    alpha_cut: f32,
+    // Distance over which the sprite fades out in front of geometry.
+    depth_fade: f32,
 }
 
+#ifdef PUSH_CONSTANTS
+var<push_constant> params: Params;
+#else
 @group(2) @binding(0)
 var<uniform> params: Params;
-
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) normal: vec3<f32>,
-    @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>,
-}
+#endif
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
@@ -34,32 +35,41 @@ struct VertexOutput {
 }
 
 @vertex
-fn vs_main(
-    model: VertexInput
-) -> VertexOutput {
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
     var out: VertexOutput;
 
-    var model_view = camera.view * params.model_matrix;
-
-    let billboard_mode = u32(params.billboard_mode);
-
-    if (billboard_mode == 1u) {
-        // Spherical billboarding.
-        model_view[0][0] = 1.0;
-        model_view[0][1] = 0.0;
-        model_view[0][2] = 0.0;
-
-        model_view[1][0] = 0.0;
-        model_view[1][1] = 1.0;
-        model_view[1][2] = 0.0;
-
-        model_view[2][0] = 0.0;
-        model_view[2][1] = 0.0;
-        model_view[2][2] = 1.0;
+    let u = f32((in_vertex_index << 1u) & 2u) * 0.5; // [0, 1]
+    let v = f32(in_vertex_index & 2u) * 0.5; // [0, 1]
+
+    // Centered quad in the XY plane.
+    let local = vec2<f32>(u - 0.5, 0.5 - v) * params.size;
+
+    let model = params.model_matrix;
+    let center = model[3].xyz;
+    let scale = vec2<f32>(length(model[0].xyz), length(model[1].xyz));
+
+    var world_position: vec3<f32>;
+
+    if (params.billboard_mode == 1u) {
+        // Face the camera, using the camera's right and up vectors.
+        let right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
+        let up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+        world_position = center + right * local.x * scale.x + up * local.y * scale.y;
+    } else if (params.billboard_mode == 2u) {
+        // Rotate around the Y axis only.
+        let to_camera = camera.view_pos.xyz - center;
+        var forward = normalize(vec3<f32>(to_camera.x, 0.0, to_camera.z));
+        if (length(vec2<f32>(to_camera.x, to_camera.z)) < 0.0001) {
+            forward = vec3<f32>(0.0, 0.0, 1.0);
+        }
+        let right = cross(vec3<f32>(0.0, 1.0, 0.0), forward);
+        world_position = center + right * local.x * scale.x + vec3<f32>(0.0, 1.0, 0.0) * local.y * scale.y;
+    } else {
+        world_position = (model * vec4<f32>(local, 0.0, 1.0)).xyz;
     }
 
-    out.clip_position = camera.proj * model_view * vec4<f32>(model.position, 1.0);
-    out.tex_coords = model.tex_coords;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.tex_coords = vec2<f32>(u, v);
 
     return out;
 }
@@ -72,7 +82,38 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 
+#ifdef DEPTH_FADE
+#ifdef PUSH_CONSTANTS
+@group(2) @binding(0)
+var t_scene_depth: texture_2d<f32>;
+#else
+@group(3) @binding(0)
+var t_scene_depth: texture_2d<f32>;
+#endif
+
+// Distance from the camera plane, for a perspective projection.
+// Works with reverse-Z too, since it's folded into the projection matrix.
+fn linear_depth(depth: f32) -> f32 {
+    return camera.proj[3][2] / (depth + camera.proj[2][2]);
+}
+#endif
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    var color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    if (color.a < params.alpha_cut) {
+        discard;
+    }
+
+#ifdef DEPTH_FADE
+    let scene_depth = textureLoad(t_scene_depth, vec2<i32>(in.clip_position.xy), 0).r;
+    let gap = linear_depth(scene_depth) - linear_depth(in.clip_position.z);
+
+    // Also hides the parts behind geometry, since there's no depth test.
+    // Colors are premultiplied, so fade all channels.
+    color *= clamp(gap / params.depth_fade, 0.0, 1.0);
+#endif
+
+    return color;
 }
@@ -0,0 +1,42 @@
+// Camera facing ribbons for trails.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tangent: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    // Signed half width.
+    @location(3) offset: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    // Expand sideways, perpendicular to both the trail and the view direction.
+    let side = cross(in.tangent, camera.view_pos.xyz - in.position);
+    let side_length = length(side);
+    let right = select(vec3<f32>(0.0), side / side_length, side_length > 1e-6);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position + right * in.offset, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
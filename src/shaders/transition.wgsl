@@ -0,0 +1,86 @@
+// Fade, wipe and iris screen transitions, blended over the final image.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct Globals {
+    camera: Camera,
+    inverse_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+    time: f32,
+    delta: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+const KIND_FADE: u32 = 0u;
+const KIND_WIPE: u32 = 1u;
+
+struct Params {
+    color: vec4<f32>,
+    // Wipe direction in UV space, normalized.
+    direction: vec2<f32>,
+    // Iris center in UV space.
+    center: vec2<f32>,
+    kind: u32,
+    // 0 shows the scene, 1 covers it.
+    coverage: f32,
+    softness: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let softness = params.softness;
+    var alpha: f32;
+
+    if params.kind == KIND_FADE {
+        alpha = params.coverage;
+    } else if params.kind == KIND_WIPE {
+        // Distance along the wipe, measured from the screen center.
+        let t = dot(in.uv - vec2<f32>(0.5), params.direction);
+        let extent = 0.5 * (abs(params.direction.x) + abs(params.direction.y));
+
+        // The edge travels from the first corner to past the last one.
+        let edge = mix(-extent, extent + softness, params.coverage);
+        alpha = clamp((edge - t) / softness, 0.0, 1.0);
+    } else {
+        let aspect = globals.viewport_size.x / max(globals.viewport_size.y, 1.0);
+        let scale = vec2<f32>(aspect, 1.0);
+        let distance = length((in.uv - params.center) * scale);
+
+        // Distance to the farthest screen corner.
+        let to_corner = max(abs(params.center), abs(vec2<f32>(1.0) - params.center)) * scale;
+        let max_radius = length(to_corner);
+
+        let radius = mix(max_radius, -softness, params.coverage);
+        alpha = clamp((distance - radius) / softness, 0.0, 1.0);
+    }
+
+    return vec4<f32>(params.color.rgb, params.color.a * alpha);
+}
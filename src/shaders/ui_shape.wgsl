@@ -0,0 +1,133 @@
+// Anti-aliased UI primitives drawn with signed distance fields.
+
+// Blurred copy of the 3D scene, see backdrop.rs.
+@group(0) @binding(0)
+var t_backdrop: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_backdrop: sampler;
+
+// Vertex shader //
+
+struct InstanceInput {
+    // Top-left corner in pixels. The shape rotates around it.
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) view_size: vec2<f32>,
+    @location(3) rotation: f32,
+    @location(4) border_width: f32,
+    // Top-left, top-right, bottom-right, bottom-left.
+    @location(5) corner_radii: vec4<f32>,
+    @location(6) color: vec4<f32>,
+    @location(7) border_color: vec4<f32>,
+    // 0: rounded rect, 1: circle, 2: capsule.
+    @location(8) kind: u32,
+    // Non-zero to draw over the blurred backdrop.
+    @location(9) backdrop_blur: u32,
+    // Size of the render target. Differs from the view size when the view is stretched.
+    @location(10) screen_size: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position relative to the shape center, in pixels.
+    @location(0) local: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) border_width: f32,
+    @location(3) corner_radii: vec4<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) border_color: vec4<f32>,
+    @location(6) @interpolate(flat) kind: u32,
+    @location(7) @interpolate(flat) backdrop_blur: u32,
+    @location(8) @interpolate(flat) screen_size: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let u0 = ((in_vertex_index << 1u) & 2u) >> 1u; // [0, 1]
+    let v0 = ((in_vertex_index & 2u)) >> 1u; // [0, 1]
+
+    // Grow the quad by a pixel so that the anti-aliased edge isn't cut off.
+    let uv = vec2<f32>(f32(u0), f32(v0));
+    let corner = uv * (instance.size + vec2<f32>(2.0)) - vec2<f32>(1.0);
+
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let pixel_position = instance.position + vec2<f32>(c * corner.x - s * corner.y, s * corner.x + c * corner.y);
+
+    out.clip_position = vec4<f32>(
+        pixel_position.x / instance.view_size.x * 2.0 - 1.0,
+        1.0 - pixel_position.y / instance.view_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.local = corner - instance.size * 0.5;
+    out.half_size = instance.size * 0.5;
+    out.border_width = instance.border_width;
+    out.corner_radii = instance.corner_radii;
+    out.color = instance.color;
+    out.border_color = instance.border_color;
+    out.kind = instance.kind;
+    out.backdrop_blur = instance.backdrop_blur;
+    out.screen_size = instance.screen_size;
+
+    return out;
+}
+
+// Fragment shader //
+
+fn sd_rounded_box(p: vec2<f32>, half_size: vec2<f32>, radii: vec4<f32>) -> f32 {
+    // Y points down, so negative y is the top half.
+    var r: f32;
+    if (p.x < 0.0) {
+        r = select(radii.w, radii.x, p.y < 0.0);
+    } else {
+        r = select(radii.z, radii.y, p.y < 0.0);
+    }
+    r = min(r, min(half_size.x, half_size.y));
+
+    let q = abs(p) - half_size + vec2<f32>(r);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - r;
+}
+
+fn sd_shape(in: VertexOutput) -> f32 {
+    let min_half = min(in.half_size.x, in.half_size.y);
+
+    switch in.kind {
+        case 1u: {
+            return length(in.local) - min_half;
+        }
+        case 2u: {
+            return sd_rounded_box(in.local, in.half_size, vec4<f32>(min_half));
+        }
+        default: {
+            return sd_rounded_box(in.local, in.half_size, in.corner_radii);
+        }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = sd_shape(in);
+
+    // Width of one pixel in distance units, so edges stay smooth at any scale.
+    let aa = max(fwidth(distance), 0.0001);
+
+    let outer = clamp(0.5 - distance / aa, 0.0, 1.0);
+    let inner = clamp(0.5 - (distance + in.border_width) / aa, 0.0, 1.0);
+
+    // Output premultiplied alpha.
+    var fill = vec4<f32>(in.color.rgb * in.color.a, in.color.a);
+
+    if (in.backdrop_blur != 0u) {
+        // The fill color tints the backdrop, which makes the fill opaque.
+        let uv = in.clip_position.xy / in.screen_size;
+        let backdrop = textureSampleLevel(t_backdrop, s_backdrop, uv, 0.0).rgb;
+        fill = vec4<f32>(backdrop * (1.0 - fill.a) + fill.rgb, 1.0);
+    }
+    let border = vec4<f32>(in.border_color.rgb * in.border_color.a, in.border_color.a);
+
+    return mix(border, fill, inner) * outer;
+}
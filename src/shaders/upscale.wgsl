@@ -0,0 +1,70 @@
+// Brings the 3D scene drawn at a reduced resolution up to the window size.
+// The sharpening follows the robust contrast adaptive sharpening of AMD FSR 1.
+
+struct Params {
+    sharpness: f32,
+    _pad0: f32,
+    _pad1: f32,
+    _pad2: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(0)
+var t_source: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s_source: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Vertex shader //
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // A single triangle covering the whole screen.
+    let u = f32((in_vertex_index << 1u) & 2u);
+    let v = f32(in_vertex_index & 2u);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(u * 2.0 - 1.0, 1.0 - v * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+// Fragment shader //
+
+@fragment
+fn fs_bilinear(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_source, in.uv);
+}
+
+@fragment
+fn fs_sharpen(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Neighbors one source texel away, so the kernel covers what the upscale blurred.
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    let center = textureSample(t_source, s_source, in.uv);
+    let n = textureSample(t_source, s_source, in.uv + vec2<f32>(0.0, -texel.y)).rgb;
+    let s = textureSample(t_source, s_source, in.uv + vec2<f32>(0.0, texel.y)).rgb;
+    let w = textureSample(t_source, s_source, in.uv + vec2<f32>(-texel.x, 0.0)).rgb;
+    let e = textureSample(t_source, s_source, in.uv + vec2<f32>(texel.x, 0.0)).rgb;
+
+    let c = center.rgb;
+    let min_rgb = min(c, min(min(n, s), min(w, e)));
+    let max_rgb = max(c, max(max(n, s), max(w, e)));
+
+    // Weaker where the neighborhood already has contrast, so edges don't ring.
+    let amount = sqrt(clamp(min(min_rgb, 1.0 - max_rgb) / max(max_rgb, vec3<f32>(1e-4)), vec3<f32>(0.0), vec3<f32>(1.0)));
+    let peak = -1.0 / mix(8.0, 5.0, clamp(params.sharpness, 0.0, 1.0));
+    let lobe = amount * peak;
+
+    let color = (c + (n + s + w + e) * lobe) / (1.0 + 4.0 * lobe);
+
+    return vec4<f32>(max(color, vec3<f32>(0.0)), center.a);
+}
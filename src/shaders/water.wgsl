@@ -0,0 +1,253 @@
+// Water surface drawn over the opaque scene, which it refracts and reflects.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+struct Globals {
+    camera: Camera,
+    inverse_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+    time: f32,
+    delta: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(0) @binding(1)
+var<uniform> globals: Globals;
+
+const REFLECTION_SCREEN_SPACE: u32 = 1u;
+
+// Steps of the screen space reflection ray march.
+const SSR_STEPS: i32 = 48;
+
+struct Params {
+    model_matrix: mat4x4<f32>,
+    shallow_color: vec4<f32>,
+    deep_color: vec4<f32>,
+    sky_color: vec4<f32>,
+    foam_color: vec4<f32>,
+    // Plane extent on the local XZ plane.
+    size: vec2<f32>,
+    // Wave drift in world units per second.
+    flow: vec2<f32>,
+    // World units per normal map tile.
+    wave_scale: f32,
+    normal_strength: f32,
+    // Zero if there is no flow map.
+    flow_map_speed: f32,
+    refraction_strength: f32,
+    // Depth over which the water goes from shallow to deep.
+    absorption_depth: f32,
+    foam_distance: f32,
+    // 0: none, 1: screen space.
+    reflection: u32,
+    _pad: f32,
+}
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(1)
+var t_normal: texture_2d<f32>;
+
+@group(1) @binding(2)
+var s_normal: sampler;
+
+@group(1) @binding(3)
+var t_flow: texture_2d<f32>;
+
+@group(1) @binding(4)
+var s_flow: sampler;
+
+// A copy of the scene color, taken right before the water is drawn.
+@group(2) @binding(0)
+var t_scene_color: texture_2d<f32>;
+
+@group(2) @binding(1)
+var t_scene_depth: texture_2d<f32>;
+
+@group(2) @binding(2)
+var s_scene: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    // Across the whole plane, for the flow map.
+    @location(1) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let u = f32((in_vertex_index << 1u) & 2u) * 0.5; // [0, 1]
+    let v = f32(in_vertex_index & 2u) * 0.5; // [0, 1]
+
+    // Centered quad in the XZ plane.
+    let local = vec3<f32>((u - 0.5) * params.size.x, 0.0, (v - 0.5) * params.size.y);
+    let world_position = params.model_matrix * vec4<f32>(local, 1.0);
+
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.uv = vec2<f32>(u, v);
+
+    return out;
+}
+
+fn sample_normal(uv: vec2<f32>) -> vec3<f32> {
+    let n = textureSample(t_normal, s_normal, uv).xyz * 2.0 - 1.0;
+
+    // Tangent space Z is up on the plane.
+    return vec3<f32>(n.x, n.z, n.y);
+}
+
+// Animated wave normal in world space.
+fn wave_normal(world_position: vec3<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let flow_dir = textureSample(t_flow, s_flow, uv).rg * 2.0 - 1.0;
+    let flow = (params.flow + flow_dir * params.flow_map_speed) / params.wave_scale;
+
+    // Two layers half a cycle apart, each fading out as it resets,
+    // so that the stretching along uneven flow never gets too far.
+    let cycle = 4.0;
+    let phase0 = fract(globals.time / cycle);
+    let phase1 = fract(globals.time / cycle + 0.5);
+    let weight = abs(phase0 * 2.0 - 1.0);
+
+    let tile = world_position.xz / params.wave_scale;
+    let n0 = sample_normal(tile - flow * phase0 * cycle);
+    let n1 = sample_normal(tile - flow * phase1 * cycle + vec2<f32>(0.5));
+
+    // A slower, larger layer so that the water never looks frozen.
+    let n2 = sample_normal(tile * 0.37 + vec2<f32>(0.013, -0.008) * globals.time);
+
+    let n = mix(n0, n1, weight) + n2 * 0.5;
+
+    let local_normal = normalize(vec3<f32>(n.x * params.normal_strength, n.y, n.z * params.normal_strength));
+
+    // Follow the plane's orientation.
+    let model = params.model_matrix;
+    let world_normal = mat3x3<f32>(normalize(model[0].xyz), normalize(model[1].xyz), normalize(model[2].xyz)) * local_normal;
+
+    return normalize(world_normal);
+}
+
+fn clamp_pixel(pixel: vec2<i32>) -> vec2<i32> {
+    return clamp(pixel, vec2<i32>(0), vec2<i32>(globals.viewport_size) - 1);
+}
+
+// Distance from the camera to the opaque surface seen through a pixel.
+fn scene_distance(pixel: vec2<i32>) -> f32 {
+    let depth = textureLoad(t_scene_depth, clamp_pixel(pixel), 0).r;
+
+    let uv = (vec2<f32>(pixel) + 0.5) / globals.viewport_size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = globals.inverse_view_proj * ndc;
+
+    if (abs(world.w) < 0.000001) {
+        return 1000000.0;
+    }
+
+    return distance(world.xyz / world.w, camera.view_pos.xyz);
+}
+
+// March the reflected ray until it goes behind something on screen.
+// Returns the hit color and how much it should be trusted.
+fn screen_space_reflection(origin: vec3<f32>, direction: vec3<f32>) -> vec4<f32> {
+    var t = 0.1;
+
+    for (var i = 0; i < SSR_STEPS; i += 1) {
+        let position = origin + direction * t;
+
+        let clip = camera.view_proj * vec4<f32>(position, 1.0);
+        if (clip.w <= 0.0) {
+            break;
+        }
+
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+            break;
+        }
+
+        let behind = distance(position, camera.view_pos.xyz) - scene_distance(vec2<i32>(uv * globals.viewport_size));
+
+        // Steps grow along the ray, so allow thicker surfaces further away.
+        if (behind > 0.0 && behind < t * 0.25 + 0.1) {
+            // Fade out at the screen edges instead of cutting off.
+            let edge = min(min(uv.x, 1.0 - uv.x), min(uv.y, 1.0 - uv.y));
+            let confidence = clamp(edge * 10.0, 0.0, 1.0);
+
+            return vec4<f32>(textureSampleLevel(t_scene_color, s_scene, uv, 0.0).rgb, confidence);
+        }
+
+        t = t * 1.12 + 0.05;
+    }
+
+    return vec4<f32>(0.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Sampled before the manual depth test, since derivatives need all fragments.
+    let normal = wave_normal(in.world_position, in.uv);
+
+    let pixel = vec2<i32>(in.clip_position.xy);
+
+    let to_surface = in.world_position - camera.view_pos.xyz;
+    let surface_distance = length(to_surface);
+    let view_dir = to_surface / surface_distance;
+
+    // There's no depth test, so hide the parts behind geometry by hand.
+    let thickness = scene_distance(pixel) - surface_distance;
+    if (thickness < 0.0) {
+        discard;
+    }
+
+    // Vertical distance to the ground, exact for level water.
+    let water_depth = thickness * abs(view_dir.y);
+
+    // Refraction, calmer near the shore so that the ground there lines up.
+    let screen_uv = in.clip_position.xy / globals.viewport_size;
+    let offset = normal.xz * params.refraction_strength * clamp(water_depth, 0.0, 1.0);
+    var refract_uv = clamp(screen_uv + offset, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    // Don't pull in things in front of the water.
+    if (scene_distance(vec2<i32>(refract_uv * globals.viewport_size)) < surface_distance) {
+        refract_uv = screen_uv;
+    }
+
+    let refracted = textureSampleLevel(t_scene_color, s_scene, refract_uv, 0.0).rgb;
+
+    let absorption = 1.0 - exp(-water_depth / params.absorption_depth);
+    let body = mix(refracted * params.shallow_color.rgb, params.deep_color.rgb, absorption);
+
+    // Reflection.
+    var reflection = params.sky_color.rgb;
+    if (params.reflection == REFLECTION_SCREEN_SPACE) {
+        let hit = screen_space_reflection(in.world_position, reflect(view_dir, normal));
+        reflection = mix(reflection, hit.rgb, hit.a);
+    }
+
+    // Schlick's approximation for water.
+    let cos_theta = clamp(dot(-view_dir, normal), 0.0, 1.0);
+    let fresnel = 0.02 + 0.98 * pow(1.0 - cos_theta, 5.0);
+
+    var color = mix(body, reflection, fresnel);
+
+    // Foam where the water gets shallow, in bands that move with the waves.
+    if (params.foam_distance > 0.0) {
+        let shore = 1.0 - clamp(water_depth / params.foam_distance, 0.0, 1.0);
+        let bands = 0.5 + 0.5 * sin(shore * 12.0 - globals.time * 2.0 + (normal.x + normal.z) * 20.0);
+        let foam = smoothstep(0.0, 1.0, shore) * mix(0.5, 1.0, bands);
+
+        color = mix(color, params.foam_color.rgb, foam * params.foam_color.a);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
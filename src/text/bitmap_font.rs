@@ -0,0 +1,266 @@
+use crate::asset::{TextureFilter, TextureImportSettings, TextureWrap};
+use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use anyhow::{bail, Context, Result};
+use cgmath::{Vector2, Vector4};
+use image::{DynamicImage, GenericImage, RgbaImage};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A character of a bitmap font.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct BitmapChar {
+    /// Region in the atlas, in pixels (x, y, width, height).
+    pub(crate) region: Vector4<u32>,
+    /// From the top left of the line to the top left of the bitmap.
+    pub(crate) offset: Vector2<i32>,
+    pub(crate) x_adv: i32,
+}
+
+/// A pre-baked font in the BMFont format, made by tools like BMFont, Hiero or
+/// Littera. Glyphs are drawn as they are in the page images, with their colors,
+/// and aren't scaled or smoothed.
+///
+/// Both the text and the XML .fnt files are read. Pages are stacked into one atlas.
+pub(crate) struct BitmapFont {
+    /// Distance between two lines, in pixels.
+    pub(crate) line_height: u32,
+
+    chars: HashMap<char, BitmapChar>,
+
+    /// Added to the advance of the first character of each pair.
+    kernings: HashMap<(char, char), i32>,
+
+    /// All pages, top to bottom.
+    pub atlas_texture: TextureId,
+    pub(crate) atlas_size: (u32, u32),
+}
+
+impl BitmapFont {
+    /// Load a .fnt file, and the page images it refers to, relative to it.
+    pub(crate) fn load_from_file<P: AsRef<Path>>(
+        path: P,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Invalid bitmap font path {:?}", path))?;
+
+        let blocks = if source.trim_start().starts_with('<') {
+            parse_xml(&source)?
+        } else {
+            parse_text(&source)
+        };
+
+        Self::from_blocks(
+            &blocks,
+            path.parent().unwrap_or(Path::new("")),
+            render_server,
+            texture_cache,
+        )
+        .with_context(|| format!("Invalid bitmap font {:?}", path))
+    }
+
+    fn from_blocks(
+        blocks: &[Block],
+        dir: &Path,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> Result<Self> {
+        let find = |tag: &str| blocks.iter().find(|b| b.tag == tag);
+        let filter = |tag: &'static str| blocks.iter().filter(move |b| b.tag == tag);
+
+        let common = find("common").context("No common block")?;
+
+        if common.get_or("packed", 0) != 0 {
+            bail!("Glyphs packed into color channels aren't supported");
+        }
+
+        // Page images, by ID.
+        let mut pages = vec![];
+        for page in filter("page") {
+            let id: usize = page.get("id")?;
+            let file: String = page.get("file")?;
+
+            let image = image::open(dir.join(&file))
+                .with_context(|| format!("Invalid bitmap font page {}", file))?
+                .to_rgba8();
+
+            if pages.len() <= id {
+                pages.resize(id + 1, None);
+            }
+            pages[id] = Some(image);
+        }
+
+        let pages: Vec<RgbaImage> = pages
+            .into_iter()
+            .collect::<Option<_>>()
+            .context("Missing page")?;
+
+        if pages.is_empty() {
+            bail!("No pages");
+        }
+
+        // Stack the pages, so that one texture can be drawn with.
+        let atlas_width = pages.iter().map(|p| p.width()).max().unwrap();
+        let atlas_height = pages.iter().map(|p| p.height()).sum();
+
+        let mut atlas_image = RgbaImage::new(atlas_width, atlas_height);
+        let mut page_tops = vec![];
+        let mut top = 0;
+        for page in &pages {
+            atlas_image.copy_from(page, 0, top)?;
+            page_tops.push(top);
+            top += page.height();
+        }
+
+        let mut chars = HashMap::new();
+        for c in filter("char") {
+            let Some(character) = char::from_u32(c.get("id")?) else {
+                continue;
+            };
+
+            let page: usize = c.get_or("page", 0);
+            let page_top = *page_tops
+                .get(page)
+                .with_context(|| format!("Character {:?} is on a missing page", character))?;
+
+            chars.insert(
+                character,
+                BitmapChar {
+                    region: Vector4::new(
+                        c.get("x")?,
+                        c.get::<u32>("y")? + page_top,
+                        c.get("width")?,
+                        c.get("height")?,
+                    ),
+                    offset: Vector2::new(c.get_or("xoffset", 0), c.get_or("yoffset", 0)),
+                    x_adv: c.get("xadvance")?,
+                },
+            );
+        }
+
+        let mut kernings = HashMap::new();
+        for k in filter("kerning") {
+            let (Some(first), Some(second)) = (
+                char::from_u32(k.get("first")?),
+                char::from_u32(k.get("second")?),
+            ) else {
+                continue;
+            };
+
+            kernings.insert((first, second), k.get("amount")?);
+        }
+
+        // Keep the pixels sharp.
+        let settings = TextureImportSettings {
+            filter: TextureFilter::Nearest,
+            wrap: TextureWrap::ClampToEdge,
+            ..Default::default()
+        };
+
+        let atlas_texture = Texture::from_image_with_settings(
+            &render_server.device,
+            &render_server.queue,
+            texture_cache,
+            &DynamicImage::ImageRgba8(atlas_image),
+            Some("bitmap font atlas"),
+            &settings,
+        )?;
+
+        Ok(Self {
+            line_height: common.get("lineHeight")?,
+            chars,
+            kernings,
+            atlas_texture,
+            atlas_size: (atlas_width, atlas_height),
+        })
+    }
+
+    pub(crate) fn get_char(&self, character: char) -> Option<&BitmapChar> {
+        self.chars.get(&character)
+    }
+
+    pub(crate) fn get_kerning(&self, first: char, second: char) -> i32 {
+        self.kernings.get(&(first, second)).copied().unwrap_or(0)
+    }
+}
+
+/// A line of a text .fnt file, or an element of an XML one.
+struct Block {
+    tag: String,
+    attributes: HashMap<String, String>,
+}
+
+impl Block {
+    fn get<T: std::str::FromStr>(&self, key: &str) -> Result<T> {
+        let value = self
+            .attributes
+            .get(key)
+            .with_context(|| format!("{} is missing {}", self.tag, key))?;
+
+        value
+            .parse()
+            .ok()
+            .with_context(|| format!("Invalid {} in {}: {}", key, self.tag, value))
+    }
+
+    fn get_or<T: std::str::FromStr>(&self, key: &str, default: T) -> T {
+        self.get(key).unwrap_or(default)
+    }
+}
+
+/// Lines like `char id=65 x=10 y=0 ...`, values may be quoted.
+fn parse_text(source: &str) -> Vec<Block> {
+    let mut blocks = vec![];
+
+    for line in source.lines() {
+        let line = line.trim();
+        let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if tag.is_empty() {
+            continue;
+        }
+
+        let mut attributes = HashMap::new();
+
+        loop {
+            rest = rest.trim_start();
+            let Some((key, value)) = rest.split_once('=') else {
+                break;
+            };
+
+            let (value, next) = match value.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+            };
+
+            attributes.insert(key.trim().to_string(), value.to_string());
+            rest = next;
+        }
+
+        blocks.push(Block {
+            tag: tag.to_string(),
+            attributes,
+        });
+    }
+
+    blocks
+}
+
+/// The same blocks as elements, nested in `pages`, `chars` and `kernings`.
+fn parse_xml(source: &str) -> Result<Vec<Block>> {
+    let document = roxmltree::Document::parse(source)?;
+
+    Ok(document
+        .descendants()
+        .filter(|n| n.is_element())
+        .map(|n| Block {
+            tag: n.tag_name().name().to_string(),
+            attributes: n
+                .attributes()
+                .map(|a| (a.name().to_string(), a.value().to_string()))
+                .collect(),
+        })
+        .collect())
+}
@@ -1,9 +1,11 @@
+use crate::asset::TextureImportSettings;
 use crate::render::{RenderServer, Texture, TextureCache, TextureId};
+use crate::text::BitmapFont;
 use allsorts::pathfinder_geometry::rect::RectI;
 use allsorts::pathfinder_geometry::vector::Vector2I;
 use cgmath::{Point2, Vector2, Vector4};
 use fontdue;
-use image::{DynamicImage, Luma};
+use image::{DynamicImage, Luma, Rgba};
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::fs;
@@ -79,6 +81,21 @@ pub(crate) struct Font {
     raw_data: Vec<u8>,
 }
 
+/// A font in the text server, rasterized on demand or pre-baked.
+pub(crate) enum FontVariant {
+    Dynamic(Box<DynamicFont>),
+    Bitmap(BitmapFont),
+}
+
+impl FontVariant {
+    pub(crate) fn atlas_texture(&self) -> TextureId {
+        match self {
+            FontVariant::Dynamic(font) => font.atlas_texture,
+            FontVariant::Bitmap(font) => font.atlas_texture,
+        }
+    }
+}
+
 pub(crate) struct DynamicFont {
     /// Raw font data.
     raw_font_data: Vec<u8>,
@@ -88,7 +105,11 @@ pub(crate) struct DynamicFont {
     /// Font size in pixel.
     pub size: u32,
 
+    /// Glyphs are rasterized with horizontal RGB subpixel coverage.
+    subpixel: bool,
+
     /// Contains all cached glyphs' bitmaps.
+    /// Luma8 for grayscale glyphs, Rgba8 (RGB coverage, max coverage in alpha) for subpixel ones.
     atlas_image: DynamicImage,
 
     /// GPU texture.
@@ -138,17 +159,7 @@ impl DynamicFont {
         let fontdue_font =
             fontdue::Font::from_bytes(buffer, fontdue::FontSettings::default()).unwrap();
 
-        let atlas_image =
-            DynamicImage::ImageLuma8(image::GrayImage::new(FONT_ATLAS_SIZE, FONT_ATLAS_SIZE));
-
-        let atlas_texture = Texture::from_image(
-            &render_server.device,
-            &render_server.queue,
-            texture_cache,
-            &atlas_image,
-            "default font atlas".into(),
-        )
-        .unwrap();
+        let (atlas_image, atlas_texture) = Self::create_atlas(false, render_server, texture_cache);
 
         // let atlas_bind_group = render_server.create_sprite2d_bind_group(&atlas_texture);
 
@@ -161,6 +172,7 @@ impl DynamicFont {
             raw_font_data,
             fontdue_font,
             size: 32,
+            subpixel: false,
             atlas_image,
             atlas_texture,
             updated_atlas_region: None,
@@ -170,6 +182,72 @@ impl DynamicFont {
         }
     }
 
+    fn create_atlas(
+        subpixel: bool,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> (DynamicImage, TextureId) {
+        let atlas_image = if subpixel {
+            DynamicImage::ImageRgba8(image::RgbaImage::new(FONT_ATLAS_SIZE, FONT_ATLAS_SIZE))
+        } else {
+            DynamicImage::ImageLuma8(image::GrayImage::new(FONT_ATLAS_SIZE, FONT_ATLAS_SIZE))
+        };
+
+        // Glyph coverage is linear data.
+        let settings = TextureImportSettings {
+            srgb: false,
+            premultiply_alpha: false,
+            ..Default::default()
+        };
+
+        let atlas_texture = Texture::from_image_with_settings(
+            &render_server.device,
+            &render_server.queue,
+            texture_cache,
+            &atlas_image,
+            "default font atlas".into(),
+            &settings,
+        )
+        .unwrap();
+
+        (atlas_image, atlas_texture)
+    }
+
+    /// The font file, for reading glyph outlines.
+    pub(crate) fn get_font_data(&self) -> &[u8] {
+        &self.raw_font_data
+    }
+
+    pub(crate) fn is_subpixel(&self) -> bool {
+        self.subpixel
+    }
+
+    /// Switch between grayscale and subpixel glyphs. This clears the glyph cache.
+    ///
+    /// The new atlas texture is stored under the same texture ID,
+    /// so handles to the atlas stay valid.
+    pub(crate) fn set_subpixel(
+        &mut self,
+        subpixel: bool,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) {
+        if self.subpixel == subpixel {
+            return;
+        }
+
+        let (atlas_image, atlas_texture) =
+            Self::create_atlas(subpixel, render_server, texture_cache);
+        texture_cache.replace(self.atlas_texture, atlas_texture);
+
+        self.subpixel = subpixel;
+        self.atlas_image = atlas_image;
+        self.updated_atlas_region = None;
+        self.next_glyph_position = Point2::new(0, 0);
+        self.max_height_of_current_row = 0;
+        self.glyph_cache.clear();
+    }
+
     pub(crate) fn get_ascent(&mut self) -> f32 {
         let metrics = self
             .fontdue_font
@@ -216,6 +294,20 @@ impl DynamicFont {
                         size,
                     );
                 }
+                DynamicImage::ImageRgba8(rgba) => {
+                    queue.write_texture(
+                        img_copy_texture,
+                        rgba,
+                        wgpu::ImageDataLayout {
+                            offset: (region.min_y() * FONT_ATLAS_SIZE as i32 + region.min_x())
+                                as wgpu::BufferAddress
+                                * 4,
+                            bytes_per_row: Some(FONT_ATLAS_SIZE * 4),
+                            rows_per_image: Some(FONT_ATLAS_SIZE),
+                        },
+                        size,
+                    );
+                }
                 _ => {}
             }
 
@@ -355,8 +447,13 @@ impl DynamicFont {
                     }
 
                     // Rasterize and get the layout metrics for the character.
-                    let (metrics, bitmap) =
-                        self.fontdue_font.rasterize_indexed(index, self.size as f32);
+                    // Subpixel bitmaps have three coverage values per pixel.
+                    let (metrics, bitmap) = if self.subpixel {
+                        self.fontdue_font
+                            .rasterize_indexed_subpixel(index, self.size as f32)
+                    } else {
+                        self.fontdue_font.rasterize_indexed(index, self.size as f32)
+                    };
 
                     // For debugging.
                     // let buffer: &[u8] = &bitmap;
@@ -391,6 +488,11 @@ impl DynamicFont {
                                             Luma([bitmap[row * metrics.width + col]]),
                                         );
                                     }
+                                    DynamicImage::ImageRgba8(img) => {
+                                        let i = (row * metrics.width + col) * 3;
+                                        let (r, g, b) = (bitmap[i], bitmap[i + 1], bitmap[i + 2]);
+                                        img.put_pixel(x, y, Rgba([r, g, b, r.max(g).max(b)]));
+                                    }
                                     _ => {
                                         panic!()
                                     }
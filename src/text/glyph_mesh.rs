@@ -0,0 +1,296 @@
+use crate::render::vertex::Vertex3d;
+use anyhow::{Context, Result};
+use cgmath::{InnerSpace, Vector2};
+use lyon::math::point;
+use lyon::path::iterator::PathIterator;
+use lyon::path::{FillRule, Path, PathEvent};
+use lyon::tessellation::{BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers};
+use rustybuzz::ttf_parser;
+
+/// Side normals of neighbouring edges closer than this are averaged, so curves look round
+/// while corners stay sharp. Cosine of about 30 degrees.
+const SMOOTH_ANGLE_COS: f32 = 0.866;
+
+/// Geometry of extruded text, in two parts so that they can have their own materials.
+pub(crate) struct TextMeshData {
+    /// Front faces at z = 0 and back faces at z = -depth.
+    pub(crate) caps: (Vec<Vertex3d>, Vec<u32>),
+    /// Walls along the glyph outlines.
+    pub(crate) sides: (Vec<Vertex3d>, Vec<u32>),
+}
+
+/// Shape a text with a font, and extrude the outlines of its glyphs.
+///
+/// The first baseline starts at the origin, with Y up and the front facing +Z.
+/// `size` is the em size, in world units. Curves are flattened to within `tolerance`.
+pub(crate) fn build_text_mesh(
+    font_data: &[u8],
+    text: &str,
+    size: f32,
+    depth: f32,
+    tolerance: f32,
+) -> Result<TextMeshData> {
+    let face = rustybuzz::Face::from_slice(font_data, 0).context("Invalid font data")?;
+
+    let scale = size / face.units_per_em() as f32;
+    let line_height = (face.ascender() - face.descender() + face.line_gap()) as f32 * scale;
+
+    let mut mesh = TextMeshData {
+        caps: (vec![], vec![]),
+        sides: (vec![], vec![]),
+    };
+
+    for (i, line) in text.lines().enumerate() {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(line);
+        buffer.guess_segment_properties();
+
+        let glyphs = rustybuzz::shape(&face, &[], buffer);
+
+        let mut pen = Vector2::new(0.0, -(i as f32) * line_height);
+
+        for (info, position) in glyphs.glyph_infos().iter().zip(glyphs.glyph_positions()) {
+            let offset =
+                pen + Vector2::new(position.x_offset as f32, position.y_offset as f32) * scale;
+
+            let mut builder = OutlineBuilder {
+                builder: Path::builder(),
+                offset,
+                scale,
+                open: false,
+            };
+
+            let glyph_id = ttf_parser::GlyphId(info.glyph_id as u16);
+
+            // Spaces have no outline.
+            if face.outline_glyph(glyph_id, &mut builder).is_some() {
+                if builder.open {
+                    builder.builder.end(true);
+                }
+                let path = builder.builder.build();
+
+                add_caps(&path, depth, size, tolerance, &mut mesh.caps)?;
+                add_sides(&path, depth, size, tolerance, &mut mesh.sides);
+            }
+
+            pen.x += position.x_advance as f32 * scale;
+            pen.y += position.y_advance as f32 * scale;
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Feeds glyph outlines in font units into a path in world units.
+struct OutlineBuilder {
+    builder: lyon::path::path::Builder,
+    offset: Vector2<f32>,
+    scale: f32,
+    /// If a contour was begun and not ended yet.
+    open: bool,
+}
+
+impl OutlineBuilder {
+    fn point(&self, x: f32, y: f32) -> lyon::math::Point {
+        point(
+            x * self.scale + self.offset.x,
+            y * self.scale + self.offset.y,
+        )
+    }
+}
+
+impl ttf_parser::OutlineBuilder for OutlineBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        if self.open {
+            self.builder.end(true);
+        }
+        self.builder.begin(self.point(x, y));
+        self.open = true;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.builder.line_to(self.point(x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.builder
+            .quadratic_bezier_to(self.point(x1, y1), self.point(x, y));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.builder
+            .cubic_bezier_to(self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+    }
+
+    fn close(&mut self) {
+        self.builder.end(true);
+        self.open = false;
+    }
+}
+
+fn add_caps(
+    path: &Path,
+    depth: f32,
+    size: f32,
+    tolerance: f32,
+    caps: &mut (Vec<Vertex3d>, Vec<u32>),
+) -> Result<()> {
+    let mut geometry: VertexBuffers<Vector2<f32>, u32> = VertexBuffers::new();
+
+    // TrueType and CFF outlines both fill by the non-zero rule.
+    FillTessellator::new()
+        .tessellate_path(
+            path,
+            &FillOptions::tolerance(tolerance).with_fill_rule(FillRule::NonZero),
+            &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
+                Vector2::new(vertex.position().x, vertex.position().y)
+            }),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to tessellate glyph: {:?}", e))?;
+
+    let (vertices, indices) = caps;
+
+    for (z, normal) in [(0.0, 1.0), (-depth, -1.0)] {
+        let start = vertices.len() as u32;
+
+        vertices.extend(geometry.vertices.iter().map(|p| Vertex3d {
+            position: [p.x, p.y, z],
+            // One texture repeat per em. Texture V goes down.
+            uv: [p.x * normal / size, -p.y / size],
+            normal: [0.0, 0.0, normal],
+            tangent: [normal, 0.0, 0.0],
+            bi_tangent: [0.0, 1.0, 0.0],
+        }));
+
+        for triangle in geometry.indices.chunks_exact(3) {
+            let a = geometry.vertices[triangle[0] as usize];
+            let b = geometry.vertices[triangle[1] as usize];
+            let c = geometry.vertices[triangle[2] as usize];
+
+            // Counter-clockwise when seen from the side the cap faces.
+            let ccw = (b - a).perp_dot(c - a) > 0.0;
+            let (b, c) = if ccw == (normal > 0.0) {
+                (triangle[1], triangle[2])
+            } else {
+                (triangle[2], triangle[1])
+            };
+
+            indices.extend_from_slice(&[start + triangle[0], start + b, start + c]);
+        }
+    }
+
+    Ok(())
+}
+
+fn add_sides(
+    path: &Path,
+    depth: f32,
+    size: f32,
+    tolerance: f32,
+    sides: &mut (Vec<Vertex3d>, Vec<u32>),
+) {
+    let mut contours = vec![];
+    let mut contour = vec![];
+
+    for event in path.iter().flattened(tolerance) {
+        match event {
+            PathEvent::Begin { at } => contour = vec![Vector2::new(at.x, at.y)],
+            PathEvent::Line { to, .. } => contour.push(Vector2::new(to.x, to.y)),
+            PathEvent::End { .. } => {
+                // Closing points repeat the first one.
+                if contour.len() > 1 && contour.first() == contour.last() {
+                    contour.pop();
+                }
+                if contour.len() > 2 {
+                    contours.push(std::mem::take(&mut contour));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Outer contours wind one way and holes the other, so the filled side of every edge
+    // is the same. The outer contours make up most of the signed area.
+    let area: f32 = contours.iter().map(|c| signed_area(c)).sum();
+    let filled_on_left = area > 0.0;
+
+    let (vertices, indices) = sides;
+
+    for contour in &contours {
+        let count = contour.len();
+
+        // Outward normal of each edge, from point i to point i + 1.
+        let normals: Vec<Vector2<f32>> = (0..count)
+            .map(|i| {
+                let d = contour[(i + 1) % count] - contour[i];
+                let right = Vector2::new(d.y, -d.x);
+                let n = if filled_on_left { right } else { -right };
+                if n.magnitude2() > 0.0 {
+                    n.normalize()
+                } else {
+                    n
+                }
+            })
+            .collect();
+
+        let vertex_normal = |edge: usize, other: usize| {
+            if normals[edge].dot(normals[other]) > SMOOTH_ANGLE_COS {
+                (normals[edge] + normals[other]).normalize()
+            } else {
+                normals[edge]
+            }
+        };
+
+        let mut length = 0.0;
+
+        for i in 0..count {
+            let a = contour[i];
+            let b = contour[(i + 1) % count];
+            let edge_length = (b - a).magnitude();
+            if edge_length <= 0.0 {
+                continue;
+            }
+
+            let normal_a = vertex_normal(i, (i + count - 1) % count);
+            let normal_b = vertex_normal(i, (i + 1) % count);
+
+            // U runs along the outline, V from front to back.
+            let tangent = (b - a) / edge_length;
+
+            let start = vertices.len() as u32;
+
+            for (p, n, u) in [(a, normal_a, length), (b, normal_b, length + edge_length)] {
+                for z in [0.0, -depth] {
+                    vertices.push(Vertex3d {
+                        position: [p.x, p.y, z],
+                        uv: [u / size, -z / size],
+                        normal: [n.x, n.y, 0.0],
+                        tangent: [tangent.x, tangent.y, 0.0],
+                        // Texture V goes down.
+                        bi_tangent: [0.0, 0.0, 1.0],
+                    });
+                }
+            }
+
+            // Front a, back a, front b, back b. Counter-clockwise seen from outside.
+            let quad = if filled_on_left {
+                [0, 1, 2, 2, 1, 3]
+            } else {
+                [0, 2, 1, 2, 3, 1]
+            };
+            indices.extend(quad.iter().map(|i| start + i));
+
+            length += edge_length;
+        }
+    }
+}
+
+/// Positive for counter-clockwise points, with Y up.
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    let count = points.len();
+
+    (0..count)
+        .map(|i| points[i].perp_dot(points[(i + 1) % count]))
+        .sum::<f32>()
+        * 0.5
+}
@@ -1,5 +1,10 @@
+pub(crate) mod bitmap_font;
 pub(crate) mod font;
+pub(crate) mod glyph_mesh;
 pub(crate) mod text_server;
+pub(crate) mod translation;
 
+pub(crate) use bitmap_font::*;
 pub use font::*;
 pub use text_server::*;
+pub use translation::Translation;
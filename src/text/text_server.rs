@@ -1,8 +1,10 @@
 use crate::math::rect_to_vector4;
 use crate::math::transform::Transform2d;
 use crate::render::atlas::{Atlas, AtlasInstance, AtlasMode};
-use crate::render::{RenderServer, Texture, TextureCache};
-use crate::text::{DynamicFont, Glyph, Script, FONT_ATLAS_SIZE};
+use crate::render::render_world::RenderWorld;
+use crate::render::{RenderServer, TextureCache, TextureId};
+use crate::text::translation::Translations;
+use crate::text::{BitmapFont, DynamicFont, FontVariant, Translation, FONT_ATLAS_SIZE};
 use cgmath::{Point2, Vector2, Vector4};
 use font_kit::source::SystemSource;
 use std::collections::HashMap;
@@ -10,9 +12,31 @@ use std::iter::Map;
 use std::time::Instant;
 use unicode_linebreak::BreakClass;
 
+/// How glyph coverage is rasterized and blended.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TextRenderMode {
+    /// One coverage value per pixel.
+    #[default]
+    Grayscale,
+    /// Per-channel coverage for horizontal RGB LCD panels. Blended per channel if the device
+    /// supports dual-source blending, otherwise averaged back to grayscale.
+    /// Only applies to 2D labels, world-space text always blends as grayscale.
+    Subpixel,
+}
+
 pub struct TextServer {
-    fonts: HashMap<String, DynamicFont>,
+    fonts: HashMap<String, FontVariant>,
     // fallback_fonts: Map<Script, DynamicFont>,
+    render_mode: TextRenderMode,
+
+    /// Coverage exponent, values above 1.0 make glyphs heavier.
+    gamma: f32,
+
+    /// Bumped whenever glyph atlases are rebuilt or the locale changes,
+    /// so that labels know to lay out their text again.
+    atlas_version: u32,
+
+    translations: Translations,
 }
 
 impl TextServer {
@@ -35,9 +59,15 @@ impl TextServer {
         );
 
         let mut fonts = HashMap::new();
-        fonts.insert("default".to_string(), font);
-
-        Self { fonts }
+        fonts.insert("default".to_string(), FontVariant::Dynamic(Box::new(font)));
+
+        Self {
+            fonts,
+            render_mode: TextRenderMode::default(),
+            gamma: 1.0,
+            atlas_version: 0,
+            translations: Translations::new(),
+        }
     }
 
     /// Load a new font from disk.
@@ -47,8 +77,121 @@ impl TextServer {
         render_server: &RenderServer,
         texture_cache: &mut TextureCache,
     ) {
-        let font = DynamicFont::load_from_file(&font_path[..], render_server, texture_cache);
-        self.fonts.insert(font_path.clone(), font);
+        let mut font = DynamicFont::load_from_file(&font_path[..], render_server, texture_cache);
+        font.set_subpixel(
+            self.render_mode == TextRenderMode::Subpixel,
+            render_server,
+            texture_cache,
+        );
+        self.fonts
+            .insert(font_path.clone(), FontVariant::Dynamic(Box::new(font)));
+    }
+
+    /// Load a pre-baked BMFont .fnt file and its pages from disk.
+    pub fn load_bitmap_font(
+        &mut self,
+        font_path: &str,
+        render_server: &RenderServer,
+        texture_cache: &mut TextureCache,
+    ) -> anyhow::Result<()> {
+        let font = BitmapFont::load_from_file(font_path, render_server, texture_cache)?;
+        self.fonts
+            .insert(font_path.to_string(), FontVariant::Bitmap(font));
+        Ok(())
+    }
+
+    pub fn unload_font(&mut self, font_id: &str) {
+        if font_id != "default" {
+            self.fonts.remove(font_id);
+        }
+    }
+
+    pub fn get_render_mode(&self) -> TextRenderMode {
+        self.render_mode
+    }
+
+    /// Switching modes rebuilds all font atlases.
+    pub fn set_render_mode(
+        &mut self,
+        mode: TextRenderMode,
+        render_server: &RenderServer,
+        render_world: &mut RenderWorld,
+    ) {
+        if self.render_mode == mode {
+            return;
+        }
+        self.render_mode = mode;
+
+        for font in self.fonts.values_mut() {
+            // Bitmap fonts are drawn as they are.
+            let FontVariant::Dynamic(font) = font else {
+                continue;
+            };
+
+            font.set_subpixel(
+                mode == TextRenderMode::Subpixel,
+                render_server,
+                &mut render_world.texture_cache,
+            );
+        }
+
+        self.atlas_version += 1;
+    }
+
+    pub fn get_gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    /// Adjust glyph coverage as `coverage^(1/gamma)`. 1.0 is neutral,
+    /// around 1.4 helps light text on dark backgrounds. Only affects 2D labels.
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma.max(0.01);
+        self.atlas_version += 1;
+    }
+
+    /// Text of the message `key` in the current locale, or `key` itself if it isn't translated.
+    pub fn tr(&self, key: &str) -> String {
+        self.translations.tr(key)
+    }
+
+    /// Add messages, e.g. from `Translation::from_csv`, merging them into existing locales.
+    pub fn add_translation(&mut self, translation: Translation) {
+        self.translations.add(translation);
+        self.atlas_version += 1;
+    }
+
+    pub fn get_locale(&self) -> &str {
+        self.translations.get_locale()
+    }
+
+    /// Switch language, e.g. to "de" or "pt_BR". Labels are translated again.
+    pub fn set_locale(&mut self, locale: &str) {
+        self.translations.set_locale(locale);
+        self.atlas_version += 1;
+    }
+
+    /// Locale of messages missing in the current one. Defaults to "en".
+    pub fn set_fallback_locale(&mut self, locale: &str) {
+        self.translations.set_fallback_locale(locale);
+        self.atlas_version += 1;
+    }
+
+    pub fn is_pseudo_localization(&self) -> bool {
+        self.translations.is_pseudo_localization()
+    }
+
+    /// Accent and lengthen all translated text, to find truncated labels and hardcoded strings.
+    pub fn set_pseudo_localization(&mut self, enabled: bool) {
+        self.translations.set_pseudo_localization(enabled);
+        self.atlas_version += 1;
+    }
+
+    pub(crate) fn get_atlas_version(&self) -> u32 {
+        self.atlas_version
+    }
+
+    pub(crate) fn get_font_atlas(&self, font_id: &str) -> Option<TextureId> {
+        self.fonts.get(font_id).map(|f| f.atlas_texture())
     }
 
     pub(crate) fn prepare(
@@ -57,12 +200,26 @@ impl TextServer {
         texture_cache: &mut TextureCache,
     ) {
         for (key, font) in &mut self.fonts {
-            font.upload(render_server, texture_cache);
+            if let FontVariant::Dynamic(font) = font {
+                font.upload(render_server, texture_cache);
+            }
+        }
+    }
+
+    /// File data of a font, none for bitmap fonts.
+    pub(crate) fn get_font_data(&self, font_id: Option<&str>) -> Option<&[u8]> {
+        match self.fonts.get(font_id.unwrap_or("default"))? {
+            FontVariant::Dynamic(font) => Some(font.get_font_data()),
+            FontVariant::Bitmap(_) => None,
         }
     }
 
     pub(crate) fn get_default_font(&self) -> &DynamicFont {
-        self.fonts.get("default").unwrap()
+        // The default font is always a system font.
+        match self.fonts.get("default").unwrap() {
+            FontVariant::Dynamic(font) => font,
+            FontVariant::Bitmap(_) => unreachable!(),
+        }
     }
 
     pub(crate) fn get_atlas(
@@ -80,54 +237,133 @@ impl TextServer {
             font = self.fonts.get_mut("default").unwrap();
         }
 
-        let (glyphs, paras) = font.get_glyphs(text);
+        match font {
+            FontVariant::Dynamic(font) => {
+                layout_dynamic_font(font, text, xform, leading, self.gamma)
+            }
+            FontVariant::Bitmap(font) => layout_bitmap_font(font, text, xform, leading),
+        }
+    }
+}
 
-        let ascent = font.get_ascent();
+fn layout_dynamic_font(
+    font: &mut DynamicFont,
+    text: &str,
+    xform: Transform2d,
+    leading: f32,
+    gamma: f32,
+) -> Atlas {
+    let (glyphs, paras) = font.get_glyphs(text);
+
+    let ascent = font.get_ascent();
+
+    // Update atlas data.
+    let mut instances = vec![];
+
+    // Move origin from baseline to top-left.
+    let origin = xform.position + Vector2::new(0.0, ascent);
+
+    let mut layout_pos = Vector2::new(0.0, 0.0);
+
+    for para in paras {
+        for i in para {
+            let g = &glyphs[i];
+
+            // We only draw valid glyphs.
+            if let Some(region) = g.region {
+                let instance = AtlasInstance {
+                    position: Vector2::new(
+                        layout_pos.x + g.offset.x as f32,
+                        layout_pos.y + g.offset.y as f32,
+                    ) + origin,
+                    size: Vector2::new(g.bitmap_size.x as f32, g.bitmap_size.y as f32),
+                    region: rect_to_vector4(region.to_f32()) / FONT_ATLAS_SIZE as f32,
+                    color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+                };
+                instances.push(instance);
+            }
 
-        // Update atlas data.
-        let mut instances = vec![];
+            if g.break_property == BreakClass::LineFeed {
+                continue;
+            }
 
-        // Move origin from baseline to top-left.
-        let origin = xform.position + Vector2::new(0.0, ascent);
+            // Update next glyph's position.
+            layout_pos.x += g.x_adv as f32;
+        }
 
-        let mut layout_pos = Vector2::new(0.0, 0.0);
+        layout_pos.x = 0.0;
+        layout_pos.y += font.size as f32 + leading;
+    }
 
-        for para in paras {
-            for i in para {
-                let g = &glyphs[i];
+    Atlas {
+        texture: Some(font.atlas_texture),
+        instances,
+        texture_size: (FONT_ATLAS_SIZE, FONT_ATLAS_SIZE),
+        mode: if font.is_subpixel() {
+            AtlasMode::SubpixelText
+        } else {
+            AtlasMode::Text
+        },
+        gamma,
+    }
+}
+
+/// Bitmap glyphs are drawn at their size in the pages, from the top left of each line.
+fn layout_bitmap_font(font: &BitmapFont, text: &str, xform: Transform2d, leading: f32) -> Atlas {
+    let (atlas_width, atlas_height) = (font.atlas_size.0 as f32, font.atlas_size.1 as f32);
+
+    let mut instances = vec![];
+
+    let mut layout_pos = Vector2::new(0.0, 0.0);
 
-                // We only draw valid glyphs.
-                if let Some(region) = g.region {
-                    let instance = AtlasInstance {
-                        position: Vector2::new(
-                            layout_pos.x + g.offset.x as f32,
-                            layout_pos.y + g.offset.y as f32,
-                        ) + origin,
-                        size: Vector2::new(g.bitmap_size.x as f32, g.bitmap_size.y as f32),
-                        region: rect_to_vector4(region.to_f32()) / FONT_ATLAS_SIZE as f32,
-                        color: Vector4::new(1.0, 1.0, 1.0, 1.0),
-                    };
-                    instances.push(instance);
-                }
+    for line in text.split('\n') {
+        let mut previous = None;
 
-                if g.break_property == BreakClass::LineFeed {
-                    continue;
-                }
+        for character in line.chars() {
+            // Characters missing from the font are skipped.
+            let Some(c) = font.get_char(character) else {
+                continue;
+            };
 
-                // Update next glyph's position.
-                layout_pos.x += g.x_adv as f32;
+            if let Some(previous) = previous {
+                layout_pos.x += font.get_kerning(previous, character) as f32;
+            }
+            previous = Some(character);
+
+            if c.region.z > 0 && c.region.w > 0 {
+                let (x, y) = (c.region.x as f32, c.region.y as f32);
+                let size = Vector2::new(c.region.z as f32, c.region.w as f32);
+
+                instances.push(AtlasInstance {
+                    // Instances are placed by their bottom left corner.
+                    position: xform.position
+                        + layout_pos
+                        + Vector2::new(c.offset.x as f32, c.offset.y as f32 + size.y),
+                    size,
+                    region: Vector4::new(
+                        x / atlas_width,
+                        y / atlas_height,
+                        (x + size.x) / atlas_width,
+                        (y + size.y) / atlas_height,
+                    ),
+                    color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+                });
             }
 
-            layout_pos.x = 0.0;
-            layout_pos.y += font.size as f32 + leading;
+            layout_pos.x += c.x_adv as f32;
         }
 
-        Atlas {
-            texture: Some(font.atlas_texture),
-            instances,
-            texture_size: (FONT_ATLAS_SIZE, FONT_ATLAS_SIZE),
-            mode: AtlasMode::Text,
-        }
+        layout_pos.x = 0.0;
+        layout_pos.y += font.line_height as f32 + leading;
+    }
+
+    Atlas {
+        texture: Some(font.atlas_texture),
+        instances,
+        texture_size: font.atlas_size,
+        // Page colors are kept, not used as coverage.
+        mode: AtlasMode::Sprite,
+        gamma: 1.0,
     }
 }
 
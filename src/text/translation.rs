@@ -0,0 +1,265 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// Messages of one locale, keyed by message ID.
+#[derive(Debug, Clone, Default)]
+pub struct Translation {
+    locale: String,
+    messages: HashMap<String, String>,
+}
+
+impl Translation {
+    pub fn new(locale: &str) -> Self {
+        Self {
+            locale: locale.to_string(),
+            messages: HashMap::new(),
+        }
+    }
+
+    pub fn get_locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn add_message(&mut self, key: &str, message: &str) {
+        self.messages.insert(key.to_string(), message.to_string());
+    }
+
+    pub fn get_message(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+
+    /// Parse a table with message IDs in the first column and one locale per remaining column,
+    /// named in the header row. Empty cells are left untranslated.
+    pub fn from_csv(source: &str) -> Result<Vec<Translation>> {
+        let mut rows = parse_csv(source)?.into_iter();
+
+        let header = rows.next().context("Translation CSV has no header row")?;
+        let mut translations: Vec<Translation> = header
+            .iter()
+            .skip(1)
+            .map(|locale| Translation::new(locale.trim()))
+            .collect();
+
+        for row in rows {
+            let Some(key) = row.first().filter(|key| !key.is_empty()) else {
+                continue;
+            };
+
+            for (translation, message) in translations.iter_mut().zip(row.iter().skip(1)) {
+                if !message.is_empty() {
+                    translation.add_message(key, message);
+                }
+            }
+        }
+
+        Ok(translations)
+    }
+
+    /// Parse the message subset of a Fluent (.ftl) file: `key = value` lines, comments and
+    /// indented continuation lines. Terms, attributes and selectors aren't supported,
+    /// and placeables are kept as they are.
+    pub fn from_fluent(locale: &str, source: &str) -> Result<Self> {
+        let mut translation = Self::new(locale);
+        let mut current: Option<(String, String)> = None;
+
+        for (number, line) in source.lines().enumerate() {
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // Continues the previous message.
+                if let Some((_, message)) = &mut current {
+                    if !line.trim().is_empty() {
+                        if !message.is_empty() {
+                            message.push('\n');
+                        }
+                        message.push_str(line.trim());
+                    }
+                    continue;
+                }
+            }
+
+            if let Some((key, message)) = current.take() {
+                translation.messages.insert(key, message);
+            }
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((key, message)) if !key.trim().is_empty() => {
+                    current = Some((key.trim().to_string(), message.trim().to_string()));
+                }
+                _ => bail!("Invalid Fluent message at line {}: {}", number + 1, line),
+            }
+        }
+
+        if let Some((key, message)) = current {
+            translation.messages.insert(key, message);
+        }
+
+        Ok(translation)
+    }
+}
+
+/// Translated text lookup, see `TextServer::tr`.
+pub(crate) struct Translations {
+    translations: HashMap<String, Translation>,
+    locale: String,
+    /// Used for messages missing in the current locale.
+    fallback_locale: String,
+    pseudo_localization: bool,
+}
+
+impl Translations {
+    pub(crate) fn new() -> Self {
+        Self {
+            translations: HashMap::new(),
+            locale: "en".to_string(),
+            fallback_locale: "en".to_string(),
+            pseudo_localization: false,
+        }
+    }
+
+    /// Messages of an already added locale are merged, replacing existing ones.
+    pub(crate) fn add(&mut self, translation: Translation) {
+        match self.translations.get_mut(&translation.locale) {
+            Some(existing) => existing.messages.extend(translation.messages),
+            None => {
+                self.translations
+                    .insert(translation.locale.clone(), translation);
+            }
+        }
+    }
+
+    pub(crate) fn get_locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub(crate) fn set_locale(&mut self, locale: &str) {
+        self.locale = locale.to_string();
+    }
+
+    pub(crate) fn set_fallback_locale(&mut self, locale: &str) {
+        self.fallback_locale = locale.to_string();
+    }
+
+    pub(crate) fn is_pseudo_localization(&self) -> bool {
+        self.pseudo_localization
+    }
+
+    pub(crate) fn set_pseudo_localization(&mut self, enabled: bool) {
+        self.pseudo_localization = enabled;
+    }
+
+    pub(crate) fn tr(&self, key: &str) -> String {
+        let message = self.lookup(key).unwrap_or(key);
+
+        if self.pseudo_localization {
+            pseudo_localize(message)
+        } else {
+            message.to_string()
+        }
+    }
+
+    /// Try "pt_BR", then "pt", then the fallback locale.
+    fn lookup(&self, key: &str) -> Option<&str> {
+        let language = self.locale.split(['_', '-']).next().unwrap_or_default();
+
+        [
+            self.locale.as_str(),
+            language,
+            self.fallback_locale.as_str(),
+        ]
+        .into_iter()
+        .filter_map(|locale| self.translations.get(locale))
+        .find_map(|translation| translation.get_message(key))
+    }
+}
+
+/// Accent letters and lengthen the text by about a third, keeping `{placeholders}` intact,
+/// so untranslated and truncated text stands out.
+fn pseudo_localize(text: &str) -> String {
+    let mut result = String::from("[");
+    let mut placeholder_depth = 0;
+    let mut letters: usize = 0;
+
+    for c in text.chars() {
+        match c {
+            '{' => placeholder_depth += 1,
+            '}' if placeholder_depth > 0 => placeholder_depth -= 1,
+            _ => {}
+        }
+
+        if placeholder_depth > 0 || c == '}' {
+            result.push(c);
+            continue;
+        }
+
+        if c.is_alphanumeric() {
+            letters += 1;
+        }
+        result.push(accented(c));
+    }
+
+    // Translations are often longer than the English text.
+    result.push_str(&"~".repeat(letters.div_ceil(3)));
+    result.push(']');
+
+    result
+}
+
+fn accented(c: char) -> char {
+    const PLAIN: &str = "abcdeghijklnoprstuwyzABCDEGHIJKLNOPRSTUWYZ";
+    const ACCENTED: &str = "åƀçđéĝĥîĵķļñöþŕšţûŵýžÅƁÇĐÉĜĤÎĴĶĻÑÖÞŔŠŢÛŴÝŽ";
+
+    PLAIN
+        .chars()
+        .position(|plain| plain == c)
+        .and_then(|i| ACCENTED.chars().nth(i))
+        .unwrap_or(c)
+}
+
+/// Rows of fields, with RFC 4180 quoting.
+fn parse_csv(source: &str) -> Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("Unterminated quoted field in CSV");
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
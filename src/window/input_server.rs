@@ -11,6 +11,8 @@ pub enum InputEvent {
     MouseMotion(MouseMotion),
     MouseScroll(MouseScroll),
     Key(Key),
+    /// The window gained (true) or lost (false) keyboard focus.
+    Focus(bool),
     Invalid,
 }
 
@@ -41,12 +43,50 @@ pub struct MouseMotion {
     consumed: bool,
 }
 
+impl Key {
+    pub fn new(key_code: KeyCode, pressed: bool) -> Self {
+        Self { key_code, pressed }
+    }
+}
+
+impl MouseButton {
+    pub fn new(button: winit::event::MouseButton, pressed: bool, position: (f32, f32)) -> Self {
+        Self {
+            button,
+            pressed,
+            position,
+            consumed: false,
+        }
+    }
+}
+
+impl MouseScroll {
+    pub fn new(delta: f32) -> Self {
+        Self {
+            delta,
+            consumed: false,
+        }
+    }
+}
+
+impl MouseMotion {
+    pub fn new(delta: (f32, f32), position: (f32, f32)) -> Self {
+        Self {
+            delta,
+            position,
+            consumed: false,
+        }
+    }
+}
+
 pub struct InputServer {
     /// Track current mouse position.
     pub(crate) mouse_position: (f32, f32),
     pub(crate) input_events: Vec<InputEvent>,
     cursor_captured: bool,
     cursor_state_changed: bool,
+    focused: bool,
+    minimized: bool,
 }
 
 impl InputServer {
@@ -56,9 +96,25 @@ impl InputServer {
             input_events: Vec::new(),
             cursor_captured: false,
             cursor_state_changed: false,
+            focused: true,
+            minimized: false,
         }
     }
 
+    /// Whether the window has keyboard focus.
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Whether the window is minimized or fully hidden by other windows.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    pub(crate) fn set_minimized(&mut self, minimized: bool) {
+        self.minimized = minimized;
+    }
+
     /// Capture or release cursor.
     pub fn set_cursor_capture(&mut self, capture: bool) {
         self.cursor_captured = capture;
@@ -75,6 +131,18 @@ impl InputServer {
         }
     }
 
+    /// Queue a synthetic event, e.g. from a test, as if it came from the window.
+    /// It's delivered by the next `World::input`.
+    pub fn inject_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::MouseMotion(motion) => self.mouse_position = motion.position,
+            InputEvent::Focus(focused) => self.focused = focused,
+            _ => {}
+        }
+
+        self.input_events.push(event);
+    }
+
     /// Handle input events.
     pub fn prepare_input_event(&mut self, window: &Window, event: &WindowEvent) {
         self.input_events.clear();
@@ -92,6 +160,11 @@ impl InputServer {
                     pressed: event.state == ElementState::Pressed,
                 },
             },
+            WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+
+                InputEvent::Focus(*focused)
+            }
             WindowEvent::MouseWheel { delta, .. } => {
                 let scroll = match delta {
                     // I'm assuming a line is about 100 pixels.
@@ -1,3 +1,5 @@
 pub(crate) mod input_server;
+pub(crate) mod window_server;
 
 pub use input_server::*;
+pub use window_server::*;
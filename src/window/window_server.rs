@@ -0,0 +1,194 @@
+use cgmath::Vector2;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window};
+
+/// A display mode a monitor supports in exclusive fullscreen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VideoMode {
+    /// In physical pixels.
+    pub size: Vector2<u32>,
+    pub bit_depth: u16,
+    /// In Hz.
+    pub refresh_rate: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    /// Current resolution in physical pixels.
+    pub size: Vector2<u32>,
+    /// Top left corner on the desktop, in physical pixels.
+    pub position: Vector2<i32>,
+    /// Current refresh rate in Hz. None if the platform doesn't report it.
+    pub refresh_rate: Option<f32>,
+    /// Physical pixels per logical pixel. 1.0 is 96 DPI.
+    pub scale_factor: f64,
+    pub video_modes: Vec<VideoMode>,
+}
+
+impl MonitorInfo {
+    pub fn get_dpi(&self) -> f32 {
+        (self.scale_factor * 96.0) as f32
+    }
+}
+
+/// How the window covers the screen. Monitors and video modes are indices into
+/// [`WindowServer::get_monitors`] and [`MonitorInfo::video_modes`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// A borderless window covering the monitor, the current one if None.
+    BorderlessFullscreen { monitor: Option<usize> },
+    /// Take over the monitor and switch it to the video mode.
+    ExclusiveFullscreen { monitor: usize, video_mode: usize },
+}
+
+/// Monitors and the window's display mode. Empty when there's no window, e.g. headless.
+pub struct WindowServer {
+    monitors: Vec<MonitorInfo>,
+    /// Same order as `monitors`.
+    handles: Vec<MonitorHandle>,
+    current_monitor: Option<usize>,
+    primary_monitor: Option<usize>,
+    window_mode: WindowMode,
+    window_mode_changed: bool,
+    monitors_changed: bool,
+}
+
+impl Default for WindowServer {
+    fn default() -> Self {
+        Self {
+            monitors: Vec::new(),
+            handles: Vec::new(),
+            current_monitor: None,
+            primary_monitor: None,
+            window_mode: WindowMode::Windowed,
+            window_mode_changed: false,
+            monitors_changed: true,
+        }
+    }
+}
+
+impl WindowServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_monitors(&self) -> &[MonitorInfo] {
+        &self.monitors
+    }
+
+    /// The monitor the window is mostly on.
+    pub fn get_current_monitor(&self) -> Option<usize> {
+        self.current_monitor
+    }
+
+    pub fn get_primary_monitor(&self) -> Option<usize> {
+        self.primary_monitor
+    }
+
+    /// Refresh rate the window is presented at, in Hz. Use it to cap the frame rate,
+    /// see [`crate::core::Engine::set_frame_limit`].
+    pub fn get_refresh_rate(&self) -> Option<f32> {
+        if let WindowMode::ExclusiveFullscreen {
+            monitor,
+            video_mode,
+        } = self.window_mode
+        {
+            let mode = self.monitors.get(monitor)?.video_modes.get(video_mode)?;
+            return Some(mode.refresh_rate);
+        }
+
+        self.monitors.get(self.current_monitor?)?.refresh_rate
+    }
+
+    pub fn get_window_mode(&self) -> WindowMode {
+        self.window_mode
+    }
+
+    /// Applied to the window at the start of the next frame. Invalid monitors or video
+    /// modes fall back to windowed.
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        self.window_mode = mode;
+
+        self.window_mode_changed = true;
+    }
+
+    /// Enumerate the monitors again, e.g. after the window moved or one was plugged in.
+    pub(crate) fn refresh_monitors(&mut self) {
+        self.monitors_changed = true;
+    }
+
+    pub fn update(&mut self, window: &Window) {
+        if self.monitors_changed {
+            self.handles = window.available_monitors().collect();
+            self.monitors = self.handles.iter().map(monitor_info).collect();
+
+            let find = |handle: Option<MonitorHandle>| {
+                handle.and_then(|handle| self.handles.iter().position(|h| *h == handle))
+            };
+            self.current_monitor = find(window.current_monitor());
+            self.primary_monitor = find(window.primary_monitor());
+
+            self.monitors_changed = false;
+        }
+
+        if self.window_mode_changed {
+            let fullscreen = self.to_fullscreen(self.window_mode);
+            if fullscreen.is_none() && self.window_mode != WindowMode::Windowed {
+                log::warn!("Invalid window mode {:?}, going windowed", self.window_mode);
+                self.window_mode = WindowMode::Windowed;
+            }
+
+            window.set_fullscreen(fullscreen);
+
+            self.window_mode_changed = false;
+            self.monitors_changed = true;
+        }
+    }
+
+    fn to_fullscreen(&self, mode: WindowMode) -> Option<Fullscreen> {
+        match mode {
+            WindowMode::Windowed => None,
+            WindowMode::BorderlessFullscreen { monitor: None } => {
+                Some(Fullscreen::Borderless(None))
+            }
+            WindowMode::BorderlessFullscreen {
+                monitor: Some(monitor),
+            } => Some(Fullscreen::Borderless(Some(
+                self.handles.get(monitor)?.clone(),
+            ))),
+            WindowMode::ExclusiveFullscreen {
+                monitor,
+                video_mode,
+            } => {
+                let handle = self.handles.get(monitor)?;
+                Some(Fullscreen::Exclusive(handle.video_modes().nth(video_mode)?))
+            }
+        }
+    }
+}
+
+fn monitor_info(handle: &MonitorHandle) -> MonitorInfo {
+    let size = handle.size();
+    let position = handle.position();
+
+    MonitorInfo {
+        name: handle.name(),
+        size: Vector2::new(size.width, size.height),
+        position: Vector2::new(position.x, position.y),
+        refresh_rate: handle
+            .refresh_rate_millihertz()
+            .map(|millihertz| millihertz as f32 / 1000.0),
+        scale_factor: handle.scale_factor(),
+        video_modes: handle
+            .video_modes()
+            .map(|mode| VideoMode {
+                size: Vector2::new(mode.size().width, mode.size().height),
+                bit_depth: mode.bit_depth(),
+                refresh_rate: mode.refresh_rate_millihertz() as f32 / 1000.0,
+            })
+            .collect(),
+    }
+}
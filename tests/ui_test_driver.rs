@@ -0,0 +1,32 @@
+use cgmath::Vector2;
+use eureka::core::UiTestDriver;
+use eureka::scene::{AsNodeUi, Button, UiEventKind};
+
+#[test]
+fn click_button() {
+    // Needs a GPU adapter, which CI machines may not have.
+    let Ok(mut driver) = UiTestDriver::new(320, 240) else {
+        eprintln!("No adapter, skipping");
+        return;
+    };
+
+    let mut button = Button::new("OK");
+    button.set_position(Vector2::new(100.0, 100.0));
+    let button = driver.add_node(button, None);
+
+    assert_eq!(driver.find_node("OK"), Some(button));
+
+    driver.click_node(button).unwrap();
+    driver.step();
+
+    let kinds: Vec<_> = driver
+        .take_ui_events()
+        .into_iter()
+        .filter(|event| event.node == button && event.name == "OK")
+        .map(|event| event.kind)
+        .collect();
+
+    assert!(kinds.contains(&UiEventKind::FocusGained));
+    assert!(kinds.contains(&UiEventKind::Pressed));
+    assert_eq!(driver.app.get_world().get_ui_focus(), Some(button));
+}